@@ -1,49 +1,85 @@
 pub type NodeId = usize;
 
 #[derive(Debug, Clone)]
-pub(super) struct BtreeNode {
+pub(crate) struct BtreeNode<V> {
     // Node unique id
-    pub(super) id: NodeId,
+    pub(crate) id: NodeId,
 
     // Number of keys currently stored in the node
-    pub(super) n: usize,
+    pub(crate) n: usize,
 
     // Indicator of the internal and leaf nodes
-    pub(super) is_leaf: bool,
+    pub(crate) is_leaf: bool,
 
-    // Node keys in monotonically increasing order key[i] <= key[i + 1]
-    pub(super) keys: Vec<u64>,
+    // Node keys in monotonically increasing order key[i] <= key[i + 1].
+    // Keys are kept apart from the values and children, a binary search over them
+    // only touches the cache lines of the keys.
+    // Internal nodes only hold separators, keys[i] is the smallest key of children[i + 1].
+    pub(crate) keys: Vec<u64>,
+
+    // Values stored with the keys in leaves, values[i] belongs to keys[i]
+    pub(crate) values: Vec<V>,
 
     // Node (number_of_keys + 1) pointers to the children
-    pub(super) children: Vec<NodeId>,
+    pub(crate) children: Vec<NodeId>,
+
+    // Next leaf in key order, leaves form a chain for sequential scans
+    pub(crate) next: Option<NodeId>,
 }
 
-impl BtreeNode {
-    pub(super) fn find_key_index(&self, key: u64) -> Option<usize> {
-        self.keys.iter().position(|&x| x == key)
+impl<V: Copy + Default> BtreeNode<V> {
+    /// Empty node with the slots for the minimum degree, values for a leaf,
+    /// children for an internal node
+    pub(crate) fn new(id: NodeId, t: usize, is_leaf: bool) -> Self {
+        let mut node = BtreeNode {
+            id,
+            n: 0,
+            is_leaf,
+            keys: vec![0; 2 * t - 1],
+            values: vec![],
+            children: vec![],
+            next: None,
+        };
+        if is_leaf {
+            node.values.resize(2 * t - 1, V::default());
+        } else {
+            node.children.resize(2 * t, 0);
+        }
+        node
     }
+}
 
-    pub(super) fn find_child_index(&self, key: u64) -> usize {
-        let mut child_index = 0;
+impl<V> BtreeNode<V> {
+    /// Index of the first entry of the key, binary search
+    pub(crate) fn find_key_index(&self, key: u64) -> Option<usize> {
+        // Slots past n hold stale keys
+        let k = self.keys[..self.n].partition_point(|&x| x < key);
 
-        while child_index < self.n && self.keys[child_index] < key {
-            child_index += 1;
-        }
+        (k < self.n && self.keys[k] == key).then_some(k)
+    }
+
+    /// Child of an internal node whose subtree holds the key, binary search
+    pub(crate) fn find_child_index(&self, key: u64) -> usize {
+        self.keys[..self.n].partition_point(|&x| x <= key)
+    }
 
-        child_index
+    /// Leftmost child of an internal node whose subtree may hold the key,
+    /// entries of a duplicate key may continue in the following children
+    pub(crate) fn find_first_child_index(&self, key: u64) -> usize {
+        self.keys[..self.n].partition_point(|&x| x < key)
     }
 }
 
 #[derive(Debug)]
-pub(super) struct Arena {
+pub(crate) struct Arena<V> {
     // All nodes in the tree
-    pub(super) nodes: Vec<BtreeNode>,
+    pub(crate) nodes: Vec<BtreeNode<V>>,
 
     // List of free node ids which can be reused
     free_list: Vec<NodeId>,
 }
 
-impl Arena {
+impl<V: Copy + Default> Arena<V> {
     pub fn new() -> Self {
         Self {
             nodes: vec![],
@@ -51,31 +87,82 @@ impl Arena {
         }
     }
 
-    pub fn allocate_node(&mut self, t: usize) -> NodeId {
-        if let Some(id) = self.free_list.pop() {
-            return id;
+    /// Leaves get slots for the values, internal nodes for the children
+    pub fn allocate_node(&mut self, t: usize, is_leaf: bool) -> NodeId {
+        let id = self.free_list.pop().unwrap_or(self.nodes.len());
+        let node = BtreeNode::new(id, t, is_leaf);
+        match self.nodes.get_mut(id) {
+            Some(slot) => *slot = node,
+            None => self.nodes.push(node),
         }
-
-        let id = self.nodes.len();
-        self.nodes.push(BtreeNode {
-            id,
-            n: 0,
-            is_leaf: true,
-            keys: vec![],
-            children: vec![],
-        });
-        self.nodes[id].keys.resize(2 * t - 1, 0);
-        self.nodes[id].children.resize(2 * t, 0);
         id
     }
 
-    pub fn deallocate_node(&mut self, id: NodeId, t: usize) {
+    /// Number of deallocated nodes waiting for reuse
+    pub fn free_count(&self) -> usize {
+        self.free_list.len()
+    }
+
+    /// The slots of the node are released, a reused id gets new ones
+    pub fn deallocate_node(&mut self, id: NodeId) {
         self.free_list.push(id);
-        self.nodes[id].n = 0;
-        self.nodes[id].is_leaf = true;
-        self.nodes[id].keys.clear();
-        self.nodes[id].keys.resize(2 * t - 1, 0);
-        self.nodes[id].children.clear();
-        self.nodes[id].children.resize(2 * t, 0);
+        let node = &mut self.nodes[id];
+        node.n = 0;
+        node.keys = vec![];
+        node.values = vec![];
+        node.children = vec![];
+        node.next = None;
+    }
+
+    /// Bytes held by the arena: the node table, the key, value and child slots
+    /// of the nodes and the free list, by their capacity
+    pub fn memory_usage(&self) -> usize {
+        let slots: usize = self
+            .nodes
+            .iter()
+            .map(|node| {
+                node.keys.capacity() * size_of::<u64>()
+                    + node.values.capacity() * size_of::<V>()
+                    + node.children.capacity() * size_of::<NodeId>()
+            })
+            .sum();
+
+        self.nodes.capacity() * size_of::<BtreeNode<V>>()
+            + slots
+            + self.free_list.capacity() * size_of::<NodeId>()
+    }
+
+    /// Move the nodes into the holes of the deallocated ones, keeping their order,
+    /// and release the memory of the holes. Children and leaf chain links are remapped.
+    /// Returns the new id of each old id, `None` for a deallocated one.
+    pub fn compact(&mut self) -> Vec<Option<NodeId>> {
+        let mut translation = vec![Some(0); self.nodes.len()];
+        for id in self.free_list.drain(..) {
+            translation[id] = None;
+        }
+        for (next_id, new_id) in translation.iter_mut().flatten().enumerate() {
+            *new_id = next_id;
+        }
+
+        let mut old_id = 0;
+        self.nodes.retain(|_| {
+            old_id += 1;
+            translation[old_id - 1].is_some()
+        });
+        for (id, node) in self.nodes.iter_mut().enumerate() {
+            node.id = id;
+            if !node.is_leaf {
+                // Slots past n + 1 hold stale children
+                for child in node.children[..=node.n].iter_mut() {
+                    *child = translation[*child].unwrap();
+                }
+                node.children[node.n + 1..].fill(0);
+            }
+            node.next = node.next.map(|next| translation[next].unwrap());
+        }
+
+        self.nodes.shrink_to_fit();
+        self.free_list.shrink_to_fit();
+        translation
     }
 }
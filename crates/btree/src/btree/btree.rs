@@ -1,60 +1,147 @@
 use crate::btree::arena::{Arena, NodeId};
 
-/// BTree
-/// - Node keys separate the ranges of keys in each subtree.
+/// Internal nodes from the root down to a leaf and the index of the child taken in each
+pub(crate) type Path = Vec<(NodeId, usize)>;
+
+/// BTree, in the B+tree variant
+/// - Keys and their values are stored in the leaves.
+///
+/// - Internal nodes only store separator keys, which separate the ranges of keys
+///   in each subtree. The keys of children[i + 1] are greater than or equal to keys[i].
+///
+/// - Each leaf links to the next leaf in key order, so scans walk the leaf chain
+///   without descending from the root again.
 ///
 /// - All leaves have the same depths, which is the tree's height.
 ///
 /// - Nodes have minimum and maximum bounds on the number of keys they can contain.
-///     We call it a minimum degree of the tree and assign it to t variable.
+///   We call it a minimum degree of the tree and assign it to t variable.
 ///
 /// - Every node other than the root must have at least (t - 1) keys.
-///     This means that every internal node has at least (t) children.
+///   This means that every internal node has at least (t) children.
 ///
 /// - Every node may contain maximum (2 * t - 1) keys.
-///     This means that every node has maximum (2 * t) children.
-///     We say that the node is full if it contains exactly (2 * t - 1) keys.
+///   This means that every node has maximum (2 * t) children.
+///   We say that the node is full if it contains exactly (2 * t - 1) keys.
 ///
 /// - The higher is (t) of the three, the smaller is its height.
 ///
 /// - The number of disk accesses required for most operations on a BTree
-///     is proportional to the height of the tree.
+///   is proportional to the height of the tree.
+///
+/// - Each key carries a value of type V, e.g. the location of a record.
+///
+/// - A key may have several entries when added with `insert_duplicate`, e.g. a field
+///   value of a non-unique index mapped to many document ids. Entries of a key are kept
+///   in insertion order and may span several leaves, so separators only bound the keys
+///   of the subtrees: keys of children[i] <= keys[i] <= keys of children[i + 1].
 ///
 
 #[derive(Debug)]
-pub struct Btree {
+pub struct Btree<V> {
     // Minimum and maximum bounds on the number of keys (minimum degree, branching factor)
-    pub(super) t: usize,
+    pub(crate) t: usize,
 
     // Arena for tree nodes
-    pub(super) arena: Arena,
+    pub(crate) arena: Arena<V>,
 
     // Root of the tree
-    pub(super) root_id: NodeId,
+    pub(crate) root_id: NodeId,
+
+    // Number of keys stored in the tree
+    pub(crate) len: usize,
 }
 
-impl Btree {
+impl<V: Copy + Default> Btree<V> {
     pub fn new(minimum_degree: usize) -> Self {
         let mut arena = Arena::new();
-        let id = arena.allocate_node(minimum_degree);
+        let id = arena.allocate_node(minimum_degree, true);
 
         Self {
             t: minimum_degree,
             arena,
             root_id: id,
+            len: 0,
         }
     }
 
-    pub(super) fn is_root(&self, id: NodeId) -> bool {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// All keys and their values in key order
+    pub fn entries(&self) -> Vec<(u64, V)> {
+        let mut entries = Vec::with_capacity(self.len);
+        entries.extend(self.range(..));
+        entries
+    }
+
+    /// Bytes held by the nodes of the tree, deallocated nodes included
+    /// until `shrink_to_fit`
+    pub fn memory_usage(&self) -> usize {
+        self.arena.memory_usage()
+    }
+
+    /// Release the memory of the deallocated nodes, e.g. after mass deletions.
+    /// Node ids change, returns the new id of each old id, `None` for a deallocated one.
+    ///
+    /// O(n) CPU time
+    pub fn shrink_to_fit(&mut self) -> Vec<Option<NodeId>> {
+        let translation = self.arena.compact();
+        self.root_id = translation[self.root_id].unwrap();
+        translation
+    }
+
+    /// Leaf with the smallest keys, the start of the leaf chain
+    pub(crate) fn first_leaf(&self) -> NodeId {
+        let mut id = self.root_id;
+
+        while !self.arena.nodes[id].is_leaf {
+            id = self.arena.nodes[id].children[0];
+        }
+
+        id
+    }
+
+    /// Leaf whose key range holds the key
+    pub(crate) fn find_leaf(&self, key: u64) -> NodeId {
+        let mut id = self.root_id;
+
+        while !self.arena.nodes[id].is_leaf {
+            let node = &self.arena.nodes[id];
+            id = node.children[node.find_child_index(key)];
+        }
+
+        id
+    }
+
+    /// Leftmost leaf which may hold the key, entries of the key may continue
+    /// in the following leaves
+    pub(crate) fn find_first_leaf(&self, key: u64) -> NodeId {
+        let mut id = self.root_id;
+
+        while !self.arena.nodes[id].is_leaf {
+            let node = &self.arena.nodes[id];
+            id = node.children[node.find_first_child_index(key)];
+        }
+
+        id
+    }
+
+    pub(crate) fn is_root(&self, id: NodeId) -> bool {
         id == self.root_id
     }
 
-    pub(super) fn is_node_full(&self, id: NodeId) -> bool {
+    pub(crate) fn is_node_full(&self, id: NodeId) -> bool {
         let node = &self.arena.nodes[id];
         node.n == 2 * self.t - 1
     }
 
-    pub(super) fn is_node_underflow(&self, id: NodeId) -> bool {
+    pub(crate) fn is_node_underflow(&self, id: NodeId) -> bool {
         if self.is_root(id) {
             return false;
         }
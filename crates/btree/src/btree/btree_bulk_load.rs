@@ -0,0 +1,123 @@
+use crate::btree::arena::{Arena, NodeId};
+use crate::btree::btree::Btree;
+
+/// BTree bulk load implementation
+impl<V: Copy + Default> Btree<V> {
+    /// Build a tree from entries in ascending key order, bottom-up.
+    /// Leaves are filled to (2 * t - 1) keys and internal nodes to (2 * t) children,
+    /// only the last node of each level may be split with its left neighbour to keep
+    /// the minimum. Entries out of order are inserted one by one after the build.
+    ///
+    /// O(n) CPU time for sorted input
+    pub fn bulk_load<I: IntoIterator<Item = (u64, V)>>(minimum_degree: usize, entries: I) -> Self {
+        let t = minimum_degree;
+        let mut arena = Arena::new();
+        let mut leaves = vec![arena.allocate_node(t, true)];
+        let mut unordered = Vec::new();
+        let mut len = 0;
+
+        for (key, value) in entries {
+            let mut leaf_id = leaves[leaves.len() - 1];
+            // Only the first leaf is ever empty, the others are created for a key
+            let n = arena.nodes[leaf_id].n;
+            if n > 0 && key <= arena.nodes[leaf_id].keys[n - 1] {
+                unordered.push((key, value));
+                continue;
+            }
+
+            if arena.nodes[leaf_id].n == 2 * t - 1 {
+                let next_id = arena.allocate_node(t, true);
+                arena.nodes[leaf_id].next = Some(next_id);
+                leaves.push(next_id);
+                leaf_id = next_id;
+            }
+            let leaf = &mut arena.nodes[leaf_id];
+            leaf.keys[leaf.n] = key;
+            leaf.values[leaf.n] = value;
+            leaf.n += 1;
+            len += 1;
+        }
+        Self::balance_last_leaf(&mut arena, &leaves, t);
+
+        // Build the internal levels from the smallest key of each node
+        let mut level: Vec<(NodeId, u64)> = leaves
+            .iter()
+            .map(|id| (*id, arena.nodes[*id].keys[0]))
+            .collect();
+        while level.len() > 1 {
+            level = Self::build_level(&mut arena, &level, t);
+        }
+
+        let mut tree = Self {
+            t,
+            arena,
+            root_id: level[0].0,
+            len,
+        };
+        for (key, value) in unordered {
+            tree.insert(key, value);
+        }
+        tree
+    }
+
+    /// Move keys from the second last leaf into a last leaf below the minimum
+    fn balance_last_leaf(arena: &mut Arena<V>, leaves: &[NodeId], t: usize) {
+        let [.., left_id, right_id] = *leaves else {
+            return;
+        };
+        let right_n = arena.nodes[right_id].n;
+        if right_n >= t - 1 {
+            return;
+        }
+
+        let moved = t - 1 - right_n;
+        let left_n = arena.nodes[left_id].n - moved;
+        let (keys, values) = {
+            let left = &arena.nodes[left_id];
+            (
+                left.keys[left_n..left_n + moved].to_vec(),
+                left.values[left_n..left_n + moved].to_vec(),
+            )
+        };
+        let right = &mut arena.nodes[right_id];
+        right.keys.copy_within(0..right_n, moved);
+        right.values.copy_within(0..right_n, moved);
+        right.keys[..moved].copy_from_slice(&keys);
+        right.values[..moved].copy_from_slice(&values);
+        right.n += moved;
+        arena.nodes[left_id].n = left_n;
+    }
+
+    /// Parents of the nodes of a level, with the smallest key of their subtrees
+    fn build_level(arena: &mut Arena<V>, level: &[(NodeId, u64)], t: usize) -> Vec<(NodeId, u64)> {
+        let mut sizes = vec![2 * t; level.len() / (2 * t)];
+        match level.len() % (2 * t) {
+            0 => {}
+            rest if rest >= t || sizes.is_empty() => sizes.push(rest),
+            rest => {
+                // Split the last two groups so the last one has t children
+                let last = sizes.len() - 1;
+                sizes[last] = 2 * t + rest - t;
+                sizes.push(t);
+            }
+        }
+
+        let mut parents = Vec::with_capacity(sizes.len());
+        let mut children = level.iter();
+        for size in sizes {
+            let id = arena.allocate_node(t, false);
+            let node = &mut arena.nodes[id];
+            let mut smallest = 0;
+            for (i, (child_id, child_smallest)) in children.by_ref().take(size).enumerate() {
+                node.children[i] = *child_id;
+                match i {
+                    0 => smallest = *child_smallest,
+                    _ => node.keys[i - 1] = *child_smallest,
+                }
+            }
+            node.n = size - 1;
+            parents.push((id, smallest));
+        }
+        parents
+    }
+}
@@ -0,0 +1,182 @@
+use crate::btree::arena::NodeId;
+use crate::btree::btree::Btree;
+
+/// Bounds the keys of a subtree must stay within, from the separators above it
+#[derive(Clone, Copy)]
+struct KeyBounds {
+    lower: Option<u64>,
+    upper: Option<u64>,
+}
+
+/// Btree invariant checks
+impl<V: Copy + Default> Btree<V> {
+    /// Verify the structure of the tree, returns the first violation found:
+    /// - keys of each node in order and within the separators above the node
+    /// - every node other than the root holds [t - 1, 2 * t - 1] keys,
+    ///   an internal root holds at least one key
+    /// - internal nodes have (n + 1) children, nodes keep their slots for the minimum degree
+    /// - all leaves at the same depth, chained in key order
+    /// - the length matches the entries and every node is either in the tree or free
+    ///
+    /// O(n) CPU time, meant for tests and debugging
+    pub fn check_invariants(&self) -> Result<(), String> {
+        let mut leaves = vec![];
+        let mut visited = vec![false; self.arena.nodes.len()];
+        let bounds = KeyBounds {
+            lower: None,
+            upper: None,
+        };
+        self.check_node(self.root_id, bounds, 0, &mut leaves, &mut visited)?;
+
+        // Leaves all have the depth of the first one
+        let depth = leaves[0].1;
+        if let Some((id, leaf_depth)) = leaves.iter().find(|(_, d)| *d != depth) {
+            return Err(format!(
+                "Leaf {} is at depth {}, expected {}",
+                id, leaf_depth, depth
+            ));
+        }
+
+        // The chain visits the leaves in the order of the tree
+        let mut chain = vec![];
+        let mut leaf = Some(self.first_leaf());
+        while let Some(id) = leaf {
+            if chain.len() == leaves.len() {
+                return Err(format!("Leaf chain is longer than {} leaves", leaves.len()));
+            }
+            chain.push(id);
+            leaf = self.arena.nodes[id].next;
+        }
+        if chain.iter().ne(leaves.iter().map(|(id, _)| id)) {
+            return Err(format!(
+                "Leaf chain {:?} does not match the leaves {:?}",
+                chain,
+                leaves.iter().map(|(id, _)| *id).collect::<Vec<_>>()
+            ));
+        }
+
+        let entries: usize = leaves.iter().map(|(id, _)| self.arena.nodes[*id].n).sum();
+        if entries != self.len {
+            return Err(format!(
+                "Tree length {} does not match its {} entries",
+                self.len, entries
+            ));
+        }
+        let node_count = visited.iter().filter(|v| **v).count();
+        if node_count + self.arena.free_count() != self.arena.nodes.len() {
+            return Err(format!(
+                "{} nodes in the tree and {} free nodes do not add up to {} allocated",
+                node_count,
+                self.arena.free_count(),
+                self.arena.nodes.len()
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn check_node(
+        &self,
+        id: NodeId,
+        bounds: KeyBounds,
+        depth: usize,
+        leaves: &mut Vec<(NodeId, usize)>,
+        visited: &mut [bool],
+    ) -> Result<(), String> {
+        let t = self.t;
+        let Some(node) = self.arena.nodes.get(id) else {
+            return Err(format!("Node {} is not allocated", id));
+        };
+        if std::mem::replace(&mut visited[id], true) {
+            return Err(format!("Node {} is reachable more than once", id));
+        }
+
+        if node.id != id {
+            return Err(format!("Node {} is stored at {}", node.id, id));
+        }
+        if node.keys.len() != 2 * t - 1 {
+            return Err(format!(
+                "Node {} has {} key slots, expected {}",
+                id,
+                node.keys.len(),
+                2 * t - 1
+            ));
+        }
+        let min = match (self.is_root(id), node.is_leaf) {
+            (true, true) => 0,
+            (true, false) => 1,
+            (false, _) => t - 1,
+        };
+        if node.n < min || node.n > 2 * t - 1 {
+            return Err(format!(
+                "Node {} has {} keys, expected [{}, {}]",
+                id,
+                node.n,
+                min,
+                2 * t - 1
+            ));
+        }
+
+        let keys = &node.keys[..node.n];
+        if let Some(k) = keys.windows(2).position(|pair| pair[0] > pair[1]) {
+            return Err(format!(
+                "Node {} keys {} and {} are out of order",
+                id,
+                keys[k],
+                keys[k + 1]
+            ));
+        }
+        let out_of_bounds = keys.iter().find(|key| {
+            bounds.lower.is_some_and(|lower| **key < lower)
+                || bounds.upper.is_some_and(|upper| **key > upper)
+        });
+        if let Some(key) = out_of_bounds {
+            return Err(format!(
+                "Node {} key {} is outside of the separators [{:?}, {:?}]",
+                id, key, bounds.lower, bounds.upper
+            ));
+        }
+
+        if node.is_leaf {
+            if node.values.len() != 2 * t - 1 || !node.children.is_empty() {
+                return Err(format!(
+                    "Leaf {} has {} value slots and {} children",
+                    id,
+                    node.values.len(),
+                    node.children.len()
+                ));
+            }
+            leaves.push((id, depth));
+            return Ok(());
+        }
+
+        if node.children.len() != 2 * t || !node.values.is_empty() {
+            return Err(format!(
+                "Internal node {} has {} child slots and {} values",
+                id,
+                node.children.len(),
+                node.values.len()
+            ));
+        }
+        if node.next.is_some() {
+            return Err(format!("Internal node {} is in the leaf chain", id));
+        }
+        for (i, c_id) in node.children[..=node.n].iter().enumerate() {
+            let child_bounds = KeyBounds {
+                lower: if i == 0 {
+                    bounds.lower
+                } else {
+                    Some(keys[i - 1])
+                },
+                upper: if i == node.n {
+                    bounds.upper
+                } else {
+                    Some(keys[i])
+                },
+            };
+            self.check_node(*c_id, child_bounds, depth + 1, leaves, visited)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+use crate::btree::arena::{BtreeNode, NodeId};
+use crate::btree::btree::{Btree, Path};
+
+/// Bidirectional cursor over the entries of a tree in key order, see `Btree::seek`.
+///
+/// The cursor rests between two entries, `next` returns the entry after it
+/// and moves forward, `prev` returns the entry before it and moves back.
+/// It keeps the path to its leaf, so it moves to either neighbour leaf
+/// without descending from the root again.
+pub struct Cursor<'a, V> {
+    tree: &'a Btree<V>,
+    path: Path,
+    // Current leaf, the cursor rests before its entry k, k == n after the last entry
+    leaf: NodeId,
+    k: usize,
+}
+
+/// Btree cursor implementation
+impl<V: Copy + Default> Btree<V> {
+    /// Cursor before the first entry
+    pub fn iter(&self) -> Cursor<'_, V> {
+        let mut cursor = Cursor {
+            tree: self,
+            path: vec![],
+            leaf: self.root_id,
+            k: 0,
+        };
+        cursor.descend(|_| 0);
+        cursor
+    }
+
+    /// Cursor before the first entry whose key is greater than or equal to the key,
+    /// the first entry of a duplicate key
+    ///
+    /// O(h) disk access
+    pub fn seek(&self, key: u64) -> Cursor<'_, V> {
+        let mut cursor = Cursor {
+            tree: self,
+            path: vec![],
+            leaf: self.root_id,
+            k: 0,
+        };
+        cursor.descend(|node| node.find_first_child_index(key));
+        let node = &self.arena.nodes[cursor.leaf];
+        cursor.k = node.keys[..node.n].partition_point(|&x| x < key);
+        cursor
+    }
+
+    /// Cursor after the last entry, `prev` walks the entries in descending order
+    pub fn seek_end(&self) -> Cursor<'_, V> {
+        let mut cursor = Cursor {
+            tree: self,
+            path: vec![],
+            leaf: self.root_id,
+            k: 0,
+        };
+        cursor.descend(|node| node.n);
+        cursor.k = self.arena.nodes[cursor.leaf].n;
+        cursor
+    }
+}
+
+impl<V: Copy + Default> Cursor<'_, V> {
+    /// Walk down from the current node to a leaf, taking the chosen child of each node
+    fn descend(&mut self, child_index: impl Fn(&BtreeNode<V>) -> usize) {
+        let nodes = &self.tree.arena.nodes;
+
+        while !nodes[self.leaf].is_leaf {
+            let c_k = child_index(&nodes[self.leaf]);
+            self.path.push((self.leaf, c_k));
+            self.leaf = nodes[self.leaf].children[c_k];
+        }
+    }
+
+    /// Move to the leaf before or after the current one, the cursor stays
+    /// where it is when there is none
+    fn move_leaf(&mut self, forward: bool) -> bool {
+        let tree = self.tree;
+        let nodes = &tree.arena.nodes;
+        let Some(level) = self.path.iter().rposition(|(id, c_k)| match forward {
+            true => *c_k < nodes[*id].n,
+            false => *c_k > 0,
+        }) else {
+            return false;
+        };
+
+        self.path.truncate(level + 1);
+        let (id, c_k) = self.path.pop().unwrap();
+        let c_k = if forward { c_k + 1 } else { c_k - 1 };
+        self.path.push((id, c_k));
+        self.leaf = nodes[id].children[c_k];
+        match forward {
+            true => {
+                self.descend(|_| 0);
+                self.k = 0;
+            }
+            false => {
+                self.descend(|node| node.n);
+                self.k = nodes[self.leaf].n;
+            }
+        }
+        true
+    }
+
+    /// Entry before the cursor, the cursor moves back over it
+    pub fn prev(&mut self) -> Option<(u64, V)> {
+        // Only an empty root leaf has no entries
+        while self.k == 0 {
+            if !self.move_leaf(false) {
+                return None;
+            }
+        }
+
+        self.k -= 1;
+        let node = &self.tree.arena.nodes[self.leaf];
+        Some((node.keys[self.k], node.values[self.k]))
+    }
+
+    /// Entry after the cursor without moving
+    pub fn peek_next(&self) -> Option<(u64, V)> {
+        let mut cursor = self.clone();
+        cursor.next()
+    }
+
+    /// Entry before the cursor without moving
+    pub fn peek_prev(&self) -> Option<(u64, V)> {
+        let mut cursor = self.clone();
+        cursor.prev()
+    }
+}
+
+impl<V> Clone for Cursor<'_, V> {
+    fn clone(&self) -> Self {
+        Self {
+            tree: self.tree,
+            path: self.path.clone(),
+            leaf: self.leaf,
+            k: self.k,
+        }
+    }
+}
+
+impl<V: Copy + Default> Iterator for Cursor<'_, V> {
+    type Item = (u64, V);
+
+    /// Entry after the cursor, the cursor moves forward over it
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.k == self.tree.arena.nodes[self.leaf].n {
+            if !self.move_leaf(true) {
+                return None;
+            }
+        }
+
+        let node = &self.tree.arena.nodes[self.leaf];
+        self.k += 1;
+        Some((node.keys[self.k - 1], node.values[self.k - 1]))
+    }
+}
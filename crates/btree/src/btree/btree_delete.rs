@@ -1,107 +1,90 @@
 use crate::btree::arena::NodeId;
-use crate::btree::btree::Btree;
+use crate::btree::btree::{Btree, Path};
 
 /// Btree delete implementation
-impl Btree {
-    pub fn delete(&mut self, key: u64) {
-        self.recursive_delete(self.root_id, key);
-
-        let nodes = &mut self.arena.nodes;
-
-        // Handle root shrinking
-        if nodes[self.root_id].n == 0 && !nodes[self.root_id].is_leaf {
-            let old_root = self.root_id;
-            self.root_id = nodes[self.root_id].children[0];
-            self.arena.deallocate_node(old_root, self.t);
-        }
+impl<V: Copy + Default> Btree<V> {
+    /// Delete the key, the first entry of a duplicate key.
+    /// Returns the removed value, `None` when the key is not in the tree.
+    ///
+    /// O(h) disk access
+    pub fn delete(&mut self, key: u64) -> Option<V> {
+        let (path, id, k) = self.find_entry(key, |_| true)?;
+        Some(self.delete_at(path, id, k))
     }
 
-    fn recursive_delete(&mut self, id: NodeId, key: u64) {
-        let pos = self.arena.nodes[id].find_key_index(key);
-
-        // We are in the leaf node
-        if self.arena.nodes[id].is_leaf {
-            if pos == None {
-                return;
-            }
-            let k = pos.unwrap();
-            let n = self.arena.nodes[id].n;
-            self.arena.nodes[id].keys.copy_within(k + 1..n, k);
-            self.arena.nodes[id].n -= 1;
-            return;
+    /// Path to the leaf holding the first entry of the key whose value matches,
+    /// the leaf and the index of the entry in it
+    fn find_entry(&self, key: u64, matches: impl Fn(&V) -> bool) -> Option<(Path, NodeId, usize)> {
+        let nodes = &self.arena.nodes;
+        let mut path = vec![];
+        let mut id = self.root_id;
+
+        while !nodes[id].is_leaf {
+            let c_k = nodes[id].find_first_child_index(key);
+            path.push((id, c_k));
+            id = nodes[id].children[c_k];
         }
+        let mut k = nodes[id].keys[..nodes[id].n].partition_point(|&x| x < key);
 
-        // We are in the internal node
-        match pos {
-            Some(k) => {
-                // Case 1: key is in the internal node
-                self.delete_from_internal_node(id, k);
-            }
-            None => {
-                // Case 2: key is not in this node, recurse to child
-                let mut c_k = self.arena.nodes[id].find_child_index(key);
-                let mut c_id = self.arena.nodes[id].children[c_k];
-
-                if self.is_node_underflow(c_id) {
-                    // Child has a minimum number of keys, need to fix before deletion
-                    self.fix_child(id, c_k);
-
-                    // After fixing, the key might have moved, so re-find the child
-                    c_k = self.arena.nodes[id].find_child_index(key);
-                    c_id = self.arena.nodes[id].children[c_k];
+        loop {
+            let node = &nodes[id];
+            while k < node.n && node.keys[k] == key {
+                if matches(&node.values[k]) {
+                    return Some((path, id, k));
                 }
-
-                self.recursive_delete(c_id, key);
+                k += 1;
+            }
+            if k < node.n {
+                return None;
             }
-        }
-    }
 
-    fn delete_from_internal_node(&mut self, id: NodeId, k: usize) {
-        let key = self.arena.nodes[id].keys[k];
-        let lc_id = self.arena.nodes[id].children[k]; // Left child
-        let rc_id = self.arena.nodes[id].children[k + 1]; // right child
-
-        if !self.is_node_underflow(lc_id) {
-            // Case 1a: left child has at least t keys
-            let predecessor = self.find_predecessor(lc_id);
-            self.arena.nodes[id].keys[k] = predecessor;
-            self.recursive_delete(lc_id, predecessor);
-        } else if !self.is_node_underflow(rc_id) {
-            // Case 1b: right child has at least t keys
-            let successor = self.find_successor(rc_id);
-            self.arena.nodes[id].keys[k] = successor;
-            self.recursive_delete(rc_id, successor);
-        } else {
-            // Case 1c: both children have t - 1 keys, merge them
-            self.merge_children(id, k);
-            self.recursive_delete(lc_id, key); // Key is now in the merged child
+            // The entries may continue in the next leaf, move the path to it
+            let (mut p_id, mut c_k) = path.pop()?;
+            while c_k == nodes[p_id].n {
+                (p_id, c_k) = path.pop()?;
+            }
+            path.push((p_id, c_k + 1));
+            id = nodes[p_id].children[c_k + 1];
+            while !nodes[id].is_leaf {
+                path.push((id, 0));
+                id = nodes[id].children[0];
+            }
+            k = 0;
         }
     }
 
-    fn find_predecessor(&self, p_id: NodeId) -> u64 {
-        // Find the maximum key in the subtree rooted at parent
-        let mut node_id = p_id;
-
-        while !self.arena.nodes[node_id].is_leaf {
-            // Rightmost child
-            node_id = self.arena.nodes[node_id].children[self.arena.nodes[node_id].n];
+    /// Remove the entry from the leaf, then fix the nodes left below the minimum
+    /// on the way up to the root. Returns the removed value.
+    fn delete_at(&mut self, path: Path, id: NodeId, k: usize) -> V {
+        let node = &mut self.arena.nodes[id];
+        let value = node.values[k];
+        node.keys.copy_within(k + 1..node.n, k);
+        node.values.copy_within(k + 1..node.n, k);
+        node.n -= 1;
+        self.len -= 1;
+
+        // A separator equal to the key may stay, it still separates the subtrees
+        let mut c_id = id;
+        for (p_id, c_k) in path.into_iter().rev() {
+            if self.arena.nodes[c_id].n >= self.t - 1 {
+                break;
+            }
+            self.fix_child(p_id, c_k);
+            c_id = p_id;
         }
 
-        // Last key in leaf
-        self.arena.nodes[node_id].keys[self.arena.nodes[node_id].n - 1]
+        self.shrink_root();
+        value
     }
 
-    fn find_successor(&self, p_id: NodeId) -> u64 {
-        // Find the minimum key in the subtree rooted at parent
-        let mut node_id = p_id;
-
-        while !self.arena.nodes[node_id].is_leaf {
-            // Leftmost child
-            node_id = self.arena.nodes[node_id].children[0];
+    /// Replace an internal root left without keys by its only child,
+    /// the tree gets one level lower
+    fn shrink_root(&mut self) {
+        while self.arena.nodes[self.root_id].n == 0 && !self.arena.nodes[self.root_id].is_leaf {
+            let old_root = self.root_id;
+            self.root_id = self.arena.nodes[old_root].children[0];
+            self.arena.deallocate_node(old_root);
         }
-
-        // First key in leaf
-        self.arena.nodes[node_id].keys[0]
     }
 
     fn fix_child(&mut self, p_id: NodeId, k: usize) {
@@ -142,15 +125,21 @@ impl Btree {
         let rc_n = nodes[rc_id].n;
         let lc_n = nodes[lc_id].n;
 
-        // Move parent key down to child
         nodes[rc_id].keys.copy_within(0..rc_n, 1);
-        nodes[rc_id].keys[0] = nodes[p_id].keys[k - 1];
+        if nodes[rc_id].is_leaf {
+            // Move left sibling's last entry to the child, it becomes the separator
+            nodes[rc_id].values.copy_within(0..rc_n, 1);
+            nodes[rc_id].keys[0] = nodes[lc_id].keys[lc_n - 1];
+            nodes[rc_id].values[0] = nodes[lc_id].values[lc_n - 1];
+            nodes[p_id].keys[k - 1] = nodes[rc_id].keys[0];
+        } else {
+            // Move parent key down to child
+            nodes[rc_id].keys[0] = nodes[p_id].keys[k - 1];
 
-        // Move left sibling's last key up to parent
-        nodes[p_id].keys[k - 1] = nodes[lc_id].keys[lc_n - 1];
+            // Move left sibling's last key up to parent
+            nodes[p_id].keys[k - 1] = nodes[lc_id].keys[lc_n - 1];
 
-        // Move left sibling's last child to the current child (if not leaf)
-        if !nodes[rc_id].is_leaf {
+            // Move left sibling's last child to the current child
             nodes[rc_id].children.copy_within(0..=rc_n, 1);
             nodes[rc_id].children[0] = nodes[lc_id].children[lc_n];
         }
@@ -168,15 +157,22 @@ impl Btree {
         let lc_n = nodes[lc_id].n;
         let rc_n = nodes[rc_id].n;
 
-        // Move parent key down to child
-        nodes[lc_id].keys[lc_n] = nodes[p_id].keys[k];
+        if nodes[lc_id].is_leaf {
+            // Move right sibling's first entry to the child, its next key becomes the separator
+            nodes[lc_id].keys[lc_n] = nodes[rc_id].keys[0];
+            nodes[lc_id].values[lc_n] = nodes[rc_id].values[0];
+            nodes[rc_id].keys.copy_within(1..rc_n, 0);
+            nodes[rc_id].values.copy_within(1..rc_n, 0);
+            nodes[p_id].keys[k] = nodes[rc_id].keys[0];
+        } else {
+            // Move parent key down to child
+            nodes[lc_id].keys[lc_n] = nodes[p_id].keys[k];
 
-        // Move right sibling's first key up to parent
-        nodes[p_id].keys[k] = nodes[rc_id].keys[0];
-        nodes[rc_id].keys.copy_within(1..rc_n, 0);
+            // Move right sibling's first key up to parent
+            nodes[p_id].keys[k] = nodes[rc_id].keys[0];
+            nodes[rc_id].keys.copy_within(1..rc_n, 0);
 
-        // Move right sibling's first child to the current child (if not leaf)
-        if !nodes[lc_id].is_leaf {
+            // Move right sibling's first child to the current child
             nodes[lc_id].children[lc_n + 1] = nodes[rc_id].children[0];
             nodes[rc_id].children.copy_within(1..=rc_n, 0);
         }
@@ -195,19 +191,26 @@ impl Btree {
         let lc_n = nodes[lc_id].n;
         let rc_n = nodes[rc_id].n;
 
-        // Move the parent key down to the left child
-        nodes[lc_id].keys[lc_n] = nodes[p_id].keys[k];
-
-        // Move all keys from right child to left
-        for i in 0..rc_n {
-            nodes[lc_id].keys[lc_n + 1 + i] = nodes[rc_id].keys[i];
-        }
+        if nodes[lc_id].is_leaf {
+            // Move all entries from right child to left, the separator is dropped
+            for i in 0..rc_n {
+                nodes[lc_id].keys[lc_n + i] = nodes[rc_id].keys[i];
+                nodes[lc_id].values[lc_n + i] = nodes[rc_id].values[i];
+            }
+            nodes[lc_id].next = nodes[rc_id].next;
+            nodes[lc_id].n = lc_n + rc_n;
+        } else {
+            // Move the parent key down to the left child
+            nodes[lc_id].keys[lc_n] = nodes[p_id].keys[k];
 
-        // Move all children from right child to left
-        if !nodes[lc_id].is_leaf {
+            // Move all keys and children from right child to left
+            for i in 0..rc_n {
+                nodes[lc_id].keys[lc_n + 1 + i] = nodes[rc_id].keys[i];
+            }
             for i in 0..=rc_n {
                 nodes[lc_id].children[lc_n + 1 + i] = nodes[rc_id].children[i];
             }
+            nodes[lc_id].n = lc_n + rc_n + 1;
         }
 
         // Remove the key and child pointer from the parent
@@ -215,12 +218,26 @@ impl Btree {
             nodes[p_id].keys[i] = nodes[p_id].keys[i + 1];
             nodes[p_id].children[i + 1] = nodes[p_id].children[i + 2];
         }
-
-        // Update nodes' key numbers
-        nodes[lc_id].n = lc_n + rc_n + 1;
         nodes[p_id].n -= 1;
 
         // Deallocate right child
-        self.arena.deallocate_node(rc_id, self.t);
+        self.arena.deallocate_node(rc_id);
+    }
+}
+
+/// Btree delete of the entries of a duplicate key
+impl<V: Copy + Default + PartialEq> Btree<V> {
+    /// Delete the entry of the key with the value, returns whether it was found
+    ///
+    /// O(h) disk access to find the first entry of the key
+    /// O(1) amortized per entry of the key before the deleted one
+    pub fn delete_entry(&mut self, key: u64, value: V) -> bool {
+        match self.find_entry(key, |v| *v == value) {
+            Some((path, id, k)) => {
+                self.delete_at(path, id, k);
+                true
+            }
+            None => false,
+        }
     }
 }
@@ -2,83 +2,91 @@ use crate::btree::arena::NodeId;
 use crate::btree::btree::Btree;
 
 /// BTree insert implementation
-impl Btree {
+impl<V: Copy + Default> Btree<V> {
+    /// Insert the key with its value, replacing the value of an existing key.
+    /// Returns the replaced value.
+    ///
     /// O(h) disk access
     /// O(md * h) = O(md * log.md(n)) CPU time
-    pub fn insert(&mut self, key: u64) {
+    pub fn insert(&mut self, key: u64, value: V) -> Option<V> {
+        // Replace in place, the descent below splits full nodes on the way
+        if let Some((id, k)) = self.search(key) {
+            let previous = self.arena.nodes[id].values[k];
+            self.arena.nodes[id].values[k] = value;
+            return Some(previous);
+        }
+
+        self.insert_entry(key, value);
+        None
+    }
+
+    /// Add an entry of the key after its existing entries, the key may repeat.
+    /// See `search_all` and `delete_entry` for the entries of a key.
+    ///
+    /// O(h) disk access
+    /// O(md * h) = O(md * log.md(n)) CPU time
+    pub fn insert_duplicate(&mut self, key: u64, value: V) {
+        self.insert_entry(key, value);
+    }
+
+    fn insert_entry(&mut self, key: u64, value: V) {
         if self.is_node_full(self.root_id) {
             let new_root_id = self.split_root();
-            self.recursive_insert(new_root_id, key);
+            self.recursive_insert(new_root_id, key, value);
         } else {
-            self.recursive_insert(self.root_id, key);
+            self.recursive_insert(self.root_id, key, value);
         }
+        self.len += 1;
     }
 
-    fn recursive_insert(&mut self, id: NodeId, key: u64) {
+    fn recursive_insert(&mut self, id: NodeId, key: u64, value: V) {
         if self.arena.nodes[id].is_leaf {
-            self.insert_into_leaf_node(id, key);
+            self.insert_into_leaf_node(id, key, value);
         } else {
-            self.insert_into_internal_node(id, key);
+            self.insert_into_internal_node(id, key, value);
         }
     }
 
-    fn insert_into_leaf_node(&mut self, id: NodeId, key: u64) {
-        let n = self.arena.nodes[id].n;
-
-        // inserting into a leaf
-        let mut pos = 0;
-
-        // find insertion position
-        while pos < n && self.arena.nodes[id].keys[pos] < key {
-            pos += 1;
-        }
+    fn insert_into_leaf_node(&mut self, id: NodeId, key: u64, value: V) {
+        let node = &mut self.arena.nodes[id];
+        let n = node.n;
 
-        // If the key is already in the leaf, do nothing
-        if self.arena.nodes[id].keys[pos] == key {
-            return;
-        }
+        // find insertion position, after the entries of a duplicate key
+        let pos = node.keys[..n].partition_point(|&x| x <= key);
 
         // shift keys and insert
-        for i in (pos..n).rev() {
-            self.arena.nodes[id].keys[i + 1] = self.arena.nodes[id].keys[i];
-        }
-        self.arena.nodes[id].keys[pos] = key;
-        self.arena.nodes[id].n += 1;
+        node.keys.copy_within(pos..n, pos + 1);
+        node.values.copy_within(pos..n, pos + 1);
+        node.keys[pos] = key;
+        node.values[pos] = value;
+        node.n += 1;
     }
 
-    fn insert_into_internal_node(&mut self, id: NodeId, key: u64) {
-        let n = self.arena.nodes[id].n;
-
+    fn insert_into_internal_node(&mut self, id: NodeId, key: u64, value: V) {
         // find the child where the key belongs
-        let mut pos = 0;
-
-        // find insertion position
-        while pos < n && key > self.arena.nodes[id].keys[pos] {
-            pos += 1;
-        }
+        let mut pos = self.arena.nodes[id].find_child_index(key);
 
         let c_id = self.arena.nodes[id].children[pos];
         if self.is_node_full(c_id) {
             // split the child if it is full
             self.split_child(id, pos);
-            if key > self.arena.nodes[id].keys[pos] {
+            if key >= self.arena.nodes[id].keys[pos] {
                 // does the key go into child[i] or child[i + 1]?
                 pos += 1;
             }
         }
 
         let c_id = self.arena.nodes[id].children[pos];
-        self.recursive_insert(c_id, key);
+        self.recursive_insert(c_id, key, value);
     }
 
     fn split_root(&mut self) -> NodeId {
         let t = self.t;
 
         // allocate the new root
-        let new_root_id = self.arena.allocate_node(t);
+        let new_root_id = self.arena.allocate_node(t, false);
 
         // set new root properties
-        self.arena.nodes[new_root_id].is_leaf = false;
         self.arena.nodes[new_root_id].n = 0;
         self.arena.nodes[new_root_id].children[0] = self.root_id;
 
@@ -89,12 +97,13 @@ impl Btree {
         new_root_id
     }
 
-    /// split creates a sibling node from a given node by splitting the node in two around a median.
-    /// split will split the child at md leaving the [0, t-1] keys
-    /// while moving the set of [t, t-1] keys to the sibling.
+    /// split creates a sibling node from a given node by splitting the node in two.
+    /// An internal child keeps the [0, t-1) keys, moves the [t, 2t-1) keys to the sibling
+    /// and the median key up to the parent.
+    /// A leaf child keeps the [0, t-1) keys and moves the [t-1, 2t-1) keys with their values
+    /// to the sibling, the first key of the sibling is copied up as the separator.
     fn split_child(&mut self, p_id: NodeId, k: usize) {
         let t = self.t;
-        let nc_id = self.arena.allocate_node(t); // new child
 
         // **************************
         // * Work on the child node *
@@ -103,46 +112,52 @@ impl Btree {
         // Get the child properties
         let c_id = self.arena.nodes[p_id].children[k];
         let is_leaf = self.arena.nodes[c_id].is_leaf;
-        let median_key = self.arena.nodes[c_id].keys[t - 1];
-
-        // Set up the new sibling node
-        self.arena.nodes[nc_id].is_leaf = is_leaf;
-        self.arena.nodes[nc_id].n = t - 1;
+        let nc_id = self.arena.allocate_node(t, is_leaf); // new child
+        let nodes = &mut self.arena.nodes;
 
-        // Copy the upper half of keys from the child to the new sibling
-        for i in 0..(t - 1) {
-            self.arena.nodes[nc_id].keys[i] = self.arena.nodes[c_id].keys[i + t];
-        }
+        let separator = if is_leaf {
+            // Copy the upper half of keys and values to the new sibling
+            for i in 0..t {
+                nodes[nc_id].keys[i] = nodes[c_id].keys[i + t - 1];
+                nodes[nc_id].values[i] = nodes[c_id].values[i + t - 1];
+            }
+            nodes[nc_id].n = t;
 
-        // If not leaf, copy the upper half of the children pointers
-        if !is_leaf {
+            // Link the new sibling into the leaf chain
+            nodes[nc_id].next = nodes[c_id].next;
+            nodes[c_id].next = Some(nc_id);
+            nodes[nc_id].keys[0]
+        } else {
+            // Copy the upper half of keys and children pointers to the new sibling
+            for i in 0..(t - 1) {
+                nodes[nc_id].keys[i] = nodes[c_id].keys[i + t];
+            }
             for i in 0..t {
-                self.arena.nodes[nc_id].children[i] =
-                    self.arena.nodes[c_id].children[i + t];
+                nodes[nc_id].children[i] = nodes[c_id].children[i + t];
             }
-        }
+            nodes[nc_id].n = t - 1;
+            nodes[c_id].keys[t - 1]
+        };
 
         // Update the original child's key count
-        self.arena.nodes[c_id].n = t - 1;
+        nodes[c_id].n = t - 1;
 
         // ***************************
         // * Work on the parent node *
         // ***************************
 
+        let parent = &mut nodes[p_id];
+        let p_n = parent.n;
+
         // Shift existing children pointers to make room for the new sibling
-        for i in (k + 1..=self.arena.nodes[p_id].n).rev() {
-            self.arena.nodes[p_id].children[i + 1] =
-                self.arena.nodes[p_id].children[i];
-        }
-        self.arena.nodes[p_id].children[k + 1] = nc_id;
+        parent.children.copy_within(k + 1..=p_n, k + 2);
+        parent.children[k + 1] = nc_id;
 
-        // Shift existing keys in the parent node to make room for the median key
-        for i in (k..self.arena.nodes[p_id].n).rev() {
-            self.arena.nodes[p_id].keys[i + 1] = self.arena.nodes[p_id].keys[i];
-        }
-        self.arena.nodes[p_id].keys[k] = median_key;
+        // Shift existing keys in the parent node to make room for the separator
+        parent.keys.copy_within(k..p_n, k + 1);
+        parent.keys[k] = separator;
 
         // Increment parent node's key count
-        self.arena.nodes[p_id].n += 1;
+        parent.n += 1;
     }
 }
@@ -0,0 +1,81 @@
+use crate::btree::arena::NodeId;
+use crate::btree::btree::Btree;
+use std::ops::{Bound, RangeBounds};
+
+/// Ordered iterator over the keys and values of a key range, see `Btree::range`.
+///
+/// The iterator descends to the leaf of the start of the range once,
+/// then follows the leaf chain.
+pub struct Range<'a, V> {
+    tree: &'a Btree<V>,
+    // Current leaf and the index of its next key to return
+    leaf: Option<NodeId>,
+    k: usize,
+    end: Bound<u64>,
+}
+
+/// Btree range scan implementation
+impl<V: Copy + Default> Btree<V> {
+    /// Keys in the range and their values, in key order
+    ///
+    /// O(h) disk access to find the start of the range
+    /// O(1) amortized per returned key
+    pub fn range<R: RangeBounds<u64>>(&self, range: R) -> Range<'_, V> {
+        let (leaf, k) = match range.start_bound() {
+            Bound::Included(start) => {
+                let id = self.find_first_leaf(*start);
+                let node = &self.arena.nodes[id];
+                (id, node.keys[..node.n].partition_point(|key| key < start))
+            }
+            Bound::Excluded(start) => {
+                let id = self.find_leaf(*start);
+                let node = &self.arena.nodes[id];
+                (id, node.keys[..node.n].partition_point(|key| key <= start))
+            }
+            Bound::Unbounded => (self.first_leaf(), 0),
+        };
+
+        Range {
+            tree: self,
+            leaf: Some(leaf),
+            k,
+            end: range.end_bound().cloned(),
+        }
+    }
+}
+
+impl<V: Copy + Default> Range<'_, V> {
+    fn is_past_end(&self, key: u64) -> bool {
+        match self.end {
+            Bound::Included(end) => key > end,
+            Bound::Excluded(end) => key >= end,
+            Bound::Unbounded => false,
+        }
+    }
+}
+
+impl<V: Copy + Default> Iterator for Range<'_, V> {
+    type Item = (u64, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(id) = self.leaf {
+            let node = &self.tree.arena.nodes[id];
+            if self.k == node.n {
+                // Leaf is done, continue in the next one
+                self.leaf = node.next;
+                self.k = 0;
+                continue;
+            }
+
+            let entry = (node.keys[self.k], node.values[self.k]);
+            if self.is_past_end(entry.0) {
+                self.leaf = None;
+                return None;
+            }
+            self.k += 1;
+            return Some(entry);
+        }
+
+        None
+    }
+}
@@ -1,29 +1,57 @@
-use crate::btree::arena::{NodeId};
+use crate::btree::arena::NodeId;
 use crate::btree::btree::Btree;
 
 /// Btree search implementation
-impl Btree {
+impl<V: Copy + Default> Btree<V> {
+    /// Leaf holding the first entry of the key and the index of the entry in it
     pub fn search(&self, key: u64) -> Option<(NodeId, usize)> {
-        self.recursive_search(self.root_id, key)
-    }
+        let id = self.find_first_leaf(key);
+        let node = &self.arena.nodes[id];
+        let k = node.keys[..node.n].partition_point(|&x| x < key);
 
-    // Private methods
-    fn recursive_search(&self, id: NodeId, key: u64) -> Option<(NodeId, usize)> {
+        // Keys of the leftmost leaf may all be smaller, the key then starts the next leaf
+        let (id, k) = match (k == node.n, node.next) {
+            (true, Some(next)) => (next, 0),
+            _ => (id, k),
+        };
         let node = &self.arena.nodes[id];
-        let mut k: usize = 0;
+        (k < node.n && node.keys[k] == key).then_some((id, k))
+    }
 
-        while k < node.n && node.keys[k] < key {
-            k += 1;
-        }
+    /// Value stored with the key, the first one of a duplicate key
+    pub fn get(&self, key: u64) -> Option<V> {
+        self.search(key)
+            .map(|(id, k)| self.arena.nodes[id].values[k])
+    }
 
-        if k < node.n && node.keys[k] == key {
-            return Some((id, k));
-        }
+    pub fn contains_key(&self, key: u64) -> bool {
+        self.search(key).is_some()
+    }
 
-        if node.is_leaf {
-            return None;
-        }
+    /// Values of all entries of the key, in insertion order
+    ///
+    /// O(h) disk access to find the first entry
+    /// O(1) amortized per returned value
+    pub fn search_all(&self, key: u64) -> Vec<V> {
+        let mut values = vec![];
+        let Some((mut id, mut k)) = self.search(key) else {
+            return values;
+        };
 
-        self.recursive_search(node.children[k], key)
+        loop {
+            let node = &self.arena.nodes[id];
+            while k < node.n && node.keys[k] == key {
+                values.push(node.values[k]);
+                k += 1;
+            }
+            match node.next {
+                // The entries may continue in the next leaf
+                Some(next) if k == node.n => {
+                    id = next;
+                    k = 0;
+                }
+                _ => return values,
+            }
+        }
     }
 }
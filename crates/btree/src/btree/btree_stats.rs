@@ -0,0 +1,138 @@
+use crate::btree::arena::NodeId;
+use crate::btree::btree::Btree;
+use std::fmt::Write;
+
+/// Number of buckets of the fill factor distribution, each covers 10% of the node capacity
+pub const FILL_BUCKETS: usize = 10;
+
+/// Shape of a tree, see `Btree::stats`
+#[derive(Debug, Clone, PartialEq)]
+pub struct BtreeStats {
+    pub height: usize,
+    pub len: usize,
+    pub node_count: usize,
+    pub leaf_count: usize,
+    /// Nodes by their keys relative to the (2 * t - 1) capacity, bucket i holds
+    /// the nodes filled to [i * 10%, (i + 1) * 10%), full nodes are in the last bucket
+    pub fill_distribution: [usize; FILL_BUCKETS],
+    /// Average keys per node relative to the capacity, in [0, 1]
+    pub average_fill: f64,
+    /// Bytes held by the arena, see `Btree::memory_usage`
+    pub memory_usage: usize,
+}
+
+/// Btree statistics and introspection
+impl<V: Copy + Default> Btree<V> {
+    /// Levels from the root down to the leaves, 1 for a single leaf
+    ///
+    /// O(h) disk access
+    pub fn height(&self) -> usize {
+        let mut id = self.root_id;
+        let mut height = 1;
+
+        while !self.arena.nodes[id].is_leaf {
+            id = self.arena.nodes[id].children[0];
+            height += 1;
+        }
+
+        height
+    }
+
+    /// Nodes in the tree, free nodes of the arena are not counted
+    ///
+    /// O(n) CPU time
+    pub fn node_count(&self) -> usize {
+        self.nodes().len()
+    }
+
+    /// Height, node counts, fill factors and memory of the nodes, e.g. to tune the minimum degree
+    ///
+    /// O(n) CPU time
+    pub fn stats(&self) -> BtreeStats {
+        let capacity = 2 * self.t - 1;
+        let nodes = self.nodes();
+        let mut fill_distribution = [0; FILL_BUCKETS];
+        let mut keys = 0;
+        let mut leaf_count = 0;
+
+        for id in &nodes {
+            let node = &self.arena.nodes[*id];
+            let bucket = (node.n * FILL_BUCKETS / capacity).min(FILL_BUCKETS - 1);
+            fill_distribution[bucket] += 1;
+            keys += node.n;
+            if node.is_leaf {
+                leaf_count += 1;
+            }
+        }
+
+        BtreeStats {
+            height: self.height(),
+            len: self.len,
+            node_count: nodes.len(),
+            leaf_count,
+            fill_distribution,
+            average_fill: keys as f64 / (nodes.len() * capacity) as f64,
+            memory_usage: self.memory_usage(),
+        }
+    }
+
+    /// Graphviz description of the tree, internal nodes list their separators
+    /// between the child ports, leaves their keys. Dashed edges follow the leaf chain.
+    ///
+    /// `dot -Tsvg tree.dot -o tree.svg` renders it
+    pub fn dump_dot(&self) -> String {
+        let mut dot = String::from("digraph btree {\n    node [shape=record];\n");
+
+        for id in self.nodes() {
+            let node = &self.arena.nodes[id];
+            let keys = &node.keys[..node.n];
+            let label = if node.is_leaf {
+                keys.iter()
+                    .map(|key| key.to_string())
+                    .collect::<Vec<_>>()
+                    .join("|")
+            } else {
+                let mut label = String::from("<c0>");
+                for (i, key) in keys.iter().enumerate() {
+                    write!(label, "|{}|<c{}>", key, i + 1).unwrap();
+                }
+                label
+            };
+            writeln!(dot, "    n{} [label=\"{}\"];", id, label).unwrap();
+
+            if node.is_leaf {
+                if let Some(next) = node.next {
+                    writeln!(
+                        dot,
+                        "    n{} -> n{} [style=dashed, constraint=false];",
+                        id, next
+                    )
+                    .unwrap();
+                }
+            } else {
+                for (i, c_id) in node.children[..=node.n].iter().enumerate() {
+                    writeln!(dot, "    n{}:c{} -> n{};", id, i, c_id).unwrap();
+                }
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Ids of the nodes in the tree, level by level from the root
+    fn nodes(&self) -> Vec<NodeId> {
+        let mut nodes = vec![self.root_id];
+        let mut i = 0;
+
+        while i < nodes.len() {
+            let node = &self.arena.nodes[nodes[i]];
+            if !node.is_leaf {
+                nodes.extend_from_slice(&node.children[..=node.n]);
+            }
+            i += 1;
+        }
+
+        nodes
+    }
+}
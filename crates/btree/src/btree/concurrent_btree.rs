@@ -0,0 +1,508 @@
+use std::collections::VecDeque;
+use std::ops::{Bound, RangeBounds};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+type NodeRef<V> = Arc<RwLock<LatchedNode<V>>>;
+
+/// Node of a concurrent tree behind its own latch.
+/// Keys and values are sized to the node, internal nodes only hold separators,
+/// keys[i] is the smallest key of children[i + 1].
+#[derive(Debug)]
+pub(crate) struct LatchedNode<V> {
+    pub(crate) keys: Vec<u64>,
+
+    // Values of a leaf, values[i] belongs to keys[i]
+    pub(crate) values: Vec<V>,
+
+    // Children of an internal node, empty for a leaf
+    pub(crate) children: Vec<NodeRef<V>>,
+}
+
+impl<V> LatchedNode<V> {
+    fn new_leaf() -> Self {
+        Self {
+            keys: vec![],
+            values: vec![],
+            children: vec![],
+        }
+    }
+
+    pub(crate) fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// Child of an internal node whose subtree holds the key
+    fn find_child_index(&self, key: u64) -> usize {
+        self.keys.partition_point(|k| *k <= key)
+    }
+}
+
+/// A thread panicked while it held a write latch of the tree, e.g. in the middle of a
+/// split or a merge. The node it was changing may be half updated, every operation
+/// reaching the node fails from then on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoisonedLatch;
+
+type ReadLatch<'a, V> = RwLockReadGuard<'a, LatchedNode<V>>;
+type WriteLatch<'a, V> = RwLockWriteGuard<'a, LatchedNode<V>>;
+
+fn read_latch<V>(node: &NodeRef<V>) -> Result<ReadLatch<'_, V>, PoisonedLatch> {
+    node.read().map_err(|_| PoisonedLatch)
+}
+
+fn write_latch<V>(node: &NodeRef<V>) -> Result<WriteLatch<'_, V>, PoisonedLatch> {
+    node.write().map_err(|_| PoisonedLatch)
+}
+
+/// BTree shared between threads, in the B+tree variant of `Btree`.
+/// - Each node has its own read-write latch, operations take `&self`.
+///
+/// - Latch crabbing: a thread latches the child before it releases the parent,
+///   so it never sees a node in the middle of a split or a merge.
+///
+/// - Readers hold read latches of at most two nodes, readers of different
+///   subtrees and a writer of another subtree don't wait for each other.
+///
+/// - Writers split full nodes and fix nodes at the minimum on the way down,
+///   like `Btree`, so a change never goes back up. A writer holds the write latches
+///   of a parent and the children it changes, it releases the parent once the child
+///   it descends into can take the change.
+///
+/// - The root pointer has its own latch, held by a writer only while the root
+///   may split or shrink.
+///
+/// - Leaves are not chained, range scans find the next leaf from the root,
+///   see `ConcurrentRange`.
+///
+/// - A panic with a write latch held poisons the latch, operations reaching
+///   the node fail with `PoisonedLatch`.
+///
+#[derive(Debug)]
+pub struct ConcurrentBtree<V> {
+    // Minimum degree, see `Btree`
+    t: usize,
+
+    // Root of the tree, replaced when the root splits or shrinks
+    pub(crate) root: RwLock<NodeRef<V>>,
+
+    // Number of keys stored in the tree
+    len: AtomicUsize,
+}
+
+impl<V: Copy + Default> ConcurrentBtree<V> {
+    pub fn new(minimum_degree: usize) -> Self {
+        Self {
+            t: minimum_degree,
+            root: RwLock::new(Arc::new(RwLock::new(LatchedNode::new_leaf()))),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Read the tree from the root, its read latch is taken before the root pointer
+    /// is released
+    fn read_root<R>(
+        &self,
+        read: impl FnOnce(ReadLatch<'_, V>) -> Result<R, PoisonedLatch>,
+    ) -> Result<R, PoisonedLatch> {
+        let root_ptr = self.root.read().map_err(|_| PoisonedLatch)?;
+        let root = root_ptr.clone();
+        let guard = read_latch(&root)?;
+        drop(root_ptr);
+
+        read(guard)
+    }
+
+    /// Levels from the root down to the leaves, 1 for a single leaf
+    pub fn height(&self) -> Result<usize, PoisonedLatch> {
+        self.read_root(height)
+    }
+
+    /// Value stored with the key
+    ///
+    /// O(h) read latches, at most two held at a time
+    pub fn get(&self, key: u64) -> Result<Option<V>, PoisonedLatch> {
+        self.read_root(|guard| search(guard, key))
+    }
+
+    pub fn contains_key(&self, key: u64) -> Result<bool, PoisonedLatch> {
+        Ok(self.get(key)?.is_some())
+    }
+
+    /// Keys in the range and their values, in key order
+    pub fn range<R: RangeBounds<u64>>(&self, range: R) -> ConcurrentRange<'_, V> {
+        ConcurrentRange {
+            tree: self,
+            start: Some(range.start_bound().cloned()),
+            end: range.end_bound().cloned(),
+            buffer: VecDeque::new(),
+        }
+    }
+
+    /// Insert the key with its value, replacing the value of an existing key.
+    /// Returns the replaced value.
+    ///
+    /// O(h) write latches, at most three held at a time
+    pub fn insert(&self, key: u64, value: V) -> Result<Option<V>, PoisonedLatch> {
+        let t = self.t;
+        let mut root_ptr = self.root.write().map_err(|_| PoisonedLatch)?;
+        let root = root_ptr.clone();
+        let mut guard = write_latch(&root)?;
+
+        let previous = if guard.keys.len() == 2 * t - 1 {
+            // Split the root under a new one
+            let new_root = Arc::new(RwLock::new(LatchedNode {
+                keys: vec![],
+                values: vec![],
+                children: vec![root.clone()],
+            }));
+            let mut new_guard = write_latch(&new_root)?;
+            split_child(&mut new_guard, &mut guard, 0, t);
+            *root_ptr = new_root.clone();
+            drop(guard);
+            drop(root_ptr);
+            insert_non_full(new_guard, key, value, t)?
+        } else {
+            drop(root_ptr);
+            insert_non_full(guard, key, value, t)?
+        };
+
+        if previous.is_none() {
+            self.len.fetch_add(1, Ordering::AcqRel);
+        }
+        Ok(previous)
+    }
+
+    /// Delete the key, returns its value
+    ///
+    /// O(h) write latches, at most four held at a time
+    pub fn delete(&self, key: u64) -> Result<Option<V>, PoisonedLatch> {
+        let t = self.t;
+        let mut root_ptr = Some(self.root.write().map_err(|_| PoisonedLatch)?);
+        let root = Arc::clone(root_ptr.as_ref().unwrap());
+        let mut guard = write_latch(&root)?;
+
+        // Only an internal root with a single key may shrink
+        if guard.is_leaf() || guard.keys.len() > 1 {
+            root_ptr = None;
+        }
+
+        let removed = if guard.is_leaf() {
+            remove_from_leaf(&mut guard, key)
+        } else {
+            let child = prepare_child(&mut guard, key, t)?;
+            if let Some(root_ptr) = root_ptr.as_mut().filter(|_| guard.keys.is_empty()) {
+                // Handle root shrinking, the children were merged
+                **root_ptr = child.clone();
+            }
+            let child_guard = write_latch(&child)?;
+            drop(guard);
+            drop(root_ptr);
+            delete_from(child_guard, key, t)?
+        };
+
+        if removed.is_some() {
+            self.len.fetch_sub(1, Ordering::AcqRel);
+        }
+        Ok(removed)
+    }
+}
+
+fn height<V>(guard: ReadLatch<'_, V>) -> Result<usize, PoisonedLatch> {
+    if guard.is_leaf() {
+        return Ok(1);
+    }
+
+    let child = guard.children[0].clone();
+    let child_guard = read_latch(&child)?;
+    drop(guard);
+    Ok(1 + height(child_guard)?)
+}
+
+fn search<V: Copy>(guard: ReadLatch<'_, V>, key: u64) -> Result<Option<V>, PoisonedLatch> {
+    if guard.is_leaf() {
+        return Ok(guard.keys.binary_search(&key).ok().map(|k| guard.values[k]));
+    }
+
+    let child = guard.children[guard.find_child_index(key)].clone();
+    let child_guard = read_latch(&child)?;
+    drop(guard);
+    search(child_guard, key)
+}
+
+/// Insert into the subtree of a node which is not full, splitting full children on the way
+fn insert_non_full<V: Copy>(
+    mut guard: WriteLatch<'_, V>,
+    key: u64,
+    value: V,
+    t: usize,
+) -> Result<Option<V>, PoisonedLatch> {
+    if guard.is_leaf() {
+        return Ok(match guard.keys.binary_search(&key) {
+            Ok(k) => Some(std::mem::replace(&mut guard.values[k], value)),
+            Err(k) => {
+                guard.keys.insert(k, key);
+                guard.values.insert(k, value);
+                None
+            }
+        });
+    }
+
+    let k = guard.find_child_index(key);
+    let child = guard.children[k].clone();
+    let mut child_guard = write_latch(&child)?;
+    if child_guard.keys.len() == 2 * t - 1 {
+        split_child(&mut guard, &mut child_guard, k, t);
+        if key >= guard.keys[k] {
+            // The key goes into the new sibling
+            drop(child_guard);
+            let sibling = guard.children[k + 1].clone();
+            let sibling_guard = write_latch(&sibling)?;
+            drop(guard);
+            return insert_non_full(sibling_guard, key, value, t);
+        }
+    }
+
+    drop(guard);
+    insert_non_full(child_guard, key, value, t)
+}
+
+/// Split a full child in two, see `Btree::split_child` for the halves.
+/// The new sibling is only reachable once the parent latch is released.
+fn split_child<V>(parent: &mut LatchedNode<V>, child: &mut LatchedNode<V>, k: usize, t: usize) {
+    let (separator, sibling) = if child.is_leaf() {
+        let sibling = LatchedNode {
+            keys: child.keys.split_off(t - 1),
+            values: child.values.split_off(t - 1),
+            children: vec![],
+        };
+        (sibling.keys[0], sibling)
+    } else {
+        let sibling = LatchedNode {
+            keys: child.keys.split_off(t),
+            values: vec![],
+            children: child.children.split_off(t),
+        };
+        (child.keys.pop().unwrap(), sibling)
+    };
+
+    parent.keys.insert(k, separator);
+    parent
+        .children
+        .insert(k + 1, Arc::new(RwLock::new(sibling)));
+}
+
+fn remove_from_leaf<V: Copy>(leaf: &mut LatchedNode<V>, key: u64) -> Option<V> {
+    let k = leaf.keys.binary_search(&key).ok()?;
+    leaf.keys.remove(k);
+    Some(leaf.values.remove(k))
+}
+
+/// Delete from the subtree of a node holding more than the minimum keys
+fn delete_from<V: Copy>(
+    mut guard: WriteLatch<'_, V>,
+    key: u64,
+    t: usize,
+) -> Result<Option<V>, PoisonedLatch> {
+    if guard.is_leaf() {
+        return Ok(remove_from_leaf(&mut guard, key));
+    }
+
+    let child = prepare_child(&mut guard, key, t)?;
+    let child_guard = write_latch(&child)?;
+    drop(guard);
+    delete_from(child_guard, key, t)
+}
+
+/// Child whose subtree holds the key, given more than the minimum keys by borrowing
+/// from or merging with a sibling. The parent latch keeps other writers away from
+/// the children, a child is latched for the time it changes.
+fn prepare_child<V: Copy>(
+    parent: &mut LatchedNode<V>,
+    key: u64,
+    t: usize,
+) -> Result<NodeRef<V>, PoisonedLatch> {
+    let k = parent.find_child_index(key);
+    let child = parent.children[k].clone();
+    if read_latch(&child)?.keys.len() > t - 1 {
+        return Ok(child);
+    }
+
+    let left = (k > 0).then(|| parent.children[k - 1].clone());
+    let right = (k < parent.keys.len()).then(|| parent.children[k + 1].clone());
+
+    // Latches are taken from left to right
+    let mut left_guard = left.as_ref().map(write_latch).transpose()?;
+    let mut child_guard = write_latch(&child)?;
+    let mut right_guard = right.as_ref().map(write_latch).transpose()?;
+
+    if let Some(left_guard) = left_guard.as_mut().filter(|g| g.keys.len() > t - 1) {
+        // Left sibling has extra keys, borrow from it
+        borrow_from_left_sibling(parent, k, left_guard, &mut child_guard);
+        return Ok(child.clone());
+    }
+    if let Some(right_guard) = right_guard.as_mut().filter(|g| g.keys.len() > t - 1) {
+        // Right sibling has extra keys, borrow from it
+        borrow_from_right_sibling(parent, k, &mut child_guard, right_guard);
+        return Ok(child.clone());
+    }
+
+    // Both siblings have minimum keys, merge with a sibling
+    Ok(match (left_guard.as_mut(), right_guard.as_mut()) {
+        (Some(left_guard), _) => {
+            merge_children(parent, k - 1, left_guard, &mut child_guard);
+            left.clone().unwrap()
+        }
+        (None, Some(right_guard)) => {
+            merge_children(parent, k, &mut child_guard, right_guard);
+            child.clone()
+        }
+        (None, None) => unreachable!("An internal node has at least two children"),
+    })
+}
+
+fn borrow_from_left_sibling<V>(
+    parent: &mut LatchedNode<V>,
+    k: usize,
+    left: &mut LatchedNode<V>,
+    child: &mut LatchedNode<V>,
+) {
+    if child.is_leaf() {
+        // Move left sibling's last entry to the child, it becomes the separator
+        child.keys.insert(0, left.keys.pop().unwrap());
+        child.values.insert(0, left.values.pop().unwrap());
+        parent.keys[k - 1] = child.keys[0];
+    } else {
+        // Move parent key down to child and left sibling's last key up to parent
+        child.keys.insert(0, parent.keys[k - 1]);
+        parent.keys[k - 1] = left.keys.pop().unwrap();
+        child.children.insert(0, left.children.pop().unwrap());
+    }
+}
+
+fn borrow_from_right_sibling<V>(
+    parent: &mut LatchedNode<V>,
+    k: usize,
+    child: &mut LatchedNode<V>,
+    right: &mut LatchedNode<V>,
+) {
+    if child.is_leaf() {
+        // Move right sibling's first entry to the child, its next key becomes the separator
+        child.keys.push(right.keys.remove(0));
+        child.values.push(right.values.remove(0));
+        parent.keys[k] = right.keys[0];
+    } else {
+        // Move parent key down to child and right sibling's first key up to parent
+        child.keys.push(parent.keys[k]);
+        parent.keys[k] = right.keys.remove(0);
+        child.children.push(right.children.remove(0));
+    }
+}
+
+/// Move all entries of the right child into the left one and drop the right child
+/// from the parent
+fn merge_children<V>(
+    parent: &mut LatchedNode<V>,
+    k: usize,
+    left: &mut LatchedNode<V>,
+    right: &mut LatchedNode<V>,
+) {
+    let separator = parent.keys.remove(k);
+    parent.children.remove(k + 1);
+
+    if left.is_leaf() {
+        // The separator is dropped
+        left.keys.append(&mut right.keys);
+        left.values.append(&mut right.values);
+    } else {
+        left.keys.push(separator);
+        left.keys.append(&mut right.keys);
+        left.children.append(&mut right.children);
+    }
+}
+
+/// Ordered iterator over a key range of a `ConcurrentBtree`.
+///
+/// The entries are read one leaf at a time under its read latch, no latch is held
+/// between leaves. The next leaf is found from the root by the smallest separator
+/// above the previous one, so a scan sees each key present for the whole scan once,
+/// in key order, whatever splits and merges happen meanwhile.
+/// A poisoned latch ends the scan with an error.
+pub struct ConcurrentRange<'a, V> {
+    tree: &'a ConcurrentBtree<V>,
+    // Start of the leaves not read yet, None once the last leaf is read
+    start: Option<Bound<u64>>,
+    end: Bound<u64>,
+    // Entries read from the last leaf
+    buffer: VecDeque<(u64, V)>,
+}
+
+impl<V: Copy + Default> ConcurrentRange<'_, V> {
+    /// Read the entries of the leaf holding the start, returns the start of the next leaf
+    fn read_leaf(&mut self, start: Bound<u64>) -> Result<Option<Bound<u64>>, PoisonedLatch> {
+        let seek = match start {
+            Bound::Included(key) | Bound::Excluded(key) => key,
+            Bound::Unbounded => 0,
+        };
+        let range = (start, self.end);
+
+        let upper = self
+            .tree
+            .read_root(|guard| read_leaf(guard, seek, None, &range, &mut self.buffer))?;
+        Ok(upper
+            .filter(|upper| range.contains(upper))
+            .map(Bound::Included))
+    }
+}
+
+/// Copy the entries in the range of the leaf holding the key, returns the smallest
+/// separator above the leaf, the first key of the next leaf
+fn read_leaf<V: Copy>(
+    guard: ReadLatch<'_, V>,
+    key: u64,
+    upper: Option<u64>,
+    range: &(Bound<u64>, Bound<u64>),
+    buffer: &mut VecDeque<(u64, V)>,
+) -> Result<Option<u64>, PoisonedLatch> {
+    if guard.is_leaf() {
+        let entries = guard.keys.iter().zip(&guard.values);
+        buffer.extend(
+            entries
+                .filter(|(key, _)| range.contains(*key))
+                .map(|(key, value)| (*key, *value)),
+        );
+        return Ok(upper);
+    }
+
+    let c_k = guard.find_child_index(key);
+    let upper = guard.keys.get(c_k).copied().or(upper);
+    let child = guard.children[c_k].clone();
+    let child_guard = read_latch(&child)?;
+    drop(guard);
+    read_leaf(child_guard, key, upper, range, buffer)
+}
+
+impl<V: Copy + Default> Iterator for ConcurrentRange<'_, V> {
+    type Item = Result<(u64, V), PoisonedLatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.buffer.pop_front() {
+                return Some(Ok(entry));
+            }
+            let start = self.start.take()?;
+            match self.read_leaf(start) {
+                Ok(next) => self.start = next,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
@@ -0,0 +1,484 @@
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
+
+/// Node of a copy-on-write tree, shared between the versions which did not change it.
+/// Keys and values are sized to the node, internal nodes only hold separators,
+/// keys[i] is the smallest key of children[i + 1].
+#[derive(Debug, Clone)]
+pub(crate) struct CowNode<V> {
+    pub(crate) keys: Vec<u64>,
+
+    // Values of a leaf, values[i] belongs to keys[i]
+    pub(crate) values: Vec<V>,
+
+    // Children of an internal node, empty for a leaf
+    pub(crate) children: Vec<Arc<CowNode<V>>>,
+}
+
+impl<V> CowNode<V> {
+    fn new_leaf() -> Self {
+        Self {
+            keys: vec![],
+            values: vec![],
+            children: vec![],
+        }
+    }
+
+    pub(crate) fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// Child of an internal node whose subtree holds the key
+    fn find_child_index(&self, key: u64) -> usize {
+        self.keys.partition_point(|k| *k <= key)
+    }
+}
+
+/// Immutable version of a `CowBtree`.
+/// Cloning is cheap and a snapshot can be read from other threads while the tree
+/// keeps changing, without locks.
+#[derive(Debug)]
+pub struct BtreeSnapshot<V> {
+    pub(crate) root: Arc<CowNode<V>>,
+    len: usize,
+    version: u64,
+}
+
+impl<V> Clone for BtreeSnapshot<V> {
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+            len: self.len,
+            version: self.version,
+        }
+    }
+}
+
+/// Copy-on-write BTree, in the B+tree variant of `Btree`.
+/// - Changes copy the nodes on the path from the root to the changed leaf,
+///   the nodes they did not touch stay shared with the committed versions.
+///
+/// - Each commit freezes the current root as an immutable version.
+///   Nodes only reachable from the uncommitted root are changed in place,
+///   a node is copied the first time it changes after a commit.
+///
+/// - Versions are numbered like the chunks of a store, so a version of a tree
+///   can be committed with the version of the chunk which stores it.
+///
+/// - Old versions live as long as a snapshot of them is held.
+///
+#[derive(Debug)]
+pub struct CowBtree<V> {
+    // Minimum degree, see `Btree`
+    t: usize,
+
+    // Root of the uncommitted version
+    pub(crate) root: Arc<CowNode<V>>,
+
+    // Number of keys stored in the uncommitted version
+    len: usize,
+
+    // Latest committed version
+    committed: BtreeSnapshot<V>,
+}
+
+impl<V: Copy + Default> CowBtree<V> {
+    /// Empty tree, its empty version 0 is committed
+    pub fn new(minimum_degree: usize) -> Self {
+        let root = Arc::new(CowNode::new_leaf());
+
+        Self {
+            t: minimum_degree,
+            root: root.clone(),
+            len: 0,
+            committed: BtreeSnapshot {
+                root,
+                len: 0,
+                version: 0,
+            },
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Latest committed version
+    pub fn version(&self) -> u64 {
+        self.committed.version
+    }
+
+    /// Latest committed version, uncommitted changes are not part of it
+    pub fn snapshot(&self) -> BtreeSnapshot<V> {
+        self.committed.clone()
+    }
+
+    pub fn has_uncommitted_changes(&self) -> bool {
+        !Arc::ptr_eq(&self.root, &self.committed.root)
+    }
+
+    /// Commit the changes as the next version, returns the version
+    pub fn commit(&mut self) -> u64 {
+        self.commit_at(self.committed.version + 1);
+        self.committed.version
+    }
+
+    /// Commit the changes as the given version, e.g. the version of the chunk being written.
+    /// Panics unless the version is newer than the latest committed one.
+    pub fn commit_at(&mut self, version: u64) {
+        assert!(
+            version > self.committed.version,
+            "Version {} is not newer than the committed version {}",
+            version,
+            self.committed.version
+        );
+        self.committed = BtreeSnapshot {
+            root: self.root.clone(),
+            len: self.len,
+            version,
+        };
+    }
+
+    /// Drop the uncommitted changes
+    pub fn rollback(&mut self) {
+        self.root = self.committed.root.clone();
+        self.len = self.committed.len;
+    }
+
+    /// Value stored with the key, uncommitted changes included
+    pub fn get(&self, key: u64) -> Option<V> {
+        get(&self.root, key)
+    }
+
+    pub fn contains_key(&self, key: u64) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Keys in the range and their values in key order, uncommitted changes included
+    pub fn range<R: RangeBounds<u64>>(&self, range: R) -> CowRange<'_, V> {
+        CowRange::new(&self.root, range)
+    }
+
+    /// Insert the key with its value, replacing the value of an existing key.
+    /// Returns the replaced value.
+    ///
+    /// O(h) nodes copied
+    pub fn insert(&mut self, key: u64, value: V) -> Option<V> {
+        let t = self.t;
+
+        if self.root.keys.len() == 2 * t - 1 {
+            // Split the root under a new one
+            let old_root = std::mem::replace(&mut self.root, Arc::new(CowNode::new_leaf()));
+            let mut root = CowNode::new_leaf();
+            root.children.push(old_root);
+            split_child(&mut root, 0, t);
+            self.root = Arc::new(root);
+        }
+
+        let previous = insert_non_full(Arc::make_mut(&mut self.root), key, value, t);
+        if previous.is_none() {
+            self.len += 1;
+        }
+        previous
+    }
+
+    /// Delete the key, returns its value
+    ///
+    /// O(h) nodes copied
+    pub fn delete(&mut self, key: u64) -> Option<V> {
+        // Don't copy the path for a missing key
+        if !self.contains_key(key) {
+            return None;
+        }
+
+        let removed = remove(Arc::make_mut(&mut self.root), key, self.t);
+        self.len -= 1;
+
+        // Handle root shrinking
+        if self.root.keys.is_empty() && !self.root.is_leaf() {
+            self.root = self.root.children[0].clone();
+        }
+        removed
+    }
+}
+
+impl<V: Copy + Default> BtreeSnapshot<V> {
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, key: u64) -> Option<V> {
+        get(&self.root, key)
+    }
+
+    pub fn contains_key(&self, key: u64) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Keys in the range and their values, in key order
+    pub fn range<R: RangeBounds<u64>>(&self, range: R) -> CowRange<'_, V> {
+        CowRange::new(&self.root, range)
+    }
+
+    /// All keys and their values in key order
+    pub fn entries(&self) -> Vec<(u64, V)> {
+        let mut entries = Vec::with_capacity(self.len);
+        entries.extend(self.range(..));
+        entries
+    }
+}
+
+fn get<V: Copy>(root: &CowNode<V>, key: u64) -> Option<V> {
+    let mut node = root;
+
+    while !node.is_leaf() {
+        node = &node.children[node.find_child_index(key)];
+    }
+
+    node.keys.binary_search(&key).ok().map(|k| node.values[k])
+}
+
+/// Insert into the subtree of a node which is not full, splitting full children on the way
+fn insert_non_full<V: Copy + Default>(
+    node: &mut CowNode<V>,
+    key: u64,
+    value: V,
+    t: usize,
+) -> Option<V> {
+    if node.is_leaf() {
+        return match node.keys.binary_search(&key) {
+            Ok(k) => Some(std::mem::replace(&mut node.values[k], value)),
+            Err(k) => {
+                node.keys.insert(k, key);
+                node.values.insert(k, value);
+                None
+            }
+        };
+    }
+
+    let mut k = node.find_child_index(key);
+    if node.children[k].keys.len() == 2 * t - 1 {
+        split_child(node, k, t);
+        if key >= node.keys[k] {
+            // does the key go into child[k] or child[k + 1]?
+            k += 1;
+        }
+    }
+
+    insert_non_full(Arc::make_mut(&mut node.children[k]), key, value, t)
+}
+
+/// Split a full child in two, see `Btree::split_child` for the halves
+fn split_child<V: Copy + Default>(parent: &mut CowNode<V>, k: usize, t: usize) {
+    let child = Arc::make_mut(&mut parent.children[k]);
+
+    let (separator, sibling) = if child.is_leaf() {
+        let sibling = CowNode {
+            keys: child.keys.split_off(t - 1),
+            values: child.values.split_off(t - 1),
+            children: vec![],
+        };
+        (sibling.keys[0], sibling)
+    } else {
+        let sibling = CowNode {
+            keys: child.keys.split_off(t),
+            values: vec![],
+            children: child.children.split_off(t),
+        };
+        (child.keys.pop().unwrap(), sibling)
+    };
+
+    parent.keys.insert(k, separator);
+    parent.children.insert(k + 1, Arc::new(sibling));
+}
+
+/// Remove the key from the subtree, children left below the minimum are fixed on the way up
+fn remove<V: Copy + Default>(node: &mut CowNode<V>, key: u64, t: usize) -> Option<V> {
+    if node.is_leaf() {
+        let k = node.keys.binary_search(&key).ok()?;
+        node.keys.remove(k);
+        return Some(node.values.remove(k));
+    }
+
+    // A separator equal to the key may stay, it still separates the subtrees
+    let k = node.find_child_index(key);
+    let removed = remove(Arc::make_mut(&mut node.children[k]), key, t);
+    if node.children[k].keys.len() < t - 1 {
+        fix_child(node, k, t);
+    }
+    removed
+}
+
+fn fix_child<V: Copy + Default>(parent: &mut CowNode<V>, k: usize, t: usize) {
+    if k > 0 && parent.children[k - 1].keys.len() > t - 1 {
+        // Left sibling has extra keys, borrow from it
+        borrow_from_left_sibling(parent, k);
+    } else if k + 1 < parent.children.len() && parent.children[k + 1].keys.len() > t - 1 {
+        // Right sibling has extra keys, borrow from it
+        borrow_from_right_sibling(parent, k);
+    } else if k > 0 {
+        // Both siblings have minimum keys, merge with a sibling
+        merge_children(parent, k - 1);
+    } else {
+        merge_children(parent, k);
+    }
+}
+
+fn borrow_from_left_sibling<V: Copy + Default>(parent: &mut CowNode<V>, k: usize) {
+    let (left, right) = parent.children.split_at_mut(k);
+    let lc = Arc::make_mut(&mut left[k - 1]);
+    let rc = Arc::make_mut(&mut right[0]);
+
+    if rc.is_leaf() {
+        // Move left sibling's last entry to the child, it becomes the separator
+        rc.keys.insert(0, lc.keys.pop().unwrap());
+        rc.values.insert(0, lc.values.pop().unwrap());
+        parent.keys[k - 1] = rc.keys[0];
+    } else {
+        // Move parent key down to child and left sibling's last key up to parent
+        rc.keys.insert(0, parent.keys[k - 1]);
+        parent.keys[k - 1] = lc.keys.pop().unwrap();
+        rc.children.insert(0, lc.children.pop().unwrap());
+    }
+}
+
+fn borrow_from_right_sibling<V: Copy + Default>(parent: &mut CowNode<V>, k: usize) {
+    let (left, right) = parent.children.split_at_mut(k + 1);
+    let lc = Arc::make_mut(&mut left[k]);
+    let rc = Arc::make_mut(&mut right[0]);
+
+    if lc.is_leaf() {
+        // Move right sibling's first entry to the child, its next key becomes the separator
+        lc.keys.push(rc.keys.remove(0));
+        lc.values.push(rc.values.remove(0));
+        parent.keys[k] = rc.keys[0];
+    } else {
+        // Move parent key down to child and right sibling's first key up to parent
+        lc.keys.push(parent.keys[k]);
+        parent.keys[k] = rc.keys.remove(0);
+        lc.children.push(rc.children.remove(0));
+    }
+}
+
+fn merge_children<V: Copy + Default>(parent: &mut CowNode<V>, k: usize) {
+    let separator = parent.keys.remove(k);
+    let rc = Arc::unwrap_or_clone(parent.children.remove(k + 1));
+    let lc = Arc::make_mut(&mut parent.children[k]);
+
+    if lc.is_leaf() {
+        // Move all entries from right child to left, the separator is dropped
+        lc.keys.extend(rc.keys);
+        lc.values.extend(rc.values);
+    } else {
+        // Move the separator down, then all keys and children from right child to left
+        lc.keys.push(separator);
+        lc.keys.extend(rc.keys);
+        lc.children.extend(rc.children);
+    }
+}
+
+/// Ordered iterator over a key range of a version, see `BtreeSnapshot::range`.
+///
+/// Leaves of a copy-on-write tree are not chained, a chain would need every leaf
+/// copied when its neighbour changes. The iterator keeps the path to the current leaf.
+pub struct CowRange<'a, V> {
+    // Internal nodes on the path and the index of the next child to visit in each
+    path: Vec<(&'a CowNode<V>, usize)>,
+    // Current leaf and the index of its next key to return
+    leaf: Option<&'a CowNode<V>>,
+    k: usize,
+    end: Bound<u64>,
+}
+
+impl<'a, V: Copy> CowRange<'a, V> {
+    fn new<R: RangeBounds<u64>>(root: &'a CowNode<V>, range: R) -> Self {
+        let mut path = vec![];
+        let mut node = root;
+
+        while !node.is_leaf() {
+            let c_k = match range.start_bound() {
+                Bound::Included(start) | Bound::Excluded(start) => node.find_child_index(*start),
+                Bound::Unbounded => 0,
+            };
+            path.push((node, c_k + 1));
+            node = &node.children[c_k];
+        }
+
+        let k = match range.start_bound() {
+            Bound::Included(start) => node.keys.partition_point(|key| key < start),
+            Bound::Excluded(start) => node.keys.partition_point(|key| key <= start),
+            Bound::Unbounded => 0,
+        };
+
+        Self {
+            path,
+            leaf: Some(node),
+            k,
+            end: range.end_bound().cloned(),
+        }
+    }
+
+    /// Leftmost leaf after the current one
+    fn next_leaf(&mut self) -> Option<&'a CowNode<V>> {
+        while let Some((node, c_k)) = self.path.pop() {
+            if c_k == node.children.len() {
+                continue;
+            }
+
+            self.path.push((node, c_k + 1));
+            let mut node: &'a CowNode<V> = &node.children[c_k];
+            while !node.is_leaf() {
+                self.path.push((node, 1));
+                node = &node.children[0];
+            }
+            return Some(node);
+        }
+
+        None
+    }
+
+    fn is_past_end(&self, key: u64) -> bool {
+        match self.end {
+            Bound::Included(end) => key > end,
+            Bound::Excluded(end) => key >= end,
+            Bound::Unbounded => false,
+        }
+    }
+}
+
+impl<V: Copy> Iterator for CowRange<'_, V> {
+    type Item = (u64, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.leaf {
+            if self.k == node.keys.len() {
+                // Leaf is done, continue in the next one
+                self.leaf = self.next_leaf();
+                self.k = 0;
+                continue;
+            }
+
+            let entry = (node.keys[self.k], node.values[self.k]);
+            if self.is_past_end(entry.0) {
+                self.leaf = None;
+                return None;
+            }
+            self.k += 1;
+            return Some(entry);
+        }
+
+        None
+    }
+}
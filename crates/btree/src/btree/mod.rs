@@ -1,7 +1,24 @@
 mod arena;
+#[allow(clippy::module_inception)]
 mod btree;
-mod btree_search;
-mod btree_insert;
+mod btree_bulk_load;
+mod btree_check;
+mod btree_cursor;
 mod btree_delete;
+mod btree_insert;
+mod btree_range;
+mod btree_search;
+mod btree_stats;
+pub(crate) mod concurrent_btree;
+pub(crate) mod cow_btree;
+mod node_codec;
+mod persistent_btree;
 
-pub use btree::Btree;
\ No newline at end of file
+pub use btree::Btree;
+pub use btree_cursor::Cursor;
+pub use btree_range::Range;
+pub use btree_stats::{BtreeStats, FILL_BUCKETS};
+pub use concurrent_btree::{ConcurrentBtree, ConcurrentRange, PoisonedLatch};
+pub use cow_btree::{BtreeSnapshot, CowBtree, CowRange};
+pub use node_codec::{InvalidNode, NodeValue};
+pub use persistent_btree::{NodeCacheStats, NodeStore, PersistentBtree, PersistentRange};
@@ -0,0 +1,160 @@
+use crate::btree::arena::{BtreeNode, NodeId};
+
+/// Value type a persistent tree can store, encoded in a fixed number of bytes
+pub trait NodeValue: Copy + Default {
+    const SIZE: usize;
+
+    fn encode(&self, bytes: &mut Vec<u8>);
+
+    /// Decode from exactly `SIZE` bytes
+    fn decode(bytes: &[u8]) -> Self;
+}
+
+impl NodeValue for () {
+    const SIZE: usize = 0;
+
+    fn encode(&self, _bytes: &mut Vec<u8>) {}
+
+    fn decode(_bytes: &[u8]) -> Self {}
+}
+
+impl NodeValue for u32 {
+    const SIZE: usize = 4;
+
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn decode(bytes: &[u8]) -> Self {
+        u32::from_le_bytes(bytes.try_into().unwrap())
+    }
+}
+
+impl NodeValue for u64 {
+    const SIZE: usize = 8;
+
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn decode(bytes: &[u8]) -> Self {
+        u64::from_le_bytes(bytes.try_into().unwrap())
+    }
+}
+
+/// Record location, e.g. (page id, slot index)
+impl NodeValue for (u32, u16) {
+    const SIZE: usize = 6;
+
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.0.to_le_bytes());
+        bytes.extend_from_slice(&self.1.to_le_bytes());
+    }
+
+    fn decode(bytes: &[u8]) -> Self {
+        (
+            u32::from_le_bytes(bytes[..4].try_into().unwrap()),
+            u16::from_le_bytes(bytes[4..6].try_into().unwrap()),
+        )
+    }
+}
+
+/// Stored node cannot be decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidNode(pub String);
+
+/// Node encoding, little endian:
+/// - is_leaf (1 byte), n (2 bytes), next leaf page (4 bytes, `u32::MAX` for none)
+/// - n keys (8 bytes each)
+/// - leaves: n values (`V::SIZE` bytes each), internal nodes: n + 1 child pages (4 bytes each)
+impl<V: NodeValue> BtreeNode<V> {
+    pub(crate) const HEADER_SIZE: usize = 7;
+    const NO_NEXT: u32 = u32::MAX;
+
+    /// Largest encoded node of the minimum degree
+    pub(crate) fn max_encoded_size(t: usize) -> usize {
+        Self::HEADER_SIZE + (2 * t - 1) * 8 + ((2 * t - 1) * V::SIZE).max(2 * t * 4)
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::HEADER_SIZE + self.n * (8 + V::SIZE.max(4)) + 4);
+        bytes.push(self.is_leaf as u8);
+        bytes.extend_from_slice(&(self.n as u16).to_le_bytes());
+        let next = self.next.map_or(Self::NO_NEXT, |next| next as u32);
+        bytes.extend_from_slice(&next.to_le_bytes());
+
+        for key in &self.keys[..self.n] {
+            bytes.extend_from_slice(&key.to_le_bytes());
+        }
+        if self.is_leaf {
+            for value in &self.values[..self.n] {
+                value.encode(&mut bytes);
+            }
+        } else {
+            for child in &self.children[..=self.n] {
+                bytes.extend_from_slice(&(*child as u32).to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Decode a node stored at the page, its slots are sized for the minimum degree
+    pub(crate) fn decode(id: NodeId, bytes: &[u8], t: usize) -> Result<Self, InvalidNode> {
+        if bytes.len() < Self::HEADER_SIZE {
+            return Err(InvalidNode(format!("Node {} is truncated", id)));
+        }
+        let is_leaf = match bytes[0] {
+            0 => false,
+            1 => true,
+            flag => {
+                return Err(InvalidNode(format!(
+                    "Node {} has an invalid leaf flag {}",
+                    id, flag
+                )));
+            }
+        };
+        let n = u16::from_le_bytes(bytes[1..3].try_into().unwrap()) as usize;
+        let next = u32::from_le_bytes(bytes[3..7].try_into().unwrap());
+        let entry_size = match is_leaf {
+            true => V::SIZE,
+            false => 4,
+        };
+        let entries = n + !is_leaf as usize;
+        if n > 2 * t - 1 || bytes.len() != Self::HEADER_SIZE + n * 8 + entries * entry_size {
+            return Err(InvalidNode(format!(
+                "Node {} with {} keys does not match its length {}",
+                id,
+                n,
+                bytes.len()
+            )));
+        }
+
+        let mut node = BtreeNode {
+            id,
+            n,
+            is_leaf,
+            keys: vec![0; 2 * t - 1],
+            values: vec![],
+            children: vec![],
+            next: (next != Self::NO_NEXT).then_some(next as NodeId),
+        };
+        let (keys, rest) = bytes[Self::HEADER_SIZE..].split_at(n * 8);
+        for (slot, key) in node.keys.iter_mut().zip(keys.chunks_exact(8)) {
+            *slot = u64::from_le_bytes(key.try_into().unwrap());
+        }
+        if is_leaf {
+            node.values = vec![V::default(); 2 * t - 1];
+            if V::SIZE > 0 {
+                for (slot, value) in node.values.iter_mut().zip(rest.chunks_exact(V::SIZE)) {
+                    *slot = V::decode(value);
+                }
+            }
+        } else {
+            node.children = vec![0; 2 * t];
+            for (slot, child) in node.children.iter_mut().zip(rest.chunks_exact(4)) {
+                *slot = u32::from_le_bytes(child.try_into().unwrap()) as NodeId;
+            }
+        }
+        Ok(node)
+    }
+}
@@ -0,0 +1,633 @@
+use crate::btree::arena::{BtreeNode, NodeId};
+use crate::btree::node_codec::{InvalidNode, NodeValue};
+use std::collections::HashMap;
+use std::ops::{Bound, RangeBounds};
+
+/// Pages a persistent tree keeps its nodes in, one node per page
+pub trait NodeStore {
+    type Error: From<InvalidNode>;
+
+    /// Largest encoded node a page holds, in bytes
+    fn node_capacity(&self) -> usize;
+
+    /// New page for a node, stored by its first `write_node`
+    fn allocate_node(&mut self) -> Result<u32, Self::Error>;
+
+    fn read_node(&mut self, page: u32) -> Result<Vec<u8>, Self::Error>;
+
+    fn write_node(&mut self, page: u32, bytes: &[u8]) -> Result<(), Self::Error>;
+
+    fn free_node(&mut self, page: u32) -> Result<(), Self::Error>;
+
+    /// Make the written nodes durable
+    fn sync(&mut self) -> Result<(), Self::Error>;
+}
+
+struct CachedNode<V> {
+    node: BtreeNode<V>,
+    // Changed since it was read or written
+    dirty: bool,
+    last_used: u64,
+}
+
+/// Counters of the node cache of a persistent tree
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NodeCacheStats {
+    pub cached_nodes: usize,
+    pub dirty_nodes: usize,
+    /// Nodes read from the store
+    pub node_reads: u64,
+    /// Nodes written to the store, on eviction or flush
+    pub node_writes: u64,
+    /// Node accesses served from the cache
+    pub cache_hits: u64,
+}
+
+/// BTree, in the B+tree variant of `Btree`, with its nodes in the pages of a `NodeStore`.
+///
+/// - Nodes are read on demand and kept in a cache of bounded size, the least recently
+///   used node is evicted first. Changed nodes are written back on eviction and by `flush`,
+///   so the tree can exceed the memory.
+///
+/// - The root page and the number of keys are kept in a meta page, the tree is opened
+///   again from it after a restart.
+///
+/// - Node ids are the page numbers of the store.
+pub struct PersistentBtree<V, S: NodeStore> {
+    // Minimum degree
+    t: usize,
+
+    store: S,
+
+    // Page with the root page, the minimum degree and the number of keys
+    meta_page: u32,
+
+    root_id: NodeId,
+
+    len: usize,
+
+    // Root or number of keys changed since the meta page was written
+    meta_dirty: bool,
+
+    cache: HashMap<NodeId, CachedNode<V>>,
+
+    // Most nodes kept in the cache
+    cache_capacity: usize,
+
+    // Advanced on every node access, orders the cached nodes by their last use
+    clock: u64,
+
+    stats: NodeCacheStats,
+}
+
+impl<V: NodeValue, S: NodeStore> PersistentBtree<V, S> {
+    const META_MAGIC: [u8; 4] = *b"KBPT";
+    const META_SIZE: usize = 20;
+    /// A split touches a parent, a child and its new sibling, a merge three nodes as well
+    const MIN_CACHE_CAPACITY: usize = 4;
+
+    /// Empty tree in new pages of the store, the meta page is written by the next `flush`
+    pub fn create(
+        mut store: S,
+        minimum_degree: usize,
+        cache_capacity: usize,
+    ) -> Result<Self, S::Error> {
+        if minimum_degree < 2
+            || BtreeNode::<V>::max_encoded_size(minimum_degree) > store.node_capacity()
+        {
+            return Err(InvalidNode(format!(
+                "Minimum degree {} does not fit a node in {} bytes",
+                minimum_degree,
+                store.node_capacity()
+            ))
+            .into());
+        }
+
+        let meta_page = store.allocate_node()?;
+        let root_id = store.allocate_node()? as NodeId;
+        let mut tree = Self::new(store, minimum_degree, meta_page, root_id, 0, cache_capacity);
+        tree.put(BtreeNode::new(root_id, minimum_degree, true))?;
+        tree.meta_dirty = true;
+        Ok(tree)
+    }
+
+    /// Tree written to the store by `flush`, found by its meta page
+    pub fn open(mut store: S, meta_page: u32, cache_capacity: usize) -> Result<Self, S::Error> {
+        let bytes = store.read_node(meta_page)?;
+        if bytes.len() != Self::META_SIZE || bytes[..4] != Self::META_MAGIC {
+            return Err(InvalidNode(format!("Page {} is not a tree meta page", meta_page)).into());
+        }
+        let t = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        let root_id = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as NodeId;
+        let len = u64::from_le_bytes(bytes[12..20].try_into().unwrap()) as usize;
+        if t < 2 {
+            return Err(InvalidNode(format!("Invalid minimum degree {}", t)).into());
+        }
+        Ok(Self::new(store, t, meta_page, root_id, len, cache_capacity))
+    }
+
+    fn new(
+        store: S,
+        t: usize,
+        meta_page: u32,
+        root_id: NodeId,
+        len: usize,
+        cache_capacity: usize,
+    ) -> Self {
+        Self {
+            t,
+            store,
+            meta_page,
+            root_id,
+            len,
+            meta_dirty: false,
+            cache: HashMap::new(),
+            cache_capacity: cache_capacity.max(Self::MIN_CACHE_CAPACITY),
+            clock: 0,
+            stats: NodeCacheStats::default(),
+        }
+    }
+
+    /// Page to `open` the tree from
+    pub fn meta_page(&self) -> u32 {
+        self.meta_page
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn cache_stats(&self) -> NodeCacheStats {
+        NodeCacheStats {
+            cached_nodes: self.cache.len(),
+            dirty_nodes: self.cache.values().filter(|cached| cached.dirty).count(),
+            ..self.stats
+        }
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Write the changed nodes and the meta page and sync the store
+    pub fn flush(&mut self) -> Result<(), S::Error> {
+        let mut dirty: Vec<NodeId> = self
+            .cache
+            .iter()
+            .filter(|(_, cached)| cached.dirty)
+            .map(|(id, _)| *id)
+            .collect();
+        dirty.sort_unstable();
+        for id in dirty {
+            let bytes = self.cache[&id].node.encode();
+            self.store.write_node(id as u32, &bytes)?;
+            self.stats.node_writes += 1;
+            if let Some(cached) = self.cache.get_mut(&id) {
+                cached.dirty = false;
+            }
+        }
+
+        if self.meta_dirty {
+            let mut meta = Vec::with_capacity(Self::META_SIZE);
+            meta.extend_from_slice(&Self::META_MAGIC);
+            meta.extend_from_slice(&(self.t as u32).to_le_bytes());
+            meta.extend_from_slice(&(self.root_id as u32).to_le_bytes());
+            meta.extend_from_slice(&(self.len as u64).to_le_bytes());
+            self.store.write_node(self.meta_page, &meta)?;
+            self.meta_dirty = false;
+        }
+        self.store.sync()
+    }
+
+    /// Flush and hand back the store
+    pub fn into_store(mut self) -> Result<S, S::Error> {
+        self.flush()?;
+        Ok(self.store)
+    }
+
+    /// Value stored with the key
+    pub fn get(&mut self, key: u64) -> Result<Option<V>, S::Error> {
+        let id = self.find_leaf(key)?;
+        let leaf = self.node(id)?;
+        Ok(leaf.find_key_index(key).map(|k| leaf.values[k]))
+    }
+
+    pub fn contains_key(&mut self, key: u64) -> Result<bool, S::Error> {
+        Ok(self.get(key)?.is_some())
+    }
+
+    /// Keys in the range and their values, in key order, read leaf by leaf
+    pub fn range<R: RangeBounds<u64>>(
+        &mut self,
+        range: R,
+    ) -> Result<PersistentRange<'_, V, S>, S::Error> {
+        let (leaf, k) = match range.start_bound() {
+            Bound::Included(start) => {
+                let id = self.find_leaf(*start)?;
+                let node = self.node(id)?;
+                (id, node.keys[..node.n].partition_point(|key| key < start))
+            }
+            Bound::Excluded(start) => {
+                let id = self.find_leaf(*start)?;
+                let node = self.node(id)?;
+                (id, node.keys[..node.n].partition_point(|key| key <= start))
+            }
+            Bound::Unbounded => (self.first_leaf()?, 0),
+        };
+
+        Ok(PersistentRange {
+            end: range.end_bound().cloned(),
+            tree: self,
+            leaf: Some(leaf),
+            k,
+        })
+    }
+
+    /// Insert the key with its value, replacing the value of an existing key.
+    /// Returns the replaced value.
+    pub fn insert(&mut self, key: u64, value: V) -> Result<Option<V>, S::Error> {
+        let t = self.t;
+        let leaf_id = self.find_leaf(key)?;
+        let leaf = self.node(leaf_id)?;
+        if let Some(k) = leaf.find_key_index(key) {
+            let mut leaf = leaf.clone();
+            let previous = leaf.values[k];
+            leaf.values[k] = value;
+            self.put(leaf)?;
+            return Ok(Some(previous));
+        }
+
+        if self.node(self.root_id)?.n == 2 * t - 1 {
+            let mut root = self.allocate(false)?;
+            root.children[0] = self.root_id;
+            self.root_id = root.id;
+            self.meta_dirty = true;
+            self.put(root)?;
+            self.split_child(self.root_id, 0)?;
+        }
+
+        // Split full nodes on the way down, the leaf then has room for the key
+        let mut id = self.root_id;
+        loop {
+            let node = self.node(id)?;
+            if node.is_leaf {
+                let mut leaf = node.clone();
+                let pos = leaf.keys[..leaf.n].partition_point(|k| *k < key);
+                leaf.keys.copy_within(pos..leaf.n, pos + 1);
+                leaf.values.copy_within(pos..leaf.n, pos + 1);
+                leaf.keys[pos] = key;
+                leaf.values[pos] = value;
+                leaf.n += 1;
+                self.put(leaf)?;
+                break;
+            }
+
+            let mut pos = node.find_child_index(key);
+            let child_id = node.children[pos];
+            if self.node(child_id)?.n == 2 * t - 1 {
+                self.split_child(id, pos)?;
+                if key >= self.node(id)?.keys[pos] {
+                    pos += 1;
+                }
+            }
+            id = self.node(id)?.children[pos];
+        }
+
+        self.len += 1;
+        self.meta_dirty = true;
+        Ok(None)
+    }
+
+    /// Remove the key, returns its value
+    pub fn delete(&mut self, key: u64) -> Result<Option<V>, S::Error> {
+        let t = self.t;
+        let Some(value) = self.get(key)? else {
+            return Ok(None);
+        };
+
+        // Grow nodes at the minimum on the way down, the leaf then keeps the minimum
+        let mut id = self.root_id;
+        loop {
+            let node = self.node(id)?;
+            if node.is_leaf {
+                let mut leaf = node.clone();
+                if let Some(k) = leaf.find_key_index(key) {
+                    leaf.keys.copy_within(k + 1..leaf.n, k);
+                    leaf.values.copy_within(k + 1..leaf.n, k);
+                    leaf.n -= 1;
+                    self.put(leaf)?;
+                }
+                break;
+            }
+
+            let mut c_k = node.find_child_index(key);
+            let child_id = node.children[c_k];
+            if self.node(child_id)?.n == t - 1 {
+                self.fix_child(id, c_k)?;
+                c_k = self.node(id)?.find_child_index(key);
+            }
+            id = self.node(id)?.children[c_k];
+        }
+
+        self.len -= 1;
+        self.meta_dirty = true;
+
+        // Handle root shrinking
+        let old_root = self.root_id;
+        let root = self.node(old_root)?;
+        if !root.is_leaf && root.n == 0 {
+            self.root_id = root.children[0];
+            self.free(old_root)?;
+        }
+        Ok(Some(value))
+    }
+
+    fn find_leaf(&mut self, key: u64) -> Result<NodeId, S::Error> {
+        let mut id = self.root_id;
+        loop {
+            let node = self.node(id)?;
+            if node.is_leaf {
+                return Ok(id);
+            }
+            id = node.children[node.find_child_index(key)];
+        }
+    }
+
+    fn first_leaf(&mut self) -> Result<NodeId, S::Error> {
+        let mut id = self.root_id;
+        loop {
+            let node = self.node(id)?;
+            if node.is_leaf {
+                return Ok(id);
+            }
+            id = node.children[0];
+        }
+    }
+
+    /// Split the full child k of the parent, see `Btree::split_child`
+    fn split_child(&mut self, p_id: NodeId, k: usize) -> Result<(), S::Error> {
+        let t = self.t;
+        let mut parent = self.node(p_id)?.clone();
+        let mut child = self.node(parent.children[k])?.clone();
+        let mut sibling = self.allocate(child.is_leaf)?;
+
+        let separator = if child.is_leaf {
+            sibling.keys[..t].copy_from_slice(&child.keys[t - 1..2 * t - 1]);
+            sibling.values[..t].copy_from_slice(&child.values[t - 1..2 * t - 1]);
+            sibling.n = t;
+            sibling.next = child.next;
+            child.next = Some(sibling.id);
+            sibling.keys[0]
+        } else {
+            sibling.keys[..t - 1].copy_from_slice(&child.keys[t..2 * t - 1]);
+            sibling.children[..t].copy_from_slice(&child.children[t..2 * t]);
+            sibling.n = t - 1;
+            child.keys[t - 1]
+        };
+        child.n = t - 1;
+
+        let p_n = parent.n;
+        parent.children.copy_within(k + 1..=p_n, k + 2);
+        parent.children[k + 1] = sibling.id;
+        parent.keys.copy_within(k..p_n, k + 1);
+        parent.keys[k] = separator;
+        parent.n += 1;
+
+        self.put(child)?;
+        self.put(sibling)?;
+        self.put(parent)
+    }
+
+    /// Give the child k of the parent more than the minimum keys
+    fn fix_child(&mut self, p_id: NodeId, k: usize) -> Result<(), S::Error> {
+        let t = self.t;
+        let mut parent = self.node(p_id)?.clone();
+        let mut child = self.node(parent.children[k])?.clone();
+
+        if k > 0 {
+            let mut left = self.node(parent.children[k - 1])?.clone();
+            if left.n > t - 1 {
+                borrow_from_left_sibling(&mut parent, &mut left, &mut child, k);
+                self.put(left)?;
+                self.put(child)?;
+                return self.put(parent);
+            }
+            if k == parent.n {
+                merge_children(&mut parent, &mut left, &child, k - 1);
+                self.put(left)?;
+                self.put(parent)?;
+                return self.free(child.id);
+            }
+        }
+
+        let right = self.node(parent.children[k + 1])?.clone();
+        if right.n > t - 1 {
+            let mut right = right;
+            borrow_from_right_sibling(&mut parent, &mut child, &mut right, k);
+            self.put(right)?;
+        } else {
+            merge_children(&mut parent, &mut child, &right, k);
+            self.free(right.id)?;
+        }
+        self.put(child)?;
+        self.put(parent)
+    }
+
+    /// Node from the cache, read from the store on a miss
+    fn node(&mut self, id: NodeId) -> Result<&BtreeNode<V>, S::Error> {
+        self.clock += 1;
+        if let Some(cached) = self.cache.get_mut(&id) {
+            cached.last_used = self.clock;
+            self.stats.cache_hits += 1;
+        } else {
+            let bytes = self.store.read_node(id as u32)?;
+            let node = BtreeNode::decode(id, &bytes, self.t)?;
+            self.stats.node_reads += 1;
+            self.evict()?;
+            self.cache.insert(
+                id,
+                CachedNode {
+                    node,
+                    dirty: false,
+                    last_used: self.clock,
+                },
+            );
+        }
+        Ok(&self.cache[&id].node)
+    }
+
+    /// Replace the cached node, it is written back later
+    fn put(&mut self, node: BtreeNode<V>) -> Result<(), S::Error> {
+        self.clock += 1;
+        if !self.cache.contains_key(&node.id) {
+            self.evict()?;
+        }
+        self.cache.insert(
+            node.id,
+            CachedNode {
+                node,
+                dirty: true,
+                last_used: self.clock,
+            },
+        );
+        Ok(())
+    }
+
+    fn allocate(&mut self, is_leaf: bool) -> Result<BtreeNode<V>, S::Error> {
+        let id = self.store.allocate_node()? as NodeId;
+        Ok(BtreeNode::new(id, self.t, is_leaf))
+    }
+
+    fn free(&mut self, id: NodeId) -> Result<(), S::Error> {
+        self.cache.remove(&id);
+        self.store.free_node(id as u32)
+    }
+
+    /// Make room for a node, the least recently used nodes are written back if changed
+    fn evict(&mut self) -> Result<(), S::Error> {
+        while self.cache.len() >= self.cache_capacity {
+            let Some(id) = self
+                .cache
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(id, _)| *id)
+            else {
+                return Ok(());
+            };
+            if self.cache[&id].dirty {
+                let bytes = self.cache[&id].node.encode();
+                self.store.write_node(id as u32, &bytes)?;
+                self.stats.node_writes += 1;
+            }
+            self.cache.remove(&id);
+        }
+        Ok(())
+    }
+}
+
+fn borrow_from_left_sibling<V: Copy>(
+    parent: &mut BtreeNode<V>,
+    left: &mut BtreeNode<V>,
+    child: &mut BtreeNode<V>,
+    k: usize,
+) {
+    let (lc_n, c_n) = (left.n, child.n);
+    child.keys.copy_within(0..c_n, 1);
+    if child.is_leaf {
+        // Move left sibling's last entry to the child, it becomes the separator
+        child.values.copy_within(0..c_n, 1);
+        child.keys[0] = left.keys[lc_n - 1];
+        child.values[0] = left.values[lc_n - 1];
+        parent.keys[k - 1] = child.keys[0];
+    } else {
+        // Rotate the separator down and left sibling's last key up
+        child.keys[0] = parent.keys[k - 1];
+        parent.keys[k - 1] = left.keys[lc_n - 1];
+        child.children.copy_within(0..=c_n, 1);
+        child.children[0] = left.children[lc_n];
+    }
+    child.n += 1;
+    left.n -= 1;
+}
+
+fn borrow_from_right_sibling<V: Copy>(
+    parent: &mut BtreeNode<V>,
+    child: &mut BtreeNode<V>,
+    right: &mut BtreeNode<V>,
+    k: usize,
+) {
+    let (c_n, rc_n) = (child.n, right.n);
+    if child.is_leaf {
+        // Move right sibling's first entry to the child, its next key becomes the separator
+        child.keys[c_n] = right.keys[0];
+        child.values[c_n] = right.values[0];
+        right.keys.copy_within(1..rc_n, 0);
+        right.values.copy_within(1..rc_n, 0);
+        parent.keys[k] = right.keys[0];
+    } else {
+        // Rotate the separator down and right sibling's first key up
+        child.keys[c_n] = parent.keys[k];
+        parent.keys[k] = right.keys[0];
+        right.keys.copy_within(1..rc_n, 0);
+        child.children[c_n + 1] = right.children[0];
+        right.children.copy_within(1..=rc_n, 0);
+    }
+    child.n += 1;
+    right.n -= 1;
+}
+
+/// Merge the child k + 1 of the parent into the child k
+fn merge_children<V: Copy>(
+    parent: &mut BtreeNode<V>,
+    left: &mut BtreeNode<V>,
+    right: &BtreeNode<V>,
+    k: usize,
+) {
+    let (lc_n, rc_n) = (left.n, right.n);
+    if left.is_leaf {
+        left.keys[lc_n..lc_n + rc_n].copy_from_slice(&right.keys[..rc_n]);
+        left.values[lc_n..lc_n + rc_n].copy_from_slice(&right.values[..rc_n]);
+        left.next = right.next;
+        left.n = lc_n + rc_n;
+    } else {
+        left.keys[lc_n] = parent.keys[k];
+        left.keys[lc_n + 1..lc_n + 1 + rc_n].copy_from_slice(&right.keys[..rc_n]);
+        left.children[lc_n + 1..lc_n + 2 + rc_n].copy_from_slice(&right.children[..=rc_n]);
+        left.n = lc_n + rc_n + 1;
+    }
+
+    let p_n = parent.n;
+    parent.keys.copy_within(k + 1..p_n, k);
+    parent.children.copy_within(k + 2..=p_n, k + 1);
+    parent.n -= 1;
+}
+
+/// Ordered iterator over a key range of a persistent tree, see `PersistentBtree::range`
+pub struct PersistentRange<'a, V, S: NodeStore> {
+    tree: &'a mut PersistentBtree<V, S>,
+    // Current leaf and the index of its next key to return
+    leaf: Option<NodeId>,
+    k: usize,
+    end: Bound<u64>,
+}
+
+impl<V: NodeValue, S: NodeStore> Iterator for PersistentRange<'_, V, S> {
+    type Item = Result<(u64, V), S::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(id) = self.leaf {
+            let node = match self.tree.node(id) {
+                Ok(node) => node,
+                Err(e) => {
+                    self.leaf = None;
+                    return Some(Err(e));
+                }
+            };
+            if self.k == node.n {
+                // Leaf is done, continue in the next one
+                self.leaf = node.next;
+                self.k = 0;
+                continue;
+            }
+
+            let entry = (node.keys[self.k], node.values[self.k]);
+            let past_end = match self.end {
+                Bound::Included(end) => entry.0 > end,
+                Bound::Excluded(end) => entry.0 >= end,
+                Bound::Unbounded => false,
+            };
+            if past_end {
+                self.leaf = None;
+                return None;
+            }
+            self.k += 1;
+            return Some(Ok(entry));
+        }
+
+        None
+    }
+}
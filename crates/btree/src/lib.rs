@@ -1,3 +1,8 @@
 mod btree;
+mod test;
 
-pub use btree::Btree;
\ No newline at end of file
+pub use btree::{
+    Btree, BtreeSnapshot, BtreeStats, ConcurrentBtree, ConcurrentRange, CowBtree, CowRange, Cursor,
+    FILL_BUCKETS, InvalidNode, NodeCacheStats, NodeStore, NodeValue, PersistentBtree,
+    PersistentRange, PoisonedLatch, Range,
+};
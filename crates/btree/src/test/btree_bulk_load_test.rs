@@ -0,0 +1,75 @@
+use crate::Btree;
+
+fn keys_per_leaf(tree: &Btree<u64>) -> Vec<usize> {
+    let mut counts = Vec::new();
+    let mut leaf = Some(tree.first_leaf());
+    while let Some(id) = leaf {
+        counts.push(tree.arena.nodes[id].n);
+        leaf = tree.arena.nodes[id].next;
+    }
+    counts
+}
+
+#[test]
+fn test_bulk_load_matches_inserts() {
+    for minimum_degree in [2, 3, 5] {
+        for count in [0u64, 1, 4, 5, 6, 9, 10, 11, 100, 1000, 1234] {
+            let tree = Btree::bulk_load(minimum_degree, (0..count).map(|key| (key * 2, key)));
+            assert_eq!(tree.len(), count as usize);
+            let expected: Vec<(u64, u64)> = (0..count).map(|key| (key * 2, key)).collect();
+            assert_eq!(tree.entries(), expected);
+            assert_eq!(
+                tree.range(100..=110).count(),
+                (0..count).filter(|key| (50..=55).contains(key)).count()
+            );
+
+            // Every node but the root keeps the minimum
+            let leaves = keys_per_leaf(&tree);
+            if leaves.len() > 1 {
+                assert!(
+                    leaves
+                        .iter()
+                        .all(|n| *n >= minimum_degree - 1 && *n < 2 * minimum_degree)
+                );
+            }
+            for key in 0..count {
+                assert_eq!(tree.get(key * 2), Some(key));
+                assert_eq!(tree.get(key * 2 + 1), None);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_bulk_loaded_leaves_are_packed() {
+    let tree = Btree::bulk_load(3, (0..50u64).map(|key| (key, key)));
+    let leaves = keys_per_leaf(&tree);
+    // 10 full leaves of 5 keys
+    assert_eq!(leaves, vec![5; 10]);
+
+    let tree = Btree::bulk_load(3, (0..51u64).map(|key| (key, key)));
+    assert_eq!(keys_per_leaf(&tree), vec![5, 5, 5, 5, 5, 5, 5, 5, 5, 4, 2]);
+}
+
+#[test]
+fn test_bulk_load_then_modify() {
+    let mut tree = Btree::bulk_load(2, (0..300u64).map(|key| (key, key)));
+    for key in (0..300).step_by(2) {
+        tree.delete(key);
+    }
+    for key in 300..400 {
+        tree.insert(key, key);
+    }
+    let expected: Vec<u64> = (1..300).step_by(2).chain(300..400).collect();
+    assert_eq!(
+        tree.range(..).map(|(key, _)| key).collect::<Vec<_>>(),
+        expected
+    );
+}
+
+#[test]
+fn test_bulk_load_unordered_input() {
+    let tree = Btree::bulk_load(2, [(5, 1), (3, 2), (8, 3), (8, 4), (1, 5)]);
+    assert_eq!(tree.entries(), vec![(1, 5), (3, 2), (5, 1), (8, 4)]);
+    assert_eq!(tree.len(), 4);
+}
@@ -0,0 +1,100 @@
+use crate::Btree;
+
+fn tree_with_keys(minimum_degree: usize, keys: impl Iterator<Item = u64>) -> Btree<u64> {
+    let mut tree = Btree::new(minimum_degree);
+    for key in keys {
+        tree.insert(key, key * 10);
+    }
+    tree
+}
+
+#[test]
+fn test_iter_in_both_directions() {
+    let tree = tree_with_keys(2, (0..300).map(|i| (i * 37) % 300));
+    let keys: Vec<u64> = tree.iter().map(|(key, _)| key).collect();
+    assert_eq!(keys, (0..300).collect::<Vec<_>>());
+
+    let mut cursor = tree.seek_end();
+    let mut keys = vec![];
+    while let Some((key, value)) = cursor.prev() {
+        assert_eq!(value, key * 10);
+        keys.push(key);
+    }
+    assert_eq!(keys, (0..300).rev().collect::<Vec<_>>());
+
+    // At either end the cursor stays in place
+    assert_eq!(cursor.prev(), None);
+    assert_eq!(cursor.next(), Some((0, 0)));
+    let mut cursor = tree.seek_end();
+    assert_eq!(cursor.next(), None);
+    assert_eq!(cursor.prev(), Some((299, 2990)));
+}
+
+#[test]
+fn test_seek() {
+    let tree = tree_with_keys(3, (0..200).map(|i| i * 2));
+
+    let mut cursor = tree.seek(100);
+    assert_eq!(cursor.peek_prev(), Some((98, 980)));
+    assert_eq!(cursor.peek_next(), Some((100, 1000)));
+    assert_eq!(cursor.next(), Some((100, 1000)));
+    assert_eq!(cursor.next(), Some((102, 1020)));
+    assert_eq!(cursor.prev(), Some((102, 1020)));
+    assert_eq!(cursor.prev(), Some((100, 1000)));
+    assert_eq!(cursor.prev(), Some((98, 980)));
+
+    // A missing key seeks to the next one
+    assert_eq!(tree.seek(101).next(), Some((102, 1020)));
+    assert_eq!(tree.seek(101).prev(), Some((100, 1000)));
+    assert_eq!(tree.seek(0).prev(), None);
+    assert_eq!(tree.seek(398).next(), Some((398, 3980)));
+    assert_eq!(tree.seek(399).next(), None);
+    assert_eq!(tree.seek(399).prev(), Some((398, 3980)));
+}
+
+#[test]
+fn test_pagination() {
+    let mut tree = tree_with_keys(2, 0..1000);
+    for key in (0..1000).filter(|key| key % 3 == 0) {
+        tree.delete(key);
+    }
+
+    // Keyset pagination, each page starts after the last key of the previous one
+    let mut pages = vec![];
+    let mut after = None;
+    loop {
+        let cursor = match after {
+            Some(last) => tree.seek(last + 1),
+            None => tree.iter(),
+        };
+        let page: Vec<u64> = cursor.take(25).map(|(key, _)| key).collect();
+        let Some(last) = page.last() else {
+            break;
+        };
+        after = Some(*last);
+        pages.push(page);
+    }
+
+    assert_eq!(pages.len(), 27);
+    assert_eq!(
+        pages.concat(),
+        (0..1000).filter(|key| key % 3 != 0).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_cursor_over_duplicates_and_empty_tree() {
+    let mut tree = Btree::new(2);
+    assert_eq!(tree.iter().next(), None);
+    assert_eq!(tree.seek(5).prev(), None);
+    assert_eq!(tree.seek_end().prev(), None);
+
+    for value in 0..20 {
+        tree.insert_duplicate(value % 2, value);
+    }
+    let mut cursor = tree.seek(1);
+    assert_eq!(cursor.next(), Some((1, 1)));
+    assert_eq!(cursor.prev(), Some((1, 1)));
+    assert_eq!(cursor.prev(), Some((0, 18)));
+    assert_eq!(tree.seek(1).count(), 10);
+}
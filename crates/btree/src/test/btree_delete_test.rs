@@ -0,0 +1,52 @@
+use crate::Btree;
+
+#[test]
+fn test_delete_returns_the_value() {
+    let mut tree = Btree::new(2);
+    for key in 0..20 {
+        tree.insert(key, key * 10);
+    }
+
+    assert_eq!(tree.delete(7), Some(70));
+    assert_eq!(tree.delete(7), None);
+    assert_eq!(tree.delete(100), None);
+    assert_eq!(tree.len(), 19);
+
+    tree.insert_duplicate(3, 31);
+    assert_eq!(tree.delete(3), Some(30));
+    assert_eq!(tree.delete(3), Some(31));
+    assert_eq!(tree.delete(3), None);
+    assert_eq!(Btree::<u64>::new(2).delete(0), None);
+}
+
+#[test]
+fn test_mass_deletes_shrink_the_root() {
+    let mut tree = Btree::new(2);
+    for key in 0..2000 {
+        tree.insert(key, key);
+    }
+    let full_height = tree.height();
+    assert!(full_height > 5);
+
+    for key in 0..1990 {
+        assert_eq!(tree.delete(key), Some(key));
+    }
+    assert!(tree.height() < full_height);
+    tree.check_invariants().unwrap();
+
+    for key in 1990..2000 {
+        tree.delete(key);
+    }
+    assert_eq!(tree.height(), 1);
+    assert!(tree.is_empty());
+    tree.check_invariants().unwrap();
+
+    // Every node but the root leaf is free for reuse
+    assert_eq!(tree.arena.free_count(), tree.arena.nodes.len() - 1);
+    let allocated = tree.arena.nodes.len();
+    for key in 0..2000 {
+        tree.insert(key, key);
+    }
+    assert_eq!(tree.arena.nodes.len(), allocated);
+    assert_eq!(tree.height(), full_height);
+}
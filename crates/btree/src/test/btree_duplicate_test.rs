@@ -0,0 +1,113 @@
+use crate::Btree;
+use crate::test::next_random;
+
+#[test]
+fn test_search_all() {
+    let mut tree = Btree::new(2);
+    // Field value -> document ids, runs of a key span several leaves
+    for document_id in 0..300u64 {
+        tree.insert_duplicate(document_id % 3, document_id);
+    }
+    tree.insert_duplicate(10, 1000);
+
+    assert_eq!(tree.len(), 301);
+    assert_eq!(
+        tree.search_all(1),
+        (0..300).filter(|id| id % 3 == 1).collect::<Vec<_>>()
+    );
+    assert_eq!(tree.search_all(10), vec![1000]);
+    assert!(tree.search_all(5).is_empty());
+    assert_eq!(tree.get(2), Some(2));
+    assert_eq!(tree.range(2..=2).count(), 100);
+
+    // Insert replaces the first entry only
+    assert_eq!(tree.insert(0, 7), Some(0));
+    assert_eq!(tree.search_all(0)[..2], [7, 3]);
+    assert_eq!(tree.len(), 301);
+}
+
+#[test]
+fn test_delete_entry() {
+    let mut tree = Btree::new(2);
+    for document_id in 0..100u64 {
+        tree.insert_duplicate(document_id % 2, document_id);
+    }
+
+    assert!(tree.delete_entry(1, 51));
+    assert!(!tree.delete_entry(1, 51));
+    assert!(!tree.delete_entry(1, 50));
+    assert!(!tree.delete_entry(4, 1));
+    assert_eq!(tree.search_all(1).len(), 49);
+
+    // Delete removes the first entry of the key
+    tree.delete(0);
+    assert_eq!(tree.search_all(0)[0], 2);
+
+    for document_id in (0..100u64).rev() {
+        tree.delete_entry(document_id % 2, document_id);
+    }
+    assert!(tree.is_empty());
+    assert!(tree.range(..).next().is_none());
+}
+
+#[test]
+fn test_random_duplicates() {
+    for minimum_degree in [2, 3, 5] {
+        let mut tree = Btree::new(minimum_degree);
+        // Entries in key order, insertion order within a key
+        let mut expected: Vec<(u64, u64)> = vec![];
+        let mut state = minimum_degree as u64;
+
+        for step in 0..4000u64 {
+            let key = next_random(&mut state) % 40;
+            match next_random(&mut state) % 4 {
+                0 => {
+                    let entries: Vec<u64> = expected
+                        .iter()
+                        .filter(|(k, _)| *k == key)
+                        .map(|(_, v)| *v)
+                        .collect();
+                    if entries.is_empty() {
+                        assert!(!tree.delete_entry(key, step));
+                        continue;
+                    }
+                    let value = entries[next_random(&mut state) as usize % entries.len()];
+                    assert!(tree.delete_entry(key, value));
+                    let index = expected.iter().position(|e| *e == (key, value)).unwrap();
+                    expected.remove(index);
+                }
+                1 => {
+                    tree.delete(key);
+                    if let Some(index) = expected.iter().position(|(k, _)| *k == key) {
+                        expected.remove(index);
+                    }
+                }
+                _ => {
+                    tree.insert_duplicate(key, step);
+                    let index = expected.partition_point(|(k, _)| *k <= key);
+                    expected.insert(index, (key, step));
+                }
+            }
+
+            if step % 200 == 0 {
+                assert_eq!(tree.entries(), expected);
+            }
+        }
+
+        assert_eq!(tree.len(), expected.len());
+        assert_eq!(tree.entries(), expected);
+        for key in 0..40 {
+            let values: Vec<u64> = expected
+                .iter()
+                .filter(|(k, _)| *k == key)
+                .map(|(_, v)| *v)
+                .collect();
+            assert_eq!(tree.search_all(key), values);
+            assert_eq!(tree.get(key), values.first().copied());
+            assert_eq!(
+                tree.range(key..key + 1).map(|(_, v)| v).collect::<Vec<_>>(),
+                values
+            );
+        }
+    }
+}
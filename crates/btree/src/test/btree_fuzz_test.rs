@@ -0,0 +1,177 @@
+use crate::Btree;
+use crate::test::next_random;
+use std::env;
+
+/// Entries in key order, insertion order within a key
+struct Model {
+    entries: Vec<(u64, u64)>,
+}
+
+impl Model {
+    fn insert(&mut self, key: u64, value: u64) -> Option<u64> {
+        let index = self.entries.partition_point(|(k, _)| *k < key);
+        match self.entries.get_mut(index) {
+            Some(entry) if entry.0 == key => Some(std::mem::replace(&mut entry.1, value)),
+            _ => {
+                self.entries.insert(index, (key, value));
+                None
+            }
+        }
+    }
+
+    fn insert_duplicate(&mut self, key: u64, value: u64) {
+        let index = self.entries.partition_point(|(k, _)| *k <= key);
+        self.entries.insert(index, (key, value));
+    }
+
+    fn delete(&mut self, key: u64) -> Option<u64> {
+        let index = self.entries.iter().position(|(k, _)| *k == key)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    fn delete_entry(&mut self, key: u64, value: u64) -> bool {
+        match self.entries.iter().position(|entry| *entry == (key, value)) {
+            Some(index) => {
+                self.entries.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Random operations against the tree and a model, the invariants are checked after each.
+/// Small key spaces make duplicate runs span leaves and deletes hit merges often.
+fn fuzz(seed: u64, minimum_degree: usize, steps: usize) {
+    let mut state = seed;
+    let key_space = 1 + next_random(&mut state) % 500;
+    let initial: Vec<(u64, u64)> = (0..next_random(&mut state) % 300)
+        .map(|i| (i * 2, i))
+        .collect();
+    let mut tree = Btree::bulk_load(minimum_degree, initial.clone());
+    let mut model = Model { entries: initial };
+    let context = |step: usize, op: &str| {
+        format!(
+            "seed {}, degree {}, step {}, {}",
+            seed, minimum_degree, step, op
+        )
+    };
+    tree.check_invariants()
+        .unwrap_or_else(|e| panic!("{}: {}", context(0, "bulk load"), e));
+
+    for step in 0..steps {
+        let key = next_random(&mut state) % key_space;
+        let value = next_random(&mut state);
+        let op = match next_random(&mut state) % 10 {
+            0..=2 => {
+                assert_eq!(tree.insert(key, value), model.insert(key, value));
+                "insert"
+            }
+            3..=4 => {
+                tree.insert_duplicate(key, value);
+                model.insert_duplicate(key, value);
+                "insert duplicate"
+            }
+            5..=7 => {
+                assert_eq!(tree.delete(key), model.delete(key));
+                "delete"
+            }
+            _ => {
+                // Mostly an existing entry of the key
+                let value = tree.search_all(key).first().copied().unwrap_or(value);
+                assert_eq!(
+                    tree.delete_entry(key, value),
+                    model.delete_entry(key, value)
+                );
+                "delete entry"
+            }
+        };
+
+        tree.check_invariants()
+            .unwrap_or_else(|e| panic!("{}: {}", context(step, op), e));
+        assert_eq!(tree.len(), model.entries.len(), "{}", context(step, op));
+    }
+
+    assert_eq!(tree.entries(), model.entries, "{}", context(steps, "end"));
+
+    let mut cursor = tree.seek_end();
+    let mut reversed = vec![];
+    while let Some(entry) = cursor.prev() {
+        reversed.push(entry);
+    }
+    reversed.reverse();
+    assert_eq!(reversed, model.entries, "{}", context(steps, "cursor"));
+}
+
+#[test]
+fn test_fuzz_inserts_and_deletes() {
+    // BTREE_FUZZ_SEEDS runs a longer session, e.g. BTREE_FUZZ_SEEDS=10000
+    let seeds = env::var("BTREE_FUZZ_SEEDS")
+        .ok()
+        .and_then(|seeds| seeds.parse().ok())
+        .unwrap_or(20);
+
+    for seed in 0..seeds {
+        for minimum_degree in [2, 3, 4, 7] {
+            fuzz(seed, minimum_degree, 1000);
+        }
+    }
+}
+
+#[test]
+fn test_fuzz_wide_nodes() {
+    // Binary searches over nodes of up to 127 keys, duplicate runs inside a single node
+    for seed in 0..4 {
+        fuzz(seed, 64, 3000);
+    }
+}
+
+#[test]
+fn test_check_invariants_finds_violations() {
+    let build = || {
+        let mut tree = Btree::new(2);
+        for key in 0..50 {
+            tree.insert(key, key);
+        }
+        tree.check_invariants().unwrap();
+        tree
+    };
+
+    let mut tree = build();
+    let leaf = tree.find_leaf(49);
+    tree.arena.nodes[leaf].keys.swap(0, 1);
+    assert!(
+        tree.check_invariants()
+            .unwrap_err()
+            .contains("out of order")
+    );
+
+    let mut tree = build();
+    let leaf = tree.first_leaf();
+    tree.arena.nodes[leaf].keys[0] = 100;
+    assert!(tree.check_invariants().unwrap_err().contains("separators"));
+
+    let mut tree = build();
+    let leaf = tree.first_leaf();
+    tree.arena.nodes[leaf].n = 0;
+    assert!(tree.check_invariants().unwrap_err().contains("has 0 keys"));
+
+    let mut tree = build();
+    let leaf = tree.first_leaf();
+    tree.arena.nodes[leaf].next = None;
+    assert!(tree.check_invariants().unwrap_err().contains("Leaf chain"));
+
+    let mut tree = build();
+    tree.len += 1;
+    assert!(tree.check_invariants().unwrap_err().contains("length"));
+
+    let mut tree = build();
+    let root = tree.root_id;
+    let first = tree.arena.nodes[root].children[0];
+    tree.arena.nodes[root].children[1] = first;
+    assert!(tree.check_invariants().is_err());
+
+    let mut tree = build();
+    tree.arena.allocate_node(2, true);
+    assert!(tree.check_invariants().unwrap_err().contains("allocated"));
+}
@@ -0,0 +1,72 @@
+use crate::Btree;
+use crate::test::next_random;
+use std::collections::BTreeMap;
+
+/// Keys of the leaves in chain order
+fn leaf_chain(tree: &Btree<u64>) -> Vec<Vec<u64>> {
+    let mut leaves = Vec::new();
+    let mut leaf = Some(tree.first_leaf());
+    while let Some(id) = leaf {
+        let node = &tree.arena.nodes[id];
+        assert!(node.is_leaf);
+        leaves.push(node.keys[..node.n].to_vec());
+        leaf = node.next;
+    }
+    leaves
+}
+
+#[test]
+fn test_random_inserts_and_deletes() {
+    for minimum_degree in [2, 3, 8] {
+        let mut tree = Btree::new(minimum_degree);
+        let mut expected = BTreeMap::new();
+        let mut state = minimum_degree as u64;
+
+        for step in 0..5000u64 {
+            let key = next_random(&mut state) % 1000;
+            if next_random(&mut state).is_multiple_of(3) {
+                tree.delete(key);
+                expected.remove(&key);
+            } else {
+                assert_eq!(tree.insert(key, step), expected.insert(key, step));
+            }
+        }
+
+        assert_eq!(tree.len(), expected.len());
+        let entries: Vec<(u64, u64)> = expected.iter().map(|(k, v)| (*k, *v)).collect();
+        assert_eq!(tree.entries(), entries);
+        for key in 0..1000 {
+            assert_eq!(tree.get(key), expected.get(&key).copied());
+        }
+
+        // Every key is in exactly one leaf, the chain visits the leaves in key order
+        let chained: Vec<u64> = leaf_chain(&tree).into_iter().flatten().collect();
+        assert_eq!(chained, expected.keys().copied().collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn test_leaves_split_into_the_chain() {
+    let mut tree = Btree::new(2);
+    for key in 0..10 {
+        tree.insert(key, key);
+    }
+
+    let root = &tree.arena.nodes[tree.root_id];
+    assert!(!root.is_leaf);
+    let leaves = leaf_chain(&tree);
+    assert!(leaves.len() > 1);
+    assert!(
+        leaves
+            .iter()
+            .all(|keys| !keys.is_empty() && keys.len() <= 3)
+    );
+
+    // Deleting everything merges the leaves back into the root
+    for key in 0..10 {
+        tree.delete(key);
+    }
+    assert!(tree.is_empty());
+    assert!(tree.arena.nodes[tree.root_id].is_leaf);
+    assert_eq!(leaf_chain(&tree), vec![Vec::<u64>::new()]);
+}
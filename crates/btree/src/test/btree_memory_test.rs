@@ -0,0 +1,77 @@
+use crate::Btree;
+
+#[test]
+fn test_memory_usage_follows_the_nodes() {
+    let mut tree = Btree::<u64>::new(4);
+    let empty = tree.memory_usage();
+    assert!(empty > 0);
+
+    for key in 0..10_000 {
+        tree.insert(key, key);
+    }
+    let full = tree.memory_usage();
+    assert!(full > 10_000 * 16);
+    assert_eq!(tree.stats().memory_usage, full);
+
+    // Deallocated nodes release their slots, the node table stays at its peak
+    for key in 0..9_900 {
+        tree.delete(key);
+    }
+    let deleted = tree.memory_usage();
+    assert!(deleted < full * 3 / 4);
+
+    tree.shrink_to_fit();
+    assert!(tree.memory_usage() < full / 20);
+    assert_eq!(tree.arena.nodes.len(), tree.node_count());
+    assert_eq!(tree.arena.free_count(), 0);
+}
+
+#[test]
+fn test_shrink_to_fit_remaps_node_ids() {
+    let mut tree = Btree::new(2);
+    for key in 0..2000 {
+        tree.insert(key, key * 10);
+    }
+    for key in (0..2000).filter(|key| key % 5 != 0) {
+        tree.delete(key);
+    }
+    let allocated = tree.arena.nodes.len();
+    let (old_leaf, k) = tree.search(1500).unwrap();
+
+    let translation = tree.shrink_to_fit();
+    assert_eq!(translation.len(), allocated);
+    assert_eq!(translation.iter().flatten().count(), tree.arena.nodes.len());
+    // Surviving nodes keep their order
+    let new_ids: Vec<usize> = translation.iter().flatten().copied().collect();
+    assert_eq!(new_ids, (0..tree.arena.nodes.len()).collect::<Vec<_>>());
+
+    assert_eq!(tree.search(1500), Some((translation[old_leaf].unwrap(), k)));
+    tree.check_invariants().unwrap();
+    assert_eq!(
+        tree.entries(),
+        (0..2000)
+            .filter(|key| key % 5 == 0)
+            .map(|key| (key, key * 10))
+            .collect::<Vec<_>>()
+    );
+
+    // The compacted tree keeps working
+    for key in 0..2000 {
+        tree.insert(key, key);
+    }
+    for key in 0..1000 {
+        tree.delete(key);
+    }
+    tree.check_invariants().unwrap();
+    assert_eq!(tree.len(), 1000);
+
+    // Nothing to compact twice in a row
+    tree.shrink_to_fit();
+    let translation = tree.shrink_to_fit();
+    assert!(
+        translation
+            .iter()
+            .enumerate()
+            .all(|(id, new_id)| *new_id == Some(id))
+    );
+}
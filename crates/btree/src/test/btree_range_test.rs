@@ -0,0 +1,50 @@
+use crate::Btree;
+
+fn tree_with_keys(minimum_degree: usize, keys: impl Iterator<Item = u64>) -> Btree<u64> {
+    let mut tree = Btree::new(minimum_degree);
+    for key in keys {
+        tree.insert(key, key * 10);
+    }
+    tree
+}
+
+#[test]
+fn test_range_bounds() {
+    // Shuffled insertion order builds internal nodes on several levels
+    let tree = tree_with_keys(2, (0..200).map(|i| (i * 37) % 200 * 2));
+    let keys = |range: Vec<(u64, u64)>| range.into_iter().map(|(key, _)| key).collect::<Vec<_>>();
+
+    assert_eq!(keys(tree.range(10..20).collect()), vec![10, 12, 14, 16, 18]);
+    assert_eq!(
+        keys(tree.range(11..=20).collect()),
+        vec![12, 14, 16, 18, 20]
+    );
+    assert_eq!(keys(tree.range(..5).collect()), vec![0, 2, 4]);
+    assert_eq!(keys(tree.range(395..).collect()), vec![396, 398]);
+    assert!(tree.range(400..).next().is_none());
+    assert!(tree.range(21..22).next().is_none());
+
+    let all: Vec<(u64, u64)> = tree.range(..).collect();
+    assert_eq!(all, tree.entries());
+    assert_eq!(all.len(), 200);
+    assert!(all.iter().all(|(key, value)| *value == key * 10));
+}
+
+#[test]
+fn test_range_after_deletes() {
+    let mut tree = tree_with_keys(3, 0..500);
+    for key in (0..500).filter(|key| key % 3 != 0) {
+        tree.delete(key);
+    }
+
+    let expected: Vec<u64> = (100..=200).filter(|key| key % 3 == 0).collect();
+    let found: Vec<u64> = tree.range(100..=200).map(|(key, _)| key).collect();
+    assert_eq!(found, expected);
+    assert_eq!(tree.range(..).count(), tree.len());
+}
+
+#[test]
+fn test_range_of_empty_tree() {
+    let tree: Btree<u64> = Btree::new(2);
+    assert!(tree.range(..).next().is_none());
+}
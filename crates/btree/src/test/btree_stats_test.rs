@@ -0,0 +1,77 @@
+use crate::{Btree, FILL_BUCKETS};
+
+#[test]
+fn test_stats_of_empty_tree() {
+    let tree = Btree::<u64>::new(3);
+    let stats = tree.stats();
+
+    assert_eq!(tree.height(), 1);
+    assert_eq!(tree.node_count(), 1);
+    assert_eq!(stats.len, 0);
+    assert_eq!(stats.leaf_count, 1);
+    assert_eq!(stats.fill_distribution[0], 1);
+    assert_eq!(stats.average_fill, 0.0);
+}
+
+#[test]
+fn test_stats_of_bulk_loaded_tree() {
+    // Packed leaves of 5 keys, 20 leaves under internal nodes of 6, 6, 5 and 3 children
+    let tree = Btree::bulk_load(3, (0..100).map(|key| (key, key)));
+    let stats = tree.stats();
+
+    assert_eq!(stats.height, 3);
+    assert_eq!(stats.len, 100);
+    assert_eq!(stats.leaf_count, 20);
+    assert_eq!(stats.node_count, 25);
+    assert_eq!(tree.node_count(), 25);
+    assert_eq!(stats.fill_distribution[FILL_BUCKETS - 1], 22);
+    assert_eq!(stats.fill_distribution[8], 1);
+    assert_eq!(stats.fill_distribution[6], 1);
+    assert_eq!(stats.fill_distribution[4], 1);
+    assert_eq!(stats.fill_distribution.iter().sum::<usize>(), 25);
+
+    // Deleted nodes are not counted
+    let mut tree = tree;
+    for key in 0..100 {
+        tree.delete(key);
+    }
+    assert_eq!(tree.node_count(), 1);
+    assert_eq!(tree.height(), 1);
+}
+
+#[test]
+fn test_sequential_inserts_leave_half_full_leaves() {
+    let mut tree = Btree::new(8);
+    for key in 0..10_000 {
+        tree.insert(key, ());
+    }
+    let stats = tree.stats();
+
+    // Splits keep t - 1 of the 2 * t - 1 keys on the left,
+    // only the rightmost node of each level is filled further
+    assert!(stats.average_fill > 0.45 && stats.average_fill < 0.55);
+    assert!(stats.node_count - stats.fill_distribution[4] <= stats.height);
+}
+
+#[test]
+fn test_dump_dot() {
+    let mut tree = Btree::new(2);
+    // The root leaf of 10, 20 and 30 is split by 40
+    for key in 1..=4 {
+        tree.insert(key * 10, ());
+    }
+
+    assert_eq!(
+        tree.dump_dot(),
+        "digraph btree {
+    node [shape=record];
+    n1 [label=\"<c0>|20|<c1>\"];
+    n1:c0 -> n0;
+    n1:c1 -> n2;
+    n0 [label=\"10\"];
+    n0 -> n2 [style=dashed, constraint=false];
+    n2 [label=\"20|30|40\"];
+}
+"
+    );
+}
@@ -0,0 +1,219 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+use crate::btree::concurrent_btree::LatchedNode;
+use crate::test::next_random;
+use crate::{ConcurrentBtree, PoisonedLatch};
+
+/// Verify key order, fill bounds and leaf depth, returns the depth of the leaves
+fn check_node(node: &LatchedNode<u64>, t: usize, is_root: bool, lower: Option<u64>) -> usize {
+    assert!(node.keys.len() < 2 * t);
+    assert!(is_root || node.keys.len() >= t - 1);
+    assert!(node.keys.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(lower.is_none_or(|lower| node.keys.iter().all(|key| *key >= lower)));
+    if node.is_leaf() {
+        assert_eq!(node.values.len(), node.keys.len());
+        return 1;
+    }
+
+    assert!(!node.keys.is_empty());
+    assert_eq!(node.children.len(), node.keys.len() + 1);
+    let depths: Vec<usize> = node
+        .children
+        .iter()
+        .enumerate()
+        .map(|(i, child)| {
+            let lower = if i == 0 {
+                lower
+            } else {
+                Some(node.keys[i - 1])
+            };
+            check_node(&child.read().unwrap(), t, false, lower)
+        })
+        .collect();
+    assert!(depths.iter().all(|depth| *depth == depths[0]));
+    depths[0] + 1
+}
+
+fn check_tree(tree: &ConcurrentBtree<u64>, t: usize) {
+    let root = tree.root.read().unwrap().clone();
+    let depth = check_node(&root.read().unwrap(), t, true, None);
+    assert_eq!(depth, tree.height().unwrap());
+}
+
+#[test]
+fn test_matches_btree_map() {
+    for minimum_degree in [2, 3, 6] {
+        let tree = ConcurrentBtree::new(minimum_degree);
+        let mut expected = BTreeMap::new();
+        let mut state = minimum_degree as u64;
+
+        for step in 0..5000u64 {
+            let key = next_random(&mut state) % 700;
+            if next_random(&mut state) % 5 < 2 {
+                assert_eq!(tree.delete(key).unwrap(), expected.remove(&key));
+            } else {
+                assert_eq!(tree.insert(key, step).unwrap(), expected.insert(key, step));
+            }
+            if step % 500 == 0 {
+                check_tree(&tree, minimum_degree);
+            }
+        }
+
+        check_tree(&tree, minimum_degree);
+        assert_eq!(tree.len(), expected.len());
+        assert_eq!(
+            tree.range(..).collect::<Result<Vec<_>, _>>().unwrap(),
+            expected.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            tree.range(100..=300)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+            expected
+                .range(100..=300)
+                .map(|(k, v)| (*k, *v))
+                .collect::<Vec<_>>()
+        );
+        assert!(tree.range(700..).next().is_none());
+
+        for key in 0..700 {
+            assert_eq!(tree.delete(key).unwrap(), expected.remove(&key));
+        }
+        assert!(tree.is_empty());
+        assert_eq!(tree.height().unwrap(), 1);
+    }
+}
+
+#[test]
+fn test_concurrent_writers() {
+    let tree = Arc::new(ConcurrentBtree::new(3));
+    let threads = 8;
+    let barrier = Arc::new(Barrier::new(threads));
+
+    let writers: Vec<_> = (0..threads as u64)
+        .map(|thread_id| {
+            let tree = tree.clone();
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                barrier.wait();
+                // Interleaved keys make the threads split and merge the same nodes
+                for i in 0..2000 {
+                    tree.insert(i * threads as u64 + thread_id, thread_id)
+                        .unwrap();
+                }
+                for i in (0..2000).filter(|i| i % 2 == 0) {
+                    let key = i * threads as u64 + thread_id;
+                    assert_eq!(tree.delete(key).unwrap(), Some(thread_id));
+                }
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    check_tree(&tree, 3);
+    assert_eq!(tree.len(), threads * 1000);
+    let entries: Vec<(u64, u64)> = tree.range(..).collect::<Result<_, _>>().unwrap();
+    assert_eq!(entries.len(), threads * 1000);
+    assert!(
+        entries
+            .iter()
+            .all(|(key, value)| (key / threads as u64) % 2 == 1 && key % threads as u64 == *value)
+    );
+}
+
+#[test]
+fn test_readers_during_writes() {
+    let tree = Arc::new(ConcurrentBtree::new(2));
+    // Even keys stay for the whole test, odd keys come and go
+    for key in (0..4000).step_by(2) {
+        tree.insert(key, key).unwrap();
+    }
+
+    let writer = {
+        let tree = tree.clone();
+        thread::spawn(move || {
+            for round in 0..5 {
+                for key in (1..4000).step_by(2) {
+                    tree.insert(key, key).unwrap();
+                }
+                for key in (1..4000).step_by(2) {
+                    assert_eq!(tree.delete(key).unwrap(), Some(key));
+                }
+                assert_eq!(tree.len(), 2000, "round {}", round);
+            }
+        })
+    };
+
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let tree = tree.clone();
+            thread::spawn(move || {
+                for _ in 0..20 {
+                    assert!(
+                        (0..4000)
+                            .step_by(2)
+                            .all(|key| tree.get(key) == Ok(Some(key)))
+                    );
+
+                    // Scans see the stable keys once, in order
+                    let keys: Vec<u64> = tree
+                        .range(1000..3000)
+                        .map(|entry| entry.unwrap().0)
+                        .collect();
+                    assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+                    assert_eq!(keys.iter().filter(|key| *key % 2 == 0).count(), 1000);
+                }
+            })
+        })
+        .collect();
+
+    writer.join().unwrap();
+    for reader in readers {
+        reader.join().unwrap();
+    }
+    check_tree(&tree, 2);
+    assert_eq!(
+        tree.range(..)
+            .map(|entry| entry.unwrap().0)
+            .collect::<Vec<_>>(),
+        (0..4000).step_by(2).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_panic_with_write_latch_poisons_tree() {
+    let tree = Arc::new(ConcurrentBtree::new(2));
+    for key in 0..100 {
+        tree.insert(key, key).unwrap();
+    }
+
+    // A writer panics in the middle of changing a leaf
+    let writer = {
+        let tree = tree.clone();
+        thread::spawn(move || {
+            let root = tree.root.read().unwrap().clone();
+            let mut leaf = root.read().unwrap().children[0].clone();
+            while !leaf.read().unwrap().is_leaf() {
+                let child = leaf.read().unwrap().children[0].clone();
+                leaf = child;
+            }
+            let mut guard = leaf.write().unwrap();
+            guard.keys.clear();
+            panic!("writer failed");
+        })
+    };
+    assert!(writer.join().is_err());
+
+    // Operations reaching the leaf fail, the rest of the tree stays readable
+    assert_eq!(tree.get(0), Err(PoisonedLatch));
+    assert_eq!(tree.insert(0, 0), Err(PoisonedLatch));
+    assert_eq!(tree.delete(1), Err(PoisonedLatch));
+    assert_eq!(tree.height(), Err(PoisonedLatch));
+    assert_eq!(tree.get(99), Ok(Some(99)));
+    assert_eq!(tree.range(..).next(), Some(Err(PoisonedLatch)));
+    assert_eq!(tree.range(90..).next(), Some(Ok((90, 90))));
+}
@@ -0,0 +1,185 @@
+use std::sync::Arc;
+use std::thread;
+
+use crate::CowBtree;
+use crate::btree::cow_btree::CowNode;
+
+/// Nodes of the second tree which are also nodes of the first
+fn shared_nodes(a: &Arc<CowNode<u64>>, b: &Arc<CowNode<u64>>) -> usize {
+    fn collect(node: &Arc<CowNode<u64>>, nodes: &mut Vec<*const CowNode<u64>>) {
+        nodes.push(Arc::as_ptr(node));
+        for child in &node.children {
+            collect(child, nodes);
+        }
+    }
+    let (mut a_nodes, mut b_nodes) = (vec![], vec![]);
+    collect(a, &mut a_nodes);
+    collect(b, &mut b_nodes);
+    b_nodes.iter().filter(|node| a_nodes.contains(node)).count()
+}
+
+fn node_count(node: &Arc<CowNode<u64>>) -> usize {
+    1 + node.children.iter().map(node_count).sum::<usize>()
+}
+
+#[test]
+fn test_snapshot_is_immutable() {
+    let mut tree = CowBtree::new(2);
+    for key in 0..100 {
+        tree.insert(key, key * 10);
+    }
+    assert_eq!(tree.commit(), 1);
+    let first = tree.snapshot();
+
+    for key in (0..100).step_by(2) {
+        assert_eq!(tree.delete(key), Some(key * 10));
+    }
+    assert_eq!(tree.insert(1, 11), Some(10));
+    tree.insert(500, 5000);
+    assert!(tree.has_uncommitted_changes());
+
+    // Uncommitted changes are only visible through the tree
+    assert_eq!(tree.snapshot().version(), 1);
+    assert_eq!(tree.get(1), Some(11));
+    assert_eq!(tree.len(), 51);
+    tree.commit_at(7);
+    let second = tree.snapshot();
+
+    assert_eq!(first.version(), 1);
+    assert_eq!(first.len(), 100);
+    assert_eq!(
+        first.entries(),
+        (0..100).map(|key| (key, key * 10)).collect::<Vec<_>>()
+    );
+    assert_eq!(second.version(), 7);
+    assert_eq!(second.len(), 51);
+    assert_eq!(second.get(0), None);
+    assert_eq!(second.get(1), Some(11));
+    assert_eq!(
+        second.range(95..).collect::<Vec<_>>(),
+        vec![(95, 950), (97, 970), (99, 990), (500, 5000)]
+    );
+}
+
+#[test]
+fn test_versions_share_unchanged_nodes() {
+    let mut tree = CowBtree::new(3);
+    for key in 0..1000 {
+        tree.insert(key, key);
+    }
+    tree.commit();
+    let before = tree.snapshot();
+
+    // Only the path to the changed leaf is copied
+    tree.insert(500, 0);
+    tree.commit();
+    let after = tree.snapshot();
+    let nodes = node_count(&after.root);
+    let height = {
+        let mut node = &after.root;
+        let mut height = 1;
+        while !node.is_leaf() {
+            node = &node.children[0];
+            height += 1;
+        }
+        height
+    };
+    assert_eq!(shared_nodes(&before.root, &after.root), nodes - height);
+
+    // Without a snapshot held a node is copied once per version
+    tree.insert(501, 0);
+    let root = Arc::as_ptr(&tree.root);
+    tree.insert(502, 0);
+    assert_eq!(Arc::as_ptr(&tree.root), root);
+}
+
+#[test]
+fn test_rollback() {
+    let mut tree = CowBtree::new(2);
+    tree.insert(1, 1);
+    tree.commit();
+    tree.insert(2, 2);
+    tree.delete(1);
+    tree.rollback();
+    assert!(!tree.has_uncommitted_changes());
+    assert_eq!(tree.range(..).collect::<Vec<_>>(), vec![(1, 1)]);
+    assert_eq!(tree.len(), 1);
+}
+
+#[test]
+#[should_panic]
+fn test_commit_at_older_version() {
+    let mut tree = CowBtree::<u64>::new(2);
+    tree.commit_at(5);
+    tree.commit_at(5);
+}
+
+#[test]
+fn test_snapshot_reads_from_other_threads() {
+    let mut tree = CowBtree::new(4);
+    for key in 0..1000 {
+        tree.insert(key, key);
+    }
+    tree.commit();
+
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let snapshot = tree.snapshot();
+            thread::spawn(move || {
+                for _ in 0..10 {
+                    assert_eq!(snapshot.range(..).count(), 1000);
+                    assert!((0..1000).all(|key| snapshot.get(key) == Some(key)));
+                }
+            })
+        })
+        .collect();
+
+    // The writer keeps changing the tree meanwhile
+    for key in 0..1000 {
+        tree.delete(key);
+        if key % 100 == 0 {
+            tree.commit();
+        }
+    }
+    for reader in readers {
+        reader.join().unwrap();
+    }
+    tree.commit();
+    assert!(tree.snapshot().is_empty());
+}
+
+#[test]
+fn test_matches_btree() {
+    let mut tree = CowBtree::new(2);
+    let mut expected = std::collections::BTreeMap::new();
+    let mut snapshots = vec![];
+    for i in 0..3000u64 {
+        let key = (i * 7919) % 1009;
+        if i % 3 == 0 {
+            assert_eq!(tree.delete(key), expected.remove(&key));
+        } else {
+            assert_eq!(tree.insert(key, i), expected.insert(key, i));
+        }
+        if i % 250 == 0 {
+            tree.commit();
+            snapshots.push((tree.snapshot(), expected.clone()));
+        }
+    }
+
+    for (snapshot, expected) in snapshots {
+        assert_eq!(snapshot.len(), expected.len());
+        assert_eq!(snapshot.entries(), expected.into_iter().collect::<Vec<_>>());
+        assert_eq!(
+            snapshot
+                .range(100..=200)
+                .map(|(key, _)| key)
+                .collect::<Vec<_>>(),
+            snapshot
+                .entries()
+                .into_iter()
+                .map(|(key, _)| key)
+                .filter(|key| (100..=200).contains(key))
+                .collect::<Vec<_>>()
+        );
+    }
+}
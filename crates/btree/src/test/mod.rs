@@ -0,0 +1,33 @@
+#[cfg(test)]
+mod btree_bulk_load_test;
+#[cfg(test)]
+mod btree_cursor_test;
+#[cfg(test)]
+mod btree_delete_test;
+#[cfg(test)]
+mod btree_duplicate_test;
+#[cfg(test)]
+mod btree_fuzz_test;
+#[cfg(test)]
+mod btree_leaf_chain_test;
+#[cfg(test)]
+mod btree_memory_test;
+#[cfg(test)]
+mod btree_range_test;
+#[cfg(test)]
+mod btree_stats_test;
+#[cfg(test)]
+mod concurrent_btree_test;
+#[cfg(test)]
+mod cow_btree_test;
+#[cfg(test)]
+mod persistent_btree_test;
+
+/// Deterministic pseudo random numbers
+#[cfg(test)]
+fn next_random(state: &mut u64) -> u64 {
+    *state = state
+        .wrapping_mul(6364136223846793005)
+        .wrapping_add(1442695040888963407);
+    *state >> 33
+}
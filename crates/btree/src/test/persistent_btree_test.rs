@@ -0,0 +1,112 @@
+use crate::{InvalidNode, NodeStore, PersistentBtree};
+use std::collections::BTreeMap;
+
+/// Node pages kept in memory
+#[derive(Default)]
+struct VecStore {
+    pages: Vec<Option<Vec<u8>>>,
+    free: Vec<u32>,
+    reads: usize,
+}
+
+impl NodeStore for VecStore {
+    type Error = InvalidNode;
+
+    fn node_capacity(&self) -> usize {
+        4068
+    }
+
+    fn allocate_node(&mut self) -> Result<u32, InvalidNode> {
+        Ok(self.free.pop().unwrap_or_else(|| {
+            self.pages.push(None);
+            self.pages.len() as u32 - 1
+        }))
+    }
+
+    fn read_node(&mut self, page: u32) -> Result<Vec<u8>, InvalidNode> {
+        self.reads += 1;
+        self.pages
+            .get(page as usize)
+            .cloned()
+            .flatten()
+            .ok_or_else(|| InvalidNode(format!("Page {} was not written", page)))
+    }
+
+    fn write_node(&mut self, page: u32, bytes: &[u8]) -> Result<(), InvalidNode> {
+        self.pages[page as usize] = Some(bytes.to_vec());
+        Ok(())
+    }
+
+    fn free_node(&mut self, page: u32) -> Result<(), InvalidNode> {
+        self.pages[page as usize] = None;
+        self.free.push(page);
+        Ok(())
+    }
+
+    fn sync(&mut self) -> Result<(), InvalidNode> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_tree_survives_reopen() {
+    let mut tree = PersistentBtree::create(VecStore::default(), 4, 8).unwrap();
+    for key in (0..2000u64).rev() {
+        assert_eq!(tree.insert(key, (key as u32, key as u16)).unwrap(), None);
+    }
+    assert_eq!(tree.insert(7, (0, 0)).unwrap(), Some((7, 7)));
+    // A small cache writes nodes back as they are evicted
+    assert!(tree.cache_stats().cached_nodes <= 8);
+    assert!(tree.cache_stats().node_writes > 0);
+
+    let meta_page = tree.meta_page();
+    let store = tree.into_store().unwrap();
+    let mut tree: PersistentBtree<(u32, u16), _> =
+        PersistentBtree::open(store, meta_page, 8).unwrap();
+    assert_eq!(tree.len(), 2000);
+    assert_eq!(tree.get(7).unwrap(), Some((0, 0)));
+    assert_eq!(tree.get(1999).unwrap(), Some((1999, 1999)));
+    assert_eq!(tree.get(2000).unwrap(), None);
+
+    let keys: Vec<u64> = tree
+        .range(100..110)
+        .unwrap()
+        .map(|entry| entry.unwrap().0)
+        .collect();
+    assert_eq!(keys, (100..110).collect::<Vec<_>>());
+    assert_eq!(tree.range(..).unwrap().count(), 2000);
+}
+
+#[test]
+fn test_matches_in_memory_map() {
+    let mut tree = PersistentBtree::create(VecStore::default(), 2, 4).unwrap();
+    let mut expected = BTreeMap::new();
+    let mut state = 17u64;
+    for step in 0..3000u64 {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        let key = (state >> 33) % 300;
+        if step % 3 == 0 {
+            assert_eq!(tree.delete(key).unwrap(), expected.remove(&key));
+        } else {
+            assert_eq!(tree.insert(key, step).unwrap(), expected.insert(key, step));
+        }
+    }
+
+    assert_eq!(tree.len(), expected.len());
+    let entries: Vec<(u64, u64)> = tree.range(..).unwrap().map(Result::unwrap).collect();
+    assert_eq!(entries, expected.into_iter().collect::<Vec<_>>());
+
+    // Freed nodes are handed back to the store
+    tree.flush().unwrap();
+    let store = tree.store();
+    let live = store.pages.iter().filter(|page| page.is_some()).count();
+    assert_eq!(live + store.free.len(), store.pages.len());
+}
+
+#[test]
+fn test_degree_must_fit_a_page() {
+    assert!(PersistentBtree::<u64, _>::create(VecStore::default(), 200, 8).is_err());
+    assert!(PersistentBtree::<u64, _>::open(VecStore::default(), 0, 8).is_err());
+}
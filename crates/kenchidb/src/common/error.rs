@@ -8,6 +8,36 @@ pub enum DatabaseError {
     InvalidData(String),
     DocumentNotFound(u64),
     InvalidQuery(String),
+    InvalidIdentifier(String),
+    DuplicateKey(String),
+    /// Stored document does not match its checksum
+    CorruptDocument {
+        id: u64,
+    },
+    /// Page read from a file does not match the checksum in its header,
+    /// e.g. torn by a crash in the middle of a write or damaged on disk.
+    /// Reopening a paged collection without its primary key index checks every page
+    /// and repairs or quarantines damaged ones, see `PagedCollection::rebuild_primary_key_index`.
+    Corruption {
+        page_id: u32,
+        expected: u32,
+        actual: u32,
+    },
+    /// Opening was cancelled by the progress callback
+    Cancelled,
+    /// Document was written since the version the caller expected
+    Conflict {
+        id: u64,
+        expected: u64,
+        actual: u64,
+    },
+    /// Collection holds the writes of a prepared transaction which is not decided yet
+    TransactionPrepared(u64),
+    /// File is locked by another writer, in this or another process
+    Locked(String),
+    /// Shared database was held exclusively by a thread that panicked,
+    /// the database may be left half updated
+    Poisoned,
 }
 
 impl From<io::Error> for DatabaseError {
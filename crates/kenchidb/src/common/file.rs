@@ -0,0 +1,30 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io,
+    path::Path,
+};
+
+/// Give the file a second name, the target must not exist.
+/// Files are hard linked, across file systems they are copied
+/// through a temporary file so the target never appears half written.
+pub fn link_or_copy(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::hard_link(from, to) {
+        Err(e) if e.kind() != io::ErrorKind::AlreadyExists => {}
+        result => return result,
+    }
+
+    let mut temporary = to.as_os_str().to_os_string();
+    temporary.push(".tmp");
+    let temporary = Path::new(&temporary);
+
+    let mut source = File::open(from)?;
+    let mut target = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(temporary)?;
+    let result = io::copy(&mut source, &mut target)
+        .and_then(|_| target.sync_all())
+        .and_then(|_| fs::hard_link(temporary, to));
+    let _ = fs::remove_file(temporary);
+    result
+}
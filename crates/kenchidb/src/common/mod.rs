@@ -1,3 +1,11 @@
+mod checksum;
 mod error;
+mod file;
+mod progress;
+mod random;
 
+pub(crate) use self::checksum::*;
 pub(crate) use self::error::*;
+pub(crate) use self::file::*;
+pub(crate) use self::progress::*;
+pub(crate) use self::random::*;
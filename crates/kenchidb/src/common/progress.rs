@@ -0,0 +1,93 @@
+use std::{
+    ops::ControlFlow,
+    time::{Duration, Instant},
+};
+
+use crate::common::DatabaseError;
+
+/// Bytes processed between two progress reports
+const REPORT_STEP: u64 = 64 * 1024;
+
+/// Progress of reading a file while a collection is opened,
+/// see `Collection::with_file_progress`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecoveryProgress {
+    pub bytes_processed: u64,
+    pub bytes_total: u64,
+    /// Time since opening started
+    pub elapsed: Duration,
+}
+
+impl RecoveryProgress {
+    /// Processed share of the file, between 0 and 1
+    pub fn fraction(&self) -> f64 {
+        if self.bytes_total == 0 {
+            return 1.0;
+        }
+        self.bytes_processed as f64 / self.bytes_total as f64
+    }
+
+    /// Time left at the rate observed so far, `None` until the first bytes are processed
+    pub fn eta(&self) -> Option<Duration> {
+        if self.bytes_processed == 0 {
+            return None;
+        }
+        let remaining = self.bytes_total.saturating_sub(self.bytes_processed);
+        Some(
+            self.elapsed
+                .mul_f64(remaining as f64 / self.bytes_processed as f64),
+        )
+    }
+}
+
+/// Forwards progress to a callback every `REPORT_STEP` bytes and once at the end.
+/// The callback cancels opening by returning `ControlFlow::Break`.
+pub(crate) struct ProgressTracker<'a> {
+    callback: &'a mut dyn FnMut(&RecoveryProgress) -> ControlFlow<()>,
+    bytes_total: u64,
+    reported: u64,
+    started: Instant,
+}
+
+impl<'a> ProgressTracker<'a> {
+    pub fn new(
+        callback: &'a mut dyn FnMut(&RecoveryProgress) -> ControlFlow<()>,
+        bytes_total: u64,
+    ) -> Self {
+        Self {
+            callback,
+            bytes_total,
+            reported: 0,
+            started: Instant::now(),
+        }
+    }
+
+    /// Record the bytes processed so far, fails with `Cancelled` if the callback says so
+    pub fn advance(&mut self, bytes_processed: u64) -> Result<(), DatabaseError> {
+        if bytes_processed < self.reported + REPORT_STEP && bytes_processed < self.bytes_total {
+            return Ok(());
+        }
+        self.report(bytes_processed)
+    }
+
+    /// Report the end of the file, also when it was empty
+    pub fn finish(&mut self) -> Result<(), DatabaseError> {
+        if self.reported == self.bytes_total && self.bytes_total > 0 {
+            return Ok(());
+        }
+        self.report(self.bytes_total)
+    }
+
+    fn report(&mut self, bytes_processed: u64) -> Result<(), DatabaseError> {
+        self.reported = bytes_processed;
+        let progress = RecoveryProgress {
+            bytes_processed,
+            bytes_total: self.bytes_total,
+            elapsed: self.started.elapsed(),
+        };
+        match (self.callback)(&progress) {
+            ControlFlow::Continue(()) => Ok(()),
+            ControlFlow::Break(()) => Err(DatabaseError::Cancelled),
+        }
+    }
+}
@@ -1,69 +1,548 @@
 use std::{
-    collections::HashMap,
-    fs::{File, OpenOptions},
-    io::{Read, Seek, SeekFrom, Write},
-    path::Path,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{Read, Write},
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
 };
 
-use crate::schema::{Document, Schema};
-use crate::{common::DatabaseError, schema::Value};
+use ::storage::{Durability, sync_parent_directory};
+
+use crate::schema::{
+    Document, FieldType, IdentifierKind, Schema, TtlPolicy, current_time_millis, encode_key,
+    validate_identifier,
+};
+use crate::{
+    common::{DatabaseError, ProgressTracker, RecoveryProgress, crc32, link_or_copy},
+    export::DatabaseSnapshot,
+    index::{
+        SecondaryIndex, index_definitions_path, index_entries_path, load_index_definitions,
+        load_index_entries, save_index_definitions, save_index_entries,
+    },
+    query::{ByteReader, FindQuery, LookupRow, QueryResult, View, hash_join},
+    schema::Value,
+    search::{TextIndex, load_text_index, save_text_index, text_index_path},
+    session::{Session, Snapshot},
+    storage::IoStats,
+    storage::catalog::{Catalog, load_catalog, save_catalog},
+    storage::paged_collection::VacuumReport,
+    storage::recovery::RecoveryReport,
+    storage::wal::{
+        DEFAULT_CHECKPOINT_SIZE, SyncMode, TransactionLog, WalRecord, WriteAheadLog,
+        transaction_log_path, wal_path,
+    },
+    transaction::PreparedWrites,
+    watch::{ChangeEvent, Watcher},
+};
 
 // Collection - stores documents with a specific schema
 pub struct Collection {
     pub schema: Schema,
-    pub documents: HashMap<u64, Document>,
+    /// Documents by id, shared with the snapshots and sessions started since the last write.
+    /// A write copies the map first while it is shared, see `Arc::make_mut`.
+    pub documents: Arc<HashMap<u64, Document>>,
     pub next_id: u64,
     pub file: Option<File>,
+    /// Path of the backing file, set together with `file`
+    pub path: Option<PathBuf>,
+    pub ttl: Option<TtlPolicy>,
+    /// Encoded primary key -> document id, empty without a primary key
+    pub primary_index: BTreeMap<Vec<u8>, u64>,
+    /// Next value of each auto increment field
+    pub sequences: HashMap<String, i64>,
+    /// Full-text index of the schema's text fields, `None` without text fields
+    pub text_index: Option<TextIndex>,
+    /// Secondary indexes by field name, see `Collection::create_index`
+    pub indexes: BTreeMap<String, SecondaryIndex>,
+    /// Side log of each online index build by field name, the ids of the documents
+    /// written since the build started, see `Collection::start_index_build`
+    pub(crate) index_builds: BTreeMap<String, BTreeSet<u64>>,
+    /// Number of writes applied since the collection was created or opened,
+    /// a committed session counts as one write
+    pub version: u64,
+    /// Subscribers to document changes, see `Collection::watch`
+    pub(crate) watchers: Vec<Watcher>,
+    /// Log of the writes since the collection file was last written, set together with `file`
+    pub(crate) wal: Option<WriteAheadLog>,
+    /// Writes not in the log yet, `None` if writes are not logged.
+    pub(crate) pending_writes: Option<Vec<WalRecord>>,
+    /// What opening the collection file recovered from its write-ahead log
+    pub(crate) recovery: RecoveryReport,
+    /// Writes of a transaction prepared for an external coordinator, applied once it
+    /// commits. The collection takes no other writes meanwhile, see `Transaction::prepare`.
+    pub(crate) prepared: Option<PreparedWrites>,
+    /// Reads and writes of the collection file and its write-ahead log since it was opened
+    pub(crate) io_stats: IoStats,
 }
 
 impl Collection {
     pub fn new(schema: Schema) -> Self {
         Self {
+            text_index: TextIndex::for_schema(&schema),
             schema,
-            documents: HashMap::new(),
+            documents: Arc::default(),
             next_id: 1,
             file: None,
+            path: None,
+            ttl: None,
+            primary_index: BTreeMap::new(),
+            sequences: HashMap::new(),
+            indexes: BTreeMap::new(),
+            index_builds: BTreeMap::new(),
+            version: 0,
+            watchers: Vec::new(),
+            wal: None,
+            pending_writes: None,
+            recovery: RecoveryReport::default(),
+            prepared: None,
+            io_stats: IoStats::default(),
         }
     }
 
     pub fn with_file<P: AsRef<Path>>(schema: Schema, path: P) -> Result<Self, DatabaseError> {
+        Self::with_file_progress(schema, path, |_| ControlFlow::Continue(()))
+    }
+
+    /// Open the collection file like `with_file`, reporting how much of the file was read.
+    /// The callback is called every 64 KiB and once the file is read, returning
+    /// `ControlFlow::Break` stops opening with `DatabaseError::Cancelled`.
+    /// The collection file is only read, a cancelled open leaves it as it was.
+    pub fn with_file_progress<P, F>(
+        schema: Schema,
+        path: P,
+        progress: F,
+    ) -> Result<Self, DatabaseError>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&RecoveryProgress) -> ControlFlow<()>,
+    {
+        Self::open_file(schema, path, progress, None)
+    }
+
+    /// Open the collection file, a transaction prepared at the end of the write-ahead log
+    /// is kept if it is in the database's transaction log and discarded otherwise.
+    /// The writes of a transaction in doubt there are kept aside for its coordinator.
+    /// Without a transaction log a prepared transaction cannot be resolved and opening fails.
+    pub(crate) fn open_file<P, F>(
+        schema: Schema,
+        path: P,
+        mut progress: F,
+        transactions: Option<&mut TransactionLog>,
+    ) -> Result<Self, DatabaseError>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&RecoveryProgress) -> ControlFlow<()>,
+    {
+        // Left by a crash while the collection file was written, the file itself is intact
+        let temporary = collection_temporary_path(path.as_ref());
+        if temporary.exists() {
+            fs::remove_file(&temporary)?;
+        }
+
         let file = OpenOptions::new()
             .create(true)
+            .truncate(false)
             .read(true)
             .write(true)
-            .open(path)?;
+            .open(&path)?;
 
         let mut collection = Self {
+            text_index: TextIndex::for_schema(&schema),
             schema,
-            documents: HashMap::new(),
+            documents: Arc::default(),
             next_id: 1,
             file: Some(file),
+            path: Some(path.as_ref().to_path_buf()),
+            ttl: None,
+            primary_index: BTreeMap::new(),
+            sequences: HashMap::new(),
+            indexes: BTreeMap::new(),
+            index_builds: BTreeMap::new(),
+            version: 0,
+            watchers: Vec::new(),
+            wal: None,
+            pending_writes: None,
+            recovery: RecoveryReport::default(),
+            prepared: None,
+            io_stats: IoStats::default(),
         };
 
-        collection.load_from_file()?;
+        collection.load_from_file(&mut progress)?;
+
+        // Writes made after the file was last written
+        let (mut wal, mut records) = WriteAheadLog::open(wal_path(path.as_ref()))?;
+        let mut report = RecoveryReport {
+            wal_bytes_discarded: wal.torn_bytes(),
+            ..RecoveryReport::default()
+        };
+        let prepared = match records.last() {
+            Some(WalRecord::Prepare { transaction, .. }) => Some(*transaction),
+            _ => None,
+        };
+        let mut decided = None;
+        let mut in_doubt = None;
+        if let Some(transaction) = prepared {
+            let Some(log) = transactions else {
+                return Err(DatabaseError::InvalidData(format!(
+                    "Write-ahead log '{}' ends with a prepared transaction, \
+                     open the collection through its database",
+                    wal.path().display()
+                )));
+            };
+            if log.is_decided(transaction, wal.path()) {
+                report.prepared_transaction = Some((transaction, true));
+                decided = Some((transaction, log));
+            } else {
+                let committed = records
+                    .iter()
+                    .rposition(|record| matches!(record, WalRecord::Commit { .. }))
+                    .map_or(0, |position| position + 1);
+                let prepared = records.split_off(committed);
+                if log.is_in_doubt(transaction, wal.path()) {
+                    // Kept aside for the coordinator's decision
+                    report.in_doubt_transaction = Some(transaction);
+                    in_doubt = Some(PreparedWrites {
+                        transaction,
+                        records: prepared,
+                    });
+                } else {
+                    // The transaction never committed
+                    report.prepared_transaction = Some((transaction, false));
+                    let size = wal.size();
+                    wal.discard_prepared()?;
+                    report.wal_bytes_discarded += size - wal.size();
+                }
+            }
+        }
+
+        report.wal_records_replayed = records
+            .iter()
+            .filter(|record| matches!(record, WalRecord::Put(_) | WalRecord::Delete(_)))
+            .count();
+        collection.replay(records)?;
+        if let Some((transaction, log)) = decided {
+            wal.append(&[collection.commit_record()])?;
+            log.resolve(transaction, &[wal.path().to_path_buf()])?;
+        }
+        collection.wal = Some(wal);
+        collection.pending_writes = Some(Vec::new());
+        collection.recovery = report;
+        collection.prepared = in_doubt;
         Ok(collection)
     }
 
+    /// What opening the collection file recovered from its write-ahead log:
+    /// writes replayed, torn or uncommitted bytes cut off and the outcome of a
+    /// transaction prepared at its end. Empty for collections without a file.
+    pub fn recovery_report(&self) -> &RecoveryReport {
+        &self.recovery
+    }
+
+    /// Reads and writes of the collection file and its write-ahead log since it was
+    /// opened, index files excluded. Zero for collections without a file.
+    pub fn io_stats(&self) -> IoStats {
+        self.io_stats
+    }
+
+    /// Insert a document, returns its id.
+    /// Auto increment fields missing from the document are set from the field's sequence.
+    /// Sequence values are unique and increasing but not contiguous: values of deleted
+    /// documents are not reused and explicit values move the sequence past them.
+    /// Sequences are saved together with the documents, after a crash they resume from
+    /// the last saved state, so values of inserts lost in the crash are handed out again.
     pub fn insert(&mut self, mut document: Document) -> Result<u64, DatabaseError> {
         document.id = self.next_id;
+        let sequences = assign_sequences(
+            &self.schema,
+            &self.sequences,
+            &self.documents,
+            &mut document,
+        )?;
+        self.schema.normalize_document(&mut document);
         self.schema.validate_document(&document)?;
 
-        self.documents.insert(document.id, document);
+        self.store_documents(vec![document])?;
         self.next_id += 1;
+        self.sequences.extend(sequences);
 
-        if self.file.is_some() {
-            self.save_to_file()?;
-        }
-
+        self.persist()?;
         Ok(self.next_id - 1)
     }
 
     pub fn find_by_id(&self, id: u64) -> Option<&Document> {
-        self.documents.get(&id)
+        let now = current_time_millis();
+        self.documents
+            .get(&id)
+            .filter(|document| !self.is_expired(document, now))
     }
 
     pub fn find_all(&self) -> Vec<&Document> {
-        self.documents.values().collect()
+        self.live_documents().collect()
+    }
+
+    /// Expire documents `expire_after` past the timestamp stored in the given long field.
+    /// Expired documents are hidden from reads and removed by `purge_expired`.
+    /// The catalog of a database records the policy, see `Database::set_ttl`.
+    pub fn set_ttl(&mut self, field: &str, expire_after: Duration) -> Result<(), DatabaseError> {
+        self.ttl = Some(TtlPolicy::new(&self.schema, field, expire_after)?);
+        Ok(())
+    }
+
+    pub fn clear_ttl(&mut self) {
+        self.ttl = None;
+    }
+
+    /// Remove expired documents, returns the number of removed documents
+    pub fn purge_expired(&mut self) -> Result<usize, DatabaseError> {
+        let Some(ttl) = &self.ttl else {
+            return Ok(0);
+        };
+        self.check_not_prepared()?;
+
+        let now = current_time_millis();
+        let count = self.documents.len();
+        let watched = self.is_watched();
+        let mut purged = Vec::new();
+        let mut purged_ids = Vec::new();
+        Arc::make_mut(&mut self.documents).retain(|id, document| {
+            let expired = ttl.is_expired(document, now);
+            if expired {
+                purged_ids.push(*id);
+                if watched {
+                    purged.push(ChangeEvent::Delete(document.clone()));
+                }
+            }
+            !expired
+        });
+        if let Some(pending) = &mut self.pending_writes {
+            pending.extend(purged_ids.iter().map(|id| WalRecord::Delete(*id)));
+        }
+        self.log_index_build_writes(purged_ids);
+        let removed = count - self.documents.len();
+
+        if removed > 0 {
+            self.rebuild_primary_index()?;
+            self.rebuild_text_index();
+            self.rebuild_secondary_indexes();
+            self.version += 1;
+        }
+        self.notify(purged);
+
+        self.persist()?;
+        Ok(removed)
+    }
+
+    /// Documents which are not expired yet
+    pub(crate) fn live_documents(&self) -> impl Iterator<Item = &Document> {
+        let now = current_time_millis();
+        self.documents
+            .values()
+            .filter(move |document| !self.is_expired(document, now))
+    }
+
+    pub(crate) fn is_expired(&self, document: &Document, now: i64) -> bool {
+        self.ttl
+            .as_ref()
+            .is_some_and(|ttl| ttl.is_expired(document, now))
+    }
+
+    /// Find a document by its primary key values
+    pub fn find_by_key(&self, key: &[Value]) -> Option<&Document> {
+        self.primary_index
+            .get(&encode_key(key))
+            .and_then(|id| self.find_by_id(*id))
+    }
+
+    /// Documents whose primary key starts with the given values, in key order.
+    /// E.g. all users of a tenant with a `(tenant_id, user_id)` key.
+    pub fn scan_key_prefix(&self, prefix: &[Value]) -> Vec<&Document> {
+        let prefix = encode_key(prefix);
+        let now = current_time_millis();
+
+        self.primary_index
+            .range(prefix.clone()..)
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .filter_map(|(_, id)| self.documents.get(id))
+            .filter(|document| !self.is_expired(document, now))
+            .collect()
+    }
+
+    /// Store new or changed documents, keeping the primary key and secondary indexes in sync.
+    /// Nothing is stored if a key or a unique index value collides with another document.
+    pub(crate) fn store_documents(
+        &mut self,
+        mut documents: Vec<Document>,
+    ) -> Result<(), DatabaseError> {
+        self.check_not_prepared()?;
+        for document in &mut documents {
+            document.version = self.next_version(document.id);
+        }
+        let keys = self.check_keys(&documents, &HashSet::new())?;
+
+        if let Some(pending) = &mut self.pending_writes {
+            pending.extend(documents.iter().cloned().map(WalRecord::Put));
+        }
+        let mut events = Vec::new();
+        if self.is_watched() {
+            for document in &documents {
+                events.push(match self.documents.get(&document.id) {
+                    Some(before) => ChangeEvent::Update {
+                        before: before.clone(),
+                        after: document.clone(),
+                    },
+                    None => ChangeEvent::Insert(document.clone()),
+                });
+            }
+        }
+
+        self.write_documents(documents, &HashSet::new(), keys);
+        self.version += 1;
+        self.notify(events);
+        Ok(())
+    }
+
+    /// Version of the document once it is written, 1 for a new document
+    pub(crate) fn next_version(&self, id: u64) -> u64 {
+        self.documents
+            .get(&id)
+            .map_or(1, |stored| stored.version + 1)
+    }
+
+    /// Check that the documents can be stored in one write together with the removal of
+    /// the `removed` documents, whose keys and unique index values are free by then.
+    /// Fails if a key or a unique index value collides with another document.
+    pub(crate) fn check_keys(
+        &self,
+        documents: &[Document],
+        removed: &HashSet<u64>,
+    ) -> Result<KeyChanges, DatabaseError> {
+        let stored: HashSet<u64> = documents.iter().map(|document| document.id).collect();
+        let mut keys = KeyChanges::default();
+
+        if self.schema.primary_key.is_some() {
+            for document in documents {
+                let key = self.schema.primary_key_of(document)?.unwrap_or_default();
+                let collides = match self.primary_index.get(&key) {
+                    Some(id) => !stored.contains(id) && !removed.contains(id),
+                    None => false,
+                };
+
+                if collides || keys.fresh.iter().any(|(k, _)| *k == key) {
+                    return Err(DatabaseError::DuplicateKey(format!(
+                        "Duplicate primary key in collection '{}' for document {}",
+                        self.schema.name, document.id
+                    )));
+                }
+                keys.fresh.push((key, document.id));
+            }
+
+            for id in stored.iter().chain(removed) {
+                if let Some(old) = self.documents.get(id)
+                    && let Some(old_key) = self.schema.primary_key_of(old)?
+                {
+                    keys.stale.push(old_key);
+                }
+            }
+        }
+
+        for index in self.indexes.values().filter(|index| index.options.unique) {
+            let mut batch = SecondaryIndex::new(index.field.clone(), index.options);
+            for document in documents {
+                let collides = match index.conflict(document) {
+                    Some(id) => !stored.contains(&id) && !removed.contains(&id),
+                    None => false,
+                };
+
+                if collides || batch.conflict(document).is_some() {
+                    return Err(DatabaseError::DuplicateKey(format!(
+                        "Duplicate value of unique index '{}' in collection '{}' for document {}",
+                        index.field, self.schema.name, document.id
+                    )));
+                }
+                batch.insert(document);
+            }
+        }
+
+        Ok(keys)
+    }
+
+    /// Remove and store documents checked by `check_keys`, keeping the primary key and
+    /// secondary indexes in sync. Returns the removed documents.
+    pub(crate) fn write_documents(
+        &mut self,
+        documents: Vec<Document>,
+        removed: &HashSet<u64>,
+        keys: KeyChanges,
+    ) -> Vec<Document> {
+        for key in &keys.stale {
+            self.primary_index.remove(key);
+        }
+        self.primary_index.extend(keys.fresh);
+        self.log_index_build_writes(
+            removed
+                .iter()
+                .copied()
+                .chain(documents.iter().map(|document| document.id)),
+        );
+
+        let stored = Arc::make_mut(&mut self.documents);
+        let mut removed_documents = Vec::with_capacity(removed.len());
+        for id in removed {
+            let Some(document) = stored.remove(id) else {
+                continue;
+            };
+            if let Some(index) = &mut self.text_index {
+                index.remove(*id);
+            }
+            for index in self.indexes.values_mut() {
+                index.remove(&document);
+            }
+            removed_documents.push(document);
+        }
+
+        for document in documents {
+            if let Some(index) = &mut self.text_index {
+                index.insert(&document);
+            }
+            for index in self.indexes.values_mut() {
+                if let Some(old) = stored.get(&document.id) {
+                    index.remove(old);
+                }
+                index.insert(&document);
+            }
+            stored.insert(document.id, document);
+        }
+        removed_documents
+    }
+
+    fn rebuild_primary_index(&mut self) -> Result<(), DatabaseError> {
+        self.primary_index.clear();
+        for document in self.documents.values() {
+            if let Some(key) = self.schema.primary_key_of(document)? {
+                self.primary_index.insert(key, document.id);
+            }
+        }
+        Ok(())
+    }
+
+    fn rebuild_text_index(&mut self) {
+        if let Some(index) = &mut self.text_index {
+            index.clear();
+            for document in self.documents.values() {
+                index.insert(document);
+            }
+        }
+    }
+
+    fn rebuild_secondary_indexes(&mut self) {
+        for index in self.indexes.values_mut() {
+            index.clear();
+            for document in self.documents.values() {
+                index.insert(document);
+            }
+        }
     }
 
     pub fn update(&mut self, id: u64, document: Document) -> Result<(), DatabaseError> {
@@ -73,73 +552,367 @@ impl Collection {
 
         let mut updated_doc = document;
         updated_doc.id = id;
+        self.schema.normalize_document(&mut updated_doc);
         self.schema.validate_document(&updated_doc)?;
 
-        self.documents.insert(id, updated_doc);
+        self.store_documents(vec![updated_doc])?;
+        self.persist()
+    }
 
-        if self.file.is_some() {
-            self.save_to_file()?;
+    /// Replace the document only if it is still at the expected version, e.g. the version
+    /// of the copy the new document was derived from. Fails with `DatabaseError::Conflict`
+    /// if it was written since, so concurrent read-modify-write cycles cannot lose updates.
+    pub fn update_if_version(
+        &mut self,
+        id: u64,
+        expected_version: u64,
+        document: Document,
+    ) -> Result<(), DatabaseError> {
+        let Some(stored) = self.documents.get(&id) else {
+            return Err(DatabaseError::DocumentNotFound(id));
+        };
+        if stored.version != expected_version {
+            return Err(DatabaseError::Conflict {
+                id,
+                expected: expected_version,
+                actual: stored.version,
+            });
         }
-
-        Ok(())
+        self.update(id, document)
     }
 
     pub fn delete(&mut self, id: u64) -> Result<(), DatabaseError> {
-        if self.documents.remove(&id).is_none() {
+        self.remove_document(id)?;
+        self.persist()
+    }
+
+    fn remove_document(&mut self, id: u64) -> Result<(), DatabaseError> {
+        self.check_not_prepared()?;
+        if !self.documents.contains_key(&id) {
             return Err(DatabaseError::DocumentNotFound(id));
         }
 
+        let removed = HashSet::from([id]);
+        let keys = self.check_keys(&[], &removed)?;
+        let documents = self.write_documents(Vec::new(), &removed, keys);
+        if let Some(pending) = &mut self.pending_writes {
+            pending.push(WalRecord::Delete(id));
+        }
+        self.version += 1;
+        self.notify(documents.into_iter().map(ChangeEvent::Delete).collect());
+        Ok(())
+    }
+
+    /// Make the writes since the last call durable by appending them to the write-ahead
+    /// log as one operation. Once the log outgrows `DEFAULT_CHECKPOINT_SIZE` the collection
+    /// file is written and the log emptied.
+    pub(crate) fn persist(&mut self) -> Result<(), DatabaseError> {
+        if self.wal.is_none() || self.pending_writes.as_ref().is_none_or(Vec::is_empty) {
+            return Ok(());
+        }
+        let commit = self.commit_record();
+        let (Some(wal), Some(records)) = (&mut self.wal, &mut self.pending_writes) else {
+            return Ok(());
+        };
+
+        records.push(commit);
+        let size = wal.size();
+        let appended = wal.append(records);
+        records.pop();
+        appended?;
+        records.clear();
+        self.io_stats.write_count += 1;
+        self.io_stats.write_bytes += wal.size() - size;
+
+        if wal.size() > DEFAULT_CHECKPOINT_SIZE {
+            self.save_to_file()?;
+        }
+        Ok(())
+    }
+
+    /// Choose when commits are synced to disk, see `SyncMode`.
+    /// Collections without a file have nothing to sync.
+    pub fn set_sync_mode(&mut self, sync_mode: SyncMode) {
+        if let Some(wal) = &mut self.wal {
+            wal.set_sync_mode(sync_mode);
+        }
+    }
+
+    /// Sync the commits the sync mode held back
+    pub fn sync(&mut self) -> Result<(), DatabaseError> {
+        match &mut self.wal {
+            Some(wal) => wal.sync(),
+            None => Ok(()),
+        }
+    }
+
+    /// Write the collection file and empty the write-ahead log
+    pub fn checkpoint(&mut self) -> Result<(), DatabaseError> {
         if self.file.is_some() {
             self.save_to_file()?;
         }
+        Ok(())
+    }
 
+    /// Bytes of the collection file and its write-ahead log
+    fn stored_size(&self) -> Result<u64, DatabaseError> {
+        let file_size = match &self.path {
+            Some(path) => fs::metadata(path)?.len(),
+            None => 0,
+        };
+        Ok(file_size + self.wal.as_ref().map_or(0, WriteAheadLog::size))
+    }
+
+    /// Apply the committed writes read back from the write-ahead log
+    pub(crate) fn replay(&mut self, records: Vec<WalRecord>) -> Result<(), DatabaseError> {
+        for record in records {
+            match record {
+                WalRecord::Put(document) => self.store_documents(vec![document])?,
+                WalRecord::Delete(id) => {
+                    if self.documents.contains_key(&id) {
+                        self.remove_document(id)?;
+                    }
+                }
+                WalRecord::Commit { next_id, sequences }
+                | WalRecord::Prepare {
+                    next_id, sequences, ..
+                } => {
+                    self.next_id = next_id;
+                    self.sequences.extend(sequences);
+                }
+            }
+        }
         Ok(())
     }
 
-    fn save_to_file(&mut self) -> Result<(), DatabaseError> {
-        // Simple serialization format
-        let serialized = self.serialize();
+    /// Commit record with the current id and sequence counters
+    pub(crate) fn commit_record(&self) -> WalRecord {
+        WalRecord::Commit {
+            next_id: self.next_id,
+            sequences: self.sequence_counters(),
+        }
+    }
 
-        if let Some(ref mut file) = self.file {
-            file.seek(SeekFrom::Start(0))?;
-            file.set_len(0)?; // Truncate file
+    fn sequence_counters(&self) -> Vec<(String, i64)> {
+        self.sequences
+            .iter()
+            .map(|(field, next)| (field.clone(), *next))
+            .collect()
+    }
 
+    /// Write the whole collection to its file, then empty the write-ahead log.
+    /// The collection is written to a temporary file next to the collection file and renamed
+    /// over it, a crash in the middle leaves the previous file in place.
+    pub(crate) fn save_to_file(&mut self) -> Result<(), DatabaseError> {
+        // Simple serialization format
+        let serialized = self.serialize()?;
+
+        if self.file.is_some()
+            && let Some(path) = &self.path
+        {
+            let temporary = collection_temporary_path(path);
+            let mut file = File::create(&temporary)?;
             file.write_all(&serialized)?;
-            file.flush()?;
+            file.sync_all()?;
+            drop(file);
+            self.io_stats.write_count += 1;
+            self.io_stats.write_bytes += serialized.len() as u64;
+
+            fs::rename(&temporary, path)?;
+            sync_parent_directory(path, Durability::Full)?;
+            // The handle still points at the replaced file
+            self.file = Some(OpenOptions::new().read(true).write(true).open(path)?);
+        }
+        let data_checksum = crc32(&serialized);
+        self.save_text_index(data_checksum)?;
+        self.save_index_entries(data_checksum)?;
+
+        // The file holds every logged write now, but for those of a prepared transaction
+        if let Some(wal) = &mut self.wal {
+            wal.reset()?;
+            if let Some(prepared) = &self.prepared {
+                wal.append(&prepared.records)?;
+            }
+        }
+        if let Some(pending) = &mut self.pending_writes {
+            pending.clear();
         }
         Ok(())
     }
 
-    fn load_from_file(&mut self) -> Result<(), DatabaseError> {
+    /// Text index file of a collection with a backing file and text fields
+    pub(crate) fn text_index_path(&self) -> Option<PathBuf> {
+        self.text_index.as_ref()?;
+        self.path.as_deref().map(text_index_path)
+    }
+
+    fn save_text_index(&self, data_checksum: u32) -> Result<(), DatabaseError> {
+        match (&self.text_index, self.text_index_path()) {
+            (Some(index), Some(path)) => save_text_index(index, &path, data_checksum),
+            _ => Ok(()),
+        }
+    }
+
+    /// Index definitions file of a collection with a backing file
+    pub(crate) fn index_definitions_path(&self) -> Option<PathBuf> {
+        self.path.as_deref().map(index_definitions_path)
+    }
+
+    /// Entries file of the secondary index on the field
+    pub(crate) fn index_entries_path(&self, field: &str) -> Option<PathBuf> {
+        self.path
+            .as_deref()
+            .map(|path| index_entries_path(path, field))
+    }
+
+    /// Text index and secondary index files of the collection which exist on disk
+    pub(crate) fn index_file_paths(&self) -> Vec<PathBuf> {
+        let entries = self
+            .indexes
+            .keys()
+            .filter_map(|field| self.index_entries_path(field));
+
+        self.text_index_path()
+            .into_iter()
+            .chain(self.index_definitions_path())
+            .chain(entries)
+            .filter(|path| path.exists())
+            .collect()
+    }
+
+    fn save_index_entries(&self, data_checksum: u32) -> Result<(), DatabaseError> {
+        for index in self.indexes.values() {
+            if let Some(path) = self.index_entries_path(&index.field) {
+                save_index_entries(index, &path, data_checksum)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn save_index_definitions(&self) -> Result<(), DatabaseError> {
+        match self.index_definitions_path() {
+            Some(path) if !self.indexes.is_empty() || path.exists() => {
+                save_index_definitions(&self.indexes, &path)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn load_from_file(
+        &mut self,
+        progress: &mut dyn FnMut(&RecoveryProgress) -> ControlFlow<()>,
+    ) -> Result<(), DatabaseError> {
         if let Some(ref mut file) = self.file {
             let mut buffer = Vec::new();
             file.read_to_end(&mut buffer)?;
+            self.io_stats.read_count += 1;
+            self.io_stats.read_bytes += buffer.len() as u64;
 
+            let mut tracker = ProgressTracker::new(progress, buffer.len() as u64);
             if !buffer.is_empty() {
-                *self = Self::deserialize(&buffer, self.schema.clone())?;
+                let loaded = Self::deserialize(&buffer, self.schema.clone(), &mut tracker)?;
+                self.documents = loaded.documents;
+                self.next_id = loaded.next_id;
+                self.sequences = loaded.sequences;
+                self.rebuild_primary_index()?;
             }
+
+            self.load_text_index(crc32(&buffer))?;
+            self.load_secondary_indexes(crc32(&buffer))?;
+            tracker.finish()?;
         }
         Ok(())
     }
 
-    fn serialize(&self) -> Vec<u8> {
+    /// Load the persisted secondary indexes, rebuilding each one from the documents
+    /// if it is missing, stale, damaged or does not match the documents
+    fn load_secondary_indexes(&mut self, data_checksum: u32) -> Result<(), DatabaseError> {
+        let Some(path) = self.index_definitions_path() else {
+            return Ok(());
+        };
+
+        for mut index in load_index_definitions(&path)? {
+            let Some(path) = self.index_entries_path(&index.field) else {
+                continue;
+            };
+            let loaded = match load_index_entries(&mut index, &path, data_checksum) {
+                Ok(loaded) => loaded && index.verify(&self.documents).is_ok(),
+                Err(DatabaseError::InvalidData(_)) => false,
+                Err(e) => return Err(e),
+            };
+
+            if !loaded {
+                index.clear();
+                for document in self.documents.values() {
+                    index.insert(document);
+                }
+                save_index_entries(&index, &path, data_checksum)?;
+            }
+            self.indexes.insert(index.field.clone(), index);
+        }
+        Ok(())
+    }
+
+    /// Load the persisted text index, rebuilding it from the documents
+    /// if it is missing, stale or damaged
+    fn load_text_index(&mut self, data_checksum: u32) -> Result<(), DatabaseError> {
+        let Some(path) = self.text_index_path() else {
+            return Ok(());
+        };
+
+        match load_text_index(&path, self.schema.text_fields.clone(), data_checksum) {
+            Ok(Some(index)) => self.text_index = Some(index),
+            Ok(None) | Err(DatabaseError::InvalidData(_)) => {
+                self.rebuild_text_index();
+                self.save_text_index(data_checksum)?;
+            }
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+
+    fn serialize(&self) -> Result<Vec<u8>, DatabaseError> {
         let mut bytes = Vec::new();
 
         // Write document count
         bytes.extend_from_slice(&(self.documents.len() as u32).to_le_bytes());
         bytes.extend_from_slice(&self.next_id.to_le_bytes());
 
-        // Write documents
-        for document in self.documents.values() {
-            let doc_bytes = self.serialize_document(document);
+        // Write documents, in primary key order if the collection has one
+        let documents: Vec<&Document> = if self.schema.primary_key.is_some() {
+            self.primary_index
+                .values()
+                .filter_map(|id| self.documents.get(id))
+                .collect()
+        } else {
+            self.documents.values().collect()
+        };
+
+        for document in documents {
+            let doc_bytes = Self::serialize_document(document)?;
             bytes.extend_from_slice(&(doc_bytes.len() as u32).to_le_bytes());
             bytes.extend_from_slice(&doc_bytes);
         }
 
-        bytes
+        // Write auto increment sequences
+        bytes.extend_from_slice(&(self.sequences.len() as u32).to_le_bytes());
+        for (field, next) in &self.sequences {
+            bytes.push(field.len() as u8);
+            bytes.extend_from_slice(field.as_bytes());
+            bytes.extend_from_slice(&next.to_le_bytes());
+        }
+
+        // Write document versions
+        bytes.extend_from_slice(&(self.documents.len() as u32).to_le_bytes());
+        for document in self.documents.values() {
+            bytes.extend_from_slice(&document.id.to_le_bytes());
+            bytes.extend_from_slice(&document.version.to_le_bytes());
+        }
+
+        Ok(bytes)
     }
 
-    fn serialize_document(&self, document: &Document) -> Vec<u8> {
+    pub(crate) fn serialize_document(document: &Document) -> Result<Vec<u8>, DatabaseError> {
         let mut bytes = Vec::new();
 
         // Write document ID
@@ -154,14 +927,18 @@ impl Collection {
             bytes.push(key_bytes.len() as u8);
             bytes.extend_from_slice(key_bytes);
 
-            let value_bytes = value.serialize();
+            let value_bytes = value.serialize()?;
             bytes.extend_from_slice(&value_bytes);
         }
 
-        bytes
+        Ok(bytes)
     }
 
-    fn deserialize(bytes: &[u8], schema: Schema) -> Result<Self, DatabaseError> {
+    fn deserialize(
+        bytes: &[u8],
+        schema: Schema,
+        progress: &mut ProgressTracker,
+    ) -> Result<Self, DatabaseError> {
         let mut offset: usize;
 
         if bytes.len() < 12 {
@@ -202,17 +979,81 @@ impl Collection {
             let document = Self::deserialize_document(&bytes[offset..offset + doc_length])?;
             documents.insert(document.id, document);
             offset += doc_length;
+            progress.advance(offset as u64)?;
+        }
+
+        // Read auto increment sequences, missing in files written before sequences existed
+        let mut sequences = HashMap::new();
+        if offset + 4 <= bytes.len() {
+            let count = u32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ]) as usize;
+            offset += 4;
+
+            for _ in 0..count {
+                let name_len = *bytes.get(offset).ok_or_else(|| {
+                    DatabaseError::InvalidData("Incomplete sequence data".to_string())
+                })? as usize;
+                offset += 1;
+
+                if offset + name_len + 8 > bytes.len() {
+                    return Err(DatabaseError::InvalidData(
+                        "Incomplete sequence data".to_string(),
+                    ));
+                }
+
+                let name =
+                    String::from_utf8(bytes[offset..offset + name_len].to_vec()).map_err(|e| {
+                        DatabaseError::InvalidData(format!("Invalid sequence name UTF-8: {}", e))
+                    })?;
+                offset += name_len;
+
+                let mut next = [0u8; 8];
+                next.copy_from_slice(&bytes[offset..offset + 8]);
+                offset += 8;
+
+                sequences.insert(name, i64::from_le_bytes(next));
+            }
+        }
+
+        // Read document versions, documents of files written before versions existed stay at 0
+        if offset + 4 <= bytes.len() {
+            let mut reader = ByteReader::new(&bytes[offset..]);
+            for _ in 0..reader.read_u32()? {
+                let id = reader.read_u64()?;
+                let version = reader.read_u64()?;
+                if let Some(document) = documents.get_mut(&id) {
+                    document.version = version;
+                }
+            }
         }
 
         Ok(Self {
             schema,
-            documents,
+            documents: Arc::new(documents),
             next_id,
             file: None,
+            path: None,
+            ttl: None,
+            primary_index: BTreeMap::new(),
+            sequences,
+            text_index: None,
+            indexes: BTreeMap::new(),
+            index_builds: BTreeMap::new(),
+            version: 0,
+            watchers: Vec::new(),
+            wal: None,
+            pending_writes: None,
+            recovery: RecoveryReport::default(),
+            prepared: None,
+            io_stats: IoStats::default(),
         })
     }
 
-    fn deserialize_document(bytes: &[u8]) -> Result<Document, DatabaseError> {
+    pub(crate) fn deserialize_document(bytes: &[u8]) -> Result<Document, DatabaseError> {
         let mut offset: usize;
 
         if bytes.len() < 12 {
@@ -267,23 +1108,162 @@ impl Collection {
             offset += value_size;
         }
 
-        Ok(Document { id, data })
+        Ok(Document {
+            id,
+            data,
+            version: 0,
+        })
     }
 }
 
+/// Primary key index changes of a write, see `Collection::check_keys`
+#[derive(Default)]
+pub(crate) struct KeyChanges {
+    /// Keys of the replaced and removed documents
+    stale: Vec<Vec<u8>>,
+    /// Keys of the stored documents
+    fresh: Vec<(Vec<u8>, u64)>,
+}
+
+/// Fill in missing auto increment fields from the sequences, returns the advanced sequences.
+/// Fields without a sequence start after their largest value in `documents`.
+/// The sequences are only advanced once the document is stored.
+pub(crate) fn assign_sequences(
+    schema: &Schema,
+    sequences: &HashMap<String, i64>,
+    documents: &HashMap<u64, Document>,
+    document: &mut Document,
+) -> Result<Vec<(String, i64)>, DatabaseError> {
+    let mut advanced = Vec::new();
+
+    for field in schema.fields.iter().filter(|f| f.auto_increment) {
+        let next = match sequences.get(&field.name) {
+            Some(next) => *next,
+            None => max_sequence_value(documents, &field.name).saturating_add(1),
+        };
+
+        let assigned = match document.get(&field.name) {
+            Some(Value::Long(value)) => *value,
+            Some(Value::Int(value)) => *value as i64,
+            Some(_) => continue, // rejected by schema validation
+            None => {
+                let value = match field.field_type {
+                    FieldType::Int => Value::Int(i32::try_from(next).map_err(|_| {
+                        DatabaseError::InvalidData(format!(
+                            "Sequence of field '{}' is exhausted",
+                            field.name
+                        ))
+                    })?),
+                    _ => Value::Long(next),
+                };
+                document.set(&field.name, value);
+                next
+            }
+        };
+
+        advanced.push((field.name.clone(), next.max(assigned.saturating_add(1))));
+    }
+
+    Ok(advanced)
+}
+
+/// Largest stored value of the field, used when no sequence was persisted yet
+fn max_sequence_value(documents: &HashMap<u64, Document>, field: &str) -> i64 {
+    documents
+        .values()
+        .filter_map(|document| match document.get(field) {
+            Some(Value::Long(value)) => Some(*value),
+            Some(Value::Int(value)) => Some(*value as i64),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Temporary file a collection file at `path` is written to before it replaces it: `<path>.tmp`
+fn collection_temporary_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".tmp");
+    PathBuf::from(path)
+}
+
+/// Counters of a database, see `Database::stats`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DatabaseStats {
+    pub collections: usize,
+    /// Documents in all collections
+    pub documents: usize,
+    /// I/O of the collection files and their write-ahead logs together,
+    /// `IoStats::since` an earlier snapshot gives the I/O of an interval
+    pub io: IoStats,
+}
+
 // Main Database struct
 pub struct Database {
-    collections: HashMap<String, Collection>,
+    pub(crate) collections: HashMap<String, Collection>,
+    views: HashMap<String, View>,
+    /// Collection files recorded in the catalog file, set together with `catalog_path`
+    catalog: Option<Catalog>,
+    catalog_path: Option<PathBuf>,
+    /// Commit decisions of transactions across collection files, next to the catalog
+    pub(crate) transactions: Option<TransactionLog>,
+    /// Sync mode of the collection files, see `Database::set_sync_mode`
+    sync_mode: SyncMode,
 }
 
 impl Database {
     pub fn new() -> Self {
         Self {
             collections: HashMap::new(),
+            views: HashMap::new(),
+            catalog: None,
+            catalog_path: None,
+            transactions: None,
+            sync_mode: SyncMode::default(),
+        }
+    }
+
+    /// Database with view definitions and collection files persisted in the catalog file.
+    /// Collections are not opened, see `Database::open`.
+    pub fn with_catalog<P: AsRef<Path>>(path: P) -> Result<Self, DatabaseError> {
+        let path = path.as_ref();
+        let (catalog, views) = load_catalog(path)?;
+        if !path.exists() {
+            save_catalog(path, &catalog, &[])?;
+        }
+
+        let transactions = TransactionLog::open(transaction_log_path(path))?;
+        Ok(Self {
+            collections: HashMap::new(),
+            views: views
+                .into_iter()
+                .map(|view| (view.name.clone(), view))
+                .collect(),
+            catalog: Some(catalog),
+            catalog_path: Some(path.to_path_buf()),
+            transactions: Some(transactions),
+            sync_mode: SyncMode::default(),
+        })
+    }
+
+    /// Open the database with the catalog file at `path` and every collection file recorded
+    /// in it, with the schema it was created with
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, DatabaseError> {
+        let mut db = Self::with_catalog(path)?;
+        let entries = db
+            .catalog
+            .as_ref()
+            .map_or_else(Vec::new, |catalog| catalog.collections.clone());
+        for entry in entries {
+            db.create_collection_with_file(entry.name, entry.schema, entry.path)?;
         }
+        Ok(db)
     }
 
     pub fn create_collection(&mut self, name: String, schema: Schema) -> Result<(), DatabaseError> {
+        validate_identifier(IdentifierKind::Collection, &name)?;
+        schema.validate()?;
+
         if self.collections.contains_key(&name) {
             return Err(DatabaseError::InvalidQuery(format!(
                 "Collection '{}' already exists",
@@ -301,6 +1281,9 @@ impl Database {
         schema: Schema,
         path: P,
     ) -> Result<(), DatabaseError> {
+        validate_identifier(IdentifierKind::Collection, &name)?;
+        schema.validate()?;
+
         if self.collections.contains_key(&name) {
             return Err(DatabaseError::InvalidQuery(format!(
                 "Collection '{}' already exists",
@@ -308,12 +1291,518 @@ impl Database {
             )));
         }
 
-        let collection = Collection::with_file(schema, path)?;
+        let mut collection = Collection::open_file(
+            schema,
+            path.as_ref(),
+            |_| ControlFlow::Continue(()),
+            self.transactions.as_mut(),
+        )?;
+        collection.set_sync_mode(self.sync_mode);
+        if let Some(catalog) = &mut self.catalog {
+            if let Some(entry) = catalog.entry(&name) {
+                collection.next_id = collection.next_id.max(entry.next_id);
+                // Checked against the schema the collection is opened with
+                if let Some(ttl) = &entry.ttl {
+                    collection.set_ttl(&ttl.field, ttl.expire_after)?;
+                }
+            }
+            catalog.register(&name, path.as_ref(), &collection.schema, collection.next_id);
+        }
         self.collections.insert(name, collection);
-        Ok(())
+        self.save_catalog()
     }
 
     pub fn collection(&mut self, name: &str) -> Option<&mut Collection> {
         self.collections.get_mut(name)
     }
+
+    pub fn stats(&self) -> DatabaseStats {
+        DatabaseStats {
+            collections: self.collections.len(),
+            documents: self
+                .collections
+                .values()
+                .map(|collection| collection.documents.len())
+                .sum(),
+            io: self
+                .collections
+                .values()
+                .map(Collection::io_stats)
+                .fold(IoStats::default(), |total, io| total + io),
+        }
+    }
+
+    /// Choose when commits to the collection files are synced, for the current
+    /// collections and those created later, see `SyncMode`
+    pub fn set_sync_mode(&mut self, sync_mode: SyncMode) {
+        self.sync_mode = sync_mode;
+        for collection in self.collections.values_mut() {
+            collection.set_sync_mode(sync_mode);
+        }
+    }
+
+    /// Sync the commits held back by the sync mode in every collection,
+    /// then record the next document ids in the catalog
+    pub fn sync(&mut self) -> Result<(), DatabaseError> {
+        for collection in self.collections.values_mut() {
+            collection.sync()?;
+        }
+        self.save_catalog()
+    }
+
+    /// Rewrite the collection file from the live documents and empty its write-ahead log,
+    /// dropping deleted documents and superseded writes. Reports the bytes of the file and
+    /// the log before and after.
+    pub fn vacuum(&mut self, collection: &str) -> Result<VacuumReport, DatabaseError> {
+        let Some(target) = self.collections.get_mut(collection) else {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "Collection '{}' not found",
+                collection
+            )));
+        };
+        if target.file.is_none() {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "Collection '{}' has no file to vacuum",
+                collection
+            )));
+        }
+
+        let bytes_before = target.stored_size()?;
+        target.checkpoint()?;
+        Ok(VacuumReport {
+            bytes_before,
+            bytes_after: target.stored_size()?,
+        })
+    }
+
+    /// Start a session on the collection, commit it with `Collection::commit`
+    pub fn session(&self, collection: &str) -> Result<Session, DatabaseError> {
+        match self.collections.get(collection) {
+            Some(target) => Ok(target.session()),
+            None => Err(DatabaseError::InvalidQuery(format!(
+                "Collection '{}' not found",
+                collection
+            ))),
+        }
+    }
+
+    /// Snapshot the collections together, see `DatabaseSnapshot`.
+    /// With a `DatabasePool` take it under the read lock and export after releasing it.
+    pub fn snapshot(&self, collections: &[&str]) -> Result<DatabaseSnapshot, DatabaseError> {
+        let mut snapshots: Vec<(String, Snapshot)> = Vec::with_capacity(collections.len());
+        for name in collections {
+            let Some(collection) = self.collections.get(*name) else {
+                return Err(DatabaseError::InvalidQuery(format!(
+                    "Collection '{}' not found",
+                    name
+                )));
+            };
+            if !snapshots.iter().any(|(taken, _)| taken == name) {
+                snapshots.push((name.to_string(), collection.snapshot()));
+            }
+        }
+        Ok(DatabaseSnapshot::new(snapshots))
+    }
+
+    /// Dump the collections from a single snapshot as JSON, see `DatabaseSnapshot::to_json_writer`
+    pub fn export_json<W: Write>(
+        &self,
+        collections: &[&str],
+        writer: &mut W,
+    ) -> Result<(), DatabaseError> {
+        self.snapshot(collections)?.to_json_writer(writer)
+    }
+
+    /// Expire documents of the collection like `Collection::set_ttl`, the policy is
+    /// recorded in the catalog and applied again when the database is opened
+    pub fn set_ttl(
+        &mut self,
+        collection: &str,
+        field: &str,
+        expire_after: Duration,
+    ) -> Result<(), DatabaseError> {
+        let Some(target) = self.collections.get_mut(collection) else {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "Collection '{}' not found",
+                collection
+            )));
+        };
+        target.set_ttl(field, expire_after)?;
+        self.save_catalog()
+    }
+
+    /// Remove the TTL policy of the collection, also from the catalog
+    pub fn clear_ttl(&mut self, collection: &str) -> Result<(), DatabaseError> {
+        let Some(target) = self.collections.get_mut(collection) else {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "Collection '{}' not found",
+                collection
+            )));
+        };
+        target.clear_ttl();
+        self.save_catalog()
+    }
+
+    /// Sweep expired documents from all collections with a TTL policy
+    pub fn purge_expired(&mut self) -> Result<usize, DatabaseError> {
+        let mut removed = 0;
+        for collection in self.collections.values_mut() {
+            removed += collection.purge_expired()?;
+        }
+        Ok(removed)
+    }
+
+    /// Run a query written in the text syntax against a collection, e.g.
+    /// `age > 30 AND is_active = true ORDER BY name LIMIT 10`
+    pub fn query(&self, collection: &str, text: &str) -> Result<QueryResult<'_>, DatabaseError> {
+        let Some(target) = self.collections.get(collection) else {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "Collection '{}' not found",
+                collection
+            )));
+        };
+
+        let prepared = target.prepare(text)?;
+        if prepared.parameter_count() > 0 {
+            return Err(DatabaseError::InvalidQuery(
+                "Query text contains placeholders, prepare it and bind the parameters".to_string(),
+            ));
+        }
+        target.query(prepared.bind(&[])?)
+    }
+
+    /// Join two collections on equal field values, e.g. orders to their customers with
+    /// `lookup("orders", "customer_id", "customers", "id")`.
+    /// Every left document is returned once in id order with its matching right documents.
+    pub fn lookup(
+        &self,
+        left_collection: &str,
+        left_field: &str,
+        right_collection: &str,
+        right_field: &str,
+    ) -> Result<Vec<LookupRow<'_>>, DatabaseError> {
+        let find = |name: &str| {
+            self.collections.get(name).ok_or_else(|| {
+                DatabaseError::InvalidQuery(format!("Collection '{}' not found", name))
+            })
+        };
+        hash_join(
+            find(left_collection)?,
+            left_field,
+            find(right_collection)?,
+            right_field,
+        )
+    }
+
+    /// Save a named query over a collection, the query is validated against the collection schema
+    pub fn create_view<Q: Into<FindQuery>>(
+        &mut self,
+        name: &str,
+        collection: &str,
+        query: Q,
+    ) -> Result<(), DatabaseError> {
+        validate_identifier(IdentifierKind::View, name)?;
+
+        if self.views.contains_key(name) {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "View '{}' already exists",
+                name
+            )));
+        }
+
+        let query = query.into();
+        let Some(target) = self.collections.get(collection) else {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "Collection '{}' not found",
+                collection
+            )));
+        };
+        query.validate(&target.schema)?;
+
+        self.views
+            .insert(name.to_string(), View::new(name, collection, query));
+        self.save_catalog()
+    }
+
+    pub fn drop_view(&mut self, name: &str) -> Result<(), DatabaseError> {
+        if self.views.remove(name).is_none() {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "View '{}' not found",
+                name
+            )));
+        }
+
+        self.save_catalog()
+    }
+
+    pub fn view(&self, name: &str) -> Option<&View> {
+        self.views.get(name)
+    }
+
+    /// Views in the catalog ordered by name
+    pub fn views(&self) -> Vec<&View> {
+        let mut views: Vec<&View> = self.views.values().collect();
+        views.sort_by(|a, b| a.name.cmp(&b.name));
+        views
+    }
+
+    /// Run a saved query by name
+    pub fn find_view(&self, name: &str) -> Result<Vec<&Document>, DatabaseError> {
+        let Some(view) = self.views.get(name) else {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "View '{}' not found",
+                name
+            )));
+        };
+
+        let Some(collection) = self.collections.get(&view.collection) else {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "Collection '{}' of view '{}' not found",
+                view.collection, name
+            )));
+        };
+
+        collection.find_where(&view.query)
+    }
+
+    /// Move the catalog and every collection file into the directory, keeping their file names
+    pub fn move_to<P: AsRef<Path>>(&mut self, directory: P) -> Result<(), DatabaseError> {
+        let directory = directory.as_ref();
+        let moves = self
+            .file_paths()
+            .into_iter()
+            .filter_map(|path| {
+                let target = directory.join(path.file_name()?);
+                Some((path, target))
+            })
+            .collect();
+        self.relocate(moves)
+    }
+
+    /// Rename the catalog file.
+    /// Collection files next to the catalog move along into the directory of the new catalog,
+    /// collection files stored elsewhere stay where they are.
+    pub fn rename<P: AsRef<Path>>(&mut self, catalog_path: P) -> Result<(), DatabaseError> {
+        let Some(current) = self.catalog_path.clone() else {
+            return Err(DatabaseError::InvalidQuery(
+                "Database without a catalog file cannot be renamed".to_string(),
+            ));
+        };
+
+        let catalog_path = catalog_path.as_ref().to_path_buf();
+        let directory = catalog_path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let mut moves = vec![(current.clone(), catalog_path)];
+        for path in self.file_paths() {
+            if path != current
+                && path.parent() == current.parent()
+                && let Some(name) = path.file_name()
+            {
+                let target = directory.join(name);
+                moves.push((path, target));
+            }
+        }
+        self.relocate(moves)
+    }
+
+    /// Catalog, collection, write-ahead log and index files owned by the database
+    fn file_paths(&self) -> Vec<PathBuf> {
+        let indexes = self
+            .collections
+            .values()
+            .flat_map(Collection::index_file_paths);
+        let logs = self
+            .collections
+            .values()
+            .filter_map(|c| c.wal.as_ref())
+            .map(|wal| wal.path().to_path_buf());
+
+        let transactions = self.transactions.iter().map(|log| log.path().to_path_buf());
+
+        self.catalog_path
+            .iter()
+            .chain(self.collections.values().filter_map(|c| c.path.as_ref()))
+            .cloned()
+            .chain(transactions)
+            .chain(logs)
+            .chain(indexes)
+            .collect()
+    }
+
+    /// Move files without ever leaving the database without a complete copy:
+    /// - all new names are created and synced, the old files are untouched
+    /// - the transaction log and the catalog are written with the new paths at their
+    ///   new names, the catalog last, a crash before it leaves the database at the
+    ///   old names and a crash after it at the new ones
+    /// - only then are the old names removed
+    ///
+    /// An error before the catalog is written removes the new names and leaves the
+    /// database as it was.
+    fn relocate(&mut self, moves: Vec<(PathBuf, PathBuf)>) -> Result<(), DatabaseError> {
+        let moves: Vec<(PathBuf, PathBuf)> =
+            moves.into_iter().filter(|(from, to)| from != to).collect();
+
+        // Refuse to overwrite anything before the first file is touched
+        for (from, to) in &moves {
+            if to.exists() {
+                return Err(DatabaseError::InvalidQuery(format!(
+                    "Cannot move '{}' to '{}', the target exists",
+                    from.display(),
+                    to.display()
+                )));
+            }
+        }
+
+        // Written data must be durable before it is reachable under the new names
+        for collection in self.collections.values() {
+            if let Some(file) = &collection.file {
+                file.sync_all()?;
+            }
+            for path in collection.index_file_paths() {
+                File::open(path)?.sync_all()?;
+            }
+        }
+
+        for (index, (from, to)) in moves.iter().enumerate() {
+            if let Err(e) = link_or_copy(from, to) {
+                for (_, linked) in &moves[..index] {
+                    let _ = fs::remove_file(linked);
+                }
+                return Err(e.into());
+            }
+        }
+
+        let moved: HashMap<PathBuf, PathBuf> = moves.iter().cloned().collect();
+        let relocated = match self.relocated_files(&moved) {
+            Ok(relocated) => relocated,
+            Err(e) => {
+                for (_, linked) in &moves {
+                    let _ = fs::remove_file(linked);
+                }
+                return Err(e);
+            }
+        };
+
+        // The catalog points at the new names, switch every handle over
+        let RelocatedFiles {
+            files,
+            logs,
+            transactions,
+        } = relocated;
+        if let Some(target) = self.catalog_path.as_ref().and_then(|path| moved.get(path)) {
+            self.catalog_path = Some(target.clone());
+        }
+        if let Some(catalog) = &mut self.catalog {
+            for entry in &mut catalog.collections {
+                if let Some(target) = moved.get(&entry.path) {
+                    entry.path = target.clone();
+                }
+            }
+        }
+        for (name, (file, target)) in files {
+            let collection = self.collections.get_mut(&name).unwrap();
+            collection.file = Some(file);
+            collection.path = Some(target);
+        }
+        for (name, wal) in logs {
+            self.collections.get_mut(&name).unwrap().wal = Some(wal);
+        }
+        if transactions.is_some() {
+            self.transactions = transactions;
+        }
+
+        for (from, _) in &moves {
+            fs::remove_file(from)?;
+        }
+        for (from, _) in &moves {
+            sync_parent_directory(from, Durability::Full)?;
+        }
+        Ok(())
+    }
+
+    /// Reopen the moved files and write the transaction log and the catalog at their new
+    /// names, the database itself is not changed. The catalog is written last, once it is
+    /// durable the new names are the database.
+    fn relocated_files(
+        &mut self,
+        moved: &HashMap<PathBuf, PathBuf>,
+    ) -> Result<RelocatedFiles, DatabaseError> {
+        for to in moved.values() {
+            sync_parent_directory(to, Durability::Full)?;
+        }
+
+        // Files copied across file systems are new files, reopen every handle
+        let mut relocated = RelocatedFiles::default();
+        for (name, collection) in &self.collections {
+            if let Some(target) = collection.path.as_ref().and_then(|path| moved.get(path)) {
+                let file = OpenOptions::new().read(true).write(true).open(target)?;
+                relocated.files.push((name.clone(), (file, target.clone())));
+            }
+            if let Some(wal) = &collection.wal
+                && let Some(target) = moved.get(wal.path())
+            {
+                // Reopened for appends, its writes are applied already
+                let mut reopened = WriteAheadLog::open(target)?.0;
+                reopened.set_sync_mode(wal.sync_mode());
+                relocated.logs.push((name.clone(), reopened));
+            }
+        }
+        if let Some(transactions) = &self.transactions {
+            relocated.transactions = Some(transactions.relocated(moved)?);
+        }
+
+        if let Err(e) = self.write_catalog(moved) {
+            // A log rewritten in place would point at the new names, which are removed
+            if let Some(transactions) = &self.transactions
+                && !moved.contains_key(transactions.path())
+            {
+                let _ = transactions.rewrite();
+            }
+            return Err(e);
+        }
+        Ok(relocated)
+    }
+
+    /// Write the catalog file with the views and the collection files,
+    /// recording the next document id and the TTL policy of each open collection
+    fn save_catalog(&mut self) -> Result<(), DatabaseError> {
+        self.write_catalog(&HashMap::new())
+    }
+
+    /// Write the catalog with the moved collection files at the moved path of the catalog,
+    /// the catalog kept by the database still has the current paths
+    fn write_catalog(&mut self, moved: &HashMap<PathBuf, PathBuf>) -> Result<(), DatabaseError> {
+        let (Some(catalog), Some(path)) = (&mut self.catalog, &self.catalog_path) else {
+            return Ok(());
+        };
+
+        for entry in &mut catalog.collections {
+            if let Some(collection) = self.collections.get(&entry.name) {
+                entry.next_id = entry.next_id.max(collection.next_id);
+                entry.ttl = collection.ttl.clone();
+            }
+        }
+        let mut views: Vec<&View> = self.views.values().collect();
+        views.sort_by(|a, b| a.name.cmp(&b.name));
+        if moved.is_empty() {
+            return save_catalog(path, catalog, &views);
+        }
+
+        let mut relocated = catalog.clone();
+        for entry in &mut relocated.collections {
+            if let Some(target) = moved.get(&entry.path) {
+                entry.path = target.clone();
+            }
+        }
+        save_catalog(moved.get(path).unwrap_or(path), &relocated, &views)
+    }
+}
+
+/// Handles of the moved files, see `Database::relocate`
+#[derive(Default)]
+struct RelocatedFiles {
+    // Collection name -> reopened collection file and its path
+    files: Vec<(String, (File, PathBuf))>,
+    // Collection name -> reopened write-ahead log
+    logs: Vec<(String, WriteAheadLog)>,
+    transactions: Option<TransactionLog>,
 }
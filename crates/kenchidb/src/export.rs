@@ -0,0 +1,90 @@
+use std::io::Write;
+
+use crate::{
+    common::DatabaseError,
+    query::{write_json_document, write_json_string},
+    schema::{Document, current_time_millis},
+    session::Snapshot,
+};
+
+/// Version of the JSON dump layout
+const DUMP_FORMAT: u32 = 1;
+
+/// Snapshots of several collections taken at the same moment, no write can happen
+/// between two of them. References between the collections, e.g. an order's
+/// customer id, resolve in the snapshot as they did in the database.
+pub struct DatabaseSnapshot {
+    /// Time the snapshot was taken, in milliseconds since the Unix epoch
+    pub taken_at: i64,
+    collections: Vec<(String, Snapshot)>,
+}
+
+impl DatabaseSnapshot {
+    pub(crate) fn new(collections: Vec<(String, Snapshot)>) -> Self {
+        Self {
+            taken_at: current_time_millis(),
+            collections,
+        }
+    }
+
+    pub fn collection(&self, name: &str) -> Option<&Snapshot> {
+        self.collections
+            .iter()
+            .find(|(collection, _)| collection == name)
+            .map(|(_, snapshot)| snapshot)
+    }
+
+    /// Name and version of each collection in the snapshot, in the order they were requested.
+    /// Two snapshots with the same versions hold the same documents.
+    pub fn versions(&self) -> Vec<(&str, u64)> {
+        self.collections
+            .iter()
+            .map(|(name, snapshot)| (name.as_str(), snapshot.version))
+            .collect()
+    }
+
+    /// Write the snapshot as a single JSON object. The `snapshot` header records the dump
+    /// format, the time the snapshot was taken, and the version and document count of each
+    /// collection. The documents of each collection follow in id order.
+    pub fn to_json_writer<W: Write>(&self, writer: &mut W) -> Result<(), DatabaseError> {
+        write!(
+            writer,
+            "{{\"snapshot\":{{\"format\":{},\"taken_at\":{},\"collections\":{{",
+            DUMP_FORMAT, self.taken_at
+        )?;
+        for (i, (name, snapshot)) in self.collections.iter().enumerate() {
+            if i > 0 {
+                writer.write_all(b",")?;
+            }
+            write_json_string(writer, name)?;
+            write!(
+                writer,
+                ":{{\"version\":{},\"documents\":{}}}",
+                snapshot.version,
+                snapshot.find_all().len()
+            )?;
+        }
+        writer.write_all(b"}},\"collections\":{")?;
+
+        for (i, (name, snapshot)) in self.collections.iter().enumerate() {
+            if i > 0 {
+                writer.write_all(b",")?;
+            }
+            write_json_string(writer, name)?;
+            writer.write_all(b":[")?;
+
+            let mut documents: Vec<&Document> = snapshot.find_all();
+            documents.sort_by_key(|document| document.id);
+            for (j, document) in documents.into_iter().enumerate() {
+                if j > 0 {
+                    writer.write_all(b",")?;
+                }
+                write_json_document(writer, &snapshot.schema.fields, document)?;
+            }
+            writer.write_all(b"]")?;
+        }
+        writer.write_all(b"}}")?;
+
+        Ok(())
+    }
+}
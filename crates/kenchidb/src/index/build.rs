@@ -0,0 +1,166 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    common::DatabaseError,
+    database::Collection,
+    index::{IndexOptions, SecondaryIndex},
+    schema::Document,
+};
+
+/// Index being built from a snapshot of the collection, see `Collection::start_index_build`.
+/// The build holds no reference to the collection, its batches can run while the
+/// collection keeps taking writes, e.g. outside the lock of a `DatabasePool`.
+#[derive(Debug)]
+pub struct IndexBuild {
+    index: SecondaryIndex,
+    /// Documents of the collection when the build started, shared with the collection
+    documents: Arc<HashMap<u64, Document>>,
+    /// Ids of the documents in the order they are indexed
+    pending: Vec<u64>,
+    /// Number of documents indexed so far
+    position: usize,
+    collection: String,
+}
+
+impl IndexBuild {
+    pub fn field(&self) -> &str {
+        &self.index.field
+    }
+
+    /// Index the next `batch_size` documents, returns whether documents are left.
+    /// A unique index fails with `DuplicateKey` on the first value held twice.
+    pub fn step(&mut self, batch_size: usize) -> Result<bool, DatabaseError> {
+        let end = (self.position + batch_size).min(self.pending.len());
+        for id in &self.pending[self.position..end] {
+            let document = &self.documents[id];
+            if let Some(other) = self.index.conflict(document) {
+                return Err(DatabaseError::DuplicateKey(format!(
+                    "Documents {} and {} of collection '{}' hold the same '{}' value",
+                    other, id, self.collection, self.index.field
+                )));
+            }
+            self.index.insert(document);
+        }
+        self.position = end;
+        Ok(!self.is_done())
+    }
+
+    /// Index all remaining documents
+    pub fn run(&mut self, batch_size: usize) -> Result<(), DatabaseError> {
+        while self.step(batch_size)? {}
+        Ok(())
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.position == self.pending.len()
+    }
+
+    /// Documents indexed so far and the documents of the snapshot
+    pub fn progress(&self) -> (usize, usize) {
+        (self.position, self.pending.len())
+    }
+}
+
+impl Collection {
+    /// Start building an index without blocking writes. The returned build indexes a
+    /// snapshot of the documents in batches, see `IndexBuild::step`, while the collection
+    /// records the ids of the documents written in the meantime in a side log.
+    /// `finish_index_build` replays the side log against the built index and adds it to
+    /// the collection, queries use the index only from then on.
+    pub fn start_index_build(
+        &mut self,
+        field: &str,
+        options: IndexOptions,
+    ) -> Result<IndexBuild, DatabaseError> {
+        if self.indexes.contains_key(field) || self.index_builds.contains_key(field) {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "Index on field '{}' already exists or is being built",
+                field
+            )));
+        }
+        if !self.schema.fields.iter().any(|f| f.name == field) {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "Field '{}' not in schema '{}'",
+                field, self.schema.name
+            )));
+        }
+
+        let mut pending: Vec<u64> = self.documents.keys().copied().collect();
+        pending.sort_unstable();
+        self.index_builds
+            .insert(field.to_string(), Default::default());
+
+        Ok(IndexBuild {
+            index: SecondaryIndex::new(field.to_string(), options),
+            documents: self.documents.clone(),
+            pending,
+            position: 0,
+            collection: self.schema.name.clone(),
+        })
+    }
+
+    /// Merge the writes made during the build into the index and add it to the collection.
+    /// Fails with `InvalidQuery` if the build is not done or was aborted, and with
+    /// `DuplicateKey` if a write made a value of a unique index appear twice, the side log
+    /// is dropped in both cases.
+    pub fn finish_index_build(&mut self, build: IndexBuild) -> Result<(), DatabaseError> {
+        let field = build.index.field.clone();
+        let Some(written) = self.index_builds.remove(&field) else {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "No index build on field '{}'",
+                field
+            )));
+        };
+        if !build.is_done() {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "Index build on field '{}' has {} documents left",
+                field,
+                build.pending.len() - build.position
+            )));
+        }
+
+        // Take out the snapshot state of every written document before adding the current
+        // one, a value moving between two documents is not a conflict
+        let mut index = build.index;
+        for id in &written {
+            if let Some(document) = build.documents.get(id) {
+                index.remove(document);
+            }
+        }
+        for id in &written {
+            let Some(document) = self.documents.get(id) else {
+                continue;
+            };
+            if let Some(other) = index.conflict(document) {
+                return Err(DatabaseError::DuplicateKey(format!(
+                    "Documents {} and {} of collection '{}' hold the same '{}' value",
+                    other, id, self.schema.name, field
+                )));
+            }
+            index.insert(document);
+        }
+
+        self.indexes.insert(field, index);
+        if self.file.is_some() {
+            self.save_to_file()?;
+        }
+        self.save_index_definitions()
+    }
+
+    /// Stop recording writes for the build, the build is discarded
+    pub fn abort_index_build(&mut self, build: IndexBuild) {
+        self.index_builds.remove(build.field());
+    }
+
+    /// Record written documents in the side log of each running index build
+    pub(crate) fn log_index_build_writes(&mut self, ids: impl IntoIterator<Item = u64>) {
+        if self.index_builds.is_empty() {
+            return;
+        }
+        for id in ids {
+            for written in self.index_builds.values_mut() {
+                written.insert(id);
+            }
+        }
+    }
+}
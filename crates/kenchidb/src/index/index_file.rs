@@ -0,0 +1,111 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    common::DatabaseError,
+    index::{IndexKind, IndexOptions, SecondaryIndex},
+    query::{ByteReader, encode_string},
+    storage::index_blob::{load_index_blob, save_index_blob},
+};
+
+/// Version of the index definitions file layout
+const INDEX_DEFINITIONS_VERSION: u32 = 1;
+
+/// Version of the index entries file layout
+const INDEX_ENTRIES_VERSION: u32 = 1;
+
+/// Flag of a unique index in the definitions file
+const UNIQUE_FLAG: u8 = 1;
+
+/// Flag of a hash index in the definitions file
+const HASH_FLAG: u8 = 1 << 1;
+
+/// Index definitions file of the collection stored at the path, `<collection file>.idx`
+pub fn index_definitions_path(collection_path: &Path) -> PathBuf {
+    let mut path = collection_path.as_os_str().to_owned();
+    path.push(".idx");
+    PathBuf::from(path)
+}
+
+/// Entries file of the index on the field, `<collection file>.idx.<field>`
+pub fn index_entries_path(collection_path: &Path, field: &str) -> PathBuf {
+    let mut path = collection_path.as_os_str().to_owned();
+    path.push(".idx.");
+    path.push(field);
+    PathBuf::from(path)
+}
+
+/// Write the field and options of each index, see `save_index_blob` for the layout
+pub fn save_index_definitions(
+    indexes: &BTreeMap<String, SecondaryIndex>,
+    path: &Path,
+) -> Result<(), DatabaseError> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&(indexes.len() as u32).to_le_bytes());
+    for index in indexes.values() {
+        encode_string(&index.field, &mut body);
+        let mut flags = 0;
+        if index.options.unique {
+            flags |= UNIQUE_FLAG;
+        }
+        if index.options.kind == IndexKind::Hash {
+            flags |= HASH_FLAG;
+        }
+        body.push(flags);
+    }
+    save_index_blob(path, INDEX_DEFINITIONS_VERSION, &body, 0)
+}
+
+/// Read the definitions written by `save_index_definitions`, empty if the file is missing
+pub fn load_index_definitions(path: &Path) -> Result<Vec<SecondaryIndex>, DatabaseError> {
+    let Some(blob) = load_index_blob(path, INDEX_DEFINITIONS_VERSION, "index definitions")? else {
+        return Ok(Vec::new());
+    };
+
+    let mut reader = ByteReader::new(&blob.body);
+    let mut indexes = Vec::new();
+    for _ in 0..reader.read_u32()? {
+        let field = reader.read_string()?;
+        let flags = reader.read_u8()?;
+        let options = IndexOptions {
+            unique: flags & UNIQUE_FLAG != 0,
+            kind: match flags & HASH_FLAG {
+                0 => IndexKind::BTree,
+                _ => IndexKind::Hash,
+            },
+        };
+        indexes.push(SecondaryIndex::new(field, options));
+    }
+    Ok(indexes)
+}
+
+/// Write the entries of the index, together with the checksum of the collection data
+/// they were built from
+pub fn save_index_entries(
+    index: &SecondaryIndex,
+    path: &Path,
+    data_checksum: u32,
+) -> Result<(), DatabaseError> {
+    let mut body = Vec::new();
+    index.serialize_entries(&mut body);
+    save_index_blob(path, INDEX_ENTRIES_VERSION, &body, data_checksum)
+}
+
+/// Read the entries written by `save_index_entries` into the index.
+/// Returns `false` if the file is missing or was built from other collection data,
+/// the caller then rebuilds the index from the documents.
+pub fn load_index_entries(
+    index: &mut SecondaryIndex,
+    path: &Path,
+    data_checksum: u32,
+) -> Result<bool, DatabaseError> {
+    match load_index_blob(path, INDEX_ENTRIES_VERSION, "index")? {
+        Some(blob) if blob.data_checksum == data_checksum => {
+            index.deserialize_entries(&mut ByteReader::new(&blob.body))?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
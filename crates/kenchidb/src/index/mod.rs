@@ -0,0 +1,9 @@
+mod build;
+mod index_file;
+mod secondary;
+mod stats;
+
+pub(crate) use self::build::*;
+pub(crate) use self::index_file::*;
+pub(crate) use self::secondary::*;
+pub(crate) use self::stats::*;
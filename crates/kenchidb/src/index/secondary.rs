@@ -0,0 +1,421 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    ops::{Bound, RangeBounds},
+};
+
+use crate::{
+    common::DatabaseError,
+    database::Collection,
+    index::IndexStats,
+    query::ByteReader,
+    schema::{Document, FieldType, Value, decode_key_value, encode_key},
+};
+
+/// How the values of a secondary index are organized
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum IndexKind {
+    /// Values kept in order, answers equality and range conditions
+    #[default]
+    BTree,
+    /// Values in a hash table, answers equality conditions in constant time.
+    /// No order is maintained, which makes writes cheaper, but range conditions
+    /// cannot use the index.
+    Hash,
+}
+
+/// Options of a secondary index
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct IndexOptions {
+    /// Reject documents whose value is already held by another document
+    pub unique: bool,
+    pub kind: IndexKind,
+}
+
+impl IndexOptions {
+    pub fn unique(mut self) -> Self {
+        self.unique = true;
+        self
+    }
+
+    /// Use a hash index, for fields only ever compared with `=`
+    pub fn hash(mut self) -> Self {
+        self.kind = IndexKind::Hash;
+        self
+    }
+}
+
+/// Encoded field value -> ids of the documents holding it
+#[derive(Debug, Clone, PartialEq)]
+enum Entries {
+    Ordered(BTreeMap<Vec<u8>, BTreeSet<u64>>),
+    Hashed(HashMap<Vec<u8>, BTreeSet<u64>>),
+}
+
+impl Entries {
+    fn new(kind: IndexKind) -> Self {
+        match kind {
+            IndexKind::BTree => Entries::Ordered(BTreeMap::new()),
+            IndexKind::Hash => Entries::Hashed(HashMap::new()),
+        }
+    }
+
+    fn get(&self, key: &[u8]) -> Option<&BTreeSet<u64>> {
+        match self {
+            Entries::Ordered(entries) => entries.get(key),
+            Entries::Hashed(entries) => entries.get(key),
+        }
+    }
+
+    fn insert(&mut self, key: Vec<u8>, id: u64) {
+        match self {
+            Entries::Ordered(entries) => entries.entry(key).or_default().insert(id),
+            Entries::Hashed(entries) => entries.entry(key).or_default().insert(id),
+        };
+    }
+
+    fn remove(&mut self, key: &[u8], id: u64) {
+        let ids = match self {
+            Entries::Ordered(entries) => entries.get_mut(key),
+            Entries::Hashed(entries) => entries.get_mut(key),
+        };
+        let Some(ids) = ids else {
+            return;
+        };
+
+        ids.remove(&id);
+        if ids.is_empty() {
+            match self {
+                Entries::Ordered(entries) => entries.remove(key),
+                Entries::Hashed(entries) => entries.remove(key),
+            };
+        }
+    }
+
+    fn clear(&mut self) {
+        match self {
+            Entries::Ordered(entries) => entries.clear(),
+            Entries::Hashed(entries) => entries.clear(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Entries::Ordered(entries) => entries.len(),
+            Entries::Hashed(entries) => entries.len(),
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (&Vec<u8>, &BTreeSet<u64>)> + '_> {
+        match self {
+            Entries::Ordered(entries) => Box::new(entries.iter()),
+            Entries::Hashed(entries) => Box::new(entries.iter()),
+        }
+    }
+}
+
+/// Index of a document field, see `IndexKind` for the available organizations.
+/// Documents without the field or with a null value are not indexed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecondaryIndex {
+    pub field: String,
+    pub options: IndexOptions,
+    entries: Entries,
+}
+
+impl SecondaryIndex {
+    pub fn new(field: String, options: IndexOptions) -> Self {
+        Self {
+            field,
+            options,
+            entries: Entries::new(options.kind),
+        }
+    }
+
+    /// Number of indexed documents
+    pub fn len(&self) -> usize {
+        self.entries.iter().map(|(_, ids)| ids.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.len() == 0
+    }
+
+    /// Number of distinct indexed values
+    pub fn key_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Encoded index key of the document, `None` if the document is not indexed
+    fn key_of(&self, document: &Document) -> Option<Vec<u8>> {
+        match document.get(&self.field) {
+            None | Some(Value::Null) => None,
+            Some(value) => Some(encode_key(std::slice::from_ref(value))),
+        }
+    }
+
+    pub fn insert(&mut self, document: &Document) {
+        if let Some(key) = self.key_of(document) {
+            self.entries.insert(key, document.id);
+        }
+    }
+
+    pub fn remove(&mut self, document: &Document) {
+        if let Some(key) = self.key_of(document) {
+            self.entries.remove(&key, document.id);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Id of another document holding the same value, only checked for unique indexes
+    pub fn conflict(&self, document: &Document) -> Option<u64> {
+        if !self.options.unique {
+            return None;
+        }
+        let ids = self.entries.get(&self.key_of(document)?)?;
+        ids.iter().copied().find(|id| *id != document.id)
+    }
+
+    /// Check that every entry points at a document holding its value
+    /// and every document with a value is indexed
+    pub fn verify(&self, documents: &HashMap<u64, Document>) -> Result<(), DatabaseError> {
+        for (key, ids) in self.entries.iter() {
+            for id in ids {
+                let document = documents.get(id).ok_or_else(|| {
+                    DatabaseError::InvalidData(format!(
+                        "Index on '{}' refers to unknown document {}",
+                        self.field, id
+                    ))
+                })?;
+                if self.key_of(document).as_ref() != Some(key) {
+                    return Err(DatabaseError::InvalidData(format!(
+                        "Index on '{}' holds a stale value of document {}",
+                        self.field, id
+                    )));
+                }
+            }
+        }
+
+        let indexed = documents
+            .values()
+            .filter(|document| self.key_of(document).is_some())
+            .count();
+        if indexed != self.len() {
+            return Err(DatabaseError::InvalidData(format!(
+                "Index on '{}' holds {} of {} documents",
+                self.field,
+                self.len(),
+                indexed
+            )));
+        }
+        Ok(())
+    }
+
+    /// Entry counts and size of the index, see `IndexStats`
+    pub fn stats(&self) -> IndexStats {
+        IndexStats::of(self)
+    }
+
+    /// Encoded values and the ids of the documents holding them
+    pub(crate) fn entries(&self) -> impl Iterator<Item = (&Vec<u8>, &BTreeSet<u64>)> {
+        self.entries.iter()
+    }
+
+    pub fn serialize_entries(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        for (key, ids) in self.entries.iter() {
+            bytes.extend_from_slice(&(key.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(key);
+            bytes.extend_from_slice(&(ids.len() as u32).to_le_bytes());
+            for id in ids {
+                bytes.extend_from_slice(&id.to_le_bytes());
+            }
+        }
+    }
+
+    /// Replace the entries with the serialized ones
+    pub fn deserialize_entries(&mut self, reader: &mut ByteReader) -> Result<(), DatabaseError> {
+        let mut entries = Entries::new(self.options.kind);
+        for _ in 0..reader.read_u32()? {
+            let length = reader.read_u32()? as usize;
+            let key = reader.read_bytes(length)?.to_vec();
+            for _ in 0..reader.read_u32()? {
+                entries.insert(key.clone(), reader.read_u64()?);
+            }
+        }
+        self.entries = entries;
+        Ok(())
+    }
+
+    /// Ids of the documents whose value lies within the bounds.
+    /// B-tree indexes return them in value order. Hash indexes answer a single value
+    /// directly and have to visit every value for other bounds.
+    pub fn range<'a>(
+        &'a self,
+        lower: &Bound<Value>,
+        upper: &Bound<Value>,
+    ) -> Box<dyn Iterator<Item = u64> + 'a> {
+        Box::new(self.entries_in(lower, upper).map(|(_, id)| id))
+    }
+
+    /// Values of the documents within the bounds, decoded for a field of the type,
+    /// with the document ids. Used to answer queries without reading the documents.
+    pub fn values_in<'a>(
+        &'a self,
+        lower: &Bound<Value>,
+        upper: &Bound<Value>,
+        field_type: &'a FieldType,
+    ) -> impl Iterator<Item = Result<(Value, u64), DatabaseError>> + 'a {
+        self.entries_in(lower, upper)
+            .map(|(key, id)| decode_key_value(key, field_type).map(|(value, _)| (value, id)))
+    }
+
+    /// Encoded value and document id of the entries within the bounds, see `range`
+    fn entries_in<'a>(
+        &'a self,
+        lower: &Bound<Value>,
+        upper: &Bound<Value>,
+    ) -> Box<dyn Iterator<Item = (&'a [u8], u64)> + 'a> {
+        let encode = |bound: &Bound<Value>| match bound {
+            Bound::Included(value) => Bound::Included(encode_key(std::slice::from_ref(value))),
+            Bound::Excluded(value) => Bound::Excluded(encode_key(std::slice::from_ref(value))),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let (lower, upper) = (encode(lower), encode(upper));
+
+        // `BTreeMap::range` panics on inverted bounds, they match nothing anyway
+        let empty = match (&lower, &upper) {
+            (Bound::Included(low), Bound::Included(high)) => low > high,
+            (
+                Bound::Included(low) | Bound::Excluded(low),
+                Bound::Included(high) | Bound::Excluded(high),
+            ) => low >= high,
+            _ => false,
+        };
+
+        if empty {
+            return Box::new(std::iter::empty());
+        }
+
+        match &self.entries {
+            Entries::Ordered(entries) => Box::new(
+                entries
+                    .range((lower, upper))
+                    .flat_map(|(key, ids)| ids.iter().map(move |id| (key.as_slice(), *id))),
+            ),
+            Entries::Hashed(entries) => match (&lower, &upper) {
+                (Bound::Included(low), Bound::Included(high)) if low == high => Box::new(
+                    entries
+                        .get_key_value(low)
+                        .into_iter()
+                        .flat_map(|(key, ids)| ids.iter().map(move |id| (key.as_slice(), *id))),
+                ),
+                _ => Box::new(
+                    entries
+                        .iter()
+                        .filter(move |(key, _)| (lower.clone(), upper.clone()).contains(*key))
+                        .flat_map(|(key, ids)| ids.iter().map(move |id| (key.as_slice(), *id))),
+                ),
+            },
+        }
+    }
+}
+
+impl Collection {
+    /// Index the field, equality and range conditions on it are then answered from the index.
+    /// The index is kept in sync with every write, with `unique` a write giving a second
+    /// document the same value fails with `DuplicateKey`. Indexes are saved next to the
+    /// collection file and verified when it is opened, an index which is missing, stale or
+    /// damaged is rebuilt from the documents.
+    pub fn create_index(
+        &mut self,
+        field: &str,
+        options: IndexOptions,
+    ) -> Result<(), DatabaseError> {
+        if self.indexes.contains_key(field) {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "Index on field '{}' already exists",
+                field
+            )));
+        }
+        if !self.schema.fields.iter().any(|f| f.name == field) {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "Field '{}' not in schema '{}'",
+                field, self.schema.name
+            )));
+        }
+
+        let index = self.build_index(SecondaryIndex::new(field.to_string(), options))?;
+        self.indexes.insert(field.to_string(), index);
+        if self.file.is_some() {
+            self.save_to_file()?;
+        }
+        self.save_index_definitions()
+    }
+
+    pub fn drop_index(&mut self, field: &str) -> Result<(), DatabaseError> {
+        if self.indexes.remove(field).is_none() {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "No index on field '{}'",
+                field
+            )));
+        }
+        self.save_index_definitions()?;
+        if let Some(path) = self.index_entries_path(field)
+            && path.exists()
+        {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Reconstruct the index from the documents, e.g. after `verify_index` reported damage.
+    /// Fails with `DuplicateKey` if a unique index finds two documents with the same value.
+    pub fn rebuild_index(&mut self, field: &str) -> Result<(), DatabaseError> {
+        let Some(index) = self.indexes.get(field) else {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "No index on field '{}'",
+                field
+            )));
+        };
+
+        let index = self.build_index(SecondaryIndex::new(index.field.clone(), index.options))?;
+        self.indexes.insert(field.to_string(), index);
+        if self.file.is_some() {
+            self.save_to_file()?;
+        }
+        Ok(())
+    }
+
+    /// Check the index against the documents, an `InvalidData` error describes the first mismatch
+    pub fn verify_index(&self, field: &str) -> Result<(), DatabaseError> {
+        match self.indexes.get(field) {
+            Some(index) => index.verify(&self.documents),
+            None => Err(DatabaseError::InvalidQuery(format!(
+                "No index on field '{}'",
+                field
+            ))),
+        }
+    }
+
+    /// Fill the empty index with the documents
+    fn build_index(&self, mut index: SecondaryIndex) -> Result<SecondaryIndex, DatabaseError> {
+        for document in self.documents.values() {
+            if let Some(other) = index.conflict(document) {
+                return Err(DatabaseError::DuplicateKey(format!(
+                    "Documents {} and {} of collection '{}' hold the same '{}' value",
+                    other, document.id, self.schema.name, index.field
+                )));
+            }
+            index.insert(document);
+        }
+        Ok(index)
+    }
+
+    pub fn index(&self, field: &str) -> Option<&SecondaryIndex> {
+        self.indexes.get(field)
+    }
+}
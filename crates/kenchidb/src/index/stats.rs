@@ -0,0 +1,64 @@
+use crate::{
+    database::Collection,
+    index::{IndexKind, SecondaryIndex},
+    storage::index_blob::index_blob_page_count,
+};
+
+/// Size and value distribution of a secondary index, used by the query planner
+/// to estimate how many documents an index scan reads
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexStats {
+    pub field: String,
+    pub kind: IndexKind,
+    pub unique: bool,
+    /// Indexed documents, documents without a value for the field are not indexed
+    pub entries: usize,
+    /// Distinct indexed values. Exact, each distinct value is one key of the index
+    pub distinct_values: usize,
+    /// Pages of the index entries file
+    pub pages: u32,
+}
+
+impl IndexStats {
+    pub(crate) fn of(index: &SecondaryIndex) -> Self {
+        let mut entries = 0;
+        // Layout of `SecondaryIndex::serialize_entries`
+        let mut body_length = 4;
+        for (key, ids) in index.entries() {
+            entries += ids.len();
+            body_length += 4 + key.len() + 4 + 8 * ids.len();
+        }
+
+        Self {
+            field: index.field.clone(),
+            kind: index.options.kind,
+            unique: index.options.unique,
+            entries,
+            distinct_values: index.key_count(),
+            pages: index_blob_page_count(body_length),
+        }
+    }
+
+    /// Documents an equality condition on the field is expected to match
+    pub fn average_duplicates(&self) -> f64 {
+        if self.distinct_values == 0 {
+            return 0.0;
+        }
+        self.entries as f64 / self.distinct_values as f64
+    }
+
+    /// Fraction of the indexed documents an equality condition on the field is expected to match
+    pub fn selectivity(&self) -> f64 {
+        if self.distinct_values == 0 {
+            return 0.0;
+        }
+        1.0 / self.distinct_values as f64
+    }
+}
+
+impl Collection {
+    /// Statistics of each secondary index, ordered by field name
+    pub fn index_stats(&self) -> Vec<IndexStats> {
+        self.indexes.values().map(IndexStats::of).collect()
+    }
+}
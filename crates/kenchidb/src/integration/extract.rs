@@ -0,0 +1,105 @@
+use std::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use axum::{
+    extract::{FromRef, FromRequestParts},
+    http::{StatusCode, request::Parts},
+    response::{IntoResponse, Response},
+};
+
+use crate::{
+    common::DatabaseError,
+    integration::{DatabasePool, PooledSession},
+};
+
+/// Collection a `DbSession` extractor opens its session on, e.g.
+///
+/// ```ignore
+/// struct Users;
+///
+/// impl SessionCollection for Users {
+///     const NAME: &'static str = "users";
+/// }
+///
+/// async fn create_user(mut users: DbSession<Users>) -> Result<String, DatabaseRejection> {
+///     let id = users.insert(User::create().set("name", "alice").build())?;
+///     users.into_inner().commit()?;
+///     Ok(id.to_string())
+/// }
+/// ```
+pub trait SessionCollection {
+    const NAME: &'static str;
+}
+
+/// Session started for a single request from the `DatabasePool` in the router state.
+/// The state must provide the pool through `FromRef`, e.g. `Router::with_state(pool)`.
+pub struct DbSession<C> {
+    session: PooledSession,
+    collection: PhantomData<C>,
+}
+
+impl<C> DbSession<C> {
+    pub fn into_inner(self) -> PooledSession {
+        self.session
+    }
+}
+
+impl<C> Deref for DbSession<C> {
+    type Target = PooledSession;
+
+    fn deref(&self) -> &PooledSession {
+        &self.session
+    }
+}
+
+impl<C> DerefMut for DbSession<C> {
+    fn deref_mut(&mut self) -> &mut PooledSession {
+        &mut self.session
+    }
+}
+
+impl<S, C> FromRequestParts<S> for DbSession<C>
+where
+    DatabasePool: FromRef<S>,
+    S: Send + Sync,
+    C: SessionCollection,
+{
+    type Rejection = DatabaseRejection;
+
+    async fn from_request_parts(_parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let session = DatabasePool::from_ref(state).session(C::NAME)?;
+        Ok(Self {
+            session,
+            collection: PhantomData,
+        })
+    }
+}
+
+/// Database error returned from an extractor or a handler
+#[derive(Debug)]
+pub struct DatabaseRejection(pub DatabaseError);
+
+impl From<DatabaseError> for DatabaseRejection {
+    fn from(error: DatabaseError) -> Self {
+        Self(error)
+    }
+}
+
+impl IntoResponse for DatabaseRejection {
+    fn into_response(self) -> Response {
+        let status = match self.0 {
+            DatabaseError::DocumentNotFound(_) => StatusCode::NOT_FOUND,
+            DatabaseError::SchemaViolation(_)
+            | DatabaseError::InvalidIdentifier(_)
+            | DatabaseError::DuplicateKey(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            DatabaseError::Cancelled | DatabaseError::Locked(_) => StatusCode::SERVICE_UNAVAILABLE,
+            DatabaseError::Conflict { .. } | DatabaseError::TransactionPrepared(_) => {
+                StatusCode::CONFLICT
+            }
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, format!("{:?}", self.0)).into_response()
+    }
+}
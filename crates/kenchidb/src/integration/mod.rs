@@ -0,0 +1,5 @@
+#[cfg(feature = "axum")]
+pub(crate) mod extract;
+mod pool;
+
+pub(crate) use self::pool::*;
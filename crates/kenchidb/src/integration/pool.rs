@@ -0,0 +1,133 @@
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use crate::{
+    common::DatabaseError,
+    database::{Collection, Database},
+    index::{IndexBuild, IndexOptions},
+    session::Session,
+};
+
+/// Database shared between threads, e.g. the request handlers of a web server.
+/// Clones are cheap and share the same database. Reads take the lock in shared mode,
+/// sessions hold no lock while they run and take it exclusively only to commit.
+///
+/// A thread that panics while it holds the lock exclusively may leave the database half
+/// updated, every later access then fails with `DatabaseError::Poisoned`. Sessions run
+/// without the lock, a panicking session leaves the pool untouched.
+#[derive(Clone)]
+pub struct DatabasePool {
+    database: Arc<RwLock<Database>>,
+}
+
+impl DatabasePool {
+    pub fn new(database: Database) -> Self {
+        Self {
+            database: Arc::new(RwLock::new(database)),
+        }
+    }
+
+    /// Shared access, e.g. for queries and lookups
+    pub fn read(&self) -> Result<RwLockReadGuard<'_, Database>, DatabaseError> {
+        self.database.read().map_err(|_| DatabaseError::Poisoned)
+    }
+
+    /// Exclusive access, e.g. for creating collections and views
+    pub fn write(&self) -> Result<RwLockWriteGuard<'_, Database>, DatabaseError> {
+        self.database.write().map_err(|_| DatabaseError::Poisoned)
+    }
+
+    /// Start a session on the collection, the session keeps the pool but not the lock.
+    /// The lock is only held to share the documents of the collection with the session.
+    pub fn session(&self, collection: &str) -> Result<PooledSession, DatabaseError> {
+        let session = self.read()?.session(collection)?;
+        Ok(PooledSession {
+            pool: self.clone(),
+            collection: collection.to_string(),
+            session,
+        })
+    }
+
+    /// Add an index to a collection without blocking the other users of the pool, see
+    /// `Collection::start_index_build`. The lock is held only to start and to finish the
+    /// build, the documents are indexed in batches of `batch_size` without it.
+    pub fn build_index(
+        &self,
+        collection: &str,
+        field: &str,
+        options: IndexOptions,
+        batch_size: usize,
+    ) -> Result<(), DatabaseError> {
+        let mut build: IndexBuild = self.with_collection(collection, |target| {
+            target.start_index_build(field, options)
+        })?;
+
+        if let Err(e) = build.run(batch_size) {
+            self.with_collection(collection, |target| {
+                target.abort_index_build(build);
+                Ok(())
+            })?;
+            return Err(e);
+        }
+        self.with_collection(collection, |target| target.finish_index_build(build))
+    }
+
+    /// Run `f` on the collection with the lock held exclusively
+    fn with_collection<T>(
+        &self,
+        collection: &str,
+        f: impl FnOnce(&mut Collection) -> Result<T, DatabaseError>,
+    ) -> Result<T, DatabaseError> {
+        let mut database = self.write()?;
+        match database.collection(collection) {
+            Some(target) => f(target),
+            None => Err(DatabaseError::InvalidQuery(format!(
+                "Collection '{}' not found",
+                collection
+            ))),
+        }
+    }
+}
+
+impl From<Database> for DatabasePool {
+    fn from(database: Database) -> Self {
+        Self::new(database)
+    }
+}
+
+/// Session owned by a single request, it borrows nothing from the pool so it can be
+/// moved into a handler and passed on as `&Session`. Dropping it discards its writes.
+pub struct PooledSession {
+    pool: DatabasePool,
+    collection: String,
+    session: Session,
+}
+
+impl PooledSession {
+    pub fn collection_name(&self) -> &str {
+        &self.collection
+    }
+
+    /// Apply the writes to the pooled collection, see `Collection::commit`
+    pub fn commit(self) -> Result<Vec<u64>, DatabaseError> {
+        let session = self.session;
+        self.pool
+            .with_collection(&self.collection, |collection| collection.commit(session))
+    }
+}
+
+impl Deref for PooledSession {
+    type Target = Session;
+
+    fn deref(&self) -> &Session {
+        &self.session
+    }
+}
+
+impl DerefMut for PooledSession {
+    fn deref_mut(&mut self) -> &mut Session {
+        &mut self.session
+    }
+}
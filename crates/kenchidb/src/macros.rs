@@ -1,6 +1,6 @@
 use crate::{
-    database::Collection,
-    schema::{Document, Value},
+    common::DatabaseError,
+    schema::{Document, Schema, Value},
 };
 
 // Macro to define schemas with TypeScript-like syntax
@@ -8,67 +8,262 @@ use crate::{
 macro_rules! define_schema {
     (
         $schema_name:ident {
-            $($field_name:ident: $field_type:tt,)*
+            $($fields:tt)*
         }
     ) => {
         pub struct $schema_name;
 
         impl $schema_name {
-            pub fn schema() -> crate::schema::Schema {
-                crate::schema::Schema::new(
+            pub fn schema() -> $crate::schema::Schema {
+                $crate::schema::Schema::new(
                     stringify!($schema_name).to_string(),
-                    vec![
-                        $(
-                            define_schema!(@create_field $field_name, $field_type)
-                        ),*
-                    ]
+                    define_schema!(@fields [] $($fields)*)
                 )
             }
 
-            pub fn create() -> crate::macros::DocumentBuilder<$schema_name> {
-                crate::macros::DocumentBuilder::new()
+            pub fn create() -> $crate::macros::DocumentBuilder<$schema_name> {
+                $crate::macros::DocumentBuilder::new(Self::schema())
+            }
+        }
+
+        ::paste::paste! {
+            /// Query builder with a method per field and operator, checked at compile time
+            #[derive(Debug, Clone)]
+            pub struct [<$schema_name Query>] {
+                query: $crate::query::Query,
+            }
+
+            impl $schema_name {
+                pub fn query() -> [<$schema_name Query>] {
+                    [<$schema_name Query>] {
+                        query: $crate::query::Query::all(),
+                    }
+                }
+            }
+
+            // Method names are derived from field names like `is_active`
+            #[allow(clippy::wrong_self_convention)]
+            impl [<$schema_name Query>] {
+                define_schema!(@query_methods $($fields)*);
+
+                /// Match documents matching either query
+                pub fn or(self, other: Self) -> Self {
+                    Self {
+                        query: self.query.or(other.query),
+                    }
+                }
+
+                pub fn build(self) -> $crate::query::Query {
+                    self.query
+                }
+
+                fn and(self, query: $crate::query::Query) -> Self {
+                    Self {
+                        query: self.query.and(query),
+                    }
+                }
+            }
+
+            impl From<[<$schema_name Query>]> for $crate::query::Query {
+                fn from(query: [<$schema_name Query>]) -> Self {
+                    query.query
+                }
+            }
+
+            impl From<[<$schema_name Query>]> for $crate::query::FindQuery {
+                fn from(query: [<$schema_name Query>]) -> Self {
+                    $crate::query::FindQuery::new(query.query)
+                }
             }
         }
     };
 
-    // Handle non-nullable fields
-    (@create_field $field_name:ident, $field_type:ident) => {
-        crate::schema::Field {
-            name: stringify!($field_name).to_string(),
-            field_type: define_schema!(@field_type $field_type),
-            nullable: false,
+    // Generate the typed query methods of each field
+    (@query_methods $field_name:ident: $field_type:ident auto_increment, $($rest:tt)*) => {
+        define_schema!(@field_methods $field_name, $field_type);
+        define_schema!(@query_methods $($rest)*);
+    };
+
+    (
+        @query_methods
+        $field_name:ident: $field_type:ident $(($policy:ident))? $(?)?, $($rest:tt)*
+    ) => {
+        define_schema!(@field_methods $field_name, $field_type);
+        define_schema!(@query_methods $($rest)*);
+    };
+
+    (@query_methods) => {};
+
+    (@field_methods $field_name:ident, $field_type:ident) => {
+        define_schema!(@comparison_methods $field_name, $field_type, eq ne gt lt gte lte);
+
+        ::paste::paste! {
+            pub fn [<$field_name _between>](
+                self,
+                low: define_schema!(@rust_type $field_type),
+                high: define_schema!(@rust_type $field_type),
+            ) -> Self {
+                self.and($crate::query::Query::between(stringify!($field_name), low, high))
+            }
+
+            pub fn [<$field_name _in>](
+                self,
+                values: &[define_schema!(@rust_type $field_type)],
+            ) -> Self {
+                let values = values.iter().copied();
+                self.and($crate::query::Query::is_in(stringify!($field_name), values))
+            }
+
+            pub fn [<$field_name _not_in>](
+                self,
+                values: &[define_schema!(@rust_type $field_type)],
+            ) -> Self {
+                let values = values.iter().copied();
+                self.and($crate::query::Query::not_in(stringify!($field_name), values))
+            }
+
+            pub fn [<$field_name _exists>](self) -> Self {
+                self.and($crate::query::Query::exists(stringify!($field_name)))
+            }
+
+            pub fn [<$field_name _not_exists>](self) -> Self {
+                self.and($crate::query::Query::not_exists(stringify!($field_name)))
+            }
+
+            pub fn [<$field_name _is_null>](self) -> Self {
+                self.and($crate::query::Query::is_null(stringify!($field_name)))
+            }
+        }
+
+        define_schema!(@string_methods $field_name, $field_type);
+    };
+
+    (@comparison_methods $field_name:ident, $field_type:ident, $($operator:ident)*) => {
+        ::paste::paste! {
+            $(
+                pub fn [<$field_name _ $operator>](
+                    self,
+                    value: define_schema!(@rust_type $field_type),
+                ) -> Self {
+                    self.and($crate::query::Query::$operator(stringify!($field_name), value))
+                }
+            )*
         }
     };
 
-    // Handle nullable fields
-    (@create_field $field_name:ident, $field_type:ident?) => {
-        crate::schema::Field {
+    (@string_methods $field_name:ident, string) => {
+        ::paste::paste! {
+            pub fn [<$field_name _starts_with>](self, prefix: &str) -> Self {
+                self.and($crate::query::Query::starts_with(stringify!($field_name), prefix))
+            }
+
+            pub fn [<$field_name _ends_with>](self, suffix: &str) -> Self {
+                self.and($crate::query::Query::ends_with(stringify!($field_name), suffix))
+            }
+
+            pub fn [<$field_name _contains>](self, substring: &str) -> Self {
+                self.and($crate::query::Query::contains(stringify!($field_name), substring))
+            }
+
+            pub fn [<$field_name _like>](self, pattern: &str) -> Self {
+                self.and($crate::query::Query::like(stringify!($field_name), pattern))
+            }
+        }
+    };
+
+    (@string_methods $field_name:ident, $field_type:ident) => {};
+
+    // Collect the field definitions one at a time, nullable types end with `?`.
+    // String fields can set the overflow policy, e.g. `bio: string(truncate)?`
+    // and integer fields can take their values from a sequence, e.g. `number: long auto_increment`
+    (
+        @fields [$($field:expr),*]
+        $field_name:ident: $field_type:ident auto_increment, $($rest:tt)*
+    ) => {
+        define_schema!(
+            @fields [
+                $($field,)*
+                define_schema!(@create_field $field_name, $field_type, false, true,)
+            ] $($rest)*
+        )
+    };
+
+    (
+        @fields [$($field:expr),*]
+        $field_name:ident: $field_type:ident $(($policy:ident))??, $($rest:tt)*
+    ) => {
+        define_schema!(
+            @fields [
+                $($field,)*
+                define_schema!(@create_field $field_name, $field_type, true, false, $($policy)?)
+            ] $($rest)*
+        )
+    };
+
+    (
+        @fields [$($field:expr),*]
+        $field_name:ident: $field_type:ident $(($policy:ident))?, $($rest:tt)*
+    ) => {
+        define_schema!(
+            @fields [
+                $($field,)*
+                define_schema!(@create_field $field_name, $field_type, false, false, $($policy)?)
+            ] $($rest)*
+        )
+    };
+
+    (@fields [$($field:expr),*]) => {
+        vec![$($field),*]
+    };
+
+    (
+        @create_field $field_name:ident, $field_type:ident,
+        $nullable:literal, $auto_increment:literal, $($policy:ident)?
+    ) => {
+        $crate::schema::Field {
             name: stringify!($field_name).to_string(),
             field_type: define_schema!(@field_type $field_type),
-            nullable: true,
+            nullable: $nullable,
+            on_overflow: define_schema!(@overflow $($policy)?),
+            auto_increment: $auto_increment,
         }
     };
 
-    (@field_type byte) => { crate::schema::FieldType::Byte };
-    (@field_type short) => { crate::schema::FieldType::Short };
-    (@field_type int) => { crate::schema::FieldType::Int };
-    (@field_type long) => { crate::schema::FieldType::Long };
-    (@field_type float) => { crate::schema::FieldType::Float };
-    (@field_type double) => { crate::schema::FieldType::Double };
-    (@field_type string) => { crate::schema::FieldType::String };
-    (@field_type boolean) => { crate::schema::FieldType::Boolean };
+    (@overflow) => { $crate::schema::StringOverflow::Error };
+    (@overflow error) => { $crate::schema::StringOverflow::Error };
+    (@overflow truncate) => { $crate::schema::StringOverflow::Truncate };
+
+    (@field_type byte) => { $crate::schema::FieldType::Byte };
+    (@field_type short) => { $crate::schema::FieldType::Short };
+    (@field_type int) => { $crate::schema::FieldType::Int };
+    (@field_type long) => { $crate::schema::FieldType::Long };
+    (@field_type float) => { $crate::schema::FieldType::Float };
+    (@field_type double) => { $crate::schema::FieldType::Double };
+    (@field_type string) => { $crate::schema::FieldType::String };
+    (@field_type boolean) => { $crate::schema::FieldType::Boolean };
+
+    (@rust_type byte) => { u8 };
+    (@rust_type short) => { i16 };
+    (@rust_type int) => { i32 };
+    (@rust_type long) => { i64 };
+    (@rust_type float) => { f32 };
+    (@rust_type double) => { f64 };
+    (@rust_type string) => { &str };
+    (@rust_type boolean) => { bool };
 }
 
 // Document builder for type-safe document creation
 pub struct DocumentBuilder<T> {
     document: Document,
+    schema: Schema,
     _phantom: std::marker::PhantomData<T>,
 }
 
 impl<T> DocumentBuilder<T> {
-    pub fn new() -> Self {
+    pub fn new(schema: Schema) -> Self {
         Self {
             document: Document::new(0), // ID will be set by collection
+            schema,
             _phantom: std::marker::PhantomData,
         }
     }
@@ -78,9 +273,19 @@ impl<T> DocumentBuilder<T> {
         self
     }
 
-    pub fn build(self) -> Document {
+    /// Build the document, truncating long strings of fields with the truncate policy
+    pub fn build(mut self) -> Document {
+        self.schema.normalize_document(&mut self.document);
         self.document
     }
+
+    /// Build the document and validate it against the schema
+    pub fn try_build(self) -> Result<Document, DatabaseError> {
+        let schema = self.schema.clone();
+        let document = self.build();
+        schema.validate_document(&document)?;
+        Ok(document)
+    }
 }
 
 // Implement Into<Value> for all primitive types
@@ -120,14 +325,9 @@ impl From<f64> for Value {
     }
 }
 
+// String length limits are enforced by the schema, see `StringOverflow`
 impl From<String> for Value {
     fn from(val: String) -> Self {
-        if val.chars().count() > 255 {
-            panic!(
-                "String too long: {} characters (max 255)",
-                val.chars().count()
-            );
-        }
         Value::String(val)
     }
 }
@@ -143,94 +343,3 @@ impl From<bool> for Value {
         Value::Boolean(val)
     }
 }
-
-// Query builder for type-safe queries
-pub struct QueryBuilder<T> {
-    _phantom: std::marker::PhantomData<T>,
-}
-
-impl<T> QueryBuilder<T> {
-    pub fn new() -> Self {
-        Self {
-            _phantom: std::marker::PhantomData,
-        }
-    }
-
-    // Simple field-based filtering (can be extended)
-    pub fn where_eq(&self, field: &str, value: Value) -> SimpleQuery {
-        SimpleQuery {
-            field: field.to_string(),
-            operation: QueryOperation::Equals,
-            value,
-        }
-    }
-}
-
-#[derive(Debug)]
-pub struct SimpleQuery {
-    pub field: String,
-    pub operation: QueryOperation,
-    pub value: Value,
-}
-
-#[derive(Debug)]
-pub enum QueryOperation {
-    Equals,
-    NotEquals,
-    GreaterThan,
-    LessThan,
-}
-
-impl SimpleQuery {
-    pub fn matches(&self, document: &Document) -> bool {
-        if let Some(doc_value) = document.get(&self.field) {
-            match self.operation {
-                QueryOperation::Equals => doc_value == &self.value,
-                QueryOperation::NotEquals => doc_value != &self.value,
-                QueryOperation::GreaterThan => self.compare_greater(doc_value, &self.value),
-                QueryOperation::LessThan => self.compare_less(doc_value, &self.value),
-            }
-        } else {
-            false
-        }
-    }
-
-    fn compare_greater(&self, left: &Value, right: &Value) -> bool {
-        match (left, right) {
-            (Value::Byte(a), Value::Byte(b)) => a > b,
-            (Value::Short(a), Value::Short(b)) => a > b,
-            (Value::Int(a), Value::Int(b)) => a > b,
-            (Value::Long(a), Value::Long(b)) => a > b,
-            (Value::Float(a), Value::Float(b)) => a > b,
-            (Value::Double(a), Value::Double(b)) => a > b,
-            (Value::String(a), Value::String(b)) => a > b,
-            _ => false,
-        }
-    }
-
-    fn compare_less(&self, left: &Value, right: &Value) -> bool {
-        match (left, right) {
-            (Value::Byte(a), Value::Byte(b)) => a < b,
-            (Value::Short(a), Value::Short(b)) => a < b,
-            (Value::Int(a), Value::Int(b)) => a < b,
-            (Value::Long(a), Value::Long(b)) => a < b,
-            (Value::Float(a), Value::Float(b)) => a < b,
-            (Value::Double(a), Value::Double(b)) => a < b,
-            (Value::String(a), Value::String(b)) => a < b,
-            _ => false,
-        }
-    }
-}
-
-impl Collection {
-    pub fn find_where(&self, query: &SimpleQuery) -> Vec<&Document> {
-        self.documents
-            .values()
-            .filter(|doc| query.matches(doc))
-            .collect()
-    }
-
-    pub fn find_one_where(&self, query: &SimpleQuery) -> Option<&Document> {
-        self.documents.values().find(|doc| query.matches(doc))
-    }
-}
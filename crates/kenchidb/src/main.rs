@@ -1,10 +1,29 @@
-use crate::{common::DatabaseError, database::Database};
+use std::io;
+
+use crate::{
+    common::DatabaseError,
+    database::Database,
+    integration::DatabasePool,
+    query::{Direction, DocumentPatch, Expr, FindQuery, PreparedQuery, Query, QueryResult, Update},
+    search::SearchHit,
+    session::Snapshot,
+    storage::trace::StructuralTrace,
+};
 
 mod common;
 mod database;
+mod export;
+mod index;
+mod integration;
 mod macros;
+mod query;
 mod schema;
+mod search;
+mod session;
 mod storage;
+mod test;
+mod transaction;
+mod watch;
 
 define_schema! {
     User {
@@ -18,13 +37,32 @@ define_schema! {
 }
 
 fn main() -> Result<(), DatabaseError> {
+    let args: Vec<String> = std::env::args().collect();
+    if let [_, command, path] = args.as_slice() {
+        match command.as_str() {
+            // `kenchidb trace <path>` dumps a structural trace file
+            "trace" => return StructuralTrace::dump(path, &mut io::stdout()),
+            // `kenchidb views <catalog>` lists the saved views
+            "views" => {
+                for view in Database::with_catalog(path)?.views() {
+                    println!("{}", view);
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+    }
+
     println!("🗄️  KenchiDB Demo");
 
     // Create database
     let mut db = Database::new();
 
     // Create collections with schemas
-    db.create_collection("users".to_string(), User::schema())?;
+    db.create_collection(
+        "users".to_string(),
+        User::schema().with_text_index(&["name", "email"]),
+    )?;
 
     // Insert users
     let users = db.collection("users").unwrap();
@@ -65,5 +103,110 @@ fn main() -> Result<(), DatabaseError> {
         user1_id, user2_id, user3_id
     );
 
-    return Ok(());
+    // Query active users with a large balance, or anyone older than 30
+    let query = User::query()
+        .is_active_eq(true)
+        .balance_gt(1000.0)
+        .or(User::query().age_gt(30));
+    let results = users.find_where(query)?;
+    println!("🔍 Found {} matching users", results.len());
+
+    // Pay interest to active users
+    let interest = Update::new().set("balance", Expr::field("balance") * 1.01);
+    let updated = users.update_where(Query::eq("is_active", true), &interest)?;
+    println!("📈 Paid interest to {} users", updated);
+
+    // Reactivate a user and bump the age in place
+    users.patch(
+        user2_id,
+        &DocumentPatch::new()
+            .set("is_active", true)
+            .increment("age", 1i32),
+    )?;
+
+    // Page through users ordered by balance
+    let richest = FindQuery::new(Query::all())
+        .order_by("balance", Direction::Desc)
+        .limit(2);
+    for user in users.find_where(&richest)? {
+        println!("💰 {:?}: {:?}", user.get("name"), user.get("balance"));
+    }
+
+    // Prepare a query once and run it with different parameters
+    let older_than: PreparedQuery = users.prepare("age > $1 AND is_active = true")?;
+    for age in [21i32, 30] {
+        let results = users.find_prepared(&older_than, &[age.into()])?;
+        println!("🎂 {} active users older than {}", results.len(), age);
+    }
+
+    // Stage writes in a session, others only see them once they are committed
+    let mut session = users.session();
+    session.delete(user3_id)?;
+    println!("🧾 {} users in the session", session.find_all().len());
+    let before: Snapshot = users.snapshot();
+    users.commit(session)?;
+    println!(
+        "🧾 {} users before the commit, {} after",
+        before.find_all().len(),
+        users.find_all().len()
+    );
+
+    // Rank users by the terms of their name and email
+    let hits: Vec<SearchHit> = users.search("alice johnson")?;
+    for hit in hits {
+        println!("🔎 {:?} ({:.2})", hit.document.get("name"), hit.score);
+    }
+
+    // Log what a query cost
+    let (found, stats) = users.query(Query::gt("age", 25i32))?.with_stats();
+    println!("📊 {} users over 25, {}", found.len(), stats);
+
+    // Stream the users as JSON
+    let active: QueryResult = users.query(Query::eq("is_active", true))?;
+    active.to_json_writer(&mut io::stdout())?;
+    println!();
+
+    // Save a named query and run it by name
+    db.create_view(
+        "active_adults",
+        "users",
+        Query::eq("is_active", true).and(Query::gte("age", 18i32)),
+    )?;
+    for view in db.views() {
+        let results = db.find_view(&view.name)?;
+        println!("👀 View '{}' matches {} users", view.name, results.len());
+    }
+
+    // Ad hoc query in the text syntax
+    let youngest = db.query("users", "is_active = true ORDER BY age LIMIT 1")?;
+    for user in youngest.iter() {
+        println!("🐣 Youngest active user: {:?}", user.get("name"));
+    }
+
+    // Share the database between threads, each with its own session
+    let pool = DatabasePool::new(db);
+    let worker = pool.clone();
+    let inserted = std::thread::spawn(move || {
+        let mut session = worker.session("users")?;
+        session.insert(
+            User::create()
+                .set("id", 4i64)
+                .set("name", "Dana White")
+                .set("email", "dana@example.com")
+                .set("age", 41i32)
+                .set("is_active", true)
+                .set("balance", 300.0f64)
+                .build(),
+        )?;
+        session.commit()
+    })
+    .join()
+    .expect("worker thread panicked")?;
+    println!(
+        "🧵 Worker inserted {:?}, {} users now",
+        inserted,
+        pool.read()?.query("users", "")?.iter().count()
+    );
+
+    Ok(())
 }
@@ -0,0 +1,368 @@
+use crate::{
+    common::DatabaseError,
+    query::{
+        Case, Collation, Condition, Direction, FindQuery, OrderBy, Query, QueryOperation, SortKey,
+    },
+    schema::Value,
+};
+
+/**
+ * Tags of the encoded query nodes.
+ */
+const QUERY_CONDITION_TAG: u8 = 0;
+const QUERY_AND_TAG: u8 = 1;
+const QUERY_OR_TAG: u8 = 2;
+const QUERY_NOT_TAG: u8 = 3;
+
+/**
+ * Tags of the encoded condition operations.
+ */
+const OP_EQUALS_TAG: u8 = 0;
+const OP_NOT_EQUALS_TAG: u8 = 1;
+const OP_GREATER_THAN_TAG: u8 = 2;
+const OP_LESS_THAN_TAG: u8 = 3;
+const OP_GREATER_OR_EQUAL_TAG: u8 = 4;
+const OP_LESS_OR_EQUAL_TAG: u8 = 5;
+const OP_BETWEEN_TAG: u8 = 6;
+const OP_IN_TAG: u8 = 7;
+const OP_NOT_IN_TAG: u8 = 8;
+const OP_STARTS_WITH_TAG: u8 = 9;
+const OP_ENDS_WITH_TAG: u8 = 10;
+const OP_CONTAINS_TAG: u8 = 11;
+const OP_LIKE_TAG: u8 = 12;
+const OP_EXISTS_TAG: u8 = 13;
+const OP_NOT_EXISTS_TAG: u8 = 14;
+const OP_IS_NULL_TAG: u8 = 15;
+
+/// Binary encoding of a find query, used to persist query definitions.
+/// All integers are little endian, strings are length prefixed.
+pub fn encode_find_query(query: &FindQuery, bytes: &mut Vec<u8>) -> Result<(), DatabaseError> {
+    encode_query(&query.filter, bytes)?;
+
+    // Key count, then each key with the collation in the bits above the direction
+    let keys = query
+        .order_by
+        .as_ref()
+        .map_or(&[][..], |order_by| &order_by.keys);
+    bytes.push(keys.len() as u8);
+    for key in keys {
+        encode_short_string(&key.field, bytes);
+        let direction = match key.direction {
+            Direction::Asc => 0,
+            Direction::Desc => 1,
+        };
+        let collation = match key.collation {
+            Collation::Binary => 0,
+            Collation::CaseInsensitive => 1,
+        };
+        bytes.push(direction | collation << 1);
+    }
+
+    bytes.extend_from_slice(&(query.offset as u64).to_le_bytes());
+    // Flags of the optional limit, sample size and projection that follow in this order
+    let flags = query.limit.is_some() as u8
+        | (query.sample.is_some() as u8) << 1
+        | (query.projection.is_some() as u8) << 2;
+    bytes.push(flags);
+    for value in [query.limit, query.sample].into_iter().flatten() {
+        bytes.extend_from_slice(&(value as u64).to_le_bytes());
+    }
+    if let Some(fields) = &query.projection {
+        bytes.push(fields.len() as u8);
+        for field in fields {
+            encode_short_string(field, bytes);
+        }
+    }
+    Ok(())
+}
+
+pub fn decode_find_query(reader: &mut ByteReader) -> Result<FindQuery, DatabaseError> {
+    let filter = decode_query(reader)?;
+
+    let key_count = reader.read_u8()?;
+    let mut keys = Vec::with_capacity(key_count as usize);
+    for _ in 0..key_count {
+        let field = reader.read_short_string()?;
+        let flags = reader.read_u8()?;
+        let direction = match flags & 1 {
+            0 => Direction::Asc,
+            _ => Direction::Desc,
+        };
+        let collation = match flags >> 1 {
+            0 => Collation::Binary,
+            _ => Collation::CaseInsensitive,
+        };
+        keys.push(SortKey::new(&field, direction).collate(collation));
+    }
+    let order_by = (!keys.is_empty()).then_some(OrderBy { keys });
+
+    let offset = reader.read_u64()? as usize;
+    let flags = reader.read_u8()?;
+    let limit = match flags & 1 {
+        0 => None,
+        _ => Some(reader.read_u64()? as usize),
+    };
+    let sample = match flags & 2 {
+        0 => None,
+        _ => Some(reader.read_u64()? as usize),
+    };
+    let projection = match flags & 4 {
+        0 => None,
+        _ => {
+            let count = reader.read_u8()?;
+            let mut fields = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                fields.push(reader.read_short_string()?);
+            }
+            Some(fields)
+        }
+    };
+
+    Ok(FindQuery {
+        filter,
+        projection,
+        sample,
+        order_by,
+        offset,
+        limit,
+    })
+}
+
+fn encode_query(query: &Query, bytes: &mut Vec<u8>) -> Result<(), DatabaseError> {
+    match query {
+        Query::Condition(condition) => {
+            bytes.push(QUERY_CONDITION_TAG);
+            encode_condition(condition, bytes)?;
+        }
+        Query::And(queries) | Query::Or(queries) => {
+            bytes.push(match query {
+                Query::And(_) => QUERY_AND_TAG,
+                _ => QUERY_OR_TAG,
+            });
+            bytes.extend_from_slice(&(queries.len() as u32).to_le_bytes());
+            for query in queries {
+                encode_query(query, bytes)?;
+            }
+        }
+        Query::Not(query) => {
+            bytes.push(QUERY_NOT_TAG);
+            encode_query(query, bytes)?;
+        }
+    }
+    Ok(())
+}
+
+fn decode_query(reader: &mut ByteReader) -> Result<Query, DatabaseError> {
+    match reader.read_u8()? {
+        QUERY_CONDITION_TAG => Ok(Query::Condition(decode_condition(reader)?)),
+        tag @ (QUERY_AND_TAG | QUERY_OR_TAG) => {
+            let count = reader.read_u32()? as usize;
+            let mut queries = Vec::with_capacity(count.min(reader.remaining()));
+            for _ in 0..count {
+                queries.push(decode_query(reader)?);
+            }
+            Ok(match tag {
+                QUERY_AND_TAG => Query::And(queries),
+                _ => Query::Or(queries),
+            })
+        }
+        QUERY_NOT_TAG => Ok(Query::Not(Box::new(decode_query(reader)?))),
+        tag => Err(DatabaseError::InvalidData(format!(
+            "Unknown query tag: {}",
+            tag
+        ))),
+    }
+}
+
+fn encode_condition(condition: &Condition, bytes: &mut Vec<u8>) -> Result<(), DatabaseError> {
+    encode_short_string(&condition.field, bytes);
+
+    match &condition.operation {
+        QueryOperation::Equals(value) => encode_tagged_value(OP_EQUALS_TAG, value, bytes)?,
+        QueryOperation::NotEquals(value) => encode_tagged_value(OP_NOT_EQUALS_TAG, value, bytes)?,
+        QueryOperation::GreaterThan(value) => {
+            encode_tagged_value(OP_GREATER_THAN_TAG, value, bytes)?
+        }
+        QueryOperation::LessThan(value) => encode_tagged_value(OP_LESS_THAN_TAG, value, bytes)?,
+        QueryOperation::GreaterOrEqual(value) => {
+            encode_tagged_value(OP_GREATER_OR_EQUAL_TAG, value, bytes)?
+        }
+        QueryOperation::LessOrEqual(value) => {
+            encode_tagged_value(OP_LESS_OR_EQUAL_TAG, value, bytes)?
+        }
+        QueryOperation::Between(low, high) => {
+            bytes.push(OP_BETWEEN_TAG);
+            bytes.extend_from_slice(&low.serialize()?);
+            bytes.extend_from_slice(&high.serialize()?);
+        }
+        QueryOperation::In(values) | QueryOperation::NotIn(values) => {
+            bytes.push(match condition.operation {
+                QueryOperation::In(_) => OP_IN_TAG,
+                _ => OP_NOT_IN_TAG,
+            });
+            bytes.extend_from_slice(&(values.len() as u32).to_le_bytes());
+            for value in values {
+                bytes.extend_from_slice(&value.serialize()?);
+            }
+        }
+        QueryOperation::StartsWith(pattern, case) => {
+            encode_pattern(OP_STARTS_WITH_TAG, pattern, *case, bytes)
+        }
+        QueryOperation::EndsWith(pattern, case) => {
+            encode_pattern(OP_ENDS_WITH_TAG, pattern, *case, bytes)
+        }
+        QueryOperation::Contains(pattern, case) => {
+            encode_pattern(OP_CONTAINS_TAG, pattern, *case, bytes)
+        }
+        QueryOperation::Like(pattern, case) => encode_pattern(OP_LIKE_TAG, pattern, *case, bytes),
+        QueryOperation::Exists => bytes.push(OP_EXISTS_TAG),
+        QueryOperation::NotExists => bytes.push(OP_NOT_EXISTS_TAG),
+        QueryOperation::IsNull => bytes.push(OP_IS_NULL_TAG),
+    }
+    Ok(())
+}
+
+fn decode_condition(reader: &mut ByteReader) -> Result<Condition, DatabaseError> {
+    let field = reader.read_short_string()?;
+
+    let operation = match reader.read_u8()? {
+        OP_EQUALS_TAG => QueryOperation::Equals(reader.read_value()?),
+        OP_NOT_EQUALS_TAG => QueryOperation::NotEquals(reader.read_value()?),
+        OP_GREATER_THAN_TAG => QueryOperation::GreaterThan(reader.read_value()?),
+        OP_LESS_THAN_TAG => QueryOperation::LessThan(reader.read_value()?),
+        OP_GREATER_OR_EQUAL_TAG => QueryOperation::GreaterOrEqual(reader.read_value()?),
+        OP_LESS_OR_EQUAL_TAG => QueryOperation::LessOrEqual(reader.read_value()?),
+        OP_BETWEEN_TAG => QueryOperation::Between(reader.read_value()?, reader.read_value()?),
+        tag @ (OP_IN_TAG | OP_NOT_IN_TAG) => {
+            let count = reader.read_u32()? as usize;
+            let mut values = Vec::with_capacity(count.min(reader.remaining()));
+            for _ in 0..count {
+                values.push(reader.read_value()?);
+            }
+            match tag {
+                OP_IN_TAG => QueryOperation::In(values),
+                _ => QueryOperation::NotIn(values),
+            }
+        }
+        tag @ (OP_STARTS_WITH_TAG..=OP_LIKE_TAG) => {
+            let pattern = reader.read_string()?;
+            let case = match reader.read_u8()? {
+                0 => Case::Sensitive,
+                _ => Case::Insensitive,
+            };
+            match tag {
+                OP_STARTS_WITH_TAG => QueryOperation::StartsWith(pattern, case),
+                OP_ENDS_WITH_TAG => QueryOperation::EndsWith(pattern, case),
+                OP_CONTAINS_TAG => QueryOperation::Contains(pattern, case),
+                _ => QueryOperation::Like(pattern, case),
+            }
+        }
+        OP_EXISTS_TAG => QueryOperation::Exists,
+        OP_NOT_EXISTS_TAG => QueryOperation::NotExists,
+        OP_IS_NULL_TAG => QueryOperation::IsNull,
+        tag => {
+            return Err(DatabaseError::InvalidData(format!(
+                "Unknown query operation tag: {}",
+                tag
+            )));
+        }
+    };
+
+    Ok(Condition { field, operation })
+}
+
+fn encode_tagged_value(tag: u8, value: &Value, bytes: &mut Vec<u8>) -> Result<(), DatabaseError> {
+    bytes.push(tag);
+    bytes.extend_from_slice(&value.serialize()?);
+    Ok(())
+}
+
+fn encode_pattern(tag: u8, pattern: &str, case: Case, bytes: &mut Vec<u8>) {
+    bytes.push(tag);
+    encode_string(pattern, bytes);
+    bytes.push(match case {
+        Case::Sensitive => 0,
+        Case::Insensitive => 1,
+    });
+}
+
+/// Identifier, at most 255 bytes
+pub fn encode_short_string(value: &str, bytes: &mut Vec<u8>) {
+    bytes.push(value.len() as u8);
+    bytes.extend_from_slice(value.as_bytes());
+}
+
+pub fn encode_string(value: &str, bytes: &mut Vec<u8>) {
+    bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(value.as_bytes());
+}
+
+/// Bounds checked cursor over encoded bytes
+pub struct ByteReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, offset: 0 }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.bytes.len() - self.offset
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], DatabaseError> {
+        if len > self.remaining() {
+            return Err(DatabaseError::InvalidData(format!(
+                "Unexpected end of data at offset {}",
+                self.offset
+            )));
+        }
+
+        let bytes = &self.bytes[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, DatabaseError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, DatabaseError> {
+        let bytes = self.read_bytes(2)?;
+        Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, DatabaseError> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, DatabaseError> {
+        let bytes = self.read_bytes(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub fn read_short_string(&mut self) -> Result<String, DatabaseError> {
+        let len = self.read_u8()? as usize;
+        self.read_utf8(len)
+    }
+
+    pub fn read_string(&mut self) -> Result<String, DatabaseError> {
+        let len = self.read_u32()? as usize;
+        self.read_utf8(len)
+    }
+
+    pub fn read_value(&mut self) -> Result<Value, DatabaseError> {
+        let (value, size) = Value::deserialize(&self.bytes[self.offset..])?;
+        self.offset += size;
+        Ok(value)
+    }
+
+    fn read_utf8(&mut self, len: usize) -> Result<String, DatabaseError> {
+        let bytes = self.read_bytes(len)?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|e| DatabaseError::InvalidData(format!("Invalid UTF-8: {}", e)))
+    }
+}
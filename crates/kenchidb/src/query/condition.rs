@@ -0,0 +1,284 @@
+use std::{cmp::Ordering, fmt};
+
+use crate::{
+    common::DatabaseError,
+    schema::{Document, FieldType, MAX_STRING_BYTES, Schema, Value},
+};
+
+/// Single field predicate, the leaf of a query expression
+#[derive(Debug, Clone)]
+pub struct Condition {
+    pub field: String,
+    pub operation: QueryOperation,
+}
+
+#[derive(Debug, Clone)]
+pub enum QueryOperation {
+    Equals(Value),
+    NotEquals(Value),
+    GreaterThan(Value),
+    LessThan(Value),
+    GreaterOrEqual(Value),
+    LessOrEqual(Value),
+    /// Inclusive range, `low <= value <= high`
+    Between(Value, Value),
+    In(Vec<Value>),
+    NotIn(Vec<Value>),
+    StartsWith(String, Case),
+    EndsWith(String, Case),
+    Contains(String, Case),
+    /// SQL style pattern, `%` matches any sequence of characters and `_` a single character
+    Like(String, Case),
+    /// The document has the field, an explicit null counts as present
+    Exists,
+    /// The document does not have the field
+    NotExists,
+    /// The field is null or missing
+    IsNull,
+}
+
+/// Case sensitivity of string pattern operators
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Case {
+    Sensitive,
+    Insensitive,
+}
+
+impl Condition {
+    pub fn new(field: &str, operation: QueryOperation) -> Self {
+        Self {
+            field: field.to_string(),
+            operation,
+        }
+    }
+
+    /// Check that the field exists in the schema and the operands have the field's type.
+    /// Presence and null checks may name undeclared fields of lenient schemas.
+    pub fn validate(&self, schema: &Schema) -> Result<(), DatabaseError> {
+        let presence = matches!(
+            self.operation,
+            QueryOperation::Exists | QueryOperation::NotExists | QueryOperation::IsNull
+        );
+        let Some(field) = schema.fields.iter().find(|f| f.name == self.field) else {
+            if presence && schema.lenient {
+                return Ok(());
+            }
+            return Err(DatabaseError::InvalidQuery(format!(
+                "Field '{}' not in schema '{}'",
+                self.field, schema.name
+            )));
+        };
+
+        let operands: Vec<&Value> = match &self.operation {
+            QueryOperation::Equals(value)
+            | QueryOperation::NotEquals(value)
+            | QueryOperation::GreaterThan(value)
+            | QueryOperation::LessThan(value)
+            | QueryOperation::GreaterOrEqual(value)
+            | QueryOperation::LessOrEqual(value) => vec![value],
+            QueryOperation::Between(low, high) => vec![low, high],
+            QueryOperation::In(values) | QueryOperation::NotIn(values) => values.iter().collect(),
+            QueryOperation::StartsWith(..)
+            | QueryOperation::EndsWith(..)
+            | QueryOperation::Contains(..)
+            | QueryOperation::Like(..) => {
+                if field.field_type != FieldType::String {
+                    return Err(DatabaseError::InvalidQuery(format!(
+                        "Field '{}' has type {:?}, string pattern operators require String",
+                        field.name, field.field_type
+                    )));
+                }
+                vec![]
+            }
+            QueryOperation::Exists | QueryOperation::NotExists | QueryOperation::IsNull => vec![],
+        };
+
+        for value in operands {
+            if !field.field_type.validates(value) {
+                return Err(DatabaseError::InvalidQuery(format!(
+                    "Field '{}' has type {:?}, but the query compares it with {}",
+                    field.name,
+                    field.field_type,
+                    value.type_name()
+                )));
+            }
+
+            if let Value::String(value) = value
+                && value.len() > MAX_STRING_BYTES
+            {
+                return Err(DatabaseError::InvalidQuery(format!(
+                    "Field '{}' is compared with a {} byte string (max {})",
+                    field.name,
+                    value.len(),
+                    MAX_STRING_BYTES
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn matches(&self, document: &Document) -> bool {
+        let value = document.get(&self.field);
+        match &self.operation {
+            QueryOperation::Exists => return value.is_some(),
+            QueryOperation::NotExists => return value.is_none(),
+            QueryOperation::IsNull => return matches!(value, None | Some(Value::Null)),
+            _ => {}
+        }
+
+        // Null values never match a comparison, like missing ones
+        if let Some(doc_value) = value.filter(|value| **value != Value::Null) {
+            match &self.operation {
+                QueryOperation::Equals(value) => doc_value == value,
+                QueryOperation::NotEquals(value) => doc_value != value,
+                QueryOperation::GreaterThan(value) => {
+                    compare_values(doc_value, value) == Some(Ordering::Greater)
+                }
+                QueryOperation::LessThan(value) => {
+                    compare_values(doc_value, value) == Some(Ordering::Less)
+                }
+                QueryOperation::GreaterOrEqual(value) => matches!(
+                    compare_values(doc_value, value),
+                    Some(Ordering::Greater | Ordering::Equal)
+                ),
+                QueryOperation::LessOrEqual(value) => matches!(
+                    compare_values(doc_value, value),
+                    Some(Ordering::Less | Ordering::Equal)
+                ),
+                QueryOperation::Between(low, high) => {
+                    matches!(
+                        compare_values(doc_value, low),
+                        Some(Ordering::Greater | Ordering::Equal)
+                    ) && matches!(
+                        compare_values(doc_value, high),
+                        Some(Ordering::Less | Ordering::Equal)
+                    )
+                }
+                QueryOperation::In(values) => values.contains(doc_value),
+                QueryOperation::NotIn(values) => !values.contains(doc_value),
+                QueryOperation::StartsWith(pattern, case) => {
+                    match_string(doc_value, pattern, *case, |s, p| s.starts_with(p))
+                }
+                QueryOperation::EndsWith(pattern, case) => {
+                    match_string(doc_value, pattern, *case, |s, p| s.ends_with(p))
+                }
+                QueryOperation::Contains(pattern, case) => {
+                    match_string(doc_value, pattern, *case, |s, p| s.contains(p))
+                }
+                QueryOperation::Like(pattern, case) => {
+                    match_string(doc_value, pattern, *case, like_matches)
+                }
+                QueryOperation::Exists | QueryOperation::NotExists | QueryOperation::IsNull => {
+                    unreachable!()
+                }
+            }
+        } else {
+            false
+        }
+    }
+}
+
+/// Order two values of the same type, values of different types are not comparable
+pub fn compare_values(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Byte(a), Value::Byte(b)) => a.partial_cmp(b),
+        (Value::Short(a), Value::Short(b)) => a.partial_cmp(b),
+        (Value::Int(a), Value::Int(b)) => a.partial_cmp(b),
+        (Value::Long(a), Value::Long(b)) => a.partial_cmp(b),
+        (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+        (Value::Double(a), Value::Double(b)) => a.partial_cmp(b),
+        (Value::String(a), Value::String(b)) => a.partial_cmp(b),
+        (Value::Boolean(a), Value::Boolean(b)) => a.partial_cmp(b),
+        _ => None,
+    }
+}
+
+/// Apply a string predicate to string values, other value types never match
+fn match_string<F>(value: &Value, pattern: &str, case: Case, predicate: F) -> bool
+where
+    F: Fn(&str, &str) -> bool,
+{
+    let Value::String(value) = value else {
+        return false;
+    };
+
+    match case {
+        Case::Sensitive => predicate(value, pattern),
+        Case::Insensitive => predicate(&value.to_lowercase(), &pattern.to_lowercase()),
+    }
+}
+
+/// Match a LIKE pattern, backtracking to the last `%` on mismatch
+fn like_matches(value: &str, pattern: &str) -> bool {
+    let value: Vec<char> = value.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+
+    let (mut v, mut p) = (0, 0);
+    // Position after the last `%` and the value position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while v < value.len() {
+        // A `%` is a wildcard even where the value holds a literal `%`
+        if p < pattern.len() && pattern[p] == '%' {
+            p += 1;
+            backtrack = Some((p, v));
+        } else if p < pattern.len() && (pattern[p] == '_' || pattern[p] == value[v]) {
+            v += 1;
+            p += 1;
+        } else if let Some((backtrack_p, backtrack_v)) = backtrack {
+            // Let the last `%` swallow one more character
+            p = backtrack_p;
+            v = backtrack_v + 1;
+            backtrack = Some((backtrack_p, v));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '%')
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let field = &self.field;
+        let (operator, pattern, case) = match &self.operation {
+            QueryOperation::Equals(value) => return write!(f, "{} = {}", field, value),
+            QueryOperation::NotEquals(value) => return write!(f, "{} != {}", field, value),
+            QueryOperation::GreaterThan(value) => return write!(f, "{} > {}", field, value),
+            QueryOperation::LessThan(value) => return write!(f, "{} < {}", field, value),
+            QueryOperation::GreaterOrEqual(value) => return write!(f, "{} >= {}", field, value),
+            QueryOperation::LessOrEqual(value) => return write!(f, "{} <= {}", field, value),
+            QueryOperation::Between(low, high) => {
+                return write!(f, "{} BETWEEN {} AND {}", field, low, high);
+            }
+            QueryOperation::In(values) | QueryOperation::NotIn(values) => {
+                let operator = match self.operation {
+                    QueryOperation::In(_) => "IN",
+                    _ => "NOT IN",
+                };
+                write!(f, "{} {} (", field, operator)?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                return write!(f, ")");
+            }
+            QueryOperation::StartsWith(pattern, case) => ("STARTS WITH", pattern, case),
+            QueryOperation::EndsWith(pattern, case) => ("ENDS WITH", pattern, case),
+            QueryOperation::Contains(pattern, case) => ("CONTAINS", pattern, case),
+            QueryOperation::Like(pattern, case) => ("LIKE", pattern, case),
+            QueryOperation::Exists => return write!(f, "{} EXISTS", field),
+            QueryOperation::NotExists => return write!(f, "{} NOT EXISTS", field),
+            QueryOperation::IsNull => return write!(f, "{} IS NULL", field),
+        };
+
+        write!(f, "{} {} {:?}", field, operator, pattern)?;
+        if *case == Case::Insensitive {
+            write!(f, " IGNORE CASE")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+use std::ops;
+
+use crate::{
+    common::DatabaseError,
+    schema::{Document, Value},
+};
+
+/// Arithmetic expression evaluated against a document
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Literal(Value),
+    Field(String),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    Neg(Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BinaryOperator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl Expr {
+    /// Current value of a document field
+    pub fn field(name: &str) -> Self {
+        Expr::Field(name.to_string())
+    }
+
+    pub fn value<V: Into<Value>>(value: V) -> Self {
+        Expr::Literal(value.into())
+    }
+
+    pub fn evaluate(&self, document: &Document) -> Result<Value, DatabaseError> {
+        match self {
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Field(name) => document.get(name).cloned().ok_or_else(|| {
+                DatabaseError::InvalidData(format!(
+                    "Field '{}' is missing in document {}",
+                    name, document.id
+                ))
+            }),
+            Expr::Add(left, right) => Self::binary(BinaryOperator::Add, left, right, document),
+            Expr::Sub(left, right) => Self::binary(BinaryOperator::Sub, left, right, document),
+            Expr::Mul(left, right) => Self::binary(BinaryOperator::Mul, left, right, document),
+            Expr::Div(left, right) => Self::binary(BinaryOperator::Div, left, right, document),
+            Expr::Neg(expr) => match expr.evaluate(document)? {
+                Value::Byte(_) => Err(DatabaseError::InvalidData(
+                    "Cannot negate unsigned byte".to_string(),
+                )),
+                Value::Short(v) => v.checked_neg().map(Value::Short).ok_or_else(overflow),
+                Value::Int(v) => v.checked_neg().map(Value::Int).ok_or_else(overflow),
+                Value::Long(v) => v.checked_neg().map(Value::Long).ok_or_else(overflow),
+                Value::Float(v) => Ok(Value::Float(-v)),
+                Value::Double(v) => Ok(Value::Double(-v)),
+                value => Err(DatabaseError::InvalidData(format!(
+                    "Cannot negate {}",
+                    value.type_name()
+                ))),
+            },
+        }
+    }
+
+    fn binary(
+        operator: BinaryOperator,
+        left: &Expr,
+        right: &Expr,
+        document: &Document,
+    ) -> Result<Value, DatabaseError> {
+        let left = left.evaluate(document)?;
+        let right = right.evaluate(document)?;
+
+        // String concatenation
+        if let (BinaryOperator::Add, Value::String(a), Value::String(b)) = (operator, &left, &right)
+        {
+            return Ok(Value::String(format!("{}{}", a, b)));
+        }
+
+        match (Numeric::from_value(&left), Numeric::from_value(&right)) {
+            (Some(a), Some(b)) => {
+                let rank = a.rank.max(b.rank);
+                if rank >= RANK_FLOAT {
+                    float_op(operator, a.as_f64(), b.as_f64(), rank)
+                } else {
+                    integer_op(operator, a.as_i64(), b.as_i64(), rank)
+                }
+            }
+            _ => Err(DatabaseError::InvalidData(format!(
+                "Cannot apply {:?} to {} and {}",
+                operator,
+                left.type_name(),
+                right.type_name()
+            ))),
+        }
+    }
+}
+
+/// Numeric types ordered by width, mixed operands are widened to the wider type
+const RANK_BYTE: u8 = 0;
+const RANK_SHORT: u8 = 1;
+const RANK_INT: u8 = 2;
+const RANK_LONG: u8 = 3;
+const RANK_FLOAT: u8 = 4;
+const RANK_DOUBLE: u8 = 5;
+
+struct Numeric<'a> {
+    value: &'a Value,
+    rank: u8,
+}
+
+impl<'a> Numeric<'a> {
+    fn from_value(value: &'a Value) -> Option<Self> {
+        let rank = match value {
+            Value::Byte(_) => RANK_BYTE,
+            Value::Short(_) => RANK_SHORT,
+            Value::Int(_) => RANK_INT,
+            Value::Long(_) => RANK_LONG,
+            Value::Float(_) => RANK_FLOAT,
+            Value::Double(_) => RANK_DOUBLE,
+            _ => return None,
+        };
+        Some(Self { value, rank })
+    }
+
+    fn as_i64(&self) -> i64 {
+        match self.value {
+            Value::Byte(v) => *v as i64,
+            Value::Short(v) => *v as i64,
+            Value::Int(v) => *v as i64,
+            Value::Long(v) => *v,
+            _ => unreachable!("integer rank"),
+        }
+    }
+
+    fn as_f64(&self) -> f64 {
+        match self.value {
+            Value::Float(v) => *v as f64,
+            Value::Double(v) => *v,
+            _ => self.as_i64() as f64,
+        }
+    }
+}
+
+fn integer_op(operator: BinaryOperator, a: i64, b: i64, rank: u8) -> Result<Value, DatabaseError> {
+    let result = match operator {
+        BinaryOperator::Add => a.checked_add(b),
+        BinaryOperator::Sub => a.checked_sub(b),
+        BinaryOperator::Mul => a.checked_mul(b),
+        BinaryOperator::Div => {
+            if b == 0 {
+                return Err(DatabaseError::InvalidData("Division by zero".to_string()));
+            }
+            a.checked_div(b)
+        }
+    }
+    .ok_or_else(overflow)?;
+
+    match rank {
+        RANK_BYTE => u8::try_from(result)
+            .map(Value::Byte)
+            .map_err(|_| overflow()),
+        RANK_SHORT => i16::try_from(result)
+            .map(Value::Short)
+            .map_err(|_| overflow()),
+        RANK_INT => i32::try_from(result)
+            .map(Value::Int)
+            .map_err(|_| overflow()),
+        _ => Ok(Value::Long(result)),
+    }
+}
+
+fn float_op(operator: BinaryOperator, a: f64, b: f64, rank: u8) -> Result<Value, DatabaseError> {
+    let result = match operator {
+        BinaryOperator::Add => a + b,
+        BinaryOperator::Sub => a - b,
+        BinaryOperator::Mul => a * b,
+        BinaryOperator::Div => a / b,
+    };
+
+    match rank {
+        RANK_FLOAT => Ok(Value::Float(result as f32)),
+        _ => Ok(Value::Double(result)),
+    }
+}
+
+fn overflow() -> DatabaseError {
+    DatabaseError::InvalidData("Arithmetic overflow".to_string())
+}
+
+macro_rules! impl_expr_from {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for Expr {
+                fn from(value: $t) -> Self {
+                    Expr::Literal(value.into())
+                }
+            }
+        )*
+    };
+}
+
+impl_expr_from!(u8, i16, i32, i64, f32, f64, bool, String, &str, Value);
+
+macro_rules! impl_expr_operator {
+    ($trait:ident, $method:ident, $variant:ident) => {
+        impl<R: Into<Expr>> ops::$trait<R> for Expr {
+            type Output = Expr;
+
+            fn $method(self, right: R) -> Self::Output {
+                Expr::$variant(Box::new(self), Box::new(right.into()))
+            }
+        }
+    };
+}
+
+impl_expr_operator!(Add, add, Add);
+impl_expr_operator!(Sub, sub, Sub);
+impl_expr_operator!(Mul, mul, Mul);
+impl_expr_operator!(Div, div, Div);
+
+impl ops::Neg for Expr {
+    type Output = Expr;
+
+    fn neg(self) -> Self::Output {
+        Expr::Neg(Box::new(self))
+    }
+}
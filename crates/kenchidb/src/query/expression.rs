@@ -0,0 +1,294 @@
+use std::{fmt, ops};
+
+use crate::{
+    common::DatabaseError,
+    query::{Case, Condition, QueryOperation},
+    schema::{Document, Schema, Value},
+};
+
+/// Query expression tree.
+/// Leaves are single field conditions, inner nodes combine them with boolean logic.
+#[derive(Debug, Clone)]
+pub enum Query {
+    /// Single field predicate
+    Condition(Condition),
+    /// All sub-queries must match, an empty group matches every document
+    And(Vec<Query>),
+    /// At least one sub-query must match, an empty group matches no document
+    Or(Vec<Query>),
+    /// Sub-query must not match
+    Not(Box<Query>),
+}
+
+impl Query {
+    /// Query matching every document
+    pub fn all() -> Self {
+        Query::And(vec![])
+    }
+
+    pub fn eq<V: Into<Value>>(field: &str, value: V) -> Self {
+        Self::condition(field, QueryOperation::Equals(value.into()))
+    }
+
+    pub fn ne<V: Into<Value>>(field: &str, value: V) -> Self {
+        Self::condition(field, QueryOperation::NotEquals(value.into()))
+    }
+
+    pub fn gt<V: Into<Value>>(field: &str, value: V) -> Self {
+        Self::condition(field, QueryOperation::GreaterThan(value.into()))
+    }
+
+    pub fn lt<V: Into<Value>>(field: &str, value: V) -> Self {
+        Self::condition(field, QueryOperation::LessThan(value.into()))
+    }
+
+    pub fn gte<V: Into<Value>>(field: &str, value: V) -> Self {
+        Self::condition(field, QueryOperation::GreaterOrEqual(value.into()))
+    }
+
+    pub fn lte<V: Into<Value>>(field: &str, value: V) -> Self {
+        Self::condition(field, QueryOperation::LessOrEqual(value.into()))
+    }
+
+    /// Inclusive range query
+    pub fn between<V: Into<Value>>(field: &str, low: V, high: V) -> Self {
+        Self::condition(field, QueryOperation::Between(low.into(), high.into()))
+    }
+
+    pub fn is_in<V: Into<Value>, I: IntoIterator<Item = V>>(field: &str, values: I) -> Self {
+        let values = values.into_iter().map(Into::into).collect();
+        Self::condition(field, QueryOperation::In(values))
+    }
+
+    pub fn not_in<V: Into<Value>, I: IntoIterator<Item = V>>(field: &str, values: I) -> Self {
+        let values = values.into_iter().map(Into::into).collect();
+        Self::condition(field, QueryOperation::NotIn(values))
+    }
+
+    pub fn starts_with(field: &str, prefix: &str) -> Self {
+        Self::condition(
+            field,
+            QueryOperation::StartsWith(prefix.into(), Case::Sensitive),
+        )
+    }
+
+    pub fn starts_with_ignore_case(field: &str, prefix: &str) -> Self {
+        Self::condition(
+            field,
+            QueryOperation::StartsWith(prefix.into(), Case::Insensitive),
+        )
+    }
+
+    pub fn ends_with(field: &str, suffix: &str) -> Self {
+        Self::condition(
+            field,
+            QueryOperation::EndsWith(suffix.into(), Case::Sensitive),
+        )
+    }
+
+    pub fn ends_with_ignore_case(field: &str, suffix: &str) -> Self {
+        Self::condition(
+            field,
+            QueryOperation::EndsWith(suffix.into(), Case::Insensitive),
+        )
+    }
+
+    pub fn contains(field: &str, substring: &str) -> Self {
+        Self::condition(
+            field,
+            QueryOperation::Contains(substring.into(), Case::Sensitive),
+        )
+    }
+
+    pub fn contains_ignore_case(field: &str, substring: &str) -> Self {
+        Self::condition(
+            field,
+            QueryOperation::Contains(substring.into(), Case::Insensitive),
+        )
+    }
+
+    /// SQL style pattern match, `%` matches any sequence and `_` a single character
+    pub fn like(field: &str, pattern: &str) -> Self {
+        Self::condition(field, QueryOperation::Like(pattern.into(), Case::Sensitive))
+    }
+
+    pub fn like_ignore_case(field: &str, pattern: &str) -> Self {
+        Self::condition(
+            field,
+            QueryOperation::Like(pattern.into(), Case::Insensitive),
+        )
+    }
+
+    /// Documents that have the field, even if its value is null
+    pub fn exists(field: &str) -> Self {
+        Self::condition(field, QueryOperation::Exists)
+    }
+
+    /// Documents without the field, an explicit null counts as present
+    pub fn not_exists(field: &str) -> Self {
+        Self::condition(field, QueryOperation::NotExists)
+    }
+
+    /// Documents whose field is null or missing
+    pub fn is_null(field: &str) -> Self {
+        Self::condition(field, QueryOperation::IsNull)
+    }
+
+    pub fn condition(field: &str, operation: QueryOperation) -> Self {
+        Query::Condition(Condition::new(field, operation))
+    }
+
+    /// Combine with another query, both must match
+    pub fn and(self, other: Query) -> Self {
+        match self {
+            Query::And(mut queries) => {
+                queries.push(other);
+                Query::And(queries)
+            }
+            query => Query::And(vec![query, other]),
+        }
+    }
+
+    /// Combine with another query, either must match
+    pub fn or(self, other: Query) -> Self {
+        match self {
+            Query::Or(mut queries) => {
+                queries.push(other);
+                Query::Or(queries)
+            }
+            query => Query::Or(vec![query, other]),
+        }
+    }
+
+    /// Check every condition against the schema,
+    /// unknown fields and mismatched operand types are rejected with `InvalidQuery`
+    pub fn validate(&self, schema: &Schema) -> Result<(), DatabaseError> {
+        match self {
+            Query::Condition(condition) => condition.validate(schema),
+            Query::And(queries) | Query::Or(queries) => {
+                queries.iter().try_for_each(|query| query.validate(schema))
+            }
+            Query::Not(query) => query.validate(schema),
+        }
+    }
+
+    pub fn matches(&self, document: &Document) -> bool {
+        match self {
+            Query::Condition(condition) => condition.matches(document),
+            Query::And(queries) => queries.iter().all(|query| query.matches(document)),
+            Query::Or(queries) => queries.iter().any(|query| query.matches(document)),
+            Query::Not(query) => !query.matches(document),
+        }
+    }
+}
+
+impl ops::Not for Query {
+    type Output = Query;
+
+    fn not(self) -> Self::Output {
+        match self {
+            Query::Not(query) => *query,
+            query => Query::Not(Box::new(query)),
+        }
+    }
+}
+
+impl From<Condition> for Query {
+    fn from(condition: Condition) -> Self {
+        Query::Condition(condition)
+    }
+}
+
+/// Group of queries which must all match
+pub fn and<I: IntoIterator<Item = Query>>(queries: I) -> Query {
+    Query::And(queries.into_iter().collect())
+}
+
+/// Group of queries of which at least one must match
+pub fn or<I: IntoIterator<Item = Query>>(queries: I) -> Query {
+    Query::Or(queries.into_iter().collect())
+}
+
+/// Negate a query
+pub fn not(query: Query) -> Query {
+    !query
+}
+
+// Query builder for type-safe queries
+pub struct QueryBuilder<T> {
+    _phantom: std::marker::PhantomData<T>,
+}
+
+impl<T> QueryBuilder<T> {
+    pub fn new() -> Self {
+        Self {
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    // Simple field-based filtering (combine the results with and/or/not)
+    pub fn where_eq(&self, field: &str, value: Value) -> Query {
+        Query::eq(field, value)
+    }
+
+    pub fn where_ne(&self, field: &str, value: Value) -> Query {
+        Query::ne(field, value)
+    }
+
+    pub fn where_gt(&self, field: &str, value: Value) -> Query {
+        Query::gt(field, value)
+    }
+
+    pub fn where_lt(&self, field: &str, value: Value) -> Query {
+        Query::lt(field, value)
+    }
+
+    pub fn where_gte(&self, field: &str, value: Value) -> Query {
+        Query::gte(field, value)
+    }
+
+    pub fn where_lte(&self, field: &str, value: Value) -> Query {
+        Query::lte(field, value)
+    }
+
+    pub fn where_between(&self, field: &str, low: Value, high: Value) -> Query {
+        Query::between(field, low, high)
+    }
+
+    pub fn where_in(&self, field: &str, values: Vec<Value>) -> Query {
+        Query::is_in(field, values)
+    }
+
+    pub fn where_not_in(&self, field: &str, values: Vec<Value>) -> Query {
+        Query::not_in(field, values)
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Query::Condition(condition) => write!(f, "{}", condition),
+            Query::And(queries) if queries.is_empty() => write!(f, "TRUE"),
+            Query::Or(queries) if queries.is_empty() => write!(f, "FALSE"),
+            Query::And(queries) | Query::Or(queries) => {
+                let separator = match self {
+                    Query::And(_) => " AND ",
+                    _ => " OR ",
+                };
+                for (i, query) in queries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "{}", separator)?;
+                    }
+                    match query {
+                        Query::And(group) | Query::Or(group) if group.len() > 1 => {
+                            write!(f, "({})", query)?
+                        }
+                        query => write!(f, "{}", query)?,
+                    }
+                }
+                Ok(())
+            }
+            Query::Not(query) => write!(f, "NOT ({})", query),
+        }
+    }
+}
@@ -0,0 +1,420 @@
+use std::{cmp::Ordering, collections::BinaryHeap, fmt};
+
+use crate::{
+    common::{DatabaseError, Random},
+    database::Collection,
+    query::{
+        AccessPath, Collation, Direction, OrderBy, Query, QueryPlanner, SortKey, reservoir_sample,
+        sort_values,
+    },
+    schema::{Document, FieldType, Schema, Value},
+};
+
+/// Filter with sorting and pagination
+#[derive(Debug, Clone)]
+pub struct FindQuery {
+    pub filter: Query,
+    /// Fields returned by `Collection::find_projected`, all fields if `None`
+    pub projection: Option<Vec<String>>,
+    /// Size of the random sample drawn from the matching documents
+    /// before sorting and pagination
+    pub sample: Option<usize>,
+    pub order_by: Option<OrderBy>,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+impl FindQuery {
+    pub fn new(filter: Query) -> Self {
+        Self {
+            filter,
+            projection: None,
+            sample: None,
+            order_by: None,
+            offset: 0,
+            limit: None,
+        }
+    }
+
+    /// Return only the fields, together with the document id.
+    /// Applies to `Collection::find_projected`, other reads return whole documents.
+    pub fn select(mut self, fields: &[&str]) -> Self {
+        self.projection = Some(fields.iter().map(|field| field.to_string()).collect());
+        self
+    }
+
+    /// Keep a uniform random sample of at most `size` matching documents,
+    /// ordering and pagination apply to the sample
+    pub fn sample(mut self, size: usize) -> Self {
+        self.sample = Some(size);
+        self
+    }
+
+    /// Sort by the field, after the keys already ordered by
+    pub fn order_by(self, field: &str, direction: Direction) -> Self {
+        self.order_by_key(SortKey::new(field, direction))
+    }
+
+    pub fn order_by_key(mut self, key: SortKey) -> Self {
+        self.order_by = Some(match self.order_by.take() {
+            Some(order_by) => order_by.then(key),
+            None => key.into(),
+        });
+        self
+    }
+
+    /// Skip the first `offset` matching documents
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Return at most `limit` documents
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn validate(&self, schema: &Schema) -> Result<(), DatabaseError> {
+        self.filter.validate(schema)?;
+
+        for field in self.projection.iter().flatten() {
+            if !schema.fields.iter().any(|f| f.name == *field) {
+                return Err(DatabaseError::InvalidQuery(format!(
+                    "Cannot select field '{}', not in schema '{}'",
+                    field, schema.name
+                )));
+            }
+        }
+
+        for key in self.order_by.iter().flat_map(|order_by| &order_by.keys) {
+            validate_sort_key(key, schema)?;
+        }
+
+        Ok(())
+    }
+
+    /// Apply sampling, sorting and pagination to the matching documents.
+    /// With both an order and a limit only the best `offset + limit` documents
+    /// are kept in a bounded heap while the documents stream by.
+    pub fn execute<'a, I>(&self, documents: I) -> Vec<&'a Document>
+    where
+        I: Iterator<Item = &'a Document>,
+    {
+        let matching = documents.filter(|doc| self.filter.matches(doc));
+
+        match self.sample {
+            Some(size) => {
+                let sampled = reservoir_sample(matching, size, &mut Random::new());
+                self.paginate(sampled.into_iter())
+            }
+            None => self.paginate(matching),
+        }
+    }
+
+    fn paginate<'a, I>(&self, matching: I) -> Vec<&'a Document>
+    where
+        I: Iterator<Item = &'a Document>,
+    {
+        let Some(order_by) = &self.order_by else {
+            return matching
+                .skip(self.offset)
+                .take(self.limit.unwrap_or(usize::MAX))
+                .collect();
+        };
+
+        let mut sorted = match self.limit {
+            Some(limit) => top_k(matching, order_by, self.offset.saturating_add(limit)),
+            None => {
+                let mut all: Vec<&Document> = matching.collect();
+                all.sort_by(|left, right| order_by.compare(left, right));
+                all
+            }
+        };
+
+        sorted.drain(..self.offset.min(sorted.len()));
+        sorted
+    }
+}
+
+/// The field must be declared, only string fields can use a case-insensitive collation
+pub(crate) fn validate_sort_key(key: &SortKey, schema: &Schema) -> Result<(), DatabaseError> {
+    let Some(field) = schema.fields.iter().find(|f| f.name == key.field) else {
+        return Err(DatabaseError::InvalidQuery(format!(
+            "Cannot order by field '{}', not in schema '{}'",
+            key.field, schema.name
+        )));
+    };
+
+    if key.collation == Collation::CaseInsensitive && field.field_type != FieldType::String {
+        return Err(DatabaseError::InvalidQuery(format!(
+            "Cannot collate field '{}' of type {:?}, only strings have a collation",
+            key.field, field.field_type
+        )));
+    }
+
+    Ok(())
+}
+
+impl fmt::Display for FindQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.filter)?;
+        if let Some(fields) = &self.projection {
+            write!(f, " SELECT {}", fields.join(", "))?;
+        }
+        if let Some(size) = self.sample {
+            write!(f, " SAMPLE {}", size)?;
+        }
+        if let Some(order_by) = &self.order_by {
+            write!(f, " ORDER BY {}", order_by)?;
+        }
+        if self.offset > 0 {
+            write!(f, " OFFSET {}", self.offset)?;
+        }
+        if let Some(limit) = self.limit {
+            write!(f, " LIMIT {}", limit)?;
+        }
+        Ok(())
+    }
+}
+
+impl Query {
+    pub fn select(self, fields: &[&str]) -> FindQuery {
+        FindQuery::new(self).select(fields)
+    }
+
+    pub fn sample(self, size: usize) -> FindQuery {
+        FindQuery::new(self).sample(size)
+    }
+
+    pub fn order_by(self, field: &str, direction: Direction) -> FindQuery {
+        FindQuery::new(self).order_by(field, direction)
+    }
+
+    pub fn order_by_key(self, key: SortKey) -> FindQuery {
+        FindQuery::new(self).order_by_key(key)
+    }
+
+    pub fn offset(self, offset: usize) -> FindQuery {
+        FindQuery::new(self).offset(offset)
+    }
+
+    pub fn limit(self, limit: usize) -> FindQuery {
+        FindQuery::new(self).limit(limit)
+    }
+}
+
+impl From<Query> for FindQuery {
+    fn from(filter: Query) -> Self {
+        FindQuery::new(filter)
+    }
+}
+
+impl From<&Query> for FindQuery {
+    fn from(filter: &Query) -> Self {
+        FindQuery::new(filter.clone())
+    }
+}
+
+impl From<&FindQuery> for FindQuery {
+    fn from(query: &FindQuery) -> Self {
+        query.clone()
+    }
+}
+
+/// Heap entry ordered by the sort key, the greatest entry is the worst kept document
+struct Ranked<'a, 'o> {
+    document: &'a Document,
+    order_by: &'o OrderBy,
+}
+
+impl Ord for Ranked<'_, '_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.order_by.compare(self.document, other.document)
+    }
+}
+
+impl PartialOrd for Ranked<'_, '_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Ranked<'_, '_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked<'_, '_> {}
+
+/// Keep the first `k` documents in sort order using a bounded max-heap
+fn top_k<'a, I>(documents: I, order_by: &OrderBy, k: usize) -> Vec<&'a Document>
+where
+    I: Iterator<Item = &'a Document>,
+{
+    if k == 0 {
+        return vec![];
+    }
+
+    let mut heap = BinaryHeap::with_capacity(k + 1);
+    for document in documents {
+        let ranked = Ranked { document, order_by };
+        if heap.len() < k {
+            heap.push(ranked);
+        } else if heap.peek().is_some_and(|worst| ranked < *worst) {
+            heap.pop();
+            heap.push(ranked);
+        }
+    }
+
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|ranked| ranked.document)
+        .collect()
+}
+
+impl Collection {
+    pub fn find_where<Q: Into<FindQuery>>(
+        &self,
+        query: Q,
+    ) -> Result<Vec<&Document>, DatabaseError> {
+        let query = query.into();
+        query.validate(&self.schema)?;
+
+        let plan = QueryPlanner::new(self).plan(&query);
+        Ok(query.execute(self.scan(&plan.access)))
+    }
+
+    /// Matching documents holding only the fields of the query's projection,
+    /// whole documents without a projection. A covered query is answered from the
+    /// index alone without reading the documents, see `QueryPlan::covered`.
+    pub fn find_projected<Q: Into<FindQuery>>(
+        &self,
+        query: Q,
+    ) -> Result<Vec<Document>, DatabaseError> {
+        let query = query.into();
+        query.validate(&self.schema)?;
+
+        let plan = QueryPlanner::new(self).plan(&query);
+        if plan.covered
+            && let AccessPath::Index {
+                field,
+                lower,
+                upper,
+                ..
+            } = &plan.access
+            && let Some(index) = self.indexes.get(field)
+            && let Some(declared) = self.schema.fields.iter().find(|f| f.name == *field)
+        {
+            let mut partial = Vec::new();
+            for entry in index.values_in(lower, upper, &declared.field_type) {
+                let (value, id) = entry?;
+                let mut document = Document::new(id);
+                document.data.insert(field.clone(), value);
+                partial.push(document);
+            }
+            return Ok(query.execute(partial.iter()).into_iter().cloned().collect());
+        }
+
+        Ok(query
+            .execute(self.scan(&plan.access))
+            .into_iter()
+            .map(|document| match &query.projection {
+                Some(fields) => document.project(fields),
+                None => document.clone(),
+            })
+            .collect())
+    }
+
+    /// First matching document in query order.
+    /// Unordered queries stop at the first match, ordered ones keep only the best document.
+    pub fn find_one_where<Q: Into<FindQuery>>(
+        &self,
+        query: Q,
+    ) -> Result<Option<&Document>, DatabaseError> {
+        let query = query.into();
+        query.validate(&self.schema)?;
+
+        if query.limit == Some(0) {
+            return Ok(None);
+        }
+
+        let plan = QueryPlanner::new(self).plan(&query);
+        if query.sample.is_some() {
+            return Ok(query.execute(self.scan(&plan.access)).into_iter().next());
+        }
+
+        let mut matching = self
+            .scan(&plan.access)
+            .filter(|doc| query.filter.matches(doc));
+
+        Ok(match &query.order_by {
+            None => matching.nth(query.offset),
+            Some(order_by) if query.offset == 0 => {
+                matching.min_by(|left, right| order_by.compare(left, right))
+            }
+            Some(order_by) => top_k(matching, order_by, query.offset.saturating_add(1))
+                .into_iter()
+                .nth(query.offset),
+        })
+    }
+
+    /// Number of matching documents, offset and limit apply.
+    /// Documents are counted as they are scanned, without collecting them.
+    pub fn count_where<Q: Into<FindQuery>>(&self, query: Q) -> Result<usize, DatabaseError> {
+        let query = query.into();
+        query.validate(&self.schema)?;
+
+        // Every stored document matches an empty filter while nothing can expire
+        let count = if query.sample.is_some() {
+            let plan = QueryPlanner::new(self).plan(&query);
+            query.execute(self.scan(&plan.access)).len()
+        } else if self.ttl.is_none() && matches!(&query.filter, Query::And(q) if q.is_empty()) {
+            self.documents.len().saturating_sub(query.offset)
+        } else {
+            let plan = QueryPlanner::new(self).plan(&query);
+            self.scan(&plan.access)
+                .filter(|doc| query.filter.matches(doc))
+                .skip(query.offset)
+                .take(query.limit.unwrap_or(usize::MAX))
+                .count()
+        };
+
+        Ok(count.min(query.limit.unwrap_or(usize::MAX)))
+    }
+
+    /// Check whether any document matches, stops at the first match
+    pub fn exists_where<Q: Into<FindQuery>>(&self, query: Q) -> Result<bool, DatabaseError> {
+        let query = query.into();
+        let limit = query.limit.unwrap_or(1).min(1);
+        Ok(self.count_where(query.limit(limit))? > 0)
+    }
+
+    /// Deduplicated values of the field in ascending order,
+    /// over the documents matching the query or all documents.
+    /// Documents without a value for the field or with a null value are skipped.
+    pub fn distinct(&self, field: &str, query: Option<Query>) -> Result<Vec<Value>, DatabaseError> {
+        if !self.schema.fields.iter().any(|f| f.name == field) {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "Field '{}' not in schema '{}'",
+                field, self.schema.name
+            )));
+        }
+
+        let query = FindQuery::new(query.unwrap_or_else(Query::all));
+        query.validate(&self.schema)?;
+
+        let plan = QueryPlanner::new(self).plan(&query);
+        let mut values: Vec<Value> = self
+            .scan(&plan.access)
+            .filter(|doc| query.filter.matches(doc))
+            .filter_map(|doc| doc.get(field).cloned())
+            .filter(|value| *value != Value::Null)
+            .collect();
+
+        values.sort_by(sort_values);
+        values.dedup();
+        Ok(values)
+    }
+}
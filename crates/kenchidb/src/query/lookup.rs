@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use crate::{
+    common::DatabaseError,
+    database::Collection,
+    schema::{Document, FieldType, encode_key_value},
+};
+
+/// Document of the left collection with the right documents whose field matched its field
+#[derive(Debug, Clone)]
+pub struct LookupRow<'a> {
+    pub left: &'a Document,
+    /// Matched documents in id order, empty if nothing matched
+    pub matches: Vec<&'a Document>,
+}
+
+/// Join every left document to the right documents with an equal field value.
+/// The right collection is hashed on its field once, then each left document probes the table.
+/// Left documents without a match, or without a value, are returned with no matches.
+pub fn hash_join<'a>(
+    left: &'a Collection,
+    left_field: &str,
+    right: &'a Collection,
+    right_field: &str,
+) -> Result<Vec<LookupRow<'a>>, DatabaseError> {
+    let left_type = field_type(left, left_field)?;
+    let right_type = field_type(right, right_field)?;
+    if left_type != right_type {
+        return Err(DatabaseError::InvalidQuery(format!(
+            "Cannot join field '{}' of type {:?} with field '{}' of type {:?}",
+            left_field, left_type, right_field, right_type
+        )));
+    }
+
+    let mut table: HashMap<Vec<u8>, Vec<&Document>> = HashMap::new();
+    for document in right.live_documents() {
+        if let Some(value) = document.get(right_field) {
+            let mut key = Vec::new();
+            encode_key_value(value, &mut key);
+            table.entry(key).or_default().push(document);
+        }
+    }
+    for matches in table.values_mut() {
+        matches.sort_by_key(|document| document.id);
+    }
+
+    let mut rows: Vec<LookupRow> = left
+        .live_documents()
+        .map(|document| {
+            let matches = match document.get(left_field) {
+                Some(value) => {
+                    let mut key = Vec::new();
+                    encode_key_value(value, &mut key);
+                    table.get(&key).cloned().unwrap_or_default()
+                }
+                None => Vec::new(),
+            };
+            LookupRow {
+                left: document,
+                matches,
+            }
+        })
+        .collect();
+    rows.sort_by_key(|row| row.left.id);
+    Ok(rows)
+}
+
+fn field_type<'a>(collection: &'a Collection, field: &str) -> Result<&'a FieldType, DatabaseError> {
+    let schema = &collection.schema;
+    match schema.fields.iter().find(|f| f.name == field) {
+        Some(field) => Ok(&field.field_type),
+        None => Err(DatabaseError::InvalidQuery(format!(
+            "Field '{}' not in schema '{}'",
+            field, schema.name
+        ))),
+    }
+}
@@ -0,0 +1,33 @@
+mod codec;
+mod condition;
+mod expr;
+mod expression;
+mod find;
+mod lookup;
+mod order;
+mod parser;
+mod patch;
+mod planner;
+mod prepared;
+mod result;
+mod sample;
+mod stats;
+mod update;
+mod view;
+
+pub(crate) use self::codec::*;
+pub(crate) use self::condition::*;
+pub(crate) use self::expr::*;
+pub(crate) use self::expression::*;
+pub(crate) use self::find::*;
+pub(crate) use self::lookup::*;
+pub(crate) use self::order::*;
+pub(crate) use self::parser::*;
+pub(crate) use self::patch::*;
+pub(crate) use self::planner::*;
+pub(crate) use self::prepared::*;
+pub(crate) use self::result::*;
+pub(crate) use self::sample::*;
+pub(crate) use self::stats::*;
+pub(crate) use self::update::*;
+pub(crate) use self::view::*;
@@ -0,0 +1,149 @@
+use std::{cmp::Ordering, fmt};
+
+use crate::{
+    query::compare_values,
+    schema::{Document, Value},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Asc,
+    Desc,
+}
+
+/// How string values are compared when sorting
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Collation {
+    /// Unicode code point order
+    #[default]
+    Binary,
+    /// Code point order of the lowercased strings, without locale rules
+    CaseInsensitive,
+}
+
+/// One field of a sort order
+#[derive(Debug, Clone)]
+pub struct SortKey {
+    pub field: String,
+    pub direction: Direction,
+    pub collation: Collation,
+}
+
+impl SortKey {
+    pub fn new(field: &str, direction: Direction) -> Self {
+        Self {
+            field: field.to_string(),
+            direction,
+            collation: Collation::Binary,
+        }
+    }
+
+    pub fn collate(mut self, collation: Collation) -> Self {
+        self.collation = collation;
+        self
+    }
+
+    fn compare(&self, left: &Document, right: &Document) -> Ordering {
+        let ordering = compare_keys(
+            left.get(&self.field),
+            right.get(&self.field),
+            self.collation,
+        );
+        match self.direction {
+            Direction::Asc => ordering,
+            Direction::Desc => ordering.reverse(),
+        }
+    }
+}
+
+impl fmt::Display for SortKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.field)?;
+        if self.collation == Collation::CaseInsensitive {
+            write!(f, " COLLATE NOCASE")?;
+        }
+        let direction = match self.direction {
+            Direction::Asc => "ASC",
+            Direction::Desc => "DESC",
+        };
+        write!(f, " {}", direction)
+    }
+}
+
+/// Sort order of a find query, later keys break ties of earlier ones
+#[derive(Debug, Clone)]
+pub struct OrderBy {
+    pub keys: Vec<SortKey>,
+}
+
+impl OrderBy {
+    pub fn new(field: &str, direction: Direction) -> Self {
+        Self {
+            keys: vec![SortKey::new(field, direction)],
+        }
+    }
+
+    /// Append a key that orders documents equal on all previous keys
+    pub fn then(mut self, key: SortKey) -> Self {
+        self.keys.push(key);
+        self
+    }
+
+    /// Compare two documents key by key.
+    /// Missing values sort after present ones in ascending order, ties are broken by document id.
+    pub fn compare(&self, left: &Document, right: &Document) -> Ordering {
+        self.keys
+            .iter()
+            .map(|key| key.compare(left, right))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| left.id.cmp(&right.id))
+    }
+}
+
+impl From<SortKey> for OrderBy {
+    fn from(key: SortKey) -> Self {
+        Self { keys: vec![key] }
+    }
+}
+
+impl fmt::Display for OrderBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, key) in self.keys.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", key)?;
+        }
+        Ok(())
+    }
+}
+
+/// Missing and null values sort last
+fn compare_keys(left: Option<&Value>, right: Option<&Value>, collation: Collation) -> Ordering {
+    let left = left.filter(|value| **value != Value::Null);
+    let right = right.filter(|value| **value != Value::Null);
+    match (left, right) {
+        (Some(Value::String(left)), Some(Value::String(right)))
+            if collation == Collation::CaseInsensitive =>
+        {
+            let left = left.chars().flat_map(char::to_lowercase);
+            let right = right.chars().flat_map(char::to_lowercase);
+            left.cmp(right)
+        }
+        (Some(left), Some(right)) => sort_values(left, right),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Total order of present values for sorting.
+/// Floats use `total_cmp`, so NaN sorts after every number instead of equal to all of them,
+/// values of different types are ordered by their type id.
+pub(crate) fn sort_values(left: &Value, right: &Value) -> Ordering {
+    match (left, right) {
+        (Value::Float(left), Value::Float(right)) => left.total_cmp(right),
+        (Value::Double(left), Value::Double(right)) => left.total_cmp(right),
+        _ => compare_values(left, right).unwrap_or_else(|| left.type_id().cmp(&right.type_id())),
+    }
+}
@@ -0,0 +1,861 @@
+use std::{fmt, iter::Peekable, str::CharIndices};
+
+use crate::{
+    common::DatabaseError,
+    query::{
+        Case, Collation, Direction, OrderBy, PreparedQuery, Query, QueryOperation, SortKey,
+        validate_sort_key,
+    },
+    schema::{FieldType, Schema, Value},
+};
+
+/// Comparison operator of a parsed condition
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Equals,
+    NotEquals,
+    GreaterThan,
+    LessThan,
+    GreaterOrEqual,
+    LessOrEqual,
+    Between,
+    In,
+    NotIn,
+    StartsWith,
+    EndsWith,
+    Contains,
+    Like,
+    Exists,
+    NotExists,
+    IsNull,
+}
+
+/// Operand of a parsed condition, either a value or a `$n` placeholder
+#[derive(Debug, Clone)]
+pub enum Operand {
+    Value(Value),
+    /// Zero based parameter index, `$1` is parameter 0
+    Parameter(usize),
+}
+
+/// Parsed query expression whose operands may still be placeholders
+#[derive(Debug, Clone)]
+pub enum QueryTemplate {
+    Condition {
+        field: String,
+        operator: Operator,
+        operands: Vec<Operand>,
+        case: Case,
+    },
+    And(Vec<QueryTemplate>),
+    Or(Vec<QueryTemplate>),
+    Not(Box<QueryTemplate>),
+}
+
+impl QueryTemplate {
+    /// Build the query, replacing placeholders with the given parameters
+    pub fn bind(&self, parameters: &[Value]) -> Result<Query, DatabaseError> {
+        match self {
+            QueryTemplate::Condition {
+                field,
+                operator,
+                operands,
+                case,
+            } => {
+                let mut values = Vec::with_capacity(operands.len());
+                for operand in operands {
+                    values.push(match operand {
+                        Operand::Value(value) => value.clone(),
+                        Operand::Parameter(index) => {
+                            parameters.get(*index).cloned().ok_or_else(|| {
+                                DatabaseError::InvalidQuery(format!(
+                                    "Parameter ${} is not bound",
+                                    index + 1
+                                ))
+                            })?
+                        }
+                    });
+                }
+                Ok(Query::condition(
+                    field,
+                    build_operation(*operator, values, *case)?,
+                ))
+            }
+            QueryTemplate::And(templates) => Ok(Query::And(
+                templates
+                    .iter()
+                    .map(|template| template.bind(parameters))
+                    .collect::<Result<_, _>>()?,
+            )),
+            QueryTemplate::Or(templates) => Ok(Query::Or(
+                templates
+                    .iter()
+                    .map(|template| template.bind(parameters))
+                    .collect::<Result<_, _>>()?,
+            )),
+            QueryTemplate::Not(template) => Ok(!template.bind(parameters)?),
+        }
+    }
+}
+
+fn build_operation(
+    operator: Operator,
+    mut values: Vec<Value>,
+    case: Case,
+) -> Result<QueryOperation, DatabaseError> {
+    let pattern = |values: Vec<Value>| match values.into_iter().next() {
+        Some(Value::String(pattern)) => Ok(pattern),
+        Some(value) => Err(DatabaseError::InvalidQuery(format!(
+            "String pattern operators require a string operand, got {}",
+            value.type_name()
+        ))),
+        None => Err(DatabaseError::InvalidQuery(
+            "Missing pattern operand".to_string(),
+        )),
+    };
+
+    Ok(match operator {
+        Operator::Equals => QueryOperation::Equals(values.remove(0)),
+        Operator::NotEquals => QueryOperation::NotEquals(values.remove(0)),
+        Operator::GreaterThan => QueryOperation::GreaterThan(values.remove(0)),
+        Operator::LessThan => QueryOperation::LessThan(values.remove(0)),
+        Operator::GreaterOrEqual => QueryOperation::GreaterOrEqual(values.remove(0)),
+        Operator::LessOrEqual => QueryOperation::LessOrEqual(values.remove(0)),
+        Operator::Between => {
+            let high = values.remove(1);
+            QueryOperation::Between(values.remove(0), high)
+        }
+        Operator::In => QueryOperation::In(values),
+        Operator::NotIn => QueryOperation::NotIn(values),
+        Operator::StartsWith => QueryOperation::StartsWith(pattern(values)?, case),
+        Operator::EndsWith => QueryOperation::EndsWith(pattern(values)?, case),
+        Operator::Contains => QueryOperation::Contains(pattern(values)?, case),
+        Operator::Like => QueryOperation::Like(pattern(values)?, case),
+        Operator::Exists => QueryOperation::Exists,
+        Operator::NotExists => QueryOperation::NotExists,
+        Operator::IsNull => QueryOperation::IsNull,
+    })
+}
+
+/// Untyped literal as written in the query text
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    String(String),
+    Null,
+}
+
+impl Literal {
+    pub fn from_value(value: Value) -> Self {
+        match value {
+            Value::Byte(v) => Literal::Integer(v as i64),
+            Value::Short(v) => Literal::Integer(v as i64),
+            Value::Int(v) => Literal::Integer(v as i64),
+            Value::Long(v) => Literal::Integer(v),
+            Value::Float(v) => Literal::Float(v as f64),
+            Value::Double(v) => Literal::Float(v),
+            Value::Boolean(v) => Literal::Boolean(v),
+            Value::String(v) => Literal::String(v),
+            Value::Null => Literal::Null,
+        }
+    }
+
+    /// Convert to a value of the field type, integers widen to floating point types.
+    /// Returns `None` if the literal has another type or does not fit.
+    pub fn to_value(&self, field_type: &FieldType) -> Option<Value> {
+        match (self, field_type) {
+            (Literal::Integer(v), FieldType::Byte) => u8::try_from(*v).ok().map(Value::Byte),
+            (Literal::Integer(v), FieldType::Short) => i16::try_from(*v).ok().map(Value::Short),
+            (Literal::Integer(v), FieldType::Int) => i32::try_from(*v).ok().map(Value::Int),
+            (Literal::Integer(v), FieldType::Long) => Some(Value::Long(*v)),
+            (Literal::Integer(v), FieldType::Float) => Some(Value::Float(*v as f32)),
+            (Literal::Integer(v), FieldType::Double) => Some(Value::Double(*v as f64)),
+            (Literal::Float(v), FieldType::Float) => Some(Value::Float(*v as f32)),
+            (Literal::Float(v), FieldType::Double) => Some(Value::Double(*v)),
+            (Literal::Boolean(v), FieldType::Boolean) => Some(Value::Boolean(*v)),
+            (Literal::String(v), FieldType::String) => Some(Value::String(v.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Integer(v) => write!(f, "{}", v),
+            Literal::Float(v) => write!(f, "{:?}", v),
+            Literal::Boolean(v) => write!(f, "{}", v),
+            Literal::String(v) => write!(f, "{:?}", v),
+            Literal::Null => write!(f, "NULL"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Identifier(String),
+    Literal(Literal),
+    Parameter(usize),
+    Operator(Operator),
+    LeftParen,
+    RightParen,
+    Comma,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Identifier(name) => write!(f, "'{}'", name),
+            Token::Literal(literal) => write!(f, "{}", literal),
+            Token::Parameter(index) => write!(f, "${}", index + 1),
+            Token::Operator(operator) => {
+                let symbol = match operator {
+                    Operator::Equals => "=",
+                    Operator::NotEquals => "!=",
+                    Operator::GreaterThan => ">",
+                    Operator::LessThan => "<",
+                    Operator::GreaterOrEqual => ">=",
+                    _ => "<=",
+                };
+                write!(f, "'{}'", symbol)
+            }
+            Token::LeftParen => write!(f, "'('"),
+            Token::RightParen => write!(f, "')'"),
+            Token::Comma => write!(f, "','"),
+        }
+    }
+}
+
+/// Parser for the text query syntax, the same syntax `FindQuery` is displayed in:
+///
+/// ```text
+/// age >= 18 AND NOT (name STARTS WITH "a" IGNORE CASE OR id IN (1, 2, $1))
+///     ORDER BY name DESC OFFSET 20 LIMIT 10
+/// ```
+///
+/// The filter may be omitted to match every document.
+/// `field EXISTS` and `field NOT EXISTS` test whether a document has the field,
+/// `field IS NULL` and `field IS NOT NULL` whether its value is null or missing.
+/// Literals are converted to the type of the field they are compared with,
+/// `$n` placeholders are left in the template and bound later.
+pub struct QueryParser<'a> {
+    schema: &'a Schema,
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    /// Field type of every placeholder, by parameter index
+    parameters: Vec<Option<FieldType>>,
+}
+
+impl<'a> QueryParser<'a> {
+    pub fn new(schema: &'a Schema, text: &str) -> Result<Self, DatabaseError> {
+        Ok(Self {
+            schema,
+            tokens: tokenize(text)?,
+            position: 0,
+            parameters: Vec::new(),
+        })
+    }
+
+    /// Parse the whole text as a filter followed by optional
+    /// `SELECT`, `SAMPLE`, `ORDER BY`, `OFFSET` and `LIMIT` clauses
+    pub fn parse(mut self) -> Result<PreparedQuery, DatabaseError> {
+        let template = if self.at_clause() {
+            QueryTemplate::And(vec![])
+        } else {
+            self.expression()?
+        };
+
+        let mut projection = None;
+        if self.keyword("SELECT") {
+            let mut fields = vec![self.selected_field()?];
+            while self.next_if(&Token::Comma) {
+                fields.push(self.selected_field()?);
+            }
+            projection = Some(fields);
+        }
+
+        let mut sample = None;
+        if self.keyword("SAMPLE") {
+            sample = Some(self.count()?);
+        }
+
+        let mut order_by = None;
+        if self.keyword("ORDER") {
+            self.expect_keyword("BY")?;
+            let mut keys = vec![self.sort_key()?];
+            while self.next_if(&Token::Comma) {
+                keys.push(self.sort_key()?);
+            }
+            order_by = Some(OrderBy { keys });
+        }
+
+        let (mut offset, mut limit) = (None, None);
+        loop {
+            if offset.is_none() && self.keyword("OFFSET") {
+                offset = Some(self.count()?);
+            } else if limit.is_none() && self.keyword("LIMIT") {
+                limit = Some(self.count()?);
+            } else {
+                break;
+            }
+        }
+        self.expect_end()?;
+
+        Ok(PreparedQuery {
+            template,
+            parameters: self.parameter_types()?,
+            projection,
+            sample,
+            order_by,
+            offset: offset.unwrap_or(0),
+            limit,
+        })
+    }
+
+    /// Field of a `SELECT` clause, which must be declared
+    fn selected_field(&mut self) -> Result<String, DatabaseError> {
+        let field = self.identifier()?;
+        if !self.schema.fields.iter().any(|f| f.name == field) {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "Cannot select field '{}', not in schema '{}'",
+                field, self.schema.name
+            )));
+        }
+        Ok(field)
+    }
+
+    /// `field [COLLATE NOCASE | BINARY] [ASC | DESC]`
+    fn sort_key(&mut self) -> Result<SortKey, DatabaseError> {
+        let field = self.identifier()?;
+        let collation = if self.keyword("COLLATE") {
+            if self.keyword("NOCASE") {
+                Collation::CaseInsensitive
+            } else if self.keyword("BINARY") {
+                Collation::Binary
+            } else {
+                return Err(self.unexpected("NOCASE or BINARY"));
+            }
+        } else {
+            Collation::Binary
+        };
+        let direction = if self.keyword("DESC") {
+            Direction::Desc
+        } else {
+            self.keyword("ASC");
+            Direction::Asc
+        };
+
+        let key = SortKey::new(&field, direction).collate(collation);
+        validate_sort_key(&key, self.schema)?;
+        Ok(key)
+    }
+
+    /// Whether the next token starts a clause after the filter
+    fn at_clause(&self) -> bool {
+        match self.peek() {
+            None => true,
+            Some(Token::Identifier(word)) => ["SELECT", "SAMPLE", "ORDER", "OFFSET", "LIMIT"]
+                .iter()
+                .any(|clause| word.eq_ignore_ascii_case(clause)),
+            Some(_) => false,
+        }
+    }
+
+    /// Non-negative integer of a `SAMPLE`, `OFFSET` or `LIMIT` clause
+    fn count(&mut self) -> Result<usize, DatabaseError> {
+        match self.peek() {
+            Some(Token::Literal(Literal::Integer(count))) if *count >= 0 => {
+                let count = *count as usize;
+                self.position += 1;
+                Ok(count)
+            }
+            _ => Err(self.unexpected("a non-negative integer")),
+        }
+    }
+
+    fn parameter_types(&self) -> Result<Vec<FieldType>, DatabaseError> {
+        self.parameters
+            .iter()
+            .enumerate()
+            .map(|(index, field_type)| {
+                field_type.clone().ok_or_else(|| {
+                    DatabaseError::InvalidQuery(format!("Parameter ${} is not used", index + 1))
+                })
+            })
+            .collect()
+    }
+
+    fn expression(&mut self) -> Result<QueryTemplate, DatabaseError> {
+        let mut terms = vec![self.conjunction()?];
+        while self.keyword("OR") {
+            terms.push(self.conjunction()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            QueryTemplate::Or(terms)
+        })
+    }
+
+    fn conjunction(&mut self) -> Result<QueryTemplate, DatabaseError> {
+        let mut terms = vec![self.unary()?];
+        while self.keyword("AND") {
+            terms.push(self.unary()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            QueryTemplate::And(terms)
+        })
+    }
+
+    fn unary(&mut self) -> Result<QueryTemplate, DatabaseError> {
+        if self.keyword("NOT") {
+            return Ok(QueryTemplate::Not(Box::new(self.unary()?)));
+        }
+
+        if self.next_if(&Token::LeftParen) {
+            let template = self.expression()?;
+            self.expect(&Token::RightParen)?;
+            return Ok(template);
+        }
+
+        if self.keyword("TRUE") {
+            return Ok(QueryTemplate::And(vec![]));
+        }
+        if self.keyword("FALSE") {
+            return Ok(QueryTemplate::Or(vec![]));
+        }
+
+        self.condition()
+    }
+
+    fn condition(&mut self) -> Result<QueryTemplate, DatabaseError> {
+        let field = self.identifier()?;
+        let declared = self
+            .schema
+            .fields
+            .iter()
+            .find(|f| f.name == field)
+            .map(|f| f.field_type.clone());
+
+        // Presence checks, lenient schemas allow them on undeclared fields
+        let (presence, negated) = if self.keyword("EXISTS") {
+            (Some(Operator::Exists), false)
+        } else if self.keywords(&["NOT", "EXISTS"]) {
+            (Some(Operator::NotExists), false)
+        } else if self.keyword("IS") {
+            let negated = self.keyword("NOT");
+            self.expect_keyword("NULL")?;
+            (Some(Operator::IsNull), negated)
+        } else {
+            (None, false)
+        };
+        if let Some(operator) = presence
+            && (declared.is_some() || self.schema.lenient)
+        {
+            let condition = QueryTemplate::Condition {
+                field,
+                operator,
+                operands: vec![],
+                case: Case::Sensitive,
+            };
+            return Ok(match negated {
+                true => QueryTemplate::Not(Box::new(condition)),
+                false => condition,
+            });
+        }
+
+        let Some(field_type) = declared else {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "Field '{}' not in schema '{}'",
+                field, self.schema.name
+            )));
+        };
+
+        let comparison = match self.peek() {
+            Some(Token::Operator(operator)) => Some(*operator),
+            _ => None,
+        };
+        let (operator, operands) = match comparison {
+            Some(operator) => {
+                self.position += 1;
+                (operator, vec![self.operand(&field, &field_type)?])
+            }
+            _ if self.keyword("BETWEEN") => {
+                let low = self.operand(&field, &field_type)?;
+                self.expect_keyword("AND")?;
+                let high = self.operand(&field, &field_type)?;
+                (Operator::Between, vec![low, high])
+            }
+            _ if self.keyword("IN") => (Operator::In, self.operand_list(&field, &field_type)?),
+            _ if self.keyword("NOT") => {
+                self.expect_keyword("IN")?;
+                (Operator::NotIn, self.operand_list(&field, &field_type)?)
+            }
+            _ => {
+                let operator = if self.keyword("STARTS") {
+                    self.expect_keyword("WITH")?;
+                    Operator::StartsWith
+                } else if self.keyword("ENDS") {
+                    self.expect_keyword("WITH")?;
+                    Operator::EndsWith
+                } else if self.keyword("CONTAINS") {
+                    Operator::Contains
+                } else if self.keyword("LIKE") {
+                    Operator::Like
+                } else {
+                    return Err(self.unexpected(&format!("an operator after '{}'", field)));
+                };
+
+                if field_type != FieldType::String {
+                    return Err(DatabaseError::InvalidQuery(format!(
+                        "Field '{}' has type {:?}, string pattern operators require String",
+                        field, field_type
+                    )));
+                }
+                let pattern = self.operand(&field, &FieldType::String)?;
+                let case = if self.keyword("IGNORE") {
+                    self.expect_keyword("CASE")?;
+                    Case::Insensitive
+                } else {
+                    Case::Sensitive
+                };
+
+                return Ok(QueryTemplate::Condition {
+                    field,
+                    operator,
+                    operands: vec![pattern],
+                    case,
+                });
+            }
+        };
+
+        Ok(QueryTemplate::Condition {
+            field,
+            operator,
+            operands,
+            case: Case::Sensitive,
+        })
+    }
+
+    fn operand_list(
+        &mut self,
+        field: &str,
+        field_type: &FieldType,
+    ) -> Result<Vec<Operand>, DatabaseError> {
+        self.expect(&Token::LeftParen)?;
+        let mut operands = Vec::new();
+        if !self.next_if(&Token::RightParen) {
+            loop {
+                operands.push(self.operand(field, field_type)?);
+                if self.next_if(&Token::RightParen) {
+                    break;
+                }
+                self.expect(&Token::Comma)?;
+            }
+        }
+        Ok(operands)
+    }
+
+    fn operand(&mut self, field: &str, field_type: &FieldType) -> Result<Operand, DatabaseError> {
+        let literal = match self.peek() {
+            Some(Token::Parameter(index)) => {
+                let index = *index;
+                self.position += 1;
+                self.bind_parameter(index, field, field_type)?;
+                return Ok(Operand::Parameter(index));
+            }
+            Some(Token::Literal(literal)) => literal.clone(),
+            Some(Token::Identifier(word)) if word.eq_ignore_ascii_case("true") => {
+                Literal::Boolean(true)
+            }
+            Some(Token::Identifier(word)) if word.eq_ignore_ascii_case("false") => {
+                Literal::Boolean(false)
+            }
+            _ => return Err(self.unexpected("a value")),
+        };
+        self.position += 1;
+
+        literal
+            .to_value(field_type)
+            .map(Operand::Value)
+            .ok_or_else(|| {
+                DatabaseError::InvalidQuery(format!(
+                    "Field '{}' has type {:?}, but the query compares it with {}",
+                    field, field_type, literal
+                ))
+            })
+    }
+
+    /// Record the field type a placeholder is compared with
+    fn bind_parameter(
+        &mut self,
+        index: usize,
+        field: &str,
+        field_type: &FieldType,
+    ) -> Result<(), DatabaseError> {
+        if self.parameters.len() <= index {
+            self.parameters.resize(index + 1, None);
+        }
+
+        match &self.parameters[index] {
+            Some(existing) if existing != field_type => Err(DatabaseError::InvalidQuery(format!(
+                "Parameter ${} is used as {:?} and as {:?} for field '{}'",
+                index + 1,
+                existing,
+                field_type,
+                field
+            ))),
+            _ => {
+                self.parameters[index] = Some(field_type.clone());
+                Ok(())
+            }
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String, DatabaseError> {
+        match self.peek() {
+            Some(Token::Identifier(name)) => {
+                let name = name.clone();
+                self.position += 1;
+                Ok(name)
+            }
+            _ => Err(self.unexpected("a field name")),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn next_if(&mut self, expected: &Token) -> bool {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consume the keyword if it is next, keywords are case insensitive
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Identifier(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Consume the keywords if they are next in this order
+    fn keywords(&mut self, keywords: &[&str]) -> bool {
+        let matched = keywords.iter().enumerate().all(|(i, keyword)| {
+            matches!(
+                self.tokens.get(self.position + i),
+                Some((Token::Identifier(word), _)) if word.eq_ignore_ascii_case(keyword)
+            )
+        });
+        if matched {
+            self.position += keywords.len();
+        }
+        matched
+    }
+
+    fn expect(&mut self, expected: &Token) -> Result<(), DatabaseError> {
+        if self.next_if(expected) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&expected.to_string()))
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), DatabaseError> {
+        if self.keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.unexpected(keyword))
+        }
+    }
+
+    fn expect_end(&self) -> Result<(), DatabaseError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.unexpected("end of query")),
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> DatabaseError {
+        match self.tokens.get(self.position) {
+            Some((token, offset)) => DatabaseError::InvalidQuery(format!(
+                "Expected {} but found {} at offset {}",
+                expected, token, offset
+            )),
+            None => {
+                DatabaseError::InvalidQuery(format!("Expected {} but the query ended", expected))
+            }
+        }
+    }
+}
+
+/// Split the query text into tokens with their byte offsets
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, DatabaseError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some(&(offset, c)) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '(' | ')' | ',' | '=' => {
+                chars.next();
+                match c {
+                    '(' => Token::LeftParen,
+                    ')' => Token::RightParen,
+                    ',' => Token::Comma,
+                    _ => Token::Operator(Operator::Equals),
+                }
+            }
+            '!' | '<' | '>' => {
+                chars.next();
+                let or_equal = chars.next_if(|&(_, c)| c == '=').is_some();
+                Token::Operator(match (c, or_equal) {
+                    ('!', true) => Operator::NotEquals,
+                    ('<', true) => Operator::LessOrEqual,
+                    ('>', true) => Operator::GreaterOrEqual,
+                    ('<', false) => Operator::LessThan,
+                    ('>', false) => Operator::GreaterThan,
+                    _ => return Err(invalid_character(c, offset)),
+                })
+            }
+            '$' => {
+                chars.next();
+                let digits = take_while(&mut chars, text, |c| c.is_ascii_digit());
+                match digits.parse::<usize>() {
+                    Ok(number) if number > 0 => Token::Parameter(number - 1),
+                    _ => {
+                        return Err(DatabaseError::InvalidQuery(format!(
+                            "Invalid parameter '${}' at offset {}, parameters start at $1",
+                            digits, offset
+                        )));
+                    }
+                }
+            }
+            '"' | '\'' => Token::Literal(Literal::String(string_literal(&mut chars, offset)?)),
+            c if c.is_ascii_digit() || c == '-' => {
+                Token::Literal(number_literal(&mut chars, text, offset)?)
+            }
+            c if c.is_alphabetic() || c == '_' => Token::Identifier(
+                take_while(&mut chars, text, |c| c.is_alphanumeric() || c == '_').to_string(),
+            ),
+            _ => return Err(invalid_character(c, offset)),
+        };
+        tokens.push((token, offset));
+    }
+
+    Ok(tokens)
+}
+
+fn take_while<'t, F>(
+    chars: &mut Peekable<CharIndices<'t>>,
+    text: &'t str,
+    mut predicate: F,
+) -> &'t str
+where
+    F: FnMut(char) -> bool,
+{
+    let start = chars.peek().map_or(text.len(), |&(offset, _)| offset);
+    while chars.next_if(|&(_, c)| predicate(c)).is_some() {}
+    let end = chars.peek().map_or(text.len(), |&(offset, _)| offset);
+    &text[start..end]
+}
+
+fn number_literal<'t>(
+    chars: &mut Peekable<CharIndices<'t>>,
+    text: &'t str,
+    offset: usize,
+) -> Result<Literal, DatabaseError> {
+    chars.next_if(|&(_, c)| c == '-');
+    let mut previous = ' ';
+    take_while(chars, text, |c| {
+        let accepted = c.is_ascii_digit()
+            || matches!(c, '.' | 'e' | 'E')
+            || (matches!(c, '+' | '-') && matches!(previous, 'e' | 'E'));
+        previous = c;
+        accepted
+    });
+    let end = chars.peek().map_or(text.len(), |&(end, _)| end);
+    let number = &text[offset..end];
+
+    let literal = if number.contains(['.', 'e', 'E']) {
+        number.parse::<f64>().ok().map(Literal::Float)
+    } else {
+        number.parse::<i64>().ok().map(Literal::Integer)
+    };
+    literal.ok_or_else(|| {
+        DatabaseError::InvalidQuery(format!("Invalid number '{}' at offset {}", number, offset))
+    })
+}
+
+fn string_literal(
+    chars: &mut Peekable<CharIndices<'_>>,
+    offset: usize,
+) -> Result<String, DatabaseError> {
+    let unterminated =
+        || DatabaseError::InvalidQuery(format!("Unterminated string at offset {}", offset));
+    let Some((_, quote)) = chars.next() else {
+        return Err(unterminated());
+    };
+
+    let mut string = String::new();
+    loop {
+        match chars.next().ok_or_else(unterminated)? {
+            (_, c) if c == quote => return Ok(string),
+            (escape_offset, '\\') => {
+                let (_, escaped) = chars.next().ok_or_else(unterminated)?;
+                string.push(match escaped {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    '0' => '\0',
+                    '\\' | '"' | '\'' => escaped,
+                    'u' => unicode_escape(chars).ok_or_else(|| {
+                        DatabaseError::InvalidQuery(format!(
+                            "Invalid unicode escape at offset {}",
+                            escape_offset
+                        ))
+                    })?,
+                    _ => {
+                        return Err(DatabaseError::InvalidQuery(format!(
+                            "Invalid escape '\\{}' at offset {}",
+                            escaped, escape_offset
+                        )));
+                    }
+                });
+            }
+            (_, c) => string.push(c),
+        }
+    }
+}
+
+/// Read the `{XXXX}` part of a `\u{XXXX}` escape
+fn unicode_escape(chars: &mut Peekable<CharIndices<'_>>) -> Option<char> {
+    chars.next_if(|&(_, c)| c == '{')?;
+    let mut code = 0u32;
+    loop {
+        let (_, c) = chars.next()?;
+        if c == '}' {
+            return char::from_u32(code);
+        }
+        code = code.checked_mul(16)?.checked_add(c.to_digit(16)?)?;
+    }
+}
+
+fn invalid_character(c: char, offset: usize) -> DatabaseError {
+    DatabaseError::InvalidQuery(format!("Unexpected character '{}' at offset {}", c, offset))
+}
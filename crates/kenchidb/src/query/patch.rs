@@ -0,0 +1,81 @@
+use crate::{
+    common::DatabaseError,
+    database::Collection,
+    query::Expr,
+    schema::{Document, Value},
+};
+
+#[derive(Debug, Clone)]
+pub enum PatchOperation {
+    Set(Value),
+    Unset,
+    /// Add the delta to the current numeric value
+    Increment(Value),
+}
+
+/// Partial change of a single document, applied by `Collection::patch`
+#[derive(Debug, Clone, Default)]
+pub struct DocumentPatch {
+    pub operations: Vec<(String, PatchOperation)>,
+}
+
+impl DocumentPatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set<V: Into<Value>>(mut self, field: &str, value: V) -> Self {
+        self.operations
+            .push((field.to_string(), PatchOperation::Set(value.into())));
+        self
+    }
+
+    /// Remove the field, only allowed for nullable fields
+    pub fn unset(mut self, field: &str) -> Self {
+        self.operations
+            .push((field.to_string(), PatchOperation::Unset));
+        self
+    }
+
+    pub fn increment<V: Into<Value>>(mut self, field: &str, delta: V) -> Self {
+        self.operations
+            .push((field.to_string(), PatchOperation::Increment(delta.into())));
+        self
+    }
+
+    /// Apply the operations in order, later operations see the result of earlier ones
+    pub fn apply(&self, document: &mut Document) -> Result<(), DatabaseError> {
+        for (field, operation) in &self.operations {
+            match operation {
+                PatchOperation::Set(value) => {
+                    document.data.insert(field.clone(), value.clone());
+                }
+                PatchOperation::Unset => {
+                    document.data.remove(field);
+                }
+                PatchOperation::Increment(delta) => {
+                    let value = (Expr::field(field) + delta.clone()).evaluate(document)?;
+                    document.data.insert(field.clone(), value);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Collection {
+    /// Apply a patch to a single document, the patched document must still match the schema
+    pub fn patch(&mut self, id: u64, patch: &DocumentPatch) -> Result<(), DatabaseError> {
+        let Some(document) = self.find_by_id(id) else {
+            return Err(DatabaseError::DocumentNotFound(id));
+        };
+
+        let mut patched = document.clone();
+        patch.apply(&mut patched)?;
+        self.schema.normalize_document(&mut patched);
+        self.schema.validate_document(&patched)?;
+
+        self.store_documents(vec![patched])?;
+        self.persist()
+    }
+}
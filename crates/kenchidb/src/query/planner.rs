@@ -0,0 +1,504 @@
+use std::{fmt, ops::Bound};
+
+use crate::{
+    common::DatabaseError,
+    database::Collection,
+    index::IndexKind,
+    query::{Collation, FindQuery, Query, QueryOperation},
+    schema::{Document, Value, current_time_millis, encode_key, encode_key_value},
+};
+
+/// Cost of reading one candidate through an index relative to a document of a full scan
+const INDEX_ROW_COST: usize = 2;
+
+/// How the candidate documents of a query are read
+#[derive(Debug, Clone)]
+pub enum AccessPath {
+    /// Visit every document of the collection
+    FullScan,
+    /// Scan a range of the primary key index.
+    /// `prefix` holds the values of the leading key fields compared with `=`,
+    /// `lower` and `upper` bound the next key field.
+    PrimaryKey {
+        prefix: Vec<Value>,
+        lower: Bound<Value>,
+        upper: Bound<Value>,
+    },
+    /// Scan a range of the secondary index on the field,
+    /// an equality condition has the same value as both bounds
+    Index {
+        field: String,
+        kind: IndexKind,
+        lower: Bound<Value>,
+        upper: Bound<Value>,
+    },
+}
+
+/// Access path chosen for a find query.
+/// The full filter is always applied to the candidates,
+/// the access path only narrows down which documents are read.
+#[derive(Debug, Clone)]
+pub struct QueryPlan {
+    pub access: AccessPath,
+    pub query: FindQuery,
+    /// The projection, filter and order only use the value of the scanned index,
+    /// `Collection::find_projected` answers the query from the index alone
+    pub covered: bool,
+    /// Candidate documents the planner expects the access path to read
+    pub estimated_rows: usize,
+    /// Names of the primary key fields, used by `explain`
+    key_fields: Vec<String>,
+}
+
+impl QueryPlan {
+    /// Human readable description of the plan
+    pub fn explain(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// Chooses between the available indexes and a full scan.
+/// Only the top-level conjunction of the filter is used for index selection:
+/// `=` conditions on leading primary key fields, optionally followed by a range
+/// condition on the next key field, turn into a primary key range scan.
+/// `=` and range conditions on a field with a secondary index turn into an index scan.
+/// Among the candidate access paths and a full scan the one with the lowest estimated
+/// cost is chosen, see `QueryPlanner::estimate`. On equal costs a lookup of the full primary
+/// key is preferred, then an index lookup, then a primary key range scan, then an index range
+/// scan and last a full scan.
+pub struct QueryPlanner<'a> {
+    collection: &'a Collection,
+}
+
+impl<'a> QueryPlanner<'a> {
+    pub fn new(collection: &'a Collection) -> Self {
+        Self { collection }
+    }
+
+    pub fn plan(&self, query: &FindQuery) -> QueryPlan {
+        let key_fields = self
+            .collection
+            .schema
+            .primary_key
+            .clone()
+            .unwrap_or_default();
+
+        // Candidates in order of preference when their costs are equal
+        let primary_key = Self::primary_key_access(&key_fields, &query.filter);
+        let key_lookup = matches!(
+            &primary_key,
+            Some(AccessPath::PrimaryKey { prefix, .. }) if prefix.len() == key_fields.len()
+        );
+        let (index_lookups, index_ranges): (Vec<_>, Vec<_>) = self
+            .index_accesses(&query.filter)
+            .into_iter()
+            .partition(|access| {
+                matches!(access, AccessPath::Index { lower, upper, .. } if is_lookup(lower, upper))
+            });
+
+        let mut candidates = Vec::new();
+        if key_lookup {
+            candidates.extend(primary_key.clone());
+        }
+        candidates.extend(index_lookups);
+        if !key_lookup {
+            candidates.extend(primary_key);
+        }
+        candidates.extend(index_ranges);
+        candidates.push(AccessPath::FullScan);
+
+        let (access, estimated_rows, _) = candidates
+            .into_iter()
+            .map(|access| {
+                let covered = self.covers(&access, query);
+                let (rows, cost) = self.estimate(&access, covered);
+                (access, rows, cost)
+            })
+            .min_by_key(|(_, _, cost)| *cost)
+            .expect("a full scan is always possible");
+
+        QueryPlan {
+            covered: self.covers(&access, query),
+            access,
+            estimated_rows,
+            query: query.clone(),
+            key_fields,
+        }
+    }
+
+    /// Estimated candidate count and cost of the access path.
+    /// A full scan and a primary key scan read each candidate document once. A secondary index
+    /// scan reads an index entry and then the document for each candidate, unless the index
+    /// covers the query. An index lookup is estimated from the index statistics as the average
+    /// number of documents per value. Other candidates are counted, only until the scan is
+    /// certain to cost more than a full scan.
+    fn estimate(&self, access: &AccessPath, covered: bool) -> (usize, usize) {
+        let total = self.collection.documents.len();
+        let row_cost = match access {
+            AccessPath::Index { .. } if !covered => INDEX_ROW_COST,
+            _ => 1,
+        };
+        let limit = total / row_cost + 1;
+
+        let rows = match access {
+            AccessPath::FullScan => return (total, total),
+            AccessPath::PrimaryKey { .. } => self.collection.scan(access).take(limit).count(),
+            AccessPath::Index {
+                field,
+                lower,
+                upper,
+                ..
+            } => match self.collection.indexes.get(field) {
+                Some(index) if is_lookup(lower, upper) => {
+                    index.stats().average_duplicates().ceil() as usize
+                }
+                Some(index) => index.range(lower, upper).take(limit).count(),
+                None => return (total, usize::MAX),
+            },
+        };
+        (rows, rows * row_cost)
+    }
+
+    /// Whether the index scanned by the access path holds every field the query reads.
+    /// With a TTL policy the documents are read to check whether they expired.
+    fn covers(&self, access: &AccessPath, query: &FindQuery) -> bool {
+        let AccessPath::Index { field, .. } = access else {
+            return false;
+        };
+        let Some(projection) = &query.projection else {
+            return false;
+        };
+
+        self.collection.ttl.is_none()
+            && projection.iter().all(|selected| selected == field)
+            && only_reads(&query.filter, field)
+            && query
+                .order_by
+                .iter()
+                .flat_map(|order_by| &order_by.keys)
+                .all(|key| key.field == *field)
+    }
+
+    /// Scan of each secondary index with a usable condition,
+    /// an equality condition on the field wins over a range
+    fn index_accesses(&self, filter: &Query) -> Vec<AccessPath> {
+        let conjuncts = match filter {
+            Query::And(queries) => queries.iter().collect(),
+            query => vec![query],
+        };
+
+        let mut accesses = Vec::new();
+        'indexes: for (field, index) in &self.collection.indexes {
+            let kind = index.options.kind;
+            let mut lower = Bound::Unbounded;
+            let mut upper = Bound::Unbounded;
+
+            for operation in field_operations(&conjuncts, field) {
+                match operation {
+                    // Null values are not indexed
+                    QueryOperation::Equals(Value::Null) => {}
+                    QueryOperation::Equals(value) => {
+                        accesses.push(AccessPath::Index {
+                            field: field.clone(),
+                            kind,
+                            lower: Bound::Included(value.clone()),
+                            upper: Bound::Included(value.clone()),
+                        });
+                        continue 'indexes;
+                    }
+                    QueryOperation::GreaterThan(value) => lower = Bound::Excluded(value.clone()),
+                    QueryOperation::GreaterOrEqual(value) => lower = Bound::Included(value.clone()),
+                    QueryOperation::LessThan(value) => upper = Bound::Excluded(value.clone()),
+                    QueryOperation::LessOrEqual(value) => upper = Bound::Included(value.clone()),
+                    QueryOperation::Between(low, high) => {
+                        lower = Bound::Included(low.clone());
+                        upper = Bound::Included(high.clone());
+                    }
+                    _ => {}
+                }
+            }
+
+            // Hash indexes only answer equality conditions
+            if kind == IndexKind::BTree
+                && !(matches!(lower, Bound::Unbounded) && matches!(upper, Bound::Unbounded))
+            {
+                accesses.push(AccessPath::Index {
+                    field: field.clone(),
+                    kind,
+                    lower,
+                    upper,
+                });
+            }
+        }
+        accesses
+    }
+
+    fn primary_key_access(key_fields: &[String], filter: &Query) -> Option<AccessPath> {
+        let conjuncts = match filter {
+            Query::And(queries) => queries.iter().collect(),
+            query => vec![query],
+        };
+
+        let mut prefix = Vec::new();
+        let mut lower = Bound::Unbounded;
+        let mut upper = Bound::Unbounded;
+
+        for field in key_fields {
+            let operations = field_operations(&conjuncts, field);
+            if let Some(value) = operations.iter().find_map(|operation| match operation {
+                QueryOperation::Equals(value) => Some(value),
+                _ => None,
+            }) {
+                prefix.push(value.clone());
+                continue;
+            }
+
+            for operation in operations {
+                match operation {
+                    QueryOperation::GreaterThan(value) => lower = Bound::Excluded(value.clone()),
+                    QueryOperation::GreaterOrEqual(value) => lower = Bound::Included(value.clone()),
+                    QueryOperation::LessThan(value) => upper = Bound::Excluded(value.clone()),
+                    QueryOperation::LessOrEqual(value) => upper = Bound::Included(value.clone()),
+                    QueryOperation::Between(low, high) => {
+                        lower = Bound::Included(low.clone());
+                        upper = Bound::Included(high.clone());
+                    }
+                    _ => {}
+                }
+            }
+            break;
+        }
+
+        if prefix.is_empty()
+            && matches!(lower, Bound::Unbounded)
+            && matches!(upper, Bound::Unbounded)
+        {
+            return None;
+        }
+
+        Some(AccessPath::PrimaryKey {
+            prefix,
+            lower,
+            upper,
+        })
+    }
+}
+
+/// Operations of the conditions on the field
+fn field_operations<'q>(conjuncts: &[&'q Query], field: &str) -> Vec<&'q QueryOperation> {
+    conjuncts
+        .iter()
+        .filter_map(|query| match query {
+            Query::Condition(condition) if condition.field == field => Some(&condition.operation),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether every condition of the filter is on the field
+fn only_reads(filter: &Query, field: &str) -> bool {
+    match filter {
+        Query::Condition(condition) => condition.field == field,
+        Query::And(queries) | Query::Or(queries) => {
+            queries.iter().all(|query| only_reads(query, field))
+        }
+        Query::Not(query) => only_reads(query, field),
+    }
+}
+
+/// Bounds matching a single value
+fn is_lookup(lower: &Bound<Value>, upper: &Bound<Value>) -> bool {
+    matches!((lower, upper), (Bound::Included(low), Bound::Included(high)) if low == high)
+}
+
+impl fmt::Display for QueryPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.access {
+            AccessPath::FullScan => writeln!(f, "Full scan")?,
+            AccessPath::PrimaryKey {
+                prefix,
+                lower,
+                upper,
+            } => {
+                let mut bounds = Vec::new();
+                for (field, value) in self.key_fields.iter().zip(prefix) {
+                    bounds.push(format!("{} = {}", field, value));
+                }
+                if let Some(field) = self.key_fields.get(prefix.len()) {
+                    match lower {
+                        Bound::Included(value) => bounds.push(format!("{} >= {}", field, value)),
+                        Bound::Excluded(value) => bounds.push(format!("{} > {}", field, value)),
+                        Bound::Unbounded => {}
+                    }
+                    match upper {
+                        Bound::Included(value) => bounds.push(format!("{} <= {}", field, value)),
+                        Bound::Excluded(value) => bounds.push(format!("{} < {}", field, value)),
+                        Bound::Unbounded => {}
+                    }
+                }
+
+                let kind = if prefix.len() == self.key_fields.len() {
+                    "lookup"
+                } else {
+                    "range scan"
+                };
+                writeln!(f, "Primary key {}: {}", kind, bounds.join(", "))?;
+            }
+            AccessPath::Index {
+                field,
+                kind,
+                lower,
+                upper,
+            } => {
+                let mut bounds = Vec::new();
+                match lower {
+                    Bound::Included(value) if is_lookup(lower, upper) => {
+                        bounds.push(format!("{} = {}", field, value));
+                    }
+                    _ => {
+                        match lower {
+                            Bound::Included(value) => {
+                                bounds.push(format!("{} >= {}", field, value))
+                            }
+                            Bound::Excluded(value) => bounds.push(format!("{} > {}", field, value)),
+                            Bound::Unbounded => {}
+                        }
+                        match upper {
+                            Bound::Included(value) => {
+                                bounds.push(format!("{} <= {}", field, value))
+                            }
+                            Bound::Excluded(value) => bounds.push(format!("{} < {}", field, value)),
+                            Bound::Unbounded => {}
+                        }
+                    }
+                }
+
+                let scan = if is_lookup(lower, upper) {
+                    "lookup"
+                } else {
+                    "range scan"
+                };
+                let index = match kind {
+                    IndexKind::BTree => "Index",
+                    IndexKind::Hash => "Hash index",
+                };
+                writeln!(f, "{} {} on {}: {}", index, scan, field, bounds.join(", "))?;
+            }
+        }
+
+        writeln!(f, "Filter: {}", self.query.filter)?;
+
+        if let Some(fields) = &self.query.projection {
+            match self.covered {
+                true => writeln!(f, "Projection: {}, covered by the index", fields.join(", "))?,
+                false => writeln!(f, "Projection: {}", fields.join(", "))?,
+            }
+        }
+
+        if let Some(size) = self.query.sample {
+            writeln!(f, "Sample: {} documents, reservoir sampling", size)?;
+        }
+
+        if let Some(order_by) = &self.query.order_by {
+            let order_by = order_by
+                .keys
+                .iter()
+                .map(|key| match key.collation {
+                    Collation::Binary => format!("{} {:?}", key.field, key.direction),
+                    Collation::CaseInsensitive => {
+                        format!("{} {:?} case-insensitive", key.field, key.direction)
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            match self.query.limit {
+                Some(limit) => writeln!(
+                    f,
+                    "Sort: {}, top {} kept in a heap",
+                    order_by,
+                    self.query.offset.saturating_add(limit)
+                )?,
+                None => writeln!(f, "Sort: {}", order_by)?,
+            }
+        }
+        if self.query.offset > 0 {
+            writeln!(f, "Offset: {}", self.query.offset)?;
+        }
+        if let Some(limit) = self.query.limit {
+            writeln!(f, "Limit: {}", limit)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Collection {
+    /// Describe how the query would be executed
+    pub fn explain<Q: Into<FindQuery>>(&self, query: Q) -> Result<QueryPlan, DatabaseError> {
+        let query = query.into();
+        query.validate(&self.schema)?;
+        Ok(QueryPlanner::new(self).plan(&query))
+    }
+
+    /// Live candidate documents of the access path
+    pub(crate) fn scan(&self, access: &AccessPath) -> Box<dyn Iterator<Item = &Document> + '_> {
+        let now = current_time_millis();
+        let (prefix, lower, upper) = match access {
+            AccessPath::FullScan => return Box::new(self.live_documents()),
+            AccessPath::PrimaryKey {
+                prefix,
+                lower,
+                upper,
+            } => (prefix, lower, upper),
+            AccessPath::Index {
+                field,
+                lower,
+                upper,
+                ..
+            } => {
+                let Some(index) = self.indexes.get(field) else {
+                    return Box::new(self.live_documents());
+                };
+                return Box::new(
+                    index
+                        .range(lower, upper)
+                        .filter_map(|id| self.documents.get(&id))
+                        .filter(move |document| !self.is_expired(document, now)),
+                );
+            }
+        };
+
+        let prefix = encode_key(prefix);
+        let start = match lower {
+            Bound::Included(value) | Bound::Excluded(value) => {
+                let mut start = prefix.clone();
+                encode_key_value(value, &mut start);
+                start
+            }
+            Bound::Unbounded => prefix.clone(),
+        };
+        // Key encodings are self-delimiting, so comparing the leading bytes of a key
+        // with the encoded upper bound compares the bounded field
+        let end = match upper {
+            Bound::Included(value) | Bound::Excluded(value) => {
+                let mut end = prefix.clone();
+                encode_key_value(value, &mut end);
+                Some(end)
+            }
+            Bound::Unbounded => None,
+        };
+
+        Box::new(
+            self.primary_index
+                .range(start..)
+                .take_while(move |(key, _)| {
+                    key.starts_with(&prefix)
+                        && end
+                            .as_ref()
+                            .is_none_or(|end| key[..end.len().min(key.len())] <= end[..])
+                })
+                .filter_map(|(_, id)| self.documents.get(id))
+                .filter(move |document| !self.is_expired(document, now)),
+        )
+    }
+}
@@ -0,0 +1,80 @@
+use crate::{
+    common::DatabaseError,
+    database::Collection,
+    query::{FindQuery, Literal, OrderBy, QueryParser, QueryTemplate},
+    schema::{Document, FieldType, Value},
+};
+
+/// Query parsed once and executed many times with different parameters.
+/// `$1`, `$2`, ... placeholders in the template are bound at execution time,
+/// a parameter must convert to the type of the field it is compared with.
+#[derive(Debug, Clone)]
+pub struct PreparedQuery {
+    pub template: QueryTemplate,
+    /// Field type of each parameter, `$1` first
+    pub parameters: Vec<FieldType>,
+    pub projection: Option<Vec<String>>,
+    pub sample: Option<usize>,
+    pub order_by: Option<OrderBy>,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+impl PreparedQuery {
+    pub fn parameter_count(&self) -> usize {
+        self.parameters.len()
+    }
+
+    /// Build the query with the parameters substituted, `parameters[0]` binds `$1`
+    pub fn bind(&self, parameters: &[Value]) -> Result<FindQuery, DatabaseError> {
+        if parameters.len() != self.parameters.len() {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "Prepared query takes {} parameters, got {}",
+                self.parameters.len(),
+                parameters.len()
+            )));
+        }
+
+        let mut values = Vec::with_capacity(parameters.len());
+        for (index, (parameter, field_type)) in parameters.iter().zip(&self.parameters).enumerate()
+        {
+            let value = Literal::from_value(parameter.clone())
+                .to_value(field_type)
+                .ok_or_else(|| {
+                    DatabaseError::InvalidQuery(format!(
+                        "Parameter ${} must be {:?}, got {} {}",
+                        index + 1,
+                        field_type,
+                        parameter.type_name(),
+                        parameter
+                    ))
+                })?;
+            values.push(value);
+        }
+
+        Ok(FindQuery {
+            filter: self.template.bind(&values)?,
+            projection: self.projection.clone(),
+            sample: self.sample,
+            order_by: self.order_by.clone(),
+            offset: self.offset,
+            limit: self.limit,
+        })
+    }
+}
+
+impl Collection {
+    /// Parse a query template against the collection schema for repeated execution
+    pub fn prepare(&self, template: &str) -> Result<PreparedQuery, DatabaseError> {
+        QueryParser::new(&self.schema, template)?.parse()
+    }
+
+    /// Run a prepared query with the given parameters
+    pub fn find_prepared(
+        &self,
+        prepared: &PreparedQuery,
+        parameters: &[Value],
+    ) -> Result<Vec<&Document>, DatabaseError> {
+        self.find_where(prepared.bind(parameters)?)
+    }
+}
@@ -0,0 +1,199 @@
+use std::{cell::Cell, io::Write, time::Instant};
+
+use crate::{
+    common::DatabaseError,
+    database::Collection,
+    query::{FindQuery, Query, QueryPlanner, QueryStats},
+    schema::{Document, Field, Value},
+};
+
+/// Lazily evaluated find query over a collection.
+/// Unordered results are streamed straight from the collection,
+/// ordered and sampled results are collected when iteration starts.
+pub struct QueryResult<'a> {
+    collection: &'a Collection,
+    query: FindQuery,
+}
+
+impl<'a> QueryResult<'a> {
+    pub fn iter(&self) -> Box<dyn Iterator<Item = &'a Document> + '_> {
+        let plan = QueryPlanner::new(self.collection).plan(&self.query);
+        let documents = self.collection.scan(&plan.access);
+
+        if self.query.order_by.is_some() || self.query.sample.is_some() {
+            return Box::new(self.query.execute(documents).into_iter());
+        }
+
+        Box::new(
+            documents
+                .filter(|doc| self.query.filter.matches(doc))
+                .skip(self.query.offset)
+                .take(self.query.limit.unwrap_or(usize::MAX)),
+        )
+    }
+
+    /// Collect the documents together with the cost of finding them.
+    /// Unordered queries stop scanning once the limit is reached, as with `iter`.
+    pub fn with_stats(&self) -> (Vec<&'a Document>, QueryStats) {
+        let started = Instant::now();
+        let scanned = Cell::new(0);
+        let matched = Cell::new(0);
+
+        let plan = QueryPlanner::new(self.collection).plan(&self.query);
+        let matching = self
+            .collection
+            .scan(&plan.access)
+            .inspect(|_| scanned.set(scanned.get() + 1))
+            .filter(|doc| self.query.filter.matches(doc))
+            .inspect(|_| matched.set(matched.get() + 1));
+
+        // The documents are filtered already
+        let arrange = FindQuery {
+            filter: Query::all(),
+            ..self.query.clone()
+        };
+        let documents = arrange.execute(matching);
+
+        let stats = QueryStats {
+            rows_scanned: scanned.get(),
+            rows_matched: matched.get(),
+            elapsed: started.elapsed(),
+            ..QueryStats::default()
+        };
+        (documents, stats)
+    }
+
+    /// Write the documents as a JSON array of objects.
+    /// Fields follow the schema order after `_id`, missing fields are written as `null`.
+    pub fn to_json_writer<W: Write>(&self, writer: &mut W) -> Result<(), DatabaseError> {
+        let fields = &self.collection.schema.fields;
+
+        writer.write_all(b"[")?;
+        for (i, document) in self.iter().enumerate() {
+            if i > 0 {
+                writer.write_all(b",")?;
+            }
+
+            write_json_document(writer, fields, document)?;
+        }
+        writer.write_all(b"]")?;
+
+        Ok(())
+    }
+
+    /// Write the documents as RFC 4180 CSV with a header row.
+    /// Columns follow the schema order after `_id`, missing fields are left empty.
+    pub fn to_csv_writer<W: Write>(&self, writer: &mut W) -> Result<(), DatabaseError> {
+        let fields = &self.collection.schema.fields;
+
+        writer.write_all(b"_id")?;
+        for field in fields {
+            writer.write_all(b",")?;
+            write_csv_field(writer, &field.name)?;
+        }
+        writer.write_all(b"\r\n")?;
+
+        for document in self.iter() {
+            write!(writer, "{}", document.id)?;
+            for field in fields {
+                writer.write_all(b",")?;
+                match document.get(&field.name) {
+                    Some(Value::String(value)) => write_csv_field(writer, value)?,
+                    Some(value) => write_plain_value(writer, value)?,
+                    None => {}
+                }
+            }
+            writer.write_all(b"\r\n")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Write the document as a JSON object, fields follow the schema order after `_id`
+pub(crate) fn write_json_document<W: Write>(
+    writer: &mut W,
+    fields: &[Field],
+    document: &Document,
+) -> Result<(), DatabaseError> {
+    write!(writer, "{{\"_id\":{}", document.id)?;
+    for field in fields {
+        writer.write_all(b",")?;
+        write_json_string(writer, &field.name)?;
+        writer.write_all(b":")?;
+        match document.get(&field.name) {
+            Some(value) => write_json_value(writer, value)?,
+            None => writer.write_all(b"null")?,
+        }
+    }
+    writer.write_all(b"}")?;
+    Ok(())
+}
+
+fn write_json_value<W: Write>(writer: &mut W, value: &Value) -> Result<(), DatabaseError> {
+    match value {
+        Value::String(value) => write_json_string(writer, value),
+        // JSON has no representation for NaN and infinity
+        Value::Float(v) if !v.is_finite() => Ok(writer.write_all(b"null")?),
+        Value::Double(v) if !v.is_finite() => Ok(writer.write_all(b"null")?),
+        value => write_plain_value(writer, value),
+    }
+}
+
+fn write_plain_value<W: Write>(writer: &mut W, value: &Value) -> Result<(), DatabaseError> {
+    match value {
+        Value::Byte(v) => write!(writer, "{}", v)?,
+        Value::Short(v) => write!(writer, "{}", v)?,
+        Value::Int(v) => write!(writer, "{}", v)?,
+        Value::Long(v) => write!(writer, "{}", v)?,
+        Value::Float(v) => write!(writer, "{}", v)?,
+        Value::Double(v) => write!(writer, "{}", v)?,
+        Value::Boolean(v) => write!(writer, "{}", v)?,
+        Value::String(v) => writer.write_all(v.as_bytes())?,
+        Value::Null => writer.write_all(b"null")?,
+    }
+    Ok(())
+}
+
+pub(crate) fn write_json_string<W: Write>(
+    writer: &mut W,
+    value: &str,
+) -> Result<(), DatabaseError> {
+    writer.write_all(b"\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => writer.write_all(b"\\\"")?,
+            '\\' => writer.write_all(b"\\\\")?,
+            '\n' => writer.write_all(b"\\n")?,
+            '\r' => writer.write_all(b"\\r")?,
+            '\t' => writer.write_all(b"\\t")?,
+            c if (c as u32) < 0x20 => write!(writer, "\\u{:04x}", c as u32)?,
+            c => write!(writer, "{}", c)?,
+        }
+    }
+    writer.write_all(b"\"")?;
+    Ok(())
+}
+
+/// Quote the field if it contains a separator, quote or line break
+fn write_csv_field<W: Write>(writer: &mut W, value: &str) -> Result<(), DatabaseError> {
+    if value.contains([',', '"', '\n', '\r']) {
+        write!(writer, "\"{}\"", value.replace('"', "\"\""))?;
+    } else {
+        writer.write_all(value.as_bytes())?;
+    }
+    Ok(())
+}
+
+impl Collection {
+    /// Validate the query and return its result without materializing the documents
+    pub fn query<Q: Into<FindQuery>>(&self, query: Q) -> Result<QueryResult<'_>, DatabaseError> {
+        let query = query.into();
+        query.validate(&self.schema)?;
+
+        Ok(QueryResult {
+            collection: self,
+            query,
+        })
+    }
+}
@@ -0,0 +1,32 @@
+use crate::{common::Random, database::Collection, schema::Document};
+
+/// Uniform random sample of at most `size` items, read in a single pass (reservoir sampling).
+/// Every item has the same chance to be picked, whatever the length of the input.
+pub fn reservoir_sample<T, I>(items: I, size: usize, random: &mut Random) -> Vec<T>
+where
+    I: Iterator<Item = T>,
+{
+    let mut reservoir = Vec::with_capacity(size.min(1024));
+    if size == 0 {
+        return reservoir;
+    }
+
+    for (seen, item) in items.enumerate() {
+        if seen < size {
+            reservoir.push(item);
+        } else {
+            let slot = random.below(seen as u64 + 1) as usize;
+            if slot < size {
+                reservoir[slot] = item;
+            }
+        }
+    }
+    reservoir
+}
+
+impl Collection {
+    /// Up to `size` documents picked uniformly at random, in no particular order
+    pub fn sample(&self, size: usize) -> Vec<&Document> {
+        reservoir_sample(self.live_documents(), size, &mut Random::new())
+    }
+}
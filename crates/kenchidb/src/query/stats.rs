@@ -0,0 +1,25 @@
+use std::{fmt, time::Duration};
+
+/// Cost of executing a query, for logging the cost per endpoint
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct QueryStats {
+    /// Documents read from the access path, before the filter
+    pub rows_scanned: usize,
+    /// Documents that passed the filter, before sampling and pagination
+    pub rows_matched: usize,
+    /// Pages loaded from the files, zero for in-memory collections
+    pub pages_read: u64,
+    /// Page reads served from the buffer pool
+    pub cache_hits: u64,
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for QueryStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "scanned {} rows, matched {}, read {} pages, {} cache hits in {:?}",
+            self.rows_scanned, self.rows_matched, self.pages_read, self.cache_hits, self.elapsed
+        )
+    }
+}
@@ -0,0 +1,53 @@
+use crate::{
+    common::DatabaseError,
+    database::Collection,
+    query::{Expr, FindQuery},
+};
+
+/// Field assignments applied by `Collection::update_where`
+#[derive(Debug, Clone, Default)]
+pub struct Update {
+    pub assignments: Vec<(String, Expr)>,
+}
+
+impl Update {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Assign the result of an expression to a field.
+    /// Expressions see the document as it was before the update.
+    pub fn set<E: Into<Expr>>(mut self, field: &str, expr: E) -> Self {
+        self.assignments.push((field.to_string(), expr.into()));
+        self
+    }
+}
+
+impl Collection {
+    /// Apply the update to every matching document, returns the number of updated documents.
+    /// All updated documents are computed and validated before any of them is stored.
+    pub fn update_where<Q: Into<FindQuery>>(
+        &mut self,
+        query: Q,
+        update: &Update,
+    ) -> Result<usize, DatabaseError> {
+        let mut updated = Vec::new();
+
+        for document in self.find_where(query)? {
+            let mut new_document = document.clone();
+            for (field, expr) in &update.assignments {
+                new_document.set(field, expr.evaluate(document)?);
+            }
+
+            self.schema.normalize_document(&mut new_document);
+            self.schema.validate_document(&new_document)?;
+            updated.push(new_document);
+        }
+
+        let count = updated.len();
+        self.store_documents(updated)?;
+
+        self.persist()?;
+        Ok(count)
+    }
+}
@@ -0,0 +1,48 @@
+use std::fmt;
+
+use crate::{
+    common::DatabaseError,
+    query::{ByteReader, FindQuery, decode_find_query, encode_find_query, encode_short_string},
+};
+
+/// Named query definition stored in the database catalog
+#[derive(Debug, Clone)]
+pub struct View {
+    pub name: String,
+    pub collection: String,
+    pub query: FindQuery,
+}
+
+impl View {
+    pub fn new(name: &str, collection: &str, query: FindQuery) -> Self {
+        Self {
+            name: name.to_string(),
+            collection: collection.to_string(),
+            query,
+        }
+    }
+
+    pub fn serialize(&self, bytes: &mut Vec<u8>) -> Result<(), DatabaseError> {
+        encode_short_string(&self.name, bytes);
+        encode_short_string(&self.collection, bytes);
+        encode_find_query(&self.query, bytes)
+    }
+
+    pub fn deserialize(reader: &mut ByteReader) -> Result<Self, DatabaseError> {
+        let name = reader.read_short_string()?;
+        let collection = reader.read_short_string()?;
+        let query = decode_find_query(reader)?;
+
+        Ok(Self {
+            name,
+            collection,
+            query,
+        })
+    }
+}
+
+impl fmt::Display for View {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} on {}: {}", self.name, self.collection, self.query)
+    }
+}
@@ -1,6 +1,12 @@
 use std::collections::HashMap;
 
-use crate::{common::DatabaseError, schema::value::Value};
+use crate::{
+    common::DatabaseError,
+    schema::{
+        IdentifierKind, MAX_STRING_BYTES, encode_key_value, truncate_to_bytes, validate_identifier,
+        value::Value,
+    },
+};
 
 // Schema definition for type safety
 #[derive(Debug, Clone, PartialEq)]
@@ -17,42 +23,212 @@ pub enum FieldType {
 
 impl FieldType {
     pub fn validates(&self, value: &Value) -> bool {
-        match (self, value) {
-            (FieldType::Byte, Value::Byte(_)) => true,
-            (FieldType::Short, Value::Short(_)) => true,
-            (FieldType::Int, Value::Int(_)) => true,
-            (FieldType::Long, Value::Long(_)) => true,
-            (FieldType::Float, Value::Float(_)) => true,
-            (FieldType::Double, Value::Double(_)) => true,
-            (FieldType::String, Value::String(_)) => true,
-            (FieldType::Boolean, Value::Boolean(_)) => true,
-            _ => false,
-        }
+        matches!(
+            (self, value),
+            (FieldType::Byte, Value::Byte(_))
+                | (FieldType::Short, Value::Short(_))
+                | (FieldType::Int, Value::Int(_))
+                | (FieldType::Long, Value::Long(_))
+                | (FieldType::Float, Value::Float(_))
+                | (FieldType::Double, Value::Double(_))
+                | (FieldType::String, Value::String(_))
+                | (FieldType::Boolean, Value::Boolean(_))
+        )
     }
 }
 
+/// What to do with string values longer than `MAX_STRING_BYTES`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum StringOverflow {
+    /// Reject the document
+    #[default]
+    Error,
+    /// Cut the string at the last character boundary that fits
+    Truncate,
+}
+
 #[derive(Debug, Clone)]
 pub struct Field {
     pub name: String,
     pub field_type: FieldType,
     pub nullable: bool,
+    pub on_overflow: StringOverflow,
+    /// Missing values are taken from a per-collection sequence, see `Collection::insert`
+    pub auto_increment: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct Schema {
     pub name: String,
     pub fields: Vec<Field>,
+    /// Fields of the composite primary key, documents are unique and ordered by them
+    pub primary_key: Option<Vec<String>>,
+    /// String fields tokenized into the collection's full-text index
+    pub text_fields: Vec<String>,
+    /// Accept fields not declared in the schema, see `Schema::lenient`
+    pub lenient: bool,
 }
 
 impl Schema {
     pub fn new(name: String, fields: Vec<Field>) -> Self {
-        Self { name, fields }
+        Self {
+            name,
+            fields,
+            primary_key: None,
+            text_fields: Vec::new(),
+            lenient: false,
+        }
+    }
+
+    /// Declare a composite primary key, e.g. `(tenant_id, user_id)`
+    pub fn with_primary_key(mut self, fields: &[&str]) -> Self {
+        self.primary_key = Some(fields.iter().map(|f| f.to_string()).collect());
+        self
+    }
+
+    /// Declare the string fields searched by `Collection::search`
+    pub fn with_text_index(mut self, fields: &[&str]) -> Self {
+        self.text_fields = fields.iter().map(|f| f.to_string()).collect();
+        self
+    }
+
+    /// Accept documents with fields the schema does not declare, holding values of any type.
+    /// Declared fields are still validated, `Query::exists` filters on undeclared ones.
+    pub fn lenient(mut self) -> Self {
+        self.lenient = true;
+        self
+    }
+
+    /// Encoded primary key of the document, `None` without a primary key
+    pub fn primary_key_of(&self, document: &Document) -> Result<Option<Vec<u8>>, DatabaseError> {
+        let Some(key_fields) = &self.primary_key else {
+            return Ok(None);
+        };
+
+        let mut key = Vec::new();
+        for field in key_fields {
+            let Some(value) = document.get(field) else {
+                return Err(DatabaseError::SchemaViolation(format!(
+                    "Primary key field '{}' is missing",
+                    field
+                )));
+            };
+            encode_key_value(value, &mut key);
+        }
+
+        Ok(Some(key))
+    }
+
+    /// Create a schema, validating its name and field names
+    pub fn try_new(name: String, fields: Vec<Field>) -> Result<Self, DatabaseError> {
+        let schema = Self::new(name, fields);
+        schema.validate()?;
+        Ok(schema)
+    }
+
+    /// Check the schema name and field names, that field names are unique
+    /// and that primary key fields exist and are not nullable and text fields are strings
+    pub fn validate(&self) -> Result<(), DatabaseError> {
+        validate_identifier(IdentifierKind::Schema, &self.name)?;
+
+        for (i, field) in self.fields.iter().enumerate() {
+            validate_identifier(IdentifierKind::Field, &field.name)?;
+
+            if field.auto_increment
+                && (field.nullable || !matches!(field.field_type, FieldType::Int | FieldType::Long))
+            {
+                return Err(DatabaseError::SchemaViolation(format!(
+                    "Auto increment field '{}' must be a non-nullable int or long",
+                    field.name
+                )));
+            }
+
+            if self.fields[..i].iter().any(|f| f.name == field.name) {
+                return Err(DatabaseError::InvalidIdentifier(format!(
+                    "Field name '{}' is declared more than once in schema '{}'",
+                    field.name, self.name
+                )));
+            }
+        }
+
+        if let Some(key_fields) = &self.primary_key {
+            if key_fields.is_empty() {
+                return Err(DatabaseError::SchemaViolation(format!(
+                    "Primary key of schema '{}' has no fields",
+                    self.name
+                )));
+            }
+
+            for (i, name) in key_fields.iter().enumerate() {
+                match self.fields.iter().find(|f| f.name == *name) {
+                    Some(field) if !field.nullable => {}
+                    Some(_) => {
+                        return Err(DatabaseError::SchemaViolation(format!(
+                            "Primary key field '{}' must not be nullable",
+                            name
+                        )));
+                    }
+                    None => {
+                        return Err(DatabaseError::SchemaViolation(format!(
+                            "Primary key field '{}' not in schema '{}'",
+                            name, self.name
+                        )));
+                    }
+                }
+
+                if key_fields[..i].contains(name) {
+                    return Err(DatabaseError::SchemaViolation(format!(
+                        "Primary key field '{}' is listed more than once",
+                        name
+                    )));
+                }
+            }
+        }
+
+        for (i, name) in self.text_fields.iter().enumerate() {
+            match self.fields.iter().find(|f| f.name == *name) {
+                Some(field) if field.field_type == FieldType::String => {}
+                Some(_) => {
+                    return Err(DatabaseError::SchemaViolation(format!(
+                        "Text field '{}' must be a string",
+                        name
+                    )));
+                }
+                None => {
+                    return Err(DatabaseError::SchemaViolation(format!(
+                        "Text field '{}' not in schema '{}'",
+                        name, self.name
+                    )));
+                }
+            }
+
+            if self.text_fields[..i].contains(name) {
+                return Err(DatabaseError::SchemaViolation(format!(
+                    "Text field '{}' is listed more than once",
+                    name
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Apply the string overflow policies, truncating long values of `Truncate` fields
+    pub fn normalize_document(&self, document: &mut Document) {
+        for field in &self.fields {
+            if field.on_overflow == StringOverflow::Truncate
+                && let Some(Value::String(value)) = document.data.get_mut(&field.name)
+            {
+                truncate_to_bytes(value, MAX_STRING_BYTES);
+            }
+        }
     }
 
     pub fn validate_document(&self, document: &Document) -> Result<(), DatabaseError> {
         // Check that all required fields are present
         for field in &self.fields {
             match document.data.get(&field.name) {
+                Some(Value::Null) if field.nullable => {}
                 Some(value) => {
                     if !field.field_type.validates(value) {
                         return Err(DatabaseError::SchemaViolation(format!(
@@ -62,6 +238,17 @@ impl Schema {
                             value.type_name()
                         )));
                     }
+
+                    if let Value::String(value) = value
+                        && value.len() > MAX_STRING_BYTES
+                    {
+                        return Err(DatabaseError::SchemaViolation(format!(
+                            "Field '{}' is {} bytes long (max {})",
+                            field.name,
+                            value.len(),
+                            MAX_STRING_BYTES
+                        )));
+                    }
                 }
                 None => {
                     if !field.nullable {
@@ -74,14 +261,29 @@ impl Schema {
             }
         }
 
-        // Check that no extra fields are present
-        for key in document.data.keys() {
-            if !self.fields.iter().any(|f| f.name == *key) {
+        // Check that no extra fields are present, lenient schemas only limit their size
+        for (key, value) in &document.data {
+            if self.fields.iter().any(|f| f.name == *key) {
+                continue;
+            }
+
+            if !self.lenient {
                 return Err(DatabaseError::SchemaViolation(format!(
                     "Unknown field '{}' not in schema",
                     key
                 )));
             }
+            validate_identifier(IdentifierKind::Field, key)?;
+            if let Value::String(value) = value
+                && value.len() > MAX_STRING_BYTES
+            {
+                return Err(DatabaseError::SchemaViolation(format!(
+                    "Field '{}' is {} bytes long (max {})",
+                    key,
+                    value.len(),
+                    MAX_STRING_BYTES
+                )));
+            }
         }
 
         Ok(())
@@ -93,6 +295,9 @@ impl Schema {
 pub struct Document {
     pub id: u64,
     pub data: HashMap<String, Value>,
+    /// Number of times the stored document was written, 1 after the insert.
+    /// Set by the collection, see `Collection::update_if_version`.
+    pub version: u64,
 }
 
 impl Document {
@@ -100,6 +305,7 @@ impl Document {
         Self {
             id,
             data: HashMap::new(),
+            version: 0,
         }
     }
 
@@ -110,4 +316,16 @@ impl Document {
     pub fn get(&self, field: &str) -> Option<&Value> {
         self.data.get(field)
     }
+
+    /// Copy of the document with only the given fields, missing fields stay missing
+    pub fn project(&self, fields: &[String]) -> Document {
+        Document {
+            id: self.id,
+            data: fields
+                .iter()
+                .filter_map(|field| Some((field.clone(), self.data.get(field)?.clone())))
+                .collect(),
+            version: self.version,
+        }
+    }
 }
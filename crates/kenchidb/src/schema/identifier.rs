@@ -0,0 +1,69 @@
+use crate::common::DatabaseError;
+
+/// Maximum length of collection and field names in bytes.
+pub const MAX_IDENTIFIER_LENGTH: usize = 64;
+
+/// Prefix reserved for fields managed by the database itself.
+pub const SYSTEM_FIELD_PREFIX: char = '_';
+
+/// Fields managed by the database, the only names allowed to start with `_`.
+pub const SYSTEM_FIELDS: &[&str] = &["_id"];
+
+/// Kind of the validated identifier, used in error messages.
+#[derive(Debug, Clone, Copy)]
+pub enum IdentifierKind {
+    Collection,
+    Schema,
+    Field,
+    View,
+}
+
+/// Validate a collection, view or field name.
+/// Identifiers are 1 to 64 ASCII letters, digits and underscores, starting with a letter.
+/// Names starting with an underscore are reserved for system fields.
+pub fn validate_identifier(kind: IdentifierKind, name: &str) -> Result<(), DatabaseError> {
+    let error = |reason: String| {
+        Err(DatabaseError::InvalidIdentifier(format!(
+            "{:?} name '{}' {}",
+            kind, name, reason
+        )))
+    };
+
+    let Some(first) = name.chars().next() else {
+        return error("must not be empty".to_string());
+    };
+
+    if name.len() > MAX_IDENTIFIER_LENGTH {
+        return error(format!(
+            "is {} bytes long (max {})",
+            name.len(),
+            MAX_IDENTIFIER_LENGTH
+        ));
+    }
+
+    if let Some(c) = name
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && *c != '_')
+    {
+        return error(format!(
+            "contains invalid character '{}' (allowed: a-z, A-Z, 0-9, _)",
+            c
+        ));
+    }
+
+    if first == SYSTEM_FIELD_PREFIX {
+        if matches!(kind, IdentifierKind::Field) && SYSTEM_FIELDS.contains(&name) {
+            return Ok(());
+        }
+        return error(format!(
+            "must not start with '{}', the prefix is reserved for system fields",
+            SYSTEM_FIELD_PREFIX
+        ));
+    }
+
+    if first.is_ascii_digit() {
+        return error("must not start with a digit".to_string());
+    }
+
+    Ok(())
+}
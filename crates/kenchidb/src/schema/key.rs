@@ -0,0 +1,132 @@
+use crate::{
+    common::DatabaseError,
+    schema::{FieldType, Value},
+};
+
+/// Order-preserving encoding of key values.
+/// Comparing two encoded keys byte by byte gives the same order as comparing the
+/// values field by field, so composite keys sharing a prefix are stored contiguously.
+///
+/// - Integers are big endian with the sign bit flipped
+/// - Floats use their IEEE 754 bits, negative numbers are inverted
+/// - Strings escape `0x00` as `0x00 0xFF` and end with `0x00 0x00`
+pub fn encode_key(values: &[Value]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for value in values {
+        encode_key_value(value, &mut bytes);
+    }
+    bytes
+}
+
+pub fn encode_key_value(value: &Value, bytes: &mut Vec<u8>) {
+    match value {
+        Value::Byte(v) => bytes.push(*v),
+        Value::Short(v) => bytes.extend_from_slice(&((*v as u16) ^ (1 << 15)).to_be_bytes()),
+        Value::Int(v) => bytes.extend_from_slice(&((*v as u32) ^ (1 << 31)).to_be_bytes()),
+        Value::Long(v) => bytes.extend_from_slice(&((*v as u64) ^ (1 << 63)).to_be_bytes()),
+        Value::Float(v) => {
+            let bits = v.to_bits();
+            let bits = if bits >> 31 == 1 {
+                !bits
+            } else {
+                bits | (1 << 31)
+            };
+            bytes.extend_from_slice(&bits.to_be_bytes());
+        }
+        Value::Double(v) => {
+            let bits = v.to_bits();
+            let bits = if bits >> 63 == 1 {
+                !bits
+            } else {
+                bits | (1 << 63)
+            };
+            bytes.extend_from_slice(&bits.to_be_bytes());
+        }
+        Value::Boolean(v) => bytes.push(*v as u8),
+        Value::String(v) => {
+            for &b in v.as_bytes() {
+                bytes.push(b);
+                if b == 0 {
+                    bytes.push(0xFF);
+                }
+            }
+            bytes.extend_from_slice(&[0, 0]);
+        }
+        // Key fields are never nullable
+        Value::Null => {}
+    }
+}
+
+/// Decode a single value written by `encode_key_value`, returns the value and the bytes read
+pub fn decode_key_value(
+    bytes: &[u8],
+    field_type: &FieldType,
+) -> Result<(Value, usize), DatabaseError> {
+    let fixed = |size: usize| {
+        bytes.get(..size).ok_or_else(|| {
+            DatabaseError::InvalidData(format!("Key of {} bytes is too short", bytes.len()))
+        })
+    };
+
+    let value = match field_type {
+        FieldType::Byte => (Value::Byte(fixed(1)?[0]), 1),
+        FieldType::Boolean => (Value::Boolean(fixed(1)?[0] != 0), 1),
+        FieldType::Short => {
+            let bits = u16::from_be_bytes(fixed(2)?.try_into().unwrap());
+            (Value::Short((bits ^ (1 << 15)) as i16), 2)
+        }
+        FieldType::Int => {
+            let bits = u32::from_be_bytes(fixed(4)?.try_into().unwrap());
+            (Value::Int((bits ^ (1 << 31)) as i32), 4)
+        }
+        FieldType::Long => {
+            let bits = u64::from_be_bytes(fixed(8)?.try_into().unwrap());
+            (Value::Long((bits ^ (1 << 63)) as i64), 8)
+        }
+        FieldType::Float => {
+            let bits = u32::from_be_bytes(fixed(4)?.try_into().unwrap());
+            let bits = if bits >> 31 == 1 {
+                bits & !(1 << 31)
+            } else {
+                !bits
+            };
+            (Value::Float(f32::from_bits(bits)), 4)
+        }
+        FieldType::Double => {
+            let bits = u64::from_be_bytes(fixed(8)?.try_into().unwrap());
+            let bits = if bits >> 63 == 1 {
+                bits & !(1 << 63)
+            } else {
+                !bits
+            };
+            (Value::Double(f64::from_bits(bits)), 8)
+        }
+        FieldType::String => {
+            let mut text = Vec::new();
+            let mut position = 0;
+            loop {
+                match (bytes.get(position), bytes.get(position + 1)) {
+                    (Some(0), Some(0)) => break,
+                    (Some(0), Some(0xFF)) => {
+                        text.push(0);
+                        position += 2;
+                    }
+                    (Some(&b), _) if b != 0 => {
+                        text.push(b);
+                        position += 1;
+                    }
+                    _ => {
+                        return Err(DatabaseError::InvalidData(
+                            "Unterminated string key".to_string(),
+                        ));
+                    }
+                }
+            }
+            let text = String::from_utf8(text).map_err(|e| {
+                DatabaseError::InvalidData(format!("Invalid string key UTF-8: {}", e))
+            })?;
+            (Value::String(text), position + 2)
+        }
+    };
+    Ok(value)
+}
@@ -1,5 +1,11 @@
 mod document;
+mod identifier;
+mod key;
+mod ttl;
 mod value;
 
 pub(crate) use self::document::*;
+pub(crate) use self::identifier::*;
+pub(crate) use self::key::*;
+pub(crate) use self::ttl::*;
 pub(crate) use self::value::*;
@@ -0,0 +1,58 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    common::DatabaseError,
+    schema::{Document, FieldType, Schema, Value},
+};
+
+/// Collection-level time-to-live policy.
+/// Documents expire once `expire_after` has elapsed since the timestamp stored in `field`.
+/// The TTL field must be a `long` holding milliseconds since the Unix epoch,
+/// documents without a timestamp never expire.
+#[derive(Debug, Clone)]
+pub struct TtlPolicy {
+    pub field: String,
+    pub expire_after: Duration,
+}
+
+impl TtlPolicy {
+    pub fn new(
+        schema: &Schema,
+        field: &str,
+        expire_after: Duration,
+    ) -> Result<Self, DatabaseError> {
+        match schema.fields.iter().find(|f| f.name == field) {
+            Some(f) if f.field_type == FieldType::Long => Ok(Self {
+                field: field.to_string(),
+                expire_after,
+            }),
+            Some(f) => Err(DatabaseError::SchemaViolation(format!(
+                "TTL field '{}' must be of type Long, got {:?}",
+                field, f.field_type
+            ))),
+            None => Err(DatabaseError::SchemaViolation(format!(
+                "TTL field '{}' not in schema",
+                field
+            ))),
+        }
+    }
+
+    /// Check whether the document is expired at the given time (milliseconds since the Unix epoch)
+    pub fn is_expired(&self, document: &Document, now_millis: i64) -> bool {
+        match document.get(&self.field) {
+            Some(Value::Long(timestamp)) => {
+                let expire_after = self.expire_after.as_millis().min(i64::MAX as u128) as i64;
+                timestamp.saturating_add(expire_after) <= now_millis
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Current time in milliseconds since the Unix epoch
+pub fn current_time_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or(0)
+}
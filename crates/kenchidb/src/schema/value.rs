@@ -1,3 +1,5 @@
+use std::fmt;
+
 use crate::common::DatabaseError;
 
 /**
@@ -11,6 +13,7 @@ const TYPE_FLOAT_ID: u8 = 4;
 const TYPE_DOUBLE_ID: u8 = 5;
 const TYPE_BOOLEAN_ID: u8 = 6;
 const TYPE_STRING_ID: u8 = 7;
+const TYPE_NULL_ID: u8 = 8;
 
 /**
  * Size of the database value types.
@@ -22,7 +25,13 @@ const TYPE_LONG_SIZE: usize = 9; // type_id + 8 bytes
 const TYPE_FLOAT_SIZE: usize = 5; // type_id + 4 bytes
 const TYPE_DOUBLE_SIZE: usize = 9; // type_id + 8 bytes
 const TYPE_BOOLEAN_SIZE: usize = 2; // type_id + 1 byte
-const TYPE_STRING_SIZE: usize = 256; // type_id + 1 byte + 255 bytes
+const TYPE_STRING_SIZE: usize = 2 + MAX_STRING_BYTES; // type_id + length + up to 255 UTF-8 bytes
+const TYPE_NULL_SIZE: usize = 1; // type_id
+
+/**
+ * Maximum length of a string value in UTF-8 bytes, the length is stored in a single byte.
+ */
+pub const MAX_STRING_BYTES: usize = 255;
 
 /**
  * Names for the database value types.
@@ -35,6 +44,7 @@ const TYPE_FLOAT_NAME: &str = "float";
 const TYPE_DOUBLE_NAME: &str = "double";
 const TYPE_BOOLEAN_NAME: &str = "boolean";
 const TYPE_STRING_NAME: &str = "string";
+const TYPE_NULL_NAME: &str = "null";
 
 /**
  * Core primitive types for the database.
@@ -48,7 +58,9 @@ pub enum Value {
     Float(f32),
     Double(f64),
     Boolean(bool),
-    String(String), // Max 255 UTF-8 characters
+    String(String), // Max 255 UTF-8 bytes
+    /// Explicit null of a nullable field, a missing field reads as null too
+    Null,
 }
 
 impl Value {
@@ -65,11 +77,12 @@ impl Value {
             Value::Double(_) => TYPE_DOUBLE_ID,
             Value::Boolean(_) => TYPE_BOOLEAN_ID,
             Value::String(_) => TYPE_STRING_ID,
+            Value::Null => TYPE_NULL_ID,
         }
     }
 
     /**
-     * Get the size of the value.
+     * Get the largest serialized size of a value of this type.
      */
     pub fn type_size(&self) -> usize {
         match self {
@@ -81,6 +94,7 @@ impl Value {
             Value::Double(_) => TYPE_DOUBLE_SIZE,
             Value::Boolean(_) => TYPE_BOOLEAN_SIZE,
             Value::String(_) => TYPE_STRING_SIZE,
+            Value::Null => TYPE_NULL_SIZE,
         }
     }
 
@@ -97,14 +111,15 @@ impl Value {
             Value::Double(_) => TYPE_DOUBLE_NAME,
             Value::Boolean(_) => TYPE_BOOLEAN_NAME,
             Value::String(_) => TYPE_STRING_NAME,
+            Value::Null => TYPE_NULL_NAME,
         }
     }
 
     /**
-     * Serialize the value to a byte array.
+     * Serialize the value to a byte array, fails for strings over `MAX_STRING_BYTES`.
      */
-    pub fn serialize(&self) -> Vec<u8> {
-        match self {
+    pub fn serialize(&self) -> Result<Vec<u8>, DatabaseError> {
+        Ok(match self {
             Value::Byte(value) => serialize_byte(*value),
             Value::Short(value) => serialize_short(*value),
             Value::Int(value) => serialize_int(*value),
@@ -112,8 +127,9 @@ impl Value {
             Value::Float(value) => serialize_float(*value),
             Value::Double(value) => serialize_double(*value),
             Value::Boolean(value) => serialize_boolean(*value),
-            Value::String(value) => serialize_string(value),
-        }
+            Value::String(value) => serialize_string(value)?,
+            Value::Null => vec![TYPE_NULL_ID],
+        })
     }
 
     pub fn deserialize(bytes: &[u8]) -> Result<(Value, usize), DatabaseError> {
@@ -130,6 +146,7 @@ impl Value {
             TYPE_DOUBLE_ID => deserialize_double(bytes),
             TYPE_BOOLEAN_ID => deserialize_boolean(bytes),
             TYPE_STRING_ID => deserialize_string(bytes),
+            TYPE_NULL_ID => Ok((Value::Null, TYPE_NULL_SIZE)),
             _ => Err(DatabaseError::InvalidData(format!(
                 "Unknown type tag: {}",
                 bytes[0]
@@ -187,17 +204,19 @@ fn serialize_boolean(value: bool) -> Vec<u8> {
 }
 
 #[inline]
-fn serialize_string(value: &str) -> Vec<u8> {
-    if value.chars().count() > 255 {
-        panic!(
-            "String too long: {} characters (max 255)",
-            value.chars().count()
-        );
+fn serialize_string(value: &str) -> Result<Vec<u8>, DatabaseError> {
+    // Schema validation rejects or truncates long strings before documents are stored
+    if value.len() > MAX_STRING_BYTES {
+        return Err(DatabaseError::InvalidData(format!(
+            "String value is {} bytes long (max {})",
+            value.len(),
+            MAX_STRING_BYTES
+        )));
     }
     let utf8_bytes = value.as_bytes();
     let mut bytes = vec![TYPE_STRING_ID, utf8_bytes.len() as u8];
     bytes.extend_from_slice(utf8_bytes);
-    bytes
+    Ok(bytes)
 }
 
 /**
@@ -284,7 +303,7 @@ fn deserialize_boolean(bytes: &[u8]) -> Result<(Value, usize), DatabaseError> {
 
 #[inline]
 fn deserialize_string(bytes: &[u8]) -> Result<(Value, usize), DatabaseError> {
-    if bytes.len() < TYPE_STRING_SIZE {
+    if bytes.len() < 2 {
         return Err(DatabaseError::InvalidData(
             "Incomplete string length".to_string(),
         ));
@@ -300,3 +319,38 @@ fn deserialize_string(bytes: &[u8]) -> Result<(Value, usize), DatabaseError> {
         .map_err(|e| DatabaseError::InvalidData(format!("Invalid UTF-8: {}", e)))?;
     Ok((Value::String(value), 2 + len))
 }
+
+/**
+ * Truncate the string to at most `max_bytes` without splitting a character.
+ */
+pub fn truncate_to_bytes(value: &mut String, max_bytes: usize) {
+    if value.len() <= max_bytes {
+        return;
+    }
+
+    let mut end = max_bytes;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    value.truncate(end);
+}
+
+/**
+ * Literal syntax of the value, strings are quoted and escaped.
+ */
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Byte(value) => write!(f, "{}", value),
+            Value::Short(value) => write!(f, "{}", value),
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Long(value) => write!(f, "{}", value),
+            // Debug output keeps the decimal point of whole numbers
+            Value::Float(value) => write!(f, "{:?}", value),
+            Value::Double(value) => write!(f, "{:?}", value),
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{:?}", value),
+            Value::Null => write!(f, "null"),
+        }
+    }
+}
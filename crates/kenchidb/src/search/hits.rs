@@ -0,0 +1,33 @@
+use crate::{common::DatabaseError, database::Collection, schema::Document};
+
+/// Document matching a full-text search
+#[derive(Debug, Clone, Copy)]
+pub struct SearchHit<'a> {
+    pub document: &'a Document,
+    /// BM25 relevance, higher is better
+    pub score: f64,
+}
+
+impl Collection {
+    /// Documents whose text fields contain any term of the text, best matches first.
+    /// Documents matching more terms, rarer terms or a term more often rank higher,
+    /// e.g. `search("alice johnson")` ranks "Alice Johnson" above "Alice Cooper".
+    pub fn search(&self, text: &str) -> Result<Vec<SearchHit<'_>>, DatabaseError> {
+        let Some(index) = &self.text_index else {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "Collection '{}' has no text fields to search",
+                self.schema.name
+            )));
+        };
+
+        // Expired documents stay indexed until they are purged
+        Ok(index
+            .search(text)
+            .into_iter()
+            .filter_map(|(id, score)| {
+                self.find_by_id(id)
+                    .map(|document| SearchHit { document, score })
+            })
+            .collect())
+    }
+}
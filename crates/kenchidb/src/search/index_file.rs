@@ -0,0 +1,45 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    common::DatabaseError,
+    query::ByteReader,
+    search::TextIndex,
+    storage::index_blob::{load_index_blob, save_index_blob},
+};
+
+/// Version of the text index file layout
+const TEXT_INDEX_VERSION: u32 = 1;
+
+/// Text index file of the collection stored at the path, `<collection file>.fts`
+pub fn text_index_path(collection_path: &Path) -> PathBuf {
+    let mut path = collection_path.as_os_str().to_owned();
+    path.push(".fts");
+    PathBuf::from(path)
+}
+
+/// Write the index into a fresh file of index pages, see `save_index_blob` for the layout
+pub fn save_text_index(
+    index: &TextIndex,
+    path: &Path,
+    data_checksum: u32,
+) -> Result<(), DatabaseError> {
+    let mut body = Vec::new();
+    index.serialize(&mut body);
+    save_index_blob(path, TEXT_INDEX_VERSION, &body, data_checksum)
+}
+
+/// Read an index written by `save_text_index`.
+/// Returns `None` if the file is missing or was built from other collection data,
+/// the caller then rebuilds the index from the documents.
+pub fn load_text_index(
+    path: &Path,
+    fields: Vec<String>,
+    data_checksum: u32,
+) -> Result<Option<TextIndex>, DatabaseError> {
+    match load_index_blob(path, TEXT_INDEX_VERSION, "text index")? {
+        Some(blob) if blob.data_checksum == data_checksum => {
+            TextIndex::deserialize(&mut ByteReader::new(&blob.body), fields).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::{
+    common::DatabaseError,
+    query::{ByteReader, encode_string},
+    schema::{Document, Schema, Value},
+    search::tokenize,
+};
+
+/// Term frequency saturation of the BM25 ranking
+const BM25_K1: f64 = 1.2;
+
+/// Document length normalization of the BM25 ranking
+const BM25_B: f64 = 0.75;
+
+/// Inverted index over the text fields of a collection.
+/// Every term maps to the documents containing it and how often it occurs in each,
+/// the text fields of a document are indexed as a single text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextIndex {
+    pub fields: Vec<String>,
+    /// term -> document id -> term frequency
+    postings: BTreeMap<String, BTreeMap<u64, u32>>,
+    /// document id -> number of terms, documents without terms are not indexed
+    lengths: HashMap<u64, u32>,
+    total_length: u64,
+}
+
+impl TextIndex {
+    pub fn new(fields: Vec<String>) -> Self {
+        Self {
+            fields,
+            postings: BTreeMap::new(),
+            lengths: HashMap::new(),
+            total_length: 0,
+        }
+    }
+
+    /// Index of the schema's text fields, `None` if it declares none
+    pub fn for_schema(schema: &Schema) -> Option<Self> {
+        if schema.text_fields.is_empty() {
+            return None;
+        }
+        Some(Self::new(schema.text_fields.clone()))
+    }
+
+    /// Number of indexed documents
+    pub fn document_count(&self) -> usize {
+        self.lengths.len()
+    }
+
+    /// Number of distinct terms
+    pub fn term_count(&self) -> usize {
+        self.postings.len()
+    }
+
+    /// Index the document, replacing the terms of its previous version
+    pub fn insert(&mut self, document: &Document) {
+        self.remove(document.id);
+
+        let mut frequencies: BTreeMap<String, u32> = BTreeMap::new();
+        for field in &self.fields {
+            if let Some(Value::String(text)) = document.get(field) {
+                for term in tokenize(text) {
+                    *frequencies.entry(term).or_default() += 1;
+                }
+            }
+        }
+
+        let length: u32 = frequencies.values().sum();
+        if length == 0 {
+            return;
+        }
+
+        for (term, frequency) in frequencies {
+            self.postings
+                .entry(term)
+                .or_default()
+                .insert(document.id, frequency);
+        }
+        self.lengths.insert(document.id, length);
+        self.total_length += length as u64;
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        let Some(length) = self.lengths.remove(&id) else {
+            return;
+        };
+        self.total_length -= length as u64;
+
+        self.postings.retain(|_, documents| {
+            documents.remove(&id);
+            !documents.is_empty()
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.postings.clear();
+        self.lengths.clear();
+        self.total_length = 0;
+    }
+
+    /// Documents containing any term of the text with their BM25 score,
+    /// best matches first and ties in id order
+    pub fn search(&self, text: &str) -> Vec<(u64, f64)> {
+        let terms: BTreeSet<String> = tokenize(text).into_iter().collect();
+        let document_count = self.lengths.len() as f64;
+        let average_length = self.total_length as f64 / document_count.max(1.0);
+
+        let mut scores: HashMap<u64, f64> = HashMap::new();
+        for term in &terms {
+            let Some(documents) = self.postings.get(term) else {
+                continue;
+            };
+
+            // Rare terms weigh more, the +1 keeps the weight of very common terms positive
+            let frequency = documents.len() as f64;
+            let idf = (1.0 + (document_count - frequency + 0.5) / (frequency + 0.5)).ln();
+
+            for (id, &term_frequency) in documents {
+                let term_frequency = term_frequency as f64;
+                let length = self.lengths[id] as f64;
+                let normalization = 1.0 - BM25_B + BM25_B * length / average_length;
+                *scores.entry(*id).or_default() += idf * term_frequency * (BM25_K1 + 1.0)
+                    / (term_frequency + BM25_K1 * normalization);
+            }
+        }
+
+        let mut ranked: Vec<(u64, f64)> = scores.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked
+    }
+
+    pub fn serialize(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&(self.lengths.len() as u32).to_le_bytes());
+        let mut lengths: Vec<_> = self.lengths.iter().collect();
+        lengths.sort();
+        for (id, length) in lengths {
+            bytes.extend_from_slice(&id.to_le_bytes());
+            bytes.extend_from_slice(&length.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&(self.postings.len() as u32).to_le_bytes());
+        for (term, documents) in &self.postings {
+            encode_string(term, bytes);
+            bytes.extend_from_slice(&(documents.len() as u32).to_le_bytes());
+            for (id, frequency) in documents {
+                bytes.extend_from_slice(&id.to_le_bytes());
+                bytes.extend_from_slice(&frequency.to_le_bytes());
+            }
+        }
+    }
+
+    pub fn deserialize(
+        reader: &mut ByteReader,
+        fields: Vec<String>,
+    ) -> Result<Self, DatabaseError> {
+        let mut index = Self::new(fields);
+
+        for _ in 0..reader.read_u32()? {
+            let id = reader.read_u64()?;
+            let length = reader.read_u32()?;
+            index.lengths.insert(id, length);
+            index.total_length += length as u64;
+        }
+
+        for _ in 0..reader.read_u32()? {
+            let term = reader.read_string()?;
+            let mut documents = BTreeMap::new();
+            for _ in 0..reader.read_u32()? {
+                let id = reader.read_u64()?;
+                if !index.lengths.contains_key(&id) {
+                    return Err(DatabaseError::InvalidData(format!(
+                        "Posting of term '{}' refers to unknown document {}",
+                        term, id
+                    )));
+                }
+                documents.insert(id, reader.read_u32()?);
+            }
+            index.postings.insert(term, documents);
+        }
+
+        Ok(index)
+    }
+}
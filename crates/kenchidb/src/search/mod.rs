@@ -0,0 +1,9 @@
+mod hits;
+mod index_file;
+mod inverted_index;
+mod tokenizer;
+
+pub(crate) use self::hits::*;
+pub(crate) use self::index_file::*;
+pub(crate) use self::inverted_index::*;
+pub(crate) use self::tokenizer::*;
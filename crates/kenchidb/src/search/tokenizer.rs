@@ -0,0 +1,8 @@
+/// Split text into lowercase terms at every character that is not a letter or a digit,
+/// e.g. "Alice O'Neil-Johnson" yields `alice`, `o`, `neil` and `johnson`
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
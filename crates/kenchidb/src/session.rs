@@ -0,0 +1,522 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::{
+    common::DatabaseError,
+    database::{Collection, KeyChanges, assign_sequences},
+    index::SecondaryIndex,
+    query::FindQuery,
+    schema::{Document, Schema, TtlPolicy, current_time_millis},
+    storage::wal::WalRecord,
+    watch::ChangeEvent,
+};
+
+/// Read-only view of a collection at the moment it was taken.
+/// Writes to the collection after that moment are never visible through the snapshot,
+/// take a new snapshot to see them.
+///
+/// The snapshot shares the documents of the collection instead of copying them, the
+/// collection copies them on its next write if the snapshot is still alive by then.
+/// Snapshots have no indexes, queries scan the documents.
+pub struct Snapshot {
+    pub schema: Schema,
+    /// Version of the collection when the snapshot was taken
+    pub version: u64,
+    ttl: Option<TtlPolicy>,
+    documents: Arc<HashMap<u64, Document>>,
+}
+
+impl Snapshot {
+    pub fn find_by_id(&self, id: u64) -> Option<&Document> {
+        let now = current_time_millis();
+        self.documents
+            .get(&id)
+            .filter(|document| !self.is_expired(document, now))
+    }
+
+    pub fn find_all(&self) -> Vec<&Document> {
+        let now = current_time_millis();
+        self.documents
+            .values()
+            .filter(|document| !self.is_expired(document, now))
+            .collect()
+    }
+
+    pub fn find_where<Q: Into<FindQuery>>(
+        &self,
+        query: Q,
+    ) -> Result<Vec<&Document>, DatabaseError> {
+        let query = query.into();
+        query.validate(&self.schema)?;
+        Ok(query.execute(self.find_all().into_iter()))
+    }
+
+    fn is_expired(&self, document: &Document, now: i64) -> bool {
+        self.ttl
+            .as_ref()
+            .is_some_and(|ttl| ttl.is_expired(document, now))
+    }
+}
+
+/// Write recorded by a session, replayed against the collection on commit
+#[derive(Debug, Clone)]
+enum SessionWrite {
+    /// Provisional id and the document as it was passed to the session
+    Insert(u64, Document),
+    Update(u64, Document),
+    /// Update checked against the version on commit
+    UpdateIfVersion(u64, u64, Document),
+    Delete(u64),
+}
+
+/// Writes of a session checked against the collection, ready to be applied
+pub(crate) struct StagedCommit {
+    /// Documents to store with their final ids and versions
+    documents: Vec<Document>,
+    removed: HashSet<u64>,
+    keys: KeyChanges,
+    next_id: u64,
+    sequences: HashMap<String, i64>,
+    /// Records for the write-ahead log, `None` if the collection does not log its writes
+    pub(crate) records: Option<Vec<WalRecord>>,
+    pub(crate) inserted: Vec<u64>,
+    /// Final ids of the written documents in write order
+    touched: Vec<u64>,
+}
+
+impl StagedCommit {
+    /// Prepare record of the transaction with the id and sequence counters after the commit
+    pub(crate) fn prepare_record(&self, transaction: u64) -> WalRecord {
+        WalRecord::Prepare {
+            transaction,
+            next_id: self.next_id,
+            sequences: self
+                .sequences
+                .iter()
+                .map(|(field, next)| (field.clone(), *next))
+                .collect(),
+        }
+    }
+}
+
+/// Unit of work on a collection with read-your-writes semantics.
+///
+/// Visibility rules:
+/// - Reads of the session see the collection as it was when the session started,
+///   plus the session's own writes in the order they were made.
+/// - Writes of the session are invisible to the collection, its snapshots and other
+///   sessions until `Collection::commit`, dropping the session discards them.
+/// - A commit applies all writes of the session or none of them. Reads of the collection
+///   and snapshots or sessions started after the commit see all of them.
+/// - Writes committed by others after the session started are not visible to the session.
+///   The session's writes are applied on top of them, the last committed write of a
+///   document wins. Updates made with `update_if_version` fail the commit instead.
+///
+/// The session reads from a `Snapshot` of the collection and keeps its own writes next
+/// to it, nothing is copied when it starts. Primary keys and unique index values are
+/// checked against the session's own writes as they are made, and against the collection
+/// on commit.
+///
+/// Ids and auto increment values of documents inserted in a session are provisional,
+/// they are assigned again on commit and only differ if another commit inserted in between.
+/// `Collection::commit` returns the final ids.
+pub struct Session {
+    base: Snapshot,
+    /// Documents written by the session by id, `None` for deleted ones
+    written: HashMap<u64, Option<Document>>,
+    /// Primary keys of the written documents
+    keys: HashMap<Vec<u8>, u64>,
+    /// Unique indexes of the collection over the written documents
+    unique: Vec<SecondaryIndex>,
+    next_id: u64,
+    sequences: HashMap<String, i64>,
+    writes: Vec<SessionWrite>,
+}
+
+impl Session {
+    /// Insert a document, returns its provisional id
+    pub fn insert(&mut self, document: Document) -> Result<u64, DatabaseError> {
+        let id = self.next_id;
+        let mut stored = document.clone();
+        stored.id = id;
+        let sequences = assign_sequences(
+            &self.base.schema,
+            &self.sequences,
+            &self.base.documents,
+            &mut stored,
+        )?;
+
+        self.store(stored)?;
+        self.sequences.extend(sequences);
+        self.next_id += 1;
+        self.writes.push(SessionWrite::Insert(id, document));
+        Ok(id)
+    }
+
+    pub fn update(&mut self, id: u64, document: Document) -> Result<(), DatabaseError> {
+        self.replace(id, document.clone())?;
+        self.writes.push(SessionWrite::Update(id, document));
+        Ok(())
+    }
+
+    /// Update the document if it is still at the expected version, checked again on commit
+    /// against the collection, see `Collection::update_if_version`
+    pub fn update_if_version(
+        &mut self,
+        id: u64,
+        expected_version: u64,
+        document: Document,
+    ) -> Result<(), DatabaseError> {
+        let Some(stored) = self.stored(id) else {
+            return Err(DatabaseError::DocumentNotFound(id));
+        };
+        if stored.version != expected_version {
+            return Err(DatabaseError::Conflict {
+                id,
+                expected: expected_version,
+                actual: stored.version,
+            });
+        }
+
+        self.replace(id, document.clone())?;
+        self.writes.push(SessionWrite::UpdateIfVersion(
+            id,
+            expected_version,
+            document,
+        ));
+        Ok(())
+    }
+
+    pub fn delete(&mut self, id: u64) -> Result<(), DatabaseError> {
+        if self.stored(id).is_none() {
+            return Err(DatabaseError::DocumentNotFound(id));
+        }
+
+        self.unindex(id);
+        self.written.insert(id, None);
+        self.writes.push(SessionWrite::Delete(id));
+        Ok(())
+    }
+
+    /// Number of writes waiting for the commit
+    pub fn pending_writes(&self) -> usize {
+        self.writes.len()
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.base.schema
+    }
+
+    pub fn find_by_id(&self, id: u64) -> Option<&Document> {
+        let now = current_time_millis();
+        match self.written.get(&id) {
+            Some(written) => written
+                .as_ref()
+                .filter(|document| !self.base.is_expired(document, now)),
+            None => self.base.find_by_id(id),
+        }
+    }
+
+    pub fn find_all(&self) -> Vec<&Document> {
+        self.live_documents().collect()
+    }
+
+    /// Documents matching the query. Sessions have no indexes, the documents are scanned.
+    pub fn find_where<Q: Into<FindQuery>>(
+        &self,
+        query: Q,
+    ) -> Result<Vec<&Document>, DatabaseError> {
+        let query = query.into();
+        query.validate(&self.base.schema)?;
+        Ok(query.execute(self.live_documents()))
+    }
+
+    /// Documents of the base the session did not write, followed by the ones it did,
+    /// without the expired ones
+    fn live_documents(&self) -> impl Iterator<Item = &Document> {
+        let now = current_time_millis();
+        let unwritten = self
+            .base
+            .documents
+            .values()
+            .filter(|document| !self.written.contains_key(&document.id));
+        unwritten
+            .chain(self.written.values().flatten())
+            .filter(move |document| !self.base.is_expired(document, now))
+    }
+
+    /// Document as the session sees it, expired or not
+    fn stored(&self, id: u64) -> Option<&Document> {
+        match self.written.get(&id) {
+            Some(written) => written.as_ref(),
+            None => self.base.documents.get(&id),
+        }
+    }
+
+    fn replace(&mut self, id: u64, mut document: Document) -> Result<(), DatabaseError> {
+        if self.stored(id).is_none() {
+            return Err(DatabaseError::DocumentNotFound(id));
+        }
+        document.id = id;
+        self.store(document)
+    }
+
+    /// Write the document as it will be committed, unless its primary key or a unique
+    /// index value collides with another document written by the session
+    fn store(&mut self, mut document: Document) -> Result<(), DatabaseError> {
+        let schema = &self.base.schema;
+        schema.normalize_document(&mut document);
+        schema.validate_document(&document)?;
+        document.version = match self.base.documents.get(&document.id) {
+            Some(stored) => stored.version + 1,
+            None => 1,
+        };
+
+        let key = schema.primary_key_of(&document)?;
+        let key_collides = key
+            .as_ref()
+            .and_then(|key| self.keys.get(key))
+            .is_some_and(|id| *id != document.id);
+        if key_collides {
+            return Err(DatabaseError::DuplicateKey(format!(
+                "Duplicate primary key in collection '{}' for document {}",
+                schema.name, document.id
+            )));
+        }
+        if let Some(index) = self
+            .unique
+            .iter()
+            .find(|index| index.conflict(&document).is_some())
+        {
+            return Err(DatabaseError::DuplicateKey(format!(
+                "Duplicate value of unique index '{}' in collection '{}' for document {}",
+                index.field, schema.name, document.id
+            )));
+        }
+
+        self.unindex(document.id);
+        if let Some(key) = key {
+            self.keys.insert(key, document.id);
+        }
+        for index in &mut self.unique {
+            index.insert(&document);
+        }
+        self.written.insert(document.id, Some(document));
+        Ok(())
+    }
+
+    /// Drop the keys and unique index values of the session's write of the document
+    fn unindex(&mut self, id: u64) {
+        let Some(Some(document)) = self.written.get(&id) else {
+            return;
+        };
+        self.keys.retain(|_, written| *written != id);
+        for index in &mut self.unique {
+            index.remove(document);
+        }
+    }
+}
+
+impl Collection {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            schema: self.schema.clone(),
+            version: self.version,
+            ttl: self.ttl.clone(),
+            documents: Arc::clone(&self.documents),
+        }
+    }
+
+    /// Start a session reading from a snapshot of the collection, see `Session`
+    pub fn session(&self) -> Session {
+        Session {
+            base: self.snapshot(),
+            written: HashMap::new(),
+            keys: HashMap::new(),
+            unique: self
+                .indexes
+                .values()
+                .filter(|index| index.options.unique)
+                .map(|index| SecondaryIndex::new(index.field.clone(), index.options))
+                .collect(),
+            next_id: self.next_id,
+            sequences: self.sequences.clone(),
+            writes: Vec::new(),
+        }
+    }
+
+    /// Apply the writes of the session atomically, returns the final ids of the inserted
+    /// documents in insert order. Nothing is applied if any write fails, e.g. an update
+    /// of a document deleted by a commit made after the session started.
+    pub fn commit(&mut self, session: Session) -> Result<Vec<u64>, DatabaseError> {
+        let staged = self.stage(session)?;
+        let inserted = self.apply(staged);
+        self.persist()?;
+        Ok(inserted)
+    }
+
+    /// Check the writes of the session against the collection without changing it,
+    /// see `Collection::commit`. Documents the session updated with `update_if_version`
+    /// must still be at the version they had when the session started.
+    pub(crate) fn stage(&self, session: Session) -> Result<StagedCommit, DatabaseError> {
+        self.check_not_prepared()?;
+        let Session { base, writes, .. } = session;
+        let mut next_id = self.next_id;
+        let mut sequences = self.sequences.clone();
+        // provisional id -> final id
+        let mut ids: HashMap<u64, u64> = HashMap::new();
+        let mut written: HashMap<u64, Option<Document>> = HashMap::new();
+        let mut inserted = Vec::new();
+        let mut touched = Vec::new();
+
+        let exists = |written: &HashMap<u64, Option<Document>>, id: u64| match written.get(&id) {
+            Some(document) => document.is_some(),
+            None => self.documents.contains_key(&id),
+        };
+
+        for write in writes {
+            let (id, document) = match write {
+                SessionWrite::Insert(provisional, mut document) => {
+                    document.id = next_id;
+                    let advanced =
+                        assign_sequences(&self.schema, &sequences, &self.documents, &mut document)?;
+                    sequences.extend(advanced);
+                    ids.insert(provisional, next_id);
+                    inserted.push(next_id);
+                    next_id += 1;
+                    (next_id - 1, Some(document))
+                }
+                SessionWrite::Update(id, document) => {
+                    let id = *ids.get(&id).unwrap_or(&id);
+                    if !exists(&written, id) {
+                        return Err(DatabaseError::DocumentNotFound(id));
+                    }
+                    (id, Some(document))
+                }
+                SessionWrite::UpdateIfVersion(id, expected_version, document) => {
+                    let id = *ids.get(&id).unwrap_or(&id);
+                    if !exists(&written, id) {
+                        return Err(DatabaseError::DocumentNotFound(id));
+                    }
+                    // Documents inserted by the session were checked by the session
+                    if let (Some(stored), Some(read)) =
+                        (self.documents.get(&id), base.documents.get(&id))
+                        && stored.version != read.version
+                    {
+                        return Err(DatabaseError::Conflict {
+                            id,
+                            expected: expected_version,
+                            actual: stored.version,
+                        });
+                    }
+                    (id, Some(document))
+                }
+                SessionWrite::Delete(id) => {
+                    let id = *ids.get(&id).unwrap_or(&id);
+                    if !exists(&written, id) {
+                        return Err(DatabaseError::DocumentNotFound(id));
+                    }
+                    (id, None)
+                }
+            };
+
+            let document = match document {
+                Some(mut document) => {
+                    document.id = id;
+                    self.schema.normalize_document(&mut document);
+                    self.schema.validate_document(&document)?;
+                    Some(document)
+                }
+                None => None,
+            };
+            written.insert(id, document);
+            touched.push(id);
+        }
+        // The collection no longer shares its documents with the session
+        drop(base);
+
+        let mut documents = Vec::new();
+        let mut removed = HashSet::new();
+        for id in &touched {
+            match written.remove(id) {
+                Some(Some(mut document)) => {
+                    document.version = self.next_version(*id);
+                    documents.push(document);
+                }
+                Some(None) if self.documents.contains_key(id) => {
+                    removed.insert(*id);
+                }
+                _ => {}
+            }
+        }
+
+        let keys = self.check_keys(&documents, &removed)?;
+        let records = self.pending_writes.as_ref().map(|_| {
+            removed
+                .iter()
+                .map(|id| WalRecord::Delete(*id))
+                .chain(documents.iter().cloned().map(WalRecord::Put))
+                .collect()
+        });
+
+        Ok(StagedCommit {
+            documents,
+            removed,
+            keys,
+            next_id,
+            sequences,
+            records,
+            inserted,
+            touched,
+        })
+    }
+
+    /// Make the staged writes visible, returns the final ids of the inserted documents.
+    /// The writes are queued for the write-ahead log, see `Collection::persist`.
+    pub(crate) fn apply(&mut self, commit: StagedCommit) -> Vec<u64> {
+        let StagedCommit {
+            documents,
+            removed,
+            keys,
+            next_id,
+            sequences,
+            records,
+            inserted,
+            touched,
+        } = commit;
+
+        // One event per changed document, with its state before and after the commit
+        let mut events = Vec::new();
+        if self.is_watched() {
+            let after: HashMap<u64, &Document> = documents
+                .iter()
+                .map(|document| (document.id, document))
+                .collect();
+            let mut seen = HashSet::new();
+            for id in touched.into_iter().filter(|id| seen.insert(*id)) {
+                let before = self.documents.get(&id).cloned();
+                let after = after.get(&id).map(|document| (*document).clone());
+                match (before, after) {
+                    (None, Some(after)) => events.push(ChangeEvent::Insert(after)),
+                    (Some(before), Some(after)) => {
+                        events.push(ChangeEvent::Update { before, after })
+                    }
+                    (Some(before), None) => events.push(ChangeEvent::Delete(before)),
+                    (None, None) => {}
+                }
+            }
+        }
+
+        self.write_documents(documents, &removed, keys);
+        self.next_id = next_id;
+        self.sequences = sequences;
+        if let (Some(pending), Some(records)) = (&mut self.pending_writes, records) {
+            pending.extend(records);
+        }
+        self.version += 1;
+        self.notify(events);
+        inserted
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    io,
+    path::PathBuf,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use crate::{
+    common::DatabaseError,
+    schema::{Document, Schema},
+    storage::{
+        file_manager::FileManager,
+        page::{Page, PageType},
+        paged_collection::PagedCollection,
+    },
+};
+
+/// Run `f` on the shared value on tokio's blocking thread pool, so the file I/O it does
+/// never blocks an executor thread. Calls on the same value run one after another.
+async fn run_blocking<T, R, F>(shared: &Arc<Mutex<T>>, f: F) -> Result<R, DatabaseError>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: FnOnce(&mut T) -> Result<R, DatabaseError> + Send + 'static,
+{
+    let shared = Arc::clone(shared);
+    let task = tokio::task::spawn_blocking(move || {
+        // A panic in another call leaves the value as consistent as a failed call does
+        let mut value = shared.lock().unwrap_or_else(PoisonError::into_inner);
+        f(&mut value)
+    });
+    match task.await {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => Err(DatabaseError::IoError(io::Error::other(e))),
+    }
+}
+
+/// Page file for async code, see `FileManager`. Clones are cheap and share the file.
+#[derive(Clone)]
+pub struct AsyncFileManager {
+    file_manager: Arc<Mutex<FileManager>>,
+}
+
+impl AsyncFileManager {
+    pub async fn open<P: Into<PathBuf>>(path: P) -> Result<Self, DatabaseError> {
+        let path = path.into();
+        let file_manager = tokio::task::spawn_blocking(move || FileManager::new(path))
+            .await
+            .map_err(|e| DatabaseError::IoError(io::Error::other(e)))??;
+        Ok(Self::new(file_manager))
+    }
+
+    pub fn new(file_manager: FileManager) -> Self {
+        Self {
+            file_manager: Arc::new(Mutex::new(file_manager)),
+        }
+    }
+
+    pub async fn read_page(&self, page_id: u32) -> Result<Page, DatabaseError> {
+        run_blocking(&self.file_manager, move |pages| pages.read_page(page_id)).await
+    }
+
+    /// Write the page, see `FileManager::write_page`. Returns the page with its checksum updated.
+    pub async fn write_page(&self, page_id: u32, mut page: Page) -> Result<Page, DatabaseError> {
+        run_blocking(&self.file_manager, move |pages| {
+            pages.write_page(page_id, &mut page)?;
+            Ok(page)
+        })
+        .await
+    }
+
+    pub async fn allocate_page(
+        &self,
+        page_type: PageType,
+        collection_id: u32,
+    ) -> Result<(u32, Page), DatabaseError> {
+        run_blocking(&self.file_manager, move |pages| {
+            pages.allocate_page(page_type, collection_id)
+        })
+        .await
+    }
+
+    pub async fn flush(&self) -> Result<(), DatabaseError> {
+        run_blocking(&self.file_manager, FileManager::flush).await
+    }
+
+    pub async fn page_count(&self) -> Result<u32, DatabaseError> {
+        run_blocking(&self.file_manager, |pages| Ok(pages.page_count())).await
+    }
+}
+
+/// Paged collection for async code, see `PagedCollection`.
+/// Clones are cheap and share the collection, operations on it run one at a time.
+#[derive(Clone)]
+pub struct AsyncPagedCollection {
+    collection: Arc<Mutex<PagedCollection>>,
+}
+
+impl AsyncPagedCollection {
+    /// Create or open the collection file, see `PagedCollection::new`
+    pub async fn open<P: Into<PathBuf>>(
+        schema: Schema,
+        collection_id: u32,
+        path: P,
+    ) -> Result<Self, DatabaseError> {
+        let path = path.into();
+        let collection =
+            tokio::task::spawn_blocking(move || PagedCollection::new(schema, collection_id, path))
+                .await
+                .map_err(|e| DatabaseError::IoError(io::Error::other(e)))??;
+        Ok(Self::new(collection))
+    }
+
+    pub fn new(collection: PagedCollection) -> Self {
+        Self {
+            collection: Arc::new(Mutex::new(collection)),
+        }
+    }
+
+    pub async fn insert(&self, document: Document) -> Result<u64, DatabaseError> {
+        run_blocking(&self.collection, move |collection| {
+            collection.insert(document)
+        })
+        .await
+    }
+
+    pub async fn find_by_id(&self, id: u64) -> Result<Option<Document>, DatabaseError> {
+        run_blocking(&self.collection, move |collection| {
+            collection.find_by_id(id)
+        })
+        .await
+    }
+
+    pub async fn update(&self, id: u64, document: Document) -> Result<(), DatabaseError> {
+        run_blocking(&self.collection, move |collection| {
+            collection.update(id, document)
+        })
+        .await
+    }
+
+    pub async fn delete(&self, id: u64) -> Result<(), DatabaseError> {
+        run_blocking(&self.collection, move |collection| collection.delete(id)).await
+    }
+
+    /// Write the modified pages and save the indexes, see `PagedCollection::flush`
+    pub async fn flush(&self) -> Result<(), DatabaseError> {
+        run_blocking(&self.collection, PagedCollection::flush).await
+    }
+
+    /// Run `f` on the collection on the blocking thread pool,
+    /// e.g. to scan it with `PagedCollection::iter`
+    pub async fn with_collection<R, F>(&self, f: F) -> Result<R, DatabaseError>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut PagedCollection) -> Result<R, DatabaseError> + Send + 'static,
+    {
+        run_blocking(&self.collection, f).await
+    }
+}
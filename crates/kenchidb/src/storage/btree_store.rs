@@ -0,0 +1,83 @@
+use btree::{InvalidNode, NodeStore};
+
+use crate::{
+    common::DatabaseError,
+    storage::{
+        engine::StorageEngine,
+        page::{MAX_PAGE_DATA_SIZE, Page, PageType, SLOT_SIZE},
+    },
+};
+
+impl From<InvalidNode> for DatabaseError {
+    fn from(error: InvalidNode) -> Self {
+        DatabaseError::InvalidData(error.0)
+    }
+}
+
+/// Nodes of a `PersistentBtree` in index pages of a storage engine,
+/// each node is the single record of its page
+pub struct EngineNodeStore<E: StorageEngine> {
+    engine: E,
+    collection_id: u32,
+}
+
+impl<E: StorageEngine> EngineNodeStore<E> {
+    pub fn new(engine: E, collection_id: u32) -> Self {
+        Self {
+            engine,
+            collection_id,
+        }
+    }
+
+    pub fn engine(&self) -> &E {
+        &self.engine
+    }
+
+    pub fn into_engine(self) -> E {
+        self.engine
+    }
+}
+
+impl<E: StorageEngine> NodeStore for EngineNodeStore<E> {
+    type Error = DatabaseError;
+
+    fn node_capacity(&self) -> usize {
+        MAX_PAGE_DATA_SIZE - SLOT_SIZE
+    }
+
+    fn allocate_node(&mut self) -> Result<u32, DatabaseError> {
+        let (page_id, _) = self
+            .engine
+            .allocate_page(PageType::IndexPage, self.collection_id)?;
+        Ok(page_id)
+    }
+
+    fn read_node(&mut self, page: u32) -> Result<Vec<u8>, DatabaseError> {
+        let node_page = self.engine.read_page(page)?;
+        if node_page.header.page_type != PageType::IndexPage {
+            return Err(DatabaseError::InvalidData(format!(
+                "Page {} is not a tree node",
+                page
+            )));
+        }
+        Ok(node_page.get_record(0)?.to_vec())
+    }
+
+    fn write_node(&mut self, page: u32, bytes: &[u8]) -> Result<(), DatabaseError> {
+        let mut node_page = Page::new(PageType::IndexPage, self.collection_id);
+        node_page.insert_record(bytes)?;
+        self.engine.write_page(page, &mut node_page)
+    }
+
+    fn free_node(&mut self, page: u32) -> Result<(), DatabaseError> {
+        // Nodes allocated since the last flush were never written, engines read the
+        // page back when freeing it
+        let mut free = Page::new(PageType::FreePage, self.collection_id);
+        self.engine.write_page(page, &mut free)?;
+        self.engine.free_page(page)
+    }
+
+    fn sync(&mut self) -> Result<(), DatabaseError> {
+        self.engine.sync()
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::storage::page::{Page, PageType};
+
+/// In-memory copies of recently used pages, the least recently used page is evicted first.
+/// Pages are kept decoded, as `FileManager::read_page` returns them, and writes update
+/// the cached copy along with the dirty page so a cached page never differs from its
+/// stored version.
+#[derive(Debug)]
+pub struct BufferPool {
+    capacity: usize,
+    /// page_id -> (page, last use)
+    pages: HashMap<u32, (Page, u64)>,
+    /// last use -> page_id, oldest first
+    recency: BTreeMap<u64, u32>,
+    clock: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl BufferPool {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            pages: HashMap::new(),
+            recency: BTreeMap::new(),
+            clock: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of cached pages
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    pub fn contains(&self, page_id: u32) -> bool {
+        self.pages.contains_key(&page_id)
+    }
+
+    /// Copy of the cached page, counted as a hit or a miss
+    pub fn get(&mut self, page_id: u32) -> Option<Page> {
+        self.clock += 1;
+        let Some((page, last_use)) = self.pages.get_mut(&page_id) else {
+            self.misses += 1;
+            return None;
+        };
+
+        self.recency.remove(last_use);
+        *last_use = self.clock;
+        self.recency.insert(self.clock, page_id);
+        self.hits += 1;
+        Some(page.clone())
+    }
+
+    /// Cache the page as the most recently used one, evicting the least recently used
+    /// page if the pool is full
+    pub fn insert(&mut self, page_id: u32, page: Page) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.clock += 1;
+        if let Some((_, last_use)) = self.pages.remove(&page_id) {
+            self.recency.remove(&last_use);
+        } else if self.pages.len() >= self.capacity
+            && let Some((_, evicted)) = self.recency.pop_first()
+        {
+            self.pages.remove(&evicted);
+        }
+
+        self.pages.insert(page_id, (page, self.clock));
+        self.recency.insert(self.clock, page_id);
+    }
+
+    pub fn remove(&mut self, page_id: u32) {
+        if let Some((_, last_use)) = self.pages.remove(&page_id) {
+            self.recency.remove(&last_use);
+        }
+    }
+
+    /// Drop every cached page, the hit and miss counts are kept
+    pub fn clear(&mut self) {
+        self.pages.clear();
+        self.recency.clear();
+    }
+
+    /// Number of cached pages of the type
+    pub fn count(&self, page_type: PageType) -> usize {
+        self.pages
+            .values()
+            .filter(|(page, _)| page.header.page_type == page_type)
+            .count()
+    }
+}
@@ -0,0 +1,364 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use ::storage::{Durability, sync_parent_directory};
+
+use crate::{
+    common::{DatabaseError, crc32},
+    query::{ByteReader, View, encode_short_string, encode_string},
+    schema::{Field, FieldType, Schema, StringOverflow, TtlPolicy},
+    storage::{
+        file_manager::FileManager,
+        page::{MAX_PAGE_DATA_SIZE, PAGE_SIZE, PageType, SLOT_SIZE},
+        recovery::PageCheck,
+    },
+};
+
+/// Layout version of the catalog file, stored in its header page.
+/// Version 1 catalogs have no TTL policies.
+pub const CATALOG_FORMAT_VERSION: u32 = 2;
+
+/// Bytes of the serialized catalog stored in each page
+const CATALOG_CHUNK_SIZE: usize = MAX_PAGE_DATA_SIZE - SLOT_SIZE;
+
+/// Collection with a backing file recorded in the catalog
+#[derive(Debug, Clone)]
+pub struct CatalogEntry {
+    pub name: String,
+    /// Assigned when the collection is first created, never reused
+    pub id: u32,
+    pub path: PathBuf,
+    pub schema: Schema,
+    /// Next document id when the catalog was last saved, ids below it are never handed out
+    /// again even if the collection file is replaced by an older copy
+    pub next_id: u64,
+    /// Applied to the collection again when the database is opened
+    pub ttl: Option<TtlPolicy>,
+}
+
+/// Collections of a database, enough to open them again with `Database::open`
+#[derive(Debug, Clone)]
+pub struct Catalog {
+    pub next_collection_id: u32,
+    pub collections: Vec<CatalogEntry>,
+}
+
+impl Default for Catalog {
+    fn default() -> Self {
+        Self {
+            next_collection_id: 1,
+            collections: Vec::new(),
+        }
+    }
+}
+
+impl Catalog {
+    pub fn entry(&self, name: &str) -> Option<&CatalogEntry> {
+        self.collections.iter().find(|entry| entry.name == name)
+    }
+
+    /// Record the collection file, keeping the id of a collection already in the catalog
+    pub fn register(&mut self, name: &str, path: &Path, schema: &Schema, next_id: u64) {
+        if let Some(entry) = self.collections.iter_mut().find(|entry| entry.name == name) {
+            entry.path = path.to_path_buf();
+            entry.schema = schema.clone();
+            entry.next_id = entry.next_id.max(next_id);
+            return;
+        }
+
+        self.collections.push(CatalogEntry {
+            name: name.to_string(),
+            id: self.next_collection_id,
+            path: path.to_path_buf(),
+            schema: schema.clone(),
+            next_id,
+            ttl: None,
+        });
+        self.next_collection_id += 1;
+    }
+
+    /// Record the TTL policy of a collection in the catalog, `None` clears it
+    pub fn set_ttl(&mut self, name: &str, ttl: Option<TtlPolicy>) {
+        if let Some(entry) = self.collections.iter_mut().find(|entry| entry.name == name) {
+            entry.ttl = ttl;
+        }
+    }
+}
+
+/// Write the catalog and the views into a fresh file of pages. Page 0 is a header page
+/// whose record starts with the format version, the page size, the body length and its
+/// checksum, the rest of the body follows in meta pages.
+/// The file is written next to the target and renamed over it, a crash keeps the previous file.
+pub fn save_catalog(path: &Path, catalog: &Catalog, views: &[&View]) -> Result<(), DatabaseError> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&catalog.next_collection_id.to_le_bytes());
+    body.extend_from_slice(&(catalog.collections.len() as u32).to_le_bytes());
+    for entry in &catalog.collections {
+        encode_short_string(&entry.name, &mut body);
+        body.extend_from_slice(&entry.id.to_le_bytes());
+        encode_string(&entry.path.to_string_lossy(), &mut body);
+        encode_schema(&entry.schema, &mut body);
+        body.extend_from_slice(&entry.next_id.to_le_bytes());
+        match &entry.ttl {
+            Some(ttl) => {
+                body.push(1);
+                encode_short_string(&ttl.field, &mut body);
+                let expire_after = ttl.expire_after.as_millis().min(u64::MAX as u128) as u64;
+                body.extend_from_slice(&expire_after.to_le_bytes());
+            }
+            None => body.push(0),
+        }
+    }
+    body.extend_from_slice(&(views.len() as u32).to_le_bytes());
+    for view in views {
+        view.serialize(&mut body)?;
+    }
+
+    let mut bytes = Vec::with_capacity(16 + body.len());
+    bytes.extend_from_slice(&CATALOG_FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(PAGE_SIZE as u32).to_le_bytes());
+    bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&crc32(&body).to_le_bytes());
+    bytes.extend_from_slice(&body);
+
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    if temporary.exists() {
+        fs::remove_file(&temporary)?;
+    }
+
+    let mut file_manager = FileManager::new(&temporary)?;
+    for (index, chunk) in bytes.chunks(CATALOG_CHUNK_SIZE).enumerate() {
+        let page_type = match index {
+            0 => PageType::HeaderPage,
+            _ => PageType::MetaPage,
+        };
+        let (page_id, mut page) = file_manager.allocate_page(page_type, 0)?;
+        page.insert_record(chunk)?;
+        file_manager.write_page(page_id, &mut page)?;
+    }
+    file_manager.flush()?;
+    drop(file_manager);
+
+    fs::rename(&temporary, path)?;
+    sync_parent_directory(path, Durability::Full)?;
+    Ok(())
+}
+
+/// Read a catalog written by `save_catalog` with its views, empty if the file is missing or
+/// empty. Files written before the catalog had a header page hold only views.
+pub fn load_catalog(path: &Path) -> Result<(Catalog, Vec<View>), DatabaseError> {
+    let length = match fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+        Err(e) => return Err(e.into()),
+    };
+    if length == 0 {
+        return Ok((Catalog::default(), Vec::new()));
+    }
+    if !length.is_multiple_of(PAGE_SIZE as u64) || !starts_with_header_page(path)? {
+        let bytes = fs::read(path)?;
+        let mut reader = ByteReader::new(&bytes);
+        return Ok((Catalog::default(), decode_views(&mut reader)?));
+    }
+
+    let mut file_manager = FileManager::new(path)?;
+    let mut bytes = Vec::new();
+    for page_id in 0..file_manager.page_count() {
+        let page = file_manager.read_page(page_id)?;
+        let expected = match page_id {
+            0 => PageType::HeaderPage,
+            _ => PageType::MetaPage,
+        };
+        if page.header.page_type != expected {
+            return Err(DatabaseError::InvalidData(format!(
+                "Page {} of catalog '{}' is not a {:?}",
+                page_id,
+                path.display(),
+                expected
+            )));
+        }
+        bytes.extend_from_slice(page.get_record(0)?);
+    }
+
+    let mut reader = ByteReader::new(&bytes);
+    let version = reader.read_u32()?;
+    if version == 0 || version > CATALOG_FORMAT_VERSION {
+        return Err(DatabaseError::InvalidData(format!(
+            "Unsupported catalog version {}",
+            version
+        )));
+    }
+    let page_size = reader.read_u32()?;
+    if page_size != PAGE_SIZE as u32 {
+        return Err(DatabaseError::InvalidData(format!(
+            "Catalog '{}' was written with {} byte pages, expected {}",
+            path.display(),
+            page_size,
+            PAGE_SIZE
+        )));
+    }
+    let length = reader.read_u32()? as usize;
+    let checksum = reader.read_u32()?;
+    let body = reader.read_bytes(length)?;
+    if crc32(body) != checksum {
+        return Err(DatabaseError::InvalidData(format!(
+            "Checksum mismatch in catalog '{}'",
+            path.display()
+        )));
+    }
+
+    let mut reader = ByteReader::new(body);
+    let mut catalog = Catalog {
+        next_collection_id: reader.read_u32()?,
+        collections: Vec::new(),
+    };
+    for _ in 0..reader.read_u32()? {
+        let name = reader.read_short_string()?;
+        let id = reader.read_u32()?;
+        let path = PathBuf::from(reader.read_string()?);
+        let schema = decode_schema(&mut reader)?;
+        let next_id = reader.read_u64()?;
+        let ttl = match version {
+            1 => None,
+            _ => match reader.read_u8()? {
+                0 => None,
+                _ => Some(TtlPolicy {
+                    field: reader.read_short_string()?,
+                    expire_after: Duration::from_millis(reader.read_u64()?),
+                }),
+            },
+        };
+        catalog.collections.push(CatalogEntry {
+            name,
+            id,
+            path,
+            schema,
+            next_id,
+            ttl,
+        });
+    }
+    let views = decode_views(&mut reader)?;
+    Ok((catalog, views))
+}
+
+/// Whether the file starts with a valid header page, rather than the views of an older catalog
+fn starts_with_header_page(path: &Path) -> Result<bool, DatabaseError> {
+    let mut file_manager = FileManager::new(path)?;
+    Ok(match file_manager.check_page(0) {
+        Ok(PageCheck::Valid(page)) => page.header.page_type == PageType::HeaderPage,
+        _ => false,
+    })
+}
+
+fn decode_views(reader: &mut ByteReader) -> Result<Vec<View>, DatabaseError> {
+    let mut views = Vec::new();
+    for _ in 0..reader.read_u32()? {
+        views.push(View::deserialize(reader)?);
+    }
+    Ok(views)
+}
+
+fn encode_schema(schema: &Schema, bytes: &mut Vec<u8>) {
+    encode_short_string(&schema.name, bytes);
+    bytes.extend_from_slice(&(schema.fields.len() as u16).to_le_bytes());
+    for field in &schema.fields {
+        encode_short_string(&field.name, bytes);
+        bytes.push(match field.field_type {
+            FieldType::Byte => 0,
+            FieldType::Short => 1,
+            FieldType::Int => 2,
+            FieldType::Long => 3,
+            FieldType::Float => 4,
+            FieldType::Double => 5,
+            FieldType::String => 6,
+            FieldType::Boolean => 7,
+        });
+        bytes.push(field.nullable as u8);
+        bytes.push(match field.on_overflow {
+            StringOverflow::Error => 0,
+            StringOverflow::Truncate => 1,
+        });
+        bytes.push(field.auto_increment as u8);
+    }
+
+    match &schema.primary_key {
+        Some(fields) => {
+            bytes.push(fields.len() as u8);
+            for field in fields {
+                encode_short_string(field, bytes);
+            }
+        }
+        None => bytes.push(u8::MAX),
+    }
+    bytes.push(schema.text_fields.len() as u8);
+    for field in &schema.text_fields {
+        encode_short_string(field, bytes);
+    }
+    bytes.push(schema.lenient as u8);
+}
+
+fn decode_schema(reader: &mut ByteReader) -> Result<Schema, DatabaseError> {
+    let name = reader.read_short_string()?;
+    let mut fields = Vec::new();
+    for _ in 0..reader.read_u16()? {
+        let name = reader.read_short_string()?;
+        let field_type = match reader.read_u8()? {
+            0 => FieldType::Byte,
+            1 => FieldType::Short,
+            2 => FieldType::Int,
+            3 => FieldType::Long,
+            4 => FieldType::Float,
+            5 => FieldType::Double,
+            6 => FieldType::String,
+            7 => FieldType::Boolean,
+            tag => {
+                return Err(DatabaseError::InvalidData(format!(
+                    "Unknown field type {}",
+                    tag
+                )));
+            }
+        };
+        let nullable = reader.read_u8()? != 0;
+        let on_overflow = match reader.read_u8()? {
+            0 => StringOverflow::Error,
+            _ => StringOverflow::Truncate,
+        };
+        let auto_increment = reader.read_u8()? != 0;
+        fields.push(Field {
+            name,
+            field_type,
+            nullable,
+            on_overflow,
+            auto_increment,
+        });
+    }
+
+    let primary_key = match reader.read_u8()? {
+        u8::MAX => None,
+        count => {
+            let mut key = Vec::new();
+            for _ in 0..count {
+                key.push(reader.read_short_string()?);
+            }
+            Some(key)
+        }
+    };
+    let mut text_fields = Vec::new();
+    for _ in 0..reader.read_u8()? {
+        text_fields.push(reader.read_short_string()?);
+    }
+    let lenient = reader.read_u8()? != 0;
+
+    Ok(Schema {
+        name,
+        fields,
+        primary_key,
+        text_fields,
+        lenient,
+    })
+}
@@ -0,0 +1,320 @@
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use crate::{
+    common::DatabaseError,
+    storage::page::{PAGE_BODY_LENGTH_OFFSET, PAGE_CODEC_ID_OFFSET, PAGE_HEADER_SIZE, PAGE_SIZE},
+};
+
+/// Codec id of pages stored as they are
+pub const NO_CODEC: u8 = 0;
+
+/// Codec id of pages compressed with the built-in `Lz4Codec`
+pub const LZ4_CODEC_ID: u8 = 16;
+
+/// Codec id of pages encrypted with the built-in `EncryptionCodec`
+pub const AES_GCM_CODEC_ID: u8 = 17;
+
+/// Codec id of pages encoded with several codecs in turn, see `CodecRegistry::encode_page_with`
+pub const CHAINED_CODEC_ID: u8 = 18;
+
+/// Size of the encoded body length stored in front of an encoded page body
+const ENCODED_LENGTH_SIZE: usize = 2;
+
+/// Transformation of page bodies on their way to and from the file,
+/// e.g. compression, encryption or a hardware offload.
+/// The page header stays readable, the codec id stored in it selects the codec on read,
+/// so ids must never be reused for a different transformation.
+pub trait PageCodec: Send + Sync {
+    /// Id persisted in the page header, 0 is reserved for pages without a codec
+    fn id(&self) -> u8;
+
+    fn name(&self) -> &str;
+
+    /// Maximum number of bytes `encode` adds to its input.
+    /// Pages allocated while the codec is active leave this much room.
+    fn overhead(&self) -> usize {
+        0
+    }
+
+    /// The codec may leave a page as it is, e.g. a compressed page which would not fit.
+    /// Pages are then written without a codec, and no room is kept free for the codec.
+    fn optional(&self) -> bool {
+        false
+    }
+
+    fn encode(&self, page_id: u32, body: &[u8]) -> Result<Vec<u8>, DatabaseError>;
+
+    fn decode(&self, page_id: u32, encoded: &[u8]) -> Result<Vec<u8>, DatabaseError>;
+}
+
+/// Codecs known to a file, looked up by the id stored in each page header
+#[derive(Clone, Default)]
+pub struct CodecRegistry {
+    codecs: HashMap<u8, Arc<dyn PageCodec>>,
+}
+
+impl CodecRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, codec: Arc<dyn PageCodec>) -> Result<(), DatabaseError> {
+        let id = codec.id();
+        if id == NO_CODEC || id == CHAINED_CODEC_ID {
+            return Err(DatabaseError::InvalidData(format!(
+                "Codec '{}' uses the reserved id {}",
+                codec.name(),
+                id
+            )));
+        }
+        if let Some(existing) = self.codecs.get(&id) {
+            return Err(DatabaseError::InvalidData(format!(
+                "Codec '{}' uses id {} of codec '{}'",
+                codec.name(),
+                id,
+                existing.name()
+            )));
+        }
+
+        self.codecs.insert(id, codec);
+        Ok(())
+    }
+
+    pub fn get(&self, id: u8) -> Option<&Arc<dyn PageCodec>> {
+        self.codecs.get(&id)
+    }
+
+    /// Encode the body of a serialized page in place, recording its length before encoding
+    /// in the page header. The body must end with `reserved_bytes` of zeros, the room pages
+    /// allocated while the codec is active keep free. An optional codec leaves the page
+    /// as it is if its encoded body does not fit.
+    pub fn encode_page(
+        codec: &dyn PageCodec,
+        page_id: u32,
+        page_bytes: &mut [u8; PAGE_SIZE],
+    ) -> Result<(), DatabaseError> {
+        let body_end = PAGE_SIZE - Self::reserved_bytes(codec);
+        if page_bytes[body_end..].iter().any(|&byte| byte != 0) {
+            return Err(DatabaseError::InvalidData(format!(
+                "Page {} has no room for codec '{}'",
+                page_id,
+                codec.name()
+            )));
+        }
+
+        let encoded = codec.encode(page_id, &page_bytes[PAGE_HEADER_SIZE..body_end])?;
+        let encoded_start = PAGE_HEADER_SIZE + ENCODED_LENGTH_SIZE;
+        if encoded_start + encoded.len() > PAGE_SIZE {
+            if codec.optional() {
+                return Ok(());
+            }
+            return Err(DatabaseError::InvalidData(format!(
+                "Codec '{}' grew page {} beyond its declared overhead",
+                codec.name(),
+                page_id
+            )));
+        }
+
+        page_bytes[PAGE_CODEC_ID_OFFSET] = codec.id();
+        page_bytes[PAGE_BODY_LENGTH_OFFSET..PAGE_BODY_LENGTH_OFFSET + 2]
+            .copy_from_slice(&((body_end - PAGE_HEADER_SIZE) as u16).to_le_bytes());
+        page_bytes[PAGE_HEADER_SIZE..encoded_start]
+            .copy_from_slice(&(encoded.len() as u16).to_le_bytes());
+        page_bytes[encoded_start..encoded_start + encoded.len()].copy_from_slice(&encoded);
+        page_bytes[encoded_start + encoded.len()..].fill(0);
+        Ok(())
+    }
+
+    /// Encode the body of a serialized page in place with each codec in turn, e.g. compress
+    /// then encrypt. The ids of the codecs applied follow the encoded body length, optional
+    /// codecs which do not shrink the body are skipped. A single codec encodes the page
+    /// like `encode_page`.
+    pub fn encode_page_with(
+        codecs: &[Arc<dyn PageCodec>],
+        page_id: u32,
+        page_bytes: &mut [u8; PAGE_SIZE],
+    ) -> Result<(), DatabaseError> {
+        let [first, ..] = codecs else {
+            return Ok(());
+        };
+        if codecs.len() == 1 {
+            return Self::encode_page(first.as_ref(), page_id, page_bytes);
+        }
+
+        let body_end = PAGE_SIZE - Self::chained_reserved_bytes(codecs);
+        if page_bytes[body_end..].iter().any(|&byte| byte != 0) {
+            return Err(DatabaseError::InvalidData(format!(
+                "Page {} has no room for codecs {}",
+                page_id,
+                chain_name(codecs)
+            )));
+        }
+
+        let mut encoded = page_bytes[PAGE_HEADER_SIZE..body_end].to_vec();
+        let mut applied = Vec::with_capacity(codecs.len());
+        for codec in codecs {
+            let next = codec.encode(page_id, &encoded)?;
+            if codec.optional() && next.len() >= encoded.len() {
+                continue;
+            }
+            encoded = next;
+            applied.push(codec.id());
+        }
+        if applied.is_empty() {
+            return Ok(());
+        }
+
+        let encoded_start = PAGE_HEADER_SIZE + ENCODED_LENGTH_SIZE + 1 + applied.len();
+        if encoded_start + encoded.len() > PAGE_SIZE {
+            if codecs.iter().all(|codec| codec.optional()) {
+                return Ok(());
+            }
+            return Err(DatabaseError::InvalidData(format!(
+                "Codecs {} grew page {} beyond their declared overhead",
+                chain_name(codecs),
+                page_id
+            )));
+        }
+
+        page_bytes[PAGE_CODEC_ID_OFFSET] = CHAINED_CODEC_ID;
+        page_bytes[PAGE_BODY_LENGTH_OFFSET..PAGE_BODY_LENGTH_OFFSET + 2]
+            .copy_from_slice(&((body_end - PAGE_HEADER_SIZE) as u16).to_le_bytes());
+        let mut offset = PAGE_HEADER_SIZE;
+        page_bytes[offset..offset + ENCODED_LENGTH_SIZE]
+            .copy_from_slice(&(encoded.len() as u16).to_le_bytes());
+        offset += ENCODED_LENGTH_SIZE;
+        page_bytes[offset] = applied.len() as u8;
+        page_bytes[offset + 1..encoded_start].copy_from_slice(&applied);
+        page_bytes[encoded_start..encoded_start + encoded.len()].copy_from_slice(&encoded);
+        page_bytes[encoded_start + encoded.len()..].fill(0);
+        Ok(())
+    }
+
+    /// Decode the body of a page read from the file in place, pages without a codec are left as they are
+    pub fn decode_page(
+        &self,
+        page_id: u32,
+        page_bytes: &mut [u8; PAGE_SIZE],
+    ) -> Result<(), DatabaseError> {
+        let id = page_bytes[PAGE_CODEC_ID_OFFSET];
+        if id == NO_CODEC {
+            return Ok(());
+        }
+
+        // The ids of a chain follow the encoded body length, they are undone last to first
+        let mut encoded_start = PAGE_HEADER_SIZE + ENCODED_LENGTH_SIZE;
+        let ids = match id {
+            CHAINED_CODEC_ID => {
+                let count = page_bytes[encoded_start] as usize;
+                let ids = page_bytes[encoded_start + 1..encoded_start + 1 + count].to_vec();
+                encoded_start += 1 + count;
+                ids
+            }
+            id => vec![id],
+        };
+        let mut chain = Vec::with_capacity(ids.len());
+        for &id in &ids {
+            let Some(codec) = self.codecs.get(&id) else {
+                return Err(DatabaseError::InvalidData(format!(
+                    "Page {} is encoded with unknown codec {}",
+                    page_id, id
+                )));
+            };
+            chain.push(Arc::clone(codec));
+        }
+        if chain.is_empty() {
+            return Err(DatabaseError::InvalidData(format!(
+                "Page {} is encoded with an empty codec chain",
+                page_id
+            )));
+        }
+
+        let length = u16::from_le_bytes([
+            page_bytes[PAGE_HEADER_SIZE],
+            page_bytes[PAGE_HEADER_SIZE + 1],
+        ]) as usize;
+        if encoded_start + length > PAGE_SIZE {
+            return Err(DatabaseError::InvalidData(format!(
+                "Encoded body of page {} extends beyond the page",
+                page_id
+            )));
+        }
+
+        let mut body = page_bytes[encoded_start..encoded_start + length].to_vec();
+        for codec in chain.iter().rev() {
+            body = codec.decode(page_id, &body)?;
+        }
+        if PAGE_HEADER_SIZE + body.len() > PAGE_SIZE {
+            return Err(DatabaseError::InvalidData(format!(
+                "Codec {} decoded page {} beyond the page size",
+                chain_name(&chain),
+                page_id
+            )));
+        }
+        // Pages encoded before the header recorded the body length store 0
+        let body_length = u16::from_le_bytes([
+            page_bytes[PAGE_BODY_LENGTH_OFFSET],
+            page_bytes[PAGE_BODY_LENGTH_OFFSET + 1],
+        ]) as usize;
+        if body_length != 0 && body.len() != body_length {
+            return Err(DatabaseError::InvalidData(format!(
+                "Codec {} decoded page {} to {} bytes, expected {}",
+                chain_name(&chain),
+                page_id,
+                body.len(),
+                body_length
+            )));
+        }
+
+        page_bytes[PAGE_CODEC_ID_OFFSET] = NO_CODEC;
+        page_bytes[PAGE_BODY_LENGTH_OFFSET..PAGE_BODY_LENGTH_OFFSET + 2].fill(0);
+        page_bytes[PAGE_HEADER_SIZE..PAGE_HEADER_SIZE + body.len()].copy_from_slice(&body);
+        page_bytes[PAGE_HEADER_SIZE + body.len()..].fill(0);
+        Ok(())
+    }
+
+    /// Bytes to keep free at the end of pages written with the codec
+    pub fn reserved_bytes(codec: &dyn PageCodec) -> usize {
+        match codec.optional() {
+            true => 0,
+            false => ENCODED_LENGTH_SIZE + codec.overhead(),
+        }
+    }
+
+    /// Bytes to keep free at the end of pages written with the codecs in turn,
+    /// see `encode_page_with`
+    pub fn chained_reserved_bytes(codecs: &[Arc<dyn PageCodec>]) -> usize {
+        match codecs {
+            [] => 0,
+            [codec] => Self::reserved_bytes(codec.as_ref()),
+            _ if codecs.iter().all(|codec| codec.optional()) => 0,
+            _ => {
+                ENCODED_LENGTH_SIZE
+                    + 1
+                    + codecs.len()
+                    + codecs
+                        .iter()
+                        .filter(|codec| !codec.optional())
+                        .map(|codec| codec.overhead())
+                        .sum::<usize>()
+            }
+        }
+    }
+}
+
+/// Names of the codecs of a chain, e.g. `'lz4' then 'aes-gcm'`
+fn chain_name(codecs: &[Arc<dyn PageCodec>]) -> String {
+    codecs
+        .iter()
+        .map(|codec| format!("'{}'", codec.name()))
+        .collect::<Vec<_>>()
+        .join(" then ")
+}
+
+impl fmt::Debug for CodecRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut ids: Vec<_> = self.codecs.keys().collect();
+        ids.sort();
+        f.debug_struct("CodecRegistry").field("ids", &ids).finish()
+    }
+}
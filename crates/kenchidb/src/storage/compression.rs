@@ -0,0 +1,37 @@
+use crate::{
+    common::DatabaseError,
+    storage::{
+        codec::{LZ4_CODEC_ID, PageCodec},
+        page::MAX_PAGE_DATA_SIZE,
+    },
+};
+
+/// Compresses page bodies in the LZ4 block format. Pages which do not shrink are written
+/// as they are. `FileManager::enable_compression` packs compressed pages several to a slot
+/// of a second file, see `PackedPages`, and can be combined with encryption.
+#[derive(Debug, Default)]
+pub struct Lz4Codec;
+
+impl PageCodec for Lz4Codec {
+    fn id(&self) -> u8 {
+        LZ4_CODEC_ID
+    }
+
+    fn name(&self) -> &str {
+        "lz4"
+    }
+
+    fn optional(&self) -> bool {
+        true
+    }
+
+    fn encode(&self, _page_id: u32, body: &[u8]) -> Result<Vec<u8>, DatabaseError> {
+        Ok(lz4_flex::block::compress(body))
+    }
+
+    fn decode(&self, page_id: u32, encoded: &[u8]) -> Result<Vec<u8>, DatabaseError> {
+        lz4_flex::block::decompress(encoded, MAX_PAGE_DATA_SIZE).map_err(|e| {
+            DatabaseError::InvalidData(format!("Page {}: invalid LZ4 block, {}", page_id, e))
+        })
+    }
+}
@@ -0,0 +1,154 @@
+use std::{borrow::BorrowMut, time::Instant};
+
+use crate::{
+    common::DatabaseError,
+    query::{Query, QueryStats},
+    schema::{Document, current_time_millis},
+    storage::{
+        file_manager::FileManager,
+        page::{Page, PageType},
+        paged_collection::PagedCollection,
+    },
+};
+
+/// Streams the documents of a paged collection one page at a time.
+/// Only the current page is kept in memory, documents are yielded in page and slot order.
+/// After an I/O or decoding error the cursor yields the error once and then ends.
+pub struct DocumentCursor<'a, F = FileManager> {
+    collection: &'a mut PagedCollection<F>,
+    filter: Option<Query>,
+    now: i64,
+    next_page_id: u32,
+    page: Option<Page>,
+    next_slot: u16,
+    done: bool,
+    started: Instant,
+    rows_scanned: usize,
+    rows_matched: usize,
+    /// File manager counters when the cursor was created
+    pages_read: u64,
+    cache_hits: u64,
+}
+
+impl<'a, F: BorrowMut<FileManager>> DocumentCursor<'a, F> {
+    pub fn new(collection: &'a mut PagedCollection<F>, filter: Option<Query>) -> Self {
+        let pages_read = collection.file_manager.borrow().pages_read();
+        let cache_hits = collection.file_manager.borrow().cache_hits();
+        Self {
+            collection,
+            filter,
+            now: current_time_millis(),
+            next_page_id: 0,
+            page: None,
+            next_slot: 0,
+            done: false,
+            started: Instant::now(),
+            rows_scanned: 0,
+            rows_matched: 0,
+            pages_read,
+            cache_hits,
+        }
+    }
+
+    /// Cost of the documents streamed so far
+    pub fn stats(&self) -> QueryStats {
+        let file_manager = self.collection.file_manager.borrow();
+        QueryStats {
+            rows_scanned: self.rows_scanned,
+            rows_matched: self.rows_matched,
+            pages_read: file_manager.pages_read() - self.pages_read,
+            cache_hits: file_manager.cache_hits().saturating_sub(self.cache_hits),
+            elapsed: self.started.elapsed(),
+        }
+    }
+
+    /// Collect the remaining documents together with the cost of the whole scan
+    pub fn with_stats(mut self) -> Result<(Vec<Document>, QueryStats), DatabaseError> {
+        let documents = self.by_ref().collect::<Result<Vec<_>, _>>()?;
+        Ok((documents, self.stats()))
+    }
+
+    /// Next live record of the current page, skipping expired and non-matching documents
+    fn next_in_page(&mut self) -> Option<Result<Document, DatabaseError>> {
+        let page = self.page.as_ref()?;
+
+        while (self.next_slot as usize) < page.slots.len() {
+            let slot_index = self.next_slot;
+            self.next_slot += 1;
+
+            if page.slots[slot_index as usize].is_free() {
+                continue;
+            }
+
+            let document = match page
+                .get_record(slot_index)
+                .and_then(|record| self.collection.read_document(record))
+            {
+                Ok(document) => document,
+                Err(e) => return Some(Err(e)),
+            };
+            self.rows_scanned += 1;
+
+            if let Some(ttl) = &self.collection.ttl
+                && ttl.is_expired(&document, self.now)
+            {
+                continue;
+            }
+
+            if self
+                .filter
+                .as_ref()
+                .is_some_and(|filter| !filter.matches(&document))
+            {
+                continue;
+            }
+
+            self.rows_matched += 1;
+            return Some(Ok(document));
+        }
+
+        None
+    }
+}
+
+impl<F: BorrowMut<FileManager>> Iterator for DocumentCursor<'_, F> {
+    type Item = Result<Document, DatabaseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        loop {
+            if let Some(result) = self.next_in_page() {
+                self.done = result.is_err();
+                return Some(result);
+            }
+
+            // Move to the next data page of this collection
+            self.page = None;
+            if self.next_page_id >= self.collection.file_manager.borrow().page_count() {
+                self.done = true;
+                return None;
+            }
+
+            let page_id = self.next_page_id;
+            self.next_page_id += 1;
+
+            match self.collection.file_manager.borrow_mut().read_page(page_id) {
+                Ok(page)
+                    if page.header.page_type == PageType::DataPage
+                        && page.header.collection_id == self.collection.collection_id =>
+                {
+                    self.page = Some(page);
+                    self.next_slot = 0;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
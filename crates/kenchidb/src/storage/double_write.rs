@@ -0,0 +1,106 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    common::{DatabaseError, crc32},
+    storage::page::PAGE_SIZE,
+};
+
+/// Page id and CRC-32 in front of each staged page image
+const IMAGE_HEADER_SIZE: usize = 8;
+
+/// Page id and encoded bytes of a page
+pub type PageImage = (u32, Box<[u8; PAGE_SIZE]>);
+
+/// Double-write file of the page file at `path`: `<path>.dwb`
+pub fn double_write_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".dwb");
+    PathBuf::from(path)
+}
+
+/// Second copy of the pages of a flush, written and synced before any of them is written
+/// in place. A crash in the middle of a 4 KiB page write then leaves a complete image to
+/// repair the torn page from. The file is emptied once the pages are synced in place.
+pub struct DoubleWriteBuffer {
+    file: File,
+}
+
+impl DoubleWriteBuffer {
+    /// Open the file, it must have been restored or be empty
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, DatabaseError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(path)?;
+        Ok(Self { file })
+    }
+
+    /// Second handle on the same file, for a flush running on another thread
+    pub fn try_clone(&self) -> Result<Self, DatabaseError> {
+        Ok(Self {
+            file: self.file.try_clone()?,
+        })
+    }
+
+    /// Write the encoded page images and sync them
+    pub fn stage<'a>(
+        &mut self,
+        pages: impl Iterator<Item = (&'a u32, &'a Box<[u8; PAGE_SIZE]>)>,
+    ) -> Result<(), DatabaseError> {
+        let mut bytes = Vec::new();
+        for (page_id, page_bytes) in pages {
+            bytes.extend_from_slice(&page_id.to_le_bytes());
+            bytes.extend_from_slice(&image_checksum(*page_id, page_bytes).to_le_bytes());
+            bytes.extend_from_slice(page_bytes.as_slice());
+        }
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&bytes)?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    /// Empty the file once the staged pages are synced in place. The truncation is synced,
+    /// images of an older flush must never be restored over newer pages.
+    pub fn clear(&mut self) -> Result<(), DatabaseError> {
+        self.file.set_len(0)?;
+        self.file.sync_all()?;
+        Ok(())
+    }
+}
+
+/// Page images staged by a flush that did not finish, in page id order. Images cut off or
+/// damaged by a crash while staging are left out, the pages in place were not touched then.
+pub fn read_double_write(path: &Path) -> Result<Vec<PageImage>, DatabaseError> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+
+    let mut images = Vec::new();
+    for record in bytes.chunks_exact(IMAGE_HEADER_SIZE + PAGE_SIZE) {
+        let page_id = u32::from_le_bytes(record[..4].try_into().unwrap());
+        let checksum = u32::from_le_bytes(record[4..8].try_into().unwrap());
+        let page_bytes: Box<[u8; PAGE_SIZE]> =
+            Box::new(record[IMAGE_HEADER_SIZE..].try_into().unwrap());
+        if image_checksum(page_id, &page_bytes) == checksum {
+            images.push((page_id, page_bytes));
+        }
+    }
+    Ok(images)
+}
+
+fn image_checksum(page_id: u32, page_bytes: &[u8; PAGE_SIZE]) -> u32 {
+    let mut bytes = Vec::with_capacity(4 + PAGE_SIZE);
+    bytes.extend_from_slice(&page_id.to_le_bytes());
+    bytes.extend_from_slice(page_bytes);
+    crc32(&bytes)
+}
@@ -0,0 +1,199 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use ::storage::{
+    Argon2Params, EncryptionKey, KeyDerivation, Keyring, NONCE_SIZE, StorageError, TAG_SIZE,
+};
+
+use crate::{
+    common::{DatabaseError, crc32},
+    storage::codec::{AES_GCM_CODEC_ID, PageCodec},
+};
+
+/// Key id and nonce in front of each encrypted page body
+const FRAME_HEADER_SIZE: usize = 4 + NONCE_SIZE;
+
+/// Marks a key derivation file, "KENK" in ASCII
+const KEY_FILE_MAGIC: u32 = 0x4B454E4B;
+
+/// Magic, key derivation and CRC-32 of both
+const KEY_FILE_SIZE: usize = 4 + KeyDerivation::SIZE + 4;
+
+/// Key derivation file of the page file at `path`: `<path>.key`
+pub fn key_derivation_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".key");
+    PathBuf::from(path)
+}
+
+/// Encrypts page bodies with AES-256-GCM. The encrypted body starts with the id of the
+/// key and the nonce, the authentication tag follows the ciphertext. The page id and key
+/// id are authenticated along with the body, so a page copied to another position fails
+/// to decode like a changed one. The page header stays readable.
+///
+/// Nonces are the page id followed by a counter starting at a random value for each codec,
+/// no nonce repeats under a key unless a page is written 2^64 times in one session.
+pub struct EncryptionCodec {
+    keyring: Keyring,
+}
+
+impl EncryptionCodec {
+    /// Codec encrypting new pages with the key
+    pub fn new(key_id: u32, key: &EncryptionKey) -> Result<Self, DatabaseError> {
+        let keyring = Keyring::new(key_id, key).map_err(encryption_error)?;
+        Ok(Self { keyring })
+    }
+
+    /// Also read pages encrypted with an older key, e.g. while rotating keys
+    pub fn with_key(self, key_id: u32, key: &EncryptionKey) -> Self {
+        Self {
+            keyring: self.keyring.with_key(key_id, key),
+        }
+    }
+
+    /// Codec encrypting new pages with the key derived from the passphrase. The salt,
+    /// Argon2 parameters and key id are read from the key derivation file at `path`, they
+    /// take precedence over `params`. Without the file a new salt is drawn and saved
+    /// under key id 1. A wrong passphrase fails once an encrypted page is read.
+    pub fn open_with_passphrase<P: AsRef<Path>>(
+        path: P,
+        passphrase: &str,
+        params: Argon2Params,
+    ) -> Result<Self, DatabaseError> {
+        let key_derivation = match read_key_derivation(path.as_ref())? {
+            Some(key_derivation) => key_derivation,
+            None => {
+                let key_derivation =
+                    KeyDerivation::generate(1, params).map_err(encryption_error)?;
+                write_key_derivation(path.as_ref(), &key_derivation)?;
+                key_derivation
+            }
+        };
+        let keyring = key_derivation
+            .keyring(passphrase)
+            .map_err(encryption_error)?;
+        Ok(Self { keyring })
+    }
+
+    pub fn current_key(&self) -> u32 {
+        self.keyring.current_key()
+    }
+}
+
+/// Key derivation saved at `path`, `None` if the file does not exist
+fn read_key_derivation(path: &Path) -> Result<Option<KeyDerivation>, DatabaseError> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error.into()),
+    };
+    let checksum_offset = KEY_FILE_SIZE - 4;
+    if bytes.len() != KEY_FILE_SIZE
+        || u32::from_le_bytes(bytes[..4].try_into().unwrap()) != KEY_FILE_MAGIC
+        || u32::from_le_bytes(bytes[checksum_offset..].try_into().unwrap())
+            != crc32(&bytes[..checksum_offset])
+    {
+        return Err(DatabaseError::InvalidData(format!(
+            "Key derivation file {} is damaged",
+            path.display()
+        )));
+    }
+    KeyDerivation::deserialize(&bytes[4..checksum_offset])
+        .map(Some)
+        .map_err(encryption_error)
+}
+
+/// Save the key derivation at `path`. The file is written next to the target and
+/// renamed over it, a crash keeps the previous file.
+fn write_key_derivation(path: &Path, key_derivation: &KeyDerivation) -> Result<(), DatabaseError> {
+    let mut bytes = Vec::with_capacity(KEY_FILE_SIZE);
+    bytes.extend_from_slice(&KEY_FILE_MAGIC.to_le_bytes());
+    bytes.extend_from_slice(&key_derivation.serialize());
+    bytes.extend_from_slice(&crc32(&bytes).to_le_bytes());
+
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(&temporary)?;
+    file.write_all(&bytes)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+fn encryption_error(error: StorageError) -> DatabaseError {
+    match error {
+        StorageError::IoError(error) => DatabaseError::IoError(error),
+        StorageError::Encryption(message) => DatabaseError::InvalidData(message),
+        error => DatabaseError::InvalidData(format!("{:?}", error)),
+    }
+}
+
+/// Authenticated, unencrypted part of an encrypted page
+fn associated_data(page_id: u32, key_id: u32) -> [u8; 8] {
+    let mut associated = [0u8; 8];
+    associated[..4].copy_from_slice(&page_id.to_le_bytes());
+    associated[4..].copy_from_slice(&key_id.to_le_bytes());
+    associated
+}
+
+impl PageCodec for EncryptionCodec {
+    fn id(&self) -> u8 {
+        AES_GCM_CODEC_ID
+    }
+
+    fn name(&self) -> &str {
+        "aes-gcm"
+    }
+
+    fn overhead(&self) -> usize {
+        FRAME_HEADER_SIZE + TAG_SIZE
+    }
+
+    fn encode(&self, page_id: u32, body: &[u8]) -> Result<Vec<u8>, DatabaseError> {
+        let key_id = self.keyring.current_key();
+        let nonce = self.keyring.next_nonce(page_id);
+        let sealed = self
+            .keyring
+            .seal(&nonce, &associated_data(page_id, key_id), body)
+            .map_err(encryption_error)?;
+        let mut encoded = Vec::with_capacity(FRAME_HEADER_SIZE + sealed.len());
+        encoded.extend_from_slice(&key_id.to_le_bytes());
+        encoded.extend_from_slice(&nonce);
+        encoded.extend_from_slice(&sealed);
+        Ok(encoded)
+    }
+
+    fn decode(&self, page_id: u32, encoded: &[u8]) -> Result<Vec<u8>, DatabaseError> {
+        if encoded.len() < FRAME_HEADER_SIZE + TAG_SIZE {
+            return Err(DatabaseError::InvalidData(format!(
+                "Encrypted body of page {} is truncated",
+                page_id
+            )));
+        }
+        let key_id = u32::from_le_bytes(encoded[..4].try_into().unwrap());
+        let nonce: [u8; NONCE_SIZE] = encoded[4..FRAME_HEADER_SIZE].try_into().unwrap();
+
+        self.keyring
+            .open(
+                key_id,
+                &nonce,
+                &associated_data(page_id, key_id),
+                &encoded[FRAME_HEADER_SIZE..],
+            )
+            .map_err(|error| match error {
+                StorageError::Encryption(message) => {
+                    DatabaseError::InvalidData(format!("Page {}: {}", page_id, message))
+                }
+                error => encryption_error(error),
+            })
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{
+    common::DatabaseError,
+    storage::{
+        file_manager::FileManager,
+        page::{PAGE_SIZE, Page, PageType},
+    },
+};
+
+/// Page storage behind the paged structures. `FileManager` keeps the pages in a file,
+/// `MemoryEngine` in memory for tests and ephemeral caches.
+pub trait StorageEngine {
+    /// Read a page, a page that does not match its checksum is a `Corruption` error
+    fn read_page(&mut self, page_id: u32) -> Result<Page, DatabaseError>;
+
+    /// Write a page, durable once `sync` returns
+    fn write_page(&mut self, page_id: u32, page: &mut Page) -> Result<(), DatabaseError>;
+
+    /// New empty page of the collection, the page is stored by its first `write_page`
+    fn allocate_page(
+        &mut self,
+        page_type: PageType,
+        collection_id: u32,
+    ) -> Result<(u32, Page), DatabaseError>;
+
+    /// Rewrite the page as a free page, it keeps the collection id it had
+    fn free_page(&mut self, page_id: u32) -> Result<(), DatabaseError>;
+
+    /// Make the written pages durable
+    fn sync(&mut self) -> Result<(), DatabaseError>;
+
+    /// Pages allocated or written, page ids are below this
+    fn page_count(&self) -> u32;
+
+    fn stats(&self) -> EngineStats;
+}
+
+/// Counters of a storage engine
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EngineStats {
+    pub page_count: u32,
+    /// Pages read from the storage, reads served from a cache excluded
+    pub pages_read: u64,
+    /// Reads served from a cache
+    pub cache_hits: u64,
+    /// Pages written but not synced yet
+    pub dirty_pages: usize,
+}
+
+impl StorageEngine for FileManager {
+    fn read_page(&mut self, page_id: u32) -> Result<Page, DatabaseError> {
+        FileManager::read_page(self, page_id)
+    }
+
+    fn write_page(&mut self, page_id: u32, page: &mut Page) -> Result<(), DatabaseError> {
+        FileManager::write_page(self, page_id, page)
+    }
+
+    fn allocate_page(
+        &mut self,
+        page_type: PageType,
+        collection_id: u32,
+    ) -> Result<(u32, Page), DatabaseError> {
+        FileManager::allocate_page(self, page_type, collection_id)
+    }
+
+    fn free_page(&mut self, page_id: u32) -> Result<(), DatabaseError> {
+        let collection_id = FileManager::read_page(self, page_id)?.header.collection_id;
+        let mut free = self.new_page(PageType::FreePage, collection_id);
+        FileManager::write_page(self, page_id, &mut free)
+    }
+
+    fn sync(&mut self) -> Result<(), DatabaseError> {
+        self.flush()
+    }
+
+    fn page_count(&self) -> u32 {
+        FileManager::page_count(self)
+    }
+
+    fn stats(&self) -> EngineStats {
+        EngineStats {
+            page_count: FileManager::page_count(self),
+            pages_read: self.pages_read(),
+            cache_hits: self.cache_hits(),
+            dirty_pages: self.dirty_page_count(),
+        }
+    }
+}
+
+/// Pages kept in memory as their serialized bytes, nothing touches the disk.
+/// Freed pages are handed out again by `allocate_page`, lowest page id first.
+#[derive(Default)]
+pub struct MemoryEngine {
+    pages: Vec<Option<Box<[u8; PAGE_SIZE]>>>,
+    free: Vec<u32>,
+    pages_read: u64,
+}
+
+impl MemoryEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StorageEngine for MemoryEngine {
+    fn read_page(&mut self, page_id: u32) -> Result<Page, DatabaseError> {
+        let Some(slot) = self.pages.get(page_id as usize) else {
+            return Err(DatabaseError::InvalidData(
+                "Page ID out of bounds".to_string(),
+            ));
+        };
+        self.pages_read += 1;
+        match slot {
+            Some(bytes) => Page::deserialize(page_id, bytes.as_slice()),
+            None => Err(DatabaseError::InvalidData(format!(
+                "Page {} was allocated but not written",
+                page_id
+            ))),
+        }
+    }
+
+    fn write_page(&mut self, page_id: u32, page: &mut Page) -> Result<(), DatabaseError> {
+        if page_id as usize >= self.pages.len() {
+            self.pages.resize(page_id as usize + 1, None);
+        }
+        self.pages[page_id as usize] = Some(Box::new(page.serialize()));
+        self.free.retain(|free| *free != page_id);
+        Ok(())
+    }
+
+    fn allocate_page(
+        &mut self,
+        page_type: PageType,
+        collection_id: u32,
+    ) -> Result<(u32, Page), DatabaseError> {
+        let page_id = match self.free.iter().enumerate().min_by_key(|(_, id)| **id) {
+            Some((index, _)) => self.free.swap_remove(index),
+            None => {
+                self.pages.push(None);
+                self.pages.len() as u32 - 1
+            }
+        };
+        Ok((page_id, Page::new(page_type, collection_id)))
+    }
+
+    fn free_page(&mut self, page_id: u32) -> Result<(), DatabaseError> {
+        let collection_id = self.read_page(page_id)?.header.collection_id;
+        let mut free = Page::new(PageType::FreePage, collection_id);
+        self.write_page(page_id, &mut free)?;
+        self.free.push(page_id);
+        Ok(())
+    }
+
+    fn sync(&mut self) -> Result<(), DatabaseError> {
+        Ok(())
+    }
+
+    fn page_count(&self) -> u32 {
+        self.pages.len() as u32
+    }
+
+    fn stats(&self) -> EngineStats {
+        EngineStats {
+            page_count: self.page_count(),
+            pages_read: self.pages_read,
+            cache_hits: 0,
+            dirty_pages: 0,
+        }
+    }
+}
@@ -1,36 +1,240 @@
 use std::{
-    fs::{File, OpenOptions},
+    collections::{BTreeMap, HashMap},
+    fs::{File, OpenOptions, TryLockError},
     io::{Read, Seek, SeekFrom, Write},
-    path::Path,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
 };
 
+use ::storage::Argon2Params;
+
 use crate::{
     common::DatabaseError,
-    storage::page::{Page, PageType, PAGE_SIZE},
+    storage::{
+        buffer_pool::BufferPool,
+        codec::{AES_GCM_CODEC_ID, CodecRegistry, LZ4_CODEC_ID, PageCodec},
+        compression::Lz4Codec,
+        double_write::{DoubleWriteBuffer, read_double_write},
+        encryption::{EncryptionCodec, key_derivation_path},
+        flusher::{FlushBatch, FlushSignal},
+        packed::{PackedPages, packed_pages_path},
+        page::{PAGE_CODEC_ID_OFFSET, PAGE_HEADER_SIZE, PAGE_SIZE, Page, PageHeader, PageType},
+        recovery::PageCheck,
+        tier::{ColdTier, TierPolicy},
+        trace::{StructuralTrace, TraceEvent},
+        warmup::{WarmupPlan, WarmupReport},
+    },
 };
 
+/// How the page file grows when pages are written past its end
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GrowthPolicy {
+    /// Pages the file is extended by at a time, zero filled ahead of use
+    pub extent_pages: u32,
+}
+
+impl GrowthPolicy {
+    /// Grow by extents of at least `bytes`, rounded up to whole pages
+    pub fn extent_bytes(bytes: u64) -> Self {
+        Self {
+            extent_pages: bytes.div_ceil(PAGE_SIZE as u64).max(1) as u32,
+        }
+    }
+}
+
+impl Default for GrowthPolicy {
+    /// One page at a time, the file holds no pages ahead of use
+    fn default() -> Self {
+        Self { extent_pages: 1 }
+    }
+}
+
+/// Pages of one collection in a file shared by several collections,
+/// see `FileManager::collection_pages`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CollectionPages {
+    /// Data, overflow and other pages in use by the collection
+    pub used: u32,
+    /// Pages the collection freed, only its own inserts reuse them
+    pub free: u32,
+}
+
 /// Manages file I/O operations for pages
 pub struct FileManager {
     file: File,
+    path: PathBuf,
     page_count: u32,
+    /// Pages the file has room for, more than `page_count` once an extent was preallocated
+    file_pages: u32,
+    growth: GrowthPolicy,
+    trace: Option<StructuralTrace>,
+    cold: Option<ColdTier>,
+    /// Page reads since the last tier migration, only tracked with a cold tier
+    reads: HashMap<u32, u32>,
+    codecs: CodecRegistry,
+    /// Codecs new page writes are encoded with in turn, e.g. compression then encryption
+    write_codecs: Vec<Arc<dyn PageCodec>>,
+    /// Pages short enough once encoded to be packed into the slots of a second file,
+    /// see `enable_compression`
+    packed: Option<PackedPages>,
+    pool: Option<BufferPool>,
+    /// Pages read from the files, pages served from the buffer pool are not counted
+    pages_read: u64,
+    /// Encoded pages written since the last flush, not in the files yet
+    dirty: BTreeMap<u32, Box<[u8; PAGE_SIZE]>>,
+    /// Copies of the pages of a flush, written before the pages, see `enable_double_write`
+    double_write: Option<DoubleWriteBuffer>,
+    /// Number of completed flushes, locked while a flush writes, see `FlushBatch`
+    flush_generation: Arc<Mutex<u64>>,
+    /// Background flusher to wake once this many pages are dirty
+    flush_signal: Option<(Arc<FlushSignal>, usize)>,
+    /// Collection id and type of each page, `None` for pages without a readable header. Read
+    /// from the page headers the first time it is needed, kept up to date by page writes.
+    page_owners: Option<Vec<Option<(u32, PageType)>>>,
 }
 
 impl FileManager {
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, DatabaseError> {
         let file = OpenOptions::new()
             .create(true)
+            .truncate(false)
             .read(true)
             .write(true)
-            .open(path)?;
+            .open(&path)?;
+        // A second writer would silently overwrite the pages of the first,
+        // the lock is released when the file is closed
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                return Err(DatabaseError::Locked(format!(
+                    "{} is open by another writer",
+                    path.as_ref().display()
+                )));
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
 
         // Calculate page count from file size
         let file_size = file.metadata()?.len();
-        let page_count = (file_size / (PAGE_SIZE as u64)) as u32;
+        let file_pages = (file_size / (PAGE_SIZE as u64)) as u32;
+
+        let mut file_manager = Self {
+            file,
+            path: path.as_ref().to_path_buf(),
+            page_count: file_pages,
+            file_pages,
+            growth: GrowthPolicy::default(),
+            trace: None,
+            cold: None,
+            reads: HashMap::new(),
+            codecs: CodecRegistry::new(),
+            write_codecs: Vec::new(),
+            packed: None,
+            pool: None,
+            pages_read: 0,
+            dirty: BTreeMap::new(),
+            double_write: None,
+            flush_generation: Arc::new(Mutex::new(0)),
+            flush_signal: None,
+            page_owners: None,
+        };
+        // Packed pages are read once their codecs are registered again
+        if packed_pages_path(path.as_ref()).exists() {
+            file_manager.packed = Some(PackedPages::open(packed_pages_path(path.as_ref()))?);
+        }
+        file_manager.page_count = file_manager.used_pages()?;
+        Ok(file_manager)
+    }
+
+    /// Pages up to the last page that is not all zeros, preallocated pages were never
+    /// written and every written page starts with the page magic. Packed pages may lie
+    /// beyond the end of the file.
+    fn used_pages(&mut self) -> Result<u32, DatabaseError> {
+        let mut buffer = [0u8; PAGE_SIZE];
+        let mut used = self.file_pages;
+        while used > 0 {
+            self.read_hot(used - 1, &mut buffer)?;
+            if buffer.iter().any(|&byte| byte != 0) {
+                break;
+            }
+            used -= 1;
+        }
+        Ok(used.max(self.packed.as_ref().map_or(0, PackedPages::page_end)))
+    }
+
+    /// Extend the file by whole extents until it has room for `pages` pages
+    fn reserve(&mut self, pages: u32) -> Result<(), DatabaseError> {
+        if pages <= self.file_pages || self.growth.extent_pages <= 1 {
+            return Ok(());
+        }
+
+        let extent = self.growth.extent_pages;
+        let target = pages.div_ceil(extent) * extent;
+        // Zeros are written rather than setting the length, so the file system allocates
+        // the extent now instead of leaving a hole to fill page by page
+        let zeros = vec![0u8; PAGE_SIZE * 64];
+        let mut offset = self.file_pages as u64 * PAGE_SIZE as u64;
+        let end = target as u64 * PAGE_SIZE as u64;
+        self.file.seek(SeekFrom::Start(offset))?;
+        while offset < end {
+            let length = (end - offset).min(zeros.len() as u64) as usize;
+            self.file.write_all(&zeros[..length])?;
+            offset += length as u64;
+        }
+        self.trace(TraceEvent::FilePreallocated {
+            from_pages: self.file_pages,
+            to_pages: target,
+        })?;
+        self.file_pages = target;
+        Ok(())
+    }
+
+    /// Grow the file in extents from now on, see `GrowthPolicy`
+    pub fn set_growth_policy(&mut self, growth: GrowthPolicy) {
+        self.growth = growth;
+    }
+
+    pub fn growth_policy(&self) -> GrowthPolicy {
+        self.growth
+    }
+
+    /// Pages the file has room for, including preallocated pages not in use yet
+    pub fn allocated_pages(&self) -> u32 {
+        self.file_pages
+    }
 
-        Ok(Self { file, page_count })
+    /// Empty page file at `path` that reads and writes pages with the same codecs,
+    /// and packs them if this file does
+    pub fn create_like<P: AsRef<Path>>(&self, path: P) -> Result<Self, DatabaseError> {
+        let mut file_manager = Self::new(&path)?;
+        file_manager.codecs = self.codecs.clone();
+        file_manager.write_codecs = self.write_codecs.clone();
+        if self.packed.is_some() && file_manager.packed.is_none() {
+            file_manager.packed = Some(PackedPages::open(packed_pages_path(path.as_ref()))?);
+        }
+        Ok(file_manager)
+    }
+
+    /// Switch to the page file at `path`, e.g. a compacted copy renamed over the current
+    /// file. Cached pages and pages written since the last flush are dropped.
+    pub fn reopen<P: AsRef<Path>>(&mut self, path: P) -> Result<(), DatabaseError> {
+        self.file = OpenOptions::new().read(true).write(true).open(&path)?;
+        self.path = path.as_ref().to_path_buf();
+        if self.packed.is_some() || packed_pages_path(&self.path).exists() {
+            self.packed = Some(PackedPages::open(packed_pages_path(&self.path))?);
+        }
+        self.file_pages = (self.file.metadata()?.len() / (PAGE_SIZE as u64)) as u32;
+        self.page_count = self.used_pages()?;
+        self.page_owners = None;
+        self.reads.clear();
+        self.dirty.clear();
+        if let Some(pool) = &mut self.pool {
+            pool.clear();
+        }
+        Ok(())
     }
 
-    /// Read a page from file
+    /// Read a page, from the buffer pool if it is cached there
     pub fn read_page(&mut self, page_id: u32) -> Result<Page, DatabaseError> {
         if page_id >= self.page_count {
             return Err(DatabaseError::InvalidData(
@@ -38,32 +242,311 @@ impl FileManager {
             ));
         }
 
-        let offset = (page_id as u64) * (PAGE_SIZE as u64);
-        self.file.seek(SeekFrom::Start(offset))?;
+        if let Some(page) = self.pool.as_mut().and_then(|pool| pool.get(page_id)) {
+            return Ok(page);
+        }
+
+        self.pages_read += 1;
+        let page = self.load_page(page_id)?;
+        if let Some(pool) = &mut self.pool {
+            pool.insert(page_id, page.clone());
+        }
+        Ok(page)
+    }
+
+    /// Read a page from the file, pages served from the buffer pool
+    /// do not count as reads for tier migration
+    fn load_page(&mut self, page_id: u32) -> Result<Page, DatabaseError> {
+        let mut buffer = [0u8; PAGE_SIZE];
+        let dirty = self.dirty.get(&page_id);
+        if let Some(bytes) = dirty {
+            buffer.copy_from_slice(bytes.as_slice());
+        } else if self.is_packed(page_id) {
+            self.read_packed(page_id, &mut buffer)?;
+            self.codecs.decode_page(page_id, &mut buffer)?;
+            return Page::deserialize(page_id, &buffer);
+        }
+        let Some(cold) = &mut self.cold else {
+            if dirty.is_none() {
+                self.read_hot(page_id, &mut buffer)?;
+            }
+            self.codecs.decode_page(page_id, &mut buffer)?;
+            return Page::deserialize(page_id, &buffer);
+        };
+
+        let reads = self.reads.entry(page_id).or_default();
+        *reads += 1;
+
+        if cold.contains(page_id) {
+            if dirty.is_none() {
+                cold.read(page_id, &mut buffer)?;
+            }
+            if *reads >= cold.policy.promote_after_reads {
+                // A dirty page is written by the promotion
+                self.dirty.remove(&page_id);
+                self.promote(page_id, &buffer)?;
+            }
+        } else if dirty.is_none() {
+            self.read_hot(page_id, &mut buffer)?;
+        }
+
+        self.codecs.decode_page(page_id, &mut buffer)?;
+        Page::deserialize(page_id, &buffer)
+    }
 
+    /// Read a page for the integrity pass on open, bypassing the buffer pool.
+    /// Damage is returned as the check outcome, only I/O errors and pages encoded with
+    /// a codec that is not registered are errors, the latter are not damaged.
+    pub fn check_page(&mut self, page_id: u32) -> Result<PageCheck, DatabaseError> {
         let mut buffer = [0u8; PAGE_SIZE];
-        self.file.read_exact(&mut buffer)?;
+        match &mut self.cold {
+            _ if self.dirty.contains_key(&page_id) => {
+                buffer.copy_from_slice(self.dirty[&page_id].as_slice())
+            }
+            _ if self
+                .packed
+                .as_ref()
+                .is_some_and(|packed| packed.contains(page_id)) =>
+            {
+                if let Some(packed) = &mut self.packed {
+                    packed.read(page_id, &mut buffer)?
+                }
+            }
+            Some(cold) if cold.contains(page_id) => cold.read(page_id, &mut buffer)?,
+            _ => self.read_hot(page_id, &mut buffer)?,
+        }
 
-        Page::deserialize(&buffer)
+        if let Err(e) = PageHeader::deserialize(&buffer[..PAGE_HEADER_SIZE]) {
+            return Ok(PageCheck::Unreadable(format!("{:?}", e)));
+        }
+        let codec_id = buffer[PAGE_CODEC_ID_OFFSET];
+        if codec_id != 0 && self.codecs.get(codec_id).is_none() {
+            return Err(DatabaseError::InvalidData(format!(
+                "Page {} is encoded with unknown codec {}",
+                page_id, codec_id
+            )));
+        }
+
+        let page = match self
+            .codecs
+            .decode_page(page_id, &mut buffer)
+            .and_then(|_| Page::deserialize_unverified(&buffer))
+        {
+            Ok(page) => page,
+            Err(DatabaseError::IoError(e)) => return Err(e.into()),
+            Err(e) => return Ok(PageCheck::Unreadable(format!("{:?}", e))),
+        };
+
+        let actual = page.compute_checksum();
+        if actual != page.header.checksum {
+            return Ok(PageCheck::ChecksumMismatch {
+                expected: page.header.checksum,
+                page,
+                actual,
+            });
+        }
+        Ok(PageCheck::Valid(page))
     }
 
-    /// Write a page to file
+    /// Cut off an incomplete page at the end of the file, left by a crash while the file
+    /// grew. Returns the number of bytes removed.
+    pub fn truncate_partial_page(&mut self) -> Result<u64, DatabaseError> {
+        let size = self.file.metadata()?.len();
+        let partial = size % PAGE_SIZE as u64;
+        if partial > 0 {
+            self.file.set_len(size - partial)?;
+            self.file.sync_all()?;
+            self.file_pages = (size / PAGE_SIZE as u64) as u32;
+        }
+        Ok(partial)
+    }
+
+    /// Write a page, kept in memory as a dirty page until the next `flush`.
+    /// Writing a page again before the flush replaces it, so a page updated by every
+    /// insert into it reaches the file once.
     pub fn write_page(&mut self, page_id: u32, page: &mut Page) -> Result<(), DatabaseError> {
-        let offset = (page_id as u64) * (PAGE_SIZE as u64);
-        self.file.seek(SeekFrom::Start(offset))?;
+        let mut page_bytes = page.serialize();
+        CodecRegistry::encode_page_with(&self.write_codecs, page_id, &mut page_bytes)?;
 
-        let page_bytes = page.serialize();
-        self.file.write_all(&page_bytes)?;
-        self.file.flush()?;
+        self.dirty.insert(page_id, Box::new(page_bytes));
+        if let Some(pool) = &mut self.pool {
+            pool.insert(page_id, page.clone());
+        }
+        self.set_page_owner(
+            page_id,
+            Some((page.header.collection_id, page.header.page_type)),
+        );
+        if let Some((signal, watermark)) = &self.flush_signal
+            && self.dirty.len() >= *watermark
+        {
+            signal.notify();
+        }
 
         // Update page count if we wrote beyond current file size
         if page_id >= self.page_count {
+            self.trace(TraceEvent::FileGrown {
+                from_pages: self.page_count,
+                to_pages: page_id + 1,
+            })?;
             self.page_count = page_id + 1;
         }
 
         Ok(())
     }
 
+    /// Write the dirty pages to the files in page id order and sync them, then pack the
+    /// pages short enough to be packed. Pages written since the last flush are lost on
+    /// a crash.
+    pub fn flush(&mut self) -> Result<(), DatabaseError> {
+        if self.dirty.is_empty() {
+            return Ok(());
+        }
+
+        // Waits for a background flush in progress, which then skips its older copies
+        let generation = Arc::clone(&self.flush_generation);
+        let mut completed = generation.lock().unwrap_or_else(PoisonError::into_inner);
+        let (packed, dirty): (Vec<_>, Vec<_>) = std::mem::take(&mut self.dirty)
+            .into_iter()
+            .partition(|(page_id, page_bytes)| self.packs(*page_id, page_bytes));
+        self.dirty = dirty.into_iter().collect();
+        // The packed copy of a page written to the file is dropped once the file is synced,
+        // a crash before leaves the packed copy
+        let unpacked: Vec<u32> = self
+            .dirty
+            .keys()
+            .copied()
+            .filter(|page_id| self.is_packed(*page_id))
+            .collect();
+        self.reserve_for_dirty()?;
+        if let Some(double_write) = &mut self.double_write {
+            let cold = self.cold.as_ref();
+            double_write.stage(
+                self.dirty
+                    .iter()
+                    .filter(|(page_id, _)| !cold.is_some_and(|cold| cold.contains(**page_id))),
+            )?;
+        }
+        for (page_id, page_bytes) in std::mem::take(&mut self.dirty) {
+            match &mut self.cold {
+                // Cold pages are updated in place, only reads promote them
+                Some(cold) if cold.contains(page_id) => cold.write(page_id, &page_bytes)?,
+                _ => self.write_hot(page_id, &page_bytes)?,
+            }
+        }
+        self.file.sync_data()?;
+        if let Some(store) = &mut self.packed {
+            store.write(&packed, &unpacked)?;
+        }
+        if let Some(double_write) = &mut self.double_write {
+            double_write.clear()?;
+        }
+        *completed += 1;
+        Ok(())
+    }
+
+    /// Copy the dirty pages for a flush on another thread, see `BackgroundFlusher`.
+    /// Pages of the cold tier and packed pages are left for `flush`.
+    pub fn begin_flush(&mut self) -> Result<Option<FlushBatch>, DatabaseError> {
+        let cold = self.cold.as_ref();
+        let pages: BTreeMap<u32, Box<[u8; PAGE_SIZE]>> = self
+            .dirty
+            .iter()
+            .filter(|(page_id, _)| !cold.is_some_and(|cold| cold.contains(**page_id)))
+            .filter(|(page_id, page_bytes)| {
+                !self.is_packed(**page_id) && !self.packs(**page_id, page_bytes)
+            })
+            .map(|(page_id, page_bytes)| (*page_id, page_bytes.clone()))
+            .collect();
+        if pages.is_empty() {
+            return Ok(None);
+        }
+        self.reserve_for_dirty()?;
+
+        Ok(Some(FlushBatch {
+            path: self.path.clone(),
+            pages,
+            double_write: match &self.double_write {
+                Some(double_write) => Some(double_write.try_clone()?),
+                None => None,
+            },
+            generation: *self
+                .flush_generation
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+            completed: Arc::clone(&self.flush_generation),
+        }))
+    }
+
+    /// Preallocate the extents the dirty pages are written to, then count the pages
+    /// written past the end of the file as part of it
+    fn reserve_for_dirty(&mut self) -> Result<(), DatabaseError> {
+        if let Some((&last, _)) = self.dirty.last_key_value() {
+            self.reserve(last + 1)?;
+            self.file_pages = self.file_pages.max(last + 1);
+        }
+        Ok(())
+    }
+
+    /// Forget the dirty pages a batch wrote, unless they were written again since
+    pub fn finish_flush(&mut self, batch: &FlushBatch) {
+        for (page_id, page_bytes) in &batch.pages {
+            if self.dirty.get(page_id) == Some(page_bytes) {
+                self.dirty.remove(page_id);
+            }
+        }
+    }
+
+    /// Wake the background flusher once the dirty pages reach the watermark
+    pub(crate) fn set_flush_signal(&mut self, signal: Arc<FlushSignal>, watermark: usize) {
+        self.flush_signal = Some((signal, watermark.max(1)));
+    }
+
+    pub(crate) fn clear_flush_signal(&mut self) {
+        self.flush_signal = None;
+    }
+
+    /// Write every page to the double-write file at `path` before writing it in place,
+    /// so a crash while a page is written cannot leave it half old and half new.
+    /// Pages of a flush interrupted before are restored first, their number is returned.
+    /// Pages of the cold tier are not covered.
+    pub fn enable_double_write<P: AsRef<Path>>(&mut self, path: P) -> Result<u32, DatabaseError> {
+        let restored = self.restore_double_write(path.as_ref())?;
+        self.double_write = Some(DoubleWriteBuffer::open(path)?);
+        Ok(restored)
+    }
+
+    pub fn disable_double_write(&mut self) {
+        self.double_write = None;
+    }
+
+    /// Write the pages staged in the double-write file at `path` by a flush that did not
+    /// finish over the pages in place, then empty the file. Returns the number of pages
+    /// restored, none if there is no file.
+    pub fn restore_double_write(&mut self, path: &Path) -> Result<u32, DatabaseError> {
+        let images = read_double_write(path)?;
+        for (page_id, page_bytes) in &images {
+            self.write_hot(*page_id, page_bytes)?;
+            self.page_count = self.page_count.max(page_id + 1);
+            self.file_pages = self.file_pages.max(page_id + 1);
+            if let Some(pool) = &mut self.pool {
+                pool.remove(*page_id);
+            }
+        }
+        if !images.is_empty() {
+            self.file.sync_data()?;
+            self.page_owners = None;
+        }
+        if path.exists() {
+            DoubleWriteBuffer::open(path)?.clear()?;
+        }
+        Ok(images.len() as u32)
+    }
+
+    /// Pages written since the last flush
+    pub fn dirty_page_count(&self) -> usize {
+        self.dirty.len()
+    }
+
     /// Allocate a new page
     pub fn allocate_page(
         &mut self,
@@ -71,12 +554,426 @@ impl FileManager {
         collection_id: u32,
     ) -> Result<(u32, Page), DatabaseError> {
         let page_id = self.page_count;
-        let page = Page::new(page_type, collection_id);
+        let page = self.new_page(page_type, collection_id);
         self.page_count += 1;
+        self.set_page_owner(page_id, Some((collection_id, page_type)));
+        self.trace(TraceEvent::PageAllocated {
+            page_id,
+            page_type,
+            collection_id,
+        })?;
         Ok((page_id, page))
     }
 
+    /// Pages of the collection, counted from the page headers. Pages are allocated to a
+    /// collection and keep its id when freed, so each collection reuses only its own pages.
+    pub fn collection_pages(
+        &mut self,
+        collection_id: u32,
+    ) -> Result<CollectionPages, DatabaseError> {
+        let mut pages = CollectionPages::default();
+        for (owner, page_type) in self.page_owners()?.iter().flatten() {
+            if *owner != collection_id {
+                continue;
+            }
+            match page_type {
+                PageType::FreePage => pages.free += 1,
+                _ => pages.used += 1,
+            }
+        }
+        Ok(pages)
+    }
+
+    /// Ids of the collections with pages in the file, in ascending order
+    pub fn collection_ids(&mut self) -> Result<Vec<u32>, DatabaseError> {
+        let mut ids: Vec<u32> = self
+            .page_owners()?
+            .iter()
+            .flatten()
+            .map(|(owner, _)| *owner)
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        Ok(ids)
+    }
+
+    fn page_owners(&mut self) -> Result<&[Option<(u32, PageType)>], DatabaseError> {
+        if self.page_owners.is_none() {
+            let mut owners = Vec::with_capacity(self.page_count as usize);
+            let mut buffer = [0u8; PAGE_SIZE];
+            for page_id in 0..self.page_count {
+                match &mut self.cold {
+                    _ if self.dirty.contains_key(&page_id) => {
+                        buffer.copy_from_slice(self.dirty[&page_id].as_slice())
+                    }
+                    _ if self
+                        .packed
+                        .as_ref()
+                        .is_some_and(|packed| packed.contains(page_id)) =>
+                    {
+                        if let Some(packed) = &mut self.packed {
+                            packed.read(page_id, &mut buffer)?
+                        }
+                    }
+                    Some(cold) if cold.contains(page_id) => cold.read(page_id, &mut buffer)?,
+                    // Allocated pages are only in the file once they were written
+                    _ if page_id >= self.file_pages => buffer.fill(0),
+                    _ => self.read_hot(page_id, &mut buffer)?,
+                }
+                owners.push(
+                    PageHeader::deserialize(&buffer[..PAGE_HEADER_SIZE])
+                        .ok()
+                        .map(|header| (header.collection_id, header.page_type)),
+                );
+            }
+            self.page_owners = Some(owners);
+        }
+        Ok(self.page_owners.as_deref().unwrap_or_default())
+    }
+
+    fn set_page_owner(&mut self, page_id: u32, owner: Option<(u32, PageType)>) {
+        if let Some(owners) = &mut self.page_owners {
+            if owners.len() <= page_id as usize {
+                owners.resize(page_id as usize + 1, None);
+            }
+            owners[page_id as usize] = owner;
+        }
+    }
+
+    /// Empty page with room for the overhead of the write codec, e.g. to reuse a freed page
+    pub fn new_page(&self, page_type: PageType, collection_id: u32) -> Page {
+        let mut page = Page::new(page_type, collection_id);
+        page.header.free_space_size -= self.reserved_bytes() as u16;
+        page
+    }
+
+    /// Bytes at the end of each page kept free for the overhead of the write codecs
+    pub fn reserved_bytes(&self) -> usize {
+        CodecRegistry::chained_reserved_bytes(&self.write_codecs)
+    }
+
+    /// Make a codec available for reading pages encoded with it
+    pub fn register_codec(&mut self, codec: Arc<dyn PageCodec>) -> Result<(), DatabaseError> {
+        self.codecs.register(codec)
+    }
+
+    /// Encode page writes with a registered codec, or write pages as they are with `None`.
+    /// Only pages allocated while the codec is active have room for its overhead,
+    /// writing an older full page with a codec that grows data fails.
+    pub fn set_write_codec(&mut self, id: Option<u8>) -> Result<(), DatabaseError> {
+        self.write_codecs = match id {
+            Some(id) => vec![self.codecs.get(id).cloned().ok_or_else(|| {
+                DatabaseError::InvalidData(format!("Codec {} is not registered", id))
+            })?],
+            None => Vec::new(),
+        };
+        Ok(())
+    }
+
+    /// Compress the pages written from now on with the built-in `Lz4Codec`, before any
+    /// other write codec, e.g. encryption. Compressed pages are packed several to a slot
+    /// of the `<path>.packed` file next to the page file, see `PackedPages`.
+    /// A file with compressed pages is read after enabling compression, or registering
+    /// the codec, again when it is reopened.
+    pub fn enable_compression(&mut self) -> Result<(), DatabaseError> {
+        let lz4 = match self.codecs.get(LZ4_CODEC_ID) {
+            Some(codec) => Arc::clone(codec),
+            None => {
+                let codec: Arc<dyn PageCodec> = Arc::new(Lz4Codec);
+                self.codecs.register(Arc::clone(&codec))?;
+                codec
+            }
+        };
+        if !self
+            .write_codecs
+            .iter()
+            .any(|codec| codec.id() == LZ4_CODEC_ID)
+        {
+            self.write_codecs.insert(0, lz4);
+        }
+        if self.packed.is_none() {
+            self.packed = Some(PackedPages::open(packed_pages_path(&self.path))?);
+        }
+        Ok(())
+    }
+
+    /// Encrypt the pages written from now on with the codec, see `EncryptionCodec`,
+    /// after compressing them if compression is enabled.
+    /// A file with encrypted pages is read after enabling encryption with its keys again
+    /// when it is reopened.
+    pub fn enable_encryption(&mut self, codec: EncryptionCodec) -> Result<(), DatabaseError> {
+        let codec: Arc<dyn PageCodec> = Arc::new(codec);
+        self.codecs.register(Arc::clone(&codec))?;
+        self.write_codecs
+            .retain(|codec| codec.id() != AES_GCM_CODEC_ID);
+        self.write_codecs.push(codec);
+        Ok(())
+    }
+
+    /// Encrypt the pages written from now on with the key derived from the passphrase,
+    /// see `EncryptionCodec::open_with_passphrase`. The salt and Argon2 parameters are
+    /// kept in the `<path>.key` file next to the page file.
+    pub fn enable_encryption_with_passphrase(
+        &mut self,
+        passphrase: &str,
+        params: Argon2Params,
+    ) -> Result<(), DatabaseError> {
+        self.enable_encryption(EncryptionCodec::open_with_passphrase(
+            key_derivation_path(&self.path),
+            passphrase,
+            params,
+        )?)
+    }
+
+    /// Store rarely read pages in a second file on slower storage.
+    /// Pages already moved to the cold file by a previous session are found again.
+    pub fn enable_cold_tier<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        policy: TierPolicy,
+    ) -> Result<(), DatabaseError> {
+        self.cold = Some(ColdTier::open(path, policy)?);
+        self.reads.clear();
+        Ok(())
+    }
+
+    /// Move fast tier pages read less than the policy allows since the last migration
+    /// to the cold tier, and start a new read counting window.
+    /// The fast tier file keeps the page positions so page ids stay stable.
+    /// Returns the number of demoted pages, always 0 without a cold tier.
+    /// Packed pages stay packed.
+    pub fn migrate_cold_pages(&mut self) -> Result<usize, DatabaseError> {
+        if self.cold.is_none() {
+            return Ok(0);
+        }
+        self.flush()?;
+        let packed = self.packed.as_ref();
+        let Some(cold) = &mut self.cold else {
+            return Ok(0);
+        };
+
+        // Allocated pages are only in the file once they were written
+        let written_pages = (self.file.metadata()?.len() / PAGE_SIZE as u64) as u32;
+        let mut demoted = Vec::new();
+        let mut buffer = [0u8; PAGE_SIZE];
+
+        for page_id in 0..written_pages.min(self.page_count) {
+            let reads = self.reads.get(&page_id).copied().unwrap_or(0);
+            if cold.contains(page_id)
+                || reads >= cold.policy.demote_below_reads
+                || packed.is_some_and(|packed| packed.contains(page_id))
+            {
+                continue;
+            }
+
+            let offset = (page_id as u64) * (PAGE_SIZE as u64);
+            self.file.seek(SeekFrom::Start(offset))?;
+            self.file.read_exact(&mut buffer)?;
+            cold.write(page_id, &buffer)?;
+            demoted.push(page_id);
+        }
+
+        self.reads.clear();
+        for page_id in &demoted {
+            self.trace(TraceEvent::PageDemoted { page_id: *page_id })?;
+        }
+        Ok(demoted.len())
+    }
+
+    /// Cache up to `capacity` recently used pages in memory
+    pub fn enable_buffer_pool(&mut self, capacity: usize) {
+        self.pool = Some(BufferPool::new(capacity));
+    }
+
+    /// Pages `read_page` loaded from the files since they were opened, warmup excluded
+    pub fn pages_read(&self) -> u64 {
+        self.pages_read
+    }
+
+    /// Reads served from the buffer pool since it was enabled
+    pub fn cache_hits(&self) -> u64 {
+        self.pool.as_ref().map_or(0, |pool| pool.hits)
+    }
+
+    pub fn buffer_pool(&self) -> Option<&BufferPool> {
+        self.pool.as_ref()
+    }
+
+    /// Load the pages selected by the plan into the buffer pool, so the first queries after
+    /// a restart do not wait for the disk: header and meta pages first, then index pages,
+    /// then the data pages of the planned collections in their order.
+    /// Only page headers are read to select pages, loading stops when the pool is full.
+    pub fn warmup(&mut self, plan: &WarmupPlan) -> Result<WarmupReport, DatabaseError> {
+        let Some(capacity) = self.pool.as_ref().map(BufferPool::capacity) else {
+            return Err(DatabaseError::InvalidQuery(
+                "Warmup requires a buffer pool".to_string(),
+            ));
+        };
+
+        // Allocated pages are only in the file once they were written
+        self.flush()?;
+        let written_pages = (self.file.metadata()?.len() / PAGE_SIZE as u64) as u32;
+        let mut metadata = Vec::new();
+        let mut indexes = Vec::new();
+        let mut collections: Vec<Vec<u32>> = vec![Vec::new(); plan.collections.len()];
+
+        let mut buffer = [0u8; PAGE_SIZE];
+        for page_id in 0..self.page_count {
+            if self.is_packed(page_id) {
+                self.read_packed(page_id, &mut buffer)?;
+            } else if page_id < written_pages {
+                let offset = (page_id as u64) * (PAGE_SIZE as u64);
+                self.file.seek(SeekFrom::Start(offset))?;
+                self.file.read_exact(&mut buffer[..PAGE_HEADER_SIZE])?;
+            } else {
+                continue;
+            }
+            let header = PageHeader::deserialize(&buffer[..PAGE_HEADER_SIZE])?;
+
+            match header.page_type {
+                PageType::HeaderPage | PageType::MetaPage if plan.metadata => {
+                    metadata.push(page_id)
+                }
+                PageType::IndexPage if plan.indexes => indexes.push(page_id),
+                PageType::DataPage => {
+                    if let Some(position) = plan
+                        .collections
+                        .iter()
+                        .position(|id| *id == header.collection_id)
+                    {
+                        collections[position].push(page_id);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let selected: Vec<u32> = metadata
+            .into_iter()
+            .chain(indexes)
+            .chain(collections.into_iter().flatten())
+            .collect();
+        let loaded = selected.len().min(capacity);
+
+        // Load in reverse so the most important pages end up most recently used
+        for &page_id in selected[..loaded].iter().rev() {
+            if self
+                .pool
+                .as_ref()
+                .is_some_and(|pool| pool.contains(page_id))
+            {
+                continue;
+            }
+            let page = self.load_page(page_id)?;
+            if let Some(pool) = &mut self.pool {
+                pool.insert(page_id, page);
+            }
+        }
+
+        Ok(WarmupReport {
+            pages_loaded: loaded,
+            pages_skipped: selected.len() - loaded,
+        })
+    }
+
+    /// Number of pages currently stored in the cold tier
+    pub fn cold_page_count(&self) -> usize {
+        self.cold.as_ref().map_or(0, ColdTier::page_count)
+    }
+
+    /// Move a frequently read cold page back to the fast tier
+    fn promote(&mut self, page_id: u32, page_bytes: &[u8; PAGE_SIZE]) -> Result<(), DatabaseError> {
+        // The fast tier copy must be current before the cold slot is released
+        self.write_hot(page_id, page_bytes)?;
+        if let Some(cold) = &mut self.cold {
+            cold.remove(page_id)?;
+        }
+        self.trace(TraceEvent::PagePromoted { page_id })
+    }
+
+    fn is_packed(&self, page_id: u32) -> bool {
+        self.packed
+            .as_ref()
+            .is_some_and(|packed| packed.contains(page_id))
+    }
+
+    /// The encoded page is written to the packed pages file by the next flush
+    fn packs(&self, page_id: u32, page_bytes: &[u8; PAGE_SIZE]) -> bool {
+        self.packed.is_some()
+            && !self
+                .cold
+                .as_ref()
+                .is_some_and(|cold| cold.contains(page_id))
+            && PackedPages::fits(page_bytes)
+    }
+
+    fn read_packed(
+        &mut self,
+        page_id: u32,
+        buffer: &mut [u8; PAGE_SIZE],
+    ) -> Result<(), DatabaseError> {
+        match &mut self.packed {
+            Some(packed) => packed.read(page_id, buffer),
+            None => Err(DatabaseError::InvalidData(format!(
+                "Page {} is not packed",
+                page_id
+            ))),
+        }
+    }
+
+    fn read_hot(
+        &mut self,
+        page_id: u32,
+        buffer: &mut [u8; PAGE_SIZE],
+    ) -> Result<(), DatabaseError> {
+        let offset = (page_id as u64) * (PAGE_SIZE as u64);
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(buffer)?;
+        Ok(())
+    }
+
+    fn write_hot(
+        &mut self,
+        page_id: u32,
+        page_bytes: &[u8; PAGE_SIZE],
+    ) -> Result<(), DatabaseError> {
+        let offset = (page_id as u64) * (PAGE_SIZE as u64);
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(page_bytes)?;
+        self.file.flush()?;
+        Ok(())
+    }
+
+    /// Record structural operations in an append-only trace file
+    pub fn enable_trace<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        max_size: u64,
+    ) -> Result<(), DatabaseError> {
+        self.trace = Some(StructuralTrace::open(path, max_size)?);
+        Ok(())
+    }
+
+    pub fn disable_trace(&mut self) {
+        self.trace = None;
+    }
+
+    /// Append an event to the structural trace if it is enabled
+    pub fn trace(&mut self, event: TraceEvent) -> Result<(), DatabaseError> {
+        match &mut self.trace {
+            Some(trace) => trace.record(event),
+            None => Ok(()),
+        }
+    }
+
     pub fn page_count(&self) -> u32 {
         self.page_count
     }
 }
+
+impl Drop for FileManager {
+    fn drop(&mut self) {
+        // Pages written since the last flush
+        let _ = self.flush();
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::{Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::{Arc, Condvar, Mutex, PoisonError},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    common::DatabaseError,
+    storage::{double_write::DoubleWriteBuffer, file_manager::FileManager, page::PAGE_SIZE},
+};
+
+/// When the background flusher writes the dirty pages
+#[derive(Debug, Clone, Copy)]
+pub struct FlusherConfig {
+    /// Longest time a written page stays dirty
+    pub interval: Duration,
+    /// Flush early once this many pages are dirty
+    pub dirty_watermark: usize,
+}
+
+impl Default for FlusherConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(200),
+            dirty_watermark: 256,
+        }
+    }
+}
+
+#[derive(Default)]
+struct SignalState {
+    /// The dirty pages reached the watermark
+    pending: bool,
+    stopped: bool,
+}
+
+/// Wakes the flusher thread before its interval is up, also used by `ExpirySweeper`
+#[derive(Default)]
+pub struct FlushSignal {
+    state: Mutex<SignalState>,
+    condvar: Condvar,
+}
+
+impl FlushSignal {
+    pub fn notify(&self) {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pending = true;
+        self.condvar.notify_one();
+    }
+
+    pub(crate) fn stop(&self) {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .stopped = true;
+        self.condvar.notify_one();
+    }
+
+    /// Wait for a notification or the timeout, true once the flusher is stopped
+    pub(crate) fn wait(&self, timeout: Duration) -> bool {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let (mut state, _) = self
+            .condvar
+            .wait_timeout_while(state, timeout, |state| !state.pending && !state.stopped)
+            .unwrap_or_else(PoisonError::into_inner);
+        state.pending = false;
+        state.stopped
+    }
+}
+
+/// Copies of the dirty pages taken by `FileManager::begin_flush`, written to the file
+/// without holding the file manager
+pub struct FlushBatch {
+    pub(crate) path: PathBuf,
+    pub(crate) pages: BTreeMap<u32, Box<[u8; PAGE_SIZE]>>,
+    pub(crate) double_write: Option<DoubleWriteBuffer>,
+    /// Flushes completed when the batch was taken, shared with the file manager
+    pub(crate) generation: u64,
+    pub(crate) completed: Arc<Mutex<u64>>,
+}
+
+impl FlushBatch {
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Write the pages in page id order, runs of consecutive pages in a single write,
+    /// and sync the file. Skipped if another flush completed since the batch was taken,
+    /// it wrote these pages or newer versions of them.
+    pub fn write(&mut self) -> Result<bool, DatabaseError> {
+        let mut completed = self
+            .completed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if *completed != self.generation {
+            return Ok(false);
+        }
+
+        if let Some(double_write) = &mut self.double_write {
+            double_write.stage(self.pages.iter())?;
+        }
+        let mut file = OpenOptions::new().write(true).open(&self.path)?;
+        let mut run: Vec<u8> = Vec::new();
+        let mut run_start = 0;
+        let mut next_page = None;
+        for (page_id, page_bytes) in &self.pages {
+            if next_page != Some(*page_id) && !run.is_empty() {
+                file.seek(SeekFrom::Start(run_start as u64 * PAGE_SIZE as u64))?;
+                file.write_all(&run)?;
+                run.clear();
+            }
+            if run.is_empty() {
+                run_start = *page_id;
+            }
+            run.extend_from_slice(page_bytes.as_slice());
+            next_page = Some(page_id + 1);
+        }
+        if !run.is_empty() {
+            file.seek(SeekFrom::Start(run_start as u64 * PAGE_SIZE as u64))?;
+            file.write_all(&run)?;
+        }
+        file.sync_data()?;
+        if let Some(double_write) = &mut self.double_write {
+            double_write.clear()?;
+        }
+
+        *completed += 1;
+        Ok(true)
+    }
+}
+
+/// Thread flushing the dirty pages of a shared file manager every interval, or earlier
+/// once the dirty pages reach the watermark. Writes and the sync happen outside the lock,
+/// foreground writes only wait for the copy of the dirty pages. Repeated writes of a page
+/// between flushes reach the file once. The pages left dirty are flushed when the flusher
+/// is stopped or dropped.
+pub struct BackgroundFlusher {
+    signal: Arc<FlushSignal>,
+    handle: Option<JoinHandle<()>>,
+    error: Arc<Mutex<Option<DatabaseError>>>,
+}
+
+impl BackgroundFlusher {
+    /// Start flushing the file manager `pages` reaches in the shared value,
+    /// e.g. `|collection: &mut PagedCollection| &mut collection.file_manager`
+    pub fn start<T: Send + 'static>(
+        shared: Arc<Mutex<T>>,
+        pages: fn(&mut T) -> &mut FileManager,
+        config: FlusherConfig,
+    ) -> Self {
+        let signal = Arc::new(FlushSignal::default());
+        pages(&mut shared.lock().unwrap_or_else(PoisonError::into_inner))
+            .set_flush_signal(Arc::clone(&signal), config.dirty_watermark);
+
+        let error = Arc::new(Mutex::new(None));
+        let handle = {
+            let signal = Arc::clone(&signal);
+            let error = Arc::clone(&error);
+            thread::spawn(move || {
+                loop {
+                    let stopped = signal.wait(config.interval);
+                    let result = if stopped {
+                        let mut value = shared.lock().unwrap_or_else(PoisonError::into_inner);
+                        let file_manager = pages(&mut value);
+                        file_manager.clear_flush_signal();
+                        file_manager.flush()
+                    } else {
+                        flush_in_background(&shared, pages)
+                    };
+                    if let Err(e) = result {
+                        *error.lock().unwrap_or_else(PoisonError::into_inner) = Some(e);
+                    }
+                    if stopped {
+                        break;
+                    }
+                }
+            })
+        };
+
+        Self {
+            signal,
+            handle: Some(handle),
+            error,
+        }
+    }
+
+    /// Flush the pages left dirty and stop the thread.
+    /// Returns the last error a background flush ran into.
+    pub fn stop(mut self) -> Result<(), DatabaseError> {
+        self.join();
+        match self
+            .error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn join(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            self.signal.stop();
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for BackgroundFlusher {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+fn flush_in_background<T>(
+    shared: &Mutex<T>,
+    pages: fn(&mut T) -> &mut FileManager,
+) -> Result<(), DatabaseError> {
+    let batch = pages(&mut shared.lock().unwrap_or_else(PoisonError::into_inner)).begin_flush()?;
+    let Some(mut batch) = batch else {
+        return Ok(());
+    };
+    let written = batch.write()?;
+    if written {
+        pages(&mut shared.lock().unwrap_or_else(PoisonError::into_inner)).finish_flush(&batch);
+    }
+    Ok(())
+}
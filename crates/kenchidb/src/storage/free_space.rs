@@ -0,0 +1,89 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{common::DatabaseError, query::ByteReader, storage::page::SLOT_SIZE};
+
+/// Pages with less room than this are not tracked
+pub const MIN_TRACKED_SPACE: usize = 64;
+
+/// Pages of a collection file with room for more records, so inserts fill the space
+/// deleted records leave behind before the file grows. Pages without any live record are
+/// free and recycled for the next page allocated. Saved next to the collection file by
+/// `PagedCollection::flush` and rebuilt from the pages when that file cannot be used.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FreeSpaceMap {
+    /// Data page -> bytes available for records once the page is compacted
+    available: BTreeMap<u32, u16>,
+    /// Pages without records, reused lowest page first
+    free_pages: BTreeSet<u32>,
+}
+
+impl FreeSpaceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the space available in a data page
+    pub fn update(&mut self, page_id: u32, available: usize) {
+        self.free_pages.remove(&page_id);
+        if available >= MIN_TRACKED_SPACE {
+            self.available
+                .insert(page_id, available.min(u16::MAX as usize) as u16);
+        } else {
+            self.available.remove(&page_id);
+        }
+    }
+
+    /// Lowest data page with room for a record of `size` bytes and a new slot
+    pub fn find(&self, size: usize) -> Option<u32> {
+        self.available
+            .iter()
+            .find(|(_, available)| **available as usize >= size + SLOT_SIZE)
+            .map(|(page_id, _)| *page_id)
+    }
+
+    /// Bytes available in the page, `None` if it is not tracked
+    pub fn available(&self, page_id: u32) -> Option<usize> {
+        self.available
+            .get(&page_id)
+            .map(|available| *available as usize)
+    }
+
+    /// Record that the page holds no records any more
+    pub fn release(&mut self, page_id: u32) {
+        self.available.remove(&page_id);
+        self.free_pages.insert(page_id);
+    }
+
+    /// Take the lowest free page for reuse
+    pub fn take_free_page(&mut self) -> Option<u32> {
+        self.free_pages.pop_first()
+    }
+
+    pub fn free_pages(&self) -> Vec<u32> {
+        self.free_pages.iter().copied().collect()
+    }
+
+    pub fn serialize(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&(self.available.len() as u32).to_le_bytes());
+        for (page_id, available) in &self.available {
+            bytes.extend_from_slice(&page_id.to_le_bytes());
+            bytes.extend_from_slice(&available.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(self.free_pages.len() as u32).to_le_bytes());
+        for page_id in &self.free_pages {
+            bytes.extend_from_slice(&page_id.to_le_bytes());
+        }
+    }
+
+    pub fn deserialize(reader: &mut ByteReader) -> Result<Self, DatabaseError> {
+        let mut map = Self::new();
+        for _ in 0..reader.read_u32()? {
+            let page_id = reader.read_u32()?;
+            map.available.insert(page_id, reader.read_u16()?);
+        }
+        for _ in 0..reader.read_u32()? {
+            map.free_pages.insert(reader.read_u32()?);
+        }
+        Ok(map)
+    }
+}
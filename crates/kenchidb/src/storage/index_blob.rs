@@ -0,0 +1,129 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    common::{DatabaseError, crc32},
+    query::ByteReader,
+    storage::{
+        file_manager::FileManager,
+        page::{MAX_PAGE_DATA_SIZE, PageType, SLOT_SIZE},
+    },
+};
+
+/// Bytes of the serialized index stored in each index page
+const INDEX_CHUNK_SIZE: usize = MAX_PAGE_DATA_SIZE - SLOT_SIZE;
+
+/// Number of pages `save_index_blob` writes for a body of the given length
+pub fn index_blob_page_count(body_length: usize) -> u32 {
+    (16 + body_length).div_ceil(INDEX_CHUNK_SIZE) as u32
+}
+
+/// Serialized index read back from its file
+#[derive(Debug)]
+pub struct IndexBlob {
+    pub body: Vec<u8>,
+    /// Checksum of the collection data the index was built from
+    pub data_checksum: u32,
+}
+
+/// Write the serialized index into a fresh file of index pages, each holding one chunk.
+/// The first chunk starts with the layout version, the body length, its checksum and the
+/// checksum of the collection data it was built from, so a stale index is detected on load.
+/// The file is written next to the target and renamed over it, a crash keeps the previous file.
+pub fn save_index_blob(
+    path: &Path,
+    version: u32,
+    body: &[u8],
+    data_checksum: u32,
+) -> Result<(), DatabaseError> {
+    let mut bytes = Vec::with_capacity(16 + body.len());
+    bytes.extend_from_slice(&version.to_le_bytes());
+    bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&crc32(body).to_le_bytes());
+    bytes.extend_from_slice(&data_checksum.to_le_bytes());
+    bytes.extend_from_slice(body);
+
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    if temporary.exists() {
+        fs::remove_file(&temporary)?;
+    }
+
+    let mut file_manager = FileManager::new(&temporary)?;
+    for chunk in bytes.chunks(INDEX_CHUNK_SIZE) {
+        let (page_id, mut page) = file_manager.allocate_page(PageType::IndexPage, 0)?;
+        page.insert_record(chunk)?;
+        file_manager.write_page(page_id, &mut page)?;
+    }
+    file_manager.flush()?;
+    drop(file_manager);
+
+    fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+/// Read an index written by `save_index_blob`, `None` if the file is missing.
+/// Damaged files and other layout versions are `InvalidData` errors.
+pub fn load_index_blob(
+    path: &Path,
+    version: u32,
+    kind: &str,
+) -> Result<Option<IndexBlob>, DatabaseError> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let mut file_manager = FileManager::new(path)?;
+    let mut bytes = Vec::new();
+    for page_id in 0..file_manager.page_count() {
+        let page = match file_manager.read_page(page_id) {
+            Err(DatabaseError::Corruption { .. }) => {
+                return Err(DatabaseError::InvalidData(format!(
+                    "Page {} of {} '{}' does not match its checksum",
+                    page_id,
+                    kind,
+                    path.display()
+                )));
+            }
+            result => result?,
+        };
+        if page.header.page_type != PageType::IndexPage {
+            return Err(DatabaseError::InvalidData(format!(
+                "Page {} of {} '{}' is not an index page",
+                page_id,
+                kind,
+                path.display()
+            )));
+        }
+        bytes.extend_from_slice(page.get_record(0)?);
+    }
+
+    let mut reader = ByteReader::new(&bytes);
+    let stored_version = reader.read_u32()?;
+    if stored_version != version {
+        return Err(DatabaseError::InvalidData(format!(
+            "Unsupported {} version {}",
+            kind, stored_version
+        )));
+    }
+
+    let length = reader.read_u32()? as usize;
+    let checksum = reader.read_u32()?;
+    let data_checksum = reader.read_u32()?;
+    let body = reader.read_bytes(length)?;
+    if crc32(body) != checksum {
+        return Err(DatabaseError::InvalidData(format!(
+            "Checksum mismatch in {} '{}'",
+            kind,
+            path.display()
+        )));
+    }
+
+    Ok(Some(IndexBlob {
+        body: body.to_vec(),
+        data_checksum,
+    }))
+}
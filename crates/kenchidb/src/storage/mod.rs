@@ -1,3 +1,28 @@
-mod file_manager;
-mod page;
-mod paged_collection;
+#[cfg(feature = "tokio")]
+pub(crate) mod async_io;
+pub(crate) mod btree_store;
+pub(crate) mod buffer_pool;
+pub(crate) mod catalog;
+pub(crate) mod codec;
+pub(crate) mod compression;
+pub(crate) mod cursor;
+pub(crate) mod double_write;
+pub(crate) mod encryption;
+pub(crate) mod engine;
+pub(crate) mod file_manager;
+pub(crate) mod flusher;
+pub(crate) mod free_space;
+pub(crate) mod index_blob;
+pub(crate) mod packed;
+pub(crate) mod page;
+pub(crate) mod paged_collection;
+pub(crate) mod paged_database;
+pub(crate) mod recovery;
+pub(crate) mod sweeper;
+pub(crate) mod tier;
+pub(crate) mod trace;
+pub(crate) mod wal;
+pub(crate) mod warmup;
+
+/// I/O counters, the same snapshot the chunk store reports
+pub use ::storage::IoStats;
@@ -0,0 +1,337 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    common::{DatabaseError, crc32},
+    storage::page::PAGE_SIZE,
+};
+
+/// Marks a written slot of the packed pages file, "KENP" in ASCII
+const SLOT_MAGIC: u32 = 0x4B454E50;
+
+/// Magic, CRC-32 of the rest of the slot, generation and number of entries
+const SLOT_HEADER_SIZE: usize = 18;
+
+/// Page id and image length of each page in a slot
+const ENTRY_SIZE: usize = 6;
+
+/// Room for the entries and images of a slot
+const SLOT_CAPACITY: usize = PAGE_SIZE - SLOT_HEADER_SIZE;
+
+/// Packed pages file of the page file at `path`: `<path>.packed`
+pub fn packed_pages_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".packed");
+    PathBuf::from(path)
+}
+
+/// Page id and the bytes of its encoded page up to the trailing zeros
+type Image = (u32, Vec<u8>);
+
+/// Newest image of a page, a length of 0 records that the page went back to the page file
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    slot: u64,
+    offset: u16,
+    length: u16,
+}
+
+/// Pages which take fewer bytes than a page once encoded, e.g. compressed pages, packed
+/// several to a 4 KiB slot of a second file. The trailing zeros of an encoded page are
+/// not stored. A slot lists the id and length of each of its pages and is only written
+/// whole over a slot without pages in use, so a crash while a slot is written leaves the
+/// previous images. Slots carry the generation of the flush which wrote them, the newest
+/// entry of a page wins when the file is scanned on open.
+/// A page stored here takes precedence over its copy in the page file.
+pub struct PackedPages {
+    file: File,
+    entries: HashMap<u32, Entry>,
+    /// Ids of the pages with their newest entry in each slot, empty for free slots
+    slots: Vec<Vec<u32>>,
+    generation: u64,
+}
+
+impl PackedPages {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, DatabaseError> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(path)?;
+
+        let slot_count = file.metadata()?.len() / PAGE_SIZE as u64;
+        let mut newest: HashMap<u32, (u64, Entry)> = HashMap::new();
+        let mut generation = 0;
+        let mut buffer = [0u8; PAGE_SIZE];
+        file.seek(SeekFrom::Start(0))?;
+        for slot in 0..slot_count {
+            file.read_exact(&mut buffer)?;
+            // Free slots and slots torn by a crash hold no pages
+            let Some((slot_generation, slot_entries)) = parse_slot(slot, &buffer) else {
+                continue;
+            };
+            generation = generation.max(slot_generation);
+            for (page_id, entry) in slot_entries {
+                match newest.get(&page_id) {
+                    Some((newer, _)) if *newer >= slot_generation => {}
+                    _ => {
+                        newest.insert(page_id, (slot_generation, entry));
+                    }
+                }
+            }
+        }
+
+        let mut slots = vec![Vec::new(); slot_count as usize];
+        let entries: HashMap<u32, Entry> = newest
+            .into_iter()
+            .map(|(page_id, (_, entry))| (page_id, entry))
+            .collect();
+        for (page_id, entry) in &entries {
+            slots[entry.slot as usize].push(*page_id);
+        }
+
+        Ok(Self {
+            file,
+            entries,
+            slots,
+            generation,
+        })
+    }
+
+    pub fn contains(&self, page_id: u32) -> bool {
+        self.entries
+            .get(&page_id)
+            .is_some_and(|entry| entry.length > 0)
+    }
+
+    /// The encoded page is short enough to be packed
+    pub fn fits(page_bytes: &[u8; PAGE_SIZE]) -> bool {
+        ENTRY_SIZE + image_length(page_bytes) <= SLOT_CAPACITY
+    }
+
+    /// One past the highest id of a page stored here, 0 without pages
+    pub fn page_end(&self) -> u32 {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| entry.length > 0)
+            .map(|(page_id, _)| page_id + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Number of slots in the file, free slots included
+    pub fn slot_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Read a page stored here, the bytes after its image are zeros
+    pub fn read(
+        &mut self,
+        page_id: u32,
+        buffer: &mut [u8; PAGE_SIZE],
+    ) -> Result<(), DatabaseError> {
+        let Some(entry) = self.entries.get(&page_id).filter(|entry| entry.length > 0) else {
+            return Err(DatabaseError::InvalidData(format!(
+                "Page {} is not packed",
+                page_id
+            )));
+        };
+
+        let length = entry.length as usize;
+        self.file.seek(SeekFrom::Start(
+            entry.slot * PAGE_SIZE as u64 + entry.offset as u64,
+        ))?;
+        self.file.read_exact(&mut buffer[..length])?;
+        buffer[length..].fill(0);
+        Ok(())
+    }
+
+    /// Pack the encoded pages into free slots and record that the `removed` pages went
+    /// back to the page file, then sync the file. The pages must `fit`.
+    /// Pages left in a slot less than half in use are packed again with them, so the
+    /// slots of pages written often are freed.
+    pub fn write(
+        &mut self,
+        pages: &[(u32, Box<[u8; PAGE_SIZE]>)],
+        removed: &[u32],
+    ) -> Result<(), DatabaseError> {
+        let mut images: Vec<Image> = pages
+            .iter()
+            .map(|(page_id, page_bytes)| {
+                (*page_id, page_bytes[..image_length(page_bytes)].to_vec())
+            })
+            .collect();
+        images.extend(
+            removed
+                .iter()
+                .filter(|page_id| self.contains(**page_id))
+                .map(|page_id| (*page_id, Vec::new())),
+        );
+        if images.is_empty() {
+            return Ok(());
+        }
+        if let Some((page_id, _)) = images
+            .iter()
+            .find(|(_, image)| ENTRY_SIZE + image.len() > SLOT_CAPACITY)
+        {
+            return Err(DatabaseError::InvalidData(format!(
+                "Page {} is too long to be packed",
+                page_id
+            )));
+        }
+
+        // Only slots free before this write are written, slots it frees may hold the
+        // only complete images until the file is synced
+        let mut free: Vec<u64> = (0..self.slots.len() as u64)
+            .filter(|slot| self.slots[*slot as usize].is_empty())
+            .rev()
+            .collect();
+
+        let mut remaining: HashMap<u64, usize> = HashMap::new();
+        for (page_id, _) in &images {
+            if let Some(entry) = self.entries.get(page_id) {
+                let used = remaining
+                    .entry(entry.slot)
+                    .or_insert_with(|| self.used_bytes(entry.slot));
+                *used -= ENTRY_SIZE + entry.length as usize;
+            }
+        }
+        let written: HashSet<u32> = images.iter().map(|(page_id, _)| *page_id).collect();
+        for (slot, used) in remaining {
+            if used == 0 || used >= SLOT_CAPACITY / 2 {
+                continue;
+            }
+            for page_id in self.slots[slot as usize].clone() {
+                if written.contains(&page_id) {
+                    continue;
+                }
+                let entry = self.entries[&page_id];
+                let mut image = vec![0u8; entry.length as usize];
+                self.file.seek(SeekFrom::Start(
+                    slot * PAGE_SIZE as u64 + entry.offset as u64,
+                ))?;
+                self.file.read_exact(&mut image)?;
+                images.push((page_id, image));
+            }
+        }
+
+        // First fit, largest images first
+        images.sort_by_key(|(_, image)| std::cmp::Reverse(image.len()));
+        let mut bins: Vec<(usize, Vec<Image>)> = Vec::new();
+        for (page_id, image) in images {
+            let size = ENTRY_SIZE + image.len();
+            match bins
+                .iter_mut()
+                .find(|(used, _)| used + size <= SLOT_CAPACITY)
+            {
+                Some((used, bin)) => {
+                    *used += size;
+                    bin.push((page_id, image));
+                }
+                None => bins.push((size, vec![(page_id, image)])),
+            }
+        }
+
+        let generation = self.generation + 1;
+        let mut placed = Vec::new();
+        for (_, bin) in bins {
+            let slot = free.pop().unwrap_or_else(|| {
+                self.slots.push(Vec::new());
+                self.slots.len() as u64 - 1
+            });
+            let mut buffer = [0u8; PAGE_SIZE];
+            buffer[0..4].copy_from_slice(&SLOT_MAGIC.to_le_bytes());
+            buffer[8..16].copy_from_slice(&generation.to_le_bytes());
+            buffer[16..18].copy_from_slice(&(bin.len() as u16).to_le_bytes());
+            let mut entry_offset = SLOT_HEADER_SIZE;
+            let mut offset = SLOT_HEADER_SIZE + bin.len() * ENTRY_SIZE;
+            for (page_id, image) in &bin {
+                buffer[entry_offset..entry_offset + 4].copy_from_slice(&page_id.to_le_bytes());
+                buffer[entry_offset + 4..entry_offset + 6]
+                    .copy_from_slice(&(image.len() as u16).to_le_bytes());
+                entry_offset += ENTRY_SIZE;
+                buffer[offset..offset + image.len()].copy_from_slice(image);
+                placed.push((
+                    *page_id,
+                    Entry {
+                        slot,
+                        offset: offset as u16,
+                        length: image.len() as u16,
+                    },
+                ));
+                offset += image.len();
+            }
+            let checksum = crc32(&buffer[8..]);
+            buffer[4..8].copy_from_slice(&checksum.to_le_bytes());
+
+            self.file.seek(SeekFrom::Start(slot * PAGE_SIZE as u64))?;
+            self.file.write_all(&buffer)?;
+        }
+        self.file.sync_data()?;
+
+        self.generation = generation;
+        for (page_id, entry) in placed {
+            if let Some(previous) = self.entries.insert(page_id, entry) {
+                self.slots[previous.slot as usize].retain(|id| *id != page_id);
+            }
+            self.slots[entry.slot as usize].push(page_id);
+        }
+        Ok(())
+    }
+
+    /// Bytes of the entries and images in use in a slot
+    fn used_bytes(&self, slot: u64) -> usize {
+        self.slots[slot as usize]
+            .iter()
+            .map(|page_id| ENTRY_SIZE + self.entries[page_id].length as usize)
+            .sum()
+    }
+}
+
+/// Bytes of an encoded page up to its trailing zeros, at least one
+fn image_length(page_bytes: &[u8; PAGE_SIZE]) -> usize {
+    page_bytes
+        .iter()
+        .rposition(|&byte| byte != 0)
+        .map_or(1, |last| last + 1)
+}
+
+/// Generation and entries of a slot, `None` for a free or damaged slot
+fn parse_slot(slot: u64, buffer: &[u8; PAGE_SIZE]) -> Option<(u64, Vec<(u32, Entry)>)> {
+    if u32::from_le_bytes(buffer[0..4].try_into().unwrap()) != SLOT_MAGIC
+        || u32::from_le_bytes(buffer[4..8].try_into().unwrap()) != crc32(&buffer[8..])
+    {
+        return None;
+    }
+    let generation = u64::from_le_bytes(buffer[8..16].try_into().unwrap());
+    let count = u16::from_le_bytes([buffer[16], buffer[17]]) as usize;
+    if SLOT_HEADER_SIZE + count * ENTRY_SIZE > PAGE_SIZE {
+        return None;
+    }
+
+    let mut entries = Vec::with_capacity(count);
+    let mut offset = SLOT_HEADER_SIZE + count * ENTRY_SIZE;
+    for index in 0..count {
+        let start = SLOT_HEADER_SIZE + index * ENTRY_SIZE;
+        let page_id = u32::from_le_bytes(buffer[start..start + 4].try_into().unwrap());
+        let length = u16::from_le_bytes([buffer[start + 4], buffer[start + 5]]);
+        if offset + length as usize > PAGE_SIZE {
+            return None;
+        }
+        entries.push((
+            page_id,
+            Entry {
+                slot,
+                offset: offset as u16,
+                length,
+            },
+        ));
+        offset += length as usize;
+    }
+    Some((generation, entries))
+}
@@ -1,4 +1,4 @@
-use crate::common::DatabaseError;
+use crate::common::{DatabaseError, crc32};
 
 /// Page size - 4kb is a common choice for page size in many systems.
 /// It aligns well with OS page size.
@@ -7,12 +7,25 @@ pub const PAGE_SIZE: usize = 4096; // 4 KiB
 /// Page header size - contains metadata about the page.
 pub const PAGE_HEADER_SIZE: usize = 24;
 
+/// Offset of the codec id in the serialized page header.
+pub const PAGE_CODEC_ID_OFFSET: usize = 5;
+
+/// Offset of the length of the page body before encoding in the serialized page header.
+pub const PAGE_BODY_LENGTH_OFFSET: usize = 6;
+
+/// Offset of the checksum in the serialized page header.
+const PAGE_CHECKSUM_OFFSET: usize = 14;
+
 /// Maximum usable space per page (excluding header).
 pub const MAX_PAGE_DATA_SIZE: usize = PAGE_SIZE - PAGE_HEADER_SIZE;
 
+/// Slot directory entry size - offset and length of a record.
+pub const SLOT_SIZE: usize = 4;
+
 /// Page types for different kinds of data.
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[repr(u8)]
+#[allow(clippy::enum_variant_names)]
 pub enum PageType {
     /// Stores actual document data.
     DataPage = 1,
@@ -22,6 +35,10 @@ pub enum PageType {
     FreePage = 3,
     /// Header page - first page in file with database metadata.
     HeaderPage = 4,
+    /// Stores index entries, e.g. the postings of a text index.
+    IndexPage = 5,
+    /// Stores a chunk of a record too large for a data page, chained to the next chunk.
+    OverflowPage = 6,
 }
 
 impl PageType {
@@ -31,6 +48,8 @@ impl PageType {
             2 => Ok(PageType::MetaPage),
             3 => Ok(PageType::FreePage),
             4 => Ok(PageType::HeaderPage),
+            5 => Ok(PageType::IndexPage),
+            6 => Ok(PageType::OverflowPage),
             _ => Err(DatabaseError::InvalidData(format!(
                 "Invalid page type: {}",
                 value
@@ -46,8 +65,11 @@ pub struct PageHeader {
     pub magic: u32,
     /// Type of page (1 byte).
     pub page_type: PageType,
-    /// Reserved for alignment (3 bytes).
-    pub _reserved: [u8; 3],
+    /// Id of the codec the page body is encoded with, 0 for none (1 byte).
+    pub codec_id: u8,
+    /// Length of the body before it was encoded, e.g. the uncompressed length,
+    /// 0 for pages without a codec (2 bytes).
+    pub body_length: u16,
     /// Number of records/slots in this page (2 bytes).
     pub record_count: u16,
     /// Offset to start of free space (2 bytes).
@@ -67,7 +89,8 @@ impl PageHeader {
         Self {
             magic: Self::MAGIC_NUMBER,
             page_type,
-            _reserved: [0; 3],
+            codec_id: 0,
+            body_length: 0,
             record_count: 0,
             free_space_start: PAGE_HEADER_SIZE as u16,
             free_space_size: MAX_PAGE_DATA_SIZE as u16,
@@ -88,9 +111,13 @@ impl PageHeader {
         bytes[offset] = self.page_type as u8;
         offset += 1;
 
-        // Reserved (3 bytes)
-        bytes[offset..offset + 3].copy_from_slice(&self._reserved);
-        offset += 3;
+        // Codec id (1 byte)
+        bytes[offset] = self.codec_id;
+        offset += 1;
+
+        // Body length (2 bytes)
+        bytes[offset..offset + 2].copy_from_slice(&self.body_length.to_le_bytes());
+        offset += 2;
 
         // Record count (2 bytes)
         bytes[offset..offset + 2].copy_from_slice(&self.record_count.to_le_bytes());
@@ -142,9 +169,13 @@ impl PageHeader {
         let page_type = PageType::from_u8(bytes[offset])?;
         offset += 1;
 
-        // Reserved (3 bytes)
-        let reserved = [bytes[offset], bytes[offset + 1], bytes[offset + 2]];
-        offset += 3;
+        // Codec id (1 byte)
+        let codec_id = bytes[offset];
+        offset += 1;
+
+        // Body length (2 bytes)
+        let body_length = u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+        offset += 2;
 
         // Record count (2 bytes)
         let record_count = u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
@@ -178,7 +209,8 @@ impl PageHeader {
         Ok(Self {
             magic,
             page_type,
-            _reserved: reserved,
+            codec_id,
+            body_length,
             record_count,
             free_space_start,
             free_space_size,
@@ -202,24 +234,29 @@ impl SlotEntry {
         Self { offset, length }
     }
 
-    pub fn serialize(&self) -> [u8; 4] {
-        let mut bytes = [0u8; 4];
+    pub fn serialize(&self) -> [u8; SLOT_SIZE] {
+        let mut bytes = [0u8; SLOT_SIZE];
         bytes[0..2].copy_from_slice(&self.offset.to_le_bytes());
         bytes[2..4].copy_from_slice(&self.length.to_le_bytes());
         bytes
     }
 
+    /// Free slots are left behind by deleted records
+    pub fn is_free(&self) -> bool {
+        self.length == 0
+    }
+
     pub fn deserialize(bytes: &[u8]) -> Result<SlotEntry, DatabaseError> {
-        if bytes.len() < 4 {
+        if bytes.len() < SLOT_SIZE {
             return Err(DatabaseError::InvalidData(
                 "Invalid slot entry size".to_string(),
             ));
         }
 
         let offset = u16::from_le_bytes([bytes[0], bytes[1]]);
-        let length = u16::from_le_bytes([bytes[1], bytes[2]]);
+        let length = u16::from_le_bytes([bytes[2], bytes[3]]);
 
-        return Ok(SlotEntry { offset, length });
+        Ok(SlotEntry { offset, length })
     }
 }
 
@@ -243,7 +280,10 @@ impl Page {
     /// Insert a record into the page, returns slot index if successful
     pub fn insert_record(&mut self, record_data: &[u8]) -> Result<u16, DatabaseError> {
         let record_size = record_data.len();
-        let slot_size = 4; // Each slot entry is 4 bytes
+
+        // Deleted slots are reused before the slot directory is grown
+        let free_slot = self.find_free_slot();
+        let slot_size = if free_slot.is_some() { 0 } else { SLOT_SIZE };
 
         // Check if we have enough space (need spaced for data + slot entry)
         if (self.header.free_space_size as usize) < record_size + slot_size {
@@ -252,9 +292,11 @@ impl Page {
             ));
         }
 
-        // Calculate where to place the new record (grows backwards from end)
-        let data_end = PAGE_SIZE - (self.slots.len() * slot_size);
-        let new_record_offset = data_end - record_size;
+        // Records grow backwards from the end of the page,
+        // the slot directory grows forward from the header
+        let free_space_end =
+            self.header.free_space_start as usize + self.header.free_space_size as usize;
+        let new_record_offset = free_space_end - record_size;
 
         // Copy record data to page
         let data_start_in_page = new_record_offset - PAGE_HEADER_SIZE;
@@ -262,18 +304,22 @@ impl Page {
             .copy_from_slice(record_data);
 
         // Add slot entry
-        let slot_index = self.slots.len() as u16;
-        self.slots.push(SlotEntry {
-            offset: new_record_offset as u16,
-            length: record_size as u16,
-        });
+        let slot = SlotEntry::new(new_record_offset as u16, record_size as u16);
+        let slot_index = match free_slot {
+            Some(slot_index) => {
+                self.slots[slot_index as usize] = slot;
+                slot_index
+            }
+            None => {
+                self.slots.push(slot);
+                self.header.record_count += 1;
+                self.header.free_space_start += SLOT_SIZE as u16;
+                (self.slots.len() - 1) as u16
+            }
+        };
 
         // Update header
-        self.header.record_count += 1;
-        self.header.free_space_size = self
-            .header
-            .free_space_size
-            .saturating_sub((record_size + slot_size) as u16);
+        self.header.free_space_size -= (record_size + slot_size) as u16;
 
         Ok(slot_index)
     }
@@ -285,6 +331,12 @@ impl Page {
         }
 
         let slot = self.slots[slot_index as usize];
+        if slot.is_free() {
+            return Err(DatabaseError::InvalidData(
+                "Record has been deleted".to_string(),
+            ));
+        }
+
         let data_start = slot.offset as usize - PAGE_HEADER_SIZE;
         let data_end = data_start + slot.length as usize;
 
@@ -297,55 +349,165 @@ impl Page {
         Ok(&self.data[data_start..data_end])
     }
 
-    /// Calculate and update checksum for the page
-    pub fn update_checksum(&mut self) {
-        // Simple checksum - sum of all data bytes
-        let mut checksum = 0u32;
-
-        // Include slot data in checksum
-        for slot in &self.slots {
-            let slot_bytes = slot.serialize();
-            for byte in slot_bytes {
-                checksum = checksum.wrapping_add(byte as u32);
+    /// Delete a record by slot index.
+    /// The slot is marked free and reused by the next insert into this page,
+    /// the record bytes are reclaimed only when the page is compacted.
+    pub fn delete_record(&mut self, slot_index: u16) -> Result<(), DatabaseError> {
+        match self.slots.get(slot_index as usize) {
+            Some(slot) if !slot.is_free() => {
+                self.slots[slot_index as usize] = SlotEntry::new(0, 0);
+                Ok(())
             }
+            _ => Err(DatabaseError::InvalidData("Invalid slot index".to_string())),
         }
+    }
+
+    /// Replace a record, false if the new record does not fit in the page.
+    /// A record no longer than the old one takes its place, a longer one goes to the free
+    /// space. Bytes no longer used are reclaimed when the page is compacted.
+    pub fn update_record(
+        &mut self,
+        slot_index: u16,
+        record_data: &[u8],
+    ) -> Result<bool, DatabaseError> {
+        let slot = match self.slots.get(slot_index as usize) {
+            Some(slot) if !slot.is_free() => *slot,
+            _ => return Err(DatabaseError::InvalidData("Invalid slot index".to_string())),
+        };
+
+        let record_size = record_data.len();
+        let offset = if record_size <= slot.length as usize {
+            slot.offset as usize
+        } else if record_size <= self.header.free_space_size as usize {
+            let free_space_end =
+                self.header.free_space_start as usize + self.header.free_space_size as usize;
+            self.header.free_space_size -= record_size as u16;
+            free_space_end - record_size
+        } else {
+            return Ok(false);
+        };
+
+        let data_start_in_page = offset - PAGE_HEADER_SIZE;
+        self.data[data_start_in_page..data_start_in_page + record_size]
+            .copy_from_slice(record_data);
+        self.slots[slot_index as usize] = SlotEntry::new(offset as u16, record_size as u16);
+        Ok(true)
+    }
+
+    /// Number of slots holding a live record
+    pub fn live_record_count(&self) -> usize {
+        self.slots.iter().filter(|slot| !slot.is_free()).count()
+    }
+
+    /// Free space of the page once `compact` reclaimed the bytes of deleted records
+    pub fn compacted_free_space(&self, reserved: usize) -> usize {
+        let slots = self
+            .slots
+            .iter()
+            .rposition(|slot| !slot.is_free())
+            .map_or(0, |last| last + 1);
+        let live: usize = self.slots.iter().map(|slot| slot.length as usize).sum();
+        (PAGE_SIZE - reserved).saturating_sub(PAGE_HEADER_SIZE + slots * SLOT_SIZE + live)
+    }
 
-        // Include actual record data
-        for byte in &self.data {
-            checksum = checksum.wrapping_add(*byte as u32);
+    /// Move the live records together at the end of the page, reclaiming the bytes of
+    /// deleted records, and drop the free slots at the end of the slot directory.
+    /// Live records keep their slot index. The last `reserved` bytes of the page are
+    /// left for the codec the page is written with.
+    pub fn compact(&mut self, reserved: usize) {
+        while self.slots.last().is_some_and(SlotEntry::is_free) {
+            self.slots.pop();
         }
 
-        self.header.checksum = checksum;
+        let mut data = vec![0u8; MAX_PAGE_DATA_SIZE];
+        let mut end = PAGE_SIZE - reserved;
+        for slot in self.slots.iter_mut().filter(|slot| !slot.is_free()) {
+            let start = slot.offset as usize - PAGE_HEADER_SIZE;
+            let length = slot.length as usize;
+            end -= length;
+            data[end - PAGE_HEADER_SIZE..end - PAGE_HEADER_SIZE + length]
+                .copy_from_slice(&self.data[start..start + length]);
+            slot.offset = end as u16;
+        }
+
+        self.data = data;
+        self.header.record_count = self.slots.len() as u16;
+        self.header.free_space_start = (PAGE_HEADER_SIZE + self.slots.len() * SLOT_SIZE) as u16;
+        self.header.free_space_size = (end - self.header.free_space_start as usize) as u16;
+    }
+
+    fn find_free_slot(&self) -> Option<u16> {
+        self.slots
+            .iter()
+            .position(|slot| slot.is_free())
+            .map(|slot_index| slot_index as u16)
+    }
+
+    /// Calculate and update checksum for the page
+    pub fn update_checksum(&mut self) {
+        self.header.checksum = self.compute_checksum();
+    }
+
+    /// Whether the page contents match the checksum stored in its header
+    pub fn checksum_matches(&self) -> bool {
+        self.compute_checksum() == self.header.checksum
+    }
+
+    /// CRC-32 of the serialized page with the checksum field left zero
+    pub fn compute_checksum(&self) -> u32 {
+        let mut page_bytes = self.layout();
+        page_bytes[PAGE_CHECKSUM_OFFSET..PAGE_CHECKSUM_OFFSET + 4].fill(0);
+        crc32(&page_bytes)
     }
 
     /// Serialize entire page to bytes
     pub fn serialize(&mut self) -> [u8; PAGE_SIZE] {
-        let mut page_bytes = [0u8; PAGE_SIZE];
-
         // Update checksum before serializing
         self.update_checksum();
+        self.layout()
+    }
+
+    /// Page bytes as they are written, with the checksum currently in the header
+    fn layout(&self) -> [u8; PAGE_SIZE] {
+        let mut page_bytes = [0u8; PAGE_SIZE];
 
         // Serialize header
         let header_bytes = self.header.serialize();
         page_bytes[0..PAGE_HEADER_SIZE].copy_from_slice(&header_bytes);
 
+        // Copy data section
+        page_bytes[PAGE_HEADER_SIZE..PAGE_HEADER_SIZE + self.data.len()]
+            .copy_from_slice(&self.data);
+
         // Serialize slot directory (grows upward from header)
         let mut slot_offset = PAGE_HEADER_SIZE;
         for slot in &self.slots {
             let slot_bytes = slot.serialize();
-            page_bytes[slot_offset..slot_offset + 4].copy_from_slice(&slot_bytes);
-            slot_offset += 4;
+            page_bytes[slot_offset..slot_offset + SLOT_SIZE].copy_from_slice(&slot_bytes);
+            slot_offset += SLOT_SIZE;
         }
 
-        // Copy data section
-        page_bytes[PAGE_HEADER_SIZE..PAGE_HEADER_SIZE + self.data.len()]
-            .copy_from_slice(&self.data);
-
         page_bytes
     }
 
-    /// Deserialize page from bytes
-    pub fn deserialize(bytes: &[u8]) -> Result<Self, DatabaseError> {
+    /// Deserialize the page stored as `page_id`, a page that does not match its checksum
+    /// is a `Corruption` error
+    pub fn deserialize(page_id: u32, bytes: &[u8]) -> Result<Self, DatabaseError> {
+        let page = Self::deserialize_unverified(bytes)?;
+        let actual = page.compute_checksum();
+        if actual != page.header.checksum {
+            return Err(DatabaseError::Corruption {
+                page_id,
+                expected: page.header.checksum,
+                actual,
+            });
+        }
+        Ok(page)
+    }
+
+    /// Deserialize page from bytes without verifying its checksum,
+    /// e.g. to repair a damaged page
+    pub fn deserialize_unverified(bytes: &[u8]) -> Result<Self, DatabaseError> {
         if bytes.len() != PAGE_SIZE {
             return Err(DatabaseError::InvalidData("Invalid page size".to_string()));
         }
@@ -358,31 +520,26 @@ impl Page {
         let mut slot_offset = PAGE_HEADER_SIZE;
 
         for _ in 0..header.record_count {
-            if slot_offset + 4 > bytes.len() {
+            if slot_offset + SLOT_SIZE > bytes.len() {
                 return Err(DatabaseError::InvalidData(
                     "Invalid slot directory".to_string(),
                 ));
             }
 
-            let slot = SlotEntry::deserialize(&bytes[slot_offset..slot_offset + 4])?;
+            let slot = SlotEntry::deserialize(&bytes[slot_offset..slot_offset + SLOT_SIZE])?;
             slots.push(slot);
-            slot_offset += 4;
+            slot_offset += SLOT_SIZE;
         }
 
         // Copy data section
         let mut data = vec![0u8; MAX_PAGE_DATA_SIZE];
         data.copy_from_slice(&bytes[PAGE_HEADER_SIZE..PAGE_HEADER_SIZE + MAX_PAGE_DATA_SIZE]);
 
-        let page = Self {
+        Ok(Self {
             header,
             slots,
             data,
-        };
-
-        // Verify checksum
-        // Note: In production, you'd want to verify the checksum here
-
-        Ok(page)
+        })
     }
 
     /// Get available free space in bytes
@@ -392,7 +549,11 @@ impl Page {
 
     /// Check if page can fit a record of given size
     pub fn can_fit(&self, record_size: usize) -> bool {
-        let slot_size = 4;
+        let slot_size = if self.find_free_slot().is_some() {
+            0
+        } else {
+            SLOT_SIZE
+        };
         (self.header.free_space_size as usize) >= record_size + slot_size
     }
 }
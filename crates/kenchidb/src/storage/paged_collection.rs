@@ -1,49 +1,540 @@
-use std::{collections::HashMap, path::Path};
+use std::{
+    borrow::BorrowMut,
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use ::storage::{Durability, sync_parent_directory};
+use btree::Btree;
 
 use crate::{
-    common::DatabaseError,
-    schema::{Document, Value},
-    storage::{file_manager::FileManager, page::PageType},
+    common::{DatabaseError, Random, crc32},
+    query::{ByteReader, Query, reservoir_sample},
+    schema::{Document, TtlPolicy, Value, current_time_millis},
+    storage::{
+        cursor::DocumentCursor,
+        double_write::double_write_path,
+        file_manager::FileManager,
+        free_space::FreeSpaceMap,
+        index_blob::{load_index_blob, save_index_blob},
+        packed::packed_pages_path,
+        page::{MAX_PAGE_DATA_SIZE, Page, PageType, SLOT_SIZE},
+        recovery::{DamagedPage, PageCheck, PageDamage, RecoveryAction, RecoveryReport},
+        trace::TraceEvent,
+        warmup::{WarmupPlan, WarmupReport},
+    },
 };
 
-/// Enhanced collection that uses page-based storage
-pub struct PagedCollection {
+/// High bit of the stored field count, set when the record carries a checksum
+const CHECKSUM_FLAG: u32 = 1 << 31;
+
+/// Second highest bit of the stored field count, set in the stub record left in the data
+/// page for a document stored in a chain of overflow pages
+const OVERFLOW_FLAG: u32 = 1 << 30;
+
+/// Stub record: document id, flags, document length, first overflow page and a CRC-32
+const OVERFLOW_STUB_SIZE: usize = 24;
+
+/// Next page of the last overflow page of a chain
+const NO_NEXT_PAGE: u32 = u32::MAX;
+
+/// Layout version of the primary key index file
+const PRIMARY_KEY_INDEX_VERSION: u32 = 1;
+
+/// Layout version of the free-space map file
+const FREE_SPACE_MAP_VERSION: u32 = 1;
+
+/// Minimum degree of the primary key B-tree
+const PRIMARY_KEY_DEGREE: usize = 32;
+
+/// Enhanced collection that uses page-based storage. The collection owns its page file,
+/// or borrows one it shares with other collections, see `PagedDatabase`.
+pub struct PagedCollection<F = FileManager> {
     pub schema: crate::schema::Schema,
-    pub file_manager: FileManager,
+    pub file_manager: F,
     pub collection_id: u32,
-    pub documents: HashMap<u64, (u32, u16)>, // document_id -> (page_id, slot_index)
+    pub documents: Btree<(u32, u16)>, // document_id -> (page_id, slot_index)
     pub next_id: u64,
     pub current_page_id: Option<u32>, // Current page for insertions
+    pub ttl: Option<TtlPolicy>,
+    /// Append a CRC-32 of the serialized document to each written record
+    pub document_checksums: bool,
+    /// Pages with room left by deleted records and pages without records
+    pub free_space: FreeSpaceMap,
+    /// Page file the pages are stored in
+    path: PathBuf,
+    /// File the primary key index is saved to by `flush`
+    primary_key_path: PathBuf,
+    /// File the free-space map is saved to by `flush`
+    free_space_path: PathBuf,
+    /// The primary key index and free-space map files match the documents
+    primary_key_saved: bool,
+    /// Outcome of the integrity pass when the file was opened
+    pub(crate) recovery: RecoveryReport,
 }
 
 impl PagedCollection {
+    /// Create the collection file, or open an existing one. The primary key index and the
+    /// free-space map of an existing file are loaded from the files `flush` saved next to it,
+    /// and rebuilt from the pages when those are missing, damaged or older than the data.
+    /// A file without a current index was not closed cleanly: every page is checked while
+    /// the index is rebuilt, see `PagedCollection::recovery_report`.
     pub fn new<P: AsRef<Path>>(
         schema: crate::schema::Schema,
         collection_id: u32,
         file_path: P,
     ) -> Result<Self, DatabaseError> {
-        let file_manager = FileManager::new(file_path)?;
+        // Left behind by a vacuum that did not finish, the collection file is still complete.
+        // Once the collection file was renamed, the vacuum is finished by renaming its
+        // packed pages.
+        let temporary = vacuum_path(file_path.as_ref());
+        let packed_temporary = packed_pages_path(&temporary);
+        if temporary.exists() {
+            fs::remove_file(&temporary)?;
+            if packed_temporary.exists() {
+                fs::remove_file(&packed_temporary)?;
+            }
+        } else if packed_temporary.exists() {
+            fs::rename(&packed_temporary, packed_pages_path(file_path.as_ref()))?;
+        }
+        let mut file_manager = FileManager::new(&file_path)?;
+        // Torn pages of an interrupted flush are repaired before the pages are checked
+        let pages_restored =
+            file_manager.restore_double_write(&double_write_path(file_path.as_ref()))?;
+
+        let mut collection = Self::open_in(
+            schema,
+            collection_id,
+            file_manager,
+            file_path.as_ref(),
+            file_path.as_ref(),
+        )?;
+        collection.recovery.pages_restored = pages_restored;
+        Ok(collection)
+    }
+
+    /// Stage the pages of each flush in the double-write file next to the collection file,
+    /// see `FileManager::enable_double_write`. Opening the collection repairs torn pages
+    /// from it whether or not this is enabled again.
+    pub fn enable_double_write(&mut self) -> Result<(), DatabaseError> {
+        self.file_manager
+            .enable_double_write(double_write_path(&self.path))?;
+        Ok(())
+    }
+
+    /// Rewrite the live records contiguously into a new file and rename it over the
+    /// collection file, dropping the space of deleted documents and free pages.
+    /// Document ids are kept, their page locations change. The new file is written next
+    /// to the collection file, a crash before the rename keeps the old one.
+    pub fn vacuum(&mut self) -> Result<VacuumReport, DatabaseError> {
+        if self.file_manager.cold_page_count() > 0 {
+            return Err(DatabaseError::InvalidQuery(
+                "Cannot vacuum a collection with pages in the cold tier".to_string(),
+            ));
+        }
+        self.file_manager.flush()?;
+        let bytes_before = stored_bytes(&self.path)?;
+
+        let temporary = vacuum_path(&self.path);
+        let packed_temporary = packed_pages_path(&temporary);
+        for path in [&temporary, &packed_temporary] {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        let compacted = self.file_manager.create_like(&temporary)?;
+        let mut source = std::mem::replace(&mut self.file_manager, compacted);
+        let documents = std::mem::replace(&mut self.documents, Btree::new(PRIMARY_KEY_DEGREE));
+        let free_space = std::mem::take(&mut self.free_space);
+        let current_page_id = self.current_page_id.take();
+
+        let copied = self.copy_records(&mut source, &documents);
+        let compacted = std::mem::replace(&mut self.file_manager, source);
+        if let Err(e) = copied {
+            drop(compacted);
+            self.documents = documents;
+            self.free_space = free_space;
+            self.current_page_id = current_page_id;
+            let _ = fs::remove_file(&temporary);
+            let _ = fs::remove_file(&packed_temporary);
+            return Err(e);
+        }
+        drop(compacted);
 
-        Ok(Self {
+        // The saved index points into the old file
+        self.primary_key_saved = true;
+        self.mark_modified()?;
+        fs::rename(&temporary, &self.path)?;
+        if packed_temporary.exists() {
+            fs::rename(&packed_temporary, packed_pages_path(&self.path))?;
+        }
+        sync_parent_directory(&self.path, Durability::Full)?;
+        self.file_manager.reopen(&self.path)?;
+        self.flush()?;
+
+        Ok(VacuumReport {
+            bytes_before,
+            bytes_after: stored_bytes(&self.path)?,
+        })
+    }
+
+    /// Copy the records of the documents from `source` into the current file manager
+    fn copy_records(
+        &mut self,
+        source: &mut FileManager,
+        documents: &Btree<(u32, u16)>,
+    ) -> Result<(), DatabaseError> {
+        for (id, (page_id, slot_index)) in documents.entries() {
+            let page = source.read_page(page_id)?;
+            let record = page.get_record(slot_index)?;
+            let record = match parse_overflow_stub(record)? {
+                Some((length, head)) => {
+                    let serialized = read_overflow(source, record, length, head)?;
+                    self.store_large_record(id, serialized)?
+                }
+                None => record.to_vec(),
+            };
+            let location = self.find_page_for_insert(&record)?;
+            self.documents.insert(id, location);
+        }
+        self.file_manager.flush()
+    }
+}
+
+impl<F> PagedCollection<F> {
+    /// Move the collection state over to another handle on its page file,
+    /// returns the collection with the new handle and the previous handle
+    pub fn with_file_manager<G>(self, file_manager: G) -> (PagedCollection<G>, F) {
+        let collection = PagedCollection {
+            schema: self.schema,
+            file_manager,
+            collection_id: self.collection_id,
+            documents: self.documents,
+            next_id: self.next_id,
+            current_page_id: self.current_page_id,
+            ttl: self.ttl,
+            document_checksums: self.document_checksums,
+            free_space: self.free_space,
+            path: self.path,
+            primary_key_path: self.primary_key_path,
+            free_space_path: self.free_space_path,
+            primary_key_saved: self.primary_key_saved,
+            recovery: self.recovery,
+        };
+        (collection, self.file_manager)
+    }
+}
+
+impl<F: BorrowMut<FileManager>> PagedCollection<F> {
+    /// Open the collection in a page file, whose pages may belong to other collections
+    /// too. The primary key index and the free-space map are saved next to `index_path`.
+    pub fn open_in(
+        schema: crate::schema::Schema,
+        collection_id: u32,
+        file_manager: F,
+        path: &Path,
+        index_path: &Path,
+    ) -> Result<Self, DatabaseError> {
+        let mut collection = Self {
             schema,
             file_manager,
             collection_id,
-            documents: HashMap::new(),
+            documents: Btree::new(PRIMARY_KEY_DEGREE),
             next_id: 1,
             current_page_id: None,
-        })
+            ttl: None,
+            document_checksums: false,
+            free_space: FreeSpaceMap::new(),
+            path: path.to_path_buf(),
+            primary_key_path: primary_key_index_path(index_path),
+            free_space_path: free_space_map_path(index_path),
+            primary_key_saved: false,
+            recovery: RecoveryReport::default(),
+        };
+
+        if collection.pages().page_count() > 0
+            && !(collection.load_primary_key_index()? && collection.load_free_space_map()?)
+        {
+            collection.rebuild_primary_key_index()?;
+        }
+        Ok(collection)
+    }
+
+    fn pages(&mut self) -> &mut FileManager {
+        self.file_manager.borrow_mut()
+    }
+
+    /// Ids of the stored documents in ascending order
+    pub fn ids(&self) -> Vec<u64> {
+        self.documents.range(..).map(|(id, _)| id).collect()
+    }
+
+    /// Write the modified pages to the collection file, then save the primary key index
+    /// and the free-space map next to it, the next open then doesn't have to scan the
+    /// data pages
+    pub fn flush(&mut self) -> Result<(), DatabaseError> {
+        self.pages().flush()?;
+        if self.primary_key_saved {
+            return Ok(());
+        }
+
+        let entries = self.documents.entries();
+        let mut body = Vec::with_capacity(20 + entries.len() * 14);
+        body.extend_from_slice(&self.pages().page_count().to_le_bytes());
+        body.extend_from_slice(&self.next_id.to_le_bytes());
+        body.extend_from_slice(&self.current_page_id.unwrap_or(u32::MAX).to_le_bytes());
+        body.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        for (id, (page_id, slot_index)) in entries {
+            body.extend_from_slice(&id.to_le_bytes());
+            body.extend_from_slice(&page_id.to_le_bytes());
+            body.extend_from_slice(&slot_index.to_le_bytes());
+        }
+
+        save_index_blob(&self.primary_key_path, PRIMARY_KEY_INDEX_VERSION, &body, 0)?;
+
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.pages().page_count().to_le_bytes());
+        self.free_space.serialize(&mut body);
+        save_index_blob(&self.free_space_path, FREE_SPACE_MAP_VERSION, &body, 0)?;
+
+        self.primary_key_saved = true;
+        Ok(())
+    }
+
+    /// What the integrity pass found when the file was opened. Empty if the file was
+    /// opened from a current primary key index, its pages were not checked then.
+    pub fn recovery_report(&self) -> &RecoveryReport {
+        &self.recovery
+    }
+
+    /// Rebuild the primary key index and the free-space map from the pages,
+    /// checking every page on the way. An incomplete page at the end of the file is cut off.
+    /// A page that does not match its checksum is repaired if all its live records verify
+    /// against their document checksums and quarantined otherwise, as are unreadable pages.
+    pub fn rebuild_primary_key_index(&mut self) -> Result<(), DatabaseError> {
+        self.mark_modified()?;
+        self.documents = Btree::new(PRIMARY_KEY_DEGREE);
+        self.free_space = FreeSpaceMap::new();
+        self.next_id = 1;
+        self.current_page_id = None;
+
+        let mut report = RecoveryReport {
+            truncated_bytes: self.pages().truncate_partial_page()?,
+            pages_restored: self.recovery.pages_restored,
+            ..RecoveryReport::default()
+        };
+        let mut overflow_pages = Vec::new();
+        let mut overflow_heads = Vec::new();
+
+        for page_id in 0..self.pages().page_count() {
+            report.pages_checked += 1;
+            let page = match self.pages().check_page(page_id)? {
+                PageCheck::Valid(page) => page,
+                PageCheck::ChecksumMismatch {
+                    page,
+                    expected,
+                    actual,
+                } => {
+                    let damage = PageDamage::ChecksumMismatch { expected, actual };
+                    match self.repair_page(page_id, page)? {
+                        Ok(page) => {
+                            report.damaged_pages.push(DamagedPage {
+                                page_id,
+                                damage,
+                                action: RecoveryAction::Repaired,
+                            });
+                            page
+                        }
+                        Err(records) => {
+                            report.records_lost += records;
+                            report.damaged_pages.push(DamagedPage {
+                                page_id,
+                                damage,
+                                action: RecoveryAction::Quarantined,
+                            });
+                            continue;
+                        }
+                    }
+                }
+                PageCheck::Unreadable(reason) => {
+                    report.damaged_pages.push(DamagedPage {
+                        page_id,
+                        damage: PageDamage::Unreadable(reason),
+                        action: RecoveryAction::Quarantined,
+                    });
+                    continue;
+                }
+            };
+            if page.header.collection_id != self.collection_id {
+                continue;
+            }
+            if page.header.page_type == PageType::FreePage {
+                self.free_space.release(page_id);
+                continue;
+            }
+            if page.header.page_type == PageType::OverflowPage {
+                overflow_pages.push(page_id);
+                continue;
+            }
+            if page.header.page_type != PageType::DataPage {
+                continue;
+            }
+
+            let reserved = self.pages().reserved_bytes();
+            self.free_space
+                .update(page_id, page.compacted_free_space(reserved));
+            for slot_index in 0..page.slots.len() as u16 {
+                if page.slots[slot_index as usize].is_free() {
+                    continue;
+                }
+                let record = page.get_record(slot_index)?;
+                let Some(id) = record.first_chunk::<8>().map(|id| u64::from_le_bytes(*id)) else {
+                    return Err(DatabaseError::InvalidData(
+                        "Document data too short".to_string(),
+                    ));
+                };
+                if let Ok(Some((_, head))) = parse_overflow_stub(record) {
+                    overflow_heads.push(head);
+                }
+                self.documents.insert(id, (page_id, slot_index));
+                self.next_id = self.next_id.max(id + 1);
+            }
+            self.current_page_id = Some(page_id);
+        }
+
+        // Chains left behind by a crash between writing a record and freeing its old copy
+        let mut used = Vec::new();
+        for head in overflow_heads {
+            used.extend(self.overflow_chain(head));
+        }
+        for page_id in overflow_pages {
+            if !used.contains(&page_id) {
+                self.free_page(page_id)?;
+            }
+        }
+
+        self.recovery = report;
+        Ok(())
+    }
+
+    /// Write a page that does not match its checksum again if every live record verifies
+    /// against its document checksum, returns the repaired page. Otherwise the page is
+    /// left alone and the number of its live records is returned.
+    fn repair_page(
+        &mut self,
+        page_id: u32,
+        mut page: Page,
+    ) -> Result<Result<Page, usize>, DatabaseError> {
+        let live: Vec<u16> = (0..page.slots.len() as u16)
+            .filter(|slot| !page.slots[*slot as usize].is_free())
+            .collect();
+        let verified = live.iter().all(|slot| {
+            page.get_record(*slot).is_ok_and(|record| {
+                let flagged = record
+                    .get(8..12)
+                    .is_some_and(|count| count[3] & (CHECKSUM_FLAG >> 24) as u8 != 0);
+                match parse_overflow_stub(record) {
+                    Ok(Some(_)) => true,
+                    Ok(None) => flagged && self.deserialize_document(record).is_ok(),
+                    Err(_) => false,
+                }
+            })
+        });
+
+        // Other collections' pages are not this collection's to rewrite
+        if !verified || page.header.collection_id != self.collection_id {
+            return Ok(Err(live.len()));
+        }
+        self.pages().write_page(page_id, &mut page)?;
+        Ok(Ok(page))
+    }
+
+    /// Load the index saved by `flush`, false if it is missing, damaged or stale
+    fn load_primary_key_index(&mut self) -> Result<bool, DatabaseError> {
+        let blob = match load_index_blob(
+            &self.primary_key_path,
+            PRIMARY_KEY_INDEX_VERSION,
+            "primary key index",
+        ) {
+            Ok(Some(blob)) => blob,
+            Ok(None) | Err(DatabaseError::InvalidData(_)) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+
+        let mut reader = ByteReader::new(&blob.body);
+        // Pages written after the index was saved
+        if reader.read_u32()? != self.pages().page_count() {
+            return Ok(false);
+        }
+        let next_id = reader.read_u64()?;
+        let current_page_id = reader.read_u32()?;
+
+        // Saved in id order, the tree is built bottom-up
+        let count = reader.read_u32()?;
+        let mut entries = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let id = reader.read_u64()?;
+            let page_id = reader.read_u32()?;
+            let slot_index = reader.read_u16()?;
+            entries.push((id, (page_id, slot_index)));
+        }
+
+        self.documents = Btree::bulk_load(PRIMARY_KEY_DEGREE, entries);
+        self.next_id = next_id;
+        self.current_page_id = (current_page_id != u32::MAX).then_some(current_page_id);
+        self.primary_key_saved = true;
+        Ok(true)
+    }
+
+    /// Load the map saved by `flush`, false if it is missing, damaged or stale
+    fn load_free_space_map(&mut self) -> Result<bool, DatabaseError> {
+        let blob = match load_index_blob(
+            &self.free_space_path,
+            FREE_SPACE_MAP_VERSION,
+            "free-space map",
+        ) {
+            Ok(Some(blob)) => blob,
+            Ok(None) | Err(DatabaseError::InvalidData(_)) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+
+        let mut reader = ByteReader::new(&blob.body);
+        if reader.read_u32()? != self.pages().page_count() {
+            return Ok(false);
+        }
+        self.free_space = FreeSpaceMap::deserialize(&mut reader)?;
+        Ok(true)
+    }
+
+    /// Remove the saved primary key index and free-space map before the first write after
+    /// they were saved, so they are never loaded after a crash that lost later changes
+    fn mark_modified(&mut self) -> Result<(), DatabaseError> {
+        if self.primary_key_saved {
+            for path in [&self.primary_key_path, &self.free_space_path] {
+                if path.exists() {
+                    fs::remove_file(path)?;
+                }
+            }
+            self.primary_key_saved = false;
+        }
+        Ok(())
     }
 
     /// Insert a document using page-based storage
     pub fn insert(&mut self, mut document: Document) -> Result<u64, DatabaseError> {
         document.id = self.next_id;
+        self.schema.normalize_document(&mut document);
         self.schema.validate_document(&document)?;
 
         // Serialize document using existing serialization
-        let serialized_doc = self.serialize_document(&document);
+        let serialized_doc = self.serialize_document(&document)?;
+        self.mark_modified()?;
+        let record = self.store_large_record(document.id, serialized_doc)?;
 
         // Find or create a page with enough space
-        let (page_id, slot_index) = self.find_page_for_insert(&serialized_doc)?;
+        let (page_id, slot_index) = self.find_page_for_insert(&record)?;
 
         // Store mapping from document ID to page location
         self.documents.insert(document.id, (page_id, slot_index));
@@ -52,52 +543,328 @@ impl PagedCollection {
         Ok(document.id)
     }
 
-    /// Find a page with enough space for the record, or create a new one
+    /// Find a page with enough space for the record, or create a new one.
+    /// Pages with room left by deleted records are filled before a page is allocated,
+    /// free pages are reused before the file grows.
     fn find_page_for_insert(&mut self, record_data: &[u8]) -> Result<(u32, u16), DatabaseError> {
         // Try current page first
-        if let Some(current_page_id) = self.current_page_id {
-            if let Ok(mut page) = self.file_manager.read_page(current_page_id) {
-                if page.can_fit(record_data.len()) {
-                    let slot_index = page.insert_record(record_data)?;
-                    self.file_manager.write_page(current_page_id, &mut page)?;
-                    return Ok((current_page_id, slot_index));
-                }
+        if let Some(current_page_id) = self.current_page_id
+            && let Ok(mut page) = self.pages().read_page(current_page_id)
+            && page.can_fit(record_data.len())
+        {
+            let slot_index = page.insert_record(record_data)?;
+            self.write_data_page(current_page_id, &mut page)?;
+            return Ok((current_page_id, slot_index));
+        }
+
+        if let Some(page_id) = self.free_space.find(record_data.len()) {
+            let mut page = self.pages().read_page(page_id)?;
+            if !page.can_fit(record_data.len()) {
+                page.compact(self.pages().reserved_bytes());
             }
+            let slot_index = page.insert_record(record_data)?;
+            self.write_data_page(page_id, &mut page)?;
+            return Ok((page_id, slot_index));
         }
 
-        // Current page is full or doesn't exist, allocate new page
-        let (page_id, mut page) = self
-            .file_manager
-            .allocate_page(PageType::DataPage, self.collection_id)?;
+        // No page has room, allocate new page
+        let (page_id, mut page) = self.allocate_page(PageType::DataPage)?;
 
         let slot_index = page.insert_record(record_data)?;
-        self.file_manager.write_page(page_id, &mut page)?;
+        self.write_data_page(page_id, &mut page)?;
         self.current_page_id = Some(page_id);
 
         Ok((page_id, slot_index))
     }
 
+    /// New page of the collection, a free page if there is one
+    fn allocate_page(&mut self, page_type: PageType) -> Result<(u32, Page), DatabaseError> {
+        let collection_id = self.collection_id;
+        let Some(page_id) = self.free_space.take_free_page() else {
+            return self.pages().allocate_page(page_type, collection_id);
+        };
+        self.pages().trace(TraceEvent::PageReused {
+            page_id,
+            page_type,
+            collection_id,
+        })?;
+        Ok((page_id, self.pages().new_page(page_type, collection_id)))
+    }
+
+    /// Rewrite the page as a free page and record it for reuse
+    fn free_page(&mut self, page_id: u32) -> Result<(), DatabaseError> {
+        let collection_id = self.collection_id;
+        let mut free = self.pages().new_page(PageType::FreePage, collection_id);
+        self.pages().write_page(page_id, &mut free)?;
+        self.free_space.release(page_id);
+        if self.current_page_id == Some(page_id) {
+            self.current_page_id = None;
+        }
+        self.pages().trace(TraceEvent::PageFreed { page_id })
+    }
+
+    /// Record to store in a data page for the serialized document. A document too large
+    /// for an empty data page is written to a chain of overflow pages, its record is a stub
+    /// pointing at the first page of the chain.
+    fn store_large_record(
+        &mut self,
+        id: u64,
+        serialized: Vec<u8>,
+    ) -> Result<Vec<u8>, DatabaseError> {
+        let reserved = self.pages().reserved_bytes();
+        if serialized.len() + SLOT_SIZE <= MAX_PAGE_DATA_SIZE - reserved {
+            return Ok(serialized);
+        }
+
+        // Each overflow page holds the next page id followed by a chunk
+        let capacity = MAX_PAGE_DATA_SIZE - reserved - SLOT_SIZE - 4;
+        let mut pages = Vec::new();
+        for _ in 0..serialized.len().div_ceil(capacity) {
+            pages.push(self.allocate_page(PageType::OverflowPage)?);
+        }
+        let head = pages[0].0;
+        let next_pages: Vec<u32> = pages.iter().skip(1).map(|(page_id, _)| *page_id).collect();
+        for (((page_id, mut page), chunk), next) in pages
+            .into_iter()
+            .zip(serialized.chunks(capacity))
+            .zip(next_pages.into_iter().chain([NO_NEXT_PAGE]))
+        {
+            let mut bytes = Vec::with_capacity(4 + chunk.len());
+            bytes.extend_from_slice(&next.to_le_bytes());
+            bytes.extend_from_slice(chunk);
+            page.insert_record(&bytes)?;
+            self.pages().write_page(page_id, &mut page)?;
+        }
+
+        let mut stub = Vec::with_capacity(OVERFLOW_STUB_SIZE);
+        stub.extend_from_slice(&id.to_le_bytes());
+        stub.extend_from_slice(&(OVERFLOW_FLAG | CHECKSUM_FLAG).to_le_bytes());
+        stub.extend_from_slice(&(serialized.len() as u32).to_le_bytes());
+        stub.extend_from_slice(&head.to_le_bytes());
+        stub.extend_from_slice(&crc32(&stub).to_le_bytes());
+        Ok(stub)
+    }
+
+    /// Document stored in the record, read from its overflow pages if the record is a stub
+    pub(crate) fn read_document(&mut self, record: &[u8]) -> Result<Document, DatabaseError> {
+        let Some((length, head)) = parse_overflow_stub(record)? else {
+            return self.deserialize_document(record);
+        };
+
+        let bytes = read_overflow(self.pages(), record, length, head)?;
+        self.deserialize_document(&bytes)
+    }
+
+    /// Pages of the overflow chain starting at `head`, up to the first page that cannot be
+    /// read as an overflow page of the collection
+    fn overflow_chain(&mut self, head: u32) -> Vec<u32> {
+        let mut chain = Vec::new();
+        let mut page_id = head;
+        while page_id != NO_NEXT_PAGE && !chain.contains(&page_id) {
+            let Ok(page) = self.pages().read_page(page_id) else {
+                break;
+            };
+            if page.header.page_type != PageType::OverflowPage
+                || page.header.collection_id != self.collection_id
+            {
+                break;
+            }
+            chain.push(page_id);
+            match page
+                .get_record(0)
+                .map(|chunk| chunk.first_chunk::<4>().copied())
+            {
+                Ok(Some(next)) => page_id = u32::from_le_bytes(next),
+                _ => break,
+            }
+        }
+        chain
+    }
+
+    /// Free the overflow pages of a record if it is a stub
+    fn free_overflow(&mut self, record: &[u8]) -> Result<(), DatabaseError> {
+        if let Ok(Some((_, head))) = parse_overflow_stub(record) {
+            for page_id in self.overflow_chain(head) {
+                self.free_page(page_id)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a data page and record the space left in it
+    fn write_data_page(&mut self, page_id: u32, page: &mut Page) -> Result<(), DatabaseError> {
+        self.pages().write_page(page_id, page)?;
+        let reserved = self.pages().reserved_bytes();
+        self.free_space
+            .update(page_id, page.compacted_free_space(reserved));
+        Ok(())
+    }
+
     /// Retrieve a document by ID
     pub fn find_by_id(&mut self, id: u64) -> Result<Option<Document>, DatabaseError> {
-        if let Some((page_id, slot_index)) = self.documents.get(&id) {
-            let page = self.file_manager.read_page(*page_id)?;
-            let record_data = page.get_record(*slot_index)?;
-            let document = self.deserialize_document(record_data)?;
+        if let Some((page_id, slot_index)) = self.documents.get(id) {
+            let page = self.pages().read_page(page_id)?;
+            let record_data = page.get_record(slot_index)?;
+            let document = self.read_document(record_data)?;
+            if document.id != id {
+                return Err(DatabaseError::CorruptDocument { id });
+            }
+
+            // Expired documents are invisible even before the sweep removes them
+            if let Some(ttl) = &self.ttl
+                && ttl.is_expired(&document, current_time_millis())
+            {
+                return Ok(None);
+            }
+
             Ok(Some(document))
         } else {
             Ok(None)
         }
     }
 
+    /// Stream all live documents page by page
+    pub fn iter(&mut self) -> DocumentCursor<'_, F> {
+        DocumentCursor::new(self, None)
+    }
+
+    /// Up to `size` documents picked uniformly at random in a single pass over the pages
+    pub fn sample(&mut self, size: usize) -> Result<Vec<Document>, DatabaseError> {
+        // Stop at the first unreadable document instead of leaving it out of the sample
+        let mut error = None;
+        let documents = self
+            .iter()
+            .map_while(|document| document.map_err(|e| error = Some(e)).ok());
+        let sampled = reservoir_sample(documents, size, &mut Random::new());
+
+        match error {
+            Some(error) => Err(error),
+            None => Ok(sampled),
+        }
+    }
+
+    /// Stream the documents matching the query page by page
+    pub fn find_where_iter(
+        &mut self,
+        query: Query,
+    ) -> Result<DocumentCursor<'_, F>, DatabaseError> {
+        query.validate(&self.schema)?;
+        Ok(DocumentCursor::new(self, Some(query)))
+    }
+
+    /// Replace a document. The record stays in its slot if it fits in its page,
+    /// otherwise it moves to another page and its old slot is freed.
+    pub fn update(&mut self, id: u64, mut document: Document) -> Result<(), DatabaseError> {
+        let Some((page_id, slot_index)) = self.documents.get(id) else {
+            return Err(DatabaseError::DocumentNotFound(id));
+        };
+        document.id = id;
+        self.schema.normalize_document(&mut document);
+        self.schema.validate_document(&document)?;
+
+        let serialized_doc = self.serialize_document(&document)?;
+        self.mark_modified()?;
+        let record = self.store_large_record(id, serialized_doc)?;
+
+        let mut page = self.pages().read_page(page_id)?;
+        let old = page.get_record(slot_index)?.to_vec();
+        if page.update_record(slot_index, &record)? {
+            self.write_data_page(page_id, &mut page)?;
+            return self.free_overflow(&old);
+        }
+
+        // Written to its new place before the old record is freed, a crash in between
+        // leaves two copies rather than none
+        let location = self.find_page_for_insert(&record)?;
+        self.documents.insert(id, location);
+        self.free_slot(page_id, slot_index)
+    }
+
+    /// Delete a document by ID, freeing its page slot.
+    /// A page left without records is freed for reuse.
+    pub fn delete(&mut self, id: u64) -> Result<(), DatabaseError> {
+        let Some((page_id, slot_index)) = self.documents.get(id) else {
+            return Err(DatabaseError::DocumentNotFound(id));
+        };
+        self.mark_modified()?;
+        self.documents.delete(id);
+        self.free_slot(page_id, slot_index)
+    }
+
+    /// Free the slot of a deleted or moved record and its overflow pages,
+    /// freeing its page once it is empty
+    fn free_slot(&mut self, page_id: u32, slot_index: u16) -> Result<(), DatabaseError> {
+        let mut page = self.pages().read_page(page_id)?;
+        let record = page.get_record(slot_index)?.to_vec();
+        page.delete_record(slot_index)?;
+        self.pages().trace(TraceEvent::SlotFreed {
+            page_id,
+            slot_index,
+        })?;
+
+        if page.live_record_count() > 0 {
+            self.write_data_page(page_id, &mut page)?;
+        } else {
+            self.free_page(page_id)?;
+        }
+        self.free_overflow(&record)
+    }
+
+    /// Store a checksum with every document written from now on.
+    /// Checksums are verified on read whenever a record carries one,
+    /// so records written before checksums were enabled stay readable.
+    pub fn set_document_checksums(&mut self, enabled: bool) {
+        self.document_checksums = enabled;
+    }
+
+    /// Expire documents `expire_after` past the timestamp stored in the given long field.
+    /// The policy is kept in memory, `PagedDatabase::set_ttl` records it in the catalog.
+    pub fn set_ttl(&mut self, field: &str, expire_after: Duration) -> Result<(), DatabaseError> {
+        self.ttl = Some(TtlPolicy::new(&self.schema, field, expire_after)?);
+        Ok(())
+    }
+
+    pub fn clear_ttl(&mut self) {
+        self.ttl = None;
+    }
+
+    /// Remove expired documents and free their page slots,
+    /// returns the number of removed documents
+    pub fn purge_expired(&mut self) -> Result<usize, DatabaseError> {
+        let Some(ttl) = self.ttl.clone() else {
+            return Ok(0);
+        };
+
+        let now = current_time_millis();
+        let mut expired = Vec::new();
+        for (id, (page_id, slot_index)) in self.documents.entries() {
+            let page = self.pages().read_page(page_id)?;
+            let document = self.read_document(page.get_record(slot_index)?)?;
+            if ttl.is_expired(&document, now) {
+                expired.push(id);
+            }
+        }
+
+        for id in &expired {
+            self.delete(*id)?;
+        }
+
+        Ok(expired.len())
+    }
+
     /// Reuse existing document serialization logic
-    fn serialize_document(&self, document: &Document) -> Vec<u8> {
+    fn serialize_document(&self, document: &Document) -> Result<Vec<u8>, DatabaseError> {
         let mut bytes = Vec::new();
 
         // Write document ID
         bytes.extend_from_slice(&document.id.to_le_bytes());
 
-        // Write field count
-        bytes.extend_from_slice(&(document.data.len() as u32).to_le_bytes());
+        // Write field count, flagged when the record ends with a checksum
+        let mut field_count = document.data.len() as u32;
+        if self.document_checksums {
+            field_count |= CHECKSUM_FLAG;
+        }
+        bytes.extend_from_slice(&field_count.to_le_bytes());
 
         // Write fields
         for (key, value) in &document.data {
@@ -105,15 +872,21 @@ impl PagedCollection {
             bytes.push(key_bytes.len() as u8);
             bytes.extend_from_slice(key_bytes);
 
-            let value_bytes = value.serialize();
+            let value_bytes = value.serialize()?;
             bytes.extend_from_slice(&value_bytes);
         }
 
-        bytes
+        // Trailing checksum over the document bytes
+        if self.document_checksums {
+            let checksum = crc32(&bytes);
+            bytes.extend_from_slice(&checksum.to_le_bytes());
+        }
+
+        Ok(bytes)
     }
 
     /// Reuse existing document deserialization logic
-    fn deserialize_document(&self, bytes: &[u8]) -> Result<Document, DatabaseError> {
+    pub(crate) fn deserialize_document(&self, bytes: &[u8]) -> Result<Document, DatabaseError> {
         let mut offset: usize;
 
         if bytes.len() < 12 {
@@ -134,9 +907,24 @@ impl PagedCollection {
             bytes[offset + 1],
             bytes[offset + 2],
             bytes[offset + 3],
-        ]) as usize;
+        ]);
         offset += 4;
 
+        // Verify the checksum before trusting any of the field data
+        let mut bytes = bytes;
+        if field_count & CHECKSUM_FLAG != 0 {
+            if bytes.len() < 16 {
+                return Err(DatabaseError::CorruptDocument { id });
+            }
+            let (body, checksum) = bytes.split_at(bytes.len() - 4);
+            let stored = u32::from_le_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]);
+            if stored != crc32(body) {
+                return Err(DatabaseError::CorruptDocument { id });
+            }
+            bytes = body;
+        }
+        let field_count = (field_count & !CHECKSUM_FLAG) as usize;
+
         let mut data = HashMap::new();
 
         // Read fields
@@ -168,22 +956,131 @@ impl PagedCollection {
             offset += value_size;
         }
 
-        Ok(Document { id, data })
+        Ok(Document {
+            id,
+            data,
+            version: 0,
+        })
+    }
+
+    /// Load the pages of the collection into the file's buffer pool,
+    /// see `FileManager::warmup`
+    pub fn warmup(&mut self) -> Result<WarmupReport, DatabaseError> {
+        let plan = WarmupPlan::new().with_collection(self.collection_id);
+        self.pages().warmup(&plan)
     }
 
     /// Get statistics about the collection
     pub fn stats(&self) -> CollectionStats {
         CollectionStats {
             total_documents: self.documents.len(),
-            total_pages: self.file_manager.page_count(),
+            total_pages: self.file_manager.borrow().page_count(),
+            free_pages: self.free_space.free_pages().len(),
             collection_id: self.collection_id,
         }
     }
 }
 
+/// Serialized document of a stub record, read from the overflow chain starting at `head`
+fn read_overflow(
+    file_manager: &mut FileManager,
+    record: &[u8],
+    length: u32,
+    head: u32,
+) -> Result<Vec<u8>, DatabaseError> {
+    let mut bytes = Vec::with_capacity(length as usize);
+    let mut page_id = head;
+    while page_id != NO_NEXT_PAGE && bytes.len() < length as usize {
+        let page = file_manager.read_page(page_id)?;
+        if page.header.page_type != PageType::OverflowPage {
+            break;
+        }
+        let chunk = page.get_record(0)?;
+        let Some((next, chunk)) = chunk.split_first_chunk::<4>() else {
+            break;
+        };
+        bytes.extend_from_slice(chunk);
+        page_id = u32::from_le_bytes(*next);
+    }
+
+    if bytes.len() != length as usize {
+        let id = u64::from_le_bytes(record[..8].try_into().unwrap_or_default());
+        return Err(DatabaseError::CorruptDocument { id });
+    }
+    Ok(bytes)
+}
+
+/// Length and first overflow page of the document of a stub record, `None` for records
+/// holding their document. A damaged stub is a `CorruptDocument` error.
+fn parse_overflow_stub(record: &[u8]) -> Result<Option<(u32, u32)>, DatabaseError> {
+    let Some(flags) = record.get(8..12) else {
+        return Ok(None);
+    };
+    if u32::from_le_bytes([flags[0], flags[1], flags[2], flags[3]]) & OVERFLOW_FLAG == 0 {
+        return Ok(None);
+    }
+
+    let id = u64::from_le_bytes(record[..8].try_into().unwrap_or_default());
+    if record.len() != OVERFLOW_STUB_SIZE {
+        return Err(DatabaseError::CorruptDocument { id });
+    }
+    let (stub, checksum) = record.split_at(OVERFLOW_STUB_SIZE - 4);
+    if crc32(stub).to_le_bytes() != checksum {
+        return Err(DatabaseError::CorruptDocument { id });
+    }
+    let length = u32::from_le_bytes([stub[12], stub[13], stub[14], stub[15]]);
+    let head = u32::from_le_bytes([stub[16], stub[17], stub[18], stub[19]]);
+    Ok(Some((length, head)))
+}
+
+/// Primary key index file of the collection file at `path`: `<path>.pk`
+pub fn primary_key_index_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".pk");
+    PathBuf::from(path)
+}
+
+/// Free-space map file of the collection file at `path`: `<path>.fsm`
+pub fn free_space_map_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".fsm");
+    PathBuf::from(path)
+}
+
+/// Compacted copy of the collection file at `path` written by `vacuum`: `<path>.vacuum`
+fn vacuum_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".vacuum");
+    PathBuf::from(path)
+}
+
+/// Bytes of the collection file at `path` and its packed pages
+fn stored_bytes(path: &Path) -> Result<u64, DatabaseError> {
+    let packed = match fs::metadata(packed_pages_path(path)) {
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+    };
+    Ok(fs::metadata(path)?.len() + packed)
+}
+
+/// Size of a collection before and after `vacuum`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VacuumReport {
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+impl VacuumReport {
+    pub fn bytes_reclaimed(&self) -> u64 {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+}
+
 #[derive(Debug)]
 pub struct CollectionStats {
     pub total_documents: usize,
     pub total_pages: u32,
+    /// Pages without records, reused by the next inserts
+    pub free_pages: usize,
     pub collection_id: u32,
 }
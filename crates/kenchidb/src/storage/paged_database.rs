@@ -0,0 +1,202 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    common::DatabaseError,
+    schema::{IdentifierKind, Schema, validate_identifier},
+    storage::{
+        catalog::{Catalog, load_catalog, save_catalog},
+        double_write::double_write_path,
+        file_manager::{CollectionPages, FileManager},
+        paged_collection::PagedCollection,
+    },
+};
+
+/// Paged collections of a database sharing a single page file, told apart by the collection
+/// id in each page header. Names, ids, schemas and TTL policies are recorded in a catalog
+/// next to the file, each collection saves its primary key index and free-space map under its id.
+pub struct PagedDatabase {
+    pub file_manager: FileManager,
+    path: PathBuf,
+    catalog: Catalog,
+    /// Collection states by name, given the file manager while they are used
+    collections: BTreeMap<String, PagedCollection<()>>,
+}
+
+impl PagedDatabase {
+    /// Create the database file, or open an existing one with every collection in its catalog
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, DatabaseError> {
+        let path = path.as_ref().to_path_buf();
+        let mut file_manager = FileManager::new(&path)?;
+        // Torn pages of an interrupted flush are repaired before any collection checks them
+        let pages_restored = file_manager.restore_double_write(&double_write_path(&path))?;
+        let (catalog, _) = load_catalog(&paged_catalog_path(&path))?;
+
+        let mut database = Self {
+            file_manager,
+            path,
+            catalog: Catalog::default(),
+            collections: BTreeMap::new(),
+        };
+        for entry in &catalog.collections {
+            let mut collection = database.open_collection(entry.schema.clone(), entry.id)?;
+            collection.next_id = collection.next_id.max(entry.next_id);
+            collection.recovery.pages_restored = pages_restored;
+            if let Some(ttl) = &entry.ttl {
+                collection.set_ttl(&ttl.field, ttl.expire_after)?;
+            }
+            let (collection, _) = collection.with_file_manager(());
+            database.collections.insert(entry.name.clone(), collection);
+        }
+        database.catalog = catalog;
+        Ok(database)
+    }
+
+    /// Add a collection to the file, returns its collection id
+    pub fn create_collection(&mut self, name: &str, schema: Schema) -> Result<u32, DatabaseError> {
+        validate_identifier(IdentifierKind::Collection, name)?;
+        schema.validate()?;
+        if self.collections.contains_key(name) {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "Collection '{}' already exists",
+                name
+            )));
+        }
+
+        self.catalog.register(name, &self.path, &schema, 1);
+        let id = self.catalog.entry(name).map_or(0, |entry| entry.id);
+        let collection = self.open_collection(schema, id)?;
+        let (collection, _) = collection.with_file_manager(());
+        self.collections.insert(name.to_string(), collection);
+        self.save_catalog()?;
+        Ok(id)
+    }
+
+    /// Names of the collections in ascending order
+    pub fn collection_names(&self) -> Vec<&str> {
+        self.collections.keys().map(String::as_str).collect()
+    }
+
+    /// Run `f` on the collection with the shared file manager
+    pub fn with_collection<R, F>(&mut self, name: &str, f: F) -> Result<R, DatabaseError>
+    where
+        F: FnOnce(&mut PagedCollection<&mut FileManager>) -> Result<R, DatabaseError>,
+    {
+        let Some(collection) = self.collections.remove(name) else {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "Collection '{}' not found",
+                name
+            )));
+        };
+        let (mut collection, _) = collection.with_file_manager(&mut self.file_manager);
+        let result = f(&mut collection);
+        let (collection, _) = collection.with_file_manager(());
+        self.collections.insert(name.to_string(), collection);
+        result
+    }
+
+    /// Pages the collection uses and has freed in the file
+    pub fn collection_pages(&mut self, name: &str) -> Result<CollectionPages, DatabaseError> {
+        let Some(collection) = self.collections.get(name) else {
+            return Err(DatabaseError::InvalidQuery(format!(
+                "Collection '{}' not found",
+                name
+            )));
+        };
+        self.file_manager.collection_pages(collection.collection_id)
+    }
+
+    /// Expire documents of the collection like `PagedCollection::set_ttl`, the policy is
+    /// recorded in the catalog and applied again when the database is opened
+    pub fn set_ttl(
+        &mut self,
+        name: &str,
+        field: &str,
+        expire_after: Duration,
+    ) -> Result<(), DatabaseError> {
+        let ttl = self.with_collection(name, |collection| {
+            collection.set_ttl(field, expire_after)?;
+            Ok(collection.ttl.clone())
+        })?;
+        self.catalog.set_ttl(name, ttl);
+        self.save_catalog()
+    }
+
+    /// Remove the TTL policy of the collection, also from the catalog
+    pub fn clear_ttl(&mut self, name: &str) -> Result<(), DatabaseError> {
+        self.with_collection(name, |collection| {
+            collection.clear_ttl();
+            Ok(())
+        })?;
+        self.catalog.set_ttl(name, None);
+        self.save_catalog()
+    }
+
+    /// Sweep expired documents from all collections with a TTL policy,
+    /// returns the number of removed documents
+    pub fn purge_expired(&mut self) -> Result<usize, DatabaseError> {
+        let names: Vec<String> = self.collections.keys().cloned().collect();
+        let mut removed = 0;
+        for name in names {
+            removed += self.with_collection(&name, |collection| collection.purge_expired())?;
+        }
+        Ok(removed)
+    }
+
+    /// Write the modified pages, save the indexes of every collection and record the
+    /// next document ids and TTL policies in the catalog
+    pub fn flush(&mut self) -> Result<(), DatabaseError> {
+        self.file_manager.flush()?;
+        let names: Vec<String> = self.collections.keys().cloned().collect();
+        for name in names {
+            let (schema, next_id, ttl) = self.with_collection(&name, |collection| {
+                collection.flush()?;
+                Ok((
+                    collection.schema.clone(),
+                    collection.next_id,
+                    collection.ttl.clone(),
+                ))
+            })?;
+            self.catalog.register(&name, &self.path, &schema, next_id);
+            self.catalog.set_ttl(&name, ttl);
+        }
+        self.save_catalog()
+    }
+
+    fn open_collection(
+        &mut self,
+        schema: Schema,
+        collection_id: u32,
+    ) -> Result<PagedCollection<&mut FileManager>, DatabaseError> {
+        let index_path = collection_index_path(&self.path, collection_id);
+        PagedCollection::open_in(
+            schema,
+            collection_id,
+            &mut self.file_manager,
+            &self.path,
+            &index_path,
+        )
+    }
+
+    fn save_catalog(&self) -> Result<(), DatabaseError> {
+        save_catalog(&paged_catalog_path(&self.path), &self.catalog, &[])
+    }
+}
+
+/// Catalog of the database file at `path`: `<path>.catalog`
+pub fn paged_catalog_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".catalog");
+    PathBuf::from(path)
+}
+
+/// Path the index files of a collection in the database file at `path` are named after:
+/// `<path>.<collection id>`, e.g. `<path>.2.pk` for its primary key index
+pub fn collection_index_path(path: &Path, collection_id: u32) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(format!(".{}", collection_id));
+    PathBuf::from(path)
+}
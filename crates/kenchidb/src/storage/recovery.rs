@@ -0,0 +1,83 @@
+use crate::storage::page::Page;
+
+/// What is wrong with a page found by the integrity pass on open
+#[derive(Debug, Clone, PartialEq)]
+pub enum PageDamage {
+    /// Bad magic number, page type or layout, e.g. a page allocated but never written
+    Unreadable(String),
+    /// The page does not match its checksum, e.g. torn by a crash in the middle of a write
+    ChecksumMismatch { expected: u32, actual: u32 },
+}
+
+/// What the integrity pass did about a damaged page
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecoveryAction {
+    /// Every live record verified against its document checksum,
+    /// the page was written again with a fresh checksum
+    Repaired,
+    /// The page was left as it is and its records are not part of the collection
+    Quarantined,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DamagedPage {
+    pub page_id: u32,
+    pub damage: PageDamage,
+    pub action: RecoveryAction,
+}
+
+/// Outcome of checking a file when it is opened after a crash.
+/// Damage is reported here instead of being accepted silently or failing the open.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecoveryReport {
+    /// Pages whose header and checksum were verified
+    pub pages_checked: u32,
+    /// Pages rewritten from the double-write file of a flush that did not finish
+    pub pages_restored: u32,
+    pub damaged_pages: Vec<DamagedPage>,
+    /// Live records of quarantined pages left out of the collection,
+    /// not counting those of unreadable pages
+    pub records_lost: usize,
+    /// Bytes of an incomplete page at the end of the file that were cut off
+    pub truncated_bytes: u64,
+    /// Committed writes replayed from the write-ahead log
+    pub wal_records_replayed: usize,
+    /// Bytes of a torn or uncommitted tail cut off the write-ahead log
+    pub wal_bytes_discarded: u64,
+    /// Transaction prepared at the end of the write-ahead log, and whether it committed
+    pub prepared_transaction: Option<(u64, bool)>,
+    /// Transaction prepared at the end of the write-ahead log for an external coordinator,
+    /// its writes wait for `Database::commit_prepared` or `Database::rollback_prepared`
+    pub in_doubt_transaction: Option<u64>,
+}
+
+impl RecoveryReport {
+    /// Nothing was damaged or lost
+    pub fn is_clean(&self) -> bool {
+        self.damaged_pages.is_empty()
+            && self.records_lost == 0
+            && self.truncated_bytes == 0
+            && self.wal_bytes_discarded == 0
+            && !matches!(self.prepared_transaction, Some((_, false)))
+    }
+
+    pub fn quarantined_pages(&self) -> Vec<u32> {
+        self.damaged_pages
+            .iter()
+            .filter(|page| page.action == RecoveryAction::Quarantined)
+            .map(|page| page.page_id)
+            .collect()
+    }
+}
+
+/// Page read by the integrity pass, see `FileManager::check_page`
+pub enum PageCheck {
+    Valid(Page),
+    /// The page could be read but its contents do not match the checksum
+    ChecksumMismatch {
+        page: Page,
+        expected: u32,
+        actual: u32,
+    },
+    Unreadable(String),
+}
@@ -0,0 +1,112 @@
+use std::{
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{common::DatabaseError, storage::flusher::FlushSignal};
+
+/// When the background sweeper removes expired documents
+#[derive(Debug, Clone, Copy)]
+pub struct SweeperConfig {
+    /// Time between two sweeps
+    pub interval: Duration,
+}
+
+impl Default for SweeperConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Thread removing the expired documents of a shared value every interval, e.g. a
+/// `Database` swept with `Database::purge_expired`. Expired documents are hidden from
+/// reads already, the sweep frees the room they take. The value is locked for a sweep.
+pub struct ExpirySweeper {
+    signal: Arc<FlushSignal>,
+    handle: Option<JoinHandle<()>>,
+    error: Arc<Mutex<Option<DatabaseError>>>,
+    removed: Arc<AtomicUsize>,
+}
+
+impl ExpirySweeper {
+    /// Start sweeping the shared value with `sweep`, which returns the number of removed
+    /// documents, e.g. `|collection: &mut Collection| collection.purge_expired()`
+    pub fn start<T: Send + 'static>(
+        shared: Arc<Mutex<T>>,
+        sweep: fn(&mut T) -> Result<usize, DatabaseError>,
+        config: SweeperConfig,
+    ) -> Self {
+        let signal = Arc::new(FlushSignal::default());
+        let error = Arc::new(Mutex::new(None));
+        let removed = Arc::new(AtomicUsize::new(0));
+        let handle = {
+            let signal = Arc::clone(&signal);
+            let error = Arc::clone(&error);
+            let removed = Arc::clone(&removed);
+            thread::spawn(move || {
+                while !signal.wait(config.interval) {
+                    let result = sweep(&mut shared.lock().unwrap_or_else(PoisonError::into_inner));
+                    match result {
+                        Ok(count) => {
+                            removed.fetch_add(count, Ordering::Relaxed);
+                        }
+                        Err(e) => {
+                            *error.lock().unwrap_or_else(PoisonError::into_inner) = Some(e);
+                        }
+                    }
+                }
+            })
+        };
+
+        Self {
+            signal,
+            handle: Some(handle),
+            error,
+            removed,
+        }
+    }
+
+    /// Sweep now rather than at the end of the interval
+    pub fn sweep_now(&self) {
+        self.signal.notify();
+    }
+
+    /// Documents the sweeps removed since the sweeper was started
+    pub fn removed(&self) -> usize {
+        self.removed.load(Ordering::Relaxed)
+    }
+
+    /// Stop the thread, a sweep in progress is finished first.
+    /// Returns the last error a sweep ran into.
+    pub fn stop(mut self) -> Result<(), DatabaseError> {
+        self.join();
+        match self
+            .error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn join(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            self.signal.stop();
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for ExpirySweeper {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
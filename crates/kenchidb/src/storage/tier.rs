@@ -0,0 +1,149 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use crate::{common::DatabaseError, storage::page::PAGE_SIZE};
+
+/// Page id marking an unused slot of the cold tier file
+const FREE_SLOT: u32 = u32::MAX;
+
+/// Size of a cold tier slot, the page id followed by the page
+const COLD_SLOT_SIZE: u64 = 4 + PAGE_SIZE as u64;
+
+/// When pages move between the fast and the cold tier
+#[derive(Debug, Clone, Copy)]
+pub struct TierPolicy {
+    /// A cold page read this many times since the last migration is promoted back
+    pub promote_after_reads: u32,
+    /// Migration demotes fast tier pages read fewer times than this since the last migration
+    pub demote_below_reads: u32,
+}
+
+impl Default for TierPolicy {
+    fn default() -> Self {
+        Self {
+            promote_after_reads: 3,
+            demote_below_reads: 1,
+        }
+    }
+}
+
+/// Second page file on slower storage (HDD, network volume).
+/// Pages keep their ids when they move here, the cold file is a list of
+/// `[page id][page]` slots and the page index is rebuilt by scanning it on open.
+/// A page found in the cold file takes precedence over its fast tier copy.
+pub struct ColdTier {
+    file: File,
+    pub policy: TierPolicy,
+    /// page_id -> slot index
+    slots: HashMap<u32, u64>,
+    free_slots: Vec<u64>,
+    slot_count: u64,
+}
+
+impl ColdTier {
+    pub fn open<P: AsRef<Path>>(path: P, policy: TierPolicy) -> Result<Self, DatabaseError> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(path)?;
+
+        let slot_count = file.metadata()?.len() / COLD_SLOT_SIZE;
+        let mut slots = HashMap::new();
+        let mut free_slots = Vec::new();
+
+        for slot in 0..slot_count {
+            let mut page_id = [0u8; 4];
+            file.seek(SeekFrom::Start(slot * COLD_SLOT_SIZE))?;
+            file.read_exact(&mut page_id)?;
+
+            match u32::from_le_bytes(page_id) {
+                FREE_SLOT => free_slots.push(slot),
+                page_id => {
+                    slots.insert(page_id, slot);
+                }
+            }
+        }
+
+        Ok(Self {
+            file,
+            policy,
+            slots,
+            free_slots,
+            slot_count,
+        })
+    }
+
+    pub fn contains(&self, page_id: u32) -> bool {
+        self.slots.contains_key(&page_id)
+    }
+
+    /// Number of pages stored in the cold tier
+    pub fn page_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Read a page stored in the cold tier
+    pub fn read(
+        &mut self,
+        page_id: u32,
+        buffer: &mut [u8; PAGE_SIZE],
+    ) -> Result<(), DatabaseError> {
+        let Some(slot) = self.slots.get(&page_id) else {
+            return Err(DatabaseError::InvalidData(format!(
+                "Page {} is not in the cold tier",
+                page_id
+            )));
+        };
+
+        self.file.seek(SeekFrom::Start(slot * COLD_SLOT_SIZE + 4))?;
+        self.file.read_exact(buffer)?;
+        Ok(())
+    }
+
+    /// Write a page to the cold tier, in place if it is already stored here
+    pub fn write(
+        &mut self,
+        page_id: u32,
+        page_bytes: &[u8; PAGE_SIZE],
+    ) -> Result<(), DatabaseError> {
+        if let Some(slot) = self.slots.get(&page_id) {
+            self.file.seek(SeekFrom::Start(slot * COLD_SLOT_SIZE + 4))?;
+            self.file.write_all(page_bytes)?;
+            self.file.flush()?;
+            return Ok(());
+        }
+
+        let slot = self.free_slots.pop().unwrap_or_else(|| {
+            self.slot_count += 1;
+            self.slot_count - 1
+        });
+
+        // Page first in a slot still marked free, the id makes it visible on the next open
+        self.file.seek(SeekFrom::Start(slot * COLD_SLOT_SIZE))?;
+        self.file.write_all(&FREE_SLOT.to_le_bytes())?;
+        self.file.write_all(page_bytes)?;
+        self.file.seek(SeekFrom::Start(slot * COLD_SLOT_SIZE))?;
+        self.file.write_all(&page_id.to_le_bytes())?;
+        self.file.flush()?;
+
+        self.slots.insert(page_id, slot);
+        Ok(())
+    }
+
+    /// Free the slot of a page that moved back to the fast tier
+    pub fn remove(&mut self, page_id: u32) -> Result<(), DatabaseError> {
+        if let Some(slot) = self.slots.remove(&page_id) {
+            self.file.seek(SeekFrom::Start(slot * COLD_SLOT_SIZE))?;
+            self.file.write_all(&FREE_SLOT.to_le_bytes())?;
+            self.file.flush()?;
+            self.free_slots.push(slot);
+        }
+        Ok(())
+    }
+}
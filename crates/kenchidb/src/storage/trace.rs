@@ -0,0 +1,149 @@
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{common::DatabaseError, schema::current_time_millis, storage::page::PageType};
+
+/// Default maximum size of the trace file before it is rotated.
+pub const DEFAULT_TRACE_SIZE: u64 = 1024 * 1024; // 1 MiB
+
+/// Structural storage operations recorded in the trace.
+#[derive(Debug, Clone)]
+pub enum TraceEvent {
+    /// A new page was allocated at the end of the file.
+    PageAllocated {
+        page_id: u32,
+        page_type: PageType,
+        collection_id: u32,
+    },
+    /// A page write extended the file.
+    FileGrown { from_pages: u32, to_pages: u32 },
+    /// An extent of zeroed pages was added to the file ahead of use.
+    FilePreallocated { from_pages: u32, to_pages: u32 },
+    /// A record slot was freed.
+    SlotFreed { page_id: u32, slot_index: u16 },
+    /// The last record of a page was deleted, the page is free for reuse.
+    PageFreed { page_id: u32 },
+    /// A free page was allocated again.
+    PageReused {
+        page_id: u32,
+        page_type: PageType,
+        collection_id: u32,
+    },
+    /// A rarely read page moved to the cold tier.
+    PageDemoted { page_id: u32 },
+    /// A frequently read page moved back to the fast tier.
+    PagePromoted { page_id: u32 },
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceEvent::PageAllocated {
+                page_id,
+                page_type,
+                collection_id,
+            } => write!(
+                f,
+                "page_allocated page={} type={:?} collection={}",
+                page_id, page_type, collection_id
+            ),
+            TraceEvent::FileGrown {
+                from_pages,
+                to_pages,
+            } => write!(f, "file_grown from={} to={}", from_pages, to_pages),
+            TraceEvent::FilePreallocated {
+                from_pages,
+                to_pages,
+            } => write!(f, "file_preallocated from={} to={}", from_pages, to_pages),
+            TraceEvent::SlotFreed {
+                page_id,
+                slot_index,
+            } => write!(f, "slot_freed page={} slot={}", page_id, slot_index),
+            TraceEvent::PageFreed { page_id } => write!(f, "page_freed page={}", page_id),
+            TraceEvent::PageReused {
+                page_id,
+                page_type,
+                collection_id,
+            } => write!(
+                f,
+                "page_reused page={} type={:?} collection={}",
+                page_id, page_type, collection_id
+            ),
+            TraceEvent::PageDemoted { page_id } => write!(f, "page_demoted page={}", page_id),
+            TraceEvent::PagePromoted { page_id } => write!(f, "page_promoted page={}", page_id),
+        }
+    }
+}
+
+/// Append-only trace of structural storage operations.
+/// One line per event is appended to the trace file, once the file exceeds
+/// `max_size` it is rotated to `<path>.old`, so at most two files are kept.
+pub struct StructuralTrace {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+}
+
+impl StructuralTrace {
+    pub fn open<P: AsRef<Path>>(path: P, max_size: u64) -> Result<Self, DatabaseError> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_size,
+        })
+    }
+
+    /// Append an event to the trace
+    pub fn record(&mut self, event: TraceEvent) -> Result<(), DatabaseError> {
+        let line = format!("{} {}\n", current_time_millis(), event);
+
+        if self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Write the rotated and the current trace files, oldest events first
+    pub fn dump<P: AsRef<Path>, W: Write>(path: P, writer: &mut W) -> Result<(), DatabaseError> {
+        let path = path.as_ref();
+
+        for trace_path in [Self::rotated_path(path), path.to_path_buf()] {
+            match fs::read(&trace_path) {
+                Ok(bytes) => writer.write_all(&bytes)?,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<(), DatabaseError> {
+        fs::rename(&self.path, Self::rotated_path(&self.path))?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    fn rotated_path(path: &Path) -> PathBuf {
+        let mut rotated = path.as_os_str().to_os_string();
+        rotated.push(".old");
+        PathBuf::from(rotated)
+    }
+}
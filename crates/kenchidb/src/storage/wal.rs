@@ -0,0 +1,556 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    common::{DatabaseError, crc32},
+    database::Collection,
+    query::ByteReader,
+    schema::Document,
+};
+
+/// Size of the write-ahead log above which the next write checkpoints the collection,
+/// see `Collection::checkpoint`
+pub const DEFAULT_CHECKPOINT_SIZE: u64 = 1024 * 1024; // 1 MiB
+
+const PUT_RECORD: u8 = 1;
+const DELETE_RECORD: u8 = 2;
+const COMMIT_RECORD: u8 = 3;
+const PREPARE_RECORD: u8 = 4;
+
+/// When commits appended to the write-ahead log are synced to disk.
+/// Commits not synced yet survive a process crash, but not a power failure or OS crash.
+/// Modes other than `Always` trade that window for throughput: one sync covers all
+/// commits appended since the previous one (group commit).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncMode {
+    /// Sync every commit before it returns
+    #[default]
+    Always,
+    /// Sync every n-th commit
+    EveryN(u32),
+    /// Sync a commit once the previous sync is at least this many milliseconds old
+    Periodic(u64),
+    /// Leave write back to the operating system, the log is only synced at checkpoints
+    Never,
+}
+
+/// Logical write to a collection recorded in the write-ahead log
+#[derive(Debug, Clone)]
+pub enum WalRecord {
+    /// Document inserted or replaced
+    Put(Document),
+    Delete(u64),
+    /// Ends the writes of one operation, with the collection counters after it.
+    /// Writes not followed by a commit record are discarded on recovery.
+    Commit {
+        next_id: u64,
+        sequences: Vec<(String, i64)>,
+    },
+    /// Ends the writes of a collection in a transaction spanning several collection files.
+    /// They only count once the transaction is in the database's `TransactionLog`,
+    /// its commit record follows once every collection has prepared.
+    Prepare {
+        transaction: u64,
+        next_id: u64,
+        sequences: Vec<(String, i64)>,
+    },
+}
+
+impl WalRecord {
+    fn serialize(&self, bytes: &mut Vec<u8>) -> Result<(), DatabaseError> {
+        match self {
+            WalRecord::Put(document) => {
+                bytes.push(PUT_RECORD);
+                bytes.extend_from_slice(&Collection::serialize_document(document)?);
+            }
+            WalRecord::Delete(id) => {
+                bytes.push(DELETE_RECORD);
+                bytes.extend_from_slice(&id.to_le_bytes());
+            }
+            WalRecord::Commit { next_id, sequences } => {
+                bytes.push(COMMIT_RECORD);
+                Self::serialize_counters(*next_id, sequences, bytes);
+            }
+            WalRecord::Prepare {
+                transaction,
+                next_id,
+                sequences,
+            } => {
+                bytes.push(PREPARE_RECORD);
+                bytes.extend_from_slice(&transaction.to_le_bytes());
+                Self::serialize_counters(*next_id, sequences, bytes);
+            }
+        }
+        Ok(())
+    }
+
+    fn serialize_counters(next_id: u64, sequences: &[(String, i64)], bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&next_id.to_le_bytes());
+        bytes.extend_from_slice(&(sequences.len() as u32).to_le_bytes());
+        for (field, next) in sequences {
+            bytes.push(field.len() as u8);
+            bytes.extend_from_slice(field.as_bytes());
+            bytes.extend_from_slice(&next.to_le_bytes());
+        }
+    }
+
+    fn deserialize_counters(
+        reader: &mut ByteReader,
+    ) -> Result<(u64, Vec<(String, i64)>), DatabaseError> {
+        let next_id = reader.read_u64()?;
+        let mut sequences = Vec::new();
+        for _ in 0..reader.read_u32()? {
+            let field = reader.read_short_string()?;
+            let next = reader.read_u64()? as i64;
+            sequences.push((field, next));
+        }
+        Ok((next_id, sequences))
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, DatabaseError> {
+        let mut reader = ByteReader::new(bytes);
+        match reader.read_u8()? {
+            PUT_RECORD => Ok(WalRecord::Put(Collection::deserialize_document(
+                &bytes[1..],
+            )?)),
+            DELETE_RECORD => Ok(WalRecord::Delete(reader.read_u64()?)),
+            COMMIT_RECORD => {
+                let (next_id, sequences) = Self::deserialize_counters(&mut reader)?;
+                Ok(WalRecord::Commit { next_id, sequences })
+            }
+            PREPARE_RECORD => {
+                let transaction = reader.read_u64()?;
+                let (next_id, sequences) = Self::deserialize_counters(&mut reader)?;
+                Ok(WalRecord::Prepare {
+                    transaction,
+                    next_id,
+                    sequences,
+                })
+            }
+            tag => Err(DatabaseError::InvalidData(format!(
+                "Unknown write-ahead log record {}",
+                tag
+            ))),
+        }
+    }
+}
+
+/// Append-only log of the writes to a collection since its file was last written.
+/// Each record is framed by its length and checksum and the log is synced after every
+/// append, so a write is durable once it is in the log. On open the committed writes are
+/// read back for replay, a torn or damaged tail left by a crash is cut off.
+pub struct WriteAheadLog {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    /// Size up to the last commit record, a prepared transaction follows it
+    committed_size: u64,
+    sync_mode: SyncMode,
+    /// Commits appended since the last sync
+    unsynced: u32,
+    last_sync: Instant,
+    /// Bytes of a torn or uncommitted tail cut off when the log was opened
+    torn_bytes: u64,
+}
+
+impl WriteAheadLog {
+    /// Open or create the log, returns it with the committed writes it holds,
+    /// each operation ending with its commit record. The writes of a transaction
+    /// prepared but not committed follow them, ending with their prepare record.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<WalRecord>), DatabaseError> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(&path)?;
+
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        let mut records = Vec::new();
+        let mut committed_size = 0;
+        let mut complete = 0;
+        let mut complete_size = 0;
+        let mut reader = ByteReader::new(&bytes);
+        while reader.remaining() >= 8 {
+            let length = reader.read_u32()? as usize;
+            let checksum = reader.read_u32()?;
+            let Ok(payload) = reader.read_bytes(length) else {
+                break;
+            };
+            if crc32(payload) != checksum {
+                break;
+            }
+            let Ok(record) = WalRecord::deserialize(payload) else {
+                break;
+            };
+
+            let commit = matches!(record, WalRecord::Commit { .. });
+            let prepare = matches!(record, WalRecord::Prepare { .. });
+            records.push(record);
+            if commit {
+                committed_size = bytes.len() - reader.remaining();
+            }
+            if commit || prepare {
+                complete = records.len();
+                complete_size = bytes.len() - reader.remaining();
+            }
+        }
+        records.truncate(complete);
+
+        // Later appends must not land behind the torn tail
+        let size = complete_size as u64;
+        if size < bytes.len() as u64 {
+            file.set_len(size)?;
+            file.sync_all()?;
+        }
+        file.seek(SeekFrom::Start(size))?;
+
+        let wal = Self {
+            path,
+            file,
+            size,
+            committed_size: committed_size as u64,
+            sync_mode: SyncMode::default(),
+            unsynced: 0,
+            last_sync: Instant::now(),
+            torn_bytes: bytes.len() as u64 - size,
+        };
+        Ok((wal, records))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Bytes in the log
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Bytes cut off the end of the log when it was opened
+    pub fn torn_bytes(&self) -> u64 {
+        self.torn_bytes
+    }
+
+    pub fn sync_mode(&self) -> SyncMode {
+        self.sync_mode
+    }
+
+    pub fn set_sync_mode(&mut self, sync_mode: SyncMode) {
+        self.sync_mode = sync_mode;
+    }
+
+    /// Commits appended since the log was last synced
+    pub fn unsynced_commits(&self) -> u32 {
+        self.unsynced
+    }
+
+    /// Append the records, syncing the log as the sync mode asks.
+    /// Prepared transactions are always synced, they must be durable before they commit.
+    pub fn append(&mut self, records: &[WalRecord]) -> Result<(), DatabaseError> {
+        let mut bytes = Vec::new();
+        let mut payload = Vec::new();
+        for record in records {
+            payload.clear();
+            record.serialize(&mut payload)?;
+            bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&crc32(&payload).to_le_bytes());
+            bytes.extend_from_slice(&payload);
+        }
+
+        self.file.write_all(&bytes)?;
+        self.size += bytes.len() as u64;
+        self.unsynced += 1;
+        if matches!(records.last(), Some(WalRecord::Commit { .. })) {
+            self.committed_size = self.size;
+        }
+
+        let sync = match self.sync_mode {
+            _ if matches!(records.last(), Some(WalRecord::Prepare { .. })) => true,
+            SyncMode::Always => true,
+            SyncMode::EveryN(n) => self.unsynced >= n,
+            SyncMode::Periodic(millis) => self.last_sync.elapsed() >= Duration::from_millis(millis),
+            SyncMode::Never => false,
+        };
+        if sync {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Sync the commits appended so far
+    pub fn sync(&mut self) -> Result<(), DatabaseError> {
+        if self.unsynced > 0 {
+            self.file.sync_data()?;
+            self.unsynced = 0;
+        }
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+
+    /// Cut off the writes of a prepared transaction that did not commit
+    pub fn discard_prepared(&mut self) -> Result<(), DatabaseError> {
+        if self.size > self.committed_size {
+            self.file.set_len(self.committed_size)?;
+            self.file.seek(SeekFrom::Start(self.committed_size))?;
+            self.file.sync_all()?;
+            self.size = self.committed_size;
+        }
+        Ok(())
+    }
+
+    /// Empty the log once its writes are in the collection file
+    pub fn reset(&mut self) -> Result<(), DatabaseError> {
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.sync_all()?;
+        self.size = 0;
+        self.committed_size = 0;
+        self.unsynced = 0;
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+}
+
+impl Drop for WriteAheadLog {
+    fn drop(&mut self) {
+        // Commits held back by the sync mode
+        let _ = self.sync();
+    }
+}
+
+/// Write-ahead log of the collection file at `path`: `<path>.wal`
+pub fn wal_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".wal");
+    PathBuf::from(path)
+}
+
+/// Transaction whose writes are prepared in the logs of its collections
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecidedTransaction {
+    pub id: u64,
+    /// Write-ahead logs still holding the transaction as prepared
+    pub participants: Vec<PathBuf>,
+    /// Prepared for an external coordinator which has not decided yet,
+    /// see `Transaction::prepare`
+    pub in_doubt: bool,
+}
+
+/// Commit decisions of transactions spanning several collection files. A transaction
+/// commits once it is in this log: recovery keeps its prepared writes in every collection,
+/// prepared writes of transactions missing here are discarded. An entry is dropped once
+/// every collection has written its commit record.
+///
+/// Transactions prepared for an external coordinator are logged in doubt: recovery keeps
+/// their prepared writes aside until `commit_in_doubt` or `abort` decides them.
+pub struct TransactionLog {
+    path: PathBuf,
+    decided: Vec<DecidedTransaction>,
+}
+
+impl TransactionLog {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, DatabaseError> {
+        let path = path.as_ref().to_path_buf();
+        let mut decided = Vec::new();
+        if path.exists() {
+            let bytes = fs::read(&path)?;
+            let mut reader = ByteReader::new(&bytes);
+            while reader.remaining() >= 8 {
+                let length = reader.read_u32()? as usize;
+                let checksum = reader.read_u32()?;
+                let Ok(payload) = reader.read_bytes(length) else {
+                    break;
+                };
+                if crc32(payload) != checksum {
+                    break;
+                }
+                let Ok(transaction) = Self::deserialize(payload) else {
+                    break;
+                };
+                decided.push(transaction);
+            }
+        } else {
+            File::create(&path)?.sync_all()?;
+        }
+        Ok(Self { path, decided })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Transactions that still have prepared writes in some collection log
+    pub fn decided(&self) -> &[DecidedTransaction] {
+        &self.decided
+    }
+
+    /// Commit the transaction, synced before returning
+    pub fn decide(&mut self, transaction: DecidedTransaction) -> Result<(), DatabaseError> {
+        let mut bytes = Vec::new();
+        Self::frame(&transaction, &mut bytes);
+        let mut file = OpenOptions::new().append(true).open(&self.path)?;
+        file.write_all(&bytes)?;
+        file.sync_data()?;
+        self.decided.push(transaction);
+        Ok(())
+    }
+
+    /// Whether the transaction prepared in the log at `wal` committed
+    pub fn is_decided(&self, transaction: u64, wal: &Path) -> bool {
+        self.find(transaction, wal)
+            .is_some_and(|decided| !decided.in_doubt)
+    }
+
+    /// Whether the transaction prepared in the log at `wal` waits for its coordinator
+    pub fn is_in_doubt(&self, transaction: u64, wal: &Path) -> bool {
+        self.find(transaction, wal)
+            .is_some_and(|decided| decided.in_doubt)
+    }
+
+    fn find(&self, transaction: u64, wal: &Path) -> Option<&DecidedTransaction> {
+        self.decided.iter().find(|decided| {
+            decided.id == transaction && decided.participants.iter().any(|p| p == wal)
+        })
+    }
+
+    /// Ids of the transactions waiting for their coordinator
+    pub fn in_doubt(&self) -> Vec<u64> {
+        self.decided
+            .iter()
+            .filter(|decided| decided.in_doubt)
+            .map(|decided| decided.id)
+            .collect()
+    }
+
+    /// Commit a transaction in doubt, returns its entry. Synced before returning.
+    pub fn commit_in_doubt(
+        &mut self,
+        transaction: u64,
+    ) -> Result<Option<DecidedTransaction>, DatabaseError> {
+        let Some(decided) = self
+            .decided
+            .iter_mut()
+            .find(|decided| decided.id == transaction && decided.in_doubt)
+        else {
+            return Ok(None);
+        };
+        decided.in_doubt = false;
+        let decided = decided.clone();
+        self.rewrite()?;
+        Ok(Some(decided))
+    }
+
+    /// Drop a transaction in doubt, its prepared writes are discarded from then on.
+    /// Returns its entry, synced before returning.
+    pub fn abort(&mut self, transaction: u64) -> Result<Option<DecidedTransaction>, DatabaseError> {
+        let Some(position) = self
+            .decided
+            .iter()
+            .position(|decided| decided.id == transaction && decided.in_doubt)
+        else {
+            return Ok(None);
+        };
+        let decided = self.decided.remove(position);
+        self.rewrite()?;
+        Ok(Some(decided))
+    }
+
+    /// Record that the logs hold the commit record of the transaction
+    pub fn resolve(&mut self, transaction: u64, logs: &[PathBuf]) -> Result<(), DatabaseError> {
+        for decided in &mut self.decided {
+            if decided.id == transaction {
+                decided
+                    .participants
+                    .retain(|participant| !logs.contains(participant));
+            }
+        }
+        self.decided
+            .retain(|decided| decided.in_doubt || !decided.participants.is_empty());
+        self.rewrite()
+    }
+
+    /// Log following the collection logs of its transactions to their moved paths,
+    /// written at the moved path of the log, over this log's file if it does not move.
+    /// This log keeps the old paths.
+    pub fn relocated(&self, moved: &HashMap<PathBuf, PathBuf>) -> Result<Self, DatabaseError> {
+        let mut relocated = Self {
+            path: moved.get(&self.path).unwrap_or(&self.path).clone(),
+            decided: self.decided.clone(),
+        };
+        for decided in &mut relocated.decided {
+            for participant in &mut decided.participants {
+                if let Some(path) = moved.get(participant) {
+                    *participant = path.clone();
+                }
+            }
+        }
+        relocated.rewrite()?;
+        Ok(relocated)
+    }
+
+    pub fn rewrite(&self) -> Result<(), DatabaseError> {
+        let mut bytes = Vec::new();
+        for transaction in &self.decided {
+            Self::frame(transaction, &mut bytes);
+        }
+
+        // Written next to the log and renamed over it, a crash keeps the previous decisions
+        let mut temporary = self.path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+        let mut file = File::create(&temporary)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        fs::rename(&temporary, &self.path)?;
+        Ok(())
+    }
+
+    fn frame(transaction: &DecidedTransaction, bytes: &mut Vec<u8>) {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&transaction.id.to_le_bytes());
+        payload.extend_from_slice(&(transaction.participants.len() as u32).to_le_bytes());
+        for participant in &transaction.participants {
+            let participant = participant.to_string_lossy();
+            payload.extend_from_slice(&(participant.len() as u32).to_le_bytes());
+            payload.extend_from_slice(participant.as_bytes());
+        }
+        payload.push(transaction.in_doubt as u8);
+        bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&crc32(&payload).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<DecidedTransaction, DatabaseError> {
+        let mut reader = ByteReader::new(bytes);
+        let id = reader.read_u64()?;
+        let mut participants = Vec::new();
+        for _ in 0..reader.read_u32()? {
+            let length = reader.read_u32()? as usize;
+            let participant = String::from_utf8_lossy(reader.read_bytes(length)?);
+            participants.push(PathBuf::from(participant.into_owned()));
+        }
+        // Logs written before transactions could be in doubt end here
+        let in_doubt = reader.remaining() > 0 && reader.read_u8()? != 0;
+        Ok(DecidedTransaction {
+            id,
+            participants,
+            in_doubt,
+        })
+    }
+}
+
+/// Transaction log of the database with the catalog at `path`: `<path>.txn`
+pub fn transaction_log_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".txn");
+    PathBuf::from(path)
+}
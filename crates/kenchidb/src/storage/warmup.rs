@@ -0,0 +1,36 @@
+/// Pages to load into the buffer pool before the first query, most important first
+#[derive(Debug, Clone, Default)]
+pub struct WarmupPlan {
+    /// Header and meta pages, e.g. the catalog
+    pub metadata: bool,
+    /// Index pages
+    pub indexes: bool,
+    /// Collections whose data pages are loaded, in the given order
+    pub collections: Vec<u32>,
+}
+
+impl WarmupPlan {
+    /// Metadata and index pages, the pages every query touches first
+    pub fn new() -> Self {
+        Self {
+            metadata: true,
+            indexes: true,
+            collections: Vec::new(),
+        }
+    }
+
+    /// Also load the data pages of a frequently queried collection
+    pub fn with_collection(mut self, collection_id: u32) -> Self {
+        self.collections.push(collection_id);
+        self
+    }
+}
+
+/// Outcome of a warmup
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WarmupReport {
+    /// Pages read into the buffer pool
+    pub pages_loaded: usize,
+    /// Pages selected by the plan that did not fit into the buffer pool
+    pub pages_skipped: usize,
+}
@@ -0,0 +1,100 @@
+use std::fs;
+
+use crate::{
+    define_schema,
+    schema::Value,
+    storage::{
+        async_io::{AsyncFileManager, AsyncPagedCollection},
+        page::PageType,
+        paged_collection::{free_space_map_path, primary_key_index_path},
+    },
+};
+
+define_schema! {
+    Order {
+        item: string,
+    }
+}
+
+fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn test_async_file_manager() {
+    let path =
+        std::env::temp_dir().join(format!("kenchidb_async_pages_{}.pages", std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    runtime().block_on(async {
+        let pages = AsyncFileManager::open(&path).await.unwrap();
+        let (page_id, mut page) = pages.allocate_page(PageType::DataPage, 1).await.unwrap();
+        page.insert_record(b"async").unwrap();
+        let written = pages.write_page(page_id, page).await.unwrap();
+        assert_ne!(written.header.checksum, 0);
+        pages.flush().await.unwrap();
+
+        let read = pages.read_page(page_id).await.unwrap();
+        assert_eq!(read.get_record(0).unwrap(), b"async");
+        assert_eq!(pages.page_count().await.unwrap(), 1);
+    });
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_async_paged_collection() {
+    let path = std::env::temp_dir().join(format!(
+        "kenchidb_async_orders_{}.pages",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+
+    runtime().block_on(async {
+        let orders = AsyncPagedCollection::open(Order::schema(), 1, &path)
+            .await
+            .unwrap();
+
+        // Clones share the collection, e.g. across request handlers
+        let tasks: Vec<_> = (0..10)
+            .map(|i| {
+                let orders = orders.clone();
+                tokio::spawn(async move {
+                    let item = format!("item {}", i);
+                    orders
+                        .insert(Order::create().set("item", item.as_str()).build())
+                        .await
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        orders
+            .update(3, Order::create().set("item", "changed").build())
+            .await
+            .unwrap();
+        orders.delete(4).await.unwrap();
+        let changed = orders.find_by_id(3).await.unwrap().unwrap();
+        assert_eq!(
+            changed.get("item"),
+            Some(&Value::String("changed".to_string()))
+        );
+        assert!(orders.find_by_id(4).await.unwrap().is_none());
+        let count = orders
+            .with_collection(|orders| Ok(orders.iter().count()))
+            .await
+            .unwrap();
+        assert_eq!(count, 9);
+        orders.flush().await.unwrap();
+    });
+
+    fs::remove_file(&path).unwrap();
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+}
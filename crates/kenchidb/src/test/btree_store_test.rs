@@ -0,0 +1,72 @@
+use std::fs;
+
+use btree::PersistentBtree;
+
+use crate::storage::{
+    btree_store::EngineNodeStore,
+    engine::{MemoryEngine, StorageEngine},
+    file_manager::FileManager,
+    page::PageType,
+};
+
+#[test]
+fn test_tree_survives_restart() {
+    let path = std::env::temp_dir().join(format!("kenchidb_btree_{}.pages", std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    let store = EngineNodeStore::new(FileManager::new(&path).unwrap(), 7);
+    let mut tree = PersistentBtree::create(store, 64, 16).unwrap();
+    for id in 0..5000u64 {
+        tree.insert(id, (id as u32 / 10, (id % 10) as u16)).unwrap();
+    }
+    for id in (0..5000u64).step_by(5) {
+        assert!(tree.delete(id).unwrap().is_some());
+    }
+    let meta_page = tree.meta_page();
+    drop(tree.into_store().unwrap().into_engine());
+
+    let store = EngineNodeStore::new(FileManager::new(&path).unwrap(), 7);
+    let mut tree: PersistentBtree<(u32, u16), _> =
+        PersistentBtree::open(store, meta_page, 16).unwrap();
+    assert_eq!(tree.len(), 4000);
+    assert_eq!(tree.get(1234).unwrap(), Some((123, 4)));
+    assert_eq!(tree.get(1235).unwrap(), None);
+    let ids: Vec<u64> = tree
+        .range(10..=20)
+        .unwrap()
+        .map(|entry| entry.unwrap().0)
+        .collect();
+    assert_eq!(ids, vec![11, 12, 13, 14, 16, 17, 18, 19]);
+
+    // Nodes are read on demand, a lookup touches one node per level
+    let stats = tree.cache_stats();
+    assert!(stats.cached_nodes <= 16);
+    assert!(stats.node_reads < 20);
+
+    drop(tree);
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn test_nodes_are_index_pages() {
+    let store = EngineNodeStore::new(MemoryEngine::new(), 3);
+    let mut tree = PersistentBtree::create(store, 8, 4).unwrap();
+    for id in 0..200u64 {
+        tree.insert(id, id).unwrap();
+    }
+    for id in 0..200u64 {
+        tree.delete(id).unwrap();
+    }
+    let mut engine = tree.into_store().unwrap().into_engine();
+
+    let mut index_pages = 0;
+    for page_id in 0..engine.page_count() {
+        let page = engine.read_page(page_id).unwrap();
+        assert_eq!(page.header.collection_id, 3);
+        if page.header.page_type == PageType::IndexPage {
+            index_pages += 1;
+        }
+    }
+    // The meta page and the empty root leaf
+    assert_eq!(index_pages, 2);
+}
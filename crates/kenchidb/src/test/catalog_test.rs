@@ -0,0 +1,139 @@
+use std::{fs, path::PathBuf};
+
+use crate::{
+    database::Database,
+    define_schema,
+    query::{Query, View},
+    schema::{FieldType, Value},
+    storage::{catalog::load_catalog, file_manager::FileManager, page::PageType},
+};
+
+define_schema! {
+    Book {
+        number: long auto_increment,
+        title: string,
+        summary: string?,
+    }
+}
+
+define_schema! {
+    Shelf {
+        label: string,
+    }
+}
+
+fn directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("kenchidb_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+#[test]
+fn test_open_reconstructs_collections_from_the_catalog() {
+    let directory = directory("catalog_open");
+    let path = directory.join("library.catalog");
+
+    {
+        let mut db = Database::with_catalog(&path).unwrap();
+        let books = Book::schema()
+            .with_primary_key(&["title"])
+            .with_text_index(&["title", "summary"]);
+        db.create_collection_with_file("books".to_string(), books, directory.join("books.data"))
+            .unwrap();
+        db.create_collection_with_file(
+            "shelves".to_string(),
+            Shelf::schema(),
+            directory.join("shelves.data"),
+        )
+        .unwrap();
+        let books = db.collection("books").unwrap();
+        books
+            .insert(Book::create().set("title", "Dune").build())
+            .unwrap();
+        books
+            .insert(Book::create().set("title", "Emma").build())
+            .unwrap();
+        db.create_view("all_books", "books", Query::all()).unwrap();
+    }
+
+    // Page 0 is the header page, collections keep the ids they were created with
+    let mut pages = FileManager::new(&path).unwrap();
+    assert_eq!(
+        pages.read_page(0).unwrap().header.page_type,
+        PageType::HeaderPage
+    );
+    drop(pages);
+    let (catalog, views) = load_catalog(&path).unwrap();
+    assert_eq!(catalog.next_collection_id, 3);
+    assert_eq!(catalog.entry("shelves").unwrap().id, 2);
+    assert_eq!(views.len(), 1);
+
+    let mut db = Database::open(&path).unwrap();
+    assert_eq!(db.views().len(), 1);
+    assert_eq!(db.find_view("all_books").unwrap().len(), 2);
+    let books = db.collection("books").unwrap();
+    assert_eq!(books.schema.primary_key, Some(vec!["title".to_string()]));
+    assert_eq!(books.schema.text_fields, vec!["title", "summary"]);
+    let summary = books.schema.fields.iter().find(|f| f.name == "summary");
+    assert!(summary.is_some_and(|f| f.nullable && f.field_type == FieldType::String));
+    assert!(books.find_by_key(&[Value::from("Emma")]).is_some());
+
+    // Ids and sequences continue where they were
+    let id = books
+        .insert(Book::create().set("title", "Ulysses").build())
+        .unwrap();
+    assert_eq!(id, 3);
+    assert_eq!(
+        books.find_by_id(id).unwrap().get("number"),
+        Some(&Value::Long(3))
+    );
+    assert!(db.collection("shelves").unwrap().find_all().is_empty());
+
+    // Ids handed out are not reused when a collection file is replaced by an empty one
+    let shelves = db.collection("shelves").unwrap();
+    shelves
+        .insert(Shelf::create().set("label", "fiction").build())
+        .unwrap();
+    db.sync().unwrap();
+    drop(db);
+    fs::remove_file(directory.join("shelves.data")).unwrap();
+    fs::remove_file(directory.join("shelves.data.wal")).unwrap();
+    let mut db = Database::open(&path).unwrap();
+    let id = db
+        .collection("shelves")
+        .unwrap()
+        .insert(Shelf::create().set("label", "poetry").build())
+        .unwrap();
+    assert_eq!(id, 2);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_catalog_of_views_only_is_read() {
+    let directory = directory("catalog_views");
+    let path = directory.join("old.catalog");
+
+    // Catalogs written before the header page hold the views alone
+    let mut bytes = 1u32.to_le_bytes().to_vec();
+    View::new("everything", "shelves", Query::all().into())
+        .serialize(&mut bytes)
+        .unwrap();
+    fs::write(&path, bytes).unwrap();
+
+    let mut db = Database::with_catalog(&path).unwrap();
+    assert_eq!(db.view("everything").unwrap().collection, "shelves");
+    db.create_collection_with_file(
+        "shelves".to_string(),
+        Shelf::schema(),
+        directory.join("shelves.data"),
+    )
+    .unwrap();
+    drop(db);
+
+    let db = Database::open(&path).unwrap();
+    assert_eq!(db.views().len(), 1);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
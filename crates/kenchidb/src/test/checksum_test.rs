@@ -0,0 +1,64 @@
+use std::fs;
+
+use crate::{
+    common::{DatabaseError, crc32},
+    define_schema,
+    schema::Value,
+    storage::{page::PAGE_HEADER_SIZE, paged_collection::PagedCollection},
+};
+
+define_schema! {
+    Note {
+        title: string,
+    }
+}
+
+fn collection_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("kenchidb_{}_{}.pages", name, std::process::id()))
+}
+
+#[test]
+fn test_crc32_known_value() {
+    assert_eq!(crc32(b""), 0);
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+}
+
+#[test]
+fn test_document_checksum_detects_corruption() {
+    let path = collection_path("checksum");
+    let _ = fs::remove_file(&path);
+
+    let mut notes = PagedCollection::new(Note::schema(), 1, &path).unwrap();
+    let plain = notes
+        .insert(Note::create().set("title", "unchecked").build())
+        .unwrap();
+
+    notes.set_document_checksums(true);
+    let checked = notes
+        .insert(Note::create().set("title", "checked").build())
+        .unwrap();
+
+    // Records with and without a checksum are both readable
+    assert!(notes.find_by_id(plain).unwrap().is_some());
+    assert_eq!(
+        notes.find_by_id(checked).unwrap().unwrap().get("title"),
+        Some(&Value::String("checked".to_string()))
+    );
+
+    // Flip a byte of the stored title behind the page layer's back
+    let (page_id, slot_index) = notes.documents.get(checked).unwrap();
+    let mut page = notes.file_manager.read_page(page_id).unwrap();
+    let slot = page.slots[slot_index as usize];
+    let record_end = slot.offset as usize - PAGE_HEADER_SIZE + slot.length as usize;
+    // The last four bytes are the checksum itself
+    page.data[record_end - 5] ^= 0xFF;
+    notes.file_manager.write_page(page_id, &mut page).unwrap();
+
+    match notes.find_by_id(checked) {
+        Err(DatabaseError::CorruptDocument { id }) => assert_eq!(id, checked),
+        other => panic!("expected corrupt document, got {:?}", other),
+    }
+    assert!(notes.iter().any(|doc| doc.is_err()));
+
+    let _ = fs::remove_file(&path);
+}
@@ -0,0 +1,159 @@
+use std::{fs, sync::Arc};
+
+use crate::{
+    common::DatabaseError,
+    define_schema,
+    schema::Value,
+    storage::{
+        codec::{CodecRegistry, PageCodec},
+        file_manager::FileManager,
+        paged_collection::PagedCollection,
+    },
+};
+
+define_schema! {
+    Secret {
+        text: string,
+    }
+}
+
+/// Flips every bit, so the page body never appears in plain text in the file
+struct InvertCodec;
+
+impl PageCodec for InvertCodec {
+    fn id(&self) -> u8 {
+        1
+    }
+
+    fn name(&self) -> &str {
+        "invert"
+    }
+
+    fn encode(&self, _page_id: u32, body: &[u8]) -> Result<Vec<u8>, DatabaseError> {
+        Ok(body.iter().map(|byte| !byte).collect())
+    }
+
+    fn decode(&self, page_id: u32, encoded: &[u8]) -> Result<Vec<u8>, DatabaseError> {
+        self.encode(page_id, encoded)
+    }
+}
+
+/// Prepends the page id, growing every body by four bytes
+struct FramedCodec;
+
+impl PageCodec for FramedCodec {
+    fn id(&self) -> u8 {
+        2
+    }
+
+    fn name(&self) -> &str {
+        "framed"
+    }
+
+    fn overhead(&self) -> usize {
+        4
+    }
+
+    fn encode(&self, page_id: u32, body: &[u8]) -> Result<Vec<u8>, DatabaseError> {
+        let mut encoded = page_id.to_le_bytes().to_vec();
+        encoded.extend_from_slice(body);
+        Ok(encoded)
+    }
+
+    fn decode(&self, page_id: u32, encoded: &[u8]) -> Result<Vec<u8>, DatabaseError> {
+        if encoded[..4] != page_id.to_le_bytes() {
+            return Err(DatabaseError::InvalidData(
+                "Frame of another page".to_string(),
+            ));
+        }
+        Ok(encoded[4..].to_vec())
+    }
+}
+
+fn codec_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("kenchidb_{}_{}.pages", name, std::process::id()))
+}
+
+#[test]
+fn test_codec_registry_rejects_conflicting_ids() {
+    struct Unencoded;
+    impl PageCodec for Unencoded {
+        fn id(&self) -> u8 {
+            0
+        }
+        fn name(&self) -> &str {
+            "unencoded"
+        }
+        fn encode(&self, _: u32, body: &[u8]) -> Result<Vec<u8>, DatabaseError> {
+            Ok(body.to_vec())
+        }
+        fn decode(&self, _: u32, encoded: &[u8]) -> Result<Vec<u8>, DatabaseError> {
+            Ok(encoded.to_vec())
+        }
+    }
+
+    let mut registry = CodecRegistry::new();
+    registry.register(Arc::new(InvertCodec)).unwrap();
+    assert!(registry.register(Arc::new(InvertCodec)).is_err());
+    assert!(registry.register(Arc::new(Unencoded)).is_err());
+    assert_eq!(registry.get(1).unwrap().name(), "invert");
+}
+
+#[test]
+fn test_pages_round_trip_through_codecs() {
+    for codec in [
+        Arc::new(InvertCodec) as Arc<dyn PageCodec>,
+        Arc::new(FramedCodec),
+    ] {
+        let path = codec_path(&format!("codec_{}", codec.name()));
+        let _ = fs::remove_file(&path);
+
+        let mut secrets = PagedCollection::new(Secret::schema(), 1, &path).unwrap();
+        secrets.file_manager.register_codec(codec.clone()).unwrap();
+        secrets
+            .file_manager
+            .set_write_codec(Some(codec.id()))
+            .unwrap();
+
+        // Enough documents to fill several pages to the brim
+        let text = "classified".repeat(20);
+        for _ in 0..60 {
+            secrets
+                .insert(Secret::create().set("text", text.as_str()).build())
+                .unwrap();
+        }
+        assert!(secrets.stats().total_pages > 2);
+        assert_eq!(secrets.iter().count(), 60);
+        assert_eq!(
+            secrets.find_by_id(60).unwrap().unwrap().get("text"),
+            Some(&Value::String(text.clone()))
+        );
+
+        let raw = fs::read(&path).unwrap();
+        if codec.id() == InvertCodec.id() {
+            assert!(!raw.windows(10).any(|window| window == b"classified"));
+        }
+
+        // Pages name their codec, reading them requires it to be registered
+        let (page_id, _) = secrets.documents.get(1).unwrap();
+        drop(secrets);
+        let mut pages = FileManager::new(&path).unwrap();
+        assert!(pages.read_page(page_id).is_err());
+        pages.register_codec(codec.clone()).unwrap();
+        assert!(pages.read_page(page_id).unwrap().live_record_count() > 0);
+
+        let _ = fs::remove_file(&path);
+    }
+}
+
+#[test]
+fn test_write_codec_must_be_registered() {
+    let path = codec_path("codec_unregistered");
+    let _ = fs::remove_file(&path);
+
+    let mut pages = FileManager::new(&path).unwrap();
+    assert!(pages.set_write_codec(Some(9)).is_err());
+    pages.set_write_codec(None).unwrap();
+
+    let _ = fs::remove_file(&path);
+}
@@ -0,0 +1,260 @@
+use std::{fs, sync::Arc};
+
+use crate::{
+    define_schema,
+    schema::Value,
+    storage::{
+        codec::{CodecRegistry, LZ4_CODEC_ID, NO_CODEC},
+        compression::Lz4Codec,
+        file_manager::FileManager,
+        packed::{PackedPages, packed_pages_path},
+        page::{PAGE_BODY_LENGTH_OFFSET, PAGE_CODEC_ID_OFFSET, PAGE_HEADER_SIZE, PAGE_SIZE},
+        paged_collection::{PagedCollection, free_space_map_path, primary_key_index_path},
+    },
+};
+
+define_schema! {
+    Review {
+        text: string,
+    }
+}
+
+fn compression_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("kenchidb_{}_{}.pages", name, std::process::id()))
+}
+
+#[test]
+fn test_incompressible_pages_are_stored_plain() {
+    let text = "the quick brown fox jumps over the lazy dog "
+        .repeat(40)
+        .into_bytes();
+    let mut page_bytes = [0u8; PAGE_SIZE];
+    page_bytes[PAGE_HEADER_SIZE..PAGE_HEADER_SIZE + text.len()].copy_from_slice(&text);
+    let original = page_bytes;
+    CodecRegistry::encode_page(&Lz4Codec, 3, &mut page_bytes).unwrap();
+    assert_eq!(page_bytes[PAGE_CODEC_ID_OFFSET], LZ4_CODEC_ID);
+    let encoded_length = u16::from_le_bytes([
+        page_bytes[PAGE_HEADER_SIZE],
+        page_bytes[PAGE_HEADER_SIZE + 1],
+    ]);
+    assert!((encoded_length as usize) < text.len() / 10);
+
+    let mut codecs = CodecRegistry::new();
+    codecs.register(Arc::new(Lz4Codec)).unwrap();
+    codecs.decode_page(3, &mut page_bytes).unwrap();
+    assert_eq!(page_bytes, original);
+
+    // Pseudo-random bytes without repeats to find fill the whole body, no room is reserved
+    let mut state = 7u32;
+    for byte in page_bytes[PAGE_HEADER_SIZE..].iter_mut() {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        *byte = state as u8;
+    }
+    let noise = page_bytes;
+    assert_eq!(CodecRegistry::reserved_bytes(&Lz4Codec), 0);
+    CodecRegistry::encode_page(&Lz4Codec, 3, &mut page_bytes).unwrap();
+    assert_eq!(page_bytes, noise);
+    assert_eq!(page_bytes[PAGE_CODEC_ID_OFFSET], NO_CODEC);
+
+    // Damaged blocks are rejected
+    let mut damaged = original;
+    CodecRegistry::encode_page(&Lz4Codec, 3, &mut damaged).unwrap();
+    damaged[PAGE_HEADER_SIZE] = 0xff;
+    damaged[PAGE_HEADER_SIZE + 1] = 0x00;
+    damaged[PAGE_HEADER_SIZE + 2] = 0xf0;
+    assert!(codecs.decode_page(3, &mut damaged).is_err());
+}
+
+#[test]
+fn test_compressed_pages_record_their_body_length() {
+    let path = compression_path("compression_pages");
+    remove_files(&path);
+
+    let text = "great product, would buy again ".repeat(8);
+    {
+        let mut reviews = PagedCollection::new(Review::schema(), 1, &path).unwrap();
+        reviews.file_manager.enable_compression().unwrap();
+        for _ in 0..200 {
+            reviews
+                .insert(Review::create().set("text", text.as_str()).build())
+                .unwrap();
+        }
+        reviews.flush().unwrap();
+    }
+
+    // Several compressed pages share a slot of the packed file, none is left in the page file
+    let mut packed = PackedPages::open(packed_pages_path(&path)).unwrap();
+    let page_count = packed.page_end();
+    assert!(page_count >= 8);
+    assert!(packed.slot_count() * 4 <= page_count as usize);
+    assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+
+    let mut page = [0u8; PAGE_SIZE];
+    for page_id in 0..page_count {
+        packed.read(page_id, &mut page).unwrap();
+        assert_eq!(page[PAGE_CODEC_ID_OFFSET], LZ4_CODEC_ID);
+        let body_length = u16::from_le_bytes([
+            page[PAGE_BODY_LENGTH_OFFSET],
+            page[PAGE_BODY_LENGTH_OFFSET + 1],
+        ]);
+        assert_eq!(
+            body_length as usize,
+            PAGE_SIZE - PAGE_HEADER_SIZE - CodecRegistry::reserved_bytes(&Lz4Codec)
+        );
+        let encoded_length =
+            u16::from_le_bytes([page[PAGE_HEADER_SIZE], page[PAGE_HEADER_SIZE + 1]]);
+        assert!((encoded_length as usize) < PAGE_SIZE / 4);
+    }
+    drop(packed);
+
+    // The codec has to be registered to read the pages again
+    assert!(FileManager::new(&path).unwrap().read_page(0).is_err());
+    let mut reviews = PagedCollection::new(Review::schema(), 1, &path).unwrap();
+    reviews.file_manager.enable_compression().unwrap();
+    assert_eq!(reviews.iter().count(), 200);
+    assert_eq!(
+        reviews.find_by_id(200).unwrap().unwrap().get("text"),
+        Some(&Value::String(text))
+    );
+
+    drop(reviews);
+    remove_files(&path);
+}
+
+#[test]
+fn test_rewritten_packed_pages_reuse_slots() {
+    let path = compression_path("compression_rewrite");
+    remove_files(&path);
+
+    let text = "five stars, fast delivery ".repeat(8);
+    let mut reviews = PagedCollection::new(Review::schema(), 1, &path).unwrap();
+    reviews.file_manager.enable_compression().unwrap();
+    for _ in 0..100 {
+        reviews
+            .insert(Review::create().set("text", text.as_str()).build())
+            .unwrap();
+    }
+    reviews.flush().unwrap();
+    let bytes = fs::metadata(packed_pages_path(&path)).unwrap().len();
+
+    // Slots of replaced images are written again, the packed file stops growing
+    for round in 0..20 {
+        let id = round % 100 + 1;
+        reviews.delete(id).unwrap();
+        reviews
+            .insert(Review::create().set("text", text.as_str()).build())
+            .unwrap();
+        reviews.flush().unwrap();
+    }
+    assert!(fs::metadata(packed_pages_path(&path)).unwrap().len() <= bytes * 2);
+    drop(reviews);
+
+    let mut reviews = PagedCollection::new(Review::schema(), 1, &path).unwrap();
+    reviews.file_manager.enable_compression().unwrap();
+    assert_eq!(reviews.iter().count(), 100);
+    drop(reviews);
+    remove_files(&path);
+}
+
+#[test]
+fn test_torn_packed_slot_keeps_previous_images() {
+    let path = compression_path("compression_torn");
+    let _ = fs::remove_file(&path);
+
+    let image = |fill: u8| {
+        let mut page = Box::new([0u8; PAGE_SIZE]);
+        page[..100].fill(fill);
+        page
+    };
+    let mut packed = PackedPages::open(&path).unwrap();
+    packed
+        .write(&[(0, image(1)), (1, image(2)), (2, image(5))], &[])
+        .unwrap();
+    packed.write(&[(0, image(3))], &[]).unwrap();
+    packed.write(&[(1, image(4))], &[2]).unwrap();
+    drop(packed);
+
+    let mut page = [0u8; PAGE_SIZE];
+    let mut packed = PackedPages::open(&path).unwrap();
+    packed.read(0, &mut page).unwrap();
+    assert_eq!(page[..], image(3)[..]);
+    packed.read(1, &mut page).unwrap();
+    assert_eq!(page[..], image(4)[..]);
+    assert!(!packed.contains(2));
+    assert_eq!(packed.page_end(), 2);
+    drop(packed);
+
+    // Damage the slot written last, the page falls back to the image of the slot before
+    let mut raw = fs::read(&path).unwrap();
+    let last = raw
+        .chunks(PAGE_SIZE)
+        .position(|slot| slot.windows(100).any(|window| window == [4; 100]))
+        .unwrap();
+    raw[last * PAGE_SIZE + PAGE_SIZE - 1] ^= 1;
+    fs::write(&path, &raw).unwrap();
+
+    let mut packed = PackedPages::open(&path).unwrap();
+    packed.read(1, &mut page).unwrap();
+    assert_eq!(page[..], image(2)[..]);
+    packed.read(0, &mut page).unwrap();
+    assert_eq!(page[..], image(3)[..]);
+    assert!(packed.contains(2));
+    drop(packed);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_vacuum_keeps_packed_pages() {
+    let path = compression_path("compression_vacuum");
+    remove_files(&path);
+
+    // Words picked at random compress to about half, so the pages take several slots
+    let words = [
+        "arrived", "broken", "refunded", "quickly", "sturdy", "cheap", "late", "ok",
+    ];
+    let text = |id: u64| {
+        let mut state = id as u32 | 1;
+        (0..30)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                words[state as usize % words.len()]
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let mut reviews = PagedCollection::new(Review::schema(), 1, &path).unwrap();
+    reviews.file_manager.enable_compression().unwrap();
+    for id in 1..=600 {
+        reviews
+            .insert(Review::create().set("text", text(id).as_str()).build())
+            .unwrap();
+    }
+    reviews.flush().unwrap();
+    for id in 1..=400 {
+        reviews.delete(id).unwrap();
+    }
+    let report = reviews.vacuum().unwrap();
+    assert!(report.bytes_after < report.bytes_before);
+    drop(reviews);
+
+    let mut reviews = PagedCollection::new(Review::schema(), 1, &path).unwrap();
+    reviews.file_manager.enable_compression().unwrap();
+    assert_eq!(reviews.iter().count(), 200);
+    assert_eq!(
+        reviews.find_by_id(600).unwrap().unwrap().get("text"),
+        Some(&Value::String(text(600)))
+    );
+    drop(reviews);
+    remove_files(&path);
+}
+
+fn remove_files(path: &std::path::Path) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(packed_pages_path(path));
+    let _ = fs::remove_file(primary_key_index_path(path));
+    let _ = fs::remove_file(free_space_map_path(path));
+}
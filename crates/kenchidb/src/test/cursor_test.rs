@@ -0,0 +1,68 @@
+use std::fs;
+
+use crate::{
+    define_schema,
+    query::Query,
+    schema::Value,
+    storage::{page::PageType, paged_collection::PagedCollection},
+};
+
+define_schema! {
+    Event {
+        kind: string,
+        payload: string,
+    }
+}
+
+fn collection_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("kenchidb_{}_{}.pages", name, std::process::id()))
+}
+
+#[test]
+fn test_cursor_streams_all_pages() {
+    let path = collection_path("cursor");
+    let _ = fs::remove_file(&path);
+
+    let mut events = PagedCollection::new(Event::schema(), 1, &path).unwrap();
+    let payload = "x".repeat(200);
+    for i in 0..100 {
+        let kind = if i % 2 == 0 { "even" } else { "odd" };
+        events
+            .insert(
+                Event::create()
+                    .set("kind", kind)
+                    .set("payload", payload.as_str())
+                    .build(),
+            )
+            .unwrap();
+    }
+    events.delete(4).unwrap();
+
+    // Pages of another collection in the same file are skipped
+    let (page_id, mut page) = events
+        .file_manager
+        .allocate_page(PageType::DataPage, 2)
+        .unwrap();
+    page.insert_record(b"not an event").unwrap();
+    events.file_manager.write_page(page_id, &mut page).unwrap();
+    assert!(events.stats().total_pages > 2);
+
+    let ids: Vec<u64> = events.iter().map(|doc| doc.unwrap().id).collect();
+    assert_eq!(ids.len(), 99);
+    assert!(!ids.contains(&4));
+
+    let odd: Vec<_> = events
+        .find_where_iter(Query::eq("kind", "odd"))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(odd.len(), 49);
+    assert!(
+        odd.iter()
+            .all(|doc| doc.get("kind") == Some(&Value::String("odd".into())))
+    );
+
+    assert!(events.find_where_iter(Query::eq("kind", 1i32)).is_err());
+
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,91 @@
+use std::{collections::BTreeMap, fs};
+
+use crate::{
+    define_schema,
+    storage::{
+        double_write::{DoubleWriteBuffer, double_write_path},
+        page::PAGE_SIZE,
+        paged_collection::{PagedCollection, free_space_map_path, primary_key_index_path},
+    },
+};
+
+define_schema! {
+    Order {
+        item: string,
+    }
+}
+
+fn double_write_test_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("kenchidb_{}_{}.pages", name, std::process::id()))
+}
+
+fn remove_files(path: &std::path::Path) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(primary_key_index_path(path));
+    let _ = fs::remove_file(free_space_map_path(path));
+    let _ = fs::remove_file(double_write_path(path));
+}
+
+#[test]
+fn test_torn_page_is_restored_from_the_double_write_file() {
+    let path = double_write_test_path("double_write_torn");
+    remove_files(&path);
+
+    let mut orders = PagedCollection::new(Order::schema(), 1, &path).unwrap();
+    orders.enable_double_write().unwrap();
+    for number in 0..30 {
+        let item = format!("item {}", number);
+        orders
+            .insert(Order::create().set("item", item.as_str()).build())
+            .unwrap();
+    }
+    orders.flush().unwrap();
+    drop(orders);
+    // Emptied once the pages are synced in place
+    assert_eq!(fs::metadata(double_write_path(&path)).unwrap().len(), 0);
+
+    // A crash while page 0 was written in place: its image was staged, the page is torn
+    let mut raw = fs::read(&path).unwrap();
+    let image: Box<[u8; PAGE_SIZE]> = Box::new(raw[..PAGE_SIZE].try_into().unwrap());
+    let mut staged = BTreeMap::new();
+    staged.insert(0u32, image);
+    DoubleWriteBuffer::open(double_write_path(&path))
+        .unwrap()
+        .stage(staged.iter())
+        .unwrap();
+    raw[PAGE_SIZE / 2..PAGE_SIZE].fill(0);
+    fs::write(&path, &raw).unwrap();
+    let _ = fs::remove_file(primary_key_index_path(&path));
+
+    let orders = PagedCollection::new(Order::schema(), 1, &path).unwrap();
+    let report = orders.recovery_report();
+    assert_eq!(report.pages_restored, 1);
+    assert!(report.is_clean());
+    assert_eq!(orders.ids().len(), 30);
+    assert_eq!(fs::metadata(double_write_path(&path)).unwrap().len(), 0);
+
+    drop(orders);
+    remove_files(&path);
+}
+
+#[test]
+fn test_interrupted_staging_is_ignored() {
+    let path = double_write_test_path("double_write_staging");
+    remove_files(&path);
+
+    let mut orders = PagedCollection::new(Order::schema(), 1, &path).unwrap();
+    orders
+        .insert(Order::create().set("item", "kept").build())
+        .unwrap();
+    orders.flush().unwrap();
+    drop(orders);
+
+    // Cut off in the middle of the first image, nothing was written in place yet
+    fs::write(double_write_path(&path), vec![7u8; PAGE_SIZE / 3]).unwrap();
+    let orders = PagedCollection::new(Order::schema(), 1, &path).unwrap();
+    assert_eq!(orders.recovery_report().pages_restored, 0);
+    assert_eq!(orders.ids(), vec![1]);
+
+    drop(orders);
+    remove_files(&path);
+}
@@ -0,0 +1,276 @@
+use std::{fs, sync::Arc};
+
+use ::storage::{Argon2Params, EncryptionKey, generate_salt};
+
+use crate::{
+    define_schema,
+    schema::Value,
+    storage::{
+        codec::{AES_GCM_CODEC_ID, CHAINED_CODEC_ID, CodecRegistry, LZ4_CODEC_ID, PageCodec},
+        compression::Lz4Codec,
+        encryption::{EncryptionCodec, key_derivation_path},
+        file_manager::FileManager,
+        packed::{PackedPages, packed_pages_path},
+        page::{PAGE_CODEC_ID_OFFSET, PAGE_HEADER_SIZE, PAGE_SIZE},
+        paged_collection::{PagedCollection, free_space_map_path, primary_key_index_path},
+    },
+};
+
+define_schema! {
+    Patient {
+        name: string,
+    }
+}
+
+fn encryption_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("kenchidb_{}_{}.pages", name, std::process::id()))
+}
+
+/// Cheap enough for tests, real keys use `Argon2Params::default()`
+const TEST_PARAMS: Argon2Params = Argon2Params {
+    memory_kib: 64,
+    iterations: 1,
+    lanes: 1,
+};
+
+#[test]
+fn test_encrypted_pages_need_the_passphrase() {
+    let path = encryption_path("encryption_pages");
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+
+    let salt = generate_salt().unwrap();
+    let key = EncryptionKey::derive("correct horse", &salt, &TEST_PARAMS).unwrap();
+    {
+        let mut patients = PagedCollection::new(Patient::schema(), 1, &path).unwrap();
+        patients
+            .file_manager
+            .enable_encryption(EncryptionCodec::new(1, &key).unwrap())
+            .unwrap();
+        for _ in 0..40 {
+            patients
+                .insert(Patient::create().set("name", "Jane Confidential").build())
+                .unwrap();
+        }
+        patients.flush().unwrap();
+    }
+    let raw = fs::read(&path).unwrap();
+    assert!(!raw.windows(4).any(|window| window == b"Jane"));
+
+    // A wrong passphrase fails authentication instead of returning garbage
+    let wrong = EncryptionKey::derive("wrong horse", &salt, &TEST_PARAMS).unwrap();
+    let mut pages = FileManager::new(&path).unwrap();
+    pages
+        .enable_encryption(EncryptionCodec::new(1, &wrong).unwrap())
+        .unwrap();
+    assert!(pages.read_page(0).is_err());
+    drop(pages);
+
+    // A rotated key still reads pages written with the older one
+    let rotated = EncryptionKey::derive("battery staple", &salt, &TEST_PARAMS).unwrap();
+    let mut patients = PagedCollection::new(Patient::schema(), 1, &path).unwrap();
+    patients
+        .file_manager
+        .enable_encryption(EncryptionCodec::new(2, &rotated).unwrap().with_key(1, &key))
+        .unwrap();
+    assert_eq!(patients.iter().count(), 40);
+    assert_eq!(
+        patients.find_by_id(40).unwrap().unwrap().get("name"),
+        Some(&Value::String("Jane Confidential".to_string()))
+    );
+    drop(patients);
+
+    // An altered byte in an encrypted body is detected
+    let mut raw = fs::read(&path).unwrap();
+    raw[PAGE_SIZE / 2] ^= 1;
+    fs::write(&path, &raw).unwrap();
+    let mut pages = FileManager::new(&path).unwrap();
+    pages
+        .enable_encryption(EncryptionCodec::new(1, &key).unwrap())
+        .unwrap();
+    assert!(pages.read_page(0).is_err());
+
+    fs::remove_file(&path).unwrap();
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+}
+
+#[test]
+fn test_passphrase_salt_is_saved_with_the_file() {
+    let path = encryption_path("encryption_passphrase");
+    let key_path = key_derivation_path(&path);
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(&key_path);
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+
+    {
+        let mut patients = PagedCollection::new(Patient::schema(), 1, &path).unwrap();
+        patients
+            .file_manager
+            .enable_encryption_with_passphrase("correct horse", TEST_PARAMS)
+            .unwrap();
+        for _ in 0..40 {
+            patients
+                .insert(Patient::create().set("name", "Jane Confidential").build())
+                .unwrap();
+        }
+        patients.flush().unwrap();
+    }
+    let raw = fs::read(&path).unwrap();
+    assert!(!raw.windows(4).any(|window| window == b"Jane"));
+    let saved = fs::read(&key_path).unwrap();
+
+    // A wrong passphrase derives another key from the saved salt
+    let mut pages = FileManager::new(&path).unwrap();
+    pages
+        .enable_encryption_with_passphrase("wrong horse", TEST_PARAMS)
+        .unwrap();
+    assert!(pages.read_page(0).is_err());
+    drop(pages);
+
+    // The saved parameters are used, not the ones passed, and the file is kept
+    let mut patients = PagedCollection::new(Patient::schema(), 1, &path).unwrap();
+    patients
+        .file_manager
+        .enable_encryption_with_passphrase("correct horse", Argon2Params::default())
+        .unwrap();
+    assert_eq!(patients.iter().count(), 40);
+    assert_eq!(
+        patients.find_by_id(40).unwrap().unwrap().get("name"),
+        Some(&Value::String("Jane Confidential".to_string()))
+    );
+    drop(patients);
+    assert_eq!(fs::read(&key_path).unwrap(), saved);
+
+    // A damaged key file is reported instead of drawing a new salt
+    let mut damaged = saved.clone();
+    damaged[8] ^= 1;
+    fs::write(&key_path, &damaged).unwrap();
+    assert!(
+        EncryptionCodec::open_with_passphrase(&key_path, "correct horse", TEST_PARAMS).is_err()
+    );
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&key_path).unwrap();
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+}
+
+#[test]
+fn test_codec_chain_compresses_then_encrypts() {
+    let salt = generate_salt().unwrap();
+    let key = EncryptionKey::derive("correct horse", &salt, &TEST_PARAMS).unwrap();
+    let encryption: Arc<dyn PageCodec> = Arc::new(EncryptionCodec::new(1, &key).unwrap());
+    let chain = [
+        Arc::new(Lz4Codec) as Arc<dyn PageCodec>,
+        Arc::clone(&encryption),
+    ];
+    let mut codecs = CodecRegistry::new();
+    codecs.register(Arc::new(Lz4Codec)).unwrap();
+    codecs.register(encryption).unwrap();
+
+    let reserved = CodecRegistry::chained_reserved_bytes(&chain);
+    let text = "the quick brown fox jumps over the lazy dog ".repeat(40);
+    let mut page_bytes = [0u8; PAGE_SIZE];
+    page_bytes[PAGE_HEADER_SIZE..PAGE_HEADER_SIZE + text.len()].copy_from_slice(text.as_bytes());
+    let original = page_bytes;
+    CodecRegistry::encode_page_with(&chain, 3, &mut page_bytes).unwrap();
+    assert_eq!(page_bytes[PAGE_CODEC_ID_OFFSET], CHAINED_CODEC_ID);
+    assert_eq!(
+        page_bytes[PAGE_HEADER_SIZE + 2..PAGE_HEADER_SIZE + 5],
+        [2, LZ4_CODEC_ID, AES_GCM_CODEC_ID]
+    );
+    // Compressed before it was encrypted, the ciphertext is far shorter than the text
+    let encoded_length = u16::from_le_bytes([
+        page_bytes[PAGE_HEADER_SIZE],
+        page_bytes[PAGE_HEADER_SIZE + 1],
+    ]);
+    assert!((encoded_length as usize) < text.len() / 4);
+    codecs.decode_page(3, &mut page_bytes).unwrap();
+    assert_eq!(page_bytes, original);
+
+    // A body which does not compress is only encrypted
+    let mut state = 7u32;
+    for byte in page_bytes[PAGE_HEADER_SIZE..PAGE_SIZE - reserved].iter_mut() {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        *byte = state as u8;
+    }
+    let noise = page_bytes;
+    CodecRegistry::encode_page_with(&chain, 3, &mut page_bytes).unwrap();
+    assert_eq!(
+        page_bytes[PAGE_HEADER_SIZE + 2..PAGE_HEADER_SIZE + 4],
+        [1, AES_GCM_CODEC_ID]
+    );
+    codecs.decode_page(3, &mut page_bytes).unwrap();
+    assert_eq!(page_bytes, noise);
+
+    // The page is read with the codecs of the chain only
+    let mut partial = CodecRegistry::new();
+    partial.register(Arc::new(Lz4Codec)).unwrap();
+    CodecRegistry::encode_page_with(&chain, 3, &mut page_bytes).unwrap();
+    assert!(partial.decode_page(3, &mut page_bytes).is_err());
+}
+
+#[test]
+fn test_compressed_pages_can_be_encrypted() {
+    let path = encryption_path("encryption_compressed");
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(packed_pages_path(&path));
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+
+    let salt = generate_salt().unwrap();
+    let key = EncryptionKey::derive("correct horse", &salt, &TEST_PARAMS).unwrap();
+    {
+        let mut patients = PagedCollection::new(Patient::schema(), 1, &path).unwrap();
+        patients.file_manager.enable_compression().unwrap();
+        patients
+            .file_manager
+            .enable_encryption(EncryptionCodec::new(1, &key).unwrap())
+            .unwrap();
+        for _ in 0..200 {
+            patients
+                .insert(Patient::create().set("name", "Jane Confidential").build())
+                .unwrap();
+        }
+        patients.flush().unwrap();
+    }
+
+    // Encrypted pages still shrink and are packed, their text is not readable
+    let raw = fs::read(packed_pages_path(&path)).unwrap();
+    assert!(!raw.windows(4).any(|window| window == b"Jane"));
+    let mut packed = PackedPages::open(packed_pages_path(&path)).unwrap();
+    assert!(packed.slot_count() * 2 <= packed.page_end() as usize);
+    let mut page = [0u8; PAGE_SIZE];
+    packed.read(0, &mut page).unwrap();
+    assert_eq!(page[PAGE_CODEC_ID_OFFSET], CHAINED_CODEC_ID);
+    drop(packed);
+
+    // Compression alone does not read the pages
+    let mut pages = FileManager::new(&path).unwrap();
+    pages.enable_compression().unwrap();
+    assert!(pages.read_page(0).is_err());
+    drop(pages);
+
+    let mut patients = PagedCollection::new(Patient::schema(), 1, &path).unwrap();
+    patients.file_manager.enable_compression().unwrap();
+    patients
+        .file_manager
+        .enable_encryption(EncryptionCodec::new(1, &key).unwrap())
+        .unwrap();
+    assert_eq!(patients.iter().count(), 200);
+    assert_eq!(
+        patients.find_by_id(200).unwrap().unwrap().get("name"),
+        Some(&Value::String("Jane Confidential".to_string()))
+    );
+
+    drop(patients);
+    let _ = fs::remove_file(&path);
+    fs::remove_file(packed_pages_path(&path)).unwrap();
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+}
@@ -0,0 +1,99 @@
+use std::fs;
+
+use crate::{
+    common::DatabaseError,
+    storage::{
+        engine::{MemoryEngine, StorageEngine},
+        file_manager::FileManager,
+        page::PageType,
+    },
+};
+
+/// Write, read, free and sync pages through any engine
+fn exercise_engine<E: StorageEngine>(engine: &mut E) -> Vec<u32> {
+    let mut page_ids = Vec::new();
+    for number in 0..3u8 {
+        let (page_id, mut page) = engine.allocate_page(PageType::DataPage, 4).unwrap();
+        page.insert_record(&[number; 16]).unwrap();
+        engine.write_page(page_id, &mut page).unwrap();
+        page_ids.push(page_id);
+    }
+    assert_eq!(engine.page_count(), 3);
+
+    let page = engine.read_page(page_ids[1]).unwrap();
+    assert_eq!(page.header.collection_id, 4);
+    assert_eq!(page.get_record(0).unwrap(), &[1u8; 16]);
+
+    engine.free_page(page_ids[1]).unwrap();
+    let freed = engine.read_page(page_ids[1]).unwrap();
+    assert_eq!(freed.header.page_type, PageType::FreePage);
+    assert_eq!(freed.header.collection_id, 4);
+
+    engine.sync().unwrap();
+    assert_eq!(engine.stats().dirty_pages, 0);
+    assert!(engine.stats().pages_read >= 2);
+    assert!(matches!(
+        engine.read_page(10),
+        Err(DatabaseError::InvalidData(_))
+    ));
+    page_ids
+}
+
+#[test]
+fn test_file_manager_engine() {
+    let path = std::env::temp_dir().join(format!("kenchidb_engine_{}.pages", std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    let mut file_manager = FileManager::new(&path).unwrap();
+    exercise_engine(&mut file_manager);
+    // The file manager leaves reuse of freed pages to the collections
+    let (page_id, _) =
+        StorageEngine::allocate_page(&mut file_manager, PageType::DataPage, 4).unwrap();
+    assert_eq!(page_id, 3);
+    drop(file_manager);
+
+    let mut file_manager = FileManager::new(&path).unwrap();
+    let page = StorageEngine::read_page(&mut file_manager, 2).unwrap();
+    assert_eq!(page.get_record(0).unwrap(), &[2u8; 16]);
+
+    drop(file_manager);
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn test_memory_engine() {
+    let mut engine = MemoryEngine::new();
+    let page_ids = exercise_engine(&mut engine);
+
+    // The freed page is handed out again
+    let (page_id, page) = engine.allocate_page(PageType::OverflowPage, 5).unwrap();
+    assert_eq!(page_id, page_ids[1]);
+    assert_eq!(page.header.page_type, PageType::OverflowPage);
+    assert_eq!(engine.page_count(), 3);
+
+    // Allocated pages exist once they are written
+    let (page_id, mut page) = engine.allocate_page(PageType::DataPage, 5).unwrap();
+    assert!(engine.read_page(page_id).is_err());
+    engine.write_page(page_id, &mut page).unwrap();
+    assert!(engine.read_page(page_id).unwrap().slots.is_empty());
+    assert_eq!(engine.stats().page_count, 4);
+}
+
+#[test]
+fn test_file_manager_refuses_second_writer() {
+    let path = std::env::temp_dir().join(format!("kenchidb_locked_{}.pages", std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    let file_manager = FileManager::new(&path).unwrap();
+    assert!(matches!(
+        FileManager::new(&path),
+        Err(DatabaseError::Locked(_))
+    ));
+
+    // The lock goes with the file manager
+    drop(file_manager);
+    let file_manager = FileManager::new(&path).unwrap();
+
+    drop(file_manager);
+    let _ = fs::remove_file(&path);
+}
@@ -0,0 +1,111 @@
+use crate::{common::DatabaseError, database::Database, define_schema, integration::DatabasePool};
+
+define_schema! {
+    Author {
+        name: string,
+    }
+}
+
+define_schema! {
+    Book {
+        author: long,
+        title: string,
+    }
+}
+
+fn library() -> Database {
+    let mut db = Database::new();
+    db.create_collection("authors".to_string(), Author::schema())
+        .unwrap();
+    db.create_collection("books".to_string(), Book::schema())
+        .unwrap();
+
+    let authors = db.collection("authors").unwrap();
+    let author = authors
+        .insert(Author::create().set("name", "Ursula").build())
+        .unwrap();
+    let books = db.collection("books").unwrap();
+    books
+        .insert(
+            Book::create()
+                .set("author", author as i64)
+                .set("title", "The \"Dispossessed\"")
+                .build(),
+        )
+        .unwrap();
+    db
+}
+
+#[test]
+fn test_export_writes_header_and_documents() {
+    let db = library();
+    let snapshot = db.snapshot(&["authors", "books", "authors"]).unwrap();
+    assert_eq!(snapshot.versions(), vec![("authors", 1), ("books", 1)]);
+
+    let mut json = Vec::new();
+    snapshot.to_json_writer(&mut json).unwrap();
+    assert_eq!(
+        String::from_utf8(json).unwrap(),
+        format!(
+            "{{\"snapshot\":{{\"format\":1,\"taken_at\":{},\"collections\":{{\
+             \"authors\":{{\"version\":1,\"documents\":1}},\
+             \"books\":{{\"version\":1,\"documents\":1}}}}}},\
+             \"collections\":{{\
+             \"authors\":[{{\"_id\":1,\"name\":\"Ursula\"}}],\
+             \"books\":[{{\"_id\":1,\"author\":1,\"title\":\"The \\\"Dispossessed\\\"\"}}]}}}}",
+            snapshot.taken_at
+        )
+    );
+
+    assert!(matches!(
+        db.snapshot(&["authors", "publishers"]),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+}
+
+#[test]
+fn test_snapshot_is_isolated_from_later_writes() {
+    let pool = DatabasePool::new(library());
+    let snapshot = pool
+        .read()
+        .unwrap()
+        .snapshot(&["authors", "books"])
+        .unwrap();
+
+    // A new book referencing a new author, committed after the snapshot
+    let mut authors = pool.session("authors").unwrap();
+    let author = authors
+        .insert(Author::create().set("name", "Octavia").build())
+        .unwrap();
+    authors.commit().unwrap();
+    let mut books = pool.session("books").unwrap();
+    books
+        .insert(
+            Book::create()
+                .set("author", author as i64)
+                .set("title", "Kindred")
+                .build(),
+        )
+        .unwrap();
+    books.commit().unwrap();
+
+    assert_eq!(snapshot.versions(), vec![("authors", 1), ("books", 1)]);
+    assert_eq!(snapshot.collection("books").unwrap().find_all().len(), 1);
+    assert!(snapshot.collection("publishers").is_none());
+
+    let later = pool
+        .read()
+        .unwrap()
+        .snapshot(&["authors", "books"])
+        .unwrap();
+    assert_eq!(later.versions(), vec![("authors", 2), ("books", 2)]);
+
+    let mut json = Vec::new();
+    pool.read()
+        .unwrap()
+        .export_json(&["books"], &mut json)
+        .unwrap();
+    let json = String::from_utf8(json).unwrap();
+    assert!(json.contains("\"books\":{\"version\":2,\"documents\":2}"));
+    assert!(json.contains("\"title\":\"Kindred\""));
+}
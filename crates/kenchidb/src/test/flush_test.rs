@@ -0,0 +1,82 @@
+use std::fs;
+
+use crate::{
+    define_schema,
+    storage::{
+        file_manager::FileManager,
+        page::{PAGE_SIZE, PageType},
+        paged_collection::{PagedCollection, free_space_map_path, primary_key_index_path},
+    },
+};
+
+define_schema! {
+    Line {
+        text: string,
+    }
+}
+
+fn flush_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("kenchidb_{}_{}.pages", name, std::process::id()))
+}
+
+#[test]
+fn test_dirty_pages_are_written_on_flush() {
+    let path = flush_path("flush_pages");
+    let _ = fs::remove_file(&path);
+
+    let mut pages = FileManager::new(&path).unwrap();
+    for _ in 0..3 {
+        let (page_id, mut page) = pages.allocate_page(PageType::DataPage, 1).unwrap();
+        page.insert_record(b"first").unwrap();
+        pages.write_page(page_id, &mut page).unwrap();
+    }
+
+    // Rewritten pages stay one dirty page, and are read back before they reach the file
+    let mut page = pages.read_page(1).unwrap();
+    page.insert_record(b"second").unwrap();
+    pages.write_page(1, &mut page).unwrap();
+    assert_eq!(pages.dirty_page_count(), 3);
+    assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+    assert_eq!(pages.read_page(1).unwrap().live_record_count(), 2);
+
+    pages.flush().unwrap();
+    assert_eq!(pages.dirty_page_count(), 0);
+    assert_eq!(fs::metadata(&path).unwrap().len(), 3 * PAGE_SIZE as u64);
+    drop(pages);
+
+    let mut reopened = FileManager::new(&path).unwrap();
+    assert_eq!(reopened.read_page(1).unwrap().live_record_count(), 2);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_collection_writes_pages_on_flush() {
+    let path = flush_path("flush_collection");
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+
+    let mut lines = PagedCollection::new(Line::schema(), 1, &path).unwrap();
+    for i in 0..100 {
+        let text = format!("line {}", i);
+        lines
+            .insert(Line::create().set("text", text.as_str()).build())
+            .unwrap();
+    }
+    assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+    assert_eq!(
+        lines.file_manager.dirty_page_count(),
+        lines.stats().total_pages as usize
+    );
+
+    lines.flush().unwrap();
+    assert_eq!(lines.file_manager.dirty_page_count(), 0);
+    drop(lines);
+    let lines = PagedCollection::new(Line::schema(), 1, &path).unwrap();
+    assert_eq!(lines.ids().len(), 100);
+
+    fs::remove_file(&path).unwrap();
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+}
@@ -0,0 +1,136 @@
+use std::{
+    fs,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    define_schema,
+    storage::{
+        file_manager::FileManager,
+        flusher::{BackgroundFlusher, FlusherConfig},
+        page::{PAGE_SIZE, PageType},
+        paged_collection::{PagedCollection, free_space_map_path, primary_key_index_path},
+    },
+};
+
+define_schema! {
+    Event {
+        name: string,
+    }
+}
+
+fn flusher_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("kenchidb_{}_{}.pages", name, std::process::id()))
+}
+
+fn collection_pages(collection: &mut PagedCollection) -> &mut FileManager {
+    &mut collection.file_manager
+}
+
+fn insert_events(events: &Mutex<PagedCollection>, count: usize) {
+    let mut events = events.lock().unwrap();
+    for number in 0..count {
+        let name = format!("event {:0>100}", number);
+        events
+            .insert(Event::create().set("name", name.as_str()).build())
+            .unwrap();
+    }
+}
+
+/// Wait for the flusher to leave no dirty pages, false on timeout
+fn wait_until_clean(events: &Mutex<PagedCollection>) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        if events.lock().unwrap().file_manager.dirty_page_count() == 0 {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+    false
+}
+
+#[test]
+fn test_pages_are_flushed_after_the_interval() {
+    let path = flusher_path("flusher_interval");
+    let _ = fs::remove_file(&path);
+
+    let events = Arc::new(Mutex::new(
+        PagedCollection::new(Event::schema(), 1, &path).unwrap(),
+    ));
+    let config = FlusherConfig {
+        interval: Duration::from_millis(20),
+        dirty_watermark: usize::MAX,
+    };
+    let flusher = BackgroundFlusher::start(Arc::clone(&events), collection_pages, config);
+
+    insert_events(&events, 100);
+    assert!(wait_until_clean(&events));
+    let pages = events.lock().unwrap().stats().total_pages;
+    assert_eq!(
+        fs::metadata(&path).unwrap().len(),
+        pages as u64 * PAGE_SIZE as u64
+    );
+
+    flusher.stop().unwrap();
+    drop(events);
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+}
+
+#[test]
+fn test_watermark_wakes_the_flusher_early() {
+    let path = flusher_path("flusher_watermark");
+    let _ = fs::remove_file(&path);
+
+    let events = Arc::new(Mutex::new(
+        PagedCollection::new(Event::schema(), 1, &path).unwrap(),
+    ));
+    let config = FlusherConfig {
+        interval: Duration::from_secs(3600),
+        dirty_watermark: 2,
+    };
+    let flusher = BackgroundFlusher::start(Arc::clone(&events), collection_pages, config);
+
+    insert_events(&events, 100);
+    assert!(wait_until_clean(&events));
+    assert!(fs::metadata(&path).unwrap().len() > 0);
+
+    // Stopping flushes what the watermark left dirty
+    insert_events(&events, 1);
+    flusher.stop().unwrap();
+    assert_eq!(events.lock().unwrap().file_manager.dirty_page_count(), 0);
+
+    drop(events);
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+}
+
+#[test]
+fn test_stale_batch_does_not_overwrite_a_newer_flush() {
+    let path = flusher_path("flusher_stale");
+    let _ = fs::remove_file(&path);
+
+    let mut pages = FileManager::new(&path).unwrap();
+    let (page_id, mut page) = pages.allocate_page(PageType::DataPage, 1).unwrap();
+    page.insert_record(b"old").unwrap();
+    pages.write_page(page_id, &mut page).unwrap();
+    let mut batch = pages.begin_flush().unwrap().unwrap();
+    assert_eq!(batch.page_count(), 1);
+
+    page.insert_record(b"new").unwrap();
+    pages.write_page(page_id, &mut page).unwrap();
+    pages.flush().unwrap();
+
+    // The foreground flush already wrote a newer version of the page
+    assert!(!batch.write().unwrap());
+    pages.finish_flush(&batch);
+    drop(pages);
+    let mut reopened = FileManager::new(&path).unwrap();
+    assert_eq!(reopened.read_page(page_id).unwrap().live_record_count(), 2);
+
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,155 @@
+use std::fs;
+
+use crate::{
+    common::DatabaseError,
+    define_schema,
+    schema::Value,
+    storage::paged_collection::{PagedCollection, free_space_map_path, primary_key_index_path},
+};
+
+define_schema! {
+    Entry {
+        label: string,
+    }
+}
+
+fn entry(i: usize) -> crate::schema::Document {
+    let label = format!("{}{}", "entry".repeat(30), i);
+    Entry::create().set("label", label.as_str()).build()
+}
+
+/// Ids of the documents stored in the page
+fn ids_in_page(entries: &PagedCollection, page_id: u32) -> Vec<u64> {
+    entries
+        .documents
+        .entries()
+        .into_iter()
+        .filter(|(_, (page, _))| *page == page_id)
+        .map(|(id, _)| id)
+        .collect()
+}
+
+#[test]
+fn test_deleted_space_is_reused() {
+    let path =
+        std::env::temp_dir().join(format!("kenchidb_free_space_{}.pages", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+
+    let mut entries = PagedCollection::new(Entry::schema(), 1, &path).unwrap();
+    for i in 0..100 {
+        entries.insert(entry(i)).unwrap();
+    }
+    let pages = entries.stats().total_pages;
+    assert!(pages >= 4, "{} pages", pages);
+
+    // Emptied pages are freed, partly emptied ones keep track of their room
+    let page_one = ids_in_page(&entries, 1);
+    for id in &page_one {
+        entries.delete(*id).unwrap();
+    }
+    let page_zero = ids_in_page(&entries, 0);
+    for id in page_zero.iter().step_by(2) {
+        entries.delete(*id).unwrap();
+    }
+    assert_eq!(entries.free_space.free_pages(), vec![1]);
+    assert!(entries.free_space.available(0).unwrap() > 1000);
+    let deleted = page_one.len() + page_zero.len().div_ceil(2);
+
+    // Refilled without growing the file
+    for i in 0..deleted {
+        entries.insert(entry(100 + i)).unwrap();
+    }
+    assert_eq!(entries.stats().total_pages, pages);
+    assert_eq!(entries.stats().free_pages, 0);
+    assert_eq!(entries.ids().len(), 100);
+    assert!(ids_in_page(&entries, 0).len() >= page_zero.len());
+    for id in entries.ids() {
+        assert!(entries.find_by_id(id).unwrap().is_some());
+    }
+
+    // The map is saved with the primary key index, and rebuilt from the pages without it
+    entries.delete(entries.ids()[0]).unwrap();
+    entries.flush().unwrap();
+    let map = entries.free_space.clone();
+    drop(entries);
+    let entries = PagedCollection::new(Entry::schema(), 1, &path).unwrap();
+    assert_eq!(entries.free_space, map);
+    drop(entries);
+    fs::remove_file(free_space_map_path(&path)).unwrap();
+    let mut entries = PagedCollection::new(Entry::schema(), 1, &path).unwrap();
+    assert_eq!(entries.free_space, map);
+
+    // Once no page has room the file grows again
+    for i in 0..100 {
+        entries.insert(entry(200 + i)).unwrap();
+    }
+    assert!(entries.stats().total_pages > pages);
+
+    fs::remove_file(&path).unwrap();
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+}
+
+#[test]
+fn test_update_in_place_or_relocated() {
+    let path = std::env::temp_dir().join(format!(
+        "kenchidb_paged_update_{}.pages",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+
+    let long = "x".repeat(255);
+    let mut entries = PagedCollection::new(Entry::schema(), 1, &path).unwrap();
+    for i in 0..60 {
+        let label = if i == 1 { "s" } else { long.as_str() };
+        entries
+            .insert(Entry::create().set("label", label).build())
+            .unwrap();
+    }
+    let label = |entries: &mut PagedCollection, id| {
+        let document = entries.find_by_id(id).unwrap().unwrap();
+        document.get("label").cloned()
+    };
+
+    // Shorter records keep their slot
+    let location = entries.documents.get(1).unwrap();
+    let short = Entry::create().set("label", "short").build();
+    entries.update(1, short).unwrap();
+    assert_eq!(entries.documents.get(1), Some(location));
+    assert_eq!(
+        label(&mut entries, 1),
+        Some(Value::String("short".to_string()))
+    );
+
+    // Records that outgrow their full page move, their slot is freed
+    let (page_id, slot_index) = entries.documents.get(2).unwrap();
+    let moved = Entry::create().set("label", long.as_str()).build();
+    entries.update(2, moved).unwrap();
+    let (new_page, _) = entries.documents.get(2).unwrap();
+    assert_ne!(new_page, page_id);
+    assert_eq!(label(&mut entries, 2), Some(Value::String(long.clone())));
+    let page = entries.file_manager.read_page(page_id).unwrap();
+    assert!(page.slots[slot_index as usize].is_free());
+    assert!(entries.free_space.available(page_id).unwrap() > 255);
+    entries.insert(entry(60)).unwrap();
+
+    assert!(matches!(
+        entries.update(1000, entry(0)),
+        Err(DatabaseError::DocumentNotFound(1000))
+    ));
+    assert_eq!(entries.ids().len(), 61);
+
+    // The page index finds the moved record after a rebuild
+    drop(entries);
+    let mut entries = PagedCollection::new(Entry::schema(), 1, &path).unwrap();
+    assert_eq!(label(&mut entries, 2), Some(Value::String(long)));
+    assert_eq!(entries.ids().len(), 61);
+
+    fs::remove_file(&path).unwrap();
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+}
@@ -0,0 +1,91 @@
+use std::fs;
+
+use crate::{
+    define_schema,
+    storage::{
+        file_manager::{FileManager, GrowthPolicy},
+        page::{PAGE_SIZE, PageType},
+        paged_collection::{PagedCollection, free_space_map_path, primary_key_index_path},
+    },
+};
+
+define_schema! {
+    Reading {
+        sensor: string,
+    }
+}
+
+fn growth_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("kenchidb_{}_{}.pages", name, std::process::id()))
+}
+
+#[test]
+fn test_file_grows_in_extents() {
+    let path = growth_path("growth_extents");
+    let _ = fs::remove_file(&path);
+
+    let mut pages = FileManager::new(&path).unwrap();
+    pages.set_growth_policy(GrowthPolicy::extent_bytes(64 * 1024));
+    assert_eq!(pages.growth_policy().extent_pages, 16);
+
+    for _ in 0..3 {
+        let (page_id, mut page) = pages.allocate_page(PageType::DataPage, 1).unwrap();
+        page.insert_record(b"reading").unwrap();
+        pages.write_page(page_id, &mut page).unwrap();
+    }
+    pages.flush().unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().len(), 16 * PAGE_SIZE as u64);
+    assert_eq!(pages.allocated_pages(), 16);
+    assert_eq!(pages.page_count(), 3);
+
+    // The next pages fill the extent, the file only grows past it
+    for _ in 0..14 {
+        let (page_id, mut page) = pages.allocate_page(PageType::DataPage, 1).unwrap();
+        pages.write_page(page_id, &mut page).unwrap();
+    }
+    pages.flush().unwrap();
+    assert_eq!(pages.allocated_pages(), 32);
+    drop(pages);
+
+    // The zeroed pages ahead of use are not counted when the file is opened again
+    let reopened = FileManager::new(&path).unwrap();
+    assert_eq!(reopened.page_count(), 17);
+    assert_eq!(reopened.allocated_pages(), 32);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_collection_reopens_a_preallocated_file() {
+    let path = growth_path("growth_collection");
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+
+    let mut readings = PagedCollection::new(Reading::schema(), 1, &path).unwrap();
+    readings
+        .file_manager
+        .set_growth_policy(GrowthPolicy::extent_bytes(1024 * 1024));
+    for number in 0..50 {
+        let sensor = format!("sensor {}", number);
+        readings
+            .insert(Reading::create().set("sensor", sensor.as_str()).build())
+            .unwrap();
+    }
+    readings.flush().unwrap();
+    let pages = readings.stats().total_pages;
+    drop(readings);
+    assert_eq!(fs::metadata(&path).unwrap().len(), 1024 * 1024);
+
+    // Without the saved index every page is checked, the extent does not look damaged
+    fs::remove_file(primary_key_index_path(&path)).unwrap();
+    let readings = PagedCollection::new(Reading::schema(), 1, &path).unwrap();
+    assert!(readings.recovery_report().is_clean());
+    assert_eq!(readings.stats().total_pages, pages);
+    assert_eq!(readings.ids().len(), 50);
+
+    drop(readings);
+    fs::remove_file(&path).unwrap();
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+}
@@ -0,0 +1,55 @@
+use crate::{
+    common::DatabaseError,
+    database::Database,
+    schema::{Field, FieldType, IdentifierKind, Schema, StringOverflow, validate_identifier},
+};
+
+fn field(name: &str) -> Field {
+    Field {
+        name: name.to_string(),
+        field_type: FieldType::Int,
+        nullable: false,
+        on_overflow: StringOverflow::Error,
+        auto_increment: false,
+    }
+}
+
+#[test]
+fn test_identifier_rules() {
+    let kind = IdentifierKind::Field;
+    assert!(validate_identifier(kind, "user_name2").is_ok());
+    assert!(validate_identifier(kind, "_id").is_ok());
+
+    for invalid in [
+        "",
+        "_secret",
+        "2fast",
+        "with space",
+        "naïve",
+        &"a".repeat(65),
+    ] {
+        let result = validate_identifier(kind, invalid);
+        assert!(
+            matches!(result, Err(DatabaseError::InvalidIdentifier(_))),
+            "{}",
+            invalid
+        );
+    }
+
+    assert!(validate_identifier(IdentifierKind::Collection, "_id").is_err());
+}
+
+#[test]
+fn test_schema_validation() {
+    assert!(Schema::try_new("User".to_string(), vec![field("age")]).is_ok());
+    assert!(Schema::try_new("User".to_string(), vec![field("age"), field("age")]).is_err());
+    assert!(Schema::try_new("User".to_string(), vec![field("_age")]).is_err());
+
+    let mut db = Database::new();
+    let schema = Schema::new("User".to_string(), vec![field("age")]);
+    assert!(
+        db.create_collection("bad-name".to_string(), schema.clone())
+            .is_err()
+    );
+    assert!(db.create_collection("users".to_string(), schema).is_ok());
+}
@@ -0,0 +1,564 @@
+use std::{collections::BTreeSet, fs, ops::Bound, sync::Arc};
+
+use crate::{
+    common::{DatabaseError, crc32},
+    database::Collection,
+    define_schema,
+    index::{
+        IndexKind, IndexOptions, IndexStats, SecondaryIndex, index_definitions_path,
+        index_entries_path, save_index_entries,
+    },
+    query::{AccessPath, ByteReader, Direction, Query, decode_find_query, encode_find_query},
+    schema::{Document, Value},
+    storage::wal::wal_path,
+};
+
+define_schema! {
+    Account {
+        email: string,
+        age: int,
+    }
+}
+
+fn account(email: &str, age: i32) -> Document {
+    Account::create()
+        .set("email", email)
+        .set("age", age)
+        .build()
+}
+
+fn accounts() -> Collection {
+    let mut accounts = Collection::new(Account::schema());
+    for (i, email) in ["ann@x.io", "bob@x.io", "cy@x.io", "dee@x.io"]
+        .into_iter()
+        .enumerate()
+    {
+        accounts.insert(account(email, 20 + i as i32 * 10)).unwrap();
+    }
+    accounts
+}
+
+fn ids(collection: &Collection, query: Query) -> Vec<u64> {
+    let mut ids: Vec<u64> = collection
+        .find_where(query)
+        .unwrap()
+        .iter()
+        .map(|document| document.id)
+        .collect();
+    ids.sort();
+    ids
+}
+
+#[test]
+fn test_index_answers_equality_and_range_queries() {
+    let mut accounts = accounts();
+    accounts
+        .create_index("email", IndexOptions::default().unique())
+        .unwrap();
+    accounts
+        .create_index("age", IndexOptions::default())
+        .unwrap();
+
+    let plan = accounts.explain(Query::eq("email", "cy@x.io")).unwrap();
+    assert!(matches!(&plan.access, AccessPath::Index { field, .. } if field == "email"));
+    assert!(
+        plan.to_string()
+            .starts_with("Index lookup on email: email = \"cy@x.io\"")
+    );
+    assert_eq!(ids(&accounts, Query::eq("email", "cy@x.io")), vec![3]);
+
+    // An equality wins over a range on another index
+    let query = Query::gte("age", 30i32).and(Query::eq("email", "dee@x.io"));
+    let plan = accounts.explain(query.clone()).unwrap();
+    assert!(matches!(&plan.access, AccessPath::Index { field, .. } if field == "email"));
+    assert_eq!(ids(&accounts, query), vec![4]);
+
+    let query = Query::gt("age", 20i32).and(Query::lte("age", 40i32));
+    let plan = accounts.explain(query.clone()).unwrap().to_string();
+    assert!(
+        plan.starts_with("Index range scan on age: age > 20, age <= 40"),
+        "{}",
+        plan
+    );
+    assert_eq!(ids(&accounts, query), vec![2, 3]);
+    assert!(
+        ids(
+            &accounts,
+            Query::gt("age", 40i32).and(Query::lt("age", 30i32))
+        )
+        .is_empty()
+    );
+
+    // Conditions the index cannot answer fall back to a full scan
+    let plan = accounts.explain(Query::ne("age", 30i32)).unwrap();
+    assert!(matches!(plan.access, AccessPath::FullScan));
+}
+
+#[test]
+fn test_index_follows_writes() {
+    let mut accounts = accounts();
+    accounts
+        .create_index("email", IndexOptions::default().unique())
+        .unwrap();
+
+    accounts.update(1, account("ann@y.io", 21)).unwrap();
+    assert!(ids(&accounts, Query::eq("email", "ann@x.io")).is_empty());
+    assert_eq!(ids(&accounts, Query::eq("email", "ann@y.io")), vec![1]);
+
+    accounts.delete(2).unwrap();
+    assert!(ids(&accounts, Query::eq("email", "bob@x.io")).is_empty());
+    let id = accounts.insert(account("bob@x.io", 50)).unwrap();
+    assert_eq!(ids(&accounts, Query::eq("email", "bob@x.io")), vec![id]);
+    assert_eq!(accounts.index("email").unwrap().len(), 4);
+
+    // Unique values are enforced on insert, update and commit
+    assert!(matches!(
+        accounts.insert(account("cy@x.io", 60)),
+        Err(DatabaseError::DuplicateKey(_))
+    ));
+    assert!(matches!(
+        accounts.update(1, account("cy@x.io", 21)),
+        Err(DatabaseError::DuplicateKey(_))
+    ));
+    let mut session = accounts.session();
+    session.insert(account("eve@x.io", 70)).unwrap();
+    session.insert(account("eve@x.io", 80)).unwrap_err();
+    accounts.commit(session).unwrap();
+    assert_eq!(ids(&accounts, Query::eq("email", "eve@x.io")).len(), 1);
+
+    // Sessions check values against the collection on commit
+    let mut session = accounts.session();
+    session.insert(account("eve@x.io", 90)).unwrap();
+    assert!(matches!(
+        accounts.commit(session),
+        Err(DatabaseError::DuplicateKey(_))
+    ));
+    assert_eq!(ids(&accounts, Query::eq("email", "eve@x.io")).len(), 1);
+}
+
+#[test]
+fn test_create_and_drop_index() {
+    let mut duplicates = accounts();
+    duplicates.insert(account("ann@x.io", 90)).unwrap();
+    assert!(matches!(
+        duplicates.create_index("email", IndexOptions::default().unique()),
+        Err(DatabaseError::DuplicateKey(_))
+    ));
+    duplicates
+        .create_index("email", IndexOptions::default())
+        .unwrap();
+    assert_eq!(ids(&duplicates, Query::eq("email", "ann@x.io")), vec![1, 5]);
+    assert!(matches!(
+        duplicates.create_index("email", IndexOptions::default()),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+    assert!(matches!(
+        duplicates.create_index("name", IndexOptions::default()),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+
+    duplicates.drop_index("email").unwrap();
+    let plan = duplicates.explain(Query::eq("email", "ann@x.io")).unwrap();
+    assert!(matches!(plan.access, AccessPath::FullScan));
+}
+
+#[test]
+fn test_index_definitions_survive_reopen() {
+    let path = std::env::temp_dir().join(format!("kenchidb_index_{}.data", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(wal_path(&path));
+    let _ = fs::remove_file(index_definitions_path(&path));
+
+    let mut accounts = Collection::with_file(Account::schema(), &path).unwrap();
+    accounts.insert(account("ann@x.io", 20)).unwrap();
+    accounts
+        .create_index("email", IndexOptions::default().unique())
+        .unwrap();
+    accounts.insert(account("bob@x.io", 30)).unwrap();
+    drop(accounts);
+
+    let mut reopened = Collection::with_file(Account::schema(), &path).unwrap();
+    assert!(reopened.index("email").unwrap().options.unique);
+    assert_eq!(reopened.index("email").unwrap().len(), 2);
+    assert_eq!(ids(&reopened, Query::eq("email", "bob@x.io")), vec![2]);
+    assert!(matches!(
+        reopened.insert(account("ann@x.io", 40)),
+        Err(DatabaseError::DuplicateKey(_))
+    ));
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(wal_path(&path)).unwrap();
+    fs::remove_file(index_definitions_path(&path)).unwrap();
+    fs::remove_file(index_entries_path(&path, "email")).unwrap();
+}
+
+#[test]
+fn test_damaged_index_is_rebuilt_on_open() {
+    let path = std::env::temp_dir().join(format!(
+        "kenchidb_index_rebuild_{}.data",
+        std::process::id()
+    ));
+    let entries = index_entries_path(&path, "email");
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(wal_path(&path));
+
+    let mut accounts = Collection::with_file(Account::schema(), &path).unwrap();
+    accounts
+        .create_index("email", IndexOptions::default().unique())
+        .unwrap();
+    accounts.insert(account("ann@x.io", 20)).unwrap();
+    accounts.insert(account("bob@x.io", 30)).unwrap();
+    assert!(entries.exists());
+    drop(accounts);
+
+    // A missing entries file
+    fs::remove_file(&entries).unwrap();
+    let reopened = Collection::with_file(Account::schema(), &path).unwrap();
+    assert_eq!(ids(&reopened, Query::eq("email", "bob@x.io")), vec![2]);
+    assert!(entries.exists());
+    drop(reopened);
+
+    // Entries saved for the current data which do not match the documents
+    let mut forged = SecondaryIndex::new("email".to_string(), IndexOptions::default());
+    forged.insert(&Document {
+        id: 1,
+        ..account("bob@x.io", 20)
+    });
+    let data_checksum = crc32(&fs::read(&path).unwrap());
+    save_index_entries(&forged, &entries, data_checksum).unwrap();
+    let reopened = Collection::with_file(Account::schema(), &path).unwrap();
+    assert!(reopened.verify_index("email").is_ok());
+    assert_eq!(ids(&reopened, Query::eq("email", "bob@x.io")), vec![2]);
+    drop(reopened);
+
+    // Unreadable entries
+    fs::write(&entries, b"not an index").unwrap();
+    let mut reopened = Collection::with_file(Account::schema(), &path).unwrap();
+    assert_eq!(reopened.index("email").unwrap().len(), 2);
+
+    // Damage found later is repaired on demand
+    reopened
+        .indexes
+        .get_mut("email")
+        .unwrap()
+        .insert(&Document {
+            id: 7,
+            ..account("cy@x.io", 40)
+        });
+    assert!(matches!(
+        reopened.verify_index("email"),
+        Err(DatabaseError::InvalidData(_))
+    ));
+    reopened.rebuild_index("email").unwrap();
+    assert!(reopened.verify_index("email").is_ok());
+    assert!(matches!(
+        reopened.rebuild_index("age"),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+
+    // Dropping the index removes its entries file
+    reopened.drop_index("email").unwrap();
+    assert!(!entries.exists());
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(wal_path(&path)).unwrap();
+    fs::remove_file(index_definitions_path(&path)).unwrap();
+}
+
+#[test]
+fn test_hash_index_answers_equality_only() {
+    let mut accounts = accounts();
+    accounts
+        .create_index("email", IndexOptions::default().hash().unique())
+        .unwrap();
+    accounts
+        .create_index("age", IndexOptions::default().hash())
+        .unwrap();
+
+    let plan = accounts
+        .explain(Query::eq("email", "bob@x.io"))
+        .unwrap()
+        .to_string();
+    assert!(
+        plan.starts_with("Hash index lookup on email: email = \"bob@x.io\""),
+        "{}",
+        plan
+    );
+    assert_eq!(ids(&accounts, Query::eq("email", "bob@x.io")), vec![2]);
+
+    // A range cannot use the hash index
+    let plan = accounts.explain(Query::gte("age", 30i32)).unwrap();
+    assert!(matches!(plan.access, AccessPath::FullScan));
+    assert_eq!(ids(&accounts, Query::gte("age", 30i32)), vec![2, 3, 4]);
+    assert_eq!(
+        accounts
+            .index("age")
+            .unwrap()
+            .range(
+                &Bound::Included(30i32.into()),
+                &Bound::Excluded(50i32.into())
+            )
+            .collect::<BTreeSet<u64>>(),
+        [2, 3].into()
+    );
+
+    // Writes and unique values work as with a B-tree index
+    accounts.update(2, account("bea@x.io", 30)).unwrap();
+    assert!(ids(&accounts, Query::eq("email", "bob@x.io")).is_empty());
+    assert!(matches!(
+        accounts.insert(account("bea@x.io", 30)),
+        Err(DatabaseError::DuplicateKey(_))
+    ));
+    accounts.delete(3).unwrap();
+    assert_eq!(accounts.index("age").unwrap().len(), 3);
+    assert!(accounts.verify_index("email").is_ok());
+}
+
+#[test]
+fn test_hash_index_survives_reopen() {
+    let path =
+        std::env::temp_dir().join(format!("kenchidb_index_hash_{}.data", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(wal_path(&path));
+
+    let mut accounts = Collection::with_file(Account::schema(), &path).unwrap();
+    accounts.insert(account("ann@x.io", 20)).unwrap();
+    accounts
+        .create_index("email", IndexOptions::default().hash())
+        .unwrap();
+    drop(accounts);
+
+    let reopened = Collection::with_file(Account::schema(), &path).unwrap();
+    let index = reopened.index("email").unwrap();
+    assert_eq!(index.options, IndexOptions::default().hash());
+    assert_eq!(index.len(), 1);
+    assert_eq!(ids(&reopened, Query::eq("email", "ann@x.io")), vec![1]);
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(wal_path(&path)).unwrap();
+    fs::remove_file(index_definitions_path(&path)).unwrap();
+    fs::remove_file(index_entries_path(&path, "email")).unwrap();
+}
+
+#[test]
+fn test_covered_query_reads_only_the_index() {
+    let mut accounts = accounts();
+    accounts
+        .create_index("age", IndexOptions::default())
+        .unwrap();
+
+    let query = Query::gte("age", 30i32)
+        .select(&["age"])
+        .order_by("age", Direction::Desc)
+        .limit(2);
+    let plan = accounts.explain(query.clone()).unwrap();
+    assert!(plan.covered);
+    assert!(
+        plan.to_string()
+            .contains("Projection: age, covered by the index"),
+        "{}",
+        plan
+    );
+
+    let ages = |documents: Vec<Document>| -> Vec<(u64, Option<Value>)> {
+        documents
+            .into_iter()
+            .map(|document| {
+                assert!(document.data.len() <= 1);
+                (document.id, document.get("age").cloned())
+            })
+            .collect()
+    };
+    assert_eq!(
+        ages(accounts.find_projected(query.clone()).unwrap()),
+        vec![(4, Some(Value::Int(50))), (3, Some(Value::Int(40)))]
+    );
+
+    // The documents are not read, a document missing from the collection
+    // but still in the index is returned
+    Arc::make_mut(&mut accounts.documents).remove(&4);
+    assert_eq!(ages(accounts.find_projected(query).unwrap())[0].0, 4);
+
+    // Another selected field needs the documents
+    let query = Query::eq("age", 30i32).select(&["email"]);
+    let plan = accounts.explain(query.clone()).unwrap();
+    assert!(!plan.covered);
+    assert!(plan.to_string().contains("Projection: email\n"));
+    let documents = accounts.find_projected(query).unwrap();
+    assert_eq!(documents.len(), 1);
+    assert_eq!(
+        documents[0].get("email"),
+        Some(&Value::String("bob@x.io".into()))
+    );
+    assert!(documents[0].get("age").is_none());
+
+    // Text syntax and the catalog encoding keep the projection
+    let query = accounts
+        .prepare("age > 20 SELECT age, email LIMIT 1")
+        .unwrap()
+        .bind(&[])
+        .unwrap();
+    assert_eq!(query.to_string(), "age > 20 SELECT age, email LIMIT 1");
+    let mut bytes = Vec::new();
+    encode_find_query(&query, &mut bytes).unwrap();
+    let decoded = decode_find_query(&mut ByteReader::new(&bytes)).unwrap();
+    assert_eq!(decoded.projection, query.projection);
+    assert!(matches!(
+        accounts.find_projected(Query::all().select(&["name"])),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+}
+
+#[test]
+fn test_index_stats() {
+    let mut accounts = accounts();
+    accounts.insert(account("eve@x.io", 30)).unwrap();
+    accounts
+        .create_index("email", IndexOptions::default().unique().hash())
+        .unwrap();
+    accounts
+        .create_index("age", IndexOptions::default())
+        .unwrap();
+
+    let stats = accounts.index_stats();
+    assert_eq!(
+        stats,
+        vec![
+            IndexStats {
+                field: "age".to_string(),
+                kind: IndexKind::BTree,
+                unique: false,
+                entries: 5,
+                distinct_values: 4,
+                pages: 1,
+            },
+            IndexStats {
+                field: "email".to_string(),
+                kind: IndexKind::Hash,
+                unique: true,
+                entries: 5,
+                distinct_values: 5,
+                pages: 1,
+            },
+        ]
+    );
+    assert_eq!(stats[0].average_duplicates(), 1.25);
+    assert_eq!(stats[1].selectivity(), 0.2);
+
+    accounts.delete(1).unwrap();
+    assert_eq!(accounts.index_stats()[0].entries, 4);
+    assert_eq!(accounts.index_stats()[0].distinct_values, 3);
+}
+
+#[test]
+fn test_planner_compares_index_and_full_scan_costs() {
+    let mut accounts = Collection::new(Account::schema());
+    for i in 0..100 {
+        let email = format!("user{}@x.io", i);
+        accounts.insert(account(&email, 20 + i % 50)).unwrap();
+    }
+    accounts
+        .create_index("age", IndexOptions::default())
+        .unwrap();
+    accounts
+        .create_index("email", IndexOptions::default())
+        .unwrap();
+
+    // Each age is held by two documents
+    let plan = accounts.explain(Query::eq("age", 30i32)).unwrap();
+    assert!(matches!(&plan.access, AccessPath::Index { field, .. } if field == "age"));
+    assert_eq!(plan.estimated_rows, 2);
+
+    // A narrow range reads few documents through the index
+    let plan = accounts.explain(Query::lt("age", 25i32)).unwrap();
+    assert!(matches!(&plan.access, AccessPath::Index { field, .. } if field == "age"));
+    assert_eq!(plan.estimated_rows, 10);
+
+    // A broad range is cheaper to answer by reading every document once
+    let query = Query::gte("age", 25i32);
+    let plan = accounts.explain(query.clone()).unwrap();
+    assert!(matches!(plan.access, AccessPath::FullScan));
+    assert_eq!(plan.estimated_rows, 100);
+    assert_eq!(ids(&accounts, query).len(), 90);
+
+    // Of two usable indexes the more selective one is scanned
+    let query = Query::between("email", "user5", "user6").and(Query::between("age", 20i32, 60i32));
+    let plan = accounts.explain(query.clone()).unwrap();
+    assert!(
+        matches!(&plan.access, AccessPath::Index { field, .. } if field == "email"),
+        "{}",
+        plan
+    );
+    assert_eq!(ids(&accounts, query).len(), 11);
+}
+
+#[test]
+fn test_online_index_build_merges_concurrent_writes() {
+    let mut accounts = accounts();
+    let mut build = accounts
+        .start_index_build("age", IndexOptions::default())
+        .unwrap();
+    assert!(
+        accounts
+            .start_index_build("age", IndexOptions::default())
+            .is_err()
+    );
+
+    assert!(build.step(3).unwrap());
+    assert_eq!(build.progress(), (3, 4));
+
+    // Writes during the build go to the side log
+    accounts.insert(account("eve@x.io", 60)).unwrap();
+    accounts.update(1, account("ann@x.io", 45)).unwrap();
+    accounts.delete(4).unwrap();
+    let mut session = accounts.session();
+    session.insert(account("fay@x.io", 70)).unwrap();
+    accounts.commit(session).unwrap();
+
+    assert!(!build.step(3).unwrap());
+    assert!(accounts.index("age").is_none());
+    accounts.finish_index_build(build).unwrap();
+
+    accounts.verify_index("age").unwrap();
+    assert_eq!(ids(&accounts, Query::gte("age", 40i32)), vec![1, 3, 5, 6]);
+    let plan = accounts.explain(Query::eq("age", 70i32)).unwrap();
+    assert!(matches!(&plan.access, AccessPath::Index { field, .. } if field == "age"));
+}
+
+#[test]
+fn test_online_unique_index_build_detects_conflicting_writes() {
+    let mut accounts = accounts();
+    let mut build = accounts
+        .start_index_build("email", IndexOptions::default().unique())
+        .unwrap();
+    build.run(2).unwrap();
+
+    // Swapping values between documents is fine, a second holder of a value is not
+    accounts.update(1, account("tmp@x.io", 20)).unwrap();
+    accounts.update(2, account("ann@x.io", 30)).unwrap();
+    accounts.update(1, account("bob@x.io", 20)).unwrap();
+    accounts.insert(account("cy@x.io", 50)).unwrap();
+    assert!(matches!(
+        accounts.finish_index_build(build),
+        Err(DatabaseError::DuplicateKey(_))
+    ));
+    assert!(accounts.index("email").is_none());
+
+    // Unfinished and aborted builds are not added
+    accounts.delete(5).unwrap();
+    let build = accounts
+        .start_index_build("email", IndexOptions::default().unique())
+        .unwrap();
+    assert!(matches!(
+        accounts.finish_index_build(build),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+
+    let mut build = accounts
+        .start_index_build("email", IndexOptions::default().unique())
+        .unwrap();
+    build.run(10).unwrap();
+    accounts.finish_index_build(build).unwrap();
+    accounts.verify_index("email").unwrap();
+    assert_eq!(ids(&accounts, Query::eq("email", "ann@x.io")), vec![2]);
+}
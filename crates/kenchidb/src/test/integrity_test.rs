@@ -0,0 +1,112 @@
+use std::fs::{self, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+
+use crate::{
+    define_schema,
+    storage::{
+        page::{PAGE_HEADER_SIZE, PAGE_SIZE},
+        paged_collection::{PagedCollection, free_space_map_path, primary_key_index_path},
+        recovery::{PageDamage, RecoveryAction},
+    },
+};
+
+define_schema! {
+    Entry {
+        label: string,
+    }
+}
+
+fn overwrite(path: &std::path::Path, offset: u64, bytes: &[u8]) {
+    let mut file = OpenOptions::new().write(true).open(path).unwrap();
+    file.seek(SeekFrom::Start(offset)).unwrap();
+    file.write_all(bytes).unwrap();
+}
+
+fn flip(path: &std::path::Path, offset: u64) {
+    let byte = fs::read(path).unwrap()[offset as usize];
+    overwrite(path, offset, &[byte ^ 0xFF]);
+}
+
+#[test]
+fn test_unclean_open_checks_every_page() {
+    let path =
+        std::env::temp_dir().join(format!("kenchidb_integrity_{}.pages", std::process::id()));
+    let index_path = primary_key_index_path(&path);
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(&index_path);
+
+    let mut entries = PagedCollection::new(Entry::schema(), 1, &path).unwrap();
+    entries.set_document_checksums(true);
+    let text = "entry".repeat(30);
+    for i in 0..100 {
+        let label = format!("{}{}", text, i);
+        entries
+            .insert(Entry::create().set("label", label.as_str()).build())
+            .unwrap();
+    }
+    entries.flush().unwrap();
+    let pages = entries.stats().total_pages;
+    assert!(pages >= 4, "{} pages", pages);
+    drop(entries);
+
+    // Opened from the saved index, nothing is checked
+    let entries = PagedCollection::new(Entry::schema(), 1, &path).unwrap();
+    assert!(entries.recovery_report().is_clean());
+    assert_eq!(entries.recovery_report().pages_checked, 0);
+    drop(entries);
+
+    // A crash: no index, a torn record in page 0, a page 1 never written,
+    // torn free space in the last page and half a page at the end
+    fs::remove_file(&index_path).unwrap();
+    flip(&path, PAGE_SIZE as u64 - 1);
+    overwrite(&path, PAGE_SIZE as u64, &[0; PAGE_SIZE]);
+    let last = (pages - 1) as u64 * PAGE_SIZE as u64;
+    flip(&path, last + PAGE_HEADER_SIZE as u64 + 1000);
+    let size = fs::metadata(&path).unwrap().len();
+    overwrite(&path, size, &[7; 100]);
+
+    let mut entries = PagedCollection::new(Entry::schema(), 1, &path).unwrap();
+    let report = entries.recovery_report().clone();
+    assert!(!report.is_clean());
+    assert_eq!(report.truncated_bytes, 100);
+    assert_eq!(fs::metadata(&path).unwrap().len(), size);
+    assert_eq!(report.pages_checked, pages);
+    assert_eq!(report.damaged_pages.len(), 3);
+    assert!(matches!(
+        report.damaged_pages[0].damage,
+        PageDamage::ChecksumMismatch { .. }
+    ));
+    assert!(matches!(
+        report.damaged_pages[1].damage,
+        PageDamage::Unreadable(_)
+    ));
+    assert_eq!(report.quarantined_pages(), vec![0, 1]);
+    assert_eq!(report.damaged_pages[2].page_id, pages - 1);
+    assert_eq!(report.damaged_pages[2].action, RecoveryAction::Repaired);
+
+    // Records of quarantined pages are left out, all others are kept.
+    // Those of the unreadable page cannot be counted.
+    assert!(report.records_lost > 0);
+    assert!(entries.ids().len() < 100 - report.records_lost);
+    assert!(entries.ids().len() > 50);
+    assert!(entries.find_by_id(1).unwrap().is_none());
+    assert!(entries.find_by_id(100).unwrap().is_some());
+    entries.flush().unwrap();
+    drop(entries);
+
+    // The repaired page checks out the next time
+    fs::remove_file(&index_path).unwrap();
+    let entries = PagedCollection::new(Entry::schema(), 1, &path).unwrap();
+    assert_eq!(entries.recovery_report().quarantined_pages(), vec![0, 1]);
+    assert!(
+        entries
+            .recovery_report()
+            .damaged_pages
+            .iter()
+            .all(|page| page.action == RecoveryAction::Quarantined)
+    );
+
+    fs::remove_file(&path).unwrap();
+    let _ = fs::remove_file(&index_path);
+    let _ = fs::remove_file(free_space_map_path(&path));
+}
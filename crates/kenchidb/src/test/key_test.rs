@@ -0,0 +1,196 @@
+use std::fs;
+
+use crate::{
+    common::DatabaseError,
+    database::Collection,
+    define_schema,
+    query::{DocumentPatch, Expr, Query, Update},
+    schema::{FieldType, Value, decode_key_value, encode_key},
+    storage::wal::wal_path,
+};
+
+define_schema! {
+    Account {
+        tenant_id: string,
+        user_id: long,
+        email: string,
+    }
+}
+
+fn account(tenant: &str, user: i64) -> crate::schema::Document {
+    Account::create()
+        .set("tenant_id", tenant)
+        .set("user_id", user)
+        .set("email", format!("{}@{}", user, tenant))
+        .build()
+}
+
+fn user_ids(documents: &[&crate::schema::Document]) -> Vec<i64> {
+    documents
+        .iter()
+        .map(|doc| match doc.get("user_id") {
+            Some(Value::Long(id)) => *id,
+            _ => panic!("missing user_id"),
+        })
+        .collect()
+}
+
+#[test]
+fn test_key_encoding_preserves_order() {
+    let ordered = [
+        vec![Value::Long(i64::MIN)],
+        vec![Value::Long(-1)],
+        vec![Value::Long(0)],
+        vec![Value::Long(7)],
+        vec![Value::Long(i64::MAX)],
+    ];
+    for pair in ordered.windows(2) {
+        assert!(encode_key(&pair[0]) < encode_key(&pair[1]));
+    }
+
+    let doubles = [f64::NEG_INFINITY, -2.5, -0.5, 0.0, 0.5, 3.0, f64::INFINITY];
+    for pair in doubles.windows(2) {
+        assert!(encode_key(&[Value::Double(pair[0])]) < encode_key(&[Value::Double(pair[1])]));
+    }
+
+    // Shorter strings sort first even with a following key field and embedded zero bytes
+    let keys = [
+        vec![Value::String("a".into()), Value::Int(9)],
+        vec![Value::String("a\0".into()), Value::Int(0)],
+        vec![Value::String("ab".into()), Value::Int(0)],
+        vec![Value::String("b".into()), Value::Int(-5)],
+    ];
+    for pair in keys.windows(2) {
+        assert!(encode_key(&pair[0]) < encode_key(&pair[1]));
+    }
+}
+
+#[test]
+fn test_key_values_decode() {
+    let values = [
+        (Value::Byte(200), FieldType::Byte),
+        (Value::Short(-3), FieldType::Short),
+        (Value::Int(i32::MIN), FieldType::Int),
+        (Value::Long(42), FieldType::Long),
+        (Value::Float(-1.5), FieldType::Float),
+        (Value::Double(0.25), FieldType::Double),
+        (Value::Double(-0.0), FieldType::Double),
+        (Value::Boolean(true), FieldType::Boolean),
+        (Value::String("a\0b".into()), FieldType::String),
+        (Value::String(String::new()), FieldType::String),
+    ];
+    for (value, field_type) in values {
+        let mut bytes = encode_key(std::slice::from_ref(&value));
+        let length = bytes.len();
+        bytes.push(7); // a following key field
+        assert_eq!(
+            decode_key_value(&bytes, &field_type).unwrap(),
+            (value, length)
+        );
+    }
+
+    assert!(matches!(
+        decode_key_value(b"ab", &FieldType::String),
+        Err(DatabaseError::InvalidData(_))
+    ));
+    assert!(decode_key_value(&[1, 2], &FieldType::Long).is_err());
+}
+
+#[test]
+fn test_composite_primary_key() {
+    let mut accounts =
+        Collection::new(Account::schema().with_primary_key(&["tenant_id", "user_id"]));
+
+    for (tenant, user) in [
+        ("beta", 2),
+        ("acme", 20),
+        ("acme", -3),
+        ("beta", 1),
+        ("acme", 5),
+    ] {
+        accounts.insert(account(tenant, user)).unwrap();
+    }
+
+    assert!(matches!(
+        accounts.insert(account("acme", 5)),
+        Err(DatabaseError::DuplicateKey(_))
+    ));
+
+    let acme = accounts.scan_key_prefix(&[Value::String("acme".into())]);
+    assert_eq!(user_ids(&acme), [-3, 5, 20]);
+
+    let found = accounts
+        .find_by_key(&[Value::String("beta".into()), Value::Long(1)])
+        .unwrap();
+    assert_eq!(found.get("email"), Some(&Value::String("1@beta".into())));
+
+    // Key changes are checked against the other documents
+    let id = found.id;
+    assert!(matches!(
+        accounts.patch(id, &DocumentPatch::new().set("user_id", 2i64)),
+        Err(DatabaseError::DuplicateKey(_))
+    ));
+    accounts
+        .patch(id, &DocumentPatch::new().set("user_id", 9i64))
+        .unwrap();
+    assert!(
+        accounts
+            .find_by_key(&[Value::String("beta".into()), Value::Long(1)])
+            .is_none()
+    );
+
+    // Shifting all keys of a tenant at once does not collide with itself
+    let shift = Update::new().set("user_id", Expr::field("user_id") + 1i64);
+    assert_eq!(
+        accounts
+            .update_where(Query::eq("tenant_id", "beta"), &shift)
+            .unwrap(),
+        2
+    );
+    let beta = accounts.scan_key_prefix(&[Value::String("beta".into())]);
+    assert_eq!(user_ids(&beta), [3, 10]);
+
+    accounts.delete(id).unwrap();
+    accounts.insert(account("beta", 10)).unwrap();
+}
+
+#[test]
+fn test_primary_key_validation_and_persistence() {
+    assert!(
+        Account::schema()
+            .with_primary_key(&["tenant_id", "missing"])
+            .validate()
+            .is_err()
+    );
+    assert!(
+        Account::schema()
+            .with_primary_key(&["user_id", "user_id"])
+            .validate()
+            .is_err()
+    );
+
+    let path = std::env::temp_dir().join(format!("kenchidb_keys_{}.db", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(wal_path(&path));
+    let schema = Account::schema().with_primary_key(&["tenant_id", "user_id"]);
+
+    {
+        let mut accounts = Collection::with_file(schema.clone(), &path).unwrap();
+        for user in [3, 1, 2] {
+            accounts.insert(account("acme", user)).unwrap();
+        }
+    }
+
+    let mut accounts = Collection::with_file(schema, &path).unwrap();
+    let acme = accounts.scan_key_prefix(&[Value::String("acme".into())]);
+    assert_eq!(user_ids(&acme), [1, 2, 3]);
+    assert!(accounts.insert(account("acme", 2)).is_err());
+
+    // The file handle survives loading, so later writes are persisted
+    accounts.insert(account("acme", 4)).unwrap();
+    let accounts = Collection::with_file(accounts.schema.clone(), &path).unwrap();
+    assert_eq!(accounts.find_all().len(), 4);
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(wal_path(&path)).unwrap();
+}
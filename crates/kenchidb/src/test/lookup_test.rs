@@ -0,0 +1,97 @@
+use crate::{common::DatabaseError, database::Database, define_schema, schema::Value};
+
+define_schema! {
+    Customer {
+        customer_id: long,
+        name: string,
+    }
+}
+
+define_schema! {
+    Order {
+        customer: long?,
+        total: double,
+        note: string?,
+    }
+}
+
+fn database() -> Database {
+    let mut db = Database::new();
+    db.create_collection("customers".to_string(), Customer::schema())
+        .unwrap();
+    db.create_collection("orders".to_string(), Order::schema())
+        .unwrap();
+
+    let customers = db.collection("customers").unwrap();
+    for (id, name) in [(1i64, "alice"), (2, "bob"), (3, "carol")] {
+        let customer = Customer::create()
+            .set("customer_id", id)
+            .set("name", name)
+            .build();
+        customers.insert(customer).unwrap();
+    }
+
+    let orders = db.collection("orders").unwrap();
+    for (customer, total) in [(1i64, 10.0f64), (2, 20.0), (1, 30.0), (9, 40.0)] {
+        let order = Order::create()
+            .set("customer", customer)
+            .set("total", total)
+            .build();
+        orders.insert(order).unwrap();
+    }
+    orders
+        .insert(Order::create().set("total", 50.0f64).build())
+        .unwrap();
+    db
+}
+
+fn totals(documents: &[&crate::schema::Document]) -> Vec<f64> {
+    documents
+        .iter()
+        .map(|document| match document.get("total") {
+            Some(Value::Double(total)) => *total,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+#[test]
+fn test_lookup_joins_matching_documents() {
+    let db = database();
+    let rows = db
+        .lookup("customers", "customer_id", "orders", "customer")
+        .unwrap();
+
+    assert_eq!(rows.len(), 3);
+    assert_eq!(totals(&rows[0].matches), vec![10.0, 30.0]);
+    assert_eq!(totals(&rows[1].matches), vec![20.0]);
+    assert!(rows[2].matches.is_empty());
+    assert_eq!(
+        rows[2].left.get("name"),
+        Some(&Value::String("carol".to_string()))
+    );
+
+    // Orders without a customer or with an unknown one have no match
+    let rows = db
+        .lookup("orders", "customer", "customers", "customer_id")
+        .unwrap();
+    let matched: Vec<usize> = rows.iter().map(|row| row.matches.len()).collect();
+    assert_eq!(matched, vec![1, 1, 1, 0, 0]);
+}
+
+#[test]
+fn test_lookup_rejects_invalid_fields() {
+    let db = database();
+    assert!(matches!(
+        db.lookup("customers", "name", "orders", "customer"),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+    assert!(matches!(
+        db.lookup("customers", "customer_id", "orders", "missing"),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+    assert!(matches!(
+        db.lookup("customers", "customer_id", "invoices", "customer"),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+}
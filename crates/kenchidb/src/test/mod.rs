@@ -0,0 +1,100 @@
+#[cfg(all(test, feature = "tokio"))]
+mod async_test;
+#[cfg(test)]
+mod btree_store_test;
+#[cfg(test)]
+mod catalog_test;
+#[cfg(test)]
+mod checksum_test;
+#[cfg(test)]
+mod codec_test;
+#[cfg(test)]
+mod compression_test;
+#[cfg(test)]
+mod cursor_test;
+#[cfg(test)]
+mod double_write_test;
+#[cfg(test)]
+mod encryption_test;
+#[cfg(test)]
+mod engine_test;
+#[cfg(test)]
+mod export_test;
+#[cfg(test)]
+mod flush_test;
+#[cfg(test)]
+mod flusher_test;
+#[cfg(test)]
+mod free_space_test;
+#[cfg(test)]
+mod growth_test;
+#[cfg(test)]
+mod identifier_test;
+#[cfg(test)]
+mod index_test;
+#[cfg(test)]
+mod integrity_test;
+#[cfg(test)]
+mod key_test;
+#[cfg(test)]
+mod lookup_test;
+#[cfg(test)]
+mod overflow_test;
+#[cfg(test)]
+mod page_test;
+#[cfg(test)]
+mod paged_database_test;
+#[cfg(test)]
+mod parser_test;
+#[cfg(test)]
+mod patch_test;
+#[cfg(test)]
+mod planner_test;
+#[cfg(test)]
+mod pool_test;
+#[cfg(test)]
+mod prepared_test;
+#[cfg(test)]
+mod presence_test;
+#[cfg(test)]
+mod primary_key_test;
+#[cfg(test)]
+mod query_test;
+#[cfg(test)]
+mod recovery_test;
+#[cfg(test)]
+mod relocate_test;
+#[cfg(test)]
+mod result_test;
+#[cfg(test)]
+mod sample_test;
+#[cfg(test)]
+mod search_test;
+#[cfg(test)]
+mod sequence_test;
+#[cfg(test)]
+mod session_test;
+#[cfg(test)]
+mod sort_test;
+#[cfg(test)]
+mod stats_test;
+#[cfg(test)]
+mod string_test;
+#[cfg(test)]
+mod tier_test;
+#[cfg(test)]
+mod transaction_test;
+#[cfg(test)]
+mod ttl_test;
+#[cfg(test)]
+mod update_test;
+#[cfg(test)]
+mod vacuum_test;
+#[cfg(test)]
+mod view_test;
+#[cfg(test)]
+mod wal_test;
+#[cfg(test)]
+mod warmup_test;
+#[cfg(test)]
+mod watch_test;
@@ -0,0 +1,124 @@
+use std::fs;
+
+use crate::{
+    define_schema,
+    schema::{Document, Value},
+    storage::{
+        page::PageType,
+        paged_collection::{PagedCollection, free_space_map_path, primary_key_index_path},
+    },
+};
+
+define_schema! {
+    Article {
+        title: string,
+        part1: string,
+        part2: string,
+        part3: string,
+        part4: string,
+        part5: string,
+        part6: string,
+        part7: string,
+        part8: string,
+        part9: string,
+        part10: string,
+        part11: string,
+        part12: string,
+        part13: string,
+        part14: string,
+        part15: string,
+        part16: string,
+        part17: string,
+        part18: string,
+        part19: string,
+        part20: string,
+    }
+}
+
+/// Article with parts of `length` bytes, larger than a page at 255
+fn article(title: &str, length: usize) -> Document {
+    let mut builder = Article::create().set("title", title);
+    for i in 1..=20 {
+        let text: String = format!("{}{}", title, i)
+            .repeat(length)
+            .chars()
+            .take(length)
+            .collect();
+        builder = builder.set(&format!("part{}", i), text.as_str());
+    }
+    builder.build()
+}
+
+fn title(entries: &mut PagedCollection, id: u64) -> Option<Value> {
+    let document = entries.find_by_id(id).unwrap()?;
+    document.get("title").cloned()
+}
+
+/// Pages of the given type in the file
+fn pages_of_type(entries: &mut PagedCollection, page_type: PageType) -> usize {
+    (0..entries.stats().total_pages)
+        .filter(|page_id| {
+            entries
+                .file_manager
+                .read_page(*page_id)
+                .is_ok_and(|page| page.header.page_type == page_type)
+        })
+        .count()
+}
+
+#[test]
+fn test_large_documents_use_overflow_pages() {
+    let path = std::env::temp_dir().join(format!("kenchidb_overflow_{}.pages", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+
+    let mut entries = PagedCollection::new(Article::schema(), 1, &path).unwrap();
+    let big = entries.insert(article("big", 255)).unwrap();
+    let small = entries.insert(article("small", 1)).unwrap();
+    let chained = pages_of_type(&mut entries, PageType::OverflowPage);
+    assert!(chained >= 2, "{} overflow pages", chained);
+    let expected = article("big", 255);
+    let found = entries.find_by_id(big).unwrap().unwrap();
+    for field in ["title", "part1", "part20"] {
+        assert_eq!(found.get(field), expected.get(field));
+    }
+    let scanned: Vec<Document> = entries.iter().map(|doc| doc.unwrap()).collect();
+    assert_eq!(scanned.len(), 2);
+    assert_eq!(scanned[0].get("part20"), expected.get("part20"));
+
+    // Shrunk in place, the chain is freed and reused by the next large document
+    let shrunk = article("shrunk", 1);
+    entries.update(big, shrunk).unwrap();
+    assert_eq!(pages_of_type(&mut entries, PageType::OverflowPage), 0);
+    assert_eq!(entries.stats().free_pages, chained);
+    entries.update(small, article("grown", 255)).unwrap();
+    assert_eq!(
+        title(&mut entries, small),
+        Some(Value::String("grown".to_string()))
+    );
+    assert_eq!(entries.stats().free_pages, 0);
+
+    // Found again when the page index is rebuilt
+    entries.flush().unwrap();
+    drop(entries);
+    fs::remove_file(primary_key_index_path(&path)).unwrap();
+    let mut entries = PagedCollection::new(Article::schema(), 1, &path).unwrap();
+    assert!(entries.recovery_report().is_clean());
+    assert_eq!(
+        title(&mut entries, small),
+        Some(Value::String("grown".to_string()))
+    );
+    assert_eq!(
+        title(&mut entries, big),
+        Some(Value::String("shrunk".to_string()))
+    );
+
+    entries.delete(small).unwrap();
+    assert_eq!(pages_of_type(&mut entries, PageType::OverflowPage), 0);
+    assert_eq!(entries.stats().free_pages, chained);
+
+    fs::remove_file(&path).unwrap();
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+}
@@ -0,0 +1,123 @@
+use std::fs;
+
+use crate::{
+    common::DatabaseError,
+    storage::{
+        file_manager::FileManager,
+        page::{PAGE_SIZE, Page, PageType, SLOT_SIZE},
+    },
+};
+
+#[test]
+fn test_page_roundtrip() {
+    let mut page = Page::new(PageType::DataPage, 7);
+    let first = page.insert_record(b"first record").unwrap();
+    let second = page.insert_record(b"second").unwrap();
+
+    let bytes = page.serialize();
+    let restored = Page::deserialize(0, &bytes).unwrap();
+
+    assert_eq!(restored.header.collection_id, 7);
+    assert_eq!(restored.get_record(first).unwrap(), b"first record");
+    assert_eq!(restored.get_record(second).unwrap(), b"second");
+}
+
+#[test]
+fn test_page_delete_reuses_slot() {
+    let mut page = Page::new(PageType::DataPage, 1);
+    let first = page.insert_record(b"aaaa").unwrap();
+    page.insert_record(b"bbbb").unwrap();
+
+    page.delete_record(first).unwrap();
+    assert!(page.get_record(first).is_err());
+    assert_eq!(page.live_record_count(), 1);
+
+    let free_space = page.free_space();
+    let reused = page.insert_record(b"cccc").unwrap();
+    assert_eq!(reused, first);
+    assert_eq!(page.free_space(), free_space - 4);
+    assert_eq!(page.get_record(reused).unwrap(), b"cccc");
+}
+
+#[test]
+fn test_page_full() {
+    let mut page = Page::new(PageType::DataPage, 1);
+    let record = [1u8; 1000];
+    while page.can_fit(record.len()) {
+        page.insert_record(&record).unwrap();
+    }
+    assert!(page.free_space() < record.len() + SLOT_SIZE);
+    assert!(page.insert_record(&record).is_err());
+}
+
+#[test]
+fn test_page_compact_reclaims_deleted_records() {
+    let mut page = Page::new(PageType::DataPage, 1);
+    let record = [7u8; 500];
+    let mut slots = Vec::new();
+    while page.can_fit(record.len()) {
+        slots.push(page.insert_record(&record).unwrap());
+    }
+    let kept = page.insert_record(b"kept").unwrap();
+    let last = *slots.last().unwrap();
+    for slot in &slots[..3] {
+        page.delete_record(*slot).unwrap();
+    }
+    page.delete_record(last).unwrap();
+    assert!(!page.can_fit(record.len()));
+
+    // The free slot at the end of the directory is dropped, the others stay for reuse
+    let free_space = page.compacted_free_space(0);
+    page.compact(0);
+    assert_eq!(page.free_space(), free_space);
+    assert_eq!(page.slots.len(), slots.len() + 1);
+    assert_eq!(page.get_record(kept).unwrap(), b"kept");
+    assert_eq!(page.get_record(slots[3]).unwrap(), &record[..]);
+
+    let restored = Page::deserialize(0, &page.serialize()).unwrap();
+    assert_eq!(restored.get_record(kept).unwrap(), b"kept");
+    assert_eq!(restored.live_record_count(), slots.len() - 3);
+    let mut restored = restored;
+    assert_eq!(restored.insert_record(&record).unwrap(), slots[0]);
+}
+
+#[test]
+fn test_damaged_page_is_reported_on_read() {
+    let mut page = Page::new(PageType::DataPage, 1);
+    page.insert_record(b"intact").unwrap();
+    let bytes = page.serialize();
+
+    // Header fields are covered as well as the records
+    for offset in [12, PAGE_SIZE - 3] {
+        let mut damaged = bytes;
+        damaged[offset] ^= 0x01;
+        assert!(matches!(
+            Page::deserialize(4, &damaged),
+            Err(DatabaseError::Corruption { page_id: 4, expected, actual })
+                if expected == page.header.checksum && actual != expected
+        ));
+        assert!(Page::deserialize_unverified(&damaged).is_ok());
+    }
+
+    let path = std::env::temp_dir().join(format!("kenchidb_corrupt_{}.pages", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let mut pages = FileManager::new(&path).unwrap();
+    for _ in 0..2 {
+        let (page_id, mut page) = pages.allocate_page(PageType::DataPage, 1).unwrap();
+        page.insert_record(b"stored").unwrap();
+        pages.write_page(page_id, &mut page).unwrap();
+    }
+    drop(pages);
+    let mut file = fs::read(&path).unwrap();
+    file[PAGE_SIZE + 100] ^= 0xFF;
+    fs::write(&path, file).unwrap();
+
+    let mut pages = FileManager::new(&path).unwrap();
+    assert!(pages.read_page(0).is_ok());
+    assert!(matches!(
+        pages.read_page(1),
+        Err(DatabaseError::Corruption { page_id: 1, .. })
+    ));
+
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,163 @@
+use std::fs;
+
+use crate::{
+    define_schema,
+    schema::{Document, Value},
+    storage::{
+        paged_collection::{free_space_map_path, primary_key_index_path},
+        paged_database::{PagedDatabase, collection_index_path, paged_catalog_path},
+    },
+};
+
+define_schema! {
+    Author {
+        name: string,
+    }
+}
+
+define_schema! {
+    Book {
+        title: string,
+    }
+}
+
+fn book(number: u64) -> Document {
+    let title = format!("{:0>200}", number);
+    Book::create().set("title", title.as_str()).build()
+}
+
+fn database_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("kenchidb_{}_{}.pages", name, std::process::id()))
+}
+
+fn remove_database(path: &std::path::Path) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(paged_catalog_path(path));
+    for id in 1..=2 {
+        let index_path = collection_index_path(path, id);
+        let _ = fs::remove_file(primary_key_index_path(&index_path));
+        let _ = fs::remove_file(free_space_map_path(&index_path));
+    }
+}
+
+#[test]
+fn test_collections_share_one_file() {
+    let path = database_path("paged_database_shared");
+    remove_database(&path);
+
+    let mut database = PagedDatabase::open(&path).unwrap();
+    assert_eq!(
+        database
+            .create_collection("authors", Author::schema())
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        database.create_collection("books", Book::schema()).unwrap(),
+        2
+    );
+    assert!(database.create_collection("books", Book::schema()).is_err());
+
+    // Interleaved inserts put the pages of both collections in the same file
+    for number in 1..=60 {
+        let name = format!("author {}", number);
+        database
+            .with_collection("authors", |authors| {
+                authors.insert(Author::create().set("name", name.as_str()).build())
+            })
+            .unwrap();
+        database
+            .with_collection("books", |books| books.insert(book(number)))
+            .unwrap();
+    }
+    database.flush().unwrap();
+
+    let authors = database.collection_pages("authors").unwrap();
+    let books = database.collection_pages("books").unwrap();
+    assert!(authors.used > 0 && books.used > authors.used);
+    assert_eq!(
+        authors.used + books.used,
+        database.file_manager.page_count()
+    );
+    assert_eq!(database.file_manager.collection_ids().unwrap(), vec![1, 2]);
+    drop(database);
+
+    let mut database = PagedDatabase::open(&path).unwrap();
+    assert_eq!(database.collection_names(), vec!["authors", "books"]);
+    let author = database
+        .with_collection("authors", |authors| authors.find_by_id(7))
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        author.get("name"),
+        Some(&Value::String("author 7".to_string()))
+    );
+    let ids = database
+        .with_collection("books", |books| Ok(books.ids()))
+        .unwrap();
+    assert_eq!(ids, (1..=60).collect::<Vec<u64>>());
+    assert!(database.with_collection("missing", |_| Ok(())).is_err());
+
+    drop(database);
+    remove_database(&path);
+}
+
+#[test]
+fn test_free_pages_stay_with_their_collection() {
+    let path = database_path("paged_database_free");
+    remove_database(&path);
+
+    let mut database = PagedDatabase::open(&path).unwrap();
+    database
+        .create_collection("authors", Author::schema())
+        .unwrap();
+    database.create_collection("books", Book::schema()).unwrap();
+    database
+        .with_collection("books", |books| {
+            for number in 1..=40 {
+                books.insert(book(number))?;
+            }
+            for id in 1..=40 {
+                books.delete(id)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+    let freed = database.collection_pages("books").unwrap().free;
+    assert!(freed > 0);
+
+    // Another collection grows the file rather than taking the freed pages
+    let pages = database.file_manager.page_count();
+    database
+        .with_collection("authors", |authors| {
+            authors.insert(Author::create().set("name", "Ursula").build())
+        })
+        .unwrap();
+    assert_eq!(database.file_manager.page_count(), pages + 1);
+    assert_eq!(database.collection_pages("books").unwrap().free, freed);
+    database.flush().unwrap();
+    drop(database);
+
+    // Rebuilt from the pages, each collection finds only its own documents and free pages
+    for id in 1..=2 {
+        let index_path = collection_index_path(&path, id);
+        fs::remove_file(primary_key_index_path(&index_path)).unwrap();
+    }
+    let mut database = PagedDatabase::open(&path).unwrap();
+    let authors = database
+        .with_collection("authors", |authors| Ok((authors.ids(), authors.stats())))
+        .unwrap();
+    assert_eq!(authors.0, vec![1]);
+    assert_eq!(authors.1.free_pages, 0);
+    let free_pages = database
+        .with_collection("books", |books| {
+            books.insert(book(41))?;
+            Ok(books.stats().free_pages)
+        })
+        .unwrap();
+    assert_eq!(free_pages, freed as usize - 1);
+    assert_eq!(database.file_manager.page_count(), pages + 1);
+
+    drop(database);
+    remove_database(&path);
+}
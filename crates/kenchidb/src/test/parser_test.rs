@@ -0,0 +1,124 @@
+use crate::{
+    common::DatabaseError,
+    database::Database,
+    define_schema,
+    query::{Direction, Query, QueryParser},
+    schema::Value,
+};
+
+define_schema! {
+    Person {
+        name: string,
+        age: int,
+        score: double,
+        is_active: boolean,
+    }
+}
+
+fn people() -> Database {
+    let mut db = Database::new();
+    db.create_collection("people".to_string(), Person::schema())
+        .unwrap();
+    let people = db.collection("people").unwrap();
+    for (name, age, is_active) in [
+        ("ann", 41i32, true),
+        ("bob", 35i32, true),
+        ("cid", 52i32, false),
+        ("dee", 19i32, true),
+        ("eve", 33i32, true),
+    ] {
+        people
+            .insert(
+                Person::create()
+                    .set("name", name)
+                    .set("age", age)
+                    .set("score", age as f64 / 2.0)
+                    .set("is_active", is_active)
+                    .build(),
+            )
+            .unwrap();
+    }
+    db
+}
+
+fn names(db: &Database, text: &str) -> Vec<String> {
+    db.query("people", text)
+        .unwrap()
+        .iter()
+        .map(|doc| match doc.get("name") {
+            Some(Value::String(name)) => name.clone(),
+            other => panic!("unexpected name {:?}", other),
+        })
+        .collect()
+}
+
+#[test]
+fn test_text_query_with_clauses() {
+    let db = people();
+
+    assert_eq!(
+        names(&db, "age > 30 AND is_active = true ORDER BY name LIMIT 2"),
+        ["ann", "bob"]
+    );
+    assert_eq!(
+        names(
+            &db,
+            "age > 30 and is_active = TRUE order by age desc offset 1"
+        ),
+        ["bob", "eve"]
+    );
+    assert_eq!(names(&db, "ORDER BY age LIMIT 1 OFFSET 1"), ["eve"]);
+    assert_eq!(names(&db, "score >= 26 OR name LIKE 'd%'").len(), 2);
+}
+
+#[test]
+fn test_text_query_round_trips_display() {
+    let schema = Person::schema();
+    let queries = [
+        Query::all().limit(5),
+        Query::eq("name", "say \"hi\"\n")
+            .and(!Query::between("age", 18i32, 65i32))
+            .order_by("score", Direction::Desc),
+        Query::is_in("age", [1i32, 2, 3])
+            .or(Query::not_in("name", ["x", "y"]).and(Query::lte("score", -1.5f64)))
+            .or(Query::contains_ignore_case("name", "ZZ"))
+            .offset(3),
+        Query::eq("is_active", false).or(Query::Or(vec![])).into(),
+    ];
+
+    for query in queries {
+        let text = query.to_string();
+        let parsed = QueryParser::new(&schema, &text)
+            .unwrap()
+            .parse()
+            .unwrap()
+            .bind(&[])
+            .unwrap();
+        assert_eq!(parsed.to_string(), text);
+    }
+}
+
+#[test]
+fn test_text_query_errors() {
+    let db = people();
+
+    for text in [
+        "age > 30 LIMIT",
+        "age > 30 LIMIT -1",
+        "age > 30 ORDER BY missing",
+        "age > 30 name = 'x'",
+        "(age > 30",
+        "age > $1",
+        "age >= 1.5",
+    ] {
+        assert!(
+            matches!(
+                db.query("people", text),
+                Err(DatabaseError::InvalidQuery(_))
+            ),
+            "{} should not parse",
+            text
+        );
+    }
+    assert!(db.query("missing", "age > 1").is_err());
+}
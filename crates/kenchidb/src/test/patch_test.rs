@@ -0,0 +1,65 @@
+use crate::{
+    common::DatabaseError, database::Collection, define_schema, query::DocumentPatch, schema::Value,
+};
+
+define_schema! {
+    Counter {
+        label: string,
+        hits: long,
+        note: string?,
+    }
+}
+
+fn counters() -> Collection {
+    let mut counters = Collection::new(Counter::schema());
+    counters
+        .insert(
+            Counter::create()
+                .set("label", "home")
+                .set("hits", 41i64)
+                .set("note", "draft")
+                .build(),
+        )
+        .unwrap();
+    counters
+}
+
+#[test]
+fn test_patch_operations() {
+    let mut counters = counters();
+
+    let patch = DocumentPatch::new()
+        .increment("hits", 1i64)
+        .increment("hits", 10i32)
+        .set("label", "index")
+        .unset("note");
+    counters.patch(1, &patch).unwrap();
+
+    let document = counters.find_by_id(1).unwrap();
+    assert_eq!(document.get("hits"), Some(&Value::Long(52)));
+    assert_eq!(document.get("label"), Some(&Value::String("index".into())));
+    assert_eq!(document.get("note"), None);
+}
+
+#[test]
+fn test_invalid_patch_leaves_document_unchanged() {
+    let mut counters = counters();
+
+    for patch in [
+        DocumentPatch::new().set("hits", 1i64).unset("label"),
+        DocumentPatch::new().set("hits", "many"),
+        DocumentPatch::new().increment("label", 1i32),
+        DocumentPatch::new().set("unknown", 1i32),
+    ] {
+        assert!(counters.patch(1, &patch).is_err());
+    }
+
+    let document = counters.find_by_id(1).unwrap();
+    assert_eq!(document.get("hits"), Some(&Value::Long(41)));
+    assert_eq!(document.get("label"), Some(&Value::String("home".into())));
+
+    assert!(matches!(
+        counters.patch(7, &DocumentPatch::new()),
+        Err(DatabaseError::DocumentNotFound(7))
+    ));
+}
@@ -0,0 +1,129 @@
+use crate::{
+    database::Collection,
+    define_schema,
+    query::{AccessPath, Direction, Query},
+    schema::Value,
+};
+
+define_schema! {
+    Reading {
+        sensor: string,
+        seq: int,
+        value: double,
+    }
+}
+
+fn readings(primary_key: bool) -> Collection {
+    let schema = match primary_key {
+        true => Reading::schema().with_primary_key(&["sensor", "seq"]),
+        false => Reading::schema(),
+    };
+    let mut readings = Collection::new(schema);
+    for sensor in ["a", "ab", "b"] {
+        for seq in -3..5 {
+            readings
+                .insert(
+                    Reading::create()
+                        .set("sensor", sensor)
+                        .set("seq", seq)
+                        .set("value", seq as f64 * 1.5)
+                        .build(),
+                )
+                .unwrap();
+        }
+    }
+    readings
+}
+
+fn ids(collection: &Collection, query: &Query) -> Vec<u64> {
+    let mut ids: Vec<u64> = collection
+        .find_where(query)
+        .unwrap()
+        .iter()
+        .map(|doc| doc.id)
+        .collect();
+    ids.sort();
+    ids
+}
+
+#[test]
+fn test_planner_selects_primary_key() {
+    let readings = readings(true);
+
+    let plan = readings.explain(Query::gt("value", 1.0f64)).unwrap();
+    assert!(matches!(plan.access, AccessPath::FullScan));
+
+    let plan = readings
+        .explain(Query::eq("sensor", "ab").and(Query::gte("seq", 0i32)))
+        .unwrap();
+    assert!(matches!(&plan.access, AccessPath::PrimaryKey { prefix, .. } if prefix.len() == 1));
+    assert_eq!(
+        plan.explain(),
+        "Primary key range scan: sensor = \"ab\", seq >= 0\n\
+         Filter: sensor = \"ab\" AND seq >= 0\n"
+    );
+
+    let plan = readings
+        .explain(
+            Query::eq("seq", 2i32)
+                .and(Query::eq("sensor", "a"))
+                .order_by("value", Direction::Desc)
+                .limit(1),
+        )
+        .unwrap();
+    assert_eq!(
+        plan.explain(),
+        "Primary key lookup: sensor = \"a\", seq = 2\n\
+         Filter: seq = 2 AND sensor = \"a\"\n\
+         Sort: value Desc, top 1 kept in a heap\n\
+         Limit: 1\n"
+    );
+
+    // Disjunctions are not used for index selection
+    let plan = readings
+        .explain(Query::eq("sensor", "a").or(Query::eq("sensor", "b")))
+        .unwrap();
+    assert!(matches!(plan.access, AccessPath::FullScan));
+}
+
+#[test]
+fn test_index_scan_matches_full_scan() {
+    let indexed = readings(true);
+    let scanned = readings(false);
+
+    let queries = [
+        Query::eq("sensor", "a"),
+        Query::eq("sensor", "ab").and(Query::gt("seq", -1i32)),
+        Query::eq("sensor", "a").and(Query::lt("seq", 0i32)),
+        Query::eq("sensor", "b").and(Query::between("seq", -2i32, 2i32)),
+        Query::eq("sensor", "a")
+            .and(Query::lte("seq", 1i32))
+            .and(Query::gt("value", -3.0f64)),
+        Query::eq("sensor", "b").and(Query::eq("seq", 4i32)),
+        Query::eq("sensor", "c"),
+        Query::gte("sensor", "ab").and(Query::lt("sensor", "b")),
+        !Query::eq("sensor", "a"),
+    ];
+
+    for query in &queries {
+        assert_eq!(ids(&indexed, query), ids(&scanned, query), "{}", query);
+    }
+
+    let first = indexed
+        .find_one_where(Query::eq("sensor", "b").and(Query::gte("seq", 3i32)))
+        .unwrap()
+        .unwrap();
+    assert_eq!(first.get("seq"), Some(&Value::Int(3)));
+}
+
+#[test]
+fn test_query_display() {
+    let query = Query::eq("name", "o\"k")
+        .and(Query::gt("age", 3i32).or(Query::is_in("tag", [1.5f64, 2.0])))
+        .and(!Query::like_ignore_case("name", "a%"));
+    assert_eq!(
+        query.to_string(),
+        "name = \"o\\\"k\" AND (age > 3 OR tag IN (1.5, 2.0)) AND NOT (name LIKE \"a%\" IGNORE CASE)"
+    );
+    assert_eq!(Query::all().to_string(), "TRUE");
+}
@@ -0,0 +1,221 @@
+use std::thread;
+
+use crate::{
+    common::DatabaseError, database::Database, define_schema, index::IndexOptions,
+    integration::DatabasePool, session::Session,
+};
+
+define_schema! {
+    Visit {
+        page: string,
+    }
+}
+
+fn pool() -> DatabasePool {
+    let mut db = Database::new();
+    db.create_collection("visits".to_string(), Visit::schema())
+        .unwrap();
+    DatabasePool::new(db)
+}
+
+fn visit_count(session: &Session) -> usize {
+    session.find_all().len()
+}
+
+#[test]
+fn test_sessions_commit_from_many_threads() {
+    let pool = pool();
+    let workers: Vec<_> = (0..4)
+        .map(|worker| {
+            let pool = pool.clone();
+            thread::spawn(move || {
+                let mut session = pool.session("visits").unwrap();
+                for page in 0..5 {
+                    let visit = Visit::create()
+                        .set("page", format!("/{}/{}", worker, page))
+                        .build();
+                    session.insert(visit).unwrap();
+                }
+                // Reads of the session see its own writes
+                assert!(visit_count(&session) >= 5);
+                session.commit().unwrap()
+            })
+        })
+        .collect();
+
+    let mut ids: Vec<u64> = workers
+        .into_iter()
+        .flat_map(|worker| worker.join().unwrap())
+        .collect();
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), 20);
+    assert_eq!(
+        pool.read()
+            .unwrap()
+            .session("visits")
+            .unwrap()
+            .find_all()
+            .len(),
+        20
+    );
+}
+
+#[test]
+fn test_dropped_session_discards_writes() {
+    let pool = pool();
+    let mut session = pool.session("visits").unwrap();
+    session
+        .insert(Visit::create().set("page", "/").build())
+        .unwrap();
+    assert_eq!(session.collection_name(), "visits");
+    drop(session);
+    assert_eq!(pool.session("visits").unwrap().pending_writes(), 0);
+    assert!(
+        pool.read()
+            .unwrap()
+            .session("visits")
+            .unwrap()
+            .find_all()
+            .is_empty()
+    );
+
+    assert!(matches!(
+        pool.session("pages"),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+
+    // The collection is gone by the time the session commits
+    let session = pool.session("visits").unwrap();
+    *pool.write().unwrap() = Database::new();
+    assert!(matches!(
+        session.commit(),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+}
+
+#[cfg(feature = "axum")]
+#[test]
+fn test_session_extractor() {
+    use std::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    use axum::{extract::FromRequestParts, http::Request};
+
+    use crate::integration::extract::{DbSession, SessionCollection};
+
+    struct Visits;
+
+    impl SessionCollection for Visits {
+        const NAME: &'static str = "visits";
+    }
+
+    struct Pages;
+
+    impl SessionCollection for Pages {
+        const NAME: &'static str = "pages";
+    }
+
+    // The extractors never wait, a single poll completes them
+    fn ready<F: Future>(future: F) -> F::Output {
+        match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(output) => output,
+            Poll::Pending => unreachable!(),
+        }
+    }
+
+    let pool = pool();
+    let (mut parts, _) = Request::new(()).into_parts();
+
+    let mut visits = ready(DbSession::<Visits>::from_request_parts(&mut parts, &pool)).unwrap();
+    visits
+        .insert(Visit::create().set("page", "/").build())
+        .unwrap();
+    visits.into_inner().commit().unwrap();
+    assert_eq!(
+        pool.read()
+            .unwrap()
+            .session("visits")
+            .unwrap()
+            .find_all()
+            .len(),
+        1
+    );
+
+    let rejection = ready(DbSession::<Pages>::from_request_parts(&mut parts, &pool));
+    assert!(
+        matches!(rejection, Err(rejection) if matches!(rejection.0, DatabaseError::InvalidQuery(_)))
+    );
+}
+
+#[test]
+fn test_pool_builds_index_in_batches() {
+    let pool = pool();
+    let mut session = pool.session("visits").unwrap();
+    for page in 0..50 {
+        let visit = Visit::create()
+            .set("page", format!("/{}", page % 10))
+            .build();
+        session.insert(visit).unwrap();
+    }
+    session.commit().unwrap();
+
+    pool.build_index("visits", "page", IndexOptions::default(), 8)
+        .unwrap();
+    let mut db = pool.write().unwrap();
+    let visits = db.collection("visits").unwrap();
+    assert_eq!(visits.index("page").unwrap().len(), 50);
+    drop(db);
+
+    assert!(matches!(
+        pool.build_index("visits", "page", IndexOptions::default().unique(), 8),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+    assert!(matches!(
+        pool.build_index("pages", "page", IndexOptions::default(), 8),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+}
+
+#[test]
+fn test_panic_with_exclusive_access_poisons_pool() {
+    let pool = pool();
+
+    // A session panics without the lock, the pool stays usable
+    let worker = pool.clone();
+    let result = thread::spawn(move || {
+        let mut session = worker.session("visits").unwrap();
+        session
+            .insert(Visit::create().set("page", "/").build())
+            .unwrap();
+        panic!("handler failed");
+    })
+    .join();
+    assert!(result.is_err());
+    assert!(
+        pool.read()
+            .unwrap()
+            .session("visits")
+            .unwrap()
+            .find_all()
+            .is_empty()
+    );
+
+    // A panic with the lock held exclusively may leave the database half updated
+    let worker = pool.clone();
+    let result = thread::spawn(move || {
+        let _database = worker.write().unwrap();
+        panic!("handler failed");
+    })
+    .join();
+    assert!(result.is_err());
+    assert!(matches!(pool.read(), Err(DatabaseError::Poisoned)));
+    assert!(matches!(pool.write(), Err(DatabaseError::Poisoned)));
+    assert!(matches!(
+        pool.session("visits"),
+        Err(DatabaseError::Poisoned)
+    ));
+}
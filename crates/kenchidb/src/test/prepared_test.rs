@@ -0,0 +1,116 @@
+use crate::{common::DatabaseError, database::Collection, define_schema, schema::Value};
+
+define_schema! {
+    Product {
+        name: string,
+        price: double,
+        stock: int,
+    }
+}
+
+fn products() -> Collection {
+    let mut products = Collection::new(Product::schema());
+    for (name, price, stock) in [
+        ("apple", 0.5f64, 100i32),
+        ("banana", 0.25f64, 0i32),
+        ("cherry", 4.0f64, 20i32),
+    ] {
+        products
+            .insert(
+                Product::create()
+                    .set("name", name)
+                    .set("price", price)
+                    .set("stock", stock)
+                    .build(),
+            )
+            .unwrap();
+    }
+    products
+}
+
+fn names(documents: Vec<&crate::schema::Document>) -> Vec<String> {
+    let mut names: Vec<String> = documents
+        .iter()
+        .map(|doc| match doc.get("name") {
+            Some(Value::String(name)) => name.clone(),
+            other => panic!("unexpected name {:?}", other),
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_prepared_query_binds_parameters() {
+    let products = products();
+    let prepared = products
+        .prepare("stock > $1 AND (price <= $2 OR name IN (\"cherry\", $3))")
+        .unwrap();
+    assert_eq!(prepared.parameter_count(), 3);
+
+    // Integer parameters widen to the double price field
+    let cheap = products
+        .find_prepared(&prepared, &[0i32.into(), 1i64.into(), "none".into()])
+        .unwrap();
+    assert_eq!(names(cheap), ["apple", "cherry"]);
+
+    let stocked = products
+        .find_prepared(&prepared, &[50i32.into(), 0.1f64.into(), "apple".into()])
+        .unwrap();
+    assert_eq!(names(stocked), ["apple"]);
+}
+
+#[test]
+fn test_prepared_query_parses_display_syntax() {
+    let products = products();
+    let prepared = products
+        .prepare("NOT (name STARTS WITH 'B' IGNORE CASE) AND price BETWEEN 0.1 AND $1")
+        .unwrap();
+    assert_eq!(
+        prepared.bind(&[1.0f64.into()]).unwrap().to_string(),
+        "NOT (name STARTS WITH \"B\" IGNORE CASE) AND price BETWEEN 0.1 AND 1.0"
+    );
+    let found = products
+        .find_prepared(&prepared, &[10.0f64.into()])
+        .unwrap();
+    assert_eq!(names(found), ["apple", "cherry"]);
+}
+
+#[test]
+fn test_prepared_query_errors() {
+    let products = products();
+
+    for template in [
+        "stock >",
+        "missing = 1",
+        "stock = \"many\"",
+        "stock = 1 AND",
+        "name LIKE $1 AND stock = $1",
+        "stock = $2",
+        "price LIKE \"1%\"",
+        "name = \"unterminated",
+    ] {
+        assert!(
+            matches!(
+                products.prepare(template),
+                Err(DatabaseError::InvalidQuery(_))
+            ),
+            "{} should not parse",
+            template
+        );
+    }
+
+    let prepared = products.prepare("stock = $1").unwrap();
+    assert!(matches!(
+        products.find_prepared(&prepared, &[]),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+    assert!(matches!(
+        products.find_prepared(&prepared, &["ten".into()]),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+    assert!(matches!(
+        products.find_prepared(&prepared, &[Value::Long(i64::MAX)]),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+}
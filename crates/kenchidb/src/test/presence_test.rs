@@ -0,0 +1,154 @@
+use std::fs;
+
+use crate::{
+    common::DatabaseError,
+    database::Collection,
+    define_schema,
+    query::{ByteReader, FindQuery, Query, QueryParser, decode_find_query, encode_find_query},
+    schema::{Document, Value},
+    storage::wal::wal_path,
+};
+
+define_schema! {
+    Contact {
+        name: string,
+        phone: string?,
+    }
+}
+
+/// Contacts with a phone, an explicit null phone, no phone and an extra `nickname` field
+fn contacts() -> Collection {
+    let mut collection = Collection::new(Contact::schema().lenient());
+    let mut nickname = Contact::create().set("name", "dave").build();
+    nickname.set("nickname", "d");
+
+    for document in [
+        Contact::create()
+            .set("name", "alice")
+            .set("phone", "555")
+            .build(),
+        Contact::create()
+            .set("name", "bob")
+            .set("phone", Value::Null)
+            .build(),
+        Contact::create().set("name", "carol").build(),
+        nickname,
+    ] {
+        collection.insert(document).unwrap();
+    }
+    collection
+}
+
+fn names(documents: Vec<&Document>) -> Vec<String> {
+    let mut names: Vec<String> = documents
+        .iter()
+        .map(|document| match document.get("name") {
+            Some(Value::String(name)) => name.clone(),
+            _ => unreachable!(),
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_exists_is_distinct_from_is_null() {
+    let collection = contacts();
+    let find = |query: Query| names(collection.find_where(query).unwrap());
+
+    assert_eq!(find(Query::exists("phone")), vec!["alice", "bob"]);
+    assert_eq!(find(Query::not_exists("phone")), vec!["carol", "dave"]);
+    assert_eq!(find(Query::is_null("phone")), vec!["bob", "carol", "dave"]);
+    assert_eq!(
+        find(Query::exists("phone").and(Query::is_null("phone"))),
+        vec!["bob"]
+    );
+
+    // Undeclared fields of a lenient schema
+    assert_eq!(find(Query::exists("nickname")), vec!["dave"]);
+    assert_eq!(find(Query::not_exists("nickname")).len(), 3);
+
+    // Null values never match comparisons
+    assert_eq!(find(Query::ne("phone", "555")), Vec::<String>::new());
+    assert_eq!(
+        find(Contact::query().phone_exists().name_ne("alice").build()),
+        vec!["bob"]
+    );
+}
+
+#[test]
+fn test_presence_in_query_text() {
+    let collection = contacts();
+    let find = |text: &str| {
+        let prepared = collection.prepare(text).unwrap();
+        names(collection.find_prepared(&prepared, &[]).unwrap())
+    };
+
+    assert_eq!(find("phone EXISTS"), vec!["alice", "bob"]);
+    assert_eq!(find("phone not exists"), vec!["carol", "dave"]);
+    assert_eq!(find("phone IS NULL"), vec!["bob", "carol", "dave"]);
+    assert_eq!(find("phone IS NOT NULL"), vec!["alice"]);
+    assert_eq!(find("nickname EXISTS"), vec!["dave"]);
+    assert_eq!(find("name NOT IN (\"alice\")").len(), 3);
+
+    // Displayed queries parse back, and survive the catalog encoding
+    let query = FindQuery::new(Query::exists("phone").and(!Query::is_null("nickname")));
+    let text = query.to_string();
+    assert_eq!(text, "phone EXISTS AND NOT (nickname IS NULL)");
+    let schema = Contact::schema().lenient();
+    let parsed = QueryParser::new(&schema, &text).unwrap().parse().unwrap();
+    assert_eq!(parsed.bind(&[]).unwrap().to_string(), text);
+
+    let mut bytes = Vec::new();
+    encode_find_query(&query, &mut bytes).unwrap();
+    let decoded = decode_find_query(&mut ByteReader::new(&bytes)).unwrap();
+    assert_eq!(decoded.to_string(), text);
+}
+
+#[test]
+fn test_strict_schemas_reject_undeclared_fields() {
+    let mut collection = Collection::new(Contact::schema());
+    let mut document = Contact::create().set("name", "erin").build();
+    document.set("nickname", "e");
+    assert!(matches!(
+        collection.insert(document),
+        Err(DatabaseError::SchemaViolation(_))
+    ));
+    assert!(matches!(
+        collection.find_where(Query::exists("nickname")),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+    assert!(collection.prepare("nickname EXISTS").is_err());
+
+    // Explicit nulls are only allowed in nullable fields
+    let document = Contact::create().set("name", Value::Null).build();
+    assert!(matches!(
+        collection.insert(document),
+        Err(DatabaseError::SchemaViolation(_))
+    ));
+}
+
+#[test]
+fn test_explicit_null_is_persisted() {
+    let path = std::env::temp_dir().join(format!("kenchidb_presence_{}.db", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(wal_path(&path));
+
+    let mut collection = Collection::with_file(Contact::schema(), &path).unwrap();
+    let document = Contact::create()
+        .set("name", "bob")
+        .set("phone", Value::Null)
+        .build();
+    let id = collection.insert(document).unwrap();
+    drop(collection);
+
+    let reopened = Collection::with_file(Contact::schema(), &path).unwrap();
+    assert_eq!(
+        reopened.find_by_id(id).unwrap().get("phone"),
+        Some(&Value::Null)
+    );
+    assert_eq!(reopened.count_where(Query::exists("phone")).unwrap(), 1);
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(wal_path(&path)).unwrap();
+}
@@ -0,0 +1,91 @@
+use std::fs;
+
+use crate::{
+    define_schema,
+    schema::Value,
+    storage::paged_collection::{PagedCollection, free_space_map_path, primary_key_index_path},
+};
+
+define_schema! {
+    Entry {
+        label: string,
+    }
+}
+
+fn collection_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("kenchidb_{}_{}.pages", name, std::process::id()))
+}
+
+fn label(collection: &mut PagedCollection, id: u64) -> Option<Value> {
+    collection
+        .find_by_id(id)
+        .unwrap()
+        .and_then(|document| document.get("label").cloned())
+}
+
+#[test]
+fn test_primary_key_index_survives_reopen() {
+    let path = collection_path("primary_key");
+    let index_path = primary_key_index_path(&path);
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(&index_path);
+
+    let mut entries = PagedCollection::new(Entry::schema(), 1, &path).unwrap();
+    let text = "entry".repeat(30);
+    for i in 0..100 {
+        let label = format!("{}{}", text, i);
+        entries
+            .insert(Entry::create().set("label", label.as_str()).build())
+            .unwrap();
+    }
+    for id in (2..=100).step_by(3) {
+        entries.delete(id).unwrap();
+    }
+    entries.flush().unwrap();
+    let ids = entries.ids();
+    drop(entries);
+
+    // Loaded from the saved index, ids keep counting after the last one
+    let mut entries = PagedCollection::new(Entry::schema(), 1, &path).unwrap();
+    assert_eq!(entries.ids(), ids);
+    assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(ids.len(), 67);
+    assert_eq!(
+        label(&mut entries, 100),
+        Some(Value::String(format!("{}99", text)))
+    );
+    assert!(label(&mut entries, 2).is_none());
+    let id = entries
+        .insert(Entry::create().set("label", "after").build())
+        .unwrap();
+    assert_eq!(id, 101);
+
+    // The first write drops the saved index, the next open rebuilds it from the pages
+    assert!(!index_path.exists());
+    entries.delete(1).unwrap();
+    drop(entries);
+
+    let mut entries = PagedCollection::new(Entry::schema(), 1, &path).unwrap();
+    assert_eq!(entries.ids().len(), 67);
+    assert_eq!(entries.ids()[0], 3);
+    assert_eq!(
+        label(&mut entries, 101),
+        Some(Value::String("after".to_string()))
+    );
+    assert_eq!(entries.next_id, 102);
+
+    // A damaged index file is rebuilt as well
+    entries.flush().unwrap();
+    drop(entries);
+    let mut bytes = fs::read(&index_path).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xFF;
+    fs::write(&index_path, bytes).unwrap();
+    let mut entries = PagedCollection::new(Entry::schema(), 1, &path).unwrap();
+    assert_eq!(entries.ids().len(), 67);
+    assert!(label(&mut entries, 4).is_some());
+
+    fs::remove_file(&path).unwrap();
+    let _ = fs::remove_file(&index_path);
+    let _ = fs::remove_file(free_space_map_path(&path));
+}
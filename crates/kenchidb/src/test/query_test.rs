@@ -0,0 +1,329 @@
+use crate::{
+    common::DatabaseError,
+    database::Collection,
+    define_schema,
+    query::{Direction, FindQuery, Query, and, not, or},
+    schema::Value,
+};
+
+define_schema! {
+    Person {
+        name: string,
+        age: int,
+        is_active: boolean,
+    }
+}
+
+fn people() -> Collection {
+    let mut people = Collection::new(Person::schema());
+    for (name, age, is_active) in [
+        ("alice", 28, true),
+        ("bob", 35, false),
+        ("carol", 22, true),
+        ("dave", 41, true),
+    ] {
+        people
+            .insert(
+                Person::create()
+                    .set("name", name)
+                    .set("age", age)
+                    .set("is_active", is_active)
+                    .build(),
+            )
+            .unwrap();
+    }
+    people
+}
+
+fn names(people: &Collection, query: &Query) -> Vec<String> {
+    let mut names: Vec<String> = people
+        .find_where(query)
+        .unwrap()
+        .iter()
+        .map(|doc| format!("{:?}", doc.get("name").unwrap()))
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_and_or_not() {
+    let people = people();
+
+    let active_adults = Query::eq("is_active", true).and(Query::gt("age", 25i32));
+    assert_eq!(names(&people, &active_adults).len(), 2);
+
+    let young_or_inactive = Query::lt("age", 25i32).or(Query::eq("is_active", false));
+    assert_eq!(names(&people, &young_or_inactive).len(), 2);
+
+    let not_active = !Query::eq("is_active", true);
+    assert_eq!(names(&people, &not_active).len(), 1);
+}
+
+#[test]
+fn test_nested_groups() {
+    let people = people();
+
+    // active AND (age < 25 OR age > 40)
+    let query = and([
+        Query::eq("is_active", true),
+        or([Query::lt("age", 25i32), Query::gt("age", 40i32)]),
+    ]);
+    assert_eq!(names(&people, &query).len(), 2);
+
+    assert_eq!(names(&people, &not(query)).len(), 2);
+    assert_eq!(names(&people, &Query::all()).len(), 4);
+    assert!(names(&people, &or([])).is_empty());
+}
+
+#[test]
+fn test_range_and_set_operators() {
+    let people = people();
+
+    assert_eq!(
+        names(&people, &Query::between("age", 22i32, 35i32)).len(),
+        3
+    );
+    assert_eq!(names(&people, &Query::gte("age", 35i32)).len(), 2);
+    assert_eq!(names(&people, &Query::lte("age", 28i32)).len(), 2);
+    assert_eq!(
+        names(&people, &Query::is_in("name", ["alice", "dave", "zoe"])),
+        names(
+            &people,
+            &or([Query::eq("name", "alice"), Query::eq("name", "dave")])
+        )
+    );
+    assert_eq!(names(&people, &Query::not_in("name", ["alice"])).len(), 3);
+    assert_eq!(names(&people, &Query::between("name", "b", "d")).len(), 2);
+}
+
+#[test]
+fn test_string_pattern_operators() {
+    let people = people();
+
+    assert_eq!(names(&people, &Query::starts_with("name", "a")).len(), 1);
+    assert!(names(&people, &Query::starts_with("name", "A")).is_empty());
+    assert_eq!(
+        names(&people, &Query::starts_with_ignore_case("name", "A")).len(),
+        1
+    );
+    assert_eq!(names(&people, &Query::ends_with("name", "e")).len(), 2);
+    assert_eq!(names(&people, &Query::contains("name", "ar")).len(), 1);
+    assert_eq!(
+        names(&people, &Query::contains_ignore_case("name", "AR")).len(),
+        1
+    );
+    assert_eq!(names(&people, &Query::like("name", "_a%")).len(), 2);
+    assert_eq!(names(&people, &Query::like("name", "%o%")).len(), 2);
+    assert_eq!(names(&people, &Query::like("name", "bob")).len(), 1);
+    assert_eq!(names(&people, &Query::like("name", "b_")).len(), 0);
+    assert_eq!(
+        names(&people, &Query::like_ignore_case("name", "%L%E")).len(),
+        1
+    );
+}
+
+#[test]
+fn test_like_with_wildcard_characters_in_values() {
+    let mut people = Collection::new(Person::schema());
+    for (age, name) in [(1, "50%x"), (2, "%a"), (3, "a_b"), (4, "axb"), (5, "50")] {
+        people
+            .insert(
+                Person::create()
+                    .set("name", name)
+                    .set("age", age)
+                    .set("is_active", true)
+                    .build(),
+            )
+            .unwrap();
+    }
+    let ages = |pattern: &str| -> Vec<i32> {
+        let mut ages: Vec<i32> = people
+            .find_where(Query::like("name", pattern))
+            .unwrap()
+            .iter()
+            .map(|doc| match doc.get("age") {
+                Some(Value::Int(age)) => *age,
+                _ => panic!("age is not an int"),
+            })
+            .collect();
+        ages.sort();
+        ages
+    };
+
+    // Trailing `%` after a literal match, the value holds a `%` where the pattern has one
+    assert_eq!(ages("50%"), [1, 5]);
+    assert_eq!(ages("%"), [1, 2, 3, 4, 5]);
+    assert_eq!(ages("%a"), [2]);
+    assert_eq!(ages("%%x"), [1]);
+    assert_eq!(ages("5_%x"), [1]);
+    // `_` in the pattern matches a literal `_` as well as any other character
+    assert_eq!(ages("a_b"), [3, 4]);
+    assert_eq!(ages("%_b"), [3, 4]);
+    assert!(ages("a_b_").is_empty());
+}
+
+#[test]
+fn test_schema_violating_queries() {
+    let people = people();
+
+    let unknown_field = people.find_where(Query::eq("email", "a@b.c"));
+    assert!(
+        matches!(unknown_field, Err(DatabaseError::InvalidQuery(msg)) if msg.contains("email"))
+    );
+
+    let wrong_type = people.find_where(Query::gt("age", 30i64));
+    assert!(matches!(wrong_type, Err(DatabaseError::InvalidQuery(msg)) if msg.contains("age")));
+
+    let nested = Query::eq("is_active", true).and(!Query::is_in("name", [1i32, 2i32]));
+    assert!(people.find_one_where(&nested).is_err());
+
+    assert!(people.find_where(Query::like("age", "%")).is_err());
+}
+
+#[test]
+fn test_order_by_and_pagination() {
+    let people = people();
+    let ages = |query: FindQuery| -> Vec<Value> {
+        people
+            .find_where(query)
+            .unwrap()
+            .iter()
+            .map(|doc| doc.get("age").unwrap().clone())
+            .collect()
+    };
+
+    assert_eq!(
+        ages(Query::all().order_by("age", Direction::Asc)),
+        vec![
+            Value::Int(22),
+            Value::Int(28),
+            Value::Int(35),
+            Value::Int(41)
+        ]
+    );
+    assert_eq!(
+        ages(Query::all().order_by("age", Direction::Desc).limit(2)),
+        vec![Value::Int(41), Value::Int(35)]
+    );
+    assert_eq!(
+        ages(
+            Query::eq("is_active", true)
+                .order_by("age", Direction::Asc)
+                .offset(1)
+                .limit(5)
+        ),
+        vec![Value::Int(28), Value::Int(41)]
+    );
+    assert_eq!(ages(Query::all().offset(3)).len(), 1);
+    assert_eq!(ages(Query::all().limit(0)).len(), 0);
+    assert!(
+        ages(
+            Query::all()
+                .order_by("age", Direction::Asc)
+                .offset(10)
+                .limit(1)
+        )
+        .is_empty()
+    );
+
+    assert!(
+        people
+            .find_where(Query::all().order_by("missing", Direction::Asc))
+            .is_err()
+    );
+}
+
+#[test]
+fn test_count_exists_and_find_one() {
+    let people = people();
+
+    assert_eq!(people.count_where(Query::all()).unwrap(), 4);
+    assert_eq!(
+        people.count_where(Query::all().offset(1).limit(2)).unwrap(),
+        2
+    );
+    assert_eq!(people.count_where(Query::all().offset(5)).unwrap(), 0);
+    assert_eq!(people.count_where(Query::eq("is_active", true)).unwrap(), 3);
+    assert_eq!(
+        people
+            .count_where(Query::eq("is_active", true).limit(2))
+            .unwrap(),
+        2
+    );
+
+    assert!(people.exists_where(Query::gt("age", 40)).unwrap());
+    assert!(!people.exists_where(Query::gt("age", 50)).unwrap());
+    assert!(
+        people
+            .exists_where(Query::gt("age", 30).limit(0))
+            .is_ok_and(|found| !found)
+    );
+    assert!(people.count_where(Query::eq("missing", 1)).is_err());
+
+    let age = |query: FindQuery| {
+        people
+            .find_one_where(query)
+            .unwrap()
+            .map(|doc| doc.get("age").unwrap().clone())
+    };
+    assert_eq!(
+        age(Query::all().order_by("age", Direction::Desc)),
+        Some(Value::Int(41))
+    );
+    assert_eq!(
+        age(Query::eq("is_active", true)
+            .order_by("age", Direction::Asc)
+            .offset(1)),
+        Some(Value::Int(28))
+    );
+    assert_eq!(age(Query::gt("age", 50).into()), None);
+}
+
+#[test]
+fn test_distinct_values() {
+    let people = people();
+
+    assert_eq!(
+        people.distinct("is_active", None).unwrap(),
+        vec![Value::Boolean(false), Value::Boolean(true)]
+    );
+    assert_eq!(
+        people
+            .distinct("age", Some(Query::eq("is_active", true)))
+            .unwrap(),
+        vec![Value::Int(22), Value::Int(28), Value::Int(41)]
+    );
+    assert!(
+        people
+            .distinct("name", Some(Query::gt("age", 50)))
+            .unwrap()
+            .is_empty()
+    );
+    assert!(people.distinct("missing", None).is_err());
+}
+
+#[test]
+fn test_typed_query_builder() {
+    let people = people();
+
+    let query = Person::query()
+        .is_active_eq(true)
+        .age_gt(25)
+        .or(Person::query().name_starts_with("b"));
+    assert_eq!(
+        names(&people, &query.build()),
+        ["String(\"alice\")", "String(\"bob\")", "String(\"dave\")"]
+    );
+
+    let query = Person::query()
+        .age_between(20, 30)
+        .name_not_in(&["carol"])
+        .build();
+    assert_eq!(names(&people, &query), ["String(\"alice\")"]);
+
+    let young = people
+        .find_where(Person::query().age_lte(28).is_active_ne(false))
+        .unwrap();
+    assert_eq!(young.len(), 2);
+}
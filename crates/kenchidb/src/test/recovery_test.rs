@@ -0,0 +1,143 @@
+use std::{fs, ops::ControlFlow, time::Duration};
+
+use crate::{
+    common::{DatabaseError, RecoveryProgress},
+    database::Collection,
+    define_schema,
+    storage::wal::wal_path,
+};
+
+define_schema! {
+    Event {
+        kind: string,
+        payload: string,
+    }
+}
+
+fn event_file(name: &str, count: usize) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("kenchidb_{}_{}.data", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(wal_path(&path));
+
+    let mut events = Collection::new(Event::schema());
+    for i in 0..count {
+        let event = Event::create()
+            .set("kind", format!("kind-{}", i % 7))
+            .set("payload", "x".repeat(200))
+            .build();
+        events.insert(event).unwrap();
+    }
+    // One write of the whole collection instead of one per insert
+    events.path = Some(path.clone());
+    events.file = Some(fs::File::create(&path).unwrap());
+    events.save_to_file().unwrap();
+    path
+}
+
+#[test]
+fn test_open_reports_progress() {
+    let path = event_file("recovery_progress", 2000);
+    let size = fs::metadata(&path).unwrap().len();
+
+    let mut reports: Vec<RecoveryProgress> = Vec::new();
+    let events = Collection::with_file_progress(Event::schema(), &path, |progress| {
+        reports.push(*progress);
+        ControlFlow::Continue(())
+    })
+    .unwrap();
+    assert_eq!(events.find_all().len(), 2000);
+
+    // Every 64 KiB and once at the end
+    assert!(reports.len() > 2, "{} reports", reports.len());
+    assert!(
+        reports
+            .windows(2)
+            .all(|pair| pair[0].bytes_processed < pair[1].bytes_processed)
+    );
+    let last = reports.last().unwrap();
+    assert_eq!((last.bytes_processed, last.bytes_total), (size, size));
+    assert_eq!(last.fraction(), 1.0);
+    assert_eq!(last.eta(), Some(Duration::ZERO));
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(wal_path(&path)).unwrap();
+}
+
+#[test]
+fn test_open_can_be_cancelled() {
+    let path = event_file("recovery_cancel", 2000);
+    let before = fs::read(&path).unwrap();
+
+    let mut calls = 0;
+    let result = Collection::with_file_progress(Event::schema(), &path, |progress| {
+        calls += 1;
+        if progress.fraction() > 0.2 {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    assert!(matches!(result, Err(DatabaseError::Cancelled)));
+    assert!(calls >= 1);
+
+    // The file is untouched and opens normally afterwards
+    assert_eq!(fs::read(&path).unwrap(), before);
+    let events = Collection::with_file(Event::schema(), &path).unwrap();
+    assert_eq!(events.find_all().len(), 2000);
+
+    // An empty file still gets its final report
+    let empty = std::env::temp_dir().join(format!(
+        "kenchidb_recovery_empty_{}.data",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&empty);
+    let _ = fs::remove_file(wal_path(&empty));
+    let mut reports = Vec::new();
+    Collection::with_file_progress(Event::schema(), &empty, |progress| {
+        reports.push(*progress);
+        ControlFlow::Continue(())
+    })
+    .unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].fraction(), 1.0);
+    assert_eq!(reports[0].eta(), None);
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(wal_path(&path)).unwrap();
+    fs::remove_file(&empty).unwrap();
+    fs::remove_file(wal_path(&empty)).unwrap();
+}
+
+#[test]
+fn test_checkpoint_replaces_the_file() {
+    let path = event_file("recovery_checkpoint", 50);
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = std::path::PathBuf::from(temporary);
+
+    let mut events = Collection::with_file(Event::schema(), &path).unwrap();
+    events.delete(1).unwrap();
+    events.checkpoint().unwrap();
+    assert!(!temporary.exists());
+
+    // Writes after the checkpoint go to the new file
+    let event = Event::create()
+        .set("kind", "late")
+        .set("payload", "y")
+        .build();
+    events.insert(event).unwrap();
+    events.checkpoint().unwrap();
+    drop(events);
+    let saved = fs::read(&path).unwrap();
+
+    // A crash in the middle of the next checkpoint leaves half a temporary file
+    fs::write(&temporary, &saved[..saved.len() / 2]).unwrap();
+    let events = Collection::with_file(Event::schema(), &path).unwrap();
+    assert_eq!(events.find_all().len(), 50);
+    assert!(events.find_by_id(1).is_none());
+    assert!(!temporary.exists());
+    assert_eq!(fs::read(&path).unwrap(), saved);
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(wal_path(&path)).unwrap();
+}
@@ -0,0 +1,126 @@
+use std::{fs, path::PathBuf};
+
+use crate::{
+    common::DatabaseError,
+    database::{Collection, Database},
+    define_schema,
+    query::Query,
+};
+
+define_schema! {
+    Ticket {
+        title: string,
+    }
+}
+
+fn directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("kenchidb_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+#[test]
+fn test_move_and_rename_database_files() {
+    let from = directory("relocate_from");
+    let to = directory("relocate_to");
+
+    let mut db = Database::with_catalog(from.join("tickets.catalog")).unwrap();
+    db.create_collection_with_file(
+        "tickets".to_string(),
+        Ticket::schema(),
+        from.join("tickets.data"),
+    )
+    .unwrap();
+    db.collection("tickets")
+        .unwrap()
+        .insert(Ticket::create().set("title", "first").build())
+        .unwrap();
+    db.create_view("all_tickets", "tickets", Query::all())
+        .unwrap();
+
+    db.move_to(&to).unwrap();
+    assert!(!from.join("tickets.catalog").exists());
+    assert!(!from.join("tickets.data").exists());
+    assert!(to.join("tickets.catalog").exists());
+
+    // Writes after the move go to the new files
+    db.collection("tickets")
+        .unwrap()
+        .insert(Ticket::create().set("title", "second").build())
+        .unwrap();
+    let reopened = Collection::with_file(Ticket::schema(), to.join("tickets.data")).unwrap();
+    assert_eq!(reopened.find_all().len(), 2);
+
+    // Collection files next to the catalog follow a rename
+    db.rename(from.join("renamed.catalog")).unwrap();
+    assert!(from.join("renamed.catalog").exists());
+    assert!(from.join("tickets.data").exists());
+    assert!(!to.join("tickets.catalog").exists());
+    assert_eq!(
+        Database::with_catalog(from.join("renamed.catalog"))
+            .unwrap()
+            .views()
+            .len(),
+        1
+    );
+
+    // Existing files are never overwritten
+    fs::write(to.join("tickets.data"), b"occupied").unwrap();
+    assert!(matches!(
+        db.move_to(&to),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+    assert!(from.join("renamed.catalog").exists());
+    assert!(!to.join("renamed.catalog").exists());
+
+    let _ = fs::remove_dir_all(&from);
+    let _ = fs::remove_dir_all(&to);
+}
+
+#[test]
+fn test_failed_move_keeps_database_in_place() {
+    let from = directory("relocate_failed_from");
+    let to = directory("relocate_failed_to");
+
+    let mut db = Database::with_catalog(from.join("tickets.catalog")).unwrap();
+    db.create_collection_with_file(
+        "tickets".to_string(),
+        Ticket::schema(),
+        from.join("tickets.data"),
+    )
+    .unwrap();
+    db.collection("tickets")
+        .unwrap()
+        .insert(Ticket::create().set("title", "first").build())
+        .unwrap();
+
+    // The catalog cannot be written at the target, after every file got its new name
+    fs::create_dir(to.join("tickets.catalog.tmp")).unwrap();
+    assert!(db.move_to(&to).is_err());
+    let left: Vec<_> = fs::read_dir(&to)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(left, vec!["tickets.catalog.tmp"]);
+
+    // The database still works on the old files
+    db.collection("tickets")
+        .unwrap()
+        .insert(Ticket::create().set("title", "second").build())
+        .unwrap();
+    drop(db);
+    let mut db = Database::open(from.join("tickets.catalog")).unwrap();
+    assert_eq!(db.collection("tickets").unwrap().find_all().len(), 2);
+
+    // Once moved, the catalog at the new location records the new paths
+    fs::remove_dir(to.join("tickets.catalog.tmp")).unwrap();
+    db.move_to(&to).unwrap();
+    drop(db);
+    assert_eq!(fs::read_dir(&from).unwrap().count(), 0);
+    let mut db = Database::open(to.join("tickets.catalog")).unwrap();
+    assert_eq!(db.collection("tickets").unwrap().find_all().len(), 2);
+
+    let _ = fs::remove_dir_all(&from);
+    let _ = fs::remove_dir_all(&to);
+}
@@ -0,0 +1,88 @@
+use crate::{
+    database::Collection,
+    define_schema,
+    query::{Direction, Query},
+};
+
+define_schema! {
+    Item {
+        name: string,
+        price: double,
+        stock: int?,
+    }
+}
+
+fn items() -> Collection {
+    let mut items = Collection::new(Item::schema());
+    items
+        .insert(
+            Item::create()
+                .set("name", "plain")
+                .set("price", 1.5f64)
+                .set("stock", 3i32)
+                .build(),
+        )
+        .unwrap();
+    items
+        .insert(
+            Item::create()
+                .set("name", "say \"hi\", bye\n")
+                .set("price", 2.0f64)
+                .build(),
+        )
+        .unwrap();
+    items
+}
+
+#[test]
+fn test_to_json_writer() {
+    let items = items();
+    let mut out = Vec::new();
+    items
+        .query(Query::all().order_by("price", Direction::Asc))
+        .unwrap()
+        .to_json_writer(&mut out)
+        .unwrap();
+
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        concat!(
+            r#"[{"_id":1,"name":"plain","price":1.5,"stock":3},"#,
+            r#"{"_id":2,"name":"say \"hi\", bye\n","price":2,"stock":null}]"#
+        )
+    );
+}
+
+#[test]
+fn test_to_csv_writer() {
+    let items = items();
+    let mut out = Vec::new();
+    items
+        .query(Query::all().order_by("price", Direction::Desc))
+        .unwrap()
+        .to_csv_writer(&mut out)
+        .unwrap();
+
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "_id,name,price,stock\r\n2,\"say \"\"hi\"\", bye\n\",2,\r\n1,plain,1.5,3\r\n"
+    );
+}
+
+#[test]
+fn test_query_result_streams_pages() {
+    let items = items();
+
+    let result = items.query(Query::all().offset(1)).unwrap();
+    assert_eq!(result.iter().count(), 1);
+
+    let mut out = Vec::new();
+    items
+        .query(Query::gt("price", 10.0f64))
+        .unwrap()
+        .to_json_writer(&mut out)
+        .unwrap();
+    assert_eq!(out, b"[]");
+
+    assert!(items.query(Query::gt("price", 10i32)).is_err());
+}
@@ -0,0 +1,140 @@
+use std::{collections::HashSet, fs};
+
+use crate::{
+    common::Random,
+    database::Collection,
+    define_schema,
+    query::{ByteReader, Direction, Query, decode_find_query, encode_find_query, reservoir_sample},
+    schema::Value,
+    storage::paged_collection::PagedCollection,
+};
+
+define_schema! {
+    Reading {
+        sensor: int,
+        value: double,
+    }
+}
+
+fn readings(count: i32) -> Collection {
+    let mut collection = Collection::new(Reading::schema());
+    for i in 0..count {
+        let reading = Reading::create()
+            .set("sensor", i % 4)
+            .set("value", i as f64)
+            .build();
+        collection.insert(reading).unwrap();
+    }
+    collection
+}
+
+#[test]
+fn test_reservoir_sample_is_uniform() {
+    let mut random = Random::with_seed(42);
+    let mut picked = [0u32; 10];
+    for _ in 0..10_000 {
+        for item in reservoir_sample(0..10usize, 3, &mut random) {
+            picked[item] += 1;
+        }
+    }
+
+    // Each item is expected in 3 of 10 samples, 3000 times
+    for count in picked {
+        assert!((2700..3300).contains(&count), "picked {:?}", picked);
+    }
+
+    assert_eq!(reservoir_sample(0..2, 5, &mut random).len(), 2);
+    assert!(reservoir_sample(0..5, 0, &mut random).is_empty());
+
+    // The same seed gives the same sample
+    let first = reservoir_sample(0..1000, 10, &mut Random::with_seed(7));
+    let second = reservoir_sample(0..1000, 10, &mut Random::with_seed(7));
+    assert_eq!(first, second);
+}
+
+#[test]
+fn test_collection_sample() {
+    let collection = readings(100);
+    let sample = collection.sample(10);
+    let ids: HashSet<u64> = sample.iter().map(|document| document.id).collect();
+    assert_eq!(ids.len(), 10);
+    assert!(ids.iter().all(|id| (1..=100).contains(id)));
+
+    assert_eq!(collection.sample(500).len(), 100);
+}
+
+#[test]
+fn test_sample_stage_in_queries() {
+    let collection = readings(100);
+
+    // Sampled from the matching documents, then sorted
+    let query = Query::eq("sensor", 1i32)
+        .sample(5)
+        .order_by("value", Direction::Asc);
+    let values: Vec<f64> = collection
+        .find_where(query.clone())
+        .unwrap()
+        .iter()
+        .map(|document| match document.get("value") {
+            Some(Value::Double(value)) => *value,
+            _ => unreachable!(),
+        })
+        .collect();
+    assert_eq!(values.len(), 5);
+    assert!(values.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(values.iter().all(|value| *value as i32 % 4 == 1));
+
+    assert_eq!(collection.count_where(query.clone().limit(3)).unwrap(), 3);
+    assert_eq!(
+        collection.count_where(Query::all().sample(1000)).unwrap(),
+        100
+    );
+    assert!(collection.find_one_where(query.clone()).unwrap().is_some());
+    assert_eq!(collection.query(query.clone()).unwrap().iter().count(), 5);
+
+    // Text syntax, display and the catalog encoding
+    let prepared = collection
+        .prepare("sensor = 2 SAMPLE 3 ORDER BY value DESC")
+        .unwrap();
+    assert_eq!(collection.find_prepared(&prepared, &[]).unwrap().len(), 3);
+    assert_eq!(
+        collection
+            .prepare("SAMPLE 7 LIMIT 2")
+            .unwrap()
+            .bind(&[])
+            .unwrap()
+            .to_string(),
+        "TRUE SAMPLE 7 LIMIT 2"
+    );
+
+    let query = query.limit(2);
+    let mut bytes = Vec::new();
+    encode_find_query(&query, &mut bytes).unwrap();
+    let decoded = decode_find_query(&mut ByteReader::new(&bytes)).unwrap();
+    assert_eq!(decoded.to_string(), query.to_string());
+    assert_eq!(decoded.sample, Some(5));
+
+    let plan = collection.explain(query).unwrap().to_string();
+    assert!(plan.contains("Sample: 5 documents"), "{}", plan);
+}
+
+#[test]
+fn test_paged_collection_sample() {
+    let path = std::env::temp_dir().join(format!("kenchidb_sample_{}.pages", std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    let mut collection = PagedCollection::new(Reading::schema(), 1, &path).unwrap();
+    for i in 0..50 {
+        let reading = Reading::create()
+            .set("sensor", i % 4)
+            .set("value", i as f64)
+            .build();
+        collection.insert(reading).unwrap();
+    }
+
+    let sample = collection.sample(8).unwrap();
+    let ids: HashSet<u64> = sample.iter().map(|document| document.id).collect();
+    assert_eq!(ids.len(), 8);
+
+    fs::remove_file(&path).unwrap();
+}
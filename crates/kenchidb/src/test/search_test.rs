@@ -0,0 +1,175 @@
+use std::fs;
+
+use crate::{
+    common::{DatabaseError, crc32},
+    database::Collection,
+    define_schema,
+    schema::{Schema, Value},
+    search::{load_text_index, text_index_path, tokenize},
+    storage::wal::wal_path,
+};
+
+define_schema! {
+    Person {
+        name: string,
+        bio: string?,
+        age: int,
+    }
+}
+
+fn schema() -> Schema {
+    Person::schema().with_text_index(&["name", "bio"])
+}
+
+fn names(collection: &Collection, text: &str) -> Vec<String> {
+    collection
+        .search(text)
+        .unwrap()
+        .iter()
+        .map(|hit| match hit.document.get("name") {
+            Some(Value::String(name)) => name.clone(),
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+fn people() -> Collection {
+    let mut collection = Collection::new(schema());
+    for (name, bio) in [
+        ("Alice Johnson", "Engineer"),
+        ("Alice Cooper", "Singer"),
+        ("Bob Johnson", "Engineer, likes alice in wonderland"),
+        ("Carol Davis", "Painter"),
+    ] {
+        let person = Person::create()
+            .set("name", name)
+            .set("bio", bio)
+            .set("age", 30i32)
+            .build();
+        collection.insert(person).unwrap();
+    }
+    collection
+}
+
+#[test]
+fn test_tokenize() {
+    assert_eq!(
+        tokenize("Alice O'Neil-Johnson, 42 Ünter"),
+        vec!["alice", "o", "neil", "johnson", "42", "ünter"]
+    );
+    assert!(tokenize(" -- ").is_empty());
+}
+
+#[test]
+fn test_search_ranks_documents() {
+    let collection = people();
+
+    let names = names(&collection, "alice johnson");
+    assert_eq!(names[0], "Alice Johnson");
+    assert_eq!(names.len(), 3);
+    assert!(!names.contains(&"Carol Davis".to_string()));
+
+    // Case and punctuation do not matter, unknown terms match nothing
+    let hits = collection.search("ENGINEER!").unwrap();
+    assert_eq!(hits.len(), 2);
+    assert!(hits.iter().all(|hit| hit.score > 0.0));
+    assert!(collection.search("zebra").unwrap().is_empty());
+    assert!(collection.search("").unwrap().is_empty());
+}
+
+#[test]
+fn test_search_follows_writes() {
+    let mut collection = people();
+    let carol = collection.search("carol").unwrap()[0].document.id;
+
+    let renamed = Person::create()
+        .set("name", "Carol Johnson")
+        .set("age", 31i32)
+        .build();
+    collection.update(carol, renamed).unwrap();
+    assert!(collection.search("davis").unwrap().is_empty());
+    assert_eq!(collection.search("johnson").unwrap().len(), 3);
+
+    collection.delete(carol).unwrap();
+    assert_eq!(collection.search("johnson").unwrap().len(), 2);
+    assert_eq!(collection.text_index.as_ref().unwrap().document_count(), 3);
+}
+
+#[test]
+fn test_search_without_text_fields() {
+    let collection = Collection::new(Person::schema());
+    assert!(matches!(
+        collection.search("alice"),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+
+    let not_string = Person::schema().with_text_index(&["age"]);
+    assert!(matches!(
+        not_string.validate(),
+        Err(DatabaseError::SchemaViolation(_))
+    ));
+    let missing = Person::schema().with_text_index(&["title"]);
+    assert!(missing.validate().is_err());
+    let repeated = Person::schema().with_text_index(&["name", "name"]);
+    assert!(repeated.validate().is_err());
+}
+
+#[test]
+fn test_text_index_persistence() {
+    let path = std::env::temp_dir().join(format!("kenchidb_search_{}.db", std::process::id()));
+    let index_path = text_index_path(&path);
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(wal_path(&path));
+    let _ = fs::remove_file(&index_path);
+
+    let mut collection = Collection::with_file(schema(), &path).unwrap();
+    for name in ["Alice Johnson", "Bob Johnson"] {
+        let person = Person::create().set("name", name).set("age", 40i32).build();
+        collection.insert(person).unwrap();
+    }
+    let expected = collection.text_index.clone();
+    // Index files are written at checkpoints
+    collection.checkpoint().unwrap();
+    drop(collection);
+
+    // The index is read from its pages, not rebuilt
+    let data = fs::read(&path).unwrap();
+    let stored = load_text_index(
+        &index_path,
+        vec!["name".to_string(), "bio".to_string()],
+        crc32(&data),
+    )
+    .unwrap();
+    assert_eq!(stored, expected);
+    let reopened = Collection::with_file(schema(), &path).unwrap();
+    assert_eq!(reopened.text_index, expected);
+
+    // A damaged index is rebuilt from the documents
+    let mut bytes = fs::read(&index_path).unwrap();
+    let term = bytes
+        .windows(7)
+        .position(|window| window == b"johnson")
+        .unwrap();
+    bytes[term] = b'x';
+    fs::write(&index_path, &bytes).unwrap();
+    let reopened = Collection::with_file(schema(), &path).unwrap();
+    assert_eq!(reopened.text_index, expected);
+
+    // An index left behind by other collection data is not trusted
+    fs::copy(&index_path, path.with_extension("fts_copy")).unwrap();
+    let mut collection = reopened;
+    let person = Person::create()
+        .set("name", "Carol")
+        .set("age", 20i32)
+        .build();
+    collection.insert(person).unwrap();
+    collection.checkpoint().unwrap();
+    drop(collection);
+    fs::rename(path.with_extension("fts_copy"), &index_path).unwrap();
+    let reopened = Collection::with_file(schema(), &path).unwrap();
+    assert_eq!(names(&reopened, "carol"), vec!["Carol"]);
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(wal_path(&path)).unwrap();
+    fs::remove_file(&index_path).unwrap();
+}
@@ -0,0 +1,69 @@
+use std::fs;
+
+use crate::{database::Collection, define_schema, schema::Value, storage::wal::wal_path};
+
+define_schema! {
+    Order {
+        order_number: long auto_increment,
+        ticket: int auto_increment,
+        item: string,
+    }
+}
+
+fn order(item: &str) -> crate::schema::Document {
+    Order::create().set("item", item).build()
+}
+
+#[test]
+fn test_auto_increment_sequences() {
+    let mut orders = Collection::new(Order::schema());
+    assert!(orders.schema.validate().is_ok());
+
+    let first = orders.insert(order("pen")).unwrap();
+    let second = orders.insert(order("ink")).unwrap();
+    let doc = orders.find_by_id(second).unwrap();
+    assert_eq!(doc.get("order_number"), Some(&Value::Long(2)));
+    assert_eq!(doc.get("ticket"), Some(&Value::Int(2)));
+
+    // Explicit values move the sequence forward
+    let explicit = Order::create()
+        .set("item", "pad")
+        .set("order_number", 100i64)
+        .build();
+    orders.insert(explicit).unwrap();
+
+    // Failed inserts do not consume values, deleted values are not reused
+    assert!(orders.insert(Order::create().build()).is_err());
+    orders.delete(first).unwrap();
+
+    let id = orders.insert(order("cap")).unwrap();
+    let doc = orders.find_by_id(id).unwrap();
+    assert_eq!(doc.get("order_number"), Some(&Value::Long(101)));
+    assert_eq!(doc.get("ticket"), Some(&Value::Int(4)));
+}
+
+#[test]
+fn test_sequences_survive_reload() {
+    let path = std::env::temp_dir().join(format!("kenchidb_seq_{}.db", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(wal_path(&path));
+
+    {
+        let mut orders = Collection::with_file(Order::schema(), &path).unwrap();
+        for item in ["a", "b", "c"] {
+            orders.insert(order(item)).unwrap();
+        }
+        // Deleting the newest order must not make its number available again
+        orders.delete(3).unwrap();
+    }
+
+    let mut orders = Collection::with_file(Order::schema(), &path).unwrap();
+    let id = orders.insert(order("d")).unwrap();
+    assert_eq!(
+        orders.find_by_id(id).unwrap().get("order_number"),
+        Some(&Value::Long(4))
+    );
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(wal_path(&path)).unwrap();
+}
@@ -0,0 +1,189 @@
+use std::fs;
+
+use crate::{
+    common::DatabaseError,
+    database::Collection,
+    define_schema,
+    query::Query,
+    schema::{Document, Value},
+    storage::wal::wal_path,
+};
+
+define_schema! {
+    Account {
+        owner: string,
+        balance: long,
+    }
+}
+
+fn account(owner: &str, balance: i64) -> Document {
+    Account::create()
+        .set("owner", owner)
+        .set("balance", balance)
+        .build()
+}
+
+fn balance(document: Option<&Document>) -> Option<i64> {
+    match document?.get("balance") {
+        Some(Value::Long(balance)) => Some(*balance),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_session_reads_its_own_writes() {
+    let mut collection = Collection::new(Account::schema());
+    let alice = collection.insert(account("alice", 100)).unwrap();
+
+    let mut session = collection.session();
+    let bob = session.insert(account("bob", 50)).unwrap();
+    session.update(alice, account("alice", 70)).unwrap();
+
+    // The session sees its writes, the collection does not
+    assert_eq!(balance(session.find_by_id(alice)), Some(70));
+    assert_eq!(balance(session.find_by_id(bob)), Some(50));
+    assert_eq!(session.find_where(Query::all()).unwrap().len(), 2);
+    assert_eq!(balance(collection.find_by_id(alice)), Some(100));
+    assert!(collection.find_by_id(bob).is_none());
+
+    assert_eq!(collection.commit(session).unwrap(), vec![bob]);
+    assert_eq!(balance(collection.find_by_id(alice)), Some(70));
+    assert_eq!(balance(collection.find_by_id(bob)), Some(50));
+}
+
+#[test]
+fn test_snapshots_see_commits_made_before_them() {
+    let mut collection = Collection::new(Account::schema());
+    let alice = collection.insert(account("alice", 100)).unwrap();
+
+    let before = collection.snapshot();
+    let mut session = collection.session();
+    session.delete(alice).unwrap();
+    assert_eq!(session.find_all().len(), 0);
+    collection.commit(session).unwrap();
+
+    assert_eq!(balance(before.find_by_id(alice)), Some(100));
+    assert_eq!(collection.snapshot().find_all().len(), 0);
+
+    // Dropped sessions leave no trace
+    let mut session = collection.session();
+    session.insert(account("bob", 1)).unwrap();
+    assert_eq!(session.pending_writes(), 1);
+    drop(session);
+    assert_eq!(collection.find_all().len(), 0);
+}
+
+#[test]
+fn test_sessions_are_isolated_from_each_other() {
+    let mut collection = Collection::new(Account::schema());
+    let alice = collection.insert(account("alice", 100)).unwrap();
+
+    let mut first = collection.session();
+    let mut second = collection.session();
+    let bob = first.insert(account("bob", 10)).unwrap();
+    let carol = second.insert(account("carol", 20)).unwrap();
+    second.update(carol, account("carol", 25)).unwrap();
+    first.update(alice, account("alice", 90)).unwrap();
+    assert_eq!(bob, carol);
+
+    // Neither session sees the other one's writes, even after they are committed
+    collection.commit(first).unwrap();
+    assert_eq!(balance(second.find_by_id(alice)), Some(100));
+    assert_eq!(second.find_all().len(), 2);
+
+    // Inserts get their final id on commit, later writes of the session follow them
+    let ids = collection.commit(second).unwrap();
+    assert_ne!(ids, vec![carol]);
+    assert_eq!(balance(collection.find_by_id(ids[0])), Some(25));
+    assert_eq!(balance(collection.find_by_id(bob)), Some(10));
+    assert_eq!(balance(collection.find_by_id(alice)), Some(90));
+}
+
+#[test]
+fn test_commit_is_all_or_nothing() {
+    let path = std::env::temp_dir().join(format!("kenchidb_session_{}.db", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(wal_path(&path));
+
+    let mut collection = Collection::with_file(Account::schema(), &path).unwrap();
+    let alice = collection.insert(account("alice", 100)).unwrap();
+    let bob = collection.insert(account("bob", 100)).unwrap();
+
+    let mut session = collection.session();
+    session.update(alice, account("alice", 0)).unwrap();
+    session.update(bob, account("bob", 200)).unwrap();
+    session.insert(account("carol", 5)).unwrap();
+
+    // Bob is deleted underneath the session, none of its writes are applied
+    collection.delete(bob).unwrap();
+    assert!(matches!(
+        collection.commit(session),
+        Err(DatabaseError::DocumentNotFound(id)) if id == bob
+    ));
+    assert_eq!(balance(collection.find_by_id(alice)), Some(100));
+    assert_eq!(collection.find_all().len(), 1);
+
+    // Committed writes are saved together
+    let mut session = collection.session();
+    session.update(alice, account("alice", 60)).unwrap();
+    session.insert(account("carol", 40)).unwrap();
+    collection.commit(session).unwrap();
+    drop(collection);
+
+    let reopened = Collection::with_file(Account::schema(), &path).unwrap();
+    assert_eq!(reopened.find_all().len(), 2);
+    assert_eq!(balance(reopened.find_by_id(alice)), Some(60));
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(wal_path(&path)).unwrap();
+}
+
+#[test]
+fn test_update_if_version_detects_lost_updates() {
+    let path = std::env::temp_dir().join(format!("kenchidb_occ_{}.db", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(wal_path(&path));
+
+    let mut collection = Collection::with_file(Account::schema(), &path).unwrap();
+    let alice = collection.insert(account("alice", 100)).unwrap();
+    assert_eq!(collection.find_by_id(alice).unwrap().version, 1);
+
+    // Two writers read version 1, the second one to write loses
+    let read = collection.find_by_id(alice).unwrap().version;
+    collection
+        .update_if_version(alice, read, account("alice", 90))
+        .unwrap();
+    assert!(matches!(
+        collection.update_if_version(alice, read, account("alice", 80)),
+        Err(DatabaseError::Conflict { id, expected: 1, actual: 2 }) if id == alice
+    ));
+    assert_eq!(balance(collection.find_by_id(alice)), Some(90));
+    assert!(matches!(
+        collection.update_if_version(99, 1, account("nobody", 0)),
+        Err(DatabaseError::DocumentNotFound(99))
+    ));
+
+    // A session checks the version again on commit
+    let mut session = collection.session();
+    session
+        .update_if_version(alice, 2, account("alice", 70))
+        .unwrap();
+    collection.update(alice, account("alice", 85)).unwrap();
+    assert!(matches!(
+        collection.commit(session),
+        Err(DatabaseError::Conflict { actual: 3, .. })
+    ));
+    assert_eq!(balance(collection.find_by_id(alice)), Some(85));
+
+    // Versions survive the write-ahead log and checkpoints
+    drop(collection);
+    let mut collection = Collection::with_file(Account::schema(), &path).unwrap();
+    assert_eq!(collection.find_by_id(alice).unwrap().version, 3);
+    collection.checkpoint().unwrap();
+    drop(collection);
+    let collection = Collection::with_file(Account::schema(), &path).unwrap();
+    assert_eq!(collection.find_by_id(alice).unwrap().version, 3);
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(wal_path(&path)).unwrap();
+}
@@ -0,0 +1,192 @@
+use crate::{
+    common::DatabaseError,
+    database::Collection,
+    define_schema,
+    query::{
+        ByteReader, Collation, Direction, Query, QueryParser, SortKey, decode_find_query,
+        encode_find_query,
+    },
+    schema::{Document, Value},
+};
+
+define_schema! {
+    Measurement {
+        value: double,
+    }
+}
+
+define_schema! {
+    Person {
+        last_name: string,
+        first_name: string,
+        age: int,
+    }
+}
+
+fn people() -> Collection {
+    let mut collection = Collection::new(Person::schema());
+    for (last_name, first_name, age) in [
+        ("smith", "Zoe", 30),
+        ("Brown", "amy", 41),
+        ("smith", "adam", 25),
+        ("brown", "Carl", 41),
+        ("Élan", "eve", 19),
+    ] {
+        let person = Person::create()
+            .set("last_name", last_name)
+            .set("first_name", first_name)
+            .set("age", age)
+            .build();
+        collection.insert(person).unwrap();
+    }
+    collection
+}
+
+fn full_names(documents: Vec<&Document>) -> Vec<String> {
+    documents
+        .iter()
+        .map(
+            |document| match (document.get("first_name"), document.get("last_name")) {
+                (Some(Value::String(first)), Some(Value::String(last))) => {
+                    format!("{} {}", first, last)
+                }
+                _ => unreachable!(),
+            },
+        )
+        .collect()
+}
+
+#[test]
+fn test_order_by_multiple_keys() {
+    let collection = people();
+    let nocase =
+        |field: &str, direction| SortKey::new(field, direction).collate(Collation::CaseInsensitive);
+
+    let query = Query::all()
+        .order_by_key(nocase("last_name", Direction::Asc))
+        .order_by_key(nocase("first_name", Direction::Asc));
+    assert_eq!(
+        full_names(collection.find_where(query).unwrap()),
+        vec![
+            "amy Brown",
+            "Carl brown",
+            "adam smith",
+            "Zoe smith",
+            "eve Élan"
+        ]
+    );
+
+    // Binary collation puts uppercase letters first
+    let query = Query::all()
+        .order_by("last_name", Direction::Asc)
+        .order_by("first_name", Direction::Asc);
+    assert_eq!(
+        full_names(collection.find_where(query).unwrap()),
+        vec![
+            "amy Brown",
+            "Carl brown",
+            "Zoe smith",
+            "adam smith",
+            "eve Élan"
+        ]
+    );
+
+    // Each key has its own direction, also through the bounded heap
+    let query = Query::all()
+        .order_by("age", Direction::Desc)
+        .order_by_key(nocase("first_name", Direction::Desc))
+        .limit(3);
+    assert_eq!(
+        full_names(collection.find_where(query).unwrap()),
+        vec!["Carl brown", "amy Brown", "Zoe smith"]
+    );
+}
+
+#[test]
+fn test_order_by_clause_with_collation() {
+    let collection = people();
+    let find = |text: &str| {
+        let prepared = collection.prepare(text).unwrap();
+        full_names(collection.find_prepared(&prepared, &[]).unwrap())
+    };
+
+    assert_eq!(
+        find("ORDER BY last_name COLLATE NOCASE, first_name collate nocase DESC LIMIT 2"),
+        vec!["Carl brown", "amy Brown"]
+    );
+    assert_eq!(
+        find("age > 20 ORDER BY age DESC, first_name COLLATE BINARY"),
+        vec!["Carl brown", "amy Brown", "Zoe smith", "adam smith"]
+    );
+
+    // Displayed queries parse back, and survive the catalog encoding
+    let query = Query::all()
+        .order_by_key(SortKey::new("last_name", Direction::Asc).collate(Collation::CaseInsensitive))
+        .order_by("age", Direction::Desc);
+    let text = query.to_string();
+    assert_eq!(text, "TRUE ORDER BY last_name COLLATE NOCASE ASC, age DESC");
+    let schema = Person::schema();
+    let parsed = QueryParser::new(&schema, &text).unwrap().parse().unwrap();
+    assert_eq!(parsed.bind(&[]).unwrap().to_string(), text);
+
+    let mut bytes = Vec::new();
+    encode_find_query(&query, &mut bytes).unwrap();
+    let decoded = decode_find_query(&mut ByteReader::new(&bytes)).unwrap();
+    assert_eq!(decoded.to_string(), text);
+}
+
+#[test]
+fn test_collation_requires_string_field() {
+    let collection = people();
+    let query = Query::all()
+        .order_by("last_name", Direction::Asc)
+        .order_by_key(SortKey::new("age", Direction::Asc).collate(Collation::CaseInsensitive));
+    assert!(matches!(
+        collection.find_where(query),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+    assert!(collection.prepare("ORDER BY age COLLATE NOCASE").is_err());
+    assert!(
+        collection
+            .prepare("ORDER BY last_name COLLATE GERMAN")
+            .is_err()
+    );
+    assert!(collection.prepare("ORDER BY last_name, missing").is_err());
+}
+
+#[test]
+fn test_order_by_sorts_nan_last() {
+    let mut collection = Collection::new(Measurement::schema());
+    for value in [3.0, f64::NAN, -1.0, 7.5, f64::NAN, 0.0, 2.0] {
+        let measurement = Measurement::create().set("value", value).build();
+        collection.insert(measurement).unwrap();
+    }
+    let values = |documents: Vec<&Document>| -> Vec<String> {
+        documents
+            .iter()
+            .map(|document| match document.get("value") {
+                Some(Value::Double(value)) => value.to_string(),
+                _ => unreachable!(),
+            })
+            .collect()
+    };
+
+    // NaN is greater than every number instead of equal to all of them
+    let query = Query::all().order_by("value", Direction::Asc);
+    assert_eq!(
+        values(collection.find_where(query).unwrap()),
+        vec!["-1", "0", "2", "3", "7.5", "NaN", "NaN"]
+    );
+
+    // The bounded heap keeps the same order
+    let query = Query::all().order_by("value", Direction::Desc).limit(4);
+    assert_eq!(
+        values(collection.find_where(query).unwrap()),
+        vec!["NaN", "NaN", "7.5", "3"]
+    );
+    let query = Query::all().order_by("value", Direction::Asc).limit(3);
+    assert_eq!(
+        values(collection.find_where(query).unwrap()),
+        vec!["-1", "0", "2"]
+    );
+}
@@ -0,0 +1,132 @@
+use std::fs;
+
+use crate::{
+    database::{Collection, Database},
+    define_schema,
+    query::{Direction, Query},
+    storage::paged_collection::PagedCollection,
+};
+
+define_schema! {
+    Order {
+        customer: string,
+        total: int,
+    }
+}
+
+fn order(customer: &str, total: i32) -> crate::schema::Document {
+    Order::create()
+        .set("customer", customer)
+        .set("total", total)
+        .build()
+}
+
+#[test]
+fn test_query_stats_count_scanned_and_matched_rows() {
+    let mut collection = Collection::new(Order::schema());
+    for total in 0..20 {
+        let customer = if total % 2 == 0 { "ann" } else { "bob" };
+        collection.insert(order(customer, total)).unwrap();
+    }
+
+    let query = Query::eq("customer", "ann").order_by("total", Direction::Desc);
+    let (documents, stats) = collection
+        .query(query.clone().limit(3))
+        .unwrap()
+        .with_stats();
+    assert_eq!(documents.len(), 3);
+    assert_eq!(stats.rows_scanned, 20);
+    assert_eq!(stats.rows_matched, 10);
+    assert_eq!((stats.pages_read, stats.cache_hits), (0, 0));
+    assert_eq!(
+        collection.find_where(query.limit(3)).unwrap().len(),
+        documents.len()
+    );
+
+    // Unordered queries stop scanning at the limit
+    let (documents, stats) = collection
+        .query(Query::all().limit(4))
+        .unwrap()
+        .with_stats();
+    assert_eq!(documents.len(), 4);
+    assert_eq!((stats.rows_scanned, stats.rows_matched), (4, 4));
+    assert!(stats.to_string().starts_with("scanned 4 rows, matched 4"));
+}
+
+#[test]
+fn test_cursor_stats_count_pages_and_cache_hits() {
+    let path = std::env::temp_dir().join(format!("kenchidb_stats_{}.pages", std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    let mut orders = PagedCollection::new(Order::schema(), 1, &path).unwrap();
+    let customer = "c".repeat(250);
+    for total in 0..30 {
+        orders.insert(order(&customer, total)).unwrap();
+    }
+    let pages = orders.stats().total_pages as u64;
+    assert!(pages > 1);
+
+    let cursor = orders.find_where_iter(Query::lt("total", 5i32)).unwrap();
+    let (documents, stats) = cursor.with_stats().unwrap();
+    assert_eq!(documents.len(), 5);
+    assert_eq!((stats.rows_scanned, stats.rows_matched), (30, 5));
+    assert_eq!((stats.pages_read, stats.cache_hits), (pages, 0));
+
+    // A second scan is served from the buffer pool
+    orders.file_manager.enable_buffer_pool(64);
+    orders.iter().with_stats().unwrap();
+    let (_, stats) = orders.iter().with_stats().unwrap();
+    assert_eq!((stats.pages_read, stats.cache_hits), (0, pages));
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_database_stats_count_collection_file_io() {
+    let directory =
+        std::env::temp_dir().join(format!("kenchidb_database_stats_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    let catalog = directory.join("shop.catalog");
+
+    let mut db = Database::with_catalog(&catalog).unwrap();
+    db.create_collection_with_file(
+        "orders".to_string(),
+        Order::schema(),
+        directory.join("orders.data"),
+    )
+    .unwrap();
+    db.create_collection("drafts".to_string(), Order::schema())
+        .unwrap();
+    let before = db.stats();
+    assert_eq!((before.collections, before.documents), (2, 0));
+
+    let orders = db.collection("orders").unwrap();
+    for total in 0..5 {
+        orders.insert(order("ann", total)).unwrap();
+    }
+    orders.checkpoint().unwrap();
+    db.collection("drafts")
+        .unwrap()
+        .insert(order("bob", 1))
+        .unwrap();
+
+    // Each insert is appended to the log, the checkpoint writes the collection file
+    let after = db.stats();
+    assert_eq!(after.documents, 6);
+    let interval = after.io.since(&before.io);
+    assert_eq!(interval.write_count, 6);
+    assert!(interval.write_bytes > 0);
+    assert_eq!(interval.read_count, 0);
+    drop(db);
+
+    let db = Database::open(&catalog).unwrap();
+    let stats = db.stats();
+    assert_eq!((stats.collections, stats.documents), (1, 5));
+    assert_eq!(stats.io.read_count, 1);
+    assert_eq!(stats.io.write_count, 0);
+    assert!(stats.io.read_bytes > 0);
+
+    drop(db);
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,134 @@
+use std::fs;
+
+use crate::{
+    common::DatabaseError,
+    database::Collection,
+    define_schema,
+    query::Query,
+    schema::{Document, MAX_STRING_BYTES, StringOverflow, Value, truncate_to_bytes},
+    storage::{
+        paged_collection::{PagedCollection, free_space_map_path, primary_key_index_path},
+        wal::wal_path,
+    },
+};
+
+define_schema! {
+    Profile {
+        name: string,
+        bio: string(truncate)?,
+    }
+}
+
+#[test]
+fn test_multibyte_strings_are_limited_by_bytes() {
+    let mut profiles = Collection::new(Profile::schema());
+
+    // 200 characters, 400 bytes
+    let name = "é".repeat(200);
+    let result = profiles.insert(Profile::create().set("name", name.as_str()).build());
+    assert!(matches!(result, Err(DatabaseError::SchemaViolation(_))));
+
+    let name = "é".repeat(127);
+    assert!(
+        profiles
+            .insert(Profile::create().set("name", name.as_str()).build())
+            .is_ok()
+    );
+
+    assert!(matches!(
+        profiles.find_where(Query::eq("name", "x".repeat(MAX_STRING_BYTES + 1))),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+}
+
+#[test]
+fn test_truncate_policy() {
+    let schema = Profile::schema();
+    assert_eq!(schema.fields[0].on_overflow, StringOverflow::Error);
+    assert_eq!(schema.fields[1].on_overflow, StringOverflow::Truncate);
+    assert!(schema.fields[1].nullable);
+
+    // 'ü' straddles the 255 byte limit and is dropped
+    let bio = format!("{}ü", "a".repeat(254));
+    let document = Profile::create()
+        .set("name", "ann")
+        .set("bio", bio)
+        .try_build()
+        .unwrap();
+    assert_eq!(document.get("bio"), Some(&Value::String("a".repeat(254))));
+
+    assert!(
+        Profile::create()
+            .set("name", "n".repeat(300))
+            .try_build()
+            .is_err()
+    );
+
+    let mut value = "日本語".to_string();
+    truncate_to_bytes(&mut value, 7);
+    assert_eq!(value, "日本");
+}
+
+#[test]
+fn test_max_length_strings_round_trip() {
+    let path = std::env::temp_dir().join(format!("kenchidb_strings_{}.db", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(wal_path(&path));
+
+    let name = "ß".repeat(127);
+    {
+        let mut profiles = Collection::with_file(Profile::schema(), &path).unwrap();
+        let mut document = Profile::create().set("name", name.as_str()).build();
+        document.set("bio", "b".repeat(400));
+        profiles.insert(document).unwrap();
+    }
+
+    let profiles = Collection::with_file(Profile::schema(), &path).unwrap();
+    let document = profiles.find_by_id(1).unwrap();
+    assert_eq!(document.get("name"), Some(&Value::String(name)));
+    assert_eq!(
+        document.get("bio"),
+        Some(&Value::String("b".repeat(MAX_STRING_BYTES)))
+    );
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(wal_path(&path)).unwrap();
+}
+
+#[test]
+fn test_oversized_string_insert_is_an_error() {
+    let path = std::env::temp_dir().join(format!(
+        "kenchidb_oversized_strings_{}.pages",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    let mut profiles = PagedCollection::new(Profile::schema(), 1, &path).unwrap();
+
+    // 264 bytes, rejected before anything is written
+    let mut document = Document::new(0);
+    document.set("name", "long name ".repeat(26) + "end!");
+    assert!(matches!(
+        profiles.insert(document.clone()),
+        Err(DatabaseError::SchemaViolation(_))
+    ));
+    assert!(profiles.ids().is_empty());
+    let mut collection = Collection::new(Profile::schema());
+    assert!(matches!(
+        collection.insert(document),
+        Err(DatabaseError::SchemaViolation(_))
+    ));
+
+    // Serializing the value on its own fails instead of panicking
+    let value = Value::String("x".repeat(MAX_STRING_BYTES + 1));
+    assert!(matches!(
+        value.serialize(),
+        Err(DatabaseError::InvalidData(_))
+    ));
+    let value = Value::String("x".repeat(MAX_STRING_BYTES));
+    assert_eq!(value.serialize().unwrap().len(), value.type_size());
+
+    drop(profiles);
+    fs::remove_file(&path).unwrap();
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+}
@@ -0,0 +1,88 @@
+use std::fs;
+
+use crate::{
+    define_schema,
+    schema::Value,
+    storage::{file_manager::FileManager, paged_collection::PagedCollection, tier::TierPolicy},
+};
+
+define_schema! {
+    Reading {
+        sensor: string,
+        payload: string,
+    }
+}
+
+fn tier_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("kenchidb_{}_{}.pages", name, std::process::id()))
+}
+
+#[test]
+fn test_cold_tier_demotes_and_promotes_pages() {
+    let hot_path = tier_path("tier_hot");
+    let cold_path = tier_path("tier_cold");
+    let _ = fs::remove_file(&hot_path);
+    let _ = fs::remove_file(&cold_path);
+
+    let mut readings = PagedCollection::new(Reading::schema(), 1, &hot_path).unwrap();
+    let payload = "x".repeat(200);
+    for i in 0..40 {
+        readings
+            .insert(
+                Reading::create()
+                    .set("sensor", format!("s{}", i))
+                    .set("payload", payload.as_str())
+                    .build(),
+            )
+            .unwrap();
+    }
+    let total_pages = readings.stats().total_pages as usize;
+    assert!(total_pages > 2);
+
+    let policy = TierPolicy {
+        promote_after_reads: 3,
+        demote_below_reads: 1,
+    };
+    readings
+        .file_manager
+        .enable_cold_tier(&cold_path, policy)
+        .unwrap();
+
+    // Keep the page of the first document hot, everything else goes cold
+    let (hot_page, _) = readings.documents.get(1).unwrap();
+    readings.find_by_id(1).unwrap();
+    let demoted = readings.file_manager.migrate_cold_pages().unwrap();
+    assert_eq!(demoted, total_pages - 1);
+    assert_eq!(readings.file_manager.cold_page_count(), total_pages - 1);
+
+    // Reads are unaffected by where the page lives
+    let (cold_page, _) = readings.documents.get(40).unwrap();
+    assert_ne!(cold_page, hot_page);
+    let last = readings.find_by_id(40).unwrap().unwrap();
+    assert_eq!(last.get("sensor"), Some(&Value::String("s39".to_string())));
+
+    // Writes update cold pages in place
+    readings.delete(40).unwrap();
+    assert_eq!(readings.file_manager.cold_page_count(), total_pages - 1);
+
+    // The third read since the migration promotes the page back
+    assert!(readings.find_by_id(40).unwrap().is_none());
+    readings.file_manager.read_page(cold_page).unwrap();
+    assert_eq!(readings.file_manager.cold_page_count(), total_pages - 2);
+    assert_eq!(readings.iter().count(), 39);
+
+    // The tier assignment survives reopening both files
+    let expected = readings.file_manager.read_page(cold_page).unwrap();
+    drop(readings);
+    let mut pages = FileManager::new(&hot_path).unwrap();
+    pages.enable_cold_tier(&cold_path, policy).unwrap();
+    assert_eq!(pages.cold_page_count(), total_pages - 2);
+    for page_id in 0..total_pages as u32 {
+        pages.read_page(page_id).unwrap();
+    }
+    let reopened = pages.read_page(cold_page).unwrap();
+    assert_eq!(reopened.data, expected.data);
+
+    let _ = fs::remove_file(&hot_path);
+    let _ = fs::remove_file(&cold_path);
+}
@@ -0,0 +1,424 @@
+use std::{fs, path::PathBuf};
+
+use crate::{
+    common::DatabaseError,
+    database::{Collection, Database},
+    define_schema,
+    schema::{Document, Value},
+    storage::wal::{
+        DecidedTransaction, TransactionLog, WalRecord, WriteAheadLog, transaction_log_path,
+        wal_path,
+    },
+};
+
+define_schema! {
+    Account {
+        owner: string,
+        balance: long,
+    }
+}
+
+define_schema! {
+    Transfer {
+        from: long,
+        to: long,
+        amount: long,
+    }
+}
+
+fn account(owner: &str, balance: i64) -> Document {
+    Account::create()
+        .set("owner", owner)
+        .set("balance", balance)
+        .build()
+}
+
+fn transfer(from: u64, to: u64, amount: i64) -> Document {
+    Transfer::create()
+        .set("from", from as i64)
+        .set("to", to as i64)
+        .set("amount", amount)
+        .build()
+}
+
+fn balance(document: Option<&Document>) -> Option<i64> {
+    match document?.get("balance") {
+        Some(Value::Long(balance)) => Some(*balance),
+        _ => None,
+    }
+}
+
+fn bank() -> Database {
+    let mut db = Database::new();
+    db.create_collection("accounts".to_string(), Account::schema())
+        .unwrap();
+    db.create_collection("transfers".to_string(), Transfer::schema())
+        .unwrap();
+    let accounts = db.collection("accounts").unwrap();
+    accounts.insert(account("ann", 100)).unwrap();
+    accounts.insert(account("bob", 50)).unwrap();
+    db
+}
+
+#[test]
+fn test_transaction_commits_all_collections() {
+    let mut db = bank();
+
+    let mut transaction = db.begin();
+    transaction
+        .update("accounts", 1, account("ann", 70))
+        .unwrap();
+    transaction
+        .update("accounts", 2, account("bob", 80))
+        .unwrap();
+    let id = transaction.insert("transfers", transfer(1, 2, 30)).unwrap();
+    assert_eq!(transaction.pending_writes(), 3);
+
+    // The transaction reads its own writes
+    let accounts = transaction.collection("accounts").unwrap();
+    assert_eq!(balance(accounts.find_by_id(1)), Some(70));
+    assert!(
+        transaction
+            .collection("transfers")
+            .unwrap()
+            .find_by_id(id)
+            .is_some()
+    );
+    assert!(transaction.collection("loans").is_err());
+
+    let inserted = transaction.commit().unwrap();
+    assert_eq!(inserted["transfers"], vec![1]);
+    assert_eq!(inserted["accounts"], Vec::<u64>::new());
+    assert_eq!(
+        balance(db.collection("accounts").unwrap().find_by_id(1)),
+        Some(70)
+    );
+    assert_eq!(
+        balance(db.collection("accounts").unwrap().find_by_id(2)),
+        Some(80)
+    );
+    assert_eq!(db.collection("transfers").unwrap().find_all().len(), 1);
+}
+
+#[test]
+fn test_transaction_rollback() {
+    let mut db = bank();
+
+    let mut transaction = db.begin();
+    transaction
+        .update("accounts", 1, account("ann", 0))
+        .unwrap();
+    transaction
+        .insert("transfers", transfer(1, 2, 100))
+        .unwrap();
+
+    // A failed write is not recorded, the transaction goes on
+    assert!(matches!(
+        transaction.delete("accounts", 3),
+        Err(DatabaseError::DocumentNotFound(3))
+    ));
+    assert_eq!(transaction.pending_writes(), 2);
+
+    transaction.rollback();
+    assert_eq!(
+        balance(db.collection("accounts").unwrap().find_by_id(1)),
+        Some(100)
+    );
+    assert!(db.collection("transfers").unwrap().find_all().is_empty());
+}
+
+fn directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("kenchidb_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+fn open_bank(directory: &std::path::Path) -> Database {
+    let mut db = Database::with_catalog(directory.join("bank.catalog")).unwrap();
+    db.create_collection_with_file(
+        "accounts".to_string(),
+        Account::schema(),
+        directory.join("accounts.data"),
+    )
+    .unwrap();
+    db.create_collection_with_file(
+        "transfers".to_string(),
+        Transfer::schema(),
+        directory.join("transfers.data"),
+    )
+    .unwrap();
+    db
+}
+
+#[test]
+fn test_transaction_across_collection_files() {
+    let directory = directory("transaction_files");
+
+    {
+        let mut db = open_bank(&directory);
+        db.collection("accounts")
+            .unwrap()
+            .insert(account("ann", 100))
+            .unwrap();
+        let mut transaction = db.begin();
+        transaction
+            .update("accounts", 1, account("ann", 60))
+            .unwrap();
+        transaction.insert("transfers", transfer(1, 2, 40)).unwrap();
+        transaction.commit().unwrap();
+
+        // Committed transactions leave nothing to resolve
+        assert!(db.transactions.as_ref().unwrap().decided().is_empty());
+    }
+
+    let mut db = open_bank(&directory);
+    assert_eq!(
+        balance(db.collection("accounts").unwrap().find_by_id(1)),
+        Some(60)
+    );
+    assert_eq!(db.collection("transfers").unwrap().find_all().len(), 1);
+
+    // Opened on its own, a collection file is not affected by the transaction log
+    drop(db);
+    let accounts = Collection::with_file(Account::schema(), directory.join("accounts.data"));
+    assert_eq!(balance(accounts.unwrap().find_by_id(1)), Some(60));
+
+    // Writes to several collection files need the transaction log of a catalog
+    let mut db = Database::new();
+    db.create_collection_with_file(
+        "accounts".to_string(),
+        Account::schema(),
+        directory.join("accounts.data"),
+    )
+    .unwrap();
+    db.create_collection_with_file(
+        "transfers".to_string(),
+        Transfer::schema(),
+        directory.join("transfers.data"),
+    )
+    .unwrap();
+    let mut transaction = db.begin();
+    transaction
+        .update("accounts", 1, account("ann", 0))
+        .unwrap();
+    transaction.insert("transfers", transfer(1, 2, 60)).unwrap();
+    assert!(matches!(
+        transaction.commit(),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+    assert_eq!(
+        balance(db.collection("accounts").unwrap().find_by_id(1)),
+        Some(60)
+    );
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+/// Prepare a transfer in both collection logs as a crash between prepare and commit leaves it
+fn prepare_transfer(directory: &std::path::Path, transaction: u64) {
+    let (mut accounts, _) =
+        WriteAheadLog::open(wal_path(&directory.join("accounts.data"))).unwrap();
+    let mut updated = account("ann", 10);
+    updated.id = 1;
+    accounts
+        .append(&[
+            WalRecord::Put(updated),
+            WalRecord::Prepare {
+                transaction,
+                next_id: 2,
+                sequences: Vec::new(),
+            },
+        ])
+        .unwrap();
+
+    let (mut transfers, _) =
+        WriteAheadLog::open(wal_path(&directory.join("transfers.data"))).unwrap();
+    let mut inserted = transfer(1, 2, 90);
+    inserted.id = 1;
+    transfers
+        .append(&[
+            WalRecord::Put(inserted),
+            WalRecord::Prepare {
+                transaction,
+                next_id: 2,
+                sequences: Vec::new(),
+            },
+        ])
+        .unwrap();
+}
+
+#[test]
+fn test_prepared_transaction_recovery() {
+    let directory = directory("transaction_recovery");
+    {
+        let mut db = open_bank(&directory);
+        db.collection("accounts")
+            .unwrap()
+            .insert(account("ann", 100))
+            .unwrap();
+    }
+
+    // Crash before the commit decision: discarded everywhere
+    prepare_transfer(&directory, 7);
+    assert!(matches!(
+        Collection::with_file(Account::schema(), directory.join("accounts.data")),
+        Err(DatabaseError::InvalidData(_))
+    ));
+    let mut db = open_bank(&directory);
+    let report = db.collection("accounts").unwrap().recovery_report();
+    assert_eq!(report.prepared_transaction, Some((7, false)));
+    assert!(report.wal_bytes_discarded > 0);
+    assert_eq!(
+        balance(db.collection("accounts").unwrap().find_by_id(1)),
+        Some(100)
+    );
+    assert!(db.collection("transfers").unwrap().find_all().is_empty());
+    drop(db);
+
+    // Crash after the commit decision: kept everywhere
+    prepare_transfer(&directory, 8);
+    let mut log =
+        TransactionLog::open(transaction_log_path(&directory.join("bank.catalog"))).unwrap();
+    log.decide(DecidedTransaction {
+        id: 8,
+        participants: vec![
+            wal_path(&directory.join("accounts.data")),
+            wal_path(&directory.join("transfers.data")),
+        ],
+        in_doubt: false,
+    })
+    .unwrap();
+    drop(log);
+
+    let mut db = open_bank(&directory);
+    let report = db.collection("accounts").unwrap().recovery_report();
+    assert_eq!(report.prepared_transaction, Some((8, true)));
+    assert!(report.is_clean());
+    assert_eq!(
+        balance(db.collection("accounts").unwrap().find_by_id(1)),
+        Some(10)
+    );
+    assert_eq!(db.collection("transfers").unwrap().find_all().len(), 1);
+    assert!(db.transactions.as_ref().unwrap().decided().is_empty());
+
+    // The collection logs hold the commit now
+    let id = db
+        .collection("transfers")
+        .unwrap()
+        .insert(transfer(1, 2, 5))
+        .unwrap();
+    assert_eq!(id, 2);
+    drop(db);
+    let transfers = Collection::with_file(Transfer::schema(), directory.join("transfers.data"));
+    assert_eq!(transfers.unwrap().find_all().len(), 2);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_transaction_prepared_for_a_coordinator() {
+    let directory = directory("transaction_prepared");
+    let mut db = open_bank(&directory);
+    db.collection("accounts")
+        .unwrap()
+        .insert(account("ann", 100))
+        .unwrap();
+
+    let mut transaction = db.begin();
+    transaction
+        .update("accounts", 1, account("ann", 70))
+        .unwrap();
+    transaction.insert("transfers", transfer(1, 2, 30)).unwrap();
+    let prepared = transaction.prepare().unwrap();
+    assert_eq!(prepared.inserted["transfers"], vec![1]);
+    assert_eq!(db.prepared_transactions(), vec![prepared.id]);
+
+    // Not visible yet, and the collections take no other writes
+    assert_eq!(
+        balance(db.collection("accounts").unwrap().find_by_id(1)),
+        Some(100)
+    );
+    assert!(matches!(
+        db.collection("accounts").unwrap().insert(account("cid", 5)),
+        Err(DatabaseError::TransactionPrepared(id)) if id == prepared.id
+    ));
+
+    db.commit_prepared(prepared.id).unwrap();
+    assert_eq!(
+        balance(db.collection("accounts").unwrap().find_by_id(1)),
+        Some(70)
+    );
+    assert!(db.collection("transfers").unwrap().find_by_id(1).is_some());
+    assert!(db.prepared_transactions().is_empty());
+    assert!(matches!(
+        db.commit_prepared(prepared.id),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+
+    // A prepared transaction outlives a restart, then rolls back
+    let mut transaction = db.begin();
+    transaction
+        .update("accounts", 1, account("ann", 0))
+        .unwrap();
+    transaction.insert("transfers", transfer(1, 2, 70)).unwrap();
+    let rolled_back = transaction.prepare().unwrap();
+    drop(db);
+
+    let mut db = open_bank(&directory);
+    let report = db.collection("accounts").unwrap().recovery_report();
+    assert_eq!(report.in_doubt_transaction, Some(rolled_back.id));
+    assert_eq!(db.prepared_transactions(), vec![rolled_back.id]);
+    assert_eq!(
+        balance(db.collection("accounts").unwrap().find_by_id(1)),
+        Some(70)
+    );
+    db.rollback_prepared(rolled_back.id).unwrap();
+    db.collection("accounts")
+        .unwrap()
+        .insert(account("cid", 5))
+        .unwrap();
+    drop(db);
+
+    let mut db = open_bank(&directory);
+    assert!(
+        db.collection("accounts")
+            .unwrap()
+            .recovery_report()
+            .is_clean()
+    );
+    assert_eq!(
+        balance(db.collection("accounts").unwrap().find_by_id(1)),
+        Some(70)
+    );
+    assert_eq!(db.collection("transfers").unwrap().find_all().len(), 1);
+
+    // Checkpoints keep the prepared writes, a restart commits them
+    let mut transaction = db.begin();
+    transaction
+        .update("accounts", 1, account("ann", 20))
+        .unwrap();
+    transaction.insert("transfers", transfer(1, 2, 50)).unwrap();
+    let committed = transaction.prepare().unwrap();
+    db.collection("accounts").unwrap().checkpoint().unwrap();
+    drop(db);
+
+    let mut db = open_bank(&directory);
+    db.commit_prepared(committed.id).unwrap();
+    drop(db);
+    let mut db = open_bank(&directory);
+    assert_eq!(
+        balance(db.collection("accounts").unwrap().find_by_id(1)),
+        Some(20)
+    );
+    let transfers = db.collection("transfers").unwrap();
+    assert!(
+        transfers
+            .find_by_id(committed.inserted["transfers"][0])
+            .is_some()
+    );
+    assert_eq!(transfers.find_all().len(), 2);
+    assert!(db.transactions.as_ref().unwrap().decided().is_empty());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
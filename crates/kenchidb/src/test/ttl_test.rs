@@ -0,0 +1,175 @@
+use std::{
+    fs,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    database::{Collection, Database},
+    define_schema,
+    schema::{Document, current_time_millis},
+    storage::{
+        paged_database::PagedDatabase,
+        sweeper::{ExpirySweeper, SweeperConfig},
+    },
+};
+
+define_schema! {
+    Session {
+        token: string,
+        created_at: long,
+    }
+}
+
+fn ttl_directory(name: &str) -> std::path::PathBuf {
+    let directory = std::env::temp_dir().join(format!("kenchidb_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+fn session(token: &str, created_at: i64) -> Document {
+    Session::create()
+        .set("token", token)
+        .set("created_at", created_at)
+        .build()
+}
+
+#[test]
+fn test_ttl_hides_and_purges_expired() {
+    let mut sessions = Collection::new(Session::schema());
+    sessions
+        .set_ttl("created_at", Duration::from_secs(60))
+        .unwrap();
+
+    let now = current_time_millis();
+    let expired = sessions.insert(session("old", now - 120_000)).unwrap();
+    let live = sessions.insert(session("new", now)).unwrap();
+
+    assert!(sessions.find_by_id(expired).is_none());
+    assert!(sessions.find_by_id(live).is_some());
+    assert_eq!(sessions.find_all().len(), 1);
+
+    assert_eq!(sessions.purge_expired().unwrap(), 1);
+    assert_eq!(sessions.documents.len(), 1);
+}
+
+#[test]
+fn test_ttl_requires_long_field() {
+    let mut sessions = Collection::new(Session::schema());
+    assert!(sessions.set_ttl("token", Duration::from_secs(1)).is_err());
+    assert!(sessions.set_ttl("missing", Duration::from_secs(1)).is_err());
+}
+
+#[test]
+fn test_ttl_is_kept_in_the_catalog() {
+    let directory = ttl_directory("ttl_catalog");
+    let path = directory.join("sessions.catalog");
+    let now = current_time_millis();
+    {
+        let mut db = Database::with_catalog(&path).unwrap();
+        db.create_collection_with_file(
+            "sessions".to_string(),
+            Session::schema(),
+            directory.join("sessions.data"),
+        )
+        .unwrap();
+        db.set_ttl("sessions", "created_at", Duration::from_secs(60))
+            .unwrap();
+        assert!(
+            db.set_ttl("missing", "created_at", Duration::from_secs(60))
+                .is_err()
+        );
+        let sessions = db.collection("sessions").unwrap();
+        sessions.insert(session("old", now - 120_000)).unwrap();
+        sessions.insert(session("new", now)).unwrap();
+    }
+
+    let mut db = Database::open(&path).unwrap();
+    let sessions = db.collection("sessions").unwrap();
+    let ttl = sessions.ttl.clone().unwrap();
+    assert_eq!(ttl.field, "created_at");
+    assert_eq!(ttl.expire_after, Duration::from_secs(60));
+    assert_eq!(sessions.find_all().len(), 1);
+    assert_eq!(db.purge_expired().unwrap(), 1);
+
+    db.clear_ttl("sessions").unwrap();
+    drop(db);
+    let mut db = Database::open(&path).unwrap();
+    assert!(db.collection("sessions").unwrap().ttl.is_none());
+    drop(db);
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn test_paged_database_keeps_ttl_in_its_catalog() {
+    let path = ttl_directory("ttl_paged").join("sessions.pages");
+    let now = current_time_millis();
+    {
+        let mut database = PagedDatabase::open(&path).unwrap();
+        database
+            .create_collection("sessions", Session::schema())
+            .unwrap();
+        database
+            .set_ttl("sessions", "created_at", Duration::from_secs(60))
+            .unwrap();
+        database
+            .with_collection("sessions", |sessions| {
+                sessions.insert(session("old", now - 120_000))?;
+                sessions.insert(session("new", now))
+            })
+            .unwrap();
+        database.flush().unwrap();
+    }
+
+    let mut database = PagedDatabase::open(&path).unwrap();
+    let (ttl, count) = database
+        .with_collection("sessions", |sessions| {
+            Ok((sessions.ttl.clone(), sessions.iter().count()))
+        })
+        .unwrap();
+    assert_eq!(ttl.unwrap().expire_after, Duration::from_secs(60));
+    assert_eq!(count, 1);
+    assert_eq!(database.purge_expired().unwrap(), 1);
+
+    database.clear_ttl("sessions").unwrap();
+    drop(database);
+    let mut database = PagedDatabase::open(&path).unwrap();
+    let ttl = database
+        .with_collection("sessions", |sessions| Ok(sessions.ttl.clone()))
+        .unwrap();
+    assert!(ttl.is_none());
+    drop(database);
+
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_sweeper_removes_expired_documents() {
+    let mut sessions = Collection::new(Session::schema());
+    sessions
+        .set_ttl("created_at", Duration::from_secs(60))
+        .unwrap();
+    let now = current_time_millis();
+    for number in 0..3 {
+        sessions
+            .insert(session(&format!("old {}", number), now - 120_000))
+            .unwrap();
+    }
+    sessions.insert(session("new", now)).unwrap();
+
+    let sessions = Arc::new(Mutex::new(sessions));
+    let config = SweeperConfig {
+        interval: Duration::from_secs(3600),
+    };
+    let sweeper = ExpirySweeper::start(Arc::clone(&sessions), Collection::purge_expired, config);
+    sweeper.sweep_now();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while sweeper.removed() < 3 && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(sweeper.removed(), 3);
+    sweeper.stop().unwrap();
+    assert_eq!(sessions.lock().unwrap().documents.len(), 1);
+}
@@ -0,0 +1,78 @@
+use crate::{
+    database::Collection,
+    define_schema,
+    query::{Expr, Query, Update},
+    schema::Value,
+};
+
+define_schema! {
+    Account {
+        owner: string,
+        balance: double,
+        visits: int,
+        total: long,
+    }
+}
+
+fn accounts() -> Collection {
+    let mut accounts = Collection::new(Account::schema());
+    for (owner, balance) in [("alice", 100.0f64), ("bob", 50.0f64)] {
+        accounts
+            .insert(
+                Account::create()
+                    .set("owner", owner)
+                    .set("balance", balance)
+                    .set("visits", 1i32)
+                    .set("total", 10i64)
+                    .build(),
+            )
+            .unwrap();
+    }
+    accounts
+}
+
+#[test]
+fn test_update_where_with_expressions() {
+    let mut accounts = accounts();
+
+    let update = Update::new()
+        .set("balance", Expr::field("balance") + 10.0)
+        .set("visits", Expr::field("visits") * 2i32 + 1i32)
+        .set("total", Expr::field("total") + Expr::field("visits"))
+        .set("owner", Expr::field("owner") + "!");
+    let count = accounts
+        .update_where(Query::eq("owner", "alice"), &update)
+        .unwrap();
+    assert_eq!(count, 1);
+
+    let alice = accounts
+        .find_one_where(Query::eq("owner", "alice!"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(alice.get("balance"), Some(&Value::Double(110.0)));
+    assert_eq!(alice.get("visits"), Some(&Value::Int(3)));
+    // int widened to long
+    assert_eq!(alice.get("total"), Some(&Value::Long(11)));
+}
+
+#[test]
+fn test_update_where_is_all_or_nothing() {
+    let mut accounts = accounts();
+
+    // Double result does not fit the int field
+    let update = Update::new().set("visits", Expr::field("visits") + 0.5);
+    assert!(accounts.update_where(Query::all(), &update).is_err());
+
+    let update = Update::new().set("visits", Expr::field("visits") / 0i32);
+    assert!(accounts.update_where(Query::all(), &update).is_err());
+
+    let update = Update::new().set("visits", -Expr::value(i32::MIN));
+    assert!(accounts.update_where(Query::all(), &update).is_err());
+
+    assert!(
+        accounts
+            .find_all()
+            .iter()
+            .all(|doc| doc.get("visits") == Some(&Value::Int(1)))
+    );
+}
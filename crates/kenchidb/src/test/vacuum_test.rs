@@ -0,0 +1,107 @@
+use std::fs;
+
+use crate::{
+    database::Database,
+    define_schema,
+    schema::{Document, Value},
+    storage::{
+        paged_collection::{PagedCollection, free_space_map_path, primary_key_index_path},
+        wal::wal_path,
+    },
+};
+
+define_schema! {
+    Note {
+        text: string,
+    }
+}
+
+fn note(number: u64) -> Document {
+    let text = format!("{:0>200}", number);
+    Note::create().set("text", text.as_str()).build()
+}
+
+fn vacuum_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("kenchidb_{}_{}.pages", name, std::process::id()))
+}
+
+#[test]
+fn test_vacuum_compacts_the_paged_collection() {
+    let path = vacuum_path("vacuum_paged");
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+
+    let mut notes = PagedCollection::new(Note::schema(), 1, &path).unwrap();
+    for number in 1..=400 {
+        notes.insert(note(number)).unwrap();
+    }
+    for id in 1..=400 {
+        if id % 10 != 0 {
+            notes.delete(id).unwrap();
+        }
+    }
+    notes.flush().unwrap();
+    let pages_before = notes.stats().total_pages;
+
+    let report = notes.vacuum().unwrap();
+    assert_eq!(
+        report.bytes_before,
+        fs::metadata(&path).unwrap().len() + report.bytes_reclaimed()
+    );
+    assert!(notes.stats().total_pages < pages_before / 5);
+    assert_eq!(notes.stats().free_pages, 0);
+
+    // Ids are kept, new documents continue after them
+    assert_eq!(notes.ids(), (1..=40).map(|i| i * 10).collect::<Vec<u64>>());
+    let kept = notes.find_by_id(120).unwrap().unwrap();
+    assert_eq!(
+        kept.get("text"),
+        Some(&Value::String(format!("{:0>200}", 120)))
+    );
+    assert!(notes.find_by_id(121).unwrap().is_none());
+    assert_eq!(notes.insert(note(401)).unwrap(), 401);
+    notes.flush().unwrap();
+    drop(notes);
+
+    let mut notes = PagedCollection::new(Note::schema(), 1, &path).unwrap();
+    assert_eq!(notes.ids().len(), 41);
+    assert!(notes.find_by_id(401).unwrap().is_some());
+
+    fs::remove_file(&path).unwrap();
+    let _ = fs::remove_file(primary_key_index_path(&path));
+    let _ = fs::remove_file(free_space_map_path(&path));
+}
+
+#[test]
+fn test_vacuum_checkpoints_the_collection_file() {
+    let path = vacuum_path("vacuum_database");
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(wal_path(&path));
+
+    let mut db = Database::new();
+    db.create_collection_with_file("notes".to_string(), Note::schema(), &path)
+        .unwrap();
+    let notes = db.collection("notes").unwrap();
+    for number in 1..=50 {
+        notes.insert(note(number)).unwrap();
+    }
+    for id in 1..=40 {
+        notes.delete(id).unwrap();
+    }
+
+    let report = db.vacuum("notes").unwrap();
+    assert!(report.bytes_reclaimed() > 0);
+    assert_eq!(fs::metadata(wal_path(&path)).unwrap().len(), 0);
+    assert_eq!(report.bytes_after, fs::metadata(&path).unwrap().len());
+    assert_eq!(db.collection("notes").unwrap().find_all().len(), 10);
+
+    assert!(db.vacuum("missing").is_err());
+    db.create_collection("scratch".to_string(), Note::schema())
+        .unwrap();
+    assert!(db.vacuum("scratch").is_err());
+
+    drop(db);
+    fs::remove_file(&path).unwrap();
+    let _ = fs::remove_file(wal_path(&path));
+}
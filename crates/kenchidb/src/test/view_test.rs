@@ -0,0 +1,124 @@
+use std::fs;
+
+use crate::{
+    common::DatabaseError,
+    database::Database,
+    define_schema,
+    query::{Direction, Query},
+};
+
+define_schema! {
+    Member {
+        name: string,
+        age: int,
+        is_active: boolean,
+    }
+}
+
+fn catalog_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("kenchidb_{}_{}.catalog", name, std::process::id()))
+}
+
+fn populate(db: &mut Database) {
+    db.create_collection("members".to_string(), Member::schema())
+        .unwrap();
+    let members = db.collection("members").unwrap();
+    for (name, age, is_active) in [("ann", 34, true), ("ben", 15, true), ("cid", 52, false)] {
+        members
+            .insert(
+                Member::create()
+                    .set("name", name)
+                    .set("age", age)
+                    .set("is_active", is_active)
+                    .build(),
+            )
+            .unwrap();
+    }
+}
+
+#[test]
+fn test_create_and_find_view() {
+    let mut db = Database::new();
+    populate(&mut db);
+
+    db.create_view(
+        "active_adults",
+        "members",
+        Query::eq("is_active", true).and(Query::gte("age", 18i32)),
+    )
+    .unwrap();
+    db.create_view(
+        "by_age",
+        "members",
+        Query::all().order_by("age", Direction::Desc).limit(2),
+    )
+    .unwrap();
+
+    let names: Vec<&str> = db.views().iter().map(|v| v.name.as_str()).collect();
+    assert_eq!(names, ["active_adults", "by_age"]);
+
+    assert_eq!(db.find_view("active_adults").unwrap().len(), 1);
+    assert_eq!(db.find_view("by_age").unwrap().len(), 2);
+
+    db.drop_view("by_age").unwrap();
+    assert!(db.view("by_age").is_none());
+    assert!(matches!(
+        db.find_view("by_age"),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+}
+
+#[test]
+fn test_create_view_rejects_invalid_definitions() {
+    let mut db = Database::new();
+    populate(&mut db);
+
+    assert!(db.create_view("adults", "missing", Query::all()).is_err());
+    assert!(
+        db.create_view("adults", "members", Query::gt("age", "18"))
+            .is_err()
+    );
+    assert!(matches!(
+        db.create_view("1adults", "members", Query::all()),
+        Err(DatabaseError::InvalidIdentifier(_))
+    ));
+
+    db.create_view("adults", "members", Query::gte("age", 18i32))
+        .unwrap();
+    assert!(db.create_view("adults", "members", Query::all()).is_err());
+}
+
+#[test]
+fn test_views_persist_in_catalog() {
+    let path = catalog_path("views");
+    let _ = fs::remove_file(&path);
+
+    {
+        let mut db = Database::with_catalog(&path).unwrap();
+        populate(&mut db);
+        db.create_view(
+            "matching",
+            "members",
+            Query::like_ignore_case("name", "A%")
+                .or(Query::between("age", 50i32, 60i32))
+                .and(!Query::is_in("name", ["ben"]))
+                .order_by("age", Direction::Asc)
+                .offset(1)
+                .limit(5),
+        )
+        .unwrap();
+    }
+
+    let mut db = Database::with_catalog(&path).unwrap();
+    let view = db.view("matching").unwrap();
+    assert_eq!(view.collection, "members");
+    assert_eq!(view.query.offset, 1);
+    assert_eq!(view.query.limit, Some(5));
+
+    populate(&mut db);
+    let results = db.find_view("matching").unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].get("name"), Some(&"cid".into()));
+
+    fs::remove_file(&path).unwrap();
+}
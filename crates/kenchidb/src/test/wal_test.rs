@@ -0,0 +1,166 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+
+use crate::{
+    database::Collection,
+    define_schema,
+    schema::Value,
+    storage::wal::{SyncMode, WalRecord, WriteAheadLog, wal_path},
+};
+
+define_schema! {
+    Entry {
+        number: long auto_increment,
+        text: string,
+    }
+}
+
+fn entry(text: &str) -> crate::schema::Document {
+    Entry::create().set("text", text).build()
+}
+
+fn texts(collection: &Collection) -> Vec<String> {
+    let mut texts: Vec<String> = collection
+        .find_all()
+        .into_iter()
+        .map(|document| match document.get("text") {
+            Some(Value::String(text)) => text.clone(),
+            other => panic!("unexpected text {:?}", other),
+        })
+        .collect();
+    texts.sort();
+    texts
+}
+
+#[test]
+fn test_writes_are_replayed_from_the_log() {
+    let path = std::env::temp_dir().join(format!("kenchidb_wal_{}.db", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(wal_path(&path));
+
+    {
+        let mut entries = Collection::with_file(Entry::schema(), &path).unwrap();
+        entries.insert(entry("a")).unwrap();
+        entries.checkpoint().unwrap();
+        assert_eq!(fs::metadata(wal_path(&path)).unwrap().len(), 0);
+
+        entries.insert(entry("b")).unwrap();
+        entries.insert(entry("c")).unwrap();
+        let replaced = Entry::create().set("number", 2i64).set("text", "B").build();
+        entries.update(2, replaced).unwrap();
+        entries.delete(1).unwrap();
+    }
+
+    // The collection file still holds the checkpoint, the log the writes after it
+    let checkpointed = fs::read(&path).unwrap();
+    let mut entries = Collection::with_file(Entry::schema(), &path).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), checkpointed);
+    assert_eq!(texts(&entries), vec!["B", "c"]);
+
+    // Ids and sequences continue after the replayed writes
+    let id = entries.insert(entry("d")).unwrap();
+    assert_eq!(id, 4);
+    assert_eq!(
+        entries.find_by_id(id).unwrap().get("number"),
+        Some(&Value::Long(4))
+    );
+
+    entries.checkpoint().unwrap();
+    assert_eq!(fs::metadata(wal_path(&path)).unwrap().len(), 0);
+    drop(entries);
+    let entries = Collection::with_file(Entry::schema(), &path).unwrap();
+    assert_eq!(texts(&entries), vec!["B", "c", "d"]);
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(wal_path(&path)).unwrap();
+}
+
+#[test]
+fn test_torn_and_uncommitted_tail_is_discarded() {
+    let path = std::env::temp_dir().join(format!("kenchidb_wal_torn_{}.db", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(wal_path(&path));
+
+    {
+        let mut entries = Collection::with_file(Entry::schema(), &path).unwrap();
+        entries.insert(entry("kept")).unwrap();
+    }
+    let committed = fs::metadata(wal_path(&path)).unwrap().len();
+
+    // A write whose commit record never made it, followed by half a frame
+    let (mut wal, records) = WriteAheadLog::open(wal_path(&path)).unwrap();
+    assert_eq!(records.len(), 2);
+    let mut lost = entry("lost");
+    lost.id = 2;
+    wal.append(&[WalRecord::Put(lost)]).unwrap();
+    drop(wal);
+    let mut file = OpenOptions::new()
+        .append(true)
+        .open(wal_path(&path))
+        .unwrap();
+    file.write_all(&[42, 0, 0, 0, 1, 2]).unwrap();
+    drop(file);
+
+    let discarded = fs::metadata(wal_path(&path)).unwrap().len() - committed;
+    let mut entries = Collection::with_file(Entry::schema(), &path).unwrap();
+    assert_eq!(texts(&entries), vec!["kept"]);
+    assert_eq!(fs::metadata(wal_path(&path)).unwrap().len(), committed);
+    let report = entries.recovery_report();
+    assert_eq!(report.wal_records_replayed, 1);
+    assert_eq!(report.wal_bytes_discarded, discarded);
+    assert!(!report.is_clean());
+
+    // New writes follow the last committed one
+    entries.insert(entry("next")).unwrap();
+    drop(entries);
+    let entries = Collection::with_file(Entry::schema(), &path).unwrap();
+    assert_eq!(texts(&entries), vec!["kept", "next"]);
+    assert!(entries.recovery_report().is_clean());
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(wal_path(&path)).unwrap();
+}
+
+#[test]
+fn test_sync_modes_group_commits() {
+    let path = std::env::temp_dir().join(format!("kenchidb_wal_sync_{}.db", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(wal_path(&path));
+
+    let mut entries = Collection::with_file(Entry::schema(), &path).unwrap();
+    let unsynced = |entries: &Collection| entries.wal.as_ref().unwrap().unsynced_commits();
+
+    entries.insert(entry("always")).unwrap();
+    assert_eq!(unsynced(&entries), 0);
+
+    // One sync for every third commit
+    entries.set_sync_mode(SyncMode::EveryN(3));
+    entries.insert(entry("a")).unwrap();
+    entries.insert(entry("b")).unwrap();
+    assert_eq!(unsynced(&entries), 2);
+    entries.insert(entry("c")).unwrap();
+    assert_eq!(unsynced(&entries), 0);
+
+    entries.set_sync_mode(SyncMode::Periodic(60_000));
+    entries.insert(entry("d")).unwrap();
+    assert_eq!(unsynced(&entries), 1);
+    entries.set_sync_mode(SyncMode::Periodic(0));
+    entries.insert(entry("e")).unwrap();
+    assert_eq!(unsynced(&entries), 0);
+
+    entries.set_sync_mode(SyncMode::Never);
+    entries.insert(entry("f")).unwrap();
+    entries.insert(entry("g")).unwrap();
+    assert_eq!(unsynced(&entries), 2);
+    entries.sync().unwrap();
+    assert_eq!(unsynced(&entries), 0);
+
+    // Commits held back are written all the same
+    entries.insert(entry("h")).unwrap();
+    drop(entries);
+    let entries = Collection::with_file(Entry::schema(), &path).unwrap();
+    assert_eq!(entries.find_all().len(), 9);
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(wal_path(&path)).unwrap();
+}
@@ -0,0 +1,116 @@
+use std::fs;
+
+use crate::{
+    common::DatabaseError,
+    storage::{
+        buffer_pool::BufferPool,
+        file_manager::FileManager,
+        page::{Page, PageType},
+        warmup::{WarmupPlan, WarmupReport},
+    },
+};
+
+fn warmup_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("kenchidb_{}_{}.pages", name, std::process::id()))
+}
+
+#[test]
+fn test_buffer_pool_evicts_least_recently_used() {
+    let mut pool = BufferPool::new(2);
+    pool.insert(1, Page::new(PageType::DataPage, 1));
+    pool.insert(2, Page::new(PageType::DataPage, 1));
+    assert!(pool.get(1).is_some());
+
+    // Page 2 was used least recently
+    pool.insert(3, Page::new(PageType::IndexPage, 0));
+    assert!(pool.contains(1));
+    assert!(!pool.contains(2));
+    assert_eq!(pool.count(PageType::IndexPage), 1);
+    assert!(pool.get(2).is_none());
+    assert_eq!((pool.hits, pool.misses), (1, 1));
+
+    let mut empty = BufferPool::new(0);
+    empty.insert(1, Page::new(PageType::DataPage, 1));
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn test_warmup_loads_pages_by_priority() {
+    let path = warmup_path("warmup");
+    let _ = fs::remove_file(&path);
+
+    // Data pages of collections 1 and 2, then an index page and a meta page
+    let mut file_manager = FileManager::new(&path).unwrap();
+    for (page_type, collection_id) in [
+        (PageType::DataPage, 1),
+        (PageType::DataPage, 2),
+        (PageType::DataPage, 1),
+        (PageType::DataPage, 2),
+        (PageType::IndexPage, 1),
+        (PageType::MetaPage, 0),
+    ] {
+        let (page_id, mut page) = file_manager
+            .allocate_page(page_type, collection_id)
+            .unwrap();
+        page.insert_record(&page_id.to_le_bytes()).unwrap();
+        file_manager.write_page(page_id, &mut page).unwrap();
+    }
+    drop(file_manager);
+
+    let mut file_manager = FileManager::new(&path).unwrap();
+    assert!(matches!(
+        file_manager.warmup(&WarmupPlan::new()),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+
+    // Metadata and indexes come first, the collection fills the rest of the pool
+    file_manager.enable_buffer_pool(3);
+    let report = file_manager
+        .warmup(&WarmupPlan::new().with_collection(2))
+        .unwrap();
+    assert_eq!(
+        report,
+        WarmupReport {
+            pages_loaded: 3,
+            pages_skipped: 1,
+        }
+    );
+    let pool = file_manager.buffer_pool().unwrap();
+    assert!(pool.contains(5) && pool.contains(4) && pool.contains(1));
+    assert_eq!((pool.hits, pool.misses), (0, 0));
+
+    // Warmed pages are served from memory
+    let page = file_manager.read_page(1).unwrap();
+    assert_eq!(page.get_record(0).unwrap(), &1u32.to_le_bytes());
+    file_manager.read_page(0).unwrap();
+    let pool = file_manager.buffer_pool().unwrap();
+    assert_eq!((pool.hits, pool.misses), (1, 1));
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_buffer_pool_writes_through() {
+    let path = warmup_path("write_through");
+    let _ = fs::remove_file(&path);
+
+    let mut file_manager = FileManager::new(&path).unwrap();
+    file_manager.enable_buffer_pool(4);
+    let (page_id, mut page) = file_manager.allocate_page(PageType::DataPage, 1).unwrap();
+    page.insert_record(b"first").unwrap();
+    file_manager.write_page(page_id, &mut page).unwrap();
+
+    let mut cached = file_manager.read_page(page_id).unwrap();
+    cached.insert_record(b"second").unwrap();
+    file_manager.write_page(page_id, &mut cached).unwrap();
+    assert_eq!(
+        file_manager.read_page(page_id).unwrap().live_record_count(),
+        2
+    );
+    drop(file_manager);
+
+    let mut reopened = FileManager::new(&path).unwrap();
+    assert_eq!(reopened.read_page(page_id).unwrap().live_record_count(), 2);
+
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,146 @@
+use std::{sync::mpsc::Receiver, time::Duration};
+
+use crate::{
+    common::DatabaseError,
+    database::Collection,
+    define_schema,
+    query::{DocumentPatch, Query},
+    schema::{Value, current_time_millis},
+    watch::ChangeEvent,
+};
+
+define_schema! {
+    Task {
+        title: string,
+        done: boolean,
+        created_at: long,
+    }
+}
+
+fn task(title: &str, done: bool) -> crate::schema::Document {
+    Task::create()
+        .set("title", title)
+        .set("done", done)
+        .set("created_at", current_time_millis())
+        .build()
+}
+
+/// Kind and id of every event received so far
+fn drain(receiver: &Receiver<ChangeEvent>) -> Vec<(&'static str, u64)> {
+    receiver
+        .try_iter()
+        .map(|event| {
+            let kind = match event {
+                ChangeEvent::Insert(_) => "insert",
+                ChangeEvent::Update { .. } => "update",
+                ChangeEvent::Delete(_) => "delete",
+            };
+            (kind, event.id())
+        })
+        .collect()
+}
+
+#[test]
+fn test_watch_delivers_matching_changes() {
+    let mut collection = Collection::new(Task::schema());
+    let all = collection.watch(Query::all()).unwrap();
+    let open = collection.watch(Query::eq("done", false)).unwrap();
+
+    let first = collection.insert(task("write", false)).unwrap();
+    let second = collection.insert(task("review", true)).unwrap();
+    collection.update(first, task("write", true)).unwrap();
+    collection
+        .patch(second, &DocumentPatch::new().set("title", "merge"))
+        .unwrap();
+    collection.delete(second).unwrap();
+
+    assert_eq!(
+        drain(&all),
+        vec![
+            ("insert", first),
+            ("insert", second),
+            ("update", first),
+            ("update", second),
+            ("delete", second),
+        ]
+    );
+    // The update of the first task is delivered as it leaves the filter
+    assert_eq!(drain(&open), vec![("insert", first), ("update", first)]);
+
+    collection.insert(task("deploy", true)).unwrap();
+    assert!(drain(&open).is_empty());
+}
+
+#[test]
+fn test_watch_event_contents() {
+    let mut collection = Collection::new(Task::schema());
+    let receiver = collection.watch(Query::all()).unwrap();
+    let id = collection.insert(task("write", false)).unwrap();
+    collection.update(id, task("rewrite", false)).unwrap();
+    receiver.recv().unwrap();
+
+    match receiver.recv().unwrap() {
+        ChangeEvent::Update { before, after } => {
+            assert_eq!(
+                before.get("title"),
+                Some(&Value::String("write".to_string()))
+            );
+            assert_eq!(
+                after.get("title"),
+                Some(&Value::String("rewrite".to_string()))
+            );
+        }
+        event => panic!("unexpected event {:?}", event),
+    }
+}
+
+#[test]
+fn test_session_changes_are_delivered_on_commit() {
+    let mut collection = Collection::new(Task::schema());
+    let kept = collection.insert(task("write", false)).unwrap();
+    let removed = collection.insert(task("review", false)).unwrap();
+    let receiver = collection.watch(Query::all()).unwrap();
+
+    let mut session = collection.session();
+    let inserted = session.insert(task("deploy", false)).unwrap();
+    session.update(inserted, task("deploy", true)).unwrap();
+    session.update(kept, task("write", true)).unwrap();
+    session.delete(removed).unwrap();
+    let temporary = session.insert(task("draft", false)).unwrap();
+    session.delete(temporary).unwrap();
+    assert!(drain(&receiver).is_empty());
+
+    let ids = collection.commit(session).unwrap();
+    assert_eq!(
+        drain(&receiver),
+        vec![("insert", ids[0]), ("update", kept), ("delete", removed)]
+    );
+}
+
+#[test]
+fn test_watch_expired_documents_and_dropped_receivers() {
+    let mut collection = Collection::new(Task::schema());
+    collection
+        .set_ttl("created_at", Duration::from_millis(0))
+        .unwrap();
+    let receiver = collection.watch(Query::all()).unwrap();
+    let dropped = collection.watch(Query::all()).unwrap();
+    drop(dropped);
+    assert_eq!(collection.watcher_count(), 2);
+
+    let id = collection.insert(task("stale", false)).unwrap();
+    assert_eq!(collection.watcher_count(), 1);
+    std::thread::sleep(Duration::from_millis(2));
+    assert_eq!(collection.purge_expired().unwrap(), 1);
+    assert_eq!(drain(&receiver), vec![("insert", id), ("delete", id)]);
+
+    // Uncommitted session writes do not notify
+    let mut session = collection.session();
+    session.insert(task("draft", false)).unwrap();
+    assert!(drain(&receiver).is_empty());
+
+    assert!(matches!(
+        collection.watch(Query::eq("missing", 1i32)),
+        Err(DatabaseError::InvalidQuery(_))
+    ));
+}
@@ -0,0 +1,390 @@
+use std::{
+    collections::{BTreeMap, btree_map::Entry},
+    path::PathBuf,
+};
+
+use crate::{
+    common::{DatabaseError, Random},
+    database::{Collection, Database},
+    schema::Document,
+    session::{Session, StagedCommit},
+    storage::wal::{DEFAULT_CHECKPOINT_SIZE, DecidedTransaction, TransactionLog, WalRecord},
+};
+
+/// Unit of work across the collections of a database.
+///
+/// Each collection is written through a `Session` started when the transaction first
+/// touches it, reads see the collection as it was then plus the transaction's own writes.
+/// `commit` applies the writes to every collection or to none of them, `rollback` or
+/// dropping the transaction discards them.
+///
+/// Writes to several collection files are first prepared in the write-ahead log of each
+/// collection, then the commit is recorded in the transaction log next to the catalog.
+/// After a crash before that record the transaction is discarded in every collection,
+/// after it the transaction is kept in every collection reopened through the database.
+///
+/// An external coordinator takes the commit decision itself with `prepare` followed by
+/// `Database::commit_prepared` or `Database::rollback_prepared`.
+pub struct Transaction<'a> {
+    database: &'a mut Database,
+    sessions: BTreeMap<String, Session>,
+}
+
+/// Transaction prepared for an external coordinator, see `Transaction::prepare`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreparedTransaction {
+    /// Id to commit or roll the transaction back with
+    pub id: u64,
+    /// Final ids of the inserted documents of each collection written, in insert order.
+    /// The documents exist once the transaction commits.
+    pub inserted: BTreeMap<String, Vec<u64>>,
+}
+
+/// Writes of a prepared transaction held by a collection, ending with the prepare record
+pub(crate) struct PreparedWrites {
+    pub(crate) transaction: u64,
+    pub(crate) records: Vec<WalRecord>,
+}
+
+impl<'a> Transaction<'a> {
+    pub(crate) fn new(database: &'a mut Database) -> Self {
+        Self {
+            database,
+            sessions: BTreeMap::new(),
+        }
+    }
+
+    /// The collection as seen by the transaction
+    pub fn collection(&mut self, name: &str) -> Result<&Session, DatabaseError> {
+        Ok(self.session(name)?)
+    }
+
+    /// Insert a document, returns its provisional id, see `Session`
+    pub fn insert(&mut self, collection: &str, document: Document) -> Result<u64, DatabaseError> {
+        self.session(collection)?.insert(document)
+    }
+
+    pub fn update(
+        &mut self,
+        collection: &str,
+        id: u64,
+        document: Document,
+    ) -> Result<(), DatabaseError> {
+        self.session(collection)?.update(id, document)
+    }
+
+    pub fn delete(&mut self, collection: &str, id: u64) -> Result<(), DatabaseError> {
+        self.session(collection)?.delete(id)
+    }
+
+    /// Number of writes waiting for the commit
+    pub fn pending_writes(&self) -> usize {
+        self.sessions.values().map(Session::pending_writes).sum()
+    }
+
+    /// Apply the writes to all collections atomically, returns the final ids of the inserted
+    /// documents of each collection in insert order. Nothing is applied if any write fails.
+    /// Writes to several collection files need a database with a catalog.
+    pub fn commit(self) -> Result<BTreeMap<String, Vec<u64>>, DatabaseError> {
+        let Transaction { database, sessions } = self;
+
+        let mut commits = Vec::with_capacity(sessions.len());
+        for (name, session) in sessions {
+            let collection = database.existing_collection(&name)?;
+            let commit = collection.stage(session)?;
+            commits.push((name, commit));
+        }
+
+        // Collections with writes for their write-ahead log
+        let logged: Vec<String> = commits
+            .iter()
+            .filter(|(_, commit)| {
+                let records = &commit.records;
+                records.as_ref().is_some_and(|records| !records.is_empty())
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        if logged.len() > 1 && database.transactions.is_none() {
+            return Err(DatabaseError::InvalidQuery(
+                "Transaction writing several collection files needs a database with a catalog"
+                    .to_string(),
+            ));
+        }
+
+        if logged.len() <= 1 {
+            let mut inserted = BTreeMap::new();
+            for (name, commit) in commits {
+                let collection = database.existing_collection(&name)?;
+                inserted.insert(name, collection.apply(commit));
+                collection.persist()?;
+            }
+            return Ok(inserted);
+        }
+
+        let transaction = Random::new().next_u64();
+        let mut participants = Vec::with_capacity(logged.len());
+        for (name, commit) in &mut commits {
+            if !logged.contains(name) {
+                continue;
+            }
+            let collection = database.existing_collection(name)?;
+            if let Err(e) = collection.prepare_commit(commit, transaction) {
+                database.abort_prepared(&logged);
+                return Err(e);
+            }
+            participants.extend(collection.wal.as_ref().map(|wal| wal.path().to_path_buf()));
+        }
+
+        let decision = DecidedTransaction {
+            id: transaction,
+            participants,
+            in_doubt: false,
+        };
+        if let Some(log) = &mut database.transactions
+            && let Err(e) = log.decide(decision)
+        {
+            database.abort_prepared(&logged);
+            return Err(e);
+        }
+
+        // Committed, the prepared writes survive a crash from here on
+        let mut inserted = BTreeMap::new();
+        for (name, commit) in commits {
+            let collection = database.existing_collection(&name)?;
+            inserted.insert(name, collection.apply(commit));
+        }
+
+        let mut completed = Vec::with_capacity(logged.len());
+        let mut result = Ok(());
+        for name in &logged {
+            let collection = database.existing_collection(name)?;
+            match collection.complete_prepared() {
+                Ok(path) => completed.extend(path),
+                Err(e) => result = Err(e),
+            }
+        }
+        if let Some(log) = &mut database.transactions {
+            log.resolve(transaction, &completed)?;
+        }
+
+        result.map(|_| inserted)
+    }
+
+    /// First phase of a commit decided by an external coordinator. The writes are checked
+    /// and made durable in the write-ahead logs, and the transaction is logged in doubt.
+    /// `Database::commit_prepared` makes them visible and `Database::rollback_prepared`
+    /// drops them, after a restart too. Until then the collections written take no other
+    /// writes, those fail with `DatabaseError::TransactionPrepared`.
+    /// Needs a database with a catalog and a file for each collection written.
+    pub fn prepare(self) -> Result<PreparedTransaction, DatabaseError> {
+        let Transaction { database, sessions } = self;
+        if database.transactions.is_none() {
+            return Err(DatabaseError::InvalidQuery(
+                "Preparing a transaction needs a database with a catalog".to_string(),
+            ));
+        }
+
+        let mut commits = Vec::with_capacity(sessions.len());
+        for (name, session) in sessions {
+            if session.pending_writes() == 0 {
+                continue;
+            }
+            let collection = database.existing_collection(&name)?;
+            if collection.wal.is_none() {
+                return Err(DatabaseError::InvalidQuery(format!(
+                    "Collection '{}' has no file to prepare its writes in",
+                    name
+                )));
+            }
+            let commit = collection.stage(session)?;
+            commits.push((name, commit));
+        }
+
+        let id = Random::new().next_u64();
+        let names: Vec<String> = commits.iter().map(|(name, _)| name.clone()).collect();
+        let mut inserted = BTreeMap::new();
+        let mut prepared = Vec::with_capacity(commits.len());
+        let mut participants = Vec::with_capacity(commits.len());
+        for (name, mut commit) in commits {
+            let collection = database.existing_collection(&name)?;
+            let records = match collection.prepare_commit(&mut commit, id) {
+                Ok(records) => records,
+                Err(e) => {
+                    database.abort_prepared(&names);
+                    return Err(e);
+                }
+            };
+            participants.extend(collection.wal.as_ref().map(|wal| wal.path().to_path_buf()));
+            inserted.insert(name.clone(), commit.inserted);
+            prepared.push((name, records));
+        }
+
+        let decision = DecidedTransaction {
+            id,
+            participants,
+            in_doubt: true,
+        };
+        if let Some(log) = &mut database.transactions
+            && let Err(e) = log.decide(decision)
+        {
+            database.abort_prepared(&names);
+            return Err(e);
+        }
+
+        for (name, records) in prepared {
+            let collection = database.existing_collection(&name)?;
+            collection.prepared = Some(PreparedWrites {
+                transaction: id,
+                records,
+            });
+        }
+        Ok(PreparedTransaction { id, inserted })
+    }
+
+    /// Discard the writes of the transaction
+    pub fn rollback(self) {}
+
+    fn session(&mut self, collection: &str) -> Result<&mut Session, DatabaseError> {
+        match self.sessions.entry(collection.to_string()) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => Ok(entry.insert(self.database.session(collection)?)),
+        }
+    }
+}
+
+impl Database {
+    /// Start a transaction across collections, see `Transaction`
+    pub fn begin(&mut self) -> Transaction<'_> {
+        Transaction::new(self)
+    }
+
+    /// Commit a transaction prepared by `Transaction::prepare`, its writes become visible
+    /// and are durable once this returns. Prepared writes kept aside when the collections
+    /// were reopened after a restart are committed the same way.
+    pub fn commit_prepared(&mut self, id: u64) -> Result<(), DatabaseError> {
+        let Some(log) = &mut self.transactions else {
+            return Err(not_prepared(id));
+        };
+        if log.commit_in_doubt(id)?.is_none() {
+            return Err(not_prepared(id));
+        }
+
+        // Committed, the prepared writes survive a crash from here on
+        let mut completed = Vec::new();
+        let mut result = Ok(());
+        for collection in self.collections.values_mut() {
+            let Some(prepared) = collection.prepared.take_if(|p| p.transaction == id) else {
+                continue;
+            };
+            let applied = collection.replay(prepared.records).and_then(|_| {
+                // Logged already
+                if let Some(pending) = &mut collection.pending_writes {
+                    pending.clear();
+                }
+                collection.complete_prepared()
+            });
+            match applied {
+                Ok(path) => completed.extend(path),
+                Err(e) => result = Err(e),
+            }
+        }
+        if let Some(log) = &mut self.transactions {
+            log.resolve(id, &completed)?;
+        }
+        result
+    }
+
+    /// Roll back a transaction prepared by `Transaction::prepare`, its writes are dropped
+    pub fn rollback_prepared(&mut self, id: u64) -> Result<(), DatabaseError> {
+        let Some(log) = &mut self.transactions else {
+            return Err(not_prepared(id));
+        };
+        if log.abort(id)?.is_none() {
+            return Err(not_prepared(id));
+        }
+
+        // Rolled back, recovery discards the prepared writes from here on
+        for collection in self.collections.values_mut() {
+            if collection
+                .prepared
+                .take_if(|p| p.transaction == id)
+                .is_some()
+                && let Some(wal) = &mut collection.wal
+            {
+                wal.discard_prepared()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Ids of the transactions prepared by `Transaction::prepare` which are not committed
+    /// or rolled back yet, e.g. to ask their coordinator about them after a restart
+    pub fn prepared_transactions(&self) -> Vec<u64> {
+        self.transactions
+            .as_ref()
+            .map(TransactionLog::in_doubt)
+            .unwrap_or_default()
+    }
+
+    fn existing_collection(&mut self, name: &str) -> Result<&mut Collection, DatabaseError> {
+        self.collection(name)
+            .ok_or_else(|| DatabaseError::InvalidQuery(format!("Collection '{}' not found", name)))
+    }
+
+    /// Cut the prepared writes of a failed transaction from the collection logs.
+    /// Collections that did not prepare yet have nothing to cut.
+    fn abort_prepared(&mut self, collections: &[String]) {
+        for name in collections {
+            if let Ok(collection) = self.existing_collection(name)
+                && let Some(wal) = &mut collection.wal
+            {
+                let _ = wal.discard_prepared();
+            }
+        }
+    }
+}
+
+fn not_prepared(id: u64) -> DatabaseError {
+    DatabaseError::InvalidQuery(format!("Transaction {} is not prepared", id))
+}
+
+impl Collection {
+    /// Fail while the collection holds the writes of a prepared transaction
+    pub(crate) fn check_not_prepared(&self) -> Result<(), DatabaseError> {
+        match &self.prepared {
+            Some(prepared) => Err(DatabaseError::TransactionPrepared(prepared.transaction)),
+            None => Ok(()),
+        }
+    }
+
+    /// Append the staged writes to the write-ahead log followed by a prepare record,
+    /// returns the appended records
+    fn prepare_commit(
+        &mut self,
+        commit: &mut StagedCommit,
+        transaction: u64,
+    ) -> Result<Vec<WalRecord>, DatabaseError> {
+        let mut records = commit.records.take().unwrap_or_default();
+        records.push(commit.prepare_record(transaction));
+
+        if let Some(wal) = &mut self.wal {
+            wal.append(&records)?;
+        }
+        Ok(records)
+    }
+
+    /// Append the commit record of the prepared transaction, returns the path of the log.
+    /// Checkpoints once the log outgrows `DEFAULT_CHECKPOINT_SIZE`.
+    fn complete_prepared(&mut self) -> Result<Option<PathBuf>, DatabaseError> {
+        let commit = self.commit_record();
+        let Some(wal) = &mut self.wal else {
+            return Ok(None);
+        };
+        wal.append(&[commit])?;
+        let path = wal.path().to_path_buf();
+        if wal.size() > DEFAULT_CHECKPOINT_SIZE {
+            self.save_to_file()?;
+        }
+        Ok(Some(path))
+    }
+}
@@ -0,0 +1,90 @@
+use std::sync::mpsc::{Receiver, Sender, channel};
+
+use crate::{common::DatabaseError, database::Collection, query::Query, schema::Document};
+
+/// Change of a single document, as delivered to watchers
+#[derive(Debug, Clone)]
+pub enum ChangeEvent {
+    Insert(Document),
+    Update {
+        before: Document,
+        after: Document,
+    },
+    /// Deleted or purged document as it was stored
+    Delete(Document),
+}
+
+impl ChangeEvent {
+    pub fn id(&self) -> u64 {
+        match self {
+            ChangeEvent::Insert(document) | ChangeEvent::Delete(document) => document.id,
+            ChangeEvent::Update { after, .. } => after.id,
+        }
+    }
+
+    /// Whether the change concerns documents matching the filter.
+    /// Updates match if the document matched before or after, so watchers also
+    /// learn about documents leaving the filter.
+    fn matches(&self, filter: &Query) -> bool {
+        match self {
+            ChangeEvent::Insert(document) | ChangeEvent::Delete(document) => {
+                filter.matches(document)
+            }
+            ChangeEvent::Update { before, after } => {
+                filter.matches(before) || filter.matches(after)
+            }
+        }
+    }
+}
+
+/// Subscriber registered with `Collection::watch`
+#[derive(Debug)]
+pub(crate) struct Watcher {
+    filter: Query,
+    sender: Sender<ChangeEvent>,
+}
+
+impl Collection {
+    /// Subscribe to changes of documents matching the filter.
+    /// Events are sent in the order the changes are applied, after the collection
+    /// changed in memory. Writes of a session are delivered when it commits, one event
+    /// per changed document with its state before and after the commit.
+    /// Dropping the receiver ends the subscription.
+    ///
+    /// Snapshots and sessions have no watchers.
+    pub fn watch<Q: Into<Query>>(
+        &mut self,
+        filter: Q,
+    ) -> Result<Receiver<ChangeEvent>, DatabaseError> {
+        let filter = filter.into();
+        filter.validate(&self.schema)?;
+
+        let (sender, receiver) = channel();
+        self.watchers.push(Watcher { filter, sender });
+        Ok(receiver)
+    }
+
+    /// Number of active subscriptions, subscriptions with a dropped receiver
+    /// are only removed once the next event is sent
+    pub fn watcher_count(&self) -> usize {
+        self.watchers.len()
+    }
+
+    pub(crate) fn is_watched(&self) -> bool {
+        !self.watchers.is_empty()
+    }
+
+    /// Deliver the events to the watchers whose filter they match
+    pub(crate) fn notify(&mut self, events: Vec<ChangeEvent>) {
+        if events.is_empty() {
+            return;
+        }
+
+        self.watchers.retain(|watcher| {
+            events
+                .iter()
+                .filter(|event| event.matches(&watcher.filter))
+                .all(|event| watcher.sender.send(event.clone()).is_ok())
+        });
+    }
+}
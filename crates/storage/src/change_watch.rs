@@ -0,0 +1,171 @@
+use crate::error::StorageError;
+use crate::file_store::FileStoreHeader;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Interval used to re-read the version counter when OS file watching is unavailable
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Watches the version counter in a store file header for read-only consumers
+/// in other processes, so they can refresh their snapshot once the writer advances it.
+///
+/// On Linux the file is watched with inotify and waiting readers sleep until it is modified,
+/// on other platforms the version counter is polled.
+pub struct VersionWatcher {
+    file: File,
+    last_version: u64,
+    #[cfg(target_os = "linux")]
+    inotify: Option<inotify::Inotify>,
+}
+
+impl VersionWatcher {
+    pub fn open(file_name: &str) -> Result<Self, StorageError> {
+        let file = File::open(file_name)?;
+
+        let mut watcher = VersionWatcher {
+            file,
+            last_version: 0,
+            #[cfg(target_os = "linux")]
+            // Fall back to polling when the inotify watch limit is reached
+            inotify: inotify::Inotify::watch(file_name).ok(),
+        };
+        watcher.last_version = watcher.read_version()?;
+
+        Ok(watcher)
+    }
+
+    /// Last version observed by this watcher
+    pub fn version(&self) -> u64 {
+        self.last_version
+    }
+
+    /// Check without blocking, returns the new version if the file advanced
+    pub fn poll(&mut self) -> Result<Option<u64>, StorageError> {
+        let version = self.read_version()?;
+        if version == self.last_version {
+            return Ok(None);
+        }
+
+        self.last_version = version;
+        Ok(Some(version))
+    }
+
+    /// Block until the file advances or the timeout expires
+    pub fn wait(&mut self, timeout: Duration) -> Result<Option<u64>, StorageError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            if let Some(version) = self.poll()? {
+                return Ok(Some(version));
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+
+            self.sleep(deadline - now)?;
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn sleep(&mut self, timeout: Duration) -> Result<(), StorageError> {
+        match &self.inotify {
+            Some(inotify) => Ok(inotify.wait(timeout)?),
+            None => {
+                thread::sleep(timeout.min(POLL_INTERVAL));
+                Ok(())
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn sleep(&mut self, timeout: Duration) -> Result<(), StorageError> {
+        thread::sleep(timeout.min(POLL_INTERVAL));
+        Ok(())
+    }
+
+    /// Highest version of the two file header copies, the writer alternates between them
+    fn read_version(&mut self) -> Result<u64, StorageError> {
+        let mut version = 0;
+        for copy in 0..FileStoreHeader::HEADER_BLOCKS {
+            let mut bytes = [0u8; 8];
+            self.file.seek(SeekFrom::Start(
+                copy * FileStoreHeader::BLOCK_SIZE + FileStoreHeader::FIELD_VERSION_OFFSET as u64,
+            ))?;
+
+            // The header may not be written yet
+            match self.file.read_exact(&mut bytes) {
+                Ok(()) => version = version.max(u64::from_le_bytes(bytes)),
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(version)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod inotify {
+    use std::ffi::CString;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use std::time::Duration;
+
+    /// Minimal inotify handle watching a single file for modifications
+    pub struct Inotify {
+        fd: libc::c_int,
+    }
+
+    impl Inotify {
+        pub fn watch(file_name: &str) -> io::Result<Self> {
+            let path = CString::new(Path::new(file_name).as_os_str().as_bytes())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+            let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+            if fd == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            let inotify = Inotify { fd };
+
+            let mask = libc::IN_MODIFY | libc::IN_CLOSE_WRITE | libc::IN_ATTRIB;
+            if unsafe { libc::inotify_add_watch(fd, path.as_ptr(), mask) } == -1 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(inotify)
+        }
+
+        /// Wait for file events up to the timeout and drain them
+        pub fn wait(&self, timeout: Duration) -> io::Result<()> {
+            let mut poll_fd = libc::pollfd {
+                fd: self.fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let timeout_ms = timeout.as_millis().clamp(1, libc::c_int::MAX as u128);
+
+            if unsafe { libc::poll(&mut poll_fd, 1, timeout_ms as libc::c_int) } == -1 {
+                let error = io::Error::last_os_error();
+                if error.kind() != io::ErrorKind::Interrupted {
+                    return Err(error);
+                }
+            }
+
+            // Events only wake the reader, the version counter is the source of truth
+            let mut buffer = [0u8; 4096];
+            while unsafe { libc::read(self.fd, buffer.as_mut_ptr().cast(), buffer.len()) } > 0 {}
+
+            Ok(())
+        }
+    }
+
+    impl Drop for Inotify {
+        fn drop(&mut self) {
+            unsafe { libc::close(self.fd) };
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::encryption::NONCE_SIZE;
 use bitvec::prelude::BitVec;
 use bytes::Bytes;
 
@@ -62,15 +63,34 @@ pub struct Chunk {
     /// Serves as a pointer to the root page of the layout map for that specific chunk.
     /// It stores:
     /// - Position reference: It stores the position (address)
-    /// of the root page of the layout map within the chunk
+    ///   of the root page of the layout map within the chunk
     /// - Layout map root: The layout map is a special map that
-    /// contains metadata about all other maps stored in the database
+    ///   contains metadata about all other maps stored in the database
     pub layout_root_position: u64,
     /// The last used map id
     pub map_id: u32,
     /// Predicted position of the next chunk
     pub next: u64,
 
+    /// ***************
+    /// * Compression *
+    /// ***************
+    /// Chunk flags, `FLAG_COMPRESSED` if the content is stored zstd compressed,
+    /// `FLAG_ENCRYPTED` if it is stored encrypted
+    pub flags: u32,
+    /// Bytes of the content between the header and the footer before compression
+    pub uncompressed_length: u32,
+    /// Bytes of the compressed content in the file, 0 for an uncompressed chunk
+    pub compressed_length: u32,
+
+    /// **************
+    /// * Encryption *
+    /// **************
+    /// Id of the key the content is encrypted with, 0 for an unencrypted chunk
+    pub key_id: u32,
+    /// AES-GCM nonce the content is encrypted with
+    pub nonce: [u8; NONCE_SIZE],
+
     /// *********************
     /// * Buffer Management *
     /// *********************
@@ -85,10 +105,18 @@ impl Chunk {
     pub const MAX_HEADER_LENGTH: u16 = 1024;
     /// Maximum size of the chunk footer in bytes
     pub const MAX_FOOTER_LENGTH: u8 = 128;
+    /// Maximum size of a chunk in bytes, header and footer included (2GB)
+    pub const MAX_SIZE: u64 = i32::MAX as u64;
+    /// Size of a table of content entry: offset of the page in the chunk and its length
+    pub const TOC_ENTRY_SIZE: usize = 8;
+    /// The content is stored zstd compressed, page offsets refer to the uncompressed content
+    pub const FLAG_COMPRESSED: u32 = 1;
+    /// The content is stored AES-GCM encrypted after compression, followed by its tag
+    pub const FLAG_ENCRYPTED: u32 = 2;
 }
 
 /// Chunk header
-/// 64 bytes
+/// 92 bytes
 /// !IMPORTANT: Do not change field order, layout is important
 /// !IMPORTANT: Do not delete existing fields and add new fields only at the end
 #[derive(Debug, Copy, Clone)]
@@ -105,13 +133,18 @@ pub struct ChunkHeader {
     pub layout_root_position: u64,
     pub map_id: u32,
     pub next: u64,
+    pub flags: u32,
+    pub uncompressed_length: u32,
+    pub compressed_length: u32,
+    pub key_id: u32,
+    pub nonce: [u8; NONCE_SIZE],
 }
 
 impl ChunkHeader {
     /// Magic keyword for the chunk header
     pub const MAGIC: [u8; 4] = *b"KNCH";
     /// Maximum size of the chunk header
-    /// Currently only 64 bytes are occupied
+    /// Currently only 92 bytes are occupied
     pub const SIZE: usize = 96;
 
     /// Chunk header field offsets
@@ -127,7 +160,12 @@ impl ChunkHeader {
     pub const FIELD_LAYOUT_ROOT_POSITION_OFFSET: usize = 44;
     pub const FIELD_MAP_ID_OFFSET: usize = 52;
     pub const FIELD_NEXT_OFFSET: usize = 56;
-    pub const FIELD_END_OFFSET: usize = 64;
+    pub const FIELD_FLAGS_OFFSET: usize = 64;
+    pub const FIELD_UNCOMPRESSED_LENGTH_OFFSET: usize = 68;
+    pub const FIELD_COMPRESSED_LENGTH_OFFSET: usize = 72;
+    pub const FIELD_KEY_ID_OFFSET: usize = 76;
+    pub const FIELD_NONCE_OFFSET: usize = 80;
+    pub const FIELD_END_OFFSET: usize = 92;
 }
 
 /// Chunk footer
@@ -153,4 +191,4 @@ impl ChunkFooter {
     pub const FIELD_VERSION_OFFSET: usize = 8;
     pub const FIELD_CHECKSUM_OFFSET: usize = 16;
     pub const FIELD_END_OFFSET: usize = 20;
-}
\ No newline at end of file
+}
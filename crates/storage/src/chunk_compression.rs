@@ -0,0 +1,55 @@
+use crate::chunk::Chunk;
+use crate::error::StorageError;
+use crate::storage_engine::Store;
+use bytes::Bytes;
+
+impl Chunk {
+    pub fn is_compressed(&self) -> bool {
+        self.flags & Self::FLAG_COMPRESSED != 0
+    }
+
+    /// Replace the buffer of `set_pages` by its zstd compressed form at the level,
+    /// the buffer is kept as it is if compression does not make it smaller
+    pub fn compress(&mut self, level: i32) -> Result<(), StorageError> {
+        let compressed = zstd::bulk::compress(&self.buffer, level)?;
+        if compressed.len() >= self.buffer.len() {
+            return Ok(());
+        }
+        self.flags |= Self::FLAG_COMPRESSED;
+        self.compressed_length = compressed.len() as u32;
+        self.buffer = Bytes::from(compressed);
+        Ok(())
+    }
+
+    /// Uncompressed content of the chunk from the stored bytes after its header
+    pub fn decompress(&self, stored: &[u8]) -> Result<Vec<u8>, StorageError> {
+        let compressed = stored
+            .get(..self.compressed_length as usize)
+            .ok_or_else(|| self.invalid_content())?;
+        let content = zstd::bulk::decompress(compressed, self.uncompressed_length as usize)?;
+        if content.len() != self.uncompressed_length as usize {
+            return Err(self.invalid_content());
+        }
+        Ok(content)
+    }
+
+    fn invalid_content(&self) -> StorageError {
+        StorageError::InvalidChunkHeader(format!(
+            "Compressed content of chunk {} does not match its header",
+            self.id
+        ))
+    }
+}
+
+impl Store {
+    /// Compress the chunks written from now on with zstd at the level, `None` stores them
+    /// uncompressed. Chunks rewritten by `compact` are compressed as well, so cold data takes
+    /// less space, chunks of either kind are read transparently.
+    pub fn set_compression_level(&mut self, level: Option<i32>) {
+        self.compression_level = level;
+    }
+
+    pub fn compression_level(&self) -> Option<i32> {
+        self.compression_level
+    }
+}
@@ -0,0 +1,139 @@
+use crate::chunk::Chunk;
+use crate::encryption::{Argon2Params, KeyDerivation, Keyring, TAG_SIZE};
+use crate::error::StorageError;
+use crate::file_store::{FileStore, FileStoreOptions};
+use crate::storage_engine::Store;
+use bytes::Bytes;
+
+impl Chunk {
+    pub fn is_encrypted(&self) -> bool {
+        self.flags & Self::FLAG_ENCRYPTED != 0
+    }
+
+    /// Bytes of the content in the file after the header, compressed and encrypted
+    /// as the flags say
+    pub fn stored_length(&self) -> u32 {
+        let length = match self.is_compressed() {
+            true => self.compressed_length,
+            false => self.uncompressed_length,
+        };
+        match self.is_encrypted() {
+            true => length + TAG_SIZE as u32,
+            false => length,
+        }
+    }
+
+    /// Replace the buffer by its encryption with the current key of the keyring,
+    /// after `compress`. The key id and nonce are kept in the chunk header.
+    /// The chunk id, version and key id are authenticated along with the content, so
+    /// a chunk copied to another position or replaced by an older one fails to decrypt.
+    pub fn encrypt(&mut self, keyring: &Keyring) -> Result<(), StorageError> {
+        self.flags |= Self::FLAG_ENCRYPTED;
+        self.key_id = keyring.current_key();
+        self.nonce = keyring.next_nonce(self.id);
+        let sealed = keyring.seal(&self.nonce, &self.associated_data(), &self.buffer)?;
+        self.buffer = Bytes::from(sealed);
+        Ok(())
+    }
+
+    /// Content of the chunk from the stored bytes after its header, decrypted with the
+    /// keyring and decompressed as the flags say
+    pub fn decode(
+        &self,
+        stored: Vec<u8>,
+        keyring: Option<&Keyring>,
+    ) -> Result<Vec<u8>, StorageError> {
+        let stored = match self.is_encrypted() {
+            true => self.decrypt(&stored, keyring)?,
+            false => stored,
+        };
+        match self.is_compressed() {
+            true => self.decompress(&stored),
+            false => Ok(stored),
+        }
+    }
+
+    fn decrypt(&self, stored: &[u8], keyring: Option<&Keyring>) -> Result<Vec<u8>, StorageError> {
+        let keyring = keyring.ok_or_else(|| {
+            StorageError::Encryption(format!("Chunk {} is encrypted, no key is set", self.id))
+        })?;
+        let sealed = stored.get(..self.stored_length() as usize).ok_or_else(|| {
+            StorageError::InvalidChunkHeader(format!(
+                "Encrypted content of chunk {} does not match its header",
+                self.id
+            ))
+        })?;
+        keyring
+            .open(self.key_id, &self.nonce, &self.associated_data(), sealed)
+            .map_err(|error| match error {
+                StorageError::Encryption(message) => {
+                    StorageError::Encryption(format!("Chunk {}: {}", self.id, message))
+                }
+                error => error,
+            })
+    }
+
+    /// Authenticated, unencrypted part of an encrypted chunk
+    fn associated_data(&self) -> [u8; 16] {
+        let mut associated = [0u8; 16];
+        associated[..4].copy_from_slice(&self.id.to_le_bytes());
+        associated[4..12].copy_from_slice(&self.version.to_le_bytes());
+        associated[12..].copy_from_slice(&self.key_id.to_le_bytes());
+        associated
+    }
+}
+
+impl Store {
+    /// Open a store whose chunks are encrypted with the keys of the keyring.
+    /// The layout map is read while opening, so the keys are needed from the start.
+    pub fn open_encrypted(
+        file_name: String,
+        options: FileStoreOptions,
+        keyring: Keyring,
+    ) -> Result<Self, StorageError> {
+        Self::open_with(
+            FileStore::open_with_options(file_name, options)?,
+            Some(keyring),
+        )
+    }
+
+    /// Open a store whose chunks are encrypted with a key derived from the passphrase.
+    /// The salt and Argon2 parameters of a new file are kept in its header under key
+    /// id 1, those of an existing file are read back from it and take precedence over
+    /// `params`. A wrong passphrase fails once an encrypted chunk is read.
+    pub fn open_with_passphrase(
+        file_name: String,
+        options: FileStoreOptions,
+        passphrase: &str,
+        params: Argon2Params,
+    ) -> Result<Self, StorageError> {
+        let mut file_store = FileStore::open_with_options(file_name, options)?;
+        let key_derivation = match &file_store.header.key_derivation {
+            Some(key_derivation) => *key_derivation,
+            None => {
+                let key_derivation = KeyDerivation::generate(1, params)?;
+                file_store.header.key_derivation = Some(key_derivation);
+                // Both header copies, an older copy without the salt would lose the key
+                file_store.write_header()?;
+                file_store.write_header()?;
+                file_store.sync()?;
+                key_derivation
+            }
+        };
+        let keyring = key_derivation.keyring(passphrase)?;
+        Self::open_with(file_store, Some(keyring))
+    }
+
+    /// Encrypt the chunks written from now on with the current key of the keyring,
+    /// `None` writes them unencrypted. Chunks encrypted with any key of the keyring are
+    /// read transparently, encrypted chunks whose key it lacks fail to read.
+    /// Chunks rewritten by `compact` are encrypted with the current key.
+    pub fn set_encryption(&mut self, keyring: Option<Keyring>) {
+        self.encryption = keyring;
+    }
+
+    /// Id of the key new chunks are encrypted with, `None` if they are written unencrypted
+    pub fn encryption_key(&self) -> Option<u32> {
+        self.encryption.as_ref().map(Keyring::current_key)
+    }
+}
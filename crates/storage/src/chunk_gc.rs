@@ -0,0 +1,199 @@
+use crate::chunk::Chunk;
+use crate::data_util::{
+    compose_page_position, get_page_chunk_id, get_page_max_length, get_page_offset, get_page_type,
+};
+use crate::error::StorageError;
+use crate::page::{ChunkId, PageNumber, PagePosition};
+use crate::storage_engine::{Store, now_millis};
+use std::collections::BTreeMap;
+
+/// Live page bytes copied into one new chunk at most, unless a single chunk has more
+const MAX_REWRITE_LENGTH: usize = 16 * 1024 * 1024;
+
+/// Chunk id, page number and offset of a page copied by `compact`
+type Moved = (ChunkId, PageNumber, u32);
+
+/// Outcome of a compaction pass
+#[derive(Debug, Default)]
+pub struct CompactReport {
+    /// Sparse chunks whose live pages were copied into new chunks
+    pub chunks_rewritten: usize,
+    /// Chunks without live pages
+    pub chunks_freed: usize,
+    pub pages_moved: usize,
+    /// New location of each moved page by its old location, as (chunk id, page number)
+    pub relocated: BTreeMap<(ChunkId, PageNumber), (ChunkId, PageNumber)>,
+    /// New location of each moved page by its old location, as (chunk id, offset)
+    moved: BTreeMap<(ChunkId, u32), (ChunkId, u32)>,
+}
+
+impl CompactReport {
+    /// New position of a moved page, with the length code and type of its old position.
+    /// `None` if the page was not moved.
+    pub fn relocate(&self, position: PagePosition) -> Option<PagePosition> {
+        let location = (get_page_chunk_id(position), get_page_offset(position));
+        let (chunk_id, offset) = self.moved.get(&location)?;
+        Some(compose_page_position(
+            *chunk_id,
+            *offset,
+            get_page_max_length(position),
+            get_page_type(position),
+        ))
+    }
+}
+
+/// How long chunks without live pages are kept before `compact` frees them. A chunk is kept
+/// while either window covers it, so readers of older versions still find their pages.
+/// The default keeps nothing, unused chunks are freed by the next `compact`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Milliseconds a chunk is kept after it became unused
+    pub retention_time: u64,
+    /// Versions written after a chunk became unused before it is freed
+    pub retained_versions: u64,
+}
+
+impl Store {
+    pub fn set_retention(&mut self, retention: RetentionPolicy) {
+        self.retention = retention;
+    }
+
+    pub fn retention(&self) -> RetentionPolicy {
+        self.retention
+    }
+
+    /// Record the chunk as unused from now on if its last live page is gone,
+    /// the retention window starts at this time and version
+    pub(crate) fn mark_unused(&mut self, id: ChunkId) {
+        let time = now_millis().saturating_sub(self.creation_time);
+        if let Some(chunk) = self.chunks.get_mut(&id)
+            && !chunk.is_live()
+            && chunk.unused_at_version == 0
+        {
+            chunk.unused = time;
+            chunk.unused_at_version = self.version;
+        }
+    }
+
+    /// The chunk has no live pages and the retention window no longer covers it.
+    /// Chunks never marked unused, e.g. written without pages, are not retained.
+    fn is_retention_expired(&self, chunk: &Chunk) -> bool {
+        if chunk.is_live() {
+            return false;
+        }
+        if chunk.unused_at_version == 0 {
+            return true;
+        }
+        let time = now_millis().saturating_sub(self.creation_time);
+        time.saturating_sub(chunk.unused) >= self.retention.retention_time
+            && self.version.saturating_sub(chunk.unused_at_version)
+                >= self.retention.retained_versions
+    }
+
+    /// Free the chunks without live pages past the retention window and rewrite the
+    /// rewritable chunks whose live pages fill less than `target_fill_rate` percent of their
+    /// page bytes, sparsest first. Live pages are copied into new chunks, which are synced
+    /// before the old chunks are marked unused, and freed unless the retention window keeps
+    /// them. Callers update their page references from `CompactReport::relocate`.
+    pub fn compact(&mut self, target_fill_rate: u8) -> Result<CompactReport, StorageError> {
+        self.check_writable()?;
+        let mut report = CompactReport::default();
+
+        let unused: Vec<ChunkId> = self
+            .chunks
+            .values()
+            .filter(|chunk| chunk.is_saved() && self.is_retention_expired(chunk))
+            .map(|chunk| chunk.id)
+            .collect();
+        for id in unused {
+            self.free_chunk(id)?;
+            report.chunks_freed += 1;
+        }
+
+        // Chunk headers point to the layout map page, its chunk stays in place
+        let layout_chunk = get_page_chunk_id(self.layout_root_position);
+        let mut sparse: Vec<&Chunk> = self
+            .chunks
+            .values()
+            .filter(|chunk| {
+                chunk.is_rewritable()
+                    && chunk.collect_priority < target_fill_rate as u16 * 10
+                    && chunk.id != layout_chunk
+            })
+            .collect();
+        sparse.sort_by_key(|chunk| (chunk.collect_priority, chunk.id));
+        let sparse: Vec<ChunkId> = sparse.iter().map(|chunk| chunk.id).collect();
+
+        // Live pages of several sparse chunks are batched into one new chunk
+        let mut batch: Vec<(Moved, Vec<u8>)> = Vec::new();
+        let mut batch_chunks = Vec::new();
+        let mut batch_length = 0;
+        for id in sparse {
+            let chunk = &self.chunks[&id];
+            let live_length = chunk.max_length_live as usize
+                + chunk.page_count_live as usize * Chunk::TOC_ENTRY_SIZE;
+            let live: Vec<PageNumber> = (0..chunk.page_count)
+                .filter(|page_number| chunk.is_page_live(*page_number))
+                .collect();
+            if batch_length + live_length > MAX_REWRITE_LENGTH {
+                self.rewrite(&mut batch, &mut batch_chunks, &mut report)?;
+                batch_length = 0;
+            }
+
+            let table_of_content = self.read_table_of_content(id)?;
+            for page_number in live {
+                let page = self.read_page(id, page_number)?;
+                let offset = table_of_content[page_number as usize].0;
+                batch.push(((id, page_number, offset), page));
+            }
+            batch_chunks.push(id);
+            batch_length += live_length;
+        }
+        self.rewrite(&mut batch, &mut batch_chunks, &mut report)?;
+
+        // Make the erased chunk headers durable
+        if report.chunks_freed + report.chunks_rewritten > 0 {
+            self.file_store.sync()?;
+        }
+        Ok(report)
+    }
+
+    /// Write the batched pages into a new chunk, the chunks they came from become unused
+    fn rewrite(
+        &mut self,
+        batch: &mut Vec<(Moved, Vec<u8>)>,
+        batch_chunks: &mut Vec<ChunkId>,
+        report: &mut CompactReport,
+    ) -> Result<(), StorageError> {
+        if batch_chunks.is_empty() {
+            return Ok(());
+        }
+        if !batch.is_empty() {
+            let pages: Vec<&[u8]> = batch.iter().map(|(_, page)| page.as_slice()).collect();
+            let id = self.write_pages(&pages)?;
+            let mut offset = Chunk::next_page_offset(&[]);
+            for (page_number, ((chunk_id, old_number, old_offset), page)) in
+                batch.iter().enumerate()
+            {
+                report
+                    .relocated
+                    .insert((*chunk_id, *old_number), (id, page_number as PageNumber));
+                report.moved.insert((*chunk_id, *old_offset), (id, offset));
+                offset += page.len() as u32;
+            }
+            report.pages_moved += batch.len();
+        }
+        for id in batch_chunks.drain(..) {
+            if let Some(chunk) = self.chunks.get_mut(&id) {
+                chunk.remove_all_pages();
+            }
+            self.mark_unused(id);
+            if self.is_retention_expired(&self.chunks[&id]) {
+                self.free_chunk(id)?;
+            }
+            report.chunks_rewritten += 1;
+        }
+        batch.clear();
+        Ok(())
+    }
+}
@@ -1,6 +1,176 @@
-use crate::chunk::Chunk;
+use crate::chunk::{Chunk, ChunkFooter, ChunkHeader};
+use crate::encryption::NONCE_SIZE;
+use crate::error::StorageError;
+use crate::file_store::FileStoreHeader;
+use crate::page::PageNumber;
+use bitvec::prelude::BitVec;
+use bytes::Bytes;
 
 impl Chunk {
+    /// Unallocated chunk without pages
+    pub fn new(id: u32) -> Self {
+        Chunk {
+            id,
+            version: 0,
+            time: 0,
+            length: 0,
+            block: 0,
+            page_count: 0,
+            page_count_live: 0,
+            table_of_content_position: 0,
+            occupancy: BitVec::new(),
+            max_length: 0,
+            max_length_live: 0,
+            collect_priority: 0,
+            unused: 0,
+            unused_at_version: 0,
+            pin_count: 0,
+            layout_root_position: 0,
+            map_id: 0,
+            next: 0,
+            flags: 0,
+            uncompressed_length: 0,
+            compressed_length: 0,
+            key_id: 0,
+            nonce: [0; NONCE_SIZE],
+            buffer: Bytes::new(),
+        }
+    }
+
+    /// Blocks a chunk with `content_length` bytes between its header and footer occupies,
+    /// `None` if the chunk would exceed the maximum chunk size
+    pub fn block_count(content_length: usize) -> Option<u32> {
+        let size = (ChunkHeader::SIZE + content_length + ChunkFooter::SIZE) as u64;
+        if size > Self::MAX_SIZE {
+            return None;
+        }
+        Some(size.div_ceil(FileStoreHeader::BLOCK_SIZE) as u32)
+    }
+
+    /// Offset of the chunk in the file
+    pub fn file_position(&self) -> u64 {
+        self.block * FileStoreHeader::BLOCK_SIZE
+    }
+
+    /// Size of the chunk in the file in bytes, header and footer included
+    pub fn byte_length(&self) -> u64 {
+        self.length as u64 * FileStoreHeader::BLOCK_SIZE
+    }
+
+    /// Bytes available between the header and the footer
+    pub fn content_capacity(&self) -> usize {
+        self.byte_length() as usize - ChunkHeader::SIZE - ChunkFooter::SIZE
+    }
+
+    /// Lay out the pages followed by their table of content in the buffer,
+    /// all pages are live
+    pub fn set_pages(&mut self, pages: &[&[u8]]) {
+        let length: usize = pages.iter().map(|page| page.len()).sum();
+        let mut buffer = Vec::with_capacity(length + pages.len() * Self::TOC_ENTRY_SIZE);
+        let mut table_of_content = Vec::with_capacity(pages.len() * Self::TOC_ENTRY_SIZE);
+        for page in pages {
+            let offset = (ChunkHeader::SIZE + buffer.len()) as u32;
+            table_of_content.extend_from_slice(&offset.to_le_bytes());
+            table_of_content.extend_from_slice(&(page.len() as u32).to_le_bytes());
+            buffer.extend_from_slice(page);
+        }
+        self.table_of_content_position = (ChunkHeader::SIZE + buffer.len()) as u32;
+        buffer.extend_from_slice(&table_of_content);
+
+        self.page_count = pages.len() as u32;
+        self.page_count_live = self.page_count;
+        self.max_length = length as u32;
+        self.max_length_live = self.max_length;
+        self.occupancy = BitVec::repeat(false, pages.len());
+        self.flags &= !(Self::FLAG_COMPRESSED | Self::FLAG_ENCRYPTED);
+        self.uncompressed_length = buffer.len() as u32;
+        self.compressed_length = 0;
+        self.key_id = 0;
+        self.nonce = [0; NONCE_SIZE];
+        self.buffer = Bytes::from(buffer);
+        self.update_collect_priority();
+    }
+
+    /// Bytes the buffer of `set_pages` takes for the pages
+    pub fn content_length(pages: &[&[u8]]) -> usize {
+        pages.iter().map(|page| page.len()).sum::<usize>() + pages.len() * Self::TOC_ENTRY_SIZE
+    }
+
+    /// Offset in the chunk `set_pages` gives a page following the pages
+    pub fn next_page_offset(pages: &[&[u8]]) -> u32 {
+        (ChunkHeader::SIZE + pages.iter().map(|page| page.len()).sum::<usize>()) as u32
+    }
+
+    /// Offset in the chunk and length of each page, read from the table of content bytes
+    pub fn parse_table_of_content(&self, bytes: &[u8]) -> Result<Vec<(u32, u32)>, StorageError> {
+        if bytes.len() != self.page_count as usize * Self::TOC_ENTRY_SIZE {
+            return Err(StorageError::InvalidChunkHeader(format!(
+                "Table of content of chunk {} has {} bytes for {} pages",
+                self.id,
+                bytes.len(),
+                self.page_count
+            )));
+        }
+        Ok(bytes
+            .chunks_exact(Self::TOC_ENTRY_SIZE)
+            .map(|entry| {
+                (
+                    u32::from_le_bytes(entry[..4].try_into().unwrap()),
+                    u32::from_le_bytes(entry[4..].try_into().unwrap()),
+                )
+            })
+            .collect())
+    }
+
+    pub fn is_page_live(&self, page_number: PageNumber) -> bool {
+        self.occupancy
+            .get(page_number as usize)
+            .is_some_and(|removed| !*removed)
+    }
+
+    /// Mark a page of `length` bytes removed, returns false if it was removed already
+    pub fn remove_page(&mut self, page_number: PageNumber, length: u32) -> bool {
+        if !self.is_page_live(page_number) {
+            return false;
+        }
+        self.occupancy.set(page_number as usize, true);
+        self.page_count_live -= 1;
+        self.max_length_live -= length;
+        self.update_collect_priority();
+        true
+    }
+
+    /// Mark every page removed, e.g. once the live pages were copied into another chunk
+    pub fn remove_all_pages(&mut self) {
+        self.occupancy.fill(true);
+        self.page_count_live = 0;
+        self.max_length_live = 0;
+        self.update_collect_priority();
+    }
+
+    /// Replace the removed pages of the chunk, e.g. by the occupancy persisted in the layout map.
+    /// The live page count and length follow from the occupancy and the page lengths
+    /// of the table of content.
+    pub fn set_occupancy(&mut self, occupancy: BitVec, table_of_content: &[(u32, u32)]) {
+        self.page_count_live = occupancy.count_zeros() as u32;
+        self.max_length_live = table_of_content
+            .iter()
+            .zip(occupancy.iter().by_vals())
+            .filter(|(_, removed)| !removed)
+            .map(|((_, length), _)| length)
+            .sum();
+        self.occupancy = occupancy;
+        self.update_collect_priority();
+    }
+
+    /// Live page bytes per mille of all page bytes, sparse chunks are collected first
+    pub fn update_collect_priority(&mut self) {
+        self.collect_priority = match self.max_length {
+            0 => 0,
+            max_length => (self.max_length_live as u64 * 1000 / max_length as u64) as u16,
+        };
+    }
+
     pub fn is_allocated(&self) -> bool {
         self.block != 0
     }
@@ -23,5 +193,4 @@ impl Chunk {
             && self.is_evacutable()
             && (self.page_count_live < self.page_count) // Not fully occupied
     }
-
-}
\ No newline at end of file
+}
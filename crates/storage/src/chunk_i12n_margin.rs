@@ -1,5 +1,6 @@
 use crate::chunk::{Chunk, ChunkFooter, ChunkHeader};
 use crate::data_util::get_fletcher32;
+use crate::encryption::NONCE_SIZE;
 use crate::error::StorageError;
 
 impl Chunk {
@@ -17,6 +18,11 @@ impl Chunk {
             layout_root_position: self.layout_root_position,
             map_id: self.map_id,
             next: self.next,
+            flags: self.flags,
+            uncompressed_length: self.uncompressed_length,
+            compressed_length: self.compressed_length,
+            key_id: self.key_id,
+            nonce: self.nonce,
         };
 
         header.serialize_header()
@@ -44,6 +50,18 @@ impl Chunk {
     pub fn verify_footer(bytes: &[u8]) -> bool {
         ChunkFooter::verify_footer(bytes)
     }
+
+    /// Chunk as it is written to the file: the header, the buffer, zero padding
+    /// and the footer in the last bytes of the last block
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = vec![0u8; self.byte_length() as usize];
+        bytes[..ChunkHeader::SIZE].copy_from_slice(&self.serialize_header());
+        bytes[ChunkHeader::SIZE..ChunkHeader::SIZE + self.buffer.len()]
+            .copy_from_slice(&self.buffer);
+        let footer_offset = bytes.len() - ChunkFooter::SIZE;
+        bytes[footer_offset..].copy_from_slice(&self.serialize_footer());
+        bytes
+    }
 }
 
 impl ChunkHeader {
@@ -75,6 +93,16 @@ impl ChunkHeader {
             .copy_from_slice(&self.map_id.to_le_bytes());
         bytes[Self::FIELD_NEXT_OFFSET..Self::FIELD_NEXT_OFFSET + 8]
             .copy_from_slice(&self.next.to_le_bytes());
+        bytes[Self::FIELD_FLAGS_OFFSET..Self::FIELD_FLAGS_OFFSET + 4]
+            .copy_from_slice(&self.flags.to_le_bytes());
+        bytes[Self::FIELD_UNCOMPRESSED_LENGTH_OFFSET..Self::FIELD_UNCOMPRESSED_LENGTH_OFFSET + 4]
+            .copy_from_slice(&self.uncompressed_length.to_le_bytes());
+        bytes[Self::FIELD_COMPRESSED_LENGTH_OFFSET..Self::FIELD_COMPRESSED_LENGTH_OFFSET + 4]
+            .copy_from_slice(&self.compressed_length.to_le_bytes());
+        bytes[Self::FIELD_KEY_ID_OFFSET..Self::FIELD_KEY_ID_OFFSET + 4]
+            .copy_from_slice(&self.key_id.to_le_bytes());
+        bytes[Self::FIELD_NONCE_OFFSET..Self::FIELD_NONCE_OFFSET + NONCE_SIZE]
+            .copy_from_slice(&self.nonce);
 
         bytes
     }
@@ -101,6 +129,13 @@ impl ChunkHeader {
         let layout_root_position = read_u64(bytes, Self::FIELD_LAYOUT_ROOT_POSITION_OFFSET);
         let map_id = read_u32(bytes, Self::FIELD_MAP_ID_OFFSET);
         let next = read_u64(bytes, Self::FIELD_NEXT_OFFSET);
+        let flags = read_u32(bytes, Self::FIELD_FLAGS_OFFSET);
+        let uncompressed_length = read_u32(bytes, Self::FIELD_UNCOMPRESSED_LENGTH_OFFSET);
+        let compressed_length = read_u32(bytes, Self::FIELD_COMPRESSED_LENGTH_OFFSET);
+        let key_id = read_u32(bytes, Self::FIELD_KEY_ID_OFFSET);
+        let nonce = bytes[Self::FIELD_NONCE_OFFSET..Self::FIELD_NONCE_OFFSET + NONCE_SIZE]
+            .try_into()
+            .unwrap();
 
         Ok(Self {
             magic,
@@ -115,6 +150,11 @@ impl ChunkHeader {
             layout_root_position,
             map_id,
             next,
+            flags,
+            uncompressed_length,
+            compressed_length,
+            key_id,
+            nonce,
         })
     }
 }
@@ -175,16 +215,13 @@ impl ChunkFooter {
 
 // Helper functions for reading with automatic offset advancement
 fn read_u32(bytes: &[u8], offset: usize) -> u32 {
-    let value = u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
-    value
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
 }
 
 fn read_i32(bytes: &[u8], offset: usize) -> i32 {
-    let value = i32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
-    value
+    i32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
 }
 
 fn read_u64(bytes: &[u8], offset: usize) -> u64 {
-    let value = u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
-    value
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
 }
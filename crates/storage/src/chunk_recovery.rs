@@ -0,0 +1,167 @@
+use crate::chunk::{Chunk, ChunkFooter, ChunkHeader};
+use crate::data_util::get_page_chunk_id;
+use crate::error::StorageError;
+use crate::file_store::FileStoreHeader;
+use crate::layout_map::LayoutMap;
+use crate::page::ChunkId;
+use crate::storage_engine::Store;
+use bitvec::prelude::BitVec;
+
+/// Outcome of scanning the file for chunks when the store is opened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// Chunks with a valid header and footer
+    pub chunks_recovered: usize,
+    /// Chunks whose footer is missing or does not match their header,
+    /// e.g. torn by a crash while they were written. Their blocks are free.
+    pub chunks_discarded: usize,
+    /// Version of the newest valid chunk, the store continues from it
+    pub version: u64,
+}
+
+impl Store {
+    /// Find the chunks by reading a header at each block boundary after the file header.
+    /// A chunk is valid if its footer passes `verify_footer` and matches the header.
+    /// The newest valid chunk provides the version and the layout map, so a torn
+    /// last chunk falls back to the chunk written before it. The removed pages recorded
+    /// in the layout map are applied to their chunks.
+    pub(crate) fn recover(&mut self) -> Result<RecoveryReport, StorageError> {
+        let mut report = RecoveryReport::default();
+        let blocks = self.file_store.size() / FileStoreHeader::BLOCK_SIZE;
+
+        let mut block = FileStoreHeader::HEADER_BLOCKS;
+        while block < blocks {
+            let Some(header) = self.read_chunk_header(block)? else {
+                block += 1;
+                continue;
+            };
+            let Some(chunk) = self.read_chunk_footer(block, blocks, &header)? else {
+                report.chunks_discarded += 1;
+                block += 1;
+                continue;
+            };
+
+            block += chunk.length as u64;
+            // A chunk id reused after wrapping around, keep the newer chunk
+            if let Some(existing) = self.chunks.get(&chunk.id) {
+                if existing.version > chunk.version {
+                    continue;
+                }
+                self.free_space.free(existing.block, existing.length as u64);
+            }
+            self.free_space.mark_used(chunk.block, chunk.length as u64);
+            self.chunks.insert(chunk.id, chunk);
+        }
+
+        report.chunks_recovered = self.chunks.len();
+        if let Some(newest) = self.chunks.values().max_by_key(|chunk| chunk.version) {
+            report.version = newest.version;
+            self.version = newest.version;
+            self.last_chunk_id = newest.id;
+            self.layout_root_position = newest.layout_root_position;
+        }
+        if self.layout_root_position != 0 {
+            let bytes = self.read_page_at(self.layout_root_position)?;
+            self.layout = LayoutMap::deserialize(&bytes)?;
+            self.apply_occupancy()?;
+        }
+        Ok(report)
+    }
+
+    /// Mark the pages the layout map records as removed. Chunks written after the layout
+    /// page keep all their pages live, an id reused since then does not match.
+    /// Chunks left without live pages start their retention window again.
+    fn apply_occupancy(&mut self) -> Result<(), StorageError> {
+        let layout_chunk_id = get_page_chunk_id(self.layout_root_position);
+        let layout_version = self
+            .chunks
+            .get(&layout_chunk_id)
+            .map_or(0, |chunk| chunk.version);
+        let occupancies: Vec<(ChunkId, BitVec)> = self
+            .layout
+            .chunk_occupancies()
+            .map(|(id, occupancy)| (id, occupancy.clone()))
+            .collect();
+
+        for (id, occupancy) in occupancies {
+            let Some(chunk) = self.chunks.get(&id) else {
+                continue;
+            };
+            if chunk.version > layout_version || occupancy.len() != chunk.page_count as usize {
+                continue;
+            }
+            let table_of_content = self.read_table_of_content(id)?;
+            if let Some(chunk) = self.chunks.get_mut(&id) {
+                chunk.set_occupancy(occupancy, &table_of_content);
+            }
+            self.mark_unused(id);
+        }
+        Ok(())
+    }
+
+    /// Chunk header at the start of the block, `None` if the block does not start a chunk
+    fn read_chunk_header(&mut self, block: u64) -> Result<Option<ChunkHeader>, StorageError> {
+        let bytes = self.file_store.read_fully(
+            block * FileStoreHeader::BLOCK_SIZE,
+            ChunkHeader::SIZE as u32,
+        )?;
+        let header = Chunk::deserialize_header(&bytes)?;
+        if header.magic != ChunkHeader::MAGIC
+            || header.id == 0
+            || header.id > Chunk::MAX_ID
+            || header.length == 0
+        {
+            return Ok(None);
+        }
+        Ok(Some(header))
+    }
+
+    /// Chunk described by the header if its footer is valid and matches the header
+    fn read_chunk_footer(
+        &mut self,
+        block: u64,
+        blocks: u64,
+        header: &ChunkHeader,
+    ) -> Result<Option<Chunk>, StorageError> {
+        if block + header.length as u64 > blocks {
+            return Ok(None);
+        }
+        let end = (block + header.length as u64) * FileStoreHeader::BLOCK_SIZE;
+        let bytes = self
+            .file_store
+            .read_fully(end - ChunkFooter::SIZE as u64, ChunkFooter::SIZE as u32)?;
+        if !Chunk::verify_footer(&bytes) {
+            return Ok(None);
+        }
+        let footer = Chunk::deserialize_footer(&bytes)?;
+        if footer.id != header.id
+            || footer.length != header.length
+            || footer.version != header.version
+        {
+            return Ok(None);
+        }
+
+        let mut chunk = Chunk::new(header.id);
+        chunk.block = block;
+        chunk.length = header.length;
+        chunk.version = header.version;
+        chunk.time = header.time;
+        chunk.max_length = header.max_length;
+        chunk.max_length_live = header.max_length;
+        chunk.page_count = header.page_count;
+        chunk.page_count_live = header.page_count;
+        chunk.pin_count = header.pin_count;
+        chunk.table_of_content_position = header.table_of_content_position;
+        chunk.layout_root_position = header.layout_root_position;
+        chunk.map_id = header.map_id;
+        chunk.next = header.next;
+        chunk.flags = header.flags;
+        chunk.uncompressed_length = header.uncompressed_length;
+        chunk.compressed_length = header.compressed_length;
+        chunk.key_id = header.key_id;
+        chunk.nonce = header.nonce;
+        chunk.occupancy = BitVec::repeat(false, header.page_count as usize);
+        chunk.update_collect_priority();
+        Ok(Some(chunk))
+    }
+}
@@ -0,0 +1,222 @@
+use crate::direct_io;
+use crate::error::StorageError;
+use crate::file_sync::{Durability, sync_file};
+use std::fs::File;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Serialized chunk queued for the writer thread
+pub(crate) struct ChunkWrite {
+    pub(crate) version: u64,
+    /// Offset of the chunk in the file
+    pub(crate) position: u64,
+    pub(crate) buffer: Vec<u8>,
+}
+
+#[derive(Default)]
+struct AckState {
+    /// Version of the last chunk written and synced
+    acknowledged: u64,
+    /// First write or sync failure, the writer stops after it
+    error: Option<String>,
+}
+
+/// Versions acknowledged by the background chunk writer, shared with threads
+/// waiting for their commits to become durable
+#[derive(Clone, Default)]
+pub struct CommitAck {
+    shared: Arc<(Mutex<AckState>, Condvar)>,
+}
+
+impl CommitAck {
+    fn new(acknowledged: u64) -> Self {
+        let ack = CommitAck::default();
+        ack.state().acknowledged = acknowledged;
+        ack
+    }
+
+    fn state(&self) -> MutexGuard<'_, AckState> {
+        self.shared.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Version of the last chunk written and synced by the writer
+    pub fn acknowledged_version(&self) -> u64 {
+        self.state().acknowledged
+    }
+
+    /// Block until the chunk of `version` is written and synced.
+    /// Fails if the writer failed before reaching it.
+    pub fn wait(&self, version: u64) -> Result<(), StorageError> {
+        let state = self.state();
+        let state = self
+            .shared
+            .1
+            .wait_while(state, |state| {
+                state.acknowledged < version && state.error.is_none()
+            })
+            .unwrap_or_else(PoisonError::into_inner);
+        Self::result(&state, version).map(|_| ())
+    }
+
+    /// Like `wait`, false if the version is not acknowledged before the timeout
+    pub fn wait_timeout(&self, version: u64, timeout: Duration) -> Result<bool, StorageError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.state();
+        while state.acknowledged < version && state.error.is_none() {
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(false);
+            }
+            state = self
+                .shared
+                .1
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+        Self::result(&state, version)
+    }
+
+    fn result(state: &AckState, version: u64) -> Result<bool, StorageError> {
+        match &state.error {
+            Some(error) if state.acknowledged < version => Err(writer_error(error)),
+            _ => Ok(true),
+        }
+    }
+
+    /// Failure of the writer, if any
+    fn error(&self) -> Option<StorageError> {
+        self.state().error.as_deref().map(writer_error)
+    }
+
+    fn acknowledge(&self, version: u64) {
+        self.state().acknowledged = version;
+        self.shared.1.notify_all();
+    }
+
+    fn fail(&self, error: io::Error) {
+        self.state().error = Some(error.to_string());
+        self.shared.1.notify_all();
+    }
+}
+
+fn writer_error(error: &str) -> StorageError {
+    StorageError::IoError(io::Error::other(format!(
+        "Background chunk write failed: {}",
+        error
+    )))
+}
+
+/// Writer thread of a store. Chunks queued by commits are written in order, chunks
+/// queued while a sync is running are synced together, then their versions are acknowledged.
+pub(crate) struct ChunkWriter {
+    sender: Option<Sender<ChunkWrite>>,
+    thread: Option<JoinHandle<()>>,
+    ack: CommitAck,
+    /// Version of the last queued chunk
+    submitted: u64,
+}
+
+impl ChunkWriter {
+    /// Start the thread writing through its own handle of the store file,
+    /// the chunks up to `version` are already durable
+    pub(crate) fn start(
+        file: File,
+        durability: Durability,
+        direct_io: bool,
+        version: u64,
+    ) -> Result<Self, StorageError> {
+        let (sender, receiver) = mpsc::channel();
+        let ack = CommitAck::new(version);
+        let thread_ack = ack.clone();
+        let thread = thread::Builder::new()
+            .name("kenchidb-chunk-writer".to_string())
+            .spawn(move || run(file, durability, direct_io, receiver, thread_ack))?;
+        Ok(ChunkWriter {
+            sender: Some(sender),
+            thread: Some(thread),
+            ack,
+            submitted: version,
+        })
+    }
+
+    pub(crate) fn ack(&self) -> &CommitAck {
+        &self.ack
+    }
+
+    /// Queue a chunk, fails once the writer failed
+    pub(crate) fn submit(&mut self, write: ChunkWrite) -> Result<(), StorageError> {
+        if let Some(error) = self.ack.error() {
+            return Err(error);
+        }
+        let version = write.version;
+        self.sender
+            .as_ref()
+            .and_then(|sender| sender.send(write).ok())
+            .ok_or_else(|| writer_error("writer thread stopped"))?;
+        self.submitted = version;
+        Ok(())
+    }
+
+    /// True while queued chunks are not acknowledged
+    pub(crate) fn is_pending(&self) -> bool {
+        self.ack.acknowledged_version() < self.submitted
+    }
+
+    /// Block until every queued chunk is written and synced
+    pub(crate) fn wait_all(&self) -> Result<(), StorageError> {
+        self.ack.wait(self.submitted)
+    }
+
+    /// Write the queued chunks and stop the thread
+    pub(crate) fn stop(&mut self) -> Result<(), StorageError> {
+        // The thread exits once the channel is drained and closed
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        match self.ack.error() {
+            Some(error) if self.is_pending() => Err(error),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for ChunkWriter {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+fn run(
+    mut file: File,
+    durability: Durability,
+    direct_io: bool,
+    receiver: Receiver<ChunkWrite>,
+    ack: CommitAck,
+) {
+    while let Ok(write) = receiver.recv() {
+        let mut batch = vec![write];
+        batch.extend(receiver.try_iter());
+        let written = batch
+            .iter()
+            .try_for_each(|write| match direct_io {
+                true => direct_io::write_at(&mut file, write.position, &write.buffer),
+                false => {
+                    file.seek(SeekFrom::Start(write.position))?;
+                    file.write_all(&write.buffer)
+                }
+            })
+            .and_then(|_| sync_file(&file, durability));
+        match written {
+            Ok(()) => ack.acknowledge(batch[batch.len() - 1].version),
+            Err(e) => {
+                ack.fail(e);
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use crate::error::StorageError;
+
+/// Serialization of the keys and values stored in pages
+pub trait DataType: Sized {
+    fn write(&self, bytes: &mut Vec<u8>);
+
+    /// Read a value at the offset and advance the offset past it
+    fn read(bytes: &[u8], offset: &mut usize) -> Result<Self, StorageError>;
+}
+
+/// Next `length` bytes at the offset, advancing the offset past them
+pub fn read_bytes<'a>(
+    bytes: &'a [u8],
+    offset: &mut usize,
+    length: usize,
+) -> Result<&'a [u8], StorageError> {
+    let end = offset
+        .checked_add(length)
+        .filter(|end| *end <= bytes.len())
+        .ok_or_else(|| StorageError::InvalidPage("Unexpected end of page".to_string()))?;
+    let slice = &bytes[*offset..end];
+    *offset = end;
+    Ok(slice)
+}
+
+macro_rules! fixed_size_data_type {
+    ($($type:ty),*) => {
+        $(
+            impl DataType for $type {
+                fn write(&self, bytes: &mut Vec<u8>) {
+                    bytes.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn read(bytes: &[u8], offset: &mut usize) -> Result<Self, StorageError> {
+                    let slice = read_bytes(bytes, offset, size_of::<$type>())?;
+                    Ok(<$type>::from_le_bytes(slice.try_into().unwrap()))
+                }
+            }
+        )*
+    };
+}
+
+fixed_size_data_type!(u8, u16, u32, u64, i32, i64);
+
+impl DataType for Vec<u8> {
+    fn write(&self, bytes: &mut Vec<u8>) {
+        (self.len() as u32).write(bytes);
+        bytes.extend_from_slice(self);
+    }
+
+    fn read(bytes: &[u8], offset: &mut usize) -> Result<Self, StorageError> {
+        let length = u32::read(bytes, offset)?;
+        Ok(read_bytes(bytes, offset, length as usize)?.to_vec())
+    }
+}
+
+impl DataType for String {
+    fn write(&self, bytes: &mut Vec<u8>) {
+        (self.len() as u32).write(bytes);
+        bytes.extend_from_slice(self.as_bytes());
+    }
+
+    fn read(bytes: &[u8], offset: &mut usize) -> Result<Self, StorageError> {
+        let length = u32::read(bytes, offset)?;
+        String::from_utf8(read_bytes(bytes, offset, length as usize)?.to_vec())
+            .map_err(|_| StorageError::InvalidPage("String is not UTF-8".to_string()))
+    }
+}
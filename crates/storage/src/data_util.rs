@@ -1,3 +1,6 @@
+use crate::chunk::Chunk;
+use crate::page::{ChunkId, PagePosition};
+
 /// Calculate the Fletcher32 checksum.
 ///
 /// # Arguments
@@ -15,7 +18,10 @@ pub fn get_fletcher32(bytes: &[u8], offset: usize, length: usize) -> u32 {
     let (mut i, len) = (offset, offset + (length & !1));
 
     // Ensure we don't go out of bounds
-    assert!(len <= bytes.len(), "offset + length exceeds byte slice bounds");
+    assert!(
+        len <= bytes.len(),
+        "offset + length exceeds byte slice bounds"
+    );
 
     while i < len {
         // reduce after 360 words (each word is two bytes)
@@ -43,3 +49,92 @@ pub fn get_fletcher32(bytes: &[u8], offset: usize, length: usize) -> u32 {
 
     (sum2 << 16) | sum1
 }
+
+/// Page type bit of a leaf page position
+pub const PAGE_TYPE_LEAF: u8 = 0;
+/// Page type bit of an internal page position
+pub const PAGE_TYPE_NODE: u8 = 1;
+/// Maximum length of the largest length code (2MB), longer pages share it
+pub const PAGE_LARGE: u32 = 2 * 1024 * 1024;
+/// Position of a page which has not been saved yet
+pub const PAGE_POSITION_UNSAVED: PagePosition = 0;
+/// Position of a page marked as removed before it was saved
+pub const PAGE_POSITION_REMOVED: PagePosition = 1;
+
+/// Compose a page position from its parts.
+///
+/// Layout, most significant bits first:
+/// - 26 bits chunk id
+/// - 32 bits offset of the page within the chunk
+/// - 5 bits length code, see `encode_page_length`
+/// - 1 bit page type, `PAGE_TYPE_LEAF` or `PAGE_TYPE_NODE`
+///
+/// Chunk ids start at 1, so a saved page never has one of the reserved positions.
+///
+/// # Panics
+/// Panics if the chunk id exceeds `Chunk::MAX_ID` or the type is not 0 or 1
+pub fn compose_page_position(
+    chunk_id: ChunkId,
+    offset: u32,
+    length: u32,
+    page_type: u8,
+) -> PagePosition {
+    assert!(chunk_id <= Chunk::MAX_ID, "chunk id exceeds 26 bits");
+    assert!(page_type <= PAGE_TYPE_NODE, "page type is not 0 or 1");
+    ((chunk_id as u64) << 38)
+        | ((offset as u64) << 6)
+        | ((encode_page_length(length) as u64) << 1)
+        | page_type as u64
+}
+
+pub fn get_page_chunk_id(position: PagePosition) -> ChunkId {
+    (position >> 38) as ChunkId
+}
+
+pub fn get_page_offset(position: PagePosition) -> u32 {
+    (position >> 6) as u32
+}
+
+/// Upper bound of the page length, the length code keeps only an approximation
+pub fn get_page_max_length(position: PagePosition) -> u32 {
+    decode_page_length(((position >> 1) & 31) as u8)
+}
+
+pub fn get_page_type(position: PagePosition) -> u8 {
+    (position & 1) as u8
+}
+
+/// The page position refers to a saved page, neither unsaved nor removed before it was saved
+pub fn is_page_saved(position: PagePosition) -> bool {
+    position > PAGE_POSITION_REMOVED
+}
+
+/// Encode a page length as a 5 bit code of its upper bound.
+/// Codes step through 32, 48, 64, 96, 128, ... (two steps per power of two),
+/// lengths over 1MB get the code 31 which decodes to `PAGE_LARGE`.
+///
+/// # Returns
+/// The smallest code whose decoded length is at least `length`, capped at 31
+pub fn encode_page_length(length: u32) -> u8 {
+    if length <= 32 {
+        return 0;
+    }
+    let mut code = length.leading_zeros() as i32;
+    let remaining = length << (code + 1);
+    code += code;
+    if remaining & (1 << 31) != 0 {
+        code -= 1;
+    }
+    if remaining << 1 != 0 {
+        code -= 1;
+    }
+    (52 - code).min(31) as u8
+}
+
+/// Upper bound of the page length of a length code
+pub fn decode_page_length(code: u8) -> u32 {
+    if code >= 31 {
+        return PAGE_LARGE;
+    }
+    (2 + (code as u32 & 1)) << ((code >> 1) + 4)
+}
@@ -0,0 +1,160 @@
+use crate::file_store::FileStoreHeader;
+use std::alloc::{self, Layout};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+/// Alignment of the offsets, lengths and memory of direct I/O transfers.
+/// A block covers the logical sector size of common drives.
+pub const DIRECT_IO_ALIGNMENT: usize = FileStoreHeader::BLOCK_SIZE as usize;
+
+#[cfg(windows)]
+const FILE_FLAG_NO_BUFFERING: u32 = 0x2000_0000;
+
+/// Bypass the page cache for a file opened with these options.
+/// macOS has no open flag for it, see `disable_cache`.
+pub(crate) fn set_direct(options: &mut OpenOptions) {
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.custom_flags(libc::O_DIRECT);
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        options.custom_flags(FILE_FLAG_NO_BUFFERING);
+    }
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        windows
+    )))]
+    let _ = options;
+}
+
+/// Bypass the page cache for an open file, on platforms where it is not an open flag
+#[cfg(target_vendor = "apple")]
+pub(crate) fn disable_cache(file: &File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_NOCACHE, 1) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_vendor = "apple"))]
+pub(crate) fn disable_cache(_file: &File) -> io::Result<()> {
+    Ok(())
+}
+
+/// Zeroed heap buffer aligned to `DIRECT_IO_ALIGNMENT`, a whole number of blocks long
+pub(crate) struct AlignedBuffer {
+    data: NonNull<u8>,
+    length: usize,
+}
+
+// The buffer owns its memory like a `Vec<u8>`
+unsafe impl Send for AlignedBuffer {}
+
+impl AlignedBuffer {
+    /// Buffer of at least `length` bytes, rounded up to whole blocks
+    pub(crate) fn zeroed(length: usize) -> Self {
+        let length = align_up(length as u64).max(DIRECT_IO_ALIGNMENT as u64) as usize;
+        let layout = Self::layout(length);
+        let data = unsafe { alloc::alloc_zeroed(layout) };
+        let data = NonNull::new(data).unwrap_or_else(|| alloc::handle_alloc_error(layout));
+        AlignedBuffer { data, length }
+    }
+
+    fn layout(length: usize) -> Layout {
+        Layout::from_size_align(length, DIRECT_IO_ALIGNMENT).expect("Invalid buffer layout")
+    }
+}
+
+impl Deref for AlignedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.data.as_ptr(), self.length) }
+    }
+}
+
+impl DerefMut for AlignedBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.data.as_ptr(), self.length) }
+    }
+}
+
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.data.as_ptr(), Self::layout(self.length)) }
+    }
+}
+
+pub(crate) fn align_down(offset: u64) -> u64 {
+    offset - offset % DIRECT_IO_ALIGNMENT as u64
+}
+
+pub(crate) fn align_up(offset: u64) -> u64 {
+    align_down(offset + DIRECT_IO_ALIGNMENT as u64 - 1)
+}
+
+/// Read a range of a file opened for direct I/O through the blocks covering it
+pub(crate) fn read_at(file: &mut File, offset: u64, length: usize) -> io::Result<Vec<u8>> {
+    if length == 0 {
+        return Ok(Vec::new());
+    }
+    let start = align_down(offset);
+    let mut buffer = AlignedBuffer::zeroed((align_up(offset + length as u64) - start) as usize);
+    let read = read_blocks(file, start, &mut buffer)?;
+
+    let from = (offset - start) as usize;
+    if read < from + length {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!(
+                "Cannot read {} bytes at offset {}: past the end of the file",
+                length, offset
+            ),
+        ));
+    }
+    Ok(buffer[from..from + length].to_vec())
+}
+
+/// Write a range of a file opened for direct I/O. The rest of the blocks partially
+/// covered by the range is read first and written back unchanged.
+pub(crate) fn write_at(file: &mut File, offset: u64, buffer: &[u8]) -> io::Result<()> {
+    if buffer.is_empty() {
+        return Ok(());
+    }
+    let start = align_down(offset);
+    let end = offset + buffer.len() as u64;
+    let mut blocks = AlignedBuffer::zeroed((align_up(end) - start) as usize);
+    if start != offset || align_up(end) != end {
+        // Past the end of the file the blocks stay zero
+        read_blocks(file, start, &mut blocks)?;
+    }
+
+    let from = (offset - start) as usize;
+    blocks[from..from + buffer.len()].copy_from_slice(buffer);
+    file.seek(SeekFrom::Start(start))?;
+    file.write_all(&blocks)
+}
+
+/// Fill the buffer from an aligned offset, returns the bytes read before the end of the file
+fn read_blocks(file: &mut File, offset: u64, buffer: &mut [u8]) -> io::Result<usize> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut read = 0;
+    while read < buffer.len() {
+        match file.read(&mut buffer[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read)
+}
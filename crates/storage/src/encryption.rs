@@ -0,0 +1,235 @@
+use crate::error::StorageError;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Size of the AES-GCM authentication tag following the ciphertext
+pub const TAG_SIZE: usize = 16;
+/// Size of the AES-GCM nonce
+pub const NONCE_SIZE: usize = 12;
+/// Size of the salts `EncryptionKey::derive` is meant to be used with
+pub const SALT_SIZE: usize = 16;
+
+/// Cost of the Argon2id key derivation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Argon2Params {
+    /// Memory filled by the derivation in KiB, at least 8 per lane
+    pub memory_kib: u32,
+    /// Passes over the memory
+    pub iterations: u32,
+    /// Lanes of the memory
+    pub lanes: u32,
+}
+
+impl Default for Argon2Params {
+    /// 19 MiB and two passes, the minimum the OWASP password storage guidance suggests
+    fn default() -> Self {
+        Self {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            lanes: 1,
+        }
+    }
+}
+
+/// 256-bit AES key, wiped from memory when dropped
+pub struct EncryptionKey {
+    bytes: [u8; 32],
+}
+
+impl EncryptionKey {
+    /// Derive the key from a passphrase with Argon2id. The same passphrase, salt and
+    /// parameters give the same key, see `KeyDerivation` to store them with the data.
+    pub fn derive(
+        passphrase: &str,
+        salt: &[u8],
+        params: &Argon2Params,
+    ) -> Result<Self, StorageError> {
+        let params = Params::new(params.memory_kib, params.iterations, params.lanes, Some(32))
+            .map_err(|error| {
+                StorageError::Encryption(format!("Invalid Argon2 parameters: {}", error))
+            })?;
+        let mut bytes = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), salt, &mut bytes)
+            .map_err(|error| {
+                StorageError::Encryption(format!("Key derivation failed: {}", error))
+            })?;
+        Ok(Self { bytes })
+    }
+
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self { bytes }
+    }
+}
+
+impl Drop for EncryptionKey {
+    fn drop(&mut self) {
+        self.bytes.fill(0);
+        // Keeps the writes to memory about to be freed from being optimized away
+        std::hint::black_box(&mut self.bytes);
+    }
+}
+
+/// Salt and cost of the Argon2id derivation of a key from a passphrase and the id the key
+/// gets in the keyring. Kept in the file header, so the key is derived again from the
+/// passphrase alone when the file is opened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyDerivation {
+    pub key_id: u32,
+    pub params: Argon2Params,
+    pub salt: [u8; SALT_SIZE],
+}
+
+impl KeyDerivation {
+    /// Size of the serialized derivation: key id, memory, iterations, lanes and salt
+    pub const SIZE: usize = 16 + SALT_SIZE;
+
+    /// Derivation of a new key with a new random salt
+    pub fn generate(key_id: u32, params: Argon2Params) -> Result<Self, StorageError> {
+        Ok(Self {
+            key_id,
+            params,
+            salt: generate_salt()?,
+        })
+    }
+
+    pub fn derive_key(&self, passphrase: &str) -> Result<EncryptionKey, StorageError> {
+        EncryptionKey::derive(passphrase, &self.salt, &self.params)
+    }
+
+    /// Keyring sealing new data with the key derived from the passphrase
+    pub fn keyring(&self, passphrase: &str) -> Result<Keyring, StorageError> {
+        Keyring::new(self.key_id, &self.derive_key(passphrase)?)
+    }
+
+    pub fn serialize(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[0..4].copy_from_slice(&self.key_id.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.params.memory_kib.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.params.iterations.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.params.lanes.to_le_bytes());
+        bytes[16..].copy_from_slice(&self.salt);
+        bytes
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, StorageError> {
+        if bytes.len() < Self::SIZE {
+            return Err(StorageError::Encryption(
+                "Key derivation is truncated".to_string(),
+            ));
+        }
+        let read =
+            |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        Ok(Self {
+            key_id: read(0),
+            params: Argon2Params {
+                memory_kib: read(4),
+                iterations: read(8),
+                lanes: read(12),
+            },
+            salt: bytes[16..Self::SIZE].try_into().unwrap(),
+        })
+    }
+}
+
+/// Fill the buffer from the operating system's random source
+pub fn random_bytes(buffer: &mut [u8]) -> Result<(), StorageError> {
+    getrandom::fill(buffer).map_err(|error| StorageError::IoError(error.into()))
+}
+
+/// New random salt for `EncryptionKey::derive`
+pub fn generate_salt() -> Result<[u8; SALT_SIZE], StorageError> {
+    let mut salt = [0u8; SALT_SIZE];
+    random_bytes(&mut salt)?;
+    Ok(salt)
+}
+
+/// AES-256-GCM keys by id, new data is sealed with the current key and data sealed
+/// with any of the keys is opened, e.g. while rotating keys.
+///
+/// Nonces are a caller chosen prefix, e.g. the id of the page or chunk, followed by
+/// a counter starting at a random value for each keyring, so no nonce repeats under
+/// a key unless the same prefix is sealed 2^64 times in one session.
+pub struct Keyring {
+    keys: HashMap<u32, Aes256Gcm>,
+    /// Key new data is sealed with
+    current_key: u32,
+    counter: AtomicU64,
+}
+
+impl Keyring {
+    /// Keyring sealing new data with the key
+    pub fn new(key_id: u32, key: &EncryptionKey) -> Result<Self, StorageError> {
+        let mut seed = [0u8; 8];
+        random_bytes(&mut seed)?;
+        Ok(Self {
+            keys: HashMap::from([(key_id, Aes256Gcm::new(&key.bytes.into()))]),
+            current_key: key_id,
+            counter: AtomicU64::new(u64::from_le_bytes(seed)),
+        })
+    }
+
+    /// Also open data sealed with an older key
+    pub fn with_key(mut self, key_id: u32, key: &EncryptionKey) -> Self {
+        self.keys.insert(key_id, Aes256Gcm::new(&key.bytes.into()));
+        self
+    }
+
+    pub fn current_key(&self) -> u32 {
+        self.current_key
+    }
+
+    /// Unused nonce starting with the prefix
+    pub fn next_nonce(&self, prefix: u32) -> [u8; NONCE_SIZE] {
+        let mut nonce = [0u8; NONCE_SIZE];
+        nonce[..4].copy_from_slice(&prefix.to_le_bytes());
+        nonce[4..].copy_from_slice(&self.counter.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+        nonce
+    }
+
+    /// Ciphertext of the plaintext under the current key followed by its tag,
+    /// the associated data is authenticated but not stored
+    pub fn seal(
+        &self,
+        nonce: &[u8; NONCE_SIZE],
+        associated: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, StorageError> {
+        let payload = Payload {
+            msg: plaintext,
+            aad: associated,
+        };
+        self.keys[&self.current_key]
+            .encrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| StorageError::Encryption("Plaintext is too long".to_string()))
+    }
+
+    /// Plaintext of data sealed with the key, fails if the key is unknown or wrong
+    /// or the data or associated data were altered
+    pub fn open(
+        &self,
+        key_id: u32,
+        nonce: &[u8; NONCE_SIZE],
+        associated: &[u8],
+        sealed: &[u8],
+    ) -> Result<Vec<u8>, StorageError> {
+        let cipher = self
+            .keys
+            .get(&key_id)
+            .ok_or_else(|| StorageError::Encryption(format!("Unknown key {}", key_id)))?;
+        let payload = Payload {
+            msg: sealed,
+            aad: associated,
+        };
+        cipher
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| {
+                StorageError::Encryption(
+                    "Authentication failed, the key is wrong or the data was altered".to_string(),
+                )
+            })
+    }
+}
@@ -1,7 +1,29 @@
 #[derive(Debug)]
 pub enum StorageError {
     InvalidChunkHeader(String),
+    /// Neither file header copy is valid, or the file uses an unsupported format
+    InvalidFileHeader(String),
     ReadOnly(String),
+    /// File is open by another writer, in this or another process
+    Locked(String),
+    /// The store was closed
+    Closed,
+    ChunkNotFound(u32),
+    /// Page does not exist in the chunk or was removed
+    PageNotFound {
+        chunk_id: u32,
+        page_number: u32,
+    },
+    /// Stored page cannot be deserialized
+    InvalidPage(String),
+    /// Layout map page cannot be read
+    InvalidLayout(String),
+    /// No retained chunk was written at the version, or it is newer than the store
+    VersionNotFound(u64),
+    /// Chunk content exceeds the maximum chunk size
+    ChunkTooLarge(usize),
+    /// Key derivation failed, or encrypted data does not authenticate with the known keys
+    Encryption(String),
     IoError(std::io::Error),
 }
 
@@ -9,4 +31,4 @@ impl From<std::io::Error> for StorageError {
     fn from(error: std::io::Error) -> Self {
         StorageError::IoError(error)
     }
-}
\ No newline at end of file
+}
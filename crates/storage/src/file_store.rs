@@ -1,25 +1,123 @@
+use crate::encryption::KeyDerivation;
+use crate::file_sync::Durability;
+use crate::io_stats::IoStats;
+use crate::memory_file_store::MemoryFileStore;
+use crate::read_ahead::ReadAhead;
 use std::fs::File;
 use std::sync::atomic::AtomicU64;
 
 pub struct FileStore {
-    pub file: File,
+    pub backend: FileStoreBackend,
     pub size: AtomicU64,
     pub file_name: String,
     pub read_only: bool,
+    pub durability: Durability,
+    /// Reads and writes bypass the operating system page cache
+    pub direct_io: bool,
+    /// Newest valid copy of the file header, written back by `write_header`
+    pub header: FileStoreHeader,
+    pub read_ahead: ReadAhead,
     pub read_count: AtomicU64,
     pub read_bytes: AtomicU64,
     pub write_count: AtomicU64,
     pub write_bytes: AtomicU64,
+    /// Reads served from prefetched bytes, they are not counted in `read_count`
+    pub read_ahead_hits: AtomicU64,
+    /// Snapshot the current interval of `interval_stats` started with
+    pub interval_start: IoStats,
 }
 
-struct FileStoreHeader {
-    /// Magic identifier/version number for the store format.
-    /// Set to value 2 in the current implementation.
-    /// Used to identify this as a KenchiDB MVStore file.
-    /// Provides basic version identification.
+/// Storage holding the bytes of a file store
+pub enum FileStoreBackend {
+    File(File),
+    /// Nothing is written to disk, the contents are lost when the store is dropped
+    Memory(MemoryFileStore),
+}
+
+/// Options used when opening a file store
+#[derive(Debug, Clone)]
+pub struct FileStoreOptions {
+    /// Open the file without write access, the file must exist
+    pub read_only: bool,
+    /// Sync behaviour of `FileStore::sync`, file creation and renames
+    pub durability: Durability,
+    /// Blocks prefetched past sequential reads, 0 disables read-ahead
+    pub read_ahead_blocks: u32,
+    /// Bypass the page cache with `O_DIRECT`, `F_NOCACHE` on macOS or unbuffered I/O on
+    /// Windows, so pages are not cached by both the operating system and the database.
+    /// Transfers are done in whole aligned blocks. Opening fails on file systems
+    /// without direct I/O support, e.g. tmpfs.
+    pub direct_io: bool,
+}
+
+impl FileStoreOptions {
+    pub const DEFAULT_READ_AHEAD_BLOCKS: u32 = 16;
+}
+
+impl Default for FileStoreOptions {
+    fn default() -> Self {
+        FileStoreOptions {
+            read_only: false,
+            durability: Durability::default(),
+            read_ahead_blocks: Self::DEFAULT_READ_AHEAD_BLOCKS,
+            direct_io: false,
+        }
+    }
+}
+
+/// File header, kept in two copies in the first two blocks of the file.
+/// Updates alternate between the copies, so a write torn by a crash leaves the other
+/// copy intact. The copy with the highest sequence and a valid checksum is used.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FileStoreHeader {
+    /// Magic identifier of a KenchiDB store file
     pub magic: [u8; 4],
+    /// Version of the file format, files of newer formats are not opened
+    pub format: u32,
+    /// Version counter advanced by the writer, polled by readers in other processes
+    pub version: u64,
+    /// Block size the file was created with
+    pub block_size: u32,
+    /// Time the store was created, milliseconds since the Unix epoch.
+    /// Chunk times are relative to it.
+    pub creation_time: u64,
+    /// Id of the last written chunk, 0 before the first chunk
+    pub last_chunk_id: u32,
+    /// First block of the last written chunk
+    pub last_chunk_block: u64,
+    /// Number of header writes, the copy at block `sequence % 2` is the newest
+    pub sequence: u64,
+    /// Salt and parameters the encryption key is derived with from a passphrase,
+    /// see `Store::open_with_passphrase`
+    pub key_derivation: Option<KeyDerivation>,
 }
 
 impl FileStoreHeader {
     pub const MAGIC: [u8; 4] = *b"KNCH";
-}
\ No newline at end of file
+    /// Current file format, format 2 added the key derivation
+    pub const FORMAT: u32 = 2;
+    /// Size of a block, the file header and chunks occupy whole blocks
+    pub const BLOCK_SIZE: u64 = 4096;
+    /// Blocks reserved for the file header at the start of the file, chunks are placed after them
+    pub const HEADER_BLOCKS: u64 = 2;
+    /// Maximum size of a header copy, the rest of its block is zero
+    /// Currently only 88 bytes are occupied
+    pub const SIZE: usize = 96;
+
+    /// File header field offsets
+    pub const FIELD_MAGIC_OFFSET: usize = 0;
+    pub const FIELD_FORMAT_OFFSET: usize = 4;
+    pub const FIELD_VERSION_OFFSET: usize = 8;
+    pub const FIELD_BLOCK_SIZE_OFFSET: usize = 16;
+    pub const FIELD_CREATION_TIME_OFFSET: usize = 20;
+    pub const FIELD_LAST_CHUNK_ID_OFFSET: usize = 28;
+    pub const FIELD_LAST_CHUNK_BLOCK_OFFSET: usize = 32;
+    pub const FIELD_SEQUENCE_OFFSET: usize = 40;
+    /// 1 if a key derivation follows, 0 otherwise
+    pub const FIELD_KEY_DERIVATION_FLAG_OFFSET: usize = 48;
+    pub const FIELD_KEY_DERIVATION_OFFSET: usize = 52;
+    pub const FIELD_CHECKSUM_OFFSET: usize = 84;
+    pub const FIELD_END_OFFSET: usize = 88;
+    /// Offset of the checksum in format 1 headers, which end after it
+    pub const FORMAT_1_CHECKSUM_OFFSET: usize = 48;
+}
@@ -1,40 +1,226 @@
+use crate::data_util::get_fletcher32;
+use crate::direct_io;
+use crate::encryption::KeyDerivation;
 use crate::error::StorageError;
-use crate::file_store::FileStore;
-use std::fs::File;
-use std::io;
+use crate::file_store::{FileStore, FileStoreBackend, FileStoreHeader, FileStoreOptions};
+use crate::file_sync::{Durability, sync_file, sync_parent_directory};
+use crate::io_stats::IoStats;
+use crate::memory_file_store::MemoryFileStore;
+use crate::read_ahead::{AccessHint, ReadAhead};
+use crate::storage_engine::now_millis;
+use std::fs::{File, TryLockError};
 use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::{fs, io};
 
 impl FileStore {
     pub fn open(file_name: String, read_only: bool) -> Result<Self, StorageError> {
-        let file = File::options()
+        Self::open_with_options(
+            file_name,
+            FileStoreOptions {
+                read_only,
+                ..FileStoreOptions::default()
+            },
+        )
+    }
+
+    pub fn open_with_options(
+        file_name: String,
+        options: FileStoreOptions,
+    ) -> Result<Self, StorageError> {
+        let FileStoreOptions {
+            read_only,
+            durability,
+            read_ahead_blocks,
+            direct_io,
+        } = options;
+        let created = !read_only && !Path::new(&file_name).exists();
+
+        let mut open_options = File::options();
+        open_options
             .read(true)
             .write(!read_only)
-            .create(true)
-            .open(file_name.clone())?;
+            .create(!read_only)
+            .truncate(false);
+        if direct_io {
+            direct_io::set_direct(&mut open_options);
+        }
+        let file = open_options.open(file_name.clone())?;
+        if direct_io {
+            direct_io::disable_cache(&file)?;
+        }
+
+        // Readers in other processes follow the writer, see `VersionWatcher`,
+        // only a second writer is refused
+        if !read_only {
+            match file.try_lock() {
+                Ok(()) => {}
+                Err(TryLockError::WouldBlock) => {
+                    return Err(StorageError::Locked(format!(
+                        "{} is open by another writer",
+                        file_name
+                    )));
+                }
+                Err(TryLockError::Error(e)) => return Err(e.into()),
+            }
+        }
+
+        // Make the new directory entry durable
+        if created {
+            sync_parent_directory(Path::new(&file_name), durability)?;
+        }
 
         let metadata = file.metadata()?;
 
-        Ok(FileStore {
-            file,
+        let mut store = FileStore {
+            backend: FileStoreBackend::File(file),
             size: AtomicU64::new(metadata.len()),
             file_name,
             read_only,
+            durability,
+            direct_io,
+            header: FileStoreHeader::new(now_millis()),
+            read_ahead: ReadAhead::new(read_ahead_blocks),
             read_count: AtomicU64::new(0),
             read_bytes: AtomicU64::new(0),
             write_count: AtomicU64::new(0),
             write_bytes: AtomicU64::new(0),
-        })
+            read_ahead_hits: AtomicU64::new(0),
+            interval_start: IoStats::default(),
+        };
+        store.open_header()?;
+        Ok(store)
+    }
+
+    /// Store kept in memory, e.g. for tests and ephemeral stores.
+    /// The header is written like for a new file, syncs do nothing.
+    pub fn open_in_memory() -> Result<Self, StorageError> {
+        let mut store = FileStore {
+            backend: FileStoreBackend::Memory(MemoryFileStore::new()),
+            size: AtomicU64::new(0),
+            file_name: String::new(),
+            read_only: false,
+            durability: Durability::None,
+            direct_io: false,
+            header: FileStoreHeader::new(now_millis()),
+            // Reads from memory gain nothing from prefetching
+            read_ahead: ReadAhead::new(0),
+            read_count: AtomicU64::new(0),
+            read_bytes: AtomicU64::new(0),
+            write_count: AtomicU64::new(0),
+            write_bytes: AtomicU64::new(0),
+            read_ahead_hits: AtomicU64::new(0),
+            interval_start: IoStats::default(),
+        };
+        store.open_header()?;
+        Ok(store)
+    }
+
+    pub fn is_in_memory(&self) -> bool {
+        matches!(self.backend, FileStoreBackend::Memory(_))
+    }
+
+    /// Write the header of an empty store, otherwise read and validate the existing header
+    fn open_header(&mut self) -> Result<(), StorageError> {
+        if self.size() == 0 && !self.read_only {
+            // Both copies are valid from the start
+            self.write_header()?;
+            self.write_header()?;
+            self.sync()?;
+            return Ok(());
+        }
+
+        self.header = self.read_header()?.ok_or_else(|| {
+            StorageError::InvalidFileHeader(format!("No valid file header in {}", self.file_name))
+        })?;
+        if self.header.format > FileStoreHeader::FORMAT {
+            return Err(StorageError::InvalidFileHeader(format!(
+                "File format {} is newer than the supported format {}",
+                self.header.format,
+                FileStoreHeader::FORMAT
+            )));
+        }
+        if self.header.block_size as u64 != FileStoreHeader::BLOCK_SIZE {
+            return Err(StorageError::InvalidFileHeader(format!(
+                "Block size {} is not supported",
+                self.header.block_size
+            )));
+        }
+        // Older headers are written in the current format from the next header write on
+        self.header.format = FileStoreHeader::FORMAT;
+
+        Ok(())
+    }
+
+    /// Newest valid copy of the file header, `None` if neither copy is valid
+    pub fn read_header(&mut self) -> Result<Option<FileStoreHeader>, StorageError> {
+        let mut newest: Option<FileStoreHeader> = None;
+        for copy in 0..FileStoreHeader::HEADER_BLOCKS {
+            let position = copy * FileStoreHeader::BLOCK_SIZE;
+            if position + FileStoreHeader::SIZE as u64 > self.size() {
+                continue;
+            }
+            let bytes = self.read_fully(position, FileStoreHeader::SIZE as u32)?;
+            let Ok(header) = FileStoreHeader::deserialize_header(&bytes) else {
+                continue;
+            };
+            if newest.is_none_or(|newest| header.sequence > newest.sequence) {
+                newest = Some(header);
+            }
+        }
+        Ok(newest)
+    }
+
+    /// Write the header over its older copy, durable with the next sync
+    pub fn write_header(&mut self) -> Result<(), StorageError> {
+        self.header.sequence += 1;
+        let mut block = vec![0u8; FileStoreHeader::BLOCK_SIZE as usize];
+        block[..FileStoreHeader::SIZE].copy_from_slice(&self.header.serialize_header());
+        let copy = self.header.sequence % FileStoreHeader::HEADER_BLOCKS;
+        let written = self.write_fully(copy * FileStoreHeader::BLOCK_SIZE, &block);
+        if written.is_err() {
+            self.header.sequence -= 1;
+        }
+        written
     }
 
     pub fn close(self) {
-        drop(self.file);
+        drop(self.backend);
+    }
+
+    /// Release the write lock before the store is dropped, another writer can open the file
+    pub fn unlock(&self) -> Result<(), StorageError> {
+        match &self.backend {
+            FileStoreBackend::File(file) if !self.read_only => Ok(file.unlock()?),
+            _ => Ok(()),
+        }
     }
 
     pub fn size(&self) -> u64 {
         self.size.load(Ordering::Relaxed)
     }
 
+    /// Snapshot of the I/O counters since the store was opened
+    pub fn stats(&self) -> IoStats {
+        IoStats {
+            read_count: self.read_count.load(Ordering::Relaxed),
+            read_bytes: self.read_bytes.load(Ordering::Relaxed),
+            write_count: self.write_count.load(Ordering::Relaxed),
+            write_bytes: self.write_bytes.load(Ordering::Relaxed),
+            read_ahead_hits: self.read_ahead_hits.load(Ordering::Relaxed),
+        }
+    }
+
+    /// I/O counters since the previous call, or since the store was opened,
+    /// e.g. for throughput over a reporting interval
+    pub fn interval_stats(&mut self) -> IoStats {
+        let now = self.stats();
+        let interval = now.since(&self.interval_start);
+        self.interval_start = now;
+        interval
+    }
+
     pub fn get_file_name(&self) -> String {
         self.file_name.clone()
     }
@@ -59,9 +245,92 @@ impl FileStore {
             )));
         }
 
-        self.file.seek(SeekFrom::Start(offset))?;
-        let mut buffer = vec![0u8; length as usize];
-        self.file.read_exact(&mut buffer)?;
+        let prefetch = self.read_ahead.record(offset, length);
+        if let Some(buffer) = self.read_ahead.cached(offset, length) {
+            self.read_ahead_hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(buffer);
+        }
+        if prefetch == 0 {
+            return self.read_at(offset, length as usize);
+        }
+
+        // Read the following blocks along, up to the end of the file
+        let total = (length as u64 + prefetch).min(size - offset);
+        let mut buffer = self.read_at(offset, total as usize)?;
+        let prefetched = buffer.split_off(length as usize);
+        self.read_ahead.fill(offset + length as u64, prefetched);
+        Ok(buffer)
+    }
+
+    /// Read several ranges given as (offset, length), returned in the order requested.
+    /// Ranges less than a block apart are merged into one read.
+    pub fn read_fully_vectored(
+        &mut self,
+        ranges: &[(u64, u32)],
+    ) -> Result<Vec<Vec<u8>>, StorageError> {
+        let size = self.size();
+        if let Some((offset, length)) = ranges
+            .iter()
+            .find(|(offset, length)| offset + *length as u64 > size)
+        {
+            return Err(StorageError::IoError(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Cannot read {} bytes at offset {}: would exceed file size {}",
+                    length, offset, size
+                ),
+            )));
+        }
+
+        let mut order: Vec<usize> = (0..ranges.len()).collect();
+        order.sort_by_key(|index| ranges[*index].0);
+        let mut buffers = vec![Vec::new(); ranges.len()];
+        let mut next = 0;
+        while next < order.len() {
+            // Extend the span while the next range starts within a block of its end
+            let start = ranges[order[next]].0;
+            let mut end = start + ranges[order[next]].1 as u64;
+            let mut last = next + 1;
+            while last < order.len() && ranges[order[last]].0 <= end + FileStoreHeader::BLOCK_SIZE {
+                end = end.max(ranges[order[last]].0 + ranges[order[last]].1 as u64);
+                last += 1;
+            }
+
+            let span = self.read_at(start, (end - start) as usize)?;
+            for index in &order[next..last] {
+                let (offset, length) = ranges[*index];
+                let from = (offset - start) as usize;
+                buffers[*index] = span[from..from + length as usize].to_vec();
+            }
+            next = last;
+        }
+        Ok(buffers)
+    }
+
+    /// Prefetching behaviour of the following reads
+    pub fn set_access_hint(&mut self, hint: AccessHint) {
+        self.read_ahead.hint = hint;
+        self.read_ahead.clear();
+    }
+
+    pub fn access_hint(&self) -> AccessHint {
+        self.read_ahead.hint
+    }
+
+    /// Read from the backend, bounds are checked by the caller
+    fn read_at(&mut self, offset: u64, length: usize) -> Result<Vec<u8>, StorageError> {
+        let buffer = match &mut self.backend {
+            FileStoreBackend::File(file) if self.direct_io => {
+                direct_io::read_at(file, offset, length)?
+            }
+            FileStoreBackend::File(file) => {
+                file.seek(SeekFrom::Start(offset))?;
+                let mut buffer = vec![0u8; length];
+                file.read_exact(&mut buffer)?;
+                buffer
+            }
+            FileStoreBackend::Memory(memory) => memory.read_fully(offset, length as u32)?,
+        };
 
         self.read_count.fetch_add(1, Ordering::Relaxed);
         self.read_bytes.fetch_add(length as u64, Ordering::Relaxed);
@@ -76,21 +345,207 @@ impl FileStore {
             ));
         }
 
-        let length = buffer.len();
+        match &mut self.backend {
+            FileStoreBackend::File(file) if self.direct_io => {
+                direct_io::write_at(file, offset, buffer)?
+            }
+            FileStoreBackend::File(file) => {
+                file.seek(SeekFrom::Start(offset))?;
+                file.write_all(buffer)?;
+            }
+            FileStoreBackend::Memory(memory) => memory.write_fully(offset, buffer)?,
+        }
+        self.record_write(offset, buffer.len());
 
-        self.file.seek(SeekFrom::Start(offset))?;
-        self.file.write_all(&buffer)?;
+        Ok(())
+    }
 
+    /// Count a write to the file and grow the size past it.
+    /// Also used for writes made through a `writer_handle`.
+    pub(crate) fn record_write(&mut self, offset: u64, length: usize) {
+        self.read_ahead.invalidate(offset, length as u64);
         self.size
             .fetch_max(offset + (length as u64), Ordering::Relaxed);
 
         self.write_count.fetch_add(1, Ordering::Relaxed);
         self.write_bytes.fetch_add(length as u64, Ordering::Relaxed);
+    }
 
-        Ok(())
+    /// Separate write handle of the file with its own position, for a writer thread.
+    /// It bypasses the page cache like the store if `direct_io` is set.
+    pub(crate) fn writer_handle(&self) -> Result<File, StorageError> {
+        if self.is_in_memory() {
+            return Err(StorageError::IoError(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "An in-memory store has no file to write from another thread",
+            )));
+        }
+        if self.read_only {
+            return Err(StorageError::ReadOnly(
+                "File is open in a readonly mode".to_string(),
+            ));
+        }
+        let mut options = File::options();
+        // Partial blocks are read back before direct writes
+        options.read(true).write(true);
+        if self.direct_io {
+            direct_io::set_direct(&mut options);
+        }
+        let file = options.open(&self.file_name)?;
+        if self.direct_io {
+            direct_io::disable_cache(&file)?;
+        }
+        Ok(file)
     }
 
     pub fn sync(&self) -> Result<(), StorageError> {
-        Ok(self.file.sync_all()?)
+        match &self.backend {
+            FileStoreBackend::File(file) => Ok(sync_file(file, self.durability)?),
+            FileStoreBackend::Memory(memory) => memory.sync(),
+        }
+    }
+
+    /// Advance the version counter in the file header.
+    /// Readers in other processes watch it to detect that the file changed.
+    pub fn publish_version(&mut self, version: u64) -> Result<(), StorageError> {
+        self.header.version = version;
+        self.write_header()
+    }
+
+    /// Read the version counter from the file header, 0 if none was published yet
+    pub fn read_version(&mut self) -> Result<u64, StorageError> {
+        Ok(self.read_header()?.map_or(0, |header| header.version))
+    }
+
+    /// Rename the underlying file, syncing the affected directories
+    pub fn rename(&mut self, new_file_name: String) -> Result<(), StorageError> {
+        if self.is_in_memory() {
+            return Err(StorageError::IoError(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "An in-memory store has no file to rename",
+            )));
+        }
+        if self.read_only {
+            return Err(StorageError::ReadOnly(
+                "File is open in a readonly mode".to_string(),
+            ));
+        }
+
+        // File contents must be durable before the new name is
+        self.sync()?;
+        fs::rename(&self.file_name, &new_file_name)?;
+
+        let old_path = Path::new(&self.file_name);
+        let new_path = Path::new(&new_file_name);
+        sync_parent_directory(new_path, self.durability)?;
+        if old_path.parent() != new_path.parent() {
+            sync_parent_directory(old_path, self.durability)?;
+        }
+
+        self.file_name = new_file_name;
+        Ok(())
+    }
+}
+
+impl FileStoreHeader {
+    /// Header of a new file
+    pub fn new(creation_time: u64) -> Self {
+        FileStoreHeader {
+            magic: Self::MAGIC,
+            format: Self::FORMAT,
+            version: 0,
+            block_size: Self::BLOCK_SIZE as u32,
+            creation_time,
+            last_chunk_id: 0,
+            last_chunk_block: 0,
+            sequence: 0,
+            key_derivation: None,
+        }
     }
+
+    pub fn serialize_header(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+
+        bytes[Self::FIELD_MAGIC_OFFSET..Self::FIELD_MAGIC_OFFSET + 4]
+            .copy_from_slice(self.magic.as_slice());
+        bytes[Self::FIELD_FORMAT_OFFSET..Self::FIELD_FORMAT_OFFSET + 4]
+            .copy_from_slice(&self.format.to_le_bytes());
+        bytes[Self::FIELD_VERSION_OFFSET..Self::FIELD_VERSION_OFFSET + 8]
+            .copy_from_slice(&self.version.to_le_bytes());
+        bytes[Self::FIELD_BLOCK_SIZE_OFFSET..Self::FIELD_BLOCK_SIZE_OFFSET + 4]
+            .copy_from_slice(&self.block_size.to_le_bytes());
+        bytes[Self::FIELD_CREATION_TIME_OFFSET..Self::FIELD_CREATION_TIME_OFFSET + 8]
+            .copy_from_slice(&self.creation_time.to_le_bytes());
+        bytes[Self::FIELD_LAST_CHUNK_ID_OFFSET..Self::FIELD_LAST_CHUNK_ID_OFFSET + 4]
+            .copy_from_slice(&self.last_chunk_id.to_le_bytes());
+        bytes[Self::FIELD_LAST_CHUNK_BLOCK_OFFSET..Self::FIELD_LAST_CHUNK_BLOCK_OFFSET + 8]
+            .copy_from_slice(&self.last_chunk_block.to_le_bytes());
+        bytes[Self::FIELD_SEQUENCE_OFFSET..Self::FIELD_SEQUENCE_OFFSET + 8]
+            .copy_from_slice(&self.sequence.to_le_bytes());
+        if let Some(key_derivation) = &self.key_derivation {
+            bytes[Self::FIELD_KEY_DERIVATION_FLAG_OFFSET
+                ..Self::FIELD_KEY_DERIVATION_FLAG_OFFSET + 4]
+                .copy_from_slice(&1u32.to_le_bytes());
+            bytes[Self::FIELD_KEY_DERIVATION_OFFSET
+                ..Self::FIELD_KEY_DERIVATION_OFFSET + KeyDerivation::SIZE]
+                .copy_from_slice(&key_derivation.serialize());
+        }
+        let checksum = get_fletcher32(&bytes, 0, Self::FIELD_CHECKSUM_OFFSET);
+        bytes[Self::FIELD_CHECKSUM_OFFSET..Self::FIELD_CHECKSUM_OFFSET + 4]
+            .copy_from_slice(&checksum.to_le_bytes());
+
+        bytes
+    }
+
+    /// Header copy from its bytes, fails if the magic or the checksum does not match.
+    /// Format 1 headers have no key derivation.
+    pub fn deserialize_header(bytes: &[u8]) -> Result<Self, StorageError> {
+        if bytes.len() != Self::SIZE {
+            return Err(StorageError::InvalidFileHeader(
+                "Invalid file header size".to_string(),
+            ));
+        }
+
+        let magic: [u8; 4] = bytes[Self::FIELD_MAGIC_OFFSET..Self::FIELD_MAGIC_OFFSET + 4]
+            .try_into()
+            .unwrap();
+        let format = read_u32(bytes, Self::FIELD_FORMAT_OFFSET);
+        let checksum_offset = match format {
+            1 => Self::FORMAT_1_CHECKSUM_OFFSET,
+            _ => Self::FIELD_CHECKSUM_OFFSET,
+        };
+        let checksum = read_u32(bytes, checksum_offset);
+        if magic != Self::MAGIC || checksum != get_fletcher32(bytes, 0, checksum_offset) {
+            return Err(StorageError::InvalidFileHeader(
+                "File header does not match its checksum".to_string(),
+            ));
+        }
+        let key_derivation = match format {
+            1 => None,
+            _ if read_u32(bytes, Self::FIELD_KEY_DERIVATION_FLAG_OFFSET) == 0 => None,
+            _ => Some(KeyDerivation::deserialize(
+                &bytes[Self::FIELD_KEY_DERIVATION_OFFSET..],
+            )?),
+        };
+
+        Ok(Self {
+            magic,
+            format,
+            version: read_u64(bytes, Self::FIELD_VERSION_OFFSET),
+            block_size: read_u32(bytes, Self::FIELD_BLOCK_SIZE_OFFSET),
+            creation_time: read_u64(bytes, Self::FIELD_CREATION_TIME_OFFSET),
+            last_chunk_id: read_u32(bytes, Self::FIELD_LAST_CHUNK_ID_OFFSET),
+            last_chunk_block: read_u64(bytes, Self::FIELD_LAST_CHUNK_BLOCK_OFFSET),
+            sequence: read_u64(bytes, Self::FIELD_SEQUENCE_OFFSET),
+            key_derivation,
+        })
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
 }
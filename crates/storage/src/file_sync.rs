@@ -0,0 +1,85 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// How hard a sync pushes written data towards stable storage.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Durability {
+    /// Flush through the drive write cache and make file creation and renames durable:
+    /// - macOS uses F_FULLFSYNC, since plain fsync only hands data to the drive
+    /// - Windows uses FlushFileBuffers
+    /// - Other platforms use fsync
+    ///
+    /// The parent directory is synced after the file is created or renamed.
+    #[default]
+    Full,
+    /// Flush file data to the drive without metadata or directory syncs.
+    /// Data may be lost on power failure if the drive cache is volatile.
+    Relaxed,
+    /// Never sync, leave write back to the operating system.
+    None,
+}
+
+/// Sync file contents according to the durability level
+pub fn sync_file(file: &File, durability: Durability) -> io::Result<()> {
+    match durability {
+        Durability::Full => full_sync(file),
+        Durability::Relaxed => relaxed_sync(file),
+        Durability::None => Ok(()),
+    }
+}
+
+/// Sync the directory containing the path, making created/renamed entries durable.
+/// Only done for full durability, directories cannot be synced on Windows.
+pub fn sync_parent_directory(path: &Path, durability: Durability) -> io::Result<()> {
+    if durability != Durability::Full {
+        return Ok(());
+    }
+
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => sync_directory(parent),
+        _ => sync_directory(Path::new(".")),
+    }
+}
+
+#[cfg(target_vendor = "apple")]
+fn full_sync(file: &File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    // F_FULLFSYNC is not supported by every file system, fall back to fsync
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_FULLFSYNC) } == -1 {
+        return relaxed_sync(file);
+    }
+    Ok(())
+}
+
+#[cfg(not(target_vendor = "apple"))]
+fn full_sync(file: &File) -> io::Result<()> {
+    file.sync_all()
+}
+
+#[cfg(target_vendor = "apple")]
+fn relaxed_sync(file: &File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    // std uses F_FULLFSYNC on macOS, call plain fsync directly
+    if unsafe { libc::fsync(file.as_raw_fd()) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_vendor = "apple"))]
+fn relaxed_sync(file: &File) -> io::Result<()> {
+    file.sync_data()
+}
+
+#[cfg(unix)]
+fn sync_directory(path: &Path) -> io::Result<()> {
+    File::open(path)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_directory(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,85 @@
+use bitvec::prelude::BitVec;
+
+/// Blocks of the store file in use, one bit per block (set bit = used block).
+/// Chunks are allocated as runs of consecutive free blocks, the first run that fits wins.
+#[derive(Debug, Clone)]
+pub struct FreeSpaceBitSet {
+    used: BitVec,
+}
+
+impl FreeSpaceBitSet {
+    /// Free space of a new file, the first `reserved_blocks` hold the file header
+    pub fn new(reserved_blocks: u64) -> Self {
+        let mut free_space = FreeSpaceBitSet {
+            used: BitVec::new(),
+        };
+        free_space.mark_used(0, reserved_blocks);
+        free_space
+    }
+
+    /// Take the first run of `length` free blocks, returns its first block
+    pub fn allocate(&mut self, length: u64) -> u64 {
+        let block = self.predict_allocation(length);
+        self.mark_used(block, length);
+        block
+    }
+
+    /// Block the next allocation of `length` blocks would return
+    pub fn predict_allocation(&self, length: u64) -> u64 {
+        let mut start = 0;
+        let mut run = 0;
+        for (block, used) in self.used.iter().by_vals().enumerate() {
+            if used {
+                start = block as u64 + 1;
+                run = 0;
+                continue;
+            }
+            run += 1;
+            if run == length {
+                return start;
+            }
+        }
+        // Free blocks at the end of the file, then blocks past it
+        start
+    }
+
+    pub fn mark_used(&mut self, block: u64, length: u64) {
+        let end = (block + length) as usize;
+        if self.used.len() < end {
+            self.used.resize(end, false);
+        }
+        self.used[block as usize..end].fill(true);
+    }
+
+    pub fn free(&mut self, block: u64, length: u64) {
+        let end = ((block + length) as usize).min(self.used.len());
+        if (block as usize) < end {
+            self.used[block as usize..end].fill(false);
+        }
+    }
+
+    /// None of the blocks is in use
+    pub fn is_free(&self, block: u64, length: u64) -> bool {
+        let end = ((block + length) as usize).min(self.used.len());
+        (block as usize) >= end || self.used[block as usize..end].not_any()
+    }
+
+    /// Number of blocks in use
+    pub fn used_blocks(&self) -> u64 {
+        self.used.count_ones() as u64
+    }
+
+    /// Block after the last block in use, the file can be truncated to it
+    pub fn end_block(&self) -> u64 {
+        self.used.last_one().map_or(0, |block| block as u64 + 1)
+    }
+
+    /// Percentage of the blocks up to `end_block` which are in use
+    pub fn fill_rate(&self) -> u8 {
+        let end = self.end_block();
+        if end == 0 {
+            return 100;
+        }
+        (self.used_blocks() * 100 / end) as u8
+    }
+}
@@ -0,0 +1,41 @@
+use std::ops::Add;
+
+/// Snapshot of the I/O counters of a file store
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IoStats {
+    /// Reads from the file, reads served from prefetched bytes excluded
+    pub read_count: u64,
+    pub read_bytes: u64,
+    pub write_count: u64,
+    pub write_bytes: u64,
+    /// Reads served from bytes prefetched by read-ahead
+    pub read_ahead_hits: u64,
+}
+
+impl IoStats {
+    /// Counters accumulated since the `earlier` snapshot of the same store
+    pub fn since(&self, earlier: &IoStats) -> IoStats {
+        IoStats {
+            read_count: self.read_count.saturating_sub(earlier.read_count),
+            read_bytes: self.read_bytes.saturating_sub(earlier.read_bytes),
+            write_count: self.write_count.saturating_sub(earlier.write_count),
+            write_bytes: self.write_bytes.saturating_sub(earlier.write_bytes),
+            read_ahead_hits: self.read_ahead_hits.saturating_sub(earlier.read_ahead_hits),
+        }
+    }
+}
+
+/// Counters of several stores together
+impl Add for IoStats {
+    type Output = IoStats;
+
+    fn add(self, other: IoStats) -> IoStats {
+        IoStats {
+            read_count: self.read_count + other.read_count,
+            read_bytes: self.read_bytes + other.read_bytes,
+            write_count: self.write_count + other.write_count,
+            write_bytes: self.write_bytes + other.write_bytes,
+            read_ahead_hits: self.read_ahead_hits + other.read_ahead_hits,
+        }
+    }
+}
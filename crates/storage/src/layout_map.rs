@@ -0,0 +1,187 @@
+use crate::error::StorageError;
+use crate::page::ChunkId;
+use bitvec::prelude::BitVec;
+use std::collections::BTreeMap;
+
+/// Named map in the layout map
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MapEntry {
+    pub id: u32,
+    /// Position of the root page of the map, 0 while the map is empty
+    pub root_position: u64,
+}
+
+/// Metadata of the named maps in a store: the id and root page position of each map,
+/// and the removed pages of the chunks.
+/// It is written as the last page of every committed chunk, the chunk header points to it
+/// by `layout_root_position`, so the maps of the newest chunk are found on open.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LayoutMap {
+    maps: BTreeMap<String, MapEntry>,
+    /// Last assigned map id, ids are never reused
+    last_map_id: u32,
+    /// Occupancy of the chunks with removed pages by chunk id (set bit = removed page)
+    chunks: BTreeMap<ChunkId, BitVec>,
+}
+
+impl LayoutMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an empty map, returns the id of the map with the name if it exists already
+    pub fn create_map(&mut self, name: &str) -> u32 {
+        if let Some(entry) = self.maps.get(name) {
+            return entry.id;
+        }
+        self.last_map_id += 1;
+        self.maps.insert(
+            name.to_string(),
+            MapEntry {
+                id: self.last_map_id,
+                root_position: 0,
+            },
+        );
+        self.last_map_id
+    }
+
+    pub fn get(&self, name: &str) -> Option<MapEntry> {
+        self.maps.get(name).copied()
+    }
+
+    /// Name of the map with the id
+    pub fn name(&self, id: u32) -> Option<&str> {
+        self.maps
+            .iter()
+            .find(|(_, entry)| entry.id == id)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Record a new root page of the map, returns false if there is no such map
+    pub fn set_root_position(&mut self, name: &str, root_position: u64) -> bool {
+        match self.maps.get_mut(name) {
+            Some(entry) => {
+                entry.root_position = root_position;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove_map(&mut self, name: &str) -> Option<MapEntry> {
+        self.maps.remove(name)
+    }
+
+    /// Names of the maps in ascending order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.maps.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.maps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.maps.is_empty()
+    }
+
+    pub fn last_map_id(&self) -> u32 {
+        self.last_map_id
+    }
+
+    /// Record the removed pages of a chunk, a chunk without removed pages needs no entry
+    pub fn set_chunk_occupancy(&mut self, id: ChunkId, occupancy: BitVec) {
+        self.chunks.insert(id, occupancy);
+    }
+
+    pub fn chunk_occupancy(&self, id: ChunkId) -> Option<&BitVec> {
+        self.chunks.get(&id)
+    }
+
+    /// Chunks with removed pages in id order
+    pub fn chunk_occupancies(&self) -> impl Iterator<Item = (ChunkId, &BitVec)> {
+        self.chunks.iter().map(|(id, occupancy)| (*id, occupancy))
+    }
+
+    pub fn clear_chunk_occupancies(&mut self) {
+        self.chunks.clear();
+    }
+
+    /// Layout: last map id (u32), map count (u32), then for each map its name length (u16),
+    /// name bytes, id (u32) and root position (u64). Chunk count (u32) follows, then for
+    /// each chunk its id (u32), page count (u32) and occupancy bits packed eight per byte.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.last_map_id.to_le_bytes());
+        bytes.extend_from_slice(&(self.maps.len() as u32).to_le_bytes());
+        for (name, entry) in &self.maps {
+            bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(&entry.id.to_le_bytes());
+            bytes.extend_from_slice(&entry.root_position.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(self.chunks.len() as u32).to_le_bytes());
+        for (id, occupancy) in &self.chunks {
+            bytes.extend_from_slice(&id.to_le_bytes());
+            bytes.extend_from_slice(&(occupancy.len() as u32).to_le_bytes());
+            let start = bytes.len();
+            bytes.resize(start + occupancy.len().div_ceil(8), 0);
+            for page_number in occupancy.iter_ones() {
+                bytes[start + page_number / 8] |= 1 << (page_number % 8);
+            }
+        }
+        bytes
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, StorageError> {
+        let mut offset = 0;
+        let last_map_id = u32::from_le_bytes(take(bytes, &mut offset, 4)?.try_into().unwrap());
+        let count = u32::from_le_bytes(take(bytes, &mut offset, 4)?.try_into().unwrap());
+
+        let mut maps = BTreeMap::new();
+        for _ in 0..count {
+            let length = u16::from_le_bytes(take(bytes, &mut offset, 2)?.try_into().unwrap());
+            let name = String::from_utf8(take(bytes, &mut offset, length as usize)?.to_vec())
+                .map_err(|_| invalid("map name is not UTF-8"))?;
+            let id = u32::from_le_bytes(take(bytes, &mut offset, 4)?.try_into().unwrap());
+            let root_position =
+                u64::from_le_bytes(take(bytes, &mut offset, 8)?.try_into().unwrap());
+            maps.insert(name, MapEntry { id, root_position });
+        }
+
+        let count = u32::from_le_bytes(take(bytes, &mut offset, 4)?.try_into().unwrap());
+        let mut chunks = BTreeMap::new();
+        for _ in 0..count {
+            let id = u32::from_le_bytes(take(bytes, &mut offset, 4)?.try_into().unwrap());
+            let page_count = u32::from_le_bytes(take(bytes, &mut offset, 4)?.try_into().unwrap());
+            let bits = take(bytes, &mut offset, (page_count as usize).div_ceil(8))?;
+            let occupancy: BitVec = (0..page_count as usize)
+                .map(|page_number| bits[page_number / 8] & (1 << (page_number % 8)) != 0)
+                .collect();
+            chunks.insert(id, occupancy);
+        }
+        if offset != bytes.len() {
+            return Err(invalid("trailing bytes"));
+        }
+
+        Ok(LayoutMap {
+            maps,
+            last_map_id,
+            chunks,
+        })
+    }
+}
+
+fn take<'a>(bytes: &'a [u8], offset: &mut usize, length: usize) -> Result<&'a [u8], StorageError> {
+    let end = *offset + length;
+    if end > bytes.len() {
+        return Err(invalid("unexpected end"));
+    }
+    let slice = &bytes[*offset..end];
+    *offset = end;
+    Ok(slice)
+}
+
+fn invalid(reason: &str) -> StorageError {
+    StorageError::InvalidLayout(format!("Invalid layout map: {}", reason))
+}
@@ -1,26 +1,58 @@
+mod change_watch;
 mod chunk;
+mod chunk_compression;
+mod chunk_encryption;
+mod chunk_gc;
 mod chunk_i12n;
 mod chunk_i12n_margin;
+mod chunk_recovery;
+mod chunk_writer;
+mod data_type;
 mod data_util;
+mod direct_io;
+mod encryption;
 mod error;
 mod file_store;
 mod file_store_i12n;
+mod file_sync;
+mod free_space;
+mod io_stats;
+mod layout_map;
+mod memory_file_store;
+mod mv_map;
 mod page;
 mod page_impl;
+mod page_serialization;
+mod read_ahead;
+mod snapshot;
 mod storage_engine;
 mod test;
 
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use change_watch::VersionWatcher;
+pub use chunk::Chunk;
+pub use chunk_gc::{CompactReport, RetentionPolicy};
+pub use chunk_recovery::RecoveryReport;
+pub use chunk_writer::CommitAck;
+pub use data_type::DataType;
+pub use data_util::{
+    PAGE_LARGE, PAGE_POSITION_REMOVED, PAGE_POSITION_UNSAVED, PAGE_TYPE_LEAF, PAGE_TYPE_NODE,
+    compose_page_position, decode_page_length, encode_page_length, get_page_chunk_id,
+    get_page_max_length, get_page_offset, get_page_type, is_page_saved,
+};
+pub use direct_io::DIRECT_IO_ALIGNMENT;
+pub use encryption::{
+    Argon2Params, EncryptionKey, KeyDerivation, Keyring, NONCE_SIZE, SALT_SIZE, TAG_SIZE,
+    generate_salt, random_bytes,
+};
+pub use error::StorageError;
+pub use file_store::{FileStore, FileStoreBackend, FileStoreOptions};
+pub use file_sync::{Durability, sync_parent_directory};
+pub use io_stats::IoStats;
+pub use layout_map::{LayoutMap, MapEntry};
+pub use memory_file_store::MemoryFileStore;
+pub use mv_map::MvMap;
+pub use page::{ChunkId, Page, PageKind, PageNumber, PagePosition, PageReference};
+pub use page_serialization::ChunkBuilder;
+pub use read_ahead::AccessHint;
+pub use snapshot::Snapshot;
+pub use storage_engine::Store;
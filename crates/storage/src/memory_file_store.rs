@@ -0,0 +1,54 @@
+use crate::error::StorageError;
+use std::io;
+
+/// File contents kept in a growable buffer, for tests and ephemeral stores.
+/// Writes past the end grow the buffer, the gap is zero filled like a sparse file.
+#[derive(Debug, Default)]
+pub struct MemoryFileStore {
+    data: Vec<u8>,
+}
+
+impl MemoryFileStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn size(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    pub fn read_fully(&self, offset: u64, length: u32) -> Result<Vec<u8>, StorageError> {
+        let end = offset + length as u64;
+        if end > self.size() {
+            return Err(StorageError::IoError(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "Cannot read {} bytes at offset {}: would exceed size {}",
+                    length,
+                    offset,
+                    self.size()
+                ),
+            )));
+        }
+        Ok(self.data[offset as usize..end as usize].to_vec())
+    }
+
+    pub fn write_fully(&mut self, offset: u64, buffer: &[u8]) -> Result<(), StorageError> {
+        let end = offset as usize + buffer.len();
+        if self.data.len() < end {
+            self.data.resize(end, 0);
+        }
+        self.data[offset as usize..end].copy_from_slice(buffer);
+        Ok(())
+    }
+
+    /// Nothing to make durable
+    pub fn sync(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    /// Contents written so far
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+}
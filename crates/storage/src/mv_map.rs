@@ -0,0 +1,400 @@
+use crate::data_type::DataType;
+use crate::data_util::{PAGE_POSITION_UNSAVED, is_page_saved};
+use crate::error::StorageError;
+use crate::page::{ChunkId, Page, PageKind, PagePosition, PageReference};
+use crate::page_serialization::ChunkBuilder;
+use crate::storage_engine::Store;
+use std::ops::{Bound, RangeBounds};
+use std::sync::atomic::Ordering;
+
+/// Keys a page holds before it is split
+const MAX_KEYS_PER_PAGE: usize = 48;
+
+/// Persistent ordered map of a store, a copy-on-write B-tree of pages in its chunks.
+/// Pages are loaded on first access and kept. Changed pages are written by `commit`,
+/// the pages they replace are removed from their chunks then. Internal pages hold one
+/// key less than children, child `i` holds the keys below `keys[i]`.
+/// Operations take the store the map was opened from.
+#[derive(Debug)]
+pub struct MvMap<Key, Value> {
+    name: String,
+    id: u32,
+    root: Page<Key, Value>,
+    /// Positions of the saved pages replaced since the last commit
+    replaced: Vec<PagePosition>,
+}
+
+impl Store {
+    /// Open the named map, an unknown name creates an empty map.
+    /// The map is stored in the layout map by the next commit.
+    pub fn open_map<Key: DataType + Ord + Clone, Value: DataType + Clone>(
+        &mut self,
+        name: &str,
+    ) -> Result<MvMap<Key, Value>, StorageError> {
+        self.check_open()?;
+        let id = self.layout.create_map(name);
+        let root = match self.layout.get(name).map_or(0, |entry| entry.root_position) {
+            0 => Page::new_leaf(id, Vec::new(), Vec::new()),
+            position => self.read_tree_page(position)?,
+        };
+        Ok(MvMap {
+            name: name.to_string(),
+            id,
+            root,
+            replaced: Vec::new(),
+        })
+    }
+}
+
+impl<Key: DataType + Ord + Clone, Value: DataType + Clone> MvMap<Key, Value> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Number of entries
+    pub fn len(&self) -> u64 {
+        entry_count(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&mut self, store: &mut Store, key: &Key) -> Result<Option<Value>, StorageError> {
+        get(&mut self.root, store, key)
+    }
+
+    pub fn contains_key(&mut self, store: &mut Store, key: &Key) -> Result<bool, StorageError> {
+        Ok(self.get(store, key)?.is_some())
+    }
+
+    /// Insert or replace the value of the key, returns the previous value
+    pub fn put(
+        &mut self,
+        store: &mut Store,
+        key: Key,
+        value: Value,
+    ) -> Result<Option<Value>, StorageError> {
+        let previous = put(&mut self.root, store, key, value, &mut self.replaced)?;
+        if self.root.get_key_count() > MAX_KEYS_PER_PAGE {
+            let (separator, right) = split(&mut self.root);
+            let left = std::mem::replace(
+                &mut self.root,
+                Page::new_internal(self.id, Vec::new(), Vec::new(), 0),
+            );
+            let total_count = entry_count(&left) + entry_count(&right);
+            self.root = Page::new_internal(
+                self.id,
+                vec![separator],
+                vec![reference(left), reference(right)],
+                total_count,
+            );
+        }
+        Ok(previous)
+    }
+
+    /// Remove the key, returns its value
+    pub fn remove(&mut self, store: &mut Store, key: &Key) -> Result<Option<Value>, StorageError> {
+        let removed = remove(&mut self.root, store, key, &mut self.replaced)?;
+        // An internal root with a single child is replaced by the child
+        loop {
+            let child_count = match &self.root.kind {
+                PageKind::Internal { children, .. } if children.len() <= 1 => children.len(),
+                _ => break,
+            };
+            mark_changed(&mut self.root, &mut self.replaced);
+            self.root = match child_count {
+                0 => Page::new_leaf(self.id, Vec::new(), Vec::new()),
+                _ => {
+                    let child = load_child(&mut self.root, store, 0)?;
+                    std::mem::replace(child, Page::new_leaf(self.id, Vec::new(), Vec::new()))
+                }
+            };
+        }
+        Ok(removed)
+    }
+
+    /// Entries with keys in the range, in key order
+    pub fn range<R: RangeBounds<Key>>(
+        &mut self,
+        store: &mut Store,
+        range: R,
+    ) -> Result<Vec<(Key, Value)>, StorageError> {
+        let mut entries = Vec::new();
+        collect_range(&mut self.root, store, &range, &mut entries)?;
+        Ok(entries)
+    }
+
+    /// Serialize the changed pages into the builder, record the root page in the layout map
+    /// and remove the replaced pages, several maps can be saved into one chunk
+    pub fn save(
+        &mut self,
+        store: &mut Store,
+        builder: &mut ChunkBuilder,
+    ) -> Result<(), StorageError> {
+        let root_position = match self.root.is_leaf() && self.root.get_key_count() == 0 {
+            true => 0,
+            false => self.root.write_unsaved(builder),
+        };
+        store.layout_mut().create_map(&self.name);
+        store
+            .layout_mut()
+            .set_root_position(&self.name, root_position);
+        for position in self.replaced.drain(..) {
+            store.remove_page_at(position)?;
+        }
+        Ok(())
+    }
+
+    /// Write the changed pages and the layout map as a new chunk
+    pub fn commit(&mut self, store: &mut Store) -> Result<ChunkId, StorageError> {
+        let mut builder = ChunkBuilder::new(store)?;
+        self.save(store, &mut builder)?;
+        builder.commit(store)
+    }
+}
+
+fn entry_count<Key, Value>(page: &Page<Key, Value>) -> u64 {
+    match &page.kind {
+        PageKind::Leaf { values } => values.len() as u64,
+        PageKind::Internal { total_count, .. } => *total_count,
+    }
+}
+
+/// Reference to a page not written yet
+fn reference<Key, Value>(page: Page<Key, Value>) -> PageReference<Key, Value> {
+    PageReference {
+        position: None,
+        count: entry_count(&page),
+        page: Some(Box::new(page)),
+    }
+}
+
+/// Index of the child of an internal page which holds the key
+fn child_index<Key: Ord>(keys: &[Key], key: &Key) -> usize {
+    match keys.binary_search(key) {
+        Ok(index) => index + 1,
+        Err(index) => index,
+    }
+}
+
+/// The page is about to change, it gets written again by the next commit
+fn mark_changed<Key, Value>(page: &mut Page<Key, Value>, replaced: &mut Vec<PagePosition>) {
+    let position = page.get_position();
+    if is_page_saved(position) {
+        replaced.push(position);
+        page.core
+            .position
+            .store(PAGE_POSITION_UNSAVED, Ordering::Relaxed);
+    }
+}
+
+/// Child of an internal page, read from the store on first access
+fn load_child<'a, Key: DataType, Value: DataType>(
+    page: &'a mut Page<Key, Value>,
+    store: &mut Store,
+    index: usize,
+) -> Result<&'a mut Page<Key, Value>, StorageError> {
+    let PageKind::Internal { children, .. } = &mut page.kind else {
+        return Err(StorageError::InvalidPage(
+            "Leaf pages have no children".to_string(),
+        ));
+    };
+    let child = &mut children[index];
+    if child.page.is_none() {
+        let position = child
+            .position
+            .ok_or_else(|| StorageError::InvalidPage("Child page without position".to_string()))?;
+        child.page = Some(Box::new(store.read_tree_page(position)?));
+    }
+    Ok(child.page.as_mut().unwrap())
+}
+
+/// Record the new entry count of a changed child, its position is known once it is written
+fn update_child<Key, Value>(page: &mut Page<Key, Value>, index: usize) {
+    if let PageKind::Internal { children, .. } = &mut page.kind {
+        let child = &mut children[index];
+        child.count = child
+            .page
+            .as_ref()
+            .map_or(child.count, |page| entry_count(page));
+        child.position = None;
+    }
+}
+
+fn get<Key: DataType + Ord, Value: DataType + Clone>(
+    page: &mut Page<Key, Value>,
+    store: &mut Store,
+    key: &Key,
+) -> Result<Option<Value>, StorageError> {
+    if let PageKind::Leaf { values } = &page.kind {
+        return Ok(page
+            .core
+            .keys
+            .binary_search(key)
+            .ok()
+            .map(|index| values[index].clone()));
+    }
+    let index = child_index(&page.core.keys, key);
+    get(load_child(page, store, index)?, store, key)
+}
+
+fn put<Key: DataType + Ord + Clone, Value: DataType>(
+    page: &mut Page<Key, Value>,
+    store: &mut Store,
+    key: Key,
+    value: Value,
+    replaced: &mut Vec<PagePosition>,
+) -> Result<Option<Value>, StorageError> {
+    mark_changed(page, replaced);
+    let keys = &mut page.core.keys;
+    if let PageKind::Leaf { values } = &mut page.kind {
+        return Ok(match keys.binary_search(&key) {
+            Ok(index) => Some(std::mem::replace(&mut values[index], value)),
+            Err(index) => {
+                keys.insert(index, key);
+                values.insert(index, value);
+                None
+            }
+        });
+    }
+
+    let index = child_index(keys, &key);
+    let child = load_child(page, store, index)?;
+    let previous = put(child, store, key, value, replaced)?;
+    let split = match child.get_key_count() > MAX_KEYS_PER_PAGE {
+        true => Some(split(child)),
+        false => None,
+    };
+    update_child(page, index);
+    let PageKind::Internal {
+        children,
+        total_count,
+    } = &mut page.kind
+    else {
+        unreachable!();
+    };
+    if let Some((separator, right)) = split {
+        page.core.keys.insert(index, separator);
+        children.insert(index + 1, reference(right));
+    }
+    if previous.is_none() {
+        *total_count += 1;
+    }
+    Ok(previous)
+}
+
+/// Move the upper half of an overfull page into a new page, returns the key separating
+/// the two pages and the new page
+fn split<Key: Clone, Value>(page: &mut Page<Key, Value>) -> (Key, Page<Key, Value>) {
+    let tree_id = page.core.tree_id;
+    let middle = page.core.keys.len() / 2;
+    match &mut page.kind {
+        PageKind::Leaf { values } => {
+            let keys = page.core.keys.split_off(middle);
+            let values = values.split_off(middle);
+            (keys[0].clone(), Page::new_leaf(tree_id, keys, values))
+        }
+        PageKind::Internal {
+            children,
+            total_count,
+        } => {
+            let keys = page.core.keys.split_off(middle + 1);
+            let separator = page.core.keys.pop().unwrap();
+            let children = children.split_off(middle + 1);
+            let right_count: u64 = children.iter().map(|child| child.count).sum();
+            *total_count -= right_count;
+            (
+                separator,
+                Page::new_internal(tree_id, keys, children, right_count),
+            )
+        }
+    }
+}
+
+fn remove<Key: DataType + Ord, Value: DataType>(
+    page: &mut Page<Key, Value>,
+    store: &mut Store,
+    key: &Key,
+    replaced: &mut Vec<PagePosition>,
+) -> Result<Option<Value>, StorageError> {
+    if page.is_leaf() {
+        let Ok(index) = page.core.keys.binary_search(key) else {
+            return Ok(None);
+        };
+        mark_changed(page, replaced);
+        page.core.keys.remove(index);
+        let PageKind::Leaf { values } = &mut page.kind else {
+            unreachable!();
+        };
+        return Ok(Some(values.remove(index)));
+    }
+
+    let index = child_index(&page.core.keys, key);
+    let child = load_child(page, store, index)?;
+    let Some(removed) = remove(child, store, key, replaced)? else {
+        return Ok(None);
+    };
+    let child_is_empty = entry_count(child) == 0;
+    mark_changed(page, replaced);
+    update_child(page, index);
+    let PageKind::Internal {
+        children,
+        total_count,
+    } = &mut page.kind
+    else {
+        unreachable!();
+    };
+    *total_count -= 1;
+    // An empty child goes with the key separating it from a neighbour
+    if child_is_empty && children.len() > 1 {
+        children.remove(index);
+        page.core.keys.remove(index.saturating_sub(1));
+    }
+    Ok(Some(removed))
+}
+
+fn collect_range<Key: DataType + Ord + Clone, Value: DataType + Clone, R: RangeBounds<Key>>(
+    page: &mut Page<Key, Value>,
+    store: &mut Store,
+    range: &R,
+    entries: &mut Vec<(Key, Value)>,
+) -> Result<(), StorageError> {
+    if let PageKind::Leaf { values } = &page.kind {
+        for (key, value) in page.core.keys.iter().zip(values) {
+            if range.contains(key) {
+                entries.push((key.clone(), value.clone()));
+            }
+        }
+        return Ok(());
+    }
+
+    let child_count = page.core.keys.len() + 1;
+    for index in 0..child_count {
+        let keys = &page.core.keys;
+        // Child `index` holds the keys from `keys[index - 1]` up to `keys[index]`
+        let below_start = keys
+            .get(index)
+            .is_some_and(|upper| match range.start_bound() {
+                Bound::Included(start) | Bound::Excluded(start) => upper <= start,
+                Bound::Unbounded => false,
+            });
+        let lower = index.checked_sub(1).map(|lower| &keys[lower]);
+        let past_end = lower.is_some_and(|lower| match range.end_bound() {
+            Bound::Included(end) => lower > end,
+            Bound::Excluded(end) => lower >= end,
+            Bound::Unbounded => false,
+        });
+        if past_end {
+            break;
+        }
+        if !below_start {
+            collect_range(load_child(page, store, index)?, store, range, entries)?;
+        }
+    }
+    Ok(())
+}
@@ -17,7 +17,8 @@ pub struct PageCore<Key> {
     /// Encoded position of the page in the chunk.
     /// 0 = the page has not been saved yet
     /// 1 = the page marked as removed but not saved
-    /// otherwise encodes: chunk id, offset within chunk, page length, and type
+    /// otherwise encodes: chunk id, offset within chunk, page length, and type,
+    /// see `data_util::compose_page_position`
     /// Uses atomic operations to handle concurrent access during save/remove operations
     pub position: AtomicU64,
     /// Sequential 0-based page number within the chunk.
@@ -79,4 +80,4 @@ pub struct Page<Key, Value> {
     pub core: PageCore<Key>,
     /// Page kind-specific fields
     pub kind: PageKind<Key, Value>,
-}
\ No newline at end of file
+}
@@ -27,31 +27,33 @@ impl<Key, Value> Page<Key, Value> {
     pub fn get_key(&self, index: usize) -> &Key {
         &self.core.keys[index]
     }
-    
+
     pub fn get_key_count(&self) -> usize {
         self.core.keys.len()
     }
-    
+
     pub fn is_leaf(&self) -> bool {
         match &self.kind {
             PageKind::Internal { .. } => false,
             PageKind::Leaf { .. } => true,
         }
     }
-    
+
     pub fn get_position(&self) -> PagePosition {
-        self.core.position.load(std::sync::atomic::Ordering::Relaxed)
+        self.core
+            .position
+            .load(std::sync::atomic::Ordering::Relaxed)
     }
 
     pub fn calculate_memory(&self) -> u32 {
         todo!("We need to implement mv map first")
     }
 
-    pub fn add_memory(&mut self, memory: u32)  {
+    pub fn add_memory(&mut self, memory: u32) {
         assert!(self.core.memory <= u32::MAX - memory);
         self.core.memory += memory;
     }
-    
+
     pub fn get_memory(&self) -> u32 {
         self.core.memory
     }
@@ -69,4 +71,4 @@ impl<Key> PageCore<Key> {
             keys,
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,215 @@
+use crate::chunk::Chunk;
+use crate::data_type::DataType;
+use crate::data_util::{
+    PAGE_TYPE_LEAF, PAGE_TYPE_NODE, compose_page_position, get_page_type, is_page_saved,
+};
+use crate::error::StorageError;
+use crate::page::{ChunkId, Page, PageCore, PageKind, PageNumber, PagePosition, PageReference};
+use crate::storage_engine::Store;
+use std::sync::atomic::Ordering;
+
+/// Serialized pages of the next chunk, in the order they are written to it.
+/// Pages know their position before the chunk is written, so a parent page can refer
+/// to children serialized before it.
+#[derive(Debug)]
+pub struct ChunkBuilder {
+    chunk_id: ChunkId,
+    pages: Vec<Vec<u8>>,
+    /// Offset in the chunk of the next page
+    offset: u32,
+}
+
+impl ChunkBuilder {
+    /// Builder for the chunk the next `Store::commit` writes
+    pub fn new(store: &Store) -> Result<Self, StorageError> {
+        Ok(ChunkBuilder {
+            chunk_id: store.next_chunk_id()?,
+            pages: Vec::new(),
+            offset: Chunk::next_page_offset(&[]),
+        })
+    }
+
+    pub fn chunk_id(&self) -> ChunkId {
+        self.chunk_id
+    }
+
+    /// Add a serialized page of the type, `PAGE_TYPE_LEAF` or `PAGE_TYPE_NODE`,
+    /// returns its position in the chunk
+    pub fn add(&mut self, bytes: Vec<u8>, page_type: u8) -> PagePosition {
+        let position =
+            compose_page_position(self.chunk_id, self.offset, bytes.len() as u32, page_type);
+        self.offset += bytes.len() as u32;
+        self.pages.push(bytes);
+        position
+    }
+
+    pub fn pages(&self) -> Vec<&[u8]> {
+        self.pages.iter().map(Vec::as_slice).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// Write the pages and the layout map as the chunk the builder was created for
+    pub fn commit(self, store: &mut Store) -> Result<ChunkId, StorageError> {
+        let id = store.commit(&self.pages())?;
+        debug_assert_eq!(id, self.chunk_id);
+        Ok(id)
+    }
+}
+
+impl<Key: DataType, Value: DataType> Page<Key, Value> {
+    /// Serialize the unsaved pages of the subtree into the builder, children before their
+    /// parent, and record their positions. Returns the position of this page.
+    pub fn write_unsaved(&mut self, builder: &mut ChunkBuilder) -> PagePosition {
+        let position = self.get_position();
+        if is_page_saved(position) {
+            return position;
+        }
+
+        if let PageKind::Internal { children, .. } = &mut self.kind {
+            for child in children.iter_mut() {
+                if let Some(page) = &mut child.page {
+                    child.position = Some(page.write_unsaved(builder));
+                }
+            }
+        }
+
+        let bytes = self.serialize();
+        self.core.disk_space_used = bytes.len() as u32;
+        self.core.page_number = builder.len() as PageNumber;
+        let position = builder.add(bytes, self.page_type());
+        self.core.position.store(position, Ordering::Relaxed);
+        position
+    }
+
+    fn page_type(&self) -> u8 {
+        match &self.kind {
+            PageKind::Leaf { .. } => PAGE_TYPE_LEAF,
+            PageKind::Internal { .. } => PAGE_TYPE_NODE,
+        }
+    }
+
+    /// Layout: tree id (u32), page type (u8), key count (u32), the keys, then the values of
+    /// a leaf, or the subtree count (u64) and the position and count (u64 each) of every
+    /// child of an internal page. Children must have been saved.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.core.tree_id.write(&mut bytes);
+        self.page_type().write(&mut bytes);
+        (self.core.keys.len() as u32).write(&mut bytes);
+        for key in &self.core.keys {
+            key.write(&mut bytes);
+        }
+
+        match &self.kind {
+            PageKind::Leaf { values } => {
+                for value in values {
+                    value.write(&mut bytes);
+                }
+            }
+            PageKind::Internal {
+                children,
+                total_count,
+            } => {
+                total_count.write(&mut bytes);
+                (children.len() as u32).write(&mut bytes);
+                for child in children {
+                    let position = child
+                        .position
+                        .or_else(|| child.page.as_ref().map(|page| page.get_position()))
+                        .unwrap_or(0);
+                    assert!(
+                        is_page_saved(position),
+                        "child page must be saved before its parent"
+                    );
+                    position.write(&mut bytes);
+                    child.count.write(&mut bytes);
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Page read from its position, children of an internal page are not loaded.
+    /// Fails if the page type does not match the type bit of the position.
+    pub fn deserialize(position: PagePosition, bytes: &[u8]) -> Result<Self, StorageError> {
+        let mut offset = 0;
+        let tree_id = u32::read(bytes, &mut offset)?;
+        let page_type = u8::read(bytes, &mut offset)?;
+        if page_type != get_page_type(position) {
+            return Err(StorageError::InvalidPage(format!(
+                "Page type {} does not match its position",
+                page_type
+            )));
+        }
+        let key_count = u32::read(bytes, &mut offset)? as usize;
+        let mut keys = Vec::with_capacity(key_count);
+        for _ in 0..key_count {
+            keys.push(Key::read(bytes, &mut offset)?);
+        }
+
+        let kind = match page_type {
+            PAGE_TYPE_LEAF => {
+                let mut values = Vec::with_capacity(key_count);
+                for _ in 0..key_count {
+                    values.push(Value::read(bytes, &mut offset)?);
+                }
+                PageKind::Leaf { values }
+            }
+            PAGE_TYPE_NODE => {
+                let total_count = u64::read(bytes, &mut offset)?;
+                let child_count = u32::read(bytes, &mut offset)? as usize;
+                let mut children = Vec::with_capacity(child_count);
+                for _ in 0..child_count {
+                    children.push(PageReference {
+                        position: Some(u64::read(bytes, &mut offset)?),
+                        page: None,
+                        count: u64::read(bytes, &mut offset)?,
+                    });
+                }
+                PageKind::Internal {
+                    children,
+                    total_count,
+                }
+            }
+            other => {
+                return Err(StorageError::InvalidPage(format!(
+                    "Unknown page type {}",
+                    other
+                )));
+            }
+        };
+        if offset != bytes.len() {
+            return Err(StorageError::InvalidPage(format!(
+                "{} trailing bytes",
+                bytes.len() - offset
+            )));
+        }
+
+        let core = PageCore::new(tree_id, keys);
+        core.position.store(position, Ordering::Relaxed);
+        let mut page = Page { core, kind };
+        page.core.disk_space_used = bytes.len() as u32;
+        Ok(page)
+    }
+}
+
+impl Store {
+    /// Read and deserialize the page at the position
+    pub fn read_tree_page<Key: DataType, Value: DataType>(
+        &mut self,
+        position: PagePosition,
+    ) -> Result<Page<Key, Value>, StorageError> {
+        let (chunk_id, page_number) = self.locate_page(position)?;
+        let bytes = self.read_page(chunk_id, page_number)?;
+        let mut page = Page::deserialize(position, &bytes)?;
+        page.core.page_number = page_number;
+        Ok(page)
+    }
+}
@@ -0,0 +1,91 @@
+use crate::file_store::FileStoreHeader;
+
+/// Access pattern hint for `FileStore::set_access_hint`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum AccessHint {
+    /// Read ahead once consecutive reads move forward through the file
+    #[default]
+    Normal,
+    /// Read ahead from the first read, e.g. for a full scan
+    Sequential,
+    /// Never read ahead
+    Random,
+}
+
+/// Forward reads in a row before `AccessHint::Normal` starts to read ahead
+const SEQUENTIAL_READS: u32 = 2;
+
+/// Read-ahead state of a file store: the bytes prefetched after the last read
+/// and the run of forward reads that triggered it.
+#[derive(Debug, Default)]
+pub struct ReadAhead {
+    pub hint: AccessHint,
+    /// Blocks prefetched past a sequential read, 0 disables read-ahead
+    pub blocks: u32,
+    /// File offset of the first prefetched byte
+    offset: u64,
+    buffer: Vec<u8>,
+    /// End of the last read, the next sequential read starts within a block of it
+    last_end: u64,
+    sequential_reads: u32,
+}
+
+impl ReadAhead {
+    pub fn new(blocks: u32) -> Self {
+        ReadAhead {
+            blocks,
+            ..Self::default()
+        }
+    }
+
+    /// Bytes of the read if they were prefetched
+    pub fn cached(&self, offset: u64, length: u32) -> Option<Vec<u8>> {
+        let start = offset.checked_sub(self.offset)? as usize;
+        let end = start + length as usize;
+        if end > self.buffer.len() {
+            return None;
+        }
+        Some(self.buffer[start..end].to_vec())
+    }
+
+    /// Record a read, returns the bytes to prefetch past its end
+    pub fn record(&mut self, offset: u64, length: u32) -> u64 {
+        let forward = offset >= self.last_end
+            && offset - self.last_end < FileStoreHeader::BLOCK_SIZE
+            && self.last_end > 0;
+        self.sequential_reads = match forward {
+            true => self.sequential_reads + 1,
+            false => 0,
+        };
+        self.last_end = offset + length as u64;
+
+        let prefetch = match self.hint {
+            AccessHint::Random => false,
+            AccessHint::Sequential => true,
+            AccessHint::Normal => self.sequential_reads >= SEQUENTIAL_READS,
+        };
+        match prefetch {
+            true => self.blocks as u64 * FileStoreHeader::BLOCK_SIZE,
+            false => 0,
+        }
+    }
+
+    /// Keep the bytes read from `offset` for the following reads
+    pub fn fill(&mut self, offset: u64, buffer: Vec<u8>) {
+        self.offset = offset;
+        self.buffer = buffer;
+    }
+
+    /// Drop prefetched bytes a write overlaps
+    pub fn invalidate(&mut self, offset: u64, length: u64) {
+        let end = self.offset + self.buffer.len() as u64;
+        if offset < end && offset + length > self.offset {
+            self.buffer.clear();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+        self.sequential_reads = 0;
+    }
+}
@@ -0,0 +1,77 @@
+use crate::error::StorageError;
+use crate::layout_map::LayoutMap;
+use crate::page::{ChunkId, PageNumber, PagePosition};
+use crate::storage_engine::Store;
+
+/// Read-only view of the store as of a version. Pages are read through the store, which
+/// keeps taking writes meanwhile. Pages removed after the version stay readable while the
+/// retention window keeps their chunks, reads fail with `ChunkNotFound` once they are freed.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    version: u64,
+    layout_root_position: u64,
+    layout: LayoutMap,
+}
+
+impl Store {
+    /// Snapshot pinned to the version of a chunk still in the store, the layout map is the
+    /// one the chunk header points to. The current version is always available once written.
+    pub fn snapshot(&mut self, version: u64) -> Result<Snapshot, StorageError> {
+        self.check_open()?;
+        let layout_root_position = self
+            .chunks
+            .values()
+            .find(|chunk| chunk.version == version)
+            .map(|chunk| chunk.layout_root_position)
+            .ok_or(StorageError::VersionNotFound(version))?;
+
+        let layout = match layout_root_position {
+            0 => LayoutMap::new(),
+            position => {
+                let (chunk_id, page_number) = self.locate_page(position)?;
+                let bytes = self.read_retained_page(chunk_id, page_number, version)?;
+                LayoutMap::deserialize(&bytes)?
+            }
+        };
+        Ok(Snapshot {
+            version,
+            layout_root_position,
+            layout,
+        })
+    }
+}
+
+impl Snapshot {
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Position of the layout map page as of the version, 0 before the first commit
+    pub fn layout_root_position(&self) -> u64 {
+        self.layout_root_position
+    }
+
+    /// Named maps as of the version
+    pub fn layout(&self) -> &LayoutMap {
+        &self.layout
+    }
+
+    /// Read a page as it was at the version, the page may be removed since
+    pub fn read_page(
+        &self,
+        store: &mut Store,
+        chunk_id: ChunkId,
+        page_number: PageNumber,
+    ) -> Result<Vec<u8>, StorageError> {
+        store.read_retained_page(chunk_id, page_number, self.version)
+    }
+
+    pub fn read_page_at(
+        &self,
+        store: &mut Store,
+        position: PagePosition,
+    ) -> Result<Vec<u8>, StorageError> {
+        let (chunk_id, page_number) = store.locate_page(position)?;
+        self.read_page(store, chunk_id, page_number)
+    }
+}
@@ -1,7 +1,672 @@
-enum StorageEngineState {
+use crate::chunk::{Chunk, ChunkFooter, ChunkHeader};
+use crate::chunk_gc::RetentionPolicy;
+use crate::chunk_recovery::RecoveryReport;
+use crate::chunk_writer::{ChunkWrite, ChunkWriter, CommitAck};
+use crate::data_util::{
+    PAGE_LARGE, PAGE_TYPE_LEAF, compose_page_position, get_page_chunk_id, get_page_max_length,
+    get_page_offset,
+};
+use crate::encryption::Keyring;
+use crate::error::StorageError;
+use crate::file_store::{FileStore, FileStoreHeader, FileStoreOptions};
+use crate::free_space::FreeSpaceBitSet;
+use crate::io_stats::IoStats;
+use crate::layout_map::LayoutMap;
+use crate::page::{ChunkId, PageNumber, PagePosition};
+use crate::read_ahead::AccessHint;
+use bytes::Bytes;
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum StorageEngineState {
     Open,
     Stopping,
     Closed,
 }
 
-struct StorageEngine {}
\ No newline at end of file
+/// Chunked store on top of a `FileStore`.
+/// Chunks are placed in runs of free blocks after the file header, each chunk starts
+/// with its header and ends with its footer in the last bytes of its last block.
+pub struct Store {
+    pub(crate) file_store: FileStore,
+    pub(crate) state: StorageEngineState,
+    /// Chunks in the file by id
+    pub(crate) chunks: BTreeMap<ChunkId, Chunk>,
+    /// Blocks used by the file header and the chunks
+    pub(crate) free_space: FreeSpaceBitSet,
+    pub(crate) last_chunk_id: ChunkId,
+    /// Version of the last written chunk
+    pub(crate) version: u64,
+    /// Time the store was created, milliseconds since the Unix epoch, from the file header
+    pub(crate) creation_time: u64,
+    /// Named maps, written with each commit
+    pub(crate) layout: LayoutMap,
+    /// Position of the layout map page, stored in the header of each chunk
+    pub(crate) layout_root_position: u64,
+    pub(crate) recovery: RecoveryReport,
+    /// zstd level new chunks are compressed at, `None` writes them uncompressed
+    pub(crate) compression_level: Option<i32>,
+    /// Keys new chunks are encrypted with and encrypted chunks are read with,
+    /// `None` writes them unencrypted
+    pub(crate) encryption: Option<Keyring>,
+    /// Decrypted and uncompressed content of the last compressed or encrypted chunk read
+    pub(crate) decoded: Option<(ChunkId, Bytes)>,
+    /// Table of content of the last chunk a page position was looked up in
+    pub(crate) table_of_content: Option<(ChunkId, Vec<(u32, u32)>)>,
+    /// How long chunks without live pages are kept
+    pub(crate) retention: RetentionPolicy,
+    /// Background thread writing the chunks, `None` writes them on the caller thread
+    pub(crate) writer: Option<ChunkWriter>,
+}
+
+impl Store {
+    pub fn open(file_name: String, options: FileStoreOptions) -> Result<Self, StorageError> {
+        Self::open_with(FileStore::open_with_options(file_name, options)?, None)
+    }
+
+    /// Store without a file, its chunks are lost when it is dropped
+    pub fn open_in_memory() -> Result<Self, StorageError> {
+        Self::open_with(FileStore::open_in_memory()?, None)
+    }
+
+    pub(crate) fn open_with(
+        file_store: FileStore,
+        encryption: Option<Keyring>,
+    ) -> Result<Self, StorageError> {
+        let creation_time = file_store.header.creation_time;
+
+        let mut store = Store {
+            file_store,
+            state: StorageEngineState::Open,
+            chunks: BTreeMap::new(),
+            free_space: FreeSpaceBitSet::new(FileStoreHeader::HEADER_BLOCKS),
+            last_chunk_id: 0,
+            version: 0,
+            creation_time,
+            layout: LayoutMap::new(),
+            layout_root_position: 0,
+            recovery: RecoveryReport::default(),
+            compression_level: None,
+            encryption,
+            decoded: None,
+            table_of_content: None,
+            retention: RetentionPolicy::default(),
+            writer: None,
+        };
+        // The scan reads a header at each block boundary from the start of the file
+        store.file_store.set_access_hint(AccessHint::Sequential);
+        let recovered = store.recover();
+        store.file_store.set_access_hint(AccessHint::Normal);
+        store.recovery = recovered?;
+        Ok(store)
+    }
+
+    /// Outcome of the chunk scan when the store was opened
+    pub fn recovery_report(&self) -> RecoveryReport {
+        self.recovery
+    }
+
+    /// Position of the layout map page of the last commit, 0 before the first commit
+    pub fn layout_root_position(&self) -> u64 {
+        self.layout_root_position
+    }
+
+    /// Named maps as of the last commit and the changes made since
+    pub fn layout(&self) -> &LayoutMap {
+        &self.layout
+    }
+
+    /// Changes to the named maps are written by the next `commit`
+    pub fn layout_mut(&mut self) -> &mut LayoutMap {
+        &mut self.layout
+    }
+
+    /// Write the pages followed by the layout map as a new chunk. A `ChunkBuilder` gives
+    /// the pages their positions before the commit, so map roots among them can be
+    /// recorded in the layout map. The layout page of the previous commit is removed,
+    /// and the layout map records the removed pages of every chunk.
+    pub fn commit(&mut self, pages: &[&[u8]]) -> Result<ChunkId, StorageError> {
+        self.commit_with(pages, true)
+    }
+
+    /// Like `commit`, but returns the version of the new chunk once it is queued for the
+    /// background writer, without waiting for it to be written and synced.
+    /// Without a running writer the chunk is written before returning.
+    pub fn commit_async(&mut self, pages: &[&[u8]]) -> Result<u64, StorageError> {
+        self.commit_with(pages, false)?;
+        Ok(self.version)
+    }
+
+    fn commit_with(&mut self, pages: &[&[u8]], wait: bool) -> Result<ChunkId, StorageError> {
+        let id = self.next_chunk_id()?;
+        let previous = self.layout_root_position;
+        // Restored if the commit fails, the previous layout page is current until then
+        let previous_chunk = self.chunks.get(&get_page_chunk_id(previous)).cloned();
+        if previous != 0 {
+            self.remove_page_at(previous)?;
+        }
+        self.record_occupancy();
+
+        let layout = self.layout.serialize();
+        let mut chunk_pages = pages.to_vec();
+        chunk_pages.push(&layout);
+        self.layout_root_position = compose_page_position(
+            id,
+            Chunk::next_page_offset(pages),
+            layout.len() as u32,
+            PAGE_TYPE_LEAF,
+        );
+        if let Err(e) = self.write_chunk(&chunk_pages, wait) {
+            self.layout_root_position = previous;
+            if let Some(chunk) = previous_chunk {
+                self.chunks.insert(chunk.id, chunk);
+            }
+            return Err(e);
+        }
+        Ok(id)
+    }
+
+    /// Copy the occupancy of the chunks with removed pages into the layout map
+    fn record_occupancy(&mut self) {
+        self.layout.clear_chunk_occupancies();
+        for chunk in self.chunks.values() {
+            if chunk.page_count_live < chunk.page_count {
+                self.layout
+                    .set_chunk_occupancy(chunk.id, chunk.occupancy.clone());
+            }
+        }
+    }
+
+    /// Read a live page by its position
+    pub fn read_page_at(&mut self, position: PagePosition) -> Result<Vec<u8>, StorageError> {
+        let (chunk_id, page_number) = self.locate_page(position)?;
+        self.read_page(chunk_id, page_number)
+    }
+
+    /// Mark the page at the position removed, see `remove_page`
+    pub fn remove_page_at(&mut self, position: PagePosition) -> Result<(), StorageError> {
+        let (chunk_id, page_number) = self.locate_page(position)?;
+        self.remove_page(chunk_id, page_number)
+    }
+
+    /// Chunk id and number of the page at the position, found by its offset in the
+    /// table of content of the chunk. Fails if no page starts at the offset or the page
+    /// is longer than the length code of the position allows.
+    pub(crate) fn locate_page(
+        &mut self,
+        position: PagePosition,
+    ) -> Result<(ChunkId, PageNumber), StorageError> {
+        let (chunk_id, offset) = (get_page_chunk_id(position), get_page_offset(position));
+        if self
+            .table_of_content
+            .as_ref()
+            .is_none_or(|(id, _)| *id != chunk_id)
+        {
+            let entries = self.read_table_of_content(chunk_id)?;
+            self.table_of_content = Some((chunk_id, entries));
+        }
+
+        let (_, entries) = self.table_of_content.as_ref().unwrap();
+        let page_number = entries
+            .binary_search_by_key(&offset, |(offset, _)| *offset)
+            .map_err(|_| {
+                StorageError::InvalidPage(format!(
+                    "No page at offset {} of chunk {}",
+                    offset, chunk_id
+                ))
+            })?;
+        // The largest length code covers pages of any length
+        let max_length = get_page_max_length(position);
+        if max_length < PAGE_LARGE && entries[page_number].1 > max_length {
+            return Err(StorageError::InvalidPage(format!(
+                "Page at offset {} of chunk {} is longer than its position allows",
+                offset, chunk_id
+            )));
+        }
+        Ok((chunk_id, page_number as PageNumber))
+    }
+
+    /// Start a background thread writing and syncing the chunks of later commits.
+    /// `commit_async` returns once the chunk is queued, the returned handle tells which
+    /// versions are durable. Reads and other writes of the store wait for the queued chunks.
+    /// In-memory and read-only stores have no writer.
+    pub fn start_writer(&mut self) -> Result<CommitAck, StorageError> {
+        self.check_writable()?;
+        if let Some(writer) = &self.writer {
+            return Ok(writer.ack().clone());
+        }
+        let file = self.file_store.writer_handle()?;
+        let writer = ChunkWriter::start(
+            file,
+            self.file_store.durability,
+            self.file_store.direct_io,
+            self.version,
+        )?;
+        let ack = writer.ack().clone();
+        self.writer = Some(writer);
+        Ok(ack)
+    }
+
+    /// Write the queued chunks and stop the background writer, later chunks are
+    /// written on the caller thread. Fails if a queued chunk could not be written.
+    pub fn stop_writer(&mut self) -> Result<(), StorageError> {
+        match self.writer.take() {
+            Some(mut writer) => writer.stop(),
+            None => Ok(()),
+        }
+    }
+
+    /// Acknowledgements of the background writer, `None` if it is not running
+    pub fn commit_ack(&self) -> Option<CommitAck> {
+        self.writer.as_ref().map(|writer| writer.ack().clone())
+    }
+
+    /// Block until the chunk of `version` is written and synced
+    pub fn wait_for_version(&self, version: u64) -> Result<(), StorageError> {
+        match &self.writer {
+            Some(writer) => writer.ack().wait(version),
+            None => Ok(()),
+        }
+    }
+
+    /// Block until the queued chunks are written, before the file is read or written
+    /// from the caller thread
+    fn wait_for_writes(&self) -> Result<(), StorageError> {
+        match &self.writer {
+            Some(writer) if writer.is_pending() => writer.wait_all(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Write the queued chunks and the file header with the last chunk, sync the file,
+    /// release its write lock and close the store, later operations fail with
+    /// `StorageError::Closed`
+    pub fn close(&mut self) -> Result<(), StorageError> {
+        if self.state != StorageEngineState::Open {
+            return Ok(());
+        }
+        self.state = StorageEngineState::Stopping;
+        let synced = self
+            .stop_writer()
+            .and_then(|_| match self.file_store.read_only {
+                true => Ok(()),
+                false => self.write_file_header(),
+            })
+            .and_then(|_| self.file_store.sync());
+        // Released even if the sync failed, the store takes no more writes
+        let unlocked = self.file_store.unlock();
+        self.state = StorageEngineState::Closed;
+        synced.and(unlocked)
+    }
+
+    /// Record the version and the last chunk in the file header, durable with the next sync
+    fn write_file_header(&mut self) -> Result<(), StorageError> {
+        let header = &mut self.file_store.header;
+        header.version = self.version;
+        header.last_chunk_id = self.last_chunk_id;
+        header.last_chunk_block = self
+            .chunks
+            .get(&self.last_chunk_id)
+            .map_or(0, |chunk| chunk.block);
+        self.file_store.write_header()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state == StorageEngineState::Closed
+    }
+
+    /// Version of the last written chunk, 0 for an empty store
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn chunk(&self, id: ChunkId) -> Option<&Chunk> {
+        self.chunks.get(&id)
+    }
+
+    /// Chunks in the file in id order
+    pub fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.chunks.values()
+    }
+
+    /// I/O counters of the store file since it was opened
+    pub fn io_stats(&self) -> IoStats {
+        self.file_store.stats()
+    }
+
+    /// I/O counters of the store file since the previous call
+    pub fn interval_io_stats(&mut self) -> IoStats {
+        self.file_store.interval_stats()
+    }
+
+    /// Percentage of the file blocks in use by the header and the chunks
+    pub fn fill_rate(&self) -> u8 {
+        self.free_space.fill_rate()
+    }
+
+    /// Write the pages as a new chunk with the next version and sync it,
+    /// pages are addressed by the chunk id and their index in `pages`.
+    /// The chunk content is compressed if a compression level is set and that makes it smaller,
+    /// then encrypted if a keyring is set.
+    /// The chunk is placed in the first run of free blocks large enough to hold it.
+    pub fn write_pages(&mut self, pages: &[&[u8]]) -> Result<ChunkId, StorageError> {
+        self.write_chunk(pages, true)
+    }
+
+    /// Write the pages as a new chunk, queued for the background writer if it is
+    /// running. Without `wait` the chunk is not yet durable when this returns.
+    fn write_chunk(&mut self, pages: &[&[u8]], wait: bool) -> Result<ChunkId, StorageError> {
+        self.check_writable()?;
+        let content_length = Chunk::content_length(pages);
+        Chunk::block_count(content_length).ok_or(StorageError::ChunkTooLarge(content_length))?;
+
+        let mut chunk = Chunk::new(self.next_chunk_id()?);
+        chunk.set_pages(pages);
+        // Authenticated along with encrypted content
+        chunk.version = self.version + 1;
+        if let Some(level) = self.compression_level {
+            chunk.compress(level)?;
+        }
+        if let Some(keyring) = &self.encryption {
+            chunk.encrypt(keyring)?;
+        }
+        let length = Chunk::block_count(chunk.buffer.len())
+            .ok_or(StorageError::ChunkTooLarge(chunk.buffer.len()))?;
+
+        chunk.time = now_millis().saturating_sub(self.creation_time);
+        chunk.length = length;
+        chunk.block = self.free_space.allocate(length as u64);
+        chunk.next = self.free_space.predict_allocation(length as u64);
+        chunk.layout_root_position = self.layout_root_position;
+        chunk.map_id = self.layout.last_map_id();
+
+        let position = chunk.file_position();
+        let buffer = chunk.serialize();
+        let written = match &mut self.writer {
+            Some(writer) => writer.submit(ChunkWrite {
+                version: chunk.version,
+                position,
+                buffer,
+            }),
+            None => self
+                .file_store
+                .write_fully(position, &buffer)
+                .and_then(|_| self.file_store.sync()),
+        };
+        if let Err(e) = written {
+            self.free_space.free(chunk.block, length as u64);
+            return Err(e);
+        }
+        if self.writer.is_some() {
+            self.file_store
+                .record_write(position, chunk.byte_length() as usize);
+        }
+
+        // Written chunks are read back from the file
+        chunk.buffer = Bytes::new();
+        self.version = chunk.version;
+        self.last_chunk_id = chunk.id;
+        let id = chunk.id;
+        self.chunks.insert(id, chunk);
+        if wait {
+            self.wait_for_version(self.version)?;
+        }
+        Ok(id)
+    }
+
+    /// Read a live page of a chunk
+    pub fn read_page(
+        &mut self,
+        chunk_id: ChunkId,
+        page_number: PageNumber,
+    ) -> Result<Vec<u8>, StorageError> {
+        let (offset, length) = self.page_location(chunk_id, page_number)?;
+        self.read_chunk_range(chunk_id, offset, length)
+    }
+
+    /// Mark a page of a chunk removed, e.g. once a newer version of it was written.
+    /// Chunks without live pages are freed by `compact` once the retention window
+    /// passed, sparse ones are rewritten. The removal is persisted by the next `commit`.
+    pub fn remove_page(
+        &mut self,
+        chunk_id: ChunkId,
+        page_number: PageNumber,
+    ) -> Result<(), StorageError> {
+        self.check_writable()?;
+        let (_, length) = self.page_location(chunk_id, page_number)?;
+        if let Some(chunk) = self.chunks.get_mut(&chunk_id) {
+            chunk.remove_page(page_number, length);
+        }
+        self.mark_unused(chunk_id);
+        Ok(())
+    }
+
+    /// Offset in the chunk and length of a live page
+    fn page_location(
+        &mut self,
+        chunk_id: ChunkId,
+        page_number: PageNumber,
+    ) -> Result<(u32, u32), StorageError> {
+        self.check_open()?;
+        let chunk = self
+            .chunks
+            .get(&chunk_id)
+            .ok_or(StorageError::ChunkNotFound(chunk_id))?;
+        if !chunk.is_page_live(page_number) {
+            return Err(StorageError::PageNotFound {
+                chunk_id,
+                page_number,
+            });
+        }
+        self.table_of_content_entry(chunk_id, page_number)
+    }
+
+    /// Read a page of a chunk written at `max_version` or before, removed pages included.
+    /// Pages of chunks freed since, or of a newer chunk with a reused id, are not found.
+    pub(crate) fn read_retained_page(
+        &mut self,
+        chunk_id: ChunkId,
+        page_number: PageNumber,
+        max_version: u64,
+    ) -> Result<Vec<u8>, StorageError> {
+        self.check_open()?;
+        let chunk = self
+            .chunks
+            .get(&chunk_id)
+            .filter(|chunk| chunk.version <= max_version)
+            .ok_or(StorageError::ChunkNotFound(chunk_id))?;
+        if page_number >= chunk.page_count {
+            return Err(StorageError::PageNotFound {
+                chunk_id,
+                page_number,
+            });
+        }
+        let (offset, length) = self.table_of_content_entry(chunk_id, page_number)?;
+        self.read_chunk_range(chunk_id, offset, length)
+    }
+
+    /// Offset in the chunk and length of a page from the table of content
+    fn table_of_content_entry(
+        &mut self,
+        chunk_id: ChunkId,
+        page_number: PageNumber,
+    ) -> Result<(u32, u32), StorageError> {
+        let chunk = &self.chunks[&chunk_id];
+        let offset =
+            chunk.table_of_content_position + (page_number as usize * Chunk::TOC_ENTRY_SIZE) as u32;
+        let entry = self.read_chunk_range(chunk_id, offset, Chunk::TOC_ENTRY_SIZE as u32)?;
+        Ok((
+            u32::from_le_bytes(entry[..4].try_into().unwrap()),
+            u32::from_le_bytes(entry[4..].try_into().unwrap()),
+        ))
+    }
+
+    /// Offset in the chunk and length of every page of the chunk, removed ones included
+    pub(crate) fn read_table_of_content(
+        &mut self,
+        chunk_id: ChunkId,
+    ) -> Result<Vec<(u32, u32)>, StorageError> {
+        self.check_open()?;
+        let chunk = self
+            .chunks
+            .get(&chunk_id)
+            .ok_or(StorageError::ChunkNotFound(chunk_id))?;
+        let offset = chunk.table_of_content_position;
+        let length = chunk.page_count * Chunk::TOC_ENTRY_SIZE as u32;
+        let bytes = match length {
+            0 => Vec::new(),
+            _ => self.read_chunk_range(chunk_id, offset, length)?,
+        };
+        self.chunks[&chunk_id].parse_table_of_content(&bytes)
+    }
+
+    /// Bytes at an offset from the start of the chunk, offsets of a compressed or
+    /// encrypted chunk refer to its decoded content
+    fn read_chunk_range(
+        &mut self,
+        chunk_id: ChunkId,
+        offset: u32,
+        length: u32,
+    ) -> Result<Vec<u8>, StorageError> {
+        self.wait_for_writes()?;
+        let chunk = &self.chunks[&chunk_id];
+        if !chunk.is_compressed() && !chunk.is_encrypted() {
+            let position = chunk.file_position() + offset as u64;
+            return self.file_store.read_fully(position, length);
+        }
+        let content = self.decoded_content(chunk_id)?;
+        let start = (offset as usize).saturating_sub(ChunkHeader::SIZE);
+        content
+            .get(start..start + length as usize)
+            .map(|bytes| bytes.to_vec())
+            .ok_or_else(|| {
+                StorageError::InvalidChunkHeader(format!(
+                    "Range at {} of chunk {} is past its content",
+                    offset, chunk_id
+                ))
+            })
+    }
+
+    /// Content of the chunk between its header and footer, zero padded to the end of
+    /// its last block, or the decoded content of a compressed or encrypted chunk.
+    /// Fails if the header or footer in the file does not match the chunk.
+    pub fn read_chunk(&mut self, id: ChunkId) -> Result<Vec<u8>, StorageError> {
+        self.check_open()?;
+        let chunk = self
+            .chunks
+            .get(&id)
+            .ok_or(StorageError::ChunkNotFound(id))?;
+        let (position, byte_length) = (chunk.file_position(), chunk.byte_length());
+        self.wait_for_writes()?;
+
+        let mut bytes = self.file_store.read_fully(position, byte_length as u32)?;
+        let header = Chunk::deserialize_header(&bytes[..ChunkHeader::SIZE])?;
+        let footer_bytes = &bytes[bytes.len() - ChunkFooter::SIZE..];
+        if header.magic != ChunkHeader::MAGIC
+            || header.id != id
+            || !Chunk::verify_footer(footer_bytes)
+            || Chunk::deserialize_footer(footer_bytes)?.id != id
+        {
+            return Err(StorageError::InvalidChunkHeader(format!(
+                "Chunk {} does not match its header or footer at block {}",
+                id,
+                position / FileStoreHeader::BLOCK_SIZE
+            )));
+        }
+
+        bytes.truncate(bytes.len() - ChunkFooter::SIZE);
+        bytes.drain(..ChunkHeader::SIZE);
+        self.chunks[&id].decode(bytes, self.encryption.as_ref())
+    }
+
+    /// Decoded content of a compressed or encrypted chunk between its header and footer.
+    /// The content of the last chunk read is kept, pages of a chunk are often read together.
+    pub(crate) fn decoded_content(&mut self, chunk_id: ChunkId) -> Result<Bytes, StorageError> {
+        if let Some((id, content)) = &self.decoded
+            && *id == chunk_id
+        {
+            return Ok(content.clone());
+        }
+        let chunk = self
+            .chunks
+            .get(&chunk_id)
+            .ok_or(StorageError::ChunkNotFound(chunk_id))?;
+        let position = chunk.file_position() + ChunkHeader::SIZE as u64;
+        let stored = self
+            .file_store
+            .read_fully(position, chunk.stored_length())?;
+        let content = Bytes::from(chunk.decode(stored, self.encryption.as_ref())?);
+        self.decoded = Some((chunk_id, content.clone()));
+        Ok(content)
+    }
+
+    /// Forget the chunk and release its blocks for new chunks.
+    /// The chunk header is erased so the scan on open does not find the chunk again,
+    /// the erasure is durable with the next sync.
+    pub fn free_chunk(&mut self, id: ChunkId) -> Result<(), StorageError> {
+        self.check_writable()?;
+        self.wait_for_writes()?;
+        let chunk = self
+            .chunks
+            .get(&id)
+            .ok_or(StorageError::ChunkNotFound(id))?;
+        self.file_store
+            .write_fully(chunk.file_position(), &[0u8; ChunkHeader::SIZE])?;
+        self.free_space.free(chunk.block, chunk.length as u64);
+        self.chunks.remove(&id);
+        if self
+            .decoded
+            .as_ref()
+            .is_some_and(|(cached, _)| *cached == id)
+        {
+            self.decoded = None;
+        }
+        if self
+            .table_of_content
+            .as_ref()
+            .is_some_and(|(cached, _)| *cached == id)
+        {
+            self.table_of_content = None;
+        }
+        Ok(())
+    }
+
+    /// Id the next written chunk gets: the id after the last chunk id,
+    /// wrapping around and skipping ids still in use
+    pub fn next_chunk_id(&self) -> Result<ChunkId, StorageError> {
+        let mut id = self.last_chunk_id;
+        for _ in 0..Chunk::MAX_ID {
+            id = if id >= Chunk::MAX_ID { 1 } else { id + 1 };
+            if !self.chunks.contains_key(&id) {
+                return Ok(id);
+            }
+        }
+        Err(StorageError::InvalidChunkHeader(
+            "No free chunk id".to_string(),
+        ))
+    }
+
+    pub(crate) fn check_open(&self) -> Result<(), StorageError> {
+        match self.state {
+            StorageEngineState::Open => Ok(()),
+            _ => Err(StorageError::Closed),
+        }
+    }
+
+    pub(crate) fn check_writable(&self) -> Result<(), StorageError> {
+        self.check_open()?;
+        if self.file_store.read_only {
+            return Err(StorageError::ReadOnly(
+                "Store is open in a readonly mode".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
@@ -0,0 +1,31 @@
+use crate::change_watch::VersionWatcher;
+use crate::file_store::FileStore;
+use std::time::Duration;
+use std::{env, fs, thread};
+
+#[test]
+fn test_watcher_sees_published_version() {
+    let path = env::temp_dir().join(format!("kenchidb-{}-watch", std::process::id()));
+    let file_name = path.to_string_lossy().into_owned();
+    let _ = fs::remove_file(&path);
+
+    let mut store = FileStore::open(file_name.clone(), false).unwrap();
+    let mut watcher = VersionWatcher::open(&file_name).unwrap();
+    assert_eq!(watcher.version(), 0);
+    assert_eq!(watcher.poll().unwrap(), None);
+
+    store.publish_version(1).unwrap();
+    assert_eq!(watcher.poll().unwrap(), Some(1));
+    assert_eq!(store.read_version().unwrap(), 1);
+
+    let writer = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        store.publish_version(2).unwrap();
+        store
+    });
+    assert_eq!(watcher.wait(Duration::from_secs(5)).unwrap(), Some(2));
+    assert_eq!(watcher.wait(Duration::from_millis(10)).unwrap(), None);
+
+    writer.join().unwrap().close();
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,95 @@
+use crate::chunk::Chunk;
+use crate::storage_engine::Store;
+use crate::test::{open, page_position, temp_file_name};
+use std::fs;
+
+fn text_page(number: usize) -> Vec<u8> {
+    format!("page {} ", number).repeat(1000).into_bytes()
+}
+
+#[test]
+fn test_compressed_chunk_takes_fewer_blocks() {
+    let file_name = temp_file_name("compression-blocks");
+    let mut store = open(&file_name);
+    let pages: Vec<Vec<u8>> = (0..4).map(text_page).collect();
+    let refs: Vec<&[u8]> = pages.iter().map(Vec::as_slice).collect();
+
+    let plain = store.write_pages(&refs).unwrap();
+    store.set_compression_level(Some(3));
+    let compressed = store.write_pages(&refs).unwrap();
+
+    let plain_chunk = store.chunk(plain).unwrap().clone();
+    let compressed_chunk = store.chunk(compressed).unwrap().clone();
+    assert!(!plain_chunk.is_compressed());
+    assert!(compressed_chunk.is_compressed());
+    assert_eq!(compressed_chunk.length, 1);
+    assert!(plain_chunk.length > compressed_chunk.length);
+    assert_eq!(
+        compressed_chunk.uncompressed_length,
+        Chunk::content_length(&refs) as u32
+    );
+    assert!(compressed_chunk.compressed_length < compressed_chunk.uncompressed_length);
+
+    for (page_number, page) in pages.iter().enumerate() {
+        let read = store.read_page(compressed, page_number as u32).unwrap();
+        assert_eq!(&read, page);
+    }
+    let content = store.read_chunk(compressed).unwrap();
+    assert_eq!(content.len(), compressed_chunk.uncompressed_length as usize);
+    assert!(content.starts_with(&pages[0]));
+
+    store.close().unwrap();
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_incompressible_chunk_is_stored_plain() {
+    let mut store = Store::open_in_memory().unwrap();
+    store.set_compression_level(Some(3));
+
+    let id = store.write_pages(&[b"tiny"]).unwrap();
+    assert!(!store.chunk(id).unwrap().is_compressed());
+    assert_eq!(store.read_page(id, 0).unwrap(), b"tiny");
+}
+
+#[test]
+fn test_compressed_chunks_survive_reopen() {
+    let file_name = temp_file_name("compression-reopen");
+    let mut store = open(&file_name);
+    store.set_compression_level(Some(3));
+    let pages: Vec<Vec<u8>> = (0..3).map(text_page).collect();
+    let refs: Vec<&[u8]> = pages.iter().map(Vec::as_slice).collect();
+    store.layout_mut().create_map("users");
+    let id = store.commit(&refs).unwrap();
+    store.remove_page(id, 1).unwrap();
+    store.commit(&[]).unwrap();
+    store.close().unwrap();
+
+    let mut store = open(&file_name);
+    let chunk = store.chunk(id).unwrap().clone();
+    assert!(chunk.is_compressed());
+    assert!(!chunk.is_page_live(1));
+    let position = page_position(id, &refs, 2);
+    assert_eq!(store.read_page_at(position).unwrap(), pages[2]);
+    assert!(store.layout().get("users").is_some());
+
+    store.close().unwrap();
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_compact_compresses_rewritten_pages() {
+    let mut store = Store::open_in_memory().unwrap();
+    let pages: Vec<Vec<u8>> = (0..8).map(text_page).collect();
+    let refs: Vec<&[u8]> = pages.iter().map(Vec::as_slice).collect();
+    let sparse = store.write_pages(&refs).unwrap();
+    for page_number in 1..8 {
+        store.remove_page(sparse, page_number).unwrap();
+    }
+
+    store.set_compression_level(Some(3));
+    let report = store.compact(50).unwrap();
+    let (chunk_id, page_number) = report.relocated[&(sparse, 0)];
+    assert!(store.chunk(chunk_id).unwrap().is_compressed());
+    assert_eq!(store.read_page(chunk_id, page_number).unwrap(), pages[0]);
+}
@@ -0,0 +1,152 @@
+use crate::chunk::ChunkHeader;
+use crate::encryption::{Argon2Params, EncryptionKey, Keyring};
+use crate::error::StorageError;
+use crate::file_store::{FileStore, FileStoreOptions};
+use crate::storage_engine::Store;
+use crate::test::{open, page_position, temp_file_name};
+use std::fs;
+
+fn keyring(key_id: u32, byte: u8) -> Keyring {
+    Keyring::new(key_id, &EncryptionKey::from_bytes([byte; 32])).unwrap()
+}
+
+fn text_page(number: usize) -> Vec<u8> {
+    format!("secret page {} ", number).repeat(100).into_bytes()
+}
+
+fn open_encrypted(file_name: &str, keyring: Keyring) -> Result<Store, StorageError> {
+    Store::open_encrypted(file_name.to_string(), FileStoreOptions::default(), keyring)
+}
+
+#[test]
+fn test_encrypted_chunk_hides_its_pages() {
+    let file_name = temp_file_name("encryption-pages");
+    let mut store = open(&file_name);
+    store.set_encryption(Some(keyring(1, 7)));
+    store.set_compression_level(Some(3));
+    let pages: Vec<Vec<u8>> = (0..3).map(text_page).collect();
+    let refs: Vec<&[u8]> = pages.iter().map(Vec::as_slice).collect();
+
+    let id = store.write_pages(&refs).unwrap();
+    let plain = store.write_pages(&[b"public page"]).unwrap();
+    let chunk = store.chunk(id).unwrap().clone();
+    assert!(chunk.is_encrypted());
+    assert!(chunk.is_compressed());
+    assert_eq!(chunk.key_id, 1);
+    assert_eq!(chunk.nonce[..4], id.to_le_bytes());
+    for (page_number, page) in pages.iter().enumerate() {
+        assert_eq!(&store.read_page(id, page_number as u32).unwrap(), page);
+    }
+    assert!(store.read_chunk(id).unwrap().starts_with(&pages[0]));
+    assert!(store.chunk(plain).unwrap().is_encrypted());
+    store.close().unwrap();
+
+    let raw = fs::read(&file_name).unwrap();
+    assert!(!raw.windows(6).any(|window| window == b"secret"));
+    assert!(!raw.windows(6).any(|window| window == b"public"));
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_encrypted_store_needs_its_key_to_open() {
+    let file_name = temp_file_name("encryption-reopen");
+    let mut store = open_encrypted(&file_name, keyring(1, 7)).unwrap();
+    let pages: Vec<Vec<u8>> = (0..3).map(text_page).collect();
+    let refs: Vec<&[u8]> = pages.iter().map(Vec::as_slice).collect();
+    store.layout_mut().create_map("patients");
+    let id = store.commit(&refs).unwrap();
+    store.close().unwrap();
+
+    // The layout map is encrypted as well, a missing or wrong key fails the open
+    assert!(Store::open(file_name.clone(), FileStoreOptions::default()).is_err());
+    assert!(open_encrypted(&file_name, keyring(1, 8)).is_err());
+
+    // A rotated key still reads chunks written with the older one
+    let rotated = keyring(2, 9).with_key(1, &EncryptionKey::from_bytes([7; 32]));
+    let mut store = open_encrypted(&file_name, rotated).unwrap();
+    assert!(store.layout().get("patients").is_some());
+    let position = page_position(id, &refs, 1);
+    assert_eq!(store.read_page_at(position).unwrap(), pages[1]);
+    let rewritten = store.write_pages(&[b"rotated"]).unwrap();
+    assert_eq!(store.chunk(rewritten).unwrap().key_id, 2);
+    assert_eq!(store.encryption_key(), Some(2));
+
+    store.close().unwrap();
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_altered_encrypted_chunk_fails_to_read() {
+    let file_name = temp_file_name("encryption-altered");
+    let mut store = open_encrypted(&file_name, keyring(1, 7)).unwrap();
+    let id = store.write_pages(&[&text_page(0)]).unwrap();
+    let chunk = store.chunk(id).unwrap().clone();
+    store.close().unwrap();
+
+    let mut raw = fs::read(&file_name).unwrap();
+    raw[chunk.file_position() as usize + ChunkHeader::SIZE + 10] ^= 1;
+    fs::write(&file_name, &raw).unwrap();
+
+    let mut store = open_encrypted(&file_name, keyring(1, 7)).unwrap();
+    assert!(store.read_page(id, 0).is_err());
+
+    store.close().unwrap();
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_compact_encrypts_rewritten_pages() {
+    let mut store = Store::open_in_memory().unwrap();
+    let pages: Vec<Vec<u8>> = (0..8).map(text_page).collect();
+    let refs: Vec<&[u8]> = pages.iter().map(Vec::as_slice).collect();
+    let sparse = store.write_pages(&refs).unwrap();
+    for page_number in 1..8 {
+        store.remove_page(sparse, page_number).unwrap();
+    }
+
+    store.set_encryption(Some(keyring(1, 7)));
+    let report = store.compact(50).unwrap();
+    let (chunk_id, page_number) = report.relocated[&(sparse, 0)];
+    assert!(store.chunk(chunk_id).unwrap().is_encrypted());
+    assert_eq!(store.read_page(chunk_id, page_number).unwrap(), pages[0]);
+}
+
+#[test]
+fn test_passphrase_store_reopens_with_its_header_salt() {
+    let file_name = temp_file_name("encryption-passphrase");
+    let params = Argon2Params {
+        memory_kib: 64,
+        iterations: 1,
+        lanes: 1,
+    };
+    let open_with_passphrase = |passphrase: &str, params: Argon2Params| {
+        Store::open_with_passphrase(
+            file_name.clone(),
+            FileStoreOptions::default(),
+            passphrase,
+            params,
+        )
+    };
+    let mut store = open_with_passphrase("correct horse", params).unwrap();
+    let pages: Vec<Vec<u8>> = (0..3).map(text_page).collect();
+    let refs: Vec<&[u8]> = pages.iter().map(Vec::as_slice).collect();
+    store.layout_mut().create_map("patients");
+    let id = store.commit(&refs).unwrap();
+    let key_derivation = store.file_store.header.key_derivation.unwrap();
+    assert_eq!(key_derivation.key_id, 1);
+    assert_eq!(key_derivation.params, params);
+    store.close().unwrap();
+
+    let header = FileStore::open(file_name.clone(), true).unwrap().header;
+    assert_eq!(header.key_derivation, Some(key_derivation));
+    assert!(open_with_passphrase("wrong horse", params).is_err());
+
+    // The parameters stored in the header are used, not the ones passed
+    let mut store = open_with_passphrase("correct horse", Argon2Params::default()).unwrap();
+    assert!(store.layout().get("patients").is_some());
+    let position = page_position(id, &refs, 2);
+    assert_eq!(store.read_page_at(position).unwrap(), pages[2]);
+    assert_eq!(store.file_store.header.key_derivation, Some(key_derivation));
+    store.close().unwrap();
+    fs::remove_file(file_name).unwrap();
+}
@@ -0,0 +1,160 @@
+use crate::chunk_gc::RetentionPolicy;
+use crate::data_util::{PAGE_TYPE_NODE, get_page_type};
+use crate::storage_engine::Store;
+use crate::test::{open, page_position, temp_file_name};
+use std::fs;
+
+fn page(number: u8) -> Vec<u8> {
+    vec![number; 1000]
+}
+
+#[test]
+fn test_compact_rewrites_sparse_chunks() {
+    let file_name = temp_file_name("gc-sparse");
+    let mut store = open(&file_name);
+
+    // Every update writes a chunk and removes the superseded pages of the older chunks
+    let pages: Vec<Vec<u8>> = (0..8).map(page).collect();
+    let refs: Vec<&[u8]> = pages.iter().map(Vec::as_slice).collect();
+    let sparse = store.write_pages(&refs).unwrap();
+    let dense = store.write_pages(&refs[..4]).unwrap();
+    let dead = store.write_pages(&refs[..2]).unwrap();
+    for page_number in 1..8 {
+        store.remove_page(sparse, page_number).unwrap();
+    }
+    store.remove_page(dense, 0).unwrap();
+    for page_number in 0..2 {
+        store.remove_page(dead, page_number).unwrap();
+    }
+    let blocks_before = store.chunks().map(|chunk| chunk.length).sum::<u32>();
+
+    let report = store.compact(50).unwrap();
+    assert_eq!(report.chunks_freed, 1);
+    assert_eq!(report.chunks_rewritten, 1);
+    assert_eq!(report.pages_moved, 1);
+    let (chunk_id, page_number) = report.relocated[&(sparse, 0)];
+    assert_eq!(store.read_page(chunk_id, page_number).unwrap(), page(0));
+    assert!(store.chunk(sparse).is_none());
+    assert!(store.chunk(dead).is_none());
+
+    // The dense chunk stays where it is
+    assert_eq!(store.read_page(dense, 3).unwrap(), page(3));
+    let blocks_after = store.chunks().map(|chunk| chunk.length).sum::<u32>();
+    assert!(blocks_after < blocks_before);
+
+    // A higher target also rewrites the dense chunk, chunks without removed pages stay
+    let report = store.compact(100).unwrap();
+    assert_eq!(report.chunks_rewritten, 1);
+    assert_eq!(report.pages_moved, 3);
+    assert_eq!(store.chunks().count(), 2);
+    let (chunk_id, page_number) = report.relocated[&(dense, 2)];
+    assert_eq!(store.read_page(chunk_id, page_number).unwrap(), page(2));
+
+    // Positions are moved with their length code and type
+    let position = page_position(dense, &refs[..4], 3) | PAGE_TYPE_NODE as u64;
+    let moved = report.relocate(position).unwrap();
+    assert_eq!(get_page_type(moved), PAGE_TYPE_NODE);
+    assert_eq!(store.read_page_at(moved).unwrap(), page(3));
+    assert!(
+        report
+            .relocate(page_position(dense, &refs[..4], 0))
+            .is_none()
+    );
+
+    store.close().unwrap();
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_update_heavy_workload_is_bounded() {
+    let file_name = temp_file_name("gc-bounded");
+    let mut store = open(&file_name);
+
+    // Ten pages, each round rewrites two of them
+    let mut locations: Vec<(u32, u32)> = Vec::new();
+    let pages: Vec<Vec<u8>> = (0..10).map(page).collect();
+    let refs: Vec<&[u8]> = pages.iter().map(Vec::as_slice).collect();
+    let id = store.write_pages(&refs).unwrap();
+    locations.extend((0..10).map(|page_number| (id, page_number)));
+
+    for round in 0..50usize {
+        let updated = [round % 10, (round + 3) % 10];
+        let pages: Vec<&[u8]> = updated.iter().map(|index| refs[*index]).collect();
+        let id = store.write_pages(&pages).unwrap();
+        for (page_number, index) in updated.iter().enumerate() {
+            let (chunk_id, old) = locations[*index];
+            store.remove_page(chunk_id, old).unwrap();
+            locations[*index] = (id, page_number as u32);
+        }
+
+        let report = store.compact(40).unwrap();
+        for location in locations.iter_mut() {
+            if let Some(moved) = report.relocated.get(location) {
+                *location = *moved;
+            }
+        }
+    }
+
+    for (index, (chunk_id, page_number)) in locations.iter().enumerate() {
+        assert_eq!(
+            store.read_page(*chunk_id, *page_number).unwrap(),
+            page(index as u8)
+        );
+    }
+    assert!(store.chunks().count() <= 10);
+    assert!(store.fill_rate() > 0);
+
+    store.close().unwrap();
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_retained_versions_keep_unused_chunks() {
+    let mut store = Store::open_in_memory().unwrap();
+    store.set_retention(RetentionPolicy {
+        retention_time: 0,
+        retained_versions: 2,
+    });
+
+    let old = store.write_pages(&[&page(1)]).unwrap();
+    store.write_pages(&[&page(2)]).unwrap();
+    store.remove_page(old, 0).unwrap();
+    assert_eq!(store.chunk(old).unwrap().unused_at_version, 2);
+
+    // Kept for two more versions, its pages stay in the file
+    let report = store.compact(50).unwrap();
+    assert_eq!(report.chunks_freed, 0);
+    store.write_pages(&[&page(3)]).unwrap();
+    assert_eq!(store.compact(50).unwrap().chunks_freed, 0);
+
+    store.write_pages(&[&page(4)]).unwrap();
+    assert_eq!(store.compact(50).unwrap().chunks_freed, 1);
+    assert!(store.chunk(old).is_none());
+}
+
+#[test]
+fn test_retention_time_keeps_rewritten_chunks() {
+    let mut store = Store::open_in_memory().unwrap();
+    store.set_retention(RetentionPolicy {
+        retention_time: 60_000,
+        retained_versions: 0,
+    });
+
+    let pages: Vec<Vec<u8>> = (0..4).map(page).collect();
+    let refs: Vec<&[u8]> = pages.iter().map(Vec::as_slice).collect();
+    let sparse = store.write_pages(&refs).unwrap();
+    for page_number in 1..4 {
+        store.remove_page(sparse, page_number).unwrap();
+    }
+
+    // The live page moves, the old chunk is unused but within the window
+    let report = store.compact(50).unwrap();
+    assert_eq!(report.chunks_rewritten, 1);
+    let old = store.chunk(sparse).unwrap();
+    assert!(!old.is_live());
+    assert_eq!(store.compact(50).unwrap().chunks_freed, 0);
+
+    store.set_retention(RetentionPolicy::default());
+    assert_eq!(store.compact(50).unwrap().chunks_freed, 1);
+    assert!(store.chunk(sparse).is_none());
+}
@@ -15,6 +15,11 @@ fn test_header_roundtrip() {
         layout_root_position: 200,
         map_id: 42,
         next: 300,
+        flags: 1,
+        uncompressed_length: 5000,
+        compressed_length: 700,
+        key_id: 3,
+        nonce: [7; 12],
     };
 
     let serialized = original.serialize_header();
@@ -38,6 +43,14 @@ fn test_header_roundtrip() {
     );
     assert_eq!(original.map_id, deserialized.map_id);
     assert_eq!(original.next, deserialized.next);
+    assert_eq!(original.flags, deserialized.flags);
+    assert_eq!(
+        original.uncompressed_length,
+        deserialized.uncompressed_length
+    );
+    assert_eq!(original.compressed_length, deserialized.compressed_length);
+    assert_eq!(original.key_id, deserialized.key_id);
+    assert_eq!(original.nonce, deserialized.nonce);
 }
 
 #[test]
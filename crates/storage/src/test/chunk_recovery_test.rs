@@ -0,0 +1,150 @@
+use crate::chunk::ChunkFooter;
+use crate::test::{open, temp_file_name};
+use std::fs::{self, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+
+#[test]
+fn test_reopen_finds_chunks() {
+    let file_name = temp_file_name("recovery-reopen");
+    let mut store = open(&file_name);
+    assert_eq!(store.recovery_report().chunks_recovered, 0);
+
+    let first = store.write_pages(&[b"one", b"two"]).unwrap();
+    let freed = store.write_pages(&[b"gone"]).unwrap();
+    store.layout_mut().create_map("users");
+    let second = store.commit(&[&[9u8; 6000]]).unwrap();
+    store.free_chunk(freed).unwrap();
+    store.close().unwrap();
+
+    let mut store = open(&file_name);
+    let report = store.recovery_report();
+    assert_eq!(report.chunks_recovered, 2);
+    assert_eq!(report.chunks_discarded, 0);
+    assert_eq!(report.version, 3);
+    assert_eq!(store.file_store.header.version, 3);
+    assert_eq!(store.file_store.header.last_chunk_id, second);
+    assert_eq!(
+        store.locate_page(store.layout_root_position()).unwrap(),
+        (second, 1)
+    );
+    assert_eq!(store.layout().get("users").unwrap().id, 1);
+    assert!(store.chunk(freed).is_none());
+    assert_eq!(store.chunk(first).unwrap().page_count, 2);
+    assert_eq!(store.read_page(first, 1).unwrap(), b"two");
+    assert_eq!(store.read_page(second, 0).unwrap(), vec![9u8; 6000]);
+
+    // New chunks continue the version and ids, and fill the blocks of the freed chunk
+    let freed_block = store.chunk(first).unwrap().block + 1;
+    let third = store.write_pages(&[b"three"]).unwrap();
+    assert_eq!(store.version(), 4);
+    assert!(third > second);
+    assert_eq!(store.chunk(third).unwrap().block, freed_block);
+
+    store.close().unwrap();
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_torn_chunk_falls_back_to_previous() {
+    let file_name = temp_file_name("recovery-torn");
+    let mut store = open(&file_name);
+    store.layout_mut().create_map("kept");
+    let first = store.commit(&[b"first"]).unwrap();
+    store.layout_mut().create_map("lost");
+    let torn = store.commit(&[&[3u8; 5000]]).unwrap();
+    let torn_chunk = store.chunk(torn).unwrap().clone();
+    store.close().unwrap();
+
+    // The footer of the last chunk never reached the disk
+    let mut file = OpenOptions::new().write(true).open(&file_name).unwrap();
+    let footer = torn_chunk.file_position() + torn_chunk.byte_length() - ChunkFooter::SIZE as u64;
+    file.seek(SeekFrom::Start(footer)).unwrap();
+    file.write_all(&[0xff; 8]).unwrap();
+    drop(file);
+
+    let mut store = open(&file_name);
+    let report = store.recovery_report();
+    assert_eq!(report.chunks_recovered, 1);
+    assert_eq!(report.chunks_discarded, 1);
+    assert_eq!(report.version, 1);
+    assert_eq!(
+        store.locate_page(store.layout_root_position()).unwrap(),
+        (first, 1)
+    );
+    assert_eq!(store.layout().names().collect::<Vec<_>>(), vec!["kept"]);
+    assert!(store.chunk(torn).is_none());
+    assert_eq!(store.read_page(first, 0).unwrap(), b"first");
+
+    // The blocks of the torn chunk are free again
+    let next = store.write_pages(&[b"next"]).unwrap();
+    assert_eq!(store.chunk(next).unwrap().block, torn_chunk.block);
+    assert_eq!(store.version(), 2);
+
+    store.close().unwrap();
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_truncated_chunk_is_discarded() {
+    let file_name = temp_file_name("recovery-truncated");
+    let mut store = open(&file_name);
+    store.write_pages(&[b"kept"]).unwrap();
+    let cut = store.write_pages(&[&[1u8; 9000]]).unwrap();
+    let cut_chunk = store.chunk(cut).unwrap().clone();
+    store.close().unwrap();
+
+    let file = OpenOptions::new().write(true).open(&file_name).unwrap();
+    file.set_len(cut_chunk.file_position() + 4096).unwrap();
+    drop(file);
+
+    let store = open(&file_name);
+    assert_eq!(store.recovery_report().chunks_recovered, 1);
+    assert_eq!(store.recovery_report().chunks_discarded, 1);
+    assert_eq!(store.version(), 1);
+
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_removed_pages_survive_reopen() {
+    let file_name = temp_file_name("recovery-occupancy");
+    let mut store = open(&file_name);
+
+    let sparse = store
+        .commit(&[&[1u8; 1000], &[2u8; 3000], &[3u8; 500]])
+        .unwrap();
+    store.remove_page(sparse, 1).unwrap();
+    store.remove_page(sparse, 2).unwrap();
+    let latest = store.commit(&[b"latest"]).unwrap();
+    let live = store.chunk(sparse).unwrap().clone();
+    assert_eq!(live.page_count_live, 1);
+    assert_eq!(
+        store.layout().chunk_occupancy(sparse).unwrap(),
+        &live.occupancy
+    );
+    // Removed after the last commit, the removal is not persisted
+    store.remove_page(latest, 0).unwrap();
+    store.close().unwrap();
+
+    let mut store = open(&file_name);
+    let chunk = store.chunk(sparse).unwrap();
+    assert_eq!(chunk.occupancy, live.occupancy);
+    assert_eq!(chunk.page_count_live, 1);
+    assert_eq!(chunk.max_length_live, 1000);
+    assert_eq!(chunk.collect_priority, live.collect_priority);
+    assert!(!store.chunk(latest).unwrap().is_rewritable());
+    assert_eq!(store.read_page(latest, 0).unwrap(), b"latest");
+    assert!(store.read_page(sparse, 1).is_err());
+
+    // The restored live length drives the rewrite of the sparse chunk
+    let report = store.compact(50).unwrap();
+    assert_eq!(report.chunks_rewritten, 1);
+    let (chunk_id, page_number) = report.relocated[&(sparse, 0)];
+    assert_eq!(
+        store.read_page(chunk_id, page_number).unwrap(),
+        vec![1u8; 1000]
+    );
+
+    store.close().unwrap();
+    fs::remove_file(file_name).unwrap();
+}
@@ -0,0 +1,104 @@
+use crate::error::StorageError;
+use crate::storage_engine::Store;
+use crate::test::{open, page_position, temp_file_name};
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_async_commits_are_acknowledged() {
+    let file_name = temp_file_name("writer-ack");
+    let mut store = open(&file_name);
+    let ack = store.start_writer().unwrap();
+    assert_eq!(ack.acknowledged_version(), 0);
+
+    let mut versions = Vec::new();
+    for i in 0..10u8 {
+        store.layout_mut().create_map(&format!("map-{}", i));
+        versions.push(store.commit_async(&[&[i; 100]]).unwrap());
+    }
+    assert_eq!(versions, (1..=10).collect::<Vec<u64>>());
+    assert_eq!(store.version(), 10);
+
+    // Another thread waits for the last commit to become durable
+    let waiter = thread::spawn(move || ack.wait(10).map(|_| ack.acknowledged_version()));
+    assert_eq!(waiter.join().unwrap().unwrap(), 10);
+    assert!(
+        store
+            .commit_ack()
+            .unwrap()
+            .wait_timeout(10, Duration::ZERO)
+            .unwrap()
+    );
+
+    let last = store.chunks().last().unwrap().id;
+    assert_eq!(store.read_page(last, 0).unwrap(), vec![9u8; 100]);
+    store.close().unwrap();
+    assert!(store.commit_ack().is_none());
+
+    let mut store = open(&file_name);
+    assert_eq!(store.recovery_report().chunks_recovered, 10);
+    assert_eq!(store.version(), 10);
+    assert_eq!(store.layout().len(), 10);
+    assert_eq!(
+        store
+            .read_page_at(page_position(last, &[&[9u8; 100]], 0))
+            .unwrap(),
+        vec![9u8; 100]
+    );
+    store.close().unwrap();
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_reads_wait_for_queued_chunks() {
+    let file_name = temp_file_name("writer-read");
+    let mut store = open(&file_name);
+    store.start_writer().unwrap();
+
+    let id = store.next_chunk_id().unwrap();
+    let content = vec![5u8; 20_000];
+    store.commit_async(&[&content]).unwrap();
+    assert_eq!(store.read_page(id, 0).unwrap(), content);
+    assert!(store.read_chunk(id).unwrap().starts_with(&content));
+
+    // Synchronous writes are ordered after the queued ones
+    let next = store.write_pages(&[b"sync"]).unwrap();
+    assert_eq!(store.commit_ack().unwrap().acknowledged_version(), 2);
+    assert_eq!(store.read_page(next, 0).unwrap(), b"sync");
+
+    store.stop_writer().unwrap();
+    store.commit(&[b"after"]).unwrap();
+    assert_eq!(store.version(), 3);
+    store.close().unwrap();
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_close_writes_queued_chunks() {
+    let file_name = temp_file_name("writer-close");
+    let mut store = open(&file_name);
+    store.start_writer().unwrap();
+    for i in 0..5u8 {
+        store.commit_async(&[&[i; 3000]]).unwrap();
+    }
+    store.close().unwrap();
+
+    let store = open(&file_name);
+    assert_eq!(store.recovery_report().chunks_recovered, 5);
+    assert_eq!(store.file_store.header.version, 5);
+    drop(store);
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_in_memory_store_has_no_writer() {
+    let mut store = Store::open_in_memory().unwrap();
+    assert!(matches!(
+        store.start_writer(),
+        Err(StorageError::IoError(_))
+    ));
+    // Commits are written on the caller thread
+    assert_eq!(store.commit_async(&[b"page"]).unwrap(), 1);
+    store.wait_for_version(1).unwrap();
+}
@@ -1,4 +1,9 @@
-use crate::data_util::get_fletcher32;
+use crate::chunk::Chunk;
+use crate::data_util::{
+    PAGE_LARGE, PAGE_POSITION_REMOVED, PAGE_POSITION_UNSAVED, PAGE_TYPE_LEAF, PAGE_TYPE_NODE,
+    compose_page_position, decode_page_length, encode_page_length, get_fletcher32,
+    get_page_chunk_id, get_page_max_length, get_page_offset, get_page_type, is_page_saved,
+};
 
 #[test]
     fn test_fletcher32_basic() {
@@ -27,4 +32,104 @@ use crate::data_util::get_fletcher32;
         let data = b"";
         let checksum = get_fletcher32(data, 0, 0);
         assert_eq!(checksum, 0xffff_ffff);
-    }
\ No newline at end of file
+    }
+
+#[test]
+fn test_page_length_codes() {
+    let lengths: Vec<u32> = (0..31).map(decode_page_length).collect();
+    assert_eq!(&lengths[..6], &[32, 48, 64, 96, 128, 192]);
+    assert_eq!(lengths[30], 1024 * 1024);
+    assert_eq!(decode_page_length(31), PAGE_LARGE);
+
+    // Every length gets the smallest code whose length covers it
+    for length in 0..=PAGE_LARGE + 1 {
+        let code = encode_page_length(length);
+        if length <= PAGE_LARGE {
+            assert!(decode_page_length(code) >= length, "length {}", length);
+        }
+        if code > 0 {
+            assert!(decode_page_length(code - 1) < length, "length {}", length);
+        }
+    }
+    assert_eq!(encode_page_length(u32::MAX), 31);
+    for code in 0..32u8 {
+        assert_eq!(encode_page_length(decode_page_length(code)), code);
+    }
+}
+
+#[test]
+fn test_page_position_roundtrip() {
+    let chunk_ids = [0, 1, 2, 1000, Chunk::MAX_ID - 1, Chunk::MAX_ID];
+    let offsets = [0, 1, 96, 4096, 0x8000_0000, u32::MAX - 1, u32::MAX];
+    let lengths = [0, 32, 33, 100, 4096, 1024 * 1024, PAGE_LARGE, u32::MAX];
+    for chunk_id in chunk_ids {
+        for offset in offsets {
+            for length in lengths {
+                for page_type in [PAGE_TYPE_LEAF, PAGE_TYPE_NODE] {
+                    let position = compose_page_position(chunk_id, offset, length, page_type);
+                    assert_eq!(get_page_chunk_id(position), chunk_id);
+                    assert_eq!(get_page_offset(position), offset);
+                    assert_eq!(
+                        get_page_max_length(position),
+                        decode_page_length(encode_page_length(length))
+                    );
+                    assert_eq!(get_page_type(position), page_type);
+                }
+            }
+        }
+    }
+
+    // Every length code survives the composition
+    for code in 0..32u8 {
+        let position = compose_page_position(7, 4096, decode_page_length(code), PAGE_TYPE_NODE);
+        assert_eq!(get_page_max_length(position), decode_page_length(code));
+    }
+}
+
+#[test]
+fn test_page_position_field_boundaries() {
+    // Each field at its maximum with the others at zero fills only its own bits
+    assert_eq!(
+        compose_page_position(Chunk::MAX_ID, 0, 0, PAGE_TYPE_LEAF),
+        0xffff_ffc0_0000_0000
+    );
+    assert_eq!(
+        compose_page_position(0, u32::MAX, 0, PAGE_TYPE_LEAF),
+        0x0000_003f_ffff_ffc0
+    );
+    assert_eq!(
+        compose_page_position(0, 0, PAGE_LARGE, PAGE_TYPE_LEAF),
+        0x0000_0000_0000_003e
+    );
+    assert_eq!(compose_page_position(0, 0, 0, PAGE_TYPE_NODE), 1);
+    assert_eq!(
+        compose_page_position(Chunk::MAX_ID, u32::MAX, PAGE_LARGE, PAGE_TYPE_NODE),
+        u64::MAX
+    );
+    assert_eq!(get_page_chunk_id(u64::MAX), Chunk::MAX_ID);
+    assert_eq!(get_page_offset(u64::MAX), u32::MAX);
+    assert_eq!(get_page_max_length(u64::MAX), PAGE_LARGE);
+    assert_eq!(get_page_type(u64::MAX), PAGE_TYPE_NODE);
+}
+
+#[test]
+fn test_page_position_states() {
+    assert!(!is_page_saved(PAGE_POSITION_UNSAVED));
+    assert!(!is_page_saved(PAGE_POSITION_REMOVED));
+    let first_page = compose_page_position(0, 96, 0, PAGE_TYPE_LEAF);
+    let chunk_start = compose_page_position(1, 0, 0, PAGE_TYPE_LEAF);
+    assert!(is_page_saved(first_page));
+    assert!(is_page_saved(chunk_start));
+}
+
+#[test]
+#[should_panic(expected = "chunk id exceeds 26 bits")]
+fn test_page_position_rejects_large_chunk_id() {
+    compose_page_position(Chunk::MAX_ID + 1, 0, 0, PAGE_TYPE_LEAF);
+}
+
+#[test]
+#[should_panic(expected = "page type is not 0 or 1")]
+fn test_page_position_rejects_unknown_type() {
+    compose_page_position(1, 0, 0, 2);
+}
@@ -0,0 +1,92 @@
+use crate::direct_io::{AlignedBuffer, DIRECT_IO_ALIGNMENT, align_down, align_up};
+use crate::error::StorageError;
+use crate::file_store::{FileStore, FileStoreHeader, FileStoreOptions};
+use crate::storage_engine::Store;
+use crate::test::temp_file_name;
+use std::fs;
+use std::io;
+
+fn direct_options() -> FileStoreOptions {
+    FileStoreOptions {
+        direct_io: true,
+        ..FileStoreOptions::default()
+    }
+}
+
+/// Skip on file systems without direct I/O, e.g. a tmpfs temp directory
+fn open_direct(file_name: &str) -> Option<FileStore> {
+    match FileStore::open_with_options(file_name.to_string(), direct_options()) {
+        Ok(store) => Some(store),
+        Err(StorageError::IoError(e)) if e.kind() == io::ErrorKind::InvalidInput => None,
+        Err(e) => panic!("{:?}", e),
+    }
+}
+
+#[test]
+fn test_aligned_buffer() {
+    let buffer = AlignedBuffer::zeroed(100);
+    assert_eq!(buffer.len(), DIRECT_IO_ALIGNMENT);
+    assert_eq!(buffer.as_ptr() as usize % DIRECT_IO_ALIGNMENT, 0);
+    assert!(buffer.iter().all(|byte| *byte == 0));
+
+    assert_eq!(
+        AlignedBuffer::zeroed(DIRECT_IO_ALIGNMENT + 1).len(),
+        DIRECT_IO_ALIGNMENT * 2
+    );
+    assert_eq!(align_down(4097), 4096);
+    assert_eq!(align_up(4097), 8192);
+    assert_eq!(align_up(8192), 8192);
+}
+
+#[test]
+fn test_unaligned_reads_and_writes() {
+    let file_name = temp_file_name("direct-unaligned");
+    let Some(mut store) = open_direct(&file_name) else {
+        return;
+    };
+    assert!(store.direct_io);
+
+    let offset = FileStoreHeader::HEADER_BLOCKS * FileStoreHeader::BLOCK_SIZE;
+    let data: Vec<u8> = (0..10_000u32).map(|i| i as u8).collect();
+    store.write_fully(offset, &data).unwrap();
+    // A write within a block keeps the bytes around it
+    store.write_fully(offset + 4090, b"direct").unwrap();
+    store.sync().unwrap();
+
+    let mut expected = data.clone();
+    expected[4090..4096].copy_from_slice(b"direct");
+    assert_eq!(store.read_fully(offset, 10_000).unwrap(), expected);
+    assert_eq!(
+        store.read_fully(offset + 4088, 10).unwrap(),
+        &expected[4088..4098]
+    );
+    store.close();
+
+    let bytes = fs::read(&file_name).unwrap();
+    assert_eq!(&bytes[offset as usize..offset as usize + 10_000], expected);
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_store_with_direct_io() {
+    let file_name = temp_file_name("direct-store");
+    let Some(file_store) = open_direct(&file_name) else {
+        return;
+    };
+    file_store.close();
+
+    let mut store = Store::open(file_name.clone(), direct_options()).unwrap();
+    let first = store.commit(&[b"first", &[1u8; 5000]]).unwrap();
+    store.start_writer().unwrap();
+    let version = store.commit_async(&[&[2u8; 9000]]).unwrap();
+    store.wait_for_version(version).unwrap();
+    store.free_chunk(first).unwrap();
+    store.close().unwrap();
+
+    let mut store = Store::open(file_name.clone(), direct_options()).unwrap();
+    assert_eq!(store.recovery_report().chunks_recovered, 1);
+    let last = store.chunks().last().unwrap().id;
+    assert_eq!(store.read_page(last, 0).unwrap(), vec![2u8; 9000]);
+    store.close().unwrap();
+    fs::remove_file(file_name).unwrap();
+}
@@ -0,0 +1,61 @@
+use crate::encryption::{Argon2Params, EncryptionKey, Keyring, generate_salt};
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Cheap enough for tests, real keys use `Argon2Params::default()`
+const TEST_PARAMS: Argon2Params = Argon2Params {
+    memory_kib: 64,
+    iterations: 1,
+    lanes: 1,
+};
+
+#[test]
+fn test_keyring_matches_published_vectors() {
+    // GCM specification test cases 13 and 14
+    let keyring = Keyring::new(1, &EncryptionKey::from_bytes([0; 32])).unwrap();
+    assert_eq!(
+        hex(&keyring.seal(&[0; 12], &[], &[]).unwrap()),
+        "530f8afbc74536b9a963b4f1c4cb738b"
+    );
+    let sealed = keyring.seal(&[0; 12], &[], &[0; 16]).unwrap();
+    assert_eq!(
+        hex(&sealed),
+        "cea7403d4d606b6e074ec5d3baf39d18d0d1c8a799996bf0265b98b5d48ab919"
+    );
+    assert_eq!(
+        keyring.open(1, &[0; 12], &[], &sealed).unwrap(),
+        vec![0; 16]
+    );
+    assert!(keyring.open(1, &[0; 12], b"other", &sealed).is_err());
+    assert!(keyring.open(2, &[0; 12], &[], &sealed).is_err());
+}
+
+#[test]
+fn test_derived_key_depends_on_passphrase_and_salt() {
+    let salt = generate_salt().unwrap();
+    let key = EncryptionKey::derive("correct horse", &salt, &TEST_PARAMS).unwrap();
+    let again = EncryptionKey::derive("correct horse", &salt, &TEST_PARAMS).unwrap();
+    let wrong = EncryptionKey::derive("wrong horse", &salt, &TEST_PARAMS).unwrap();
+    let salted = EncryptionKey::derive("correct horse", &[7; 16], &TEST_PARAMS).unwrap();
+
+    let keyring = Keyring::new(1, &key).unwrap();
+    let nonce = keyring.next_nonce(9);
+    let sealed = keyring.seal(&nonce, b"chunk", b"secret").unwrap();
+    let open = |other: &EncryptionKey| {
+        Keyring::new(1, other)
+            .unwrap()
+            .open(1, &nonce, b"chunk", &sealed)
+    };
+    assert_eq!(open(&again).unwrap(), b"secret");
+    assert!(open(&wrong).is_err());
+    assert!(open(&salted).is_err());
+
+    // Nonces share the prefix but never repeat
+    assert_eq!(nonce[..4], 9u32.to_le_bytes());
+    assert_ne!(keyring.next_nonce(9), nonce);
+
+    // Salts shorter than 8 bytes are rejected instead of weakening the key
+    assert!(EncryptionKey::derive("correct horse", &[1; 4], &TEST_PARAMS).is_err());
+}
@@ -0,0 +1,213 @@
+use crate::data_util::get_fletcher32;
+use crate::error::StorageError;
+use crate::file_store::{FileStore, FileStoreHeader, FileStoreOptions};
+use crate::file_sync::Durability;
+use crate::storage_engine::Store;
+use crate::test::{open, temp_file_name};
+use std::fs::{self, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+
+#[test]
+fn test_write_sync_read() {
+    for durability in [Durability::Full, Durability::Relaxed, Durability::None] {
+        let file_name = temp_file_name(&format!("sync-{:?}", durability));
+        let mut store = FileStore::open_with_options(
+            file_name.clone(),
+            FileStoreOptions {
+                durability,
+                ..FileStoreOptions::default()
+            },
+        )
+        .unwrap();
+
+        store.write_fully(0, b"hello world").unwrap();
+        store.sync().unwrap();
+        assert_eq!(store.read_fully(6, 5).unwrap(), b"world");
+
+        store.close();
+        fs::remove_file(file_name).unwrap();
+    }
+}
+
+#[test]
+fn test_rename() {
+    let file_name = temp_file_name("rename-old");
+    let new_file_name = temp_file_name("rename-new");
+
+    let mut store = FileStore::open(file_name.clone(), false).unwrap();
+    let data_offset = FileStoreHeader::HEADER_BLOCKS * FileStoreHeader::BLOCK_SIZE;
+    store.write_fully(data_offset, b"data").unwrap();
+    store.rename(new_file_name.clone()).unwrap();
+
+    assert_eq!(store.get_file_name(), new_file_name);
+    assert!(fs::metadata(&file_name).is_err());
+    assert_eq!(
+        &fs::read(&new_file_name).unwrap()[data_offset as usize..],
+        b"data"
+    );
+
+    store.close();
+    fs::remove_file(new_file_name).unwrap();
+}
+
+#[test]
+fn test_read_only_does_not_create() {
+    let file_name = temp_file_name("read-only");
+    assert!(FileStore::open(file_name.clone(), true).is_err());
+    assert!(fs::metadata(&file_name).is_err());
+}
+
+#[test]
+fn test_header_is_created_and_validated() {
+    let file_name = temp_file_name("header");
+    let mut store = FileStore::open(file_name.clone(), false).unwrap();
+    assert_eq!(
+        store.size(),
+        FileStoreHeader::HEADER_BLOCKS * FileStoreHeader::BLOCK_SIZE
+    );
+    let created = store.header;
+    assert_eq!(created.format, FileStoreHeader::FORMAT);
+    assert_eq!(created.block_size as u64, FileStoreHeader::BLOCK_SIZE);
+    assert!(created.creation_time > 0);
+
+    store.header.last_chunk_id = 3;
+    store.header.last_chunk_block = 5;
+    store.write_header().unwrap();
+    store.close();
+
+    let store = FileStore::open(file_name.clone(), true).unwrap();
+    assert_eq!(store.header.creation_time, created.creation_time);
+    assert_eq!(store.header.last_chunk_id, 3);
+    assert_eq!(store.header.last_chunk_block, 5);
+    assert_eq!(store.header.sequence, created.sequence + 1);
+    store.close();
+
+    // A torn write of the newer copy falls back to the older copy
+    let newest = (created.sequence + 1) % FileStoreHeader::HEADER_BLOCKS;
+    corrupt(&file_name, newest * FileStoreHeader::BLOCK_SIZE + 30);
+    let store = FileStore::open(file_name.clone(), true).unwrap();
+    assert_eq!(store.header.sequence, created.sequence);
+    assert_eq!(store.header.last_chunk_id, 0);
+    store.close();
+
+    // Both copies damaged
+    corrupt(&file_name, (1 - newest) * FileStoreHeader::BLOCK_SIZE + 30);
+    assert!(matches!(
+        FileStore::open(file_name.clone(), true),
+        Err(StorageError::InvalidFileHeader(_))
+    ));
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_newer_format_is_rejected() {
+    let file_name = temp_file_name("header-format");
+    let mut store = FileStore::open(file_name.clone(), false).unwrap();
+    store.header.format = FileStoreHeader::FORMAT + 1;
+    store.write_header().unwrap();
+    store.close();
+
+    assert!(matches!(
+        FileStore::open(file_name.clone(), false),
+        Err(StorageError::InvalidFileHeader(_))
+    ));
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_format_1_header_is_read_and_upgraded() {
+    let file_name = temp_file_name("header-format-1");
+    let mut store = FileStore::open(file_name.clone(), false).unwrap();
+    store.header.last_chunk_id = 4;
+    let mut bytes = store.header.serialize_header();
+    store.close();
+
+    // Format 1 headers end with the checksum right after the sequence
+    bytes[FileStoreHeader::FIELD_FORMAT_OFFSET..FileStoreHeader::FIELD_FORMAT_OFFSET + 4]
+        .copy_from_slice(&1u32.to_le_bytes());
+    bytes[FileStoreHeader::FORMAT_1_CHECKSUM_OFFSET..].fill(0);
+    let checksum = get_fletcher32(&bytes, 0, FileStoreHeader::FORMAT_1_CHECKSUM_OFFSET);
+    bytes[FileStoreHeader::FORMAT_1_CHECKSUM_OFFSET..FileStoreHeader::FORMAT_1_CHECKSUM_OFFSET + 4]
+        .copy_from_slice(&checksum.to_le_bytes());
+    for copy in 0..FileStoreHeader::HEADER_BLOCKS {
+        let mut file = OpenOptions::new().write(true).open(&file_name).unwrap();
+        file.seek(SeekFrom::Start(copy * FileStoreHeader::BLOCK_SIZE))
+            .unwrap();
+        file.write_all(&bytes).unwrap();
+    }
+
+    let mut store = FileStore::open(file_name.clone(), false).unwrap();
+    assert_eq!(store.header.last_chunk_id, 4);
+    assert_eq!(store.header.key_derivation, None);
+    assert_eq!(store.header.format, FileStoreHeader::FORMAT);
+    store.write_header().unwrap();
+    store.close();
+    let store = FileStore::open(file_name.clone(), true).unwrap();
+    assert_eq!(store.header.last_chunk_id, 4);
+    store.close();
+    fs::remove_file(file_name).unwrap();
+}
+
+fn corrupt(file_name: &str, position: u64) {
+    let mut file = OpenOptions::new().write(true).open(file_name).unwrap();
+    file.seek(SeekFrom::Start(position)).unwrap();
+    file.write_all(&[0xa5; 4]).unwrap();
+}
+
+#[test]
+fn test_io_stats() {
+    let file_name = temp_file_name("io-stats");
+    let mut store = FileStore::open(file_name.clone(), false).unwrap();
+    let opened = store.stats();
+    assert_eq!(opened.write_count, 2);
+
+    let offset = FileStoreHeader::HEADER_BLOCKS * FileStoreHeader::BLOCK_SIZE;
+    store.write_fully(offset, b"hello world").unwrap();
+    store.read_fully(offset, 5).unwrap();
+    let stats = store.stats();
+    let since = stats.since(&opened);
+    assert_eq!((since.write_count, since.write_bytes), (1, 11));
+    assert_eq!((since.read_count, since.read_bytes), (1, 5));
+
+    // Intervals follow each other
+    assert_eq!(store.interval_stats(), stats);
+    store.read_fully(offset + 6, 5).unwrap();
+    let interval = store.interval_stats();
+    assert_eq!((interval.read_count, interval.write_count), (1, 0));
+    assert_eq!(store.interval_stats(), Default::default());
+    assert_eq!(stats + interval, store.stats());
+
+    store.close();
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_second_writer_is_refused() {
+    let file_name = temp_file_name("locked");
+    let store = FileStore::open(file_name.clone(), false).unwrap();
+    assert!(matches!(
+        FileStore::open(file_name.clone(), false),
+        Err(StorageError::Locked(_))
+    ));
+
+    // Readers follow the writer
+    let reader = FileStore::open(file_name.clone(), true).unwrap();
+    reader.close();
+
+    store.unlock().unwrap();
+    let other = FileStore::open(file_name.clone(), false).unwrap();
+    other.close();
+    store.close();
+
+    // Closing a chunk store releases the file
+    let mut chunks = open(&file_name);
+    assert!(matches!(
+        Store::open(file_name.clone(), FileStoreOptions::default()),
+        Err(StorageError::Locked(_))
+    ));
+    chunks.close().unwrap();
+    let mut reopened = open(&file_name);
+    reopened.close().unwrap();
+
+    fs::remove_file(file_name).unwrap();
+}
@@ -0,0 +1,87 @@
+use crate::data_util::PAGE_TYPE_LEAF;
+use crate::layout_map::LayoutMap;
+use crate::page_serialization::ChunkBuilder;
+use crate::test::{open, temp_file_name};
+use bitvec::prelude::BitVec;
+use std::fs;
+
+#[test]
+fn test_layout_map_roundtrip() {
+    let mut layout = LayoutMap::new();
+    assert_eq!(layout.create_map("users"), 1);
+    assert_eq!(layout.create_map("users_by_email"), 2);
+    assert_eq!(layout.create_map("users"), 1);
+    assert!(layout.set_root_position("users_by_email", 77));
+    assert!(!layout.set_root_position("missing", 1));
+    let mut occupancy = BitVec::repeat(false, 11);
+    occupancy.set(0, true);
+    occupancy.set(9, true);
+    layout.set_chunk_occupancy(4, occupancy.clone());
+
+    let restored = LayoutMap::deserialize(&layout.serialize()).unwrap();
+    assert_eq!(restored, layout);
+    assert_eq!(restored.get("users_by_email").unwrap().root_position, 77);
+    assert_eq!(restored.name(1), Some("users"));
+    assert_eq!(restored.chunk_occupancy(4), Some(&occupancy));
+
+    // Ids of removed maps are not reused
+    layout.remove_map("users_by_email");
+    assert_eq!(layout.create_map("orders"), 3);
+    assert_eq!(layout.len(), 2);
+
+    let bytes = layout.serialize();
+    assert!(LayoutMap::deserialize(&bytes[..bytes.len() - 1]).is_err());
+    assert!(LayoutMap::deserialize(&[bytes.as_slice(), &[0]].concat()).is_err());
+}
+
+#[test]
+fn test_maps_share_one_store() {
+    let file_name = temp_file_name("layout-maps");
+    let mut store = open(&file_name);
+    assert!(store.layout().is_empty());
+
+    // Roots of two maps in the same chunk
+    let mut builder = ChunkBuilder::new(&store).unwrap();
+    let chunk_id = builder.chunk_id();
+    let users = store.layout_mut().create_map("users");
+    let emails = store.layout_mut().create_map("users_by_email");
+    let users_root = builder.add(b"users root".to_vec(), PAGE_TYPE_LEAF);
+    let emails_root = builder.add(b"emails root".to_vec(), PAGE_TYPE_LEAF);
+    store.layout_mut().set_root_position("users", users_root);
+    store
+        .layout_mut()
+        .set_root_position("users_by_email", emails_root);
+    assert_eq!(builder.commit(&mut store).unwrap(), chunk_id);
+    assert_eq!(store.chunk(chunk_id).unwrap().map_id, 2);
+    assert_eq!(store.read_page_at(emails_root).unwrap(), b"emails root");
+
+    // A later commit replaces one root and the layout page, the old ones are removed
+    let mut builder = ChunkBuilder::new(&store).unwrap();
+    let next_id = builder.chunk_id();
+    store.remove_page_at(users_root).unwrap();
+    let users_root = builder.add(b"new users root".to_vec(), PAGE_TYPE_LEAF);
+    store.layout_mut().set_root_position("users", users_root);
+    builder.commit(&mut store).unwrap();
+    assert_eq!(store.chunk(chunk_id).unwrap().page_count_live, 1);
+    assert_eq!(
+        store.locate_page(store.layout_root_position()).unwrap(),
+        (next_id, 1)
+    );
+    store.close().unwrap();
+
+    let mut store = open(&file_name);
+    let users_entry = store.layout().get("users").unwrap();
+    let emails_entry = store.layout().get("users_by_email").unwrap();
+    assert_eq!((users_entry.id, emails_entry.id), (users, emails));
+    assert_eq!(
+        store.read_page_at(users_entry.root_position).unwrap(),
+        b"new users root"
+    );
+    assert_eq!(
+        store.read_page_at(emails_entry.root_position).unwrap(),
+        b"emails root"
+    );
+
+    store.close().unwrap();
+    fs::remove_file(file_name).unwrap();
+}
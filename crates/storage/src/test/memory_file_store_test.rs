@@ -0,0 +1,56 @@
+use crate::file_store::{FileStore, FileStoreHeader};
+use crate::memory_file_store::MemoryFileStore;
+use crate::storage_engine::Store;
+
+#[test]
+fn test_memory_read_write() {
+    let mut memory = MemoryFileStore::new();
+    assert_eq!(memory.size(), 0);
+    assert!(memory.read_fully(0, 1).is_err());
+
+    // Writing past the end zero fills the gap
+    memory.write_fully(4, b"data").unwrap();
+    assert_eq!(memory.size(), 8);
+    assert_eq!(memory.read_fully(0, 8).unwrap(), b"\0\0\0\0data");
+    memory.write_fully(2, b"xyz").unwrap();
+    assert_eq!(memory.as_bytes(), b"\0\0xyzata");
+    assert!(memory.read_fully(6, 3).is_err());
+    memory.sync().unwrap();
+}
+
+#[test]
+fn test_in_memory_file_store() {
+    let mut store = FileStore::open_in_memory().unwrap();
+    assert!(store.is_in_memory());
+    assert_eq!(
+        store.size(),
+        FileStoreHeader::HEADER_BLOCKS * FileStoreHeader::BLOCK_SIZE
+    );
+    assert_eq!(store.read_header().unwrap(), Some(store.header));
+
+    store.publish_version(7).unwrap();
+    assert_eq!(store.read_version().unwrap(), 7);
+    assert!(store.rename("elsewhere".to_string()).is_err());
+}
+
+#[test]
+fn test_in_memory_store() {
+    let mut store = Store::open_in_memory().unwrap();
+
+    let sparse = store.write_pages(&[b"one", b"two", b"three"]).unwrap();
+    store.layout_mut().create_map("numbers");
+    let committed = store.commit(&[b"four"]).unwrap();
+    assert_eq!(store.read_page(sparse, 2).unwrap(), b"three");
+    assert_eq!(store.read_page(committed, 0).unwrap(), b"four");
+    assert_eq!(store.layout().get("numbers").unwrap().id, 1);
+
+    store.remove_page(sparse, 0).unwrap();
+    store.remove_page(sparse, 1).unwrap();
+    let report = store.compact(50).unwrap();
+    assert_eq!(report.chunks_rewritten, 1);
+    let (chunk_id, page_number) = report.relocated[&(sparse, 2)];
+    assert_eq!(store.read_page(chunk_id, page_number).unwrap(), b"three");
+
+    store.close().unwrap();
+    assert!(store.read_page(committed, 0).is_err());
+}
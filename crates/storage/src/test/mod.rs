@@ -1,4 +1,73 @@
 #[cfg(test)]
+mod change_watch_test;
+#[cfg(test)]
+mod chunk_impl_margin_test;
+#[cfg(test)]
 mod data_util_test;
 #[cfg(test)]
-mod chunk_impl_margin_test;
\ No newline at end of file
+mod file_store_test;
+
+#[cfg(test)]
+mod storage_engine_test;
+#[cfg(test)]
+mod chunk_gc_test;
+#[cfg(test)]
+mod chunk_recovery_test;
+#[cfg(test)]
+mod layout_map_test;
+#[cfg(test)]
+mod page_serialization_test;
+#[cfg(test)]
+mod memory_file_store_test;
+#[cfg(test)]
+mod read_ahead_test;
+#[cfg(test)]
+mod chunk_compression_test;
+#[cfg(test)]
+mod snapshot_test;
+#[cfg(test)]
+mod mv_map_test;
+#[cfg(test)]
+mod chunk_writer_test;
+#[cfg(test)]
+mod direct_io_test;
+#[cfg(test)]
+mod encryption_test;
+#[cfg(test)]
+mod chunk_encryption_test;
+
+#[cfg(test)]
+use crate::chunk::Chunk;
+#[cfg(test)]
+use crate::data_util::{PAGE_TYPE_LEAF, compose_page_position};
+#[cfg(test)]
+use crate::file_store::FileStoreOptions;
+#[cfg(test)]
+use crate::page::{ChunkId, PagePosition};
+#[cfg(test)]
+use crate::storage_engine::Store;
+
+/// Path of a fresh file in the temp directory, a file left by an earlier run is removed
+#[cfg(test)]
+fn temp_file_name(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("kenchidb-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_file(&path);
+    path.to_string_lossy().into_owned()
+}
+
+/// Store with the default file options
+#[cfg(test)]
+fn open(file_name: &str) -> Store {
+    Store::open(file_name.to_string(), FileStoreOptions::default()).unwrap()
+}
+
+/// Position of a page of a chunk written from `pages` by `Store::commit`
+#[cfg(test)]
+fn page_position(chunk_id: ChunkId, pages: &[&[u8]], index: usize) -> PagePosition {
+    compose_page_position(
+        chunk_id,
+        Chunk::next_page_offset(&pages[..index]),
+        pages[index].len() as u32,
+        PAGE_TYPE_LEAF,
+    )
+}
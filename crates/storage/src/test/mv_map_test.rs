@@ -0,0 +1,120 @@
+use crate::page_serialization::ChunkBuilder;
+use crate::storage_engine::Store;
+use crate::test::{open, temp_file_name};
+use std::collections::BTreeMap;
+use std::fs;
+
+#[test]
+fn test_put_get_remove() {
+    let mut store = Store::open_in_memory().unwrap();
+    let mut map = store.open_map::<String, u64>("users_by_email").unwrap();
+    assert!(map.is_empty());
+
+    let email = "ada@example.com".to_string();
+    assert_eq!(map.put(&mut store, email.clone(), 1).unwrap(), None);
+    assert_eq!(map.put(&mut store, email.clone(), 2).unwrap(), Some(1));
+    assert_eq!(map.get(&mut store, &email).unwrap(), Some(2));
+    assert!(!map.contains_key(&mut store, &"bob".to_string()).unwrap());
+    assert_eq!(map.len(), 1);
+
+    assert_eq!(map.remove(&mut store, &email).unwrap(), Some(2));
+    assert_eq!(map.remove(&mut store, &email).unwrap(), None);
+    assert!(map.is_empty());
+}
+
+#[test]
+fn test_large_map_matches_btree_map() {
+    let mut store = Store::open_in_memory().unwrap();
+    let mut map = store.open_map::<u32, u64>("numbers").unwrap();
+    let mut expected = BTreeMap::new();
+
+    // Keys in a scrambled order split leaves and internal pages
+    for index in 0..5000u32 {
+        let key = index.wrapping_mul(2654435761) % 10007;
+        map.put(&mut store, key, index as u64).unwrap();
+        expected.insert(key, index as u64);
+    }
+    map.commit(&mut store).unwrap();
+    for key in (0..10007u32).filter(|key| key % 3 == 0) {
+        assert_eq!(map.remove(&mut store, &key).unwrap(), expected.remove(&key));
+    }
+    assert_eq!(map.len(), expected.len() as u64);
+
+    let entries = map.range(&mut store, ..).unwrap();
+    let all: Vec<(u32, u64)> = expected.iter().map(|(k, v)| (*k, *v)).collect();
+    assert_eq!(entries, all);
+    let entries = map.range(&mut store, 100..=2000).unwrap();
+    let part: Vec<(u32, u64)> = expected.range(100..=2000).map(|(k, v)| (*k, *v)).collect();
+    assert_eq!(entries, part);
+
+    for key in expected.keys().copied().collect::<Vec<_>>() {
+        map.remove(&mut store, &key).unwrap();
+    }
+    assert!(map.is_empty());
+    assert!(map.range(&mut store, ..).unwrap().is_empty());
+}
+
+#[test]
+fn test_map_survives_reopen() {
+    let file_name = temp_file_name("mv-map-reopen");
+    let mut store = open(&file_name);
+    let mut map = store.open_map::<u32, String>("names").unwrap();
+    for key in 0..200 {
+        map.put(&mut store, key, format!("name {}", key)).unwrap();
+    }
+    map.commit(&mut store).unwrap();
+    map.put(&mut store, 7, "changed".to_string()).unwrap();
+    map.remove(&mut store, &8).unwrap();
+    map.commit(&mut store).unwrap();
+    // Uncommitted changes are lost
+    map.put(&mut store, 9, "lost".to_string()).unwrap();
+    store.close().unwrap();
+
+    let mut store = open(&file_name);
+    let mut map = store.open_map::<u32, String>("names").unwrap();
+    assert_eq!(map.len(), 199);
+    assert_eq!(map.get(&mut store, &7).unwrap().unwrap(), "changed");
+    assert_eq!(map.get(&mut store, &8).unwrap(), None);
+    assert_eq!(map.get(&mut store, &9).unwrap().unwrap(), "name 9");
+    assert_eq!(map.range(&mut store, 190..).unwrap().len(), 10);
+
+    store.close().unwrap();
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_replaced_pages_are_removed() {
+    let mut store = Store::open_in_memory().unwrap();
+    let mut map = store.open_map::<u32, u32>("counters").unwrap();
+    for key in 0..500 {
+        map.put(&mut store, key, 0).unwrap();
+    }
+    let first = map.commit(&mut store).unwrap();
+    let page_count = store.chunk(first).unwrap().page_count;
+
+    // Only the path to the changed leaf is written again
+    map.put(&mut store, 42, 1).unwrap();
+    let second = map.commit(&mut store).unwrap();
+    let chunk = store.chunk(first).unwrap();
+    assert_eq!(chunk.page_count_live, page_count - 3);
+    assert!(store.chunk(second).unwrap().page_count < page_count);
+}
+
+#[test]
+fn test_maps_saved_into_one_chunk() {
+    let mut store = Store::open_in_memory().unwrap();
+    let mut users = store.open_map::<u32, String>("users").unwrap();
+    let mut orders = store.open_map::<u64, u32>("orders").unwrap();
+    users.put(&mut store, 1, "ada".to_string()).unwrap();
+    orders.put(&mut store, 10, 1).unwrap();
+
+    let mut builder = ChunkBuilder::new(&store).unwrap();
+    users.save(&mut store, &mut builder).unwrap();
+    orders.save(&mut store, &mut builder).unwrap();
+    builder.commit(&mut store).unwrap();
+
+    assert_ne!(store.layout().get("users").unwrap().root_position, 0);
+    assert_ne!(store.layout().get("orders").unwrap().root_position, 0);
+    let mut reopened = store.open_map::<u64, u32>("orders").unwrap();
+    assert_eq!(reopened.get(&mut store, &10).unwrap(), Some(1));
+}
@@ -0,0 +1,123 @@
+use crate::chunk::Chunk;
+use crate::data_util::{
+    PAGE_TYPE_LEAF, PAGE_TYPE_NODE, get_page_chunk_id, get_page_max_length, get_page_offset,
+    get_page_type,
+};
+use crate::error::StorageError;
+use crate::page::{Page, PageKind, PageReference};
+use crate::page_serialization::ChunkBuilder;
+use crate::test::{open, temp_file_name};
+use std::fs;
+
+fn leaf(keys: &[u64]) -> Page<u64, String> {
+    let values = keys.iter().map(|key| format!("value {}", key)).collect();
+    Page::new_leaf(7, keys.to_vec(), values)
+}
+
+fn child(page: Page<u64, String>) -> PageReference<u64, String> {
+    PageReference {
+        position: None,
+        count: page.get_key_count() as u64,
+        page: Some(Box::new(page)),
+    }
+}
+
+#[test]
+fn test_leaf_roundtrip() {
+    let page = leaf(&[1, 5, 9]);
+    let bytes = page.serialize();
+    let restored = Page::<u64, String>::deserialize(1 << 32, &bytes).unwrap();
+
+    assert_eq!(restored.core.tree_id, 7);
+    assert_eq!(restored.core.keys, vec![1, 5, 9]);
+    assert_eq!(restored.get_position(), 1 << 32);
+    assert_eq!(restored.core.disk_space_used, bytes.len() as u32);
+    match restored.kind {
+        PageKind::Leaf { values } => assert_eq!(values[2], "value 9"),
+        PageKind::Internal { .. } => panic!("expected a leaf"),
+    }
+
+    assert!(Page::<u64, String>::deserialize(1, &bytes[..bytes.len() - 1]).is_err());
+    assert!(Page::<u64, String>::deserialize(1, &[bytes.as_slice(), &[0]].concat()).is_err());
+}
+
+#[test]
+fn test_tree_is_written_children_first() {
+    let file_name = temp_file_name("page-serialization");
+    let mut store = open(&file_name);
+
+    let mut root: Page<u64, String> = Page::new_internal(
+        7,
+        vec![10],
+        vec![child(leaf(&[1, 2, 3])), child(leaf(&[10, 11]))],
+        5,
+    );
+    let mut builder = ChunkBuilder::new(&store).unwrap();
+    let root_position = root.write_unsaved(&mut builder);
+    assert_eq!(builder.len(), 3);
+    assert_eq!(get_page_chunk_id(root_position), builder.chunk_id());
+    assert_eq!(
+        get_page_offset(root_position),
+        Chunk::next_page_offset(&builder.pages()[..2])
+    );
+    assert!(get_page_max_length(root_position) >= builder.pages()[2].len() as u32);
+    assert_eq!(get_page_type(root_position), PAGE_TYPE_NODE);
+    assert_eq!(root.core.page_number, 2);
+
+    // Saved pages are not serialized again
+    assert_eq!(root.write_unsaved(&mut builder), root_position);
+    assert_eq!(builder.len(), 3);
+
+    store.layout_mut().create_map("numbers");
+    store
+        .layout_mut()
+        .set_root_position("numbers", root_position);
+    let page_lengths: u32 = builder.pages().iter().map(|page| page.len() as u32).sum();
+    let chunk_id = builder.commit(&mut store).unwrap();
+
+    // Header fields describe the serialized pages and the layout page
+    let chunk: &Chunk = store.chunk(chunk_id).unwrap();
+    assert_eq!(chunk.page_count, 4);
+    let layout_length = store.layout().serialize().len() as u32;
+    assert_eq!(chunk.max_length, page_lengths + layout_length);
+    store.close().unwrap();
+
+    let mut store = open(&file_name);
+    let root_position = store.layout().get("numbers").unwrap().root_position;
+    let root: Page<u64, String> = store.read_tree_page(root_position).unwrap();
+    let PageKind::Internal {
+        children,
+        total_count,
+    } = &root.kind
+    else {
+        panic!("expected an internal page");
+    };
+    assert_eq!(*total_count, 5);
+    assert_eq!(root.core.keys, vec![10]);
+    assert_eq!(root.core.page_number, 2);
+    assert!(children.iter().all(|child| child.page.is_none()));
+    assert_eq!(children[1].count, 2);
+
+    let right_position = children[1].position.unwrap();
+    assert_eq!(get_page_type(right_position), PAGE_TYPE_LEAF);
+    let right: Page<u64, String> = store.read_tree_page(right_position).unwrap();
+    assert_eq!(right.core.keys, vec![10, 11]);
+    assert_eq!(right.core.page_number, 1);
+
+    // A position whose type bit does not match the page, or which points between pages
+    assert!(matches!(
+        store.read_tree_page::<u64, String>(right_position | 1),
+        Err(StorageError::InvalidPage(_))
+    ));
+    assert!(matches!(
+        store.read_page_at(right_position + (1 << 6)),
+        Err(StorageError::InvalidPage(_))
+    ));
+    match right.kind {
+        PageKind::Leaf { values } => assert_eq!(values, vec!["value 10", "value 11"]),
+        PageKind::Internal { .. } => panic!("expected a leaf"),
+    }
+
+    store.close().unwrap();
+    fs::remove_file(file_name).unwrap();
+}
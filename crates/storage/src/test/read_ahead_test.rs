@@ -0,0 +1,112 @@
+use crate::file_store::{FileStore, FileStoreHeader, FileStoreOptions};
+use crate::read_ahead::AccessHint;
+use crate::test::temp_file_name;
+use std::fs;
+use std::sync::atomic::Ordering;
+
+const DATA_OFFSET: u64 = FileStoreHeader::HEADER_BLOCKS * FileStoreHeader::BLOCK_SIZE;
+
+/// Store with 64 blocks of data after the header, each byte is its block number
+fn open_with_data(name: &str, read_ahead_blocks: u32) -> (String, FileStore) {
+    let file_name = temp_file_name(name);
+    let mut store = FileStore::open_with_options(
+        file_name.clone(),
+        FileStoreOptions {
+            read_ahead_blocks,
+            ..FileStoreOptions::default()
+        },
+    )
+    .unwrap();
+    let data: Vec<u8> = (0..64u8)
+        .flat_map(|block| vec![block; FileStoreHeader::BLOCK_SIZE as usize])
+        .collect();
+    store.write_fully(DATA_OFFSET, &data).unwrap();
+    (file_name, store)
+}
+
+fn block_offset(block: u64) -> u64 {
+    DATA_OFFSET + block * FileStoreHeader::BLOCK_SIZE
+}
+
+fn reads(store: &FileStore) -> (u64, u64) {
+    (
+        store.read_count.load(Ordering::Relaxed),
+        store.read_ahead_hits.load(Ordering::Relaxed),
+    )
+}
+
+#[test]
+fn test_sequential_reads_are_prefetched() {
+    let (file_name, mut store) = open_with_data("read-ahead-sequential", 8);
+    let (read_count, _) = reads(&store);
+
+    // A header sized read at each block boundary, like the chunk scan on open
+    for block in 0..32 {
+        assert_eq!(
+            store.read_fully(block_offset(block), 96).unwrap(),
+            vec![block as u8; 96]
+        );
+    }
+    let (scanned, hits) = reads(&store);
+    assert!(scanned - read_count <= 6, "{} reads", scanned - read_count);
+    assert_eq!(hits + scanned - read_count, 32);
+
+    // A write into the prefetched blocks is seen by the next read
+    store.set_access_hint(AccessHint::Sequential);
+    store.read_fully(block_offset(40), 16).unwrap();
+    store.write_fully(block_offset(41), b"updated").unwrap();
+    assert_eq!(store.read_fully(block_offset(41), 7).unwrap(), b"updated");
+
+    store.close();
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_random_hint_disables_read_ahead() {
+    let (file_name, mut store) = open_with_data("read-ahead-random", 8);
+    store.set_access_hint(AccessHint::Random);
+    let (read_count, hits) = reads(&store);
+    for block in 0..8 {
+        store.read_fully(block_offset(block), 96).unwrap();
+    }
+    assert_eq!(reads(&store), (read_count + 8, hits));
+
+    // Disabled by the options
+    let (other_file_name, mut other) = open_with_data("read-ahead-disabled", 0);
+    other.set_access_hint(AccessHint::Sequential);
+    let (read_count, hits) = reads(&other);
+    for block in 0..8 {
+        other.read_fully(block_offset(block), 96).unwrap();
+    }
+    assert_eq!(reads(&other), (read_count + 8, hits));
+
+    store.close();
+    other.close();
+    fs::remove_file(file_name).unwrap();
+    fs::remove_file(other_file_name).unwrap();
+}
+
+#[test]
+fn test_vectored_reads_merge_near_ranges() {
+    let (file_name, mut store) = open_with_data("read-ahead-vectored", 0);
+    let (read_count, _) = reads(&store);
+
+    let ranges = [
+        (block_offset(2), 10),
+        (block_offset(0), 4),
+        (block_offset(1), 20),
+        (block_offset(60), 4),
+    ];
+    let buffers = store.read_fully_vectored(&ranges).unwrap();
+    assert_eq!(buffers[0], vec![2u8; 10]);
+    assert_eq!(buffers[1], vec![0u8; 4]);
+    assert_eq!(buffers[2], vec![1u8; 20]);
+    assert_eq!(buffers[3], vec![60u8; 4]);
+    // Blocks 0 to 2 in one read, block 60 in another
+    assert_eq!(reads(&store).0, read_count + 2);
+
+    assert!(store.read_fully_vectored(&[(block_offset(64), 1)]).is_err());
+
+    store.close();
+    fs::remove_file(file_name).unwrap();
+}
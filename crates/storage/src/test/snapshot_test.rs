@@ -0,0 +1,72 @@
+use crate::chunk_gc::RetentionPolicy;
+use crate::error::StorageError;
+use crate::storage_engine::Store;
+use crate::test::page_position;
+
+fn retaining_store() -> Store {
+    let mut store = Store::open_in_memory().unwrap();
+    store.set_retention(RetentionPolicy {
+        retention_time: 0,
+        retained_versions: 10,
+    });
+    store
+}
+
+#[test]
+fn test_snapshot_reads_frozen_state() {
+    let mut store = retaining_store();
+    store.layout_mut().create_map("users");
+    let first = store.commit(&[b"alice v1", b"bob v1"]).unwrap();
+    let root = page_position(first, &[b"alice v1", b"bob v1"], 0);
+    store.layout_mut().set_root_position("users", root);
+    store.commit(&[]).unwrap();
+    let snapshot = store.snapshot(store.version()).unwrap();
+
+    // Writes continue, alice is updated and the old page removed
+    let second = store.write_pages(&[b"alice v2"]).unwrap();
+    store.remove_page(first, 0).unwrap();
+    store.layout_mut().remove_map("users");
+    store.commit(&[]).unwrap();
+    store.compact(100).unwrap();
+
+    assert_eq!(snapshot.layout().get("users").unwrap().root_position, root);
+    assert!(store.layout().get("users").is_none());
+    assert_eq!(
+        snapshot.read_page_at(&mut store, root).unwrap(),
+        b"alice v1"
+    );
+    assert!(store.read_page_at(root).is_err());
+    // Chunks written after the snapshot are not part of it
+    assert!(matches!(
+        snapshot.read_page(&mut store, second, 0),
+        Err(StorageError::ChunkNotFound(_))
+    ));
+}
+
+#[test]
+fn test_snapshot_of_unretained_version_fails() {
+    let mut store = Store::open_in_memory().unwrap();
+    let first = store.write_pages(&[b"one"]).unwrap();
+    let snapshot = store.snapshot(1).unwrap();
+    assert_eq!(snapshot.version(), 1);
+    assert_eq!(snapshot.layout_root_position(), 0);
+    assert!(snapshot.layout().is_empty());
+
+    store.write_pages(&[b"two"]).unwrap();
+    store.remove_page(first, 0).unwrap();
+    store.compact(100).unwrap();
+
+    // Without a retention window the chunk of version 1 is freed right away
+    assert!(matches!(
+        snapshot.read_page(&mut store, first, 0),
+        Err(StorageError::ChunkNotFound(_))
+    ));
+    assert!(matches!(
+        store.snapshot(1),
+        Err(StorageError::VersionNotFound(1))
+    ));
+    assert!(matches!(
+        store.snapshot(3),
+        Err(StorageError::VersionNotFound(3))
+    ));
+}
@@ -0,0 +1,131 @@
+use crate::chunk::{Chunk, ChunkFooter, ChunkHeader};
+use crate::error::StorageError;
+use crate::file_store::FileStoreHeader;
+use crate::test::{open, temp_file_name};
+use std::fs;
+
+#[test]
+fn test_chunks_are_block_aligned() {
+    let file_name = temp_file_name("store-layout");
+    let mut store = open(&file_name);
+
+    let small = store.write_pages(&[b"small"]).unwrap();
+    let large_content = vec![7u8; FileStoreHeader::BLOCK_SIZE as usize * 2];
+    let large = store.write_pages(&[&large_content]).unwrap();
+    assert_eq!(store.version(), 2);
+
+    let small_chunk = store.chunk(small).unwrap().clone();
+    assert_eq!(small_chunk.block, FileStoreHeader::HEADER_BLOCKS);
+    assert_eq!(small_chunk.length, 1);
+    assert_eq!(small_chunk.version, 1);
+    assert!(small_chunk.is_saved());
+    let large_chunk = store.chunk(large).unwrap().clone();
+    assert_eq!(large_chunk.block, FileStoreHeader::HEADER_BLOCKS + 1);
+    assert_eq!(large_chunk.length, 3);
+
+    let content = store.read_chunk(small).unwrap();
+    assert_eq!(
+        content.len(),
+        store.chunk(small).unwrap().content_capacity()
+    );
+    assert!(content.starts_with(b"small"));
+    // Table of content after the page, zero padding after it
+    assert!(
+        content[5 + Chunk::TOC_ENTRY_SIZE..]
+            .iter()
+            .all(|byte| *byte == 0)
+    );
+    assert_eq!(store.read_page(small, 0).unwrap(), b"small");
+    assert_eq!(store.read_page(large, 0).unwrap(), large_content);
+
+    // Header at the start of the first block, footer at the end of the last
+    let bytes = fs::read(&file_name).unwrap();
+    let start = large_chunk.file_position() as usize;
+    let end = start + large_chunk.byte_length() as usize;
+    assert_eq!(bytes.len(), end);
+    let header = ChunkHeader::deserialize_header(&bytes[start..start + ChunkHeader::SIZE]).unwrap();
+    assert_eq!(header.id, large);
+    assert!(ChunkFooter::verify_footer(
+        &bytes[end - ChunkFooter::SIZE..end]
+    ));
+
+    store.close().unwrap();
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_freed_blocks_are_reused() {
+    let file_name = temp_file_name("store-free");
+    let mut store = open(&file_name);
+
+    let first = store.write_pages(&[&[1u8; 5000]]).unwrap();
+    let second = store.write_pages(&[b"second"]).unwrap();
+    let first_block = store.chunk(first).unwrap().block;
+    store.free_chunk(first).unwrap();
+    assert!(matches!(
+        store.read_chunk(first),
+        Err(StorageError::ChunkNotFound(_))
+    ));
+    assert!(store.fill_rate() < 100);
+
+    // A chunk that fits the freed run takes it, a larger one goes past the last chunk
+    let third = store.write_pages(&[b"third"]).unwrap();
+    assert_eq!(store.chunk(third).unwrap().block, first_block);
+    let fourth = store.write_pages(&[&[4u8; 9000]]).unwrap();
+    assert_eq!(
+        store.chunk(fourth).unwrap().block,
+        store.chunk(second).unwrap().block + 1
+    );
+    assert_ne!(third, first);
+    assert!(store.read_page(second, 0).unwrap() == b"second");
+
+    store.close().unwrap();
+    assert!(matches!(
+        store.write_pages(&[b"late"]),
+        Err(StorageError::Closed)
+    ));
+    fs::remove_file(file_name).unwrap();
+}
+
+#[test]
+fn test_chunk_size_limit() {
+    assert_eq!(Chunk::block_count(0), Some(1));
+    assert_eq!(
+        Chunk::block_count(
+            FileStoreHeader::BLOCK_SIZE as usize - ChunkHeader::SIZE - ChunkFooter::SIZE
+        ),
+        Some(1)
+    );
+    assert_eq!(Chunk::block_count(Chunk::MAX_SIZE as usize), None);
+}
+
+#[test]
+fn test_pages_in_a_chunk() {
+    let file_name = temp_file_name("store-pages");
+    let mut store = open(&file_name);
+
+    let id = store.write_pages(&[b"first", b"", b"third page"]).unwrap();
+    let chunk = store.chunk(id).unwrap();
+    assert_eq!(chunk.page_count, 3);
+    assert_eq!(chunk.page_count_live, 3);
+    assert_eq!(chunk.max_length, 15);
+    assert_eq!(chunk.collect_priority, 1000);
+    assert_eq!(store.read_page(id, 1).unwrap(), b"");
+    assert_eq!(store.read_page(id, 2).unwrap(), b"third page");
+
+    store.remove_page(id, 2).unwrap();
+    let chunk = store.chunk(id).unwrap();
+    assert_eq!(chunk.page_count_live, 2);
+    assert_eq!(chunk.max_length_live, 5);
+    assert_eq!(chunk.collect_priority, 333);
+    assert!(chunk.is_rewritable());
+    assert!(matches!(
+        store.read_page(id, 2),
+        Err(StorageError::PageNotFound { .. })
+    ));
+    assert!(store.read_page(id, 3).is_err());
+    assert_eq!(store.read_page(id, 0).unwrap(), b"first");
+
+    store.close().unwrap();
+    fs::remove_file(file_name).unwrap();
+}